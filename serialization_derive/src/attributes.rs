@@ -0,0 +1,78 @@
+use proc_macro2::{
+    Ident,
+    TokenTree,
+};
+use syn::{
+    Attribute,
+    LitInt,
+    Meta,
+    Variant,
+};
+
+// Collect the names of the options given in the `#[serde(...)]` attributes
+// among the given ones, each paired with whether or not it is a bare flag,
+// such as `skip`, rather than having a value, such as `rename = "x"`.
+fn serde_options(attrs: &[Attribute]) -> Vec<(Ident, bool)> {
+    let mut options = Vec::new();
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        if let Meta::List(list) = &attr.meta {
+            let tokens: Vec<TokenTree> =
+                list.tokens.clone().into_iter().collect();
+            for (i, token) in tokens.iter().enumerate() {
+                let TokenTree::Ident(ident) = token else {
+                    continue;
+                };
+                let is_bare = match tokens.get(i + 1) {
+                    None => true,
+                    Some(TokenTree::Punct(punct)) => punct.as_char() == ',',
+                    Some(_) => false,
+                };
+                let is_first = i == 0
+                    || matches!(
+                        &tokens[i - 1],
+                        TokenTree::Punct(punct) if punct.as_char() == ','
+                    );
+                if is_first {
+                    options.push((ident.clone(), is_bare));
+                }
+            }
+        }
+    }
+    options
+}
+
+/// Return the name of the first option among the given `#[serde(...)]`
+/// attributes which is one of the given names, whether or not it has a
+/// value, such as `skip_serializing_if = "..."`.
+pub fn find_serde_option(
+    attrs: &[Attribute],
+    names: &[&str],
+) -> Option<Ident> {
+    serde_options(attrs)
+        .into_iter()
+        .map(|(ident, _)| ident)
+        .find(|ident| names.iter().any(|name| ident == name))
+}
+
+/// Determine whether or not any of the given `#[serde(...)]` attributes
+/// contains one of the given bare flags, such as `skip`.
+pub fn has_serde_flag(
+    attrs: &[Attribute],
+    flags: &[&str],
+) -> bool {
+    serde_options(attrs).iter().any(|(ident, is_bare)| {
+        *is_bare && flags.iter().any(|flag| ident == flag)
+    })
+}
+
+/// Return the tag given to the variant by `#[serialization(tag = N)]`,
+/// which the `serialization` attribute macro passes on as
+/// `#[serial_tag(N)]`, if any.
+pub fn variant_tag(variant: &Variant) -> syn::Result<Option<u32>> {
+    variant
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("serial_tag"))
+        .map(|attr| attr.parse_args::<LitInt>()?.base10_parse())
+        .transpose()
+}
@@ -0,0 +1,148 @@
+use crate::attributes::{
+    has_serde_flag,
+    variant_tag,
+};
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse_quote,
+    Attribute,
+    Data,
+    DeriveInput,
+    Field,
+    Fields,
+    GenericParam,
+    Variant,
+};
+
+fn is_skipped(attrs: &[Attribute]) -> bool {
+    has_serde_flag(attrs, &["skip", "skip_serializing"])
+}
+
+fn field_fingerprint(field: &Field) -> TokenStream {
+    let ty = &field.ty;
+    quote! {
+        <#ty as ::serialization::LayoutFingerprint>::LAYOUT_FINGERPRINT
+    }
+}
+
+fn fields_fingerprint(fields: &Fields) -> TokenStream {
+    let fields = fields
+        .iter()
+        .filter(|field| !is_skipped(&field.attrs))
+        .collect::<Vec<_>>();
+    let len = fields.len();
+    let parts = fields.into_iter().map(field_fingerprint);
+    quote! {
+        {
+            let fingerprint =
+                ::serialization::__private::fields_fingerprint(#len);
+            #(
+                let fingerprint = ::serialization::__private::fingerprint_add(
+                    fingerprint,
+                    #parts,
+                );
+            )*
+            fingerprint
+        }
+    }
+}
+
+// Unit structs and variants have no encoding at all, and newtype structs and
+// variants are encoded the same way as the values they wrap.
+fn shape_fingerprint(fields: &Fields) -> TokenStream {
+    match fields {
+        Fields::Unit => quote! {
+            ::serialization::__private::unit_fingerprint()
+        },
+        Fields::Unnamed(unnamed) if unnamed.unnamed.len() == 1 => {
+            field_fingerprint(&unnamed.unnamed[0])
+        },
+        _ => fields_fingerprint(fields),
+    }
+}
+
+// Variants with tags are encoded by their tags rather than their positions,
+// so the tag of each is included, and the order in which they are declared
+// is not.
+fn enum_fingerprint(variants: &[&Variant]) -> syn::Result<TokenStream> {
+    let mut parts = Vec::with_capacity(variants.len());
+    for variant in variants {
+        let shape = shape_fingerprint(&variant.fields);
+        parts.push((variant_tag(variant)?, shape));
+    }
+    if parts.iter().any(|(tag, _)| tag.is_some()) {
+        parts.sort_by_key(|(tag, _)| *tag);
+    }
+    let len = parts.len();
+    let parts = parts.into_iter().map(|(tag, shape)| {
+        let fingerprint = if let Some(tag) = tag {
+            quote! {
+                ::serialization::__private::fingerprint_add(
+                    fingerprint,
+                    #tag as u64,
+                )
+            }
+        } else {
+            quote!(fingerprint)
+        };
+        quote! {
+            ::serialization::__private::fingerprint_add(#fingerprint, #shape)
+        }
+    });
+    Ok(quote! {
+        {
+            let fingerprint =
+                ::serialization::__private::enum_fingerprint(#len);
+            #(
+                let fingerprint = #parts;
+            )*
+            fingerprint
+        }
+    })
+}
+
+pub fn derive(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let fingerprint = match &input.data {
+        Data::Struct(data) => shape_fingerprint(&data.fields),
+        Data::Enum(data) => enum_fingerprint(
+            &data
+                .variants
+                .iter()
+                .filter(|variant| !is_skipped(&variant.attrs))
+                .collect::<Vec<_>>(),
+        )?,
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "LayoutFingerprint cannot be derived for unions",
+            ));
+        },
+    };
+    let mut generics = input.generics.clone();
+    for param in &mut generics.params {
+        if let GenericParam::Type(param) = param {
+            param.bounds.push(parse_quote!(::serialization::LayoutFingerprint));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let name = &input.ident;
+    Ok(quote! {
+        impl #impl_generics ::serialization::LayoutFingerprint
+            for #name #ty_generics #where_clause
+        {
+            const LAYOUT_FINGERPRINT: u64 = #fingerprint;
+        }
+
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// This is the fingerprint of the encoding of the type, which
+            /// changes whenever its layout or the version of the encoding
+            /// does.
+            pub const WIRE_FINGERPRINT: u64 =
+                ::serialization::__private::wire_fingerprint(
+                    <Self as ::serialization::LayoutFingerprint>
+                        ::LAYOUT_FINGERPRINT,
+                );
+        }
+    })
+}
@@ -0,0 +1,103 @@
+//! This crate provides the derive macros of the [`serialization`] crate.
+//! It is not intended to be used directly; enable the `derive` feature of
+//! [`serialization`] instead.
+//!
+//! [`serialization`]: https://docs.rs/serialization
+
+#![warn(clippy::pedantic)]
+#![warn(missing_docs)]
+
+mod attributes;
+mod layout_fingerprint;
+mod max_size;
+mod serial;
+
+use proc_macro::TokenStream;
+use syn::{
+    parse_macro_input,
+    DeriveInput,
+};
+
+/// Derive the `LayoutFingerprint` trait for a struct or enum, combining
+/// the fingerprints of the layouts of its fields (and for enums, the number
+/// of variants, and any tags given to them with `#[serialization]`) in the
+/// order they are encoded.  An inherent `WIRE_FINGERPRINT` constant is also
+/// added to the type, which includes the version of the encoding as well.
+#[proc_macro_derive(LayoutFingerprint, attributes(serial_tag))]
+pub fn derive_layout_fingerprint(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    layout_fingerprint::derive(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Select alternative encodings for fields of a struct or enum, by
+/// rewriting `#[serialization(...)]` attributes on its fields into the
+/// `#[serde(with = "...")]` attributes they stand for.  It must be placed
+/// before the derives of `Serialize` and `Deserialize`.  The encodings are
+/// `bytes`, `fixed`, `lossy_f32`, `skip_default`, and `zigzag`, which are
+/// the modules of `serialization::with`.
+///
+/// Variants of an enum may also be given `#[serialization(tag = N)]`
+/// attributes, selecting the numbers which identify them in encodings in
+/// place of their positions, so that variants can be reordered or removed
+/// without changing how the others are encoded.  A variant without a tag
+/// gets the one after the variant before it, as with discriminants.
+/// Variants may also be given aliases, with `#[serialization(alias = N)]`,
+/// which are tags no longer produced but still accepted when decoding, so
+/// that values stored when variants were renumbered, or before several were
+/// merged into one, can still be decoded.
+///
+/// Fields may also be given `#[serialization(skip)]`, which leaves them out
+/// of encodings and gives them their defaults when decoding, as
+/// `#[serde(skip)]` does, or `#[serialization(since = N)]`, which marks
+/// them as added in version `N` of the type and gives them their defaults
+/// when missing from encodings of earlier versions, as `#[serde(default)]`
+/// does.  Since only fields at the end of a struct can be missing, fields
+/// with versions must be named, and must come after the fields of earlier
+/// versions.  Such encodings are only decoded if missing fields are
+/// allowed by the `Config` used.
+///
+/// Since fields are identified by their positions, fields which are only
+/// sometimes encoded cannot be decoded, so `#[serde(skip_serializing_if)]`,
+/// and `#[serde(skip_serializing)]` without `skip_deserializing`, are
+/// rejected on the fields of the type.
+#[proc_macro_attribute]
+pub fn serialization(
+    args: TokenStream,
+    input: TokenStream,
+) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    serial::expand(&args.into(), input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// This is the older name of the [`serialization`] attribute macro, kept so
+/// that code written for it still builds.  The attributes on fields and
+/// variants may be written `#[serial(...)]` as well, with either name.
+///
+/// [`serialization`]: attr.serialization.html
+#[proc_macro_attribute]
+pub fn serial(
+    args: TokenStream,
+    input: TokenStream,
+) -> TokenStream {
+    serialization(args, input)
+}
+
+/// Derive the `MaxSerializedSize` trait for a struct or enum, summing the
+/// maximum sizes of its fields (and for enums, adding the size of the
+/// largest variant index, or tag given with `#[serialization]`, to the
+/// largest variant).  Fields with `#[serde(flatten)]`,
+/// `#[serde(with = "...")]` or `#[serde(serialize_with = "...")]`,
+/// internally or adjacently tagged enums, and types with
+/// `#[serde(into = "...")]`, are rejected, since their sizes are not
+/// bounded by their types.
+#[proc_macro_derive(MaxSerializedSize, attributes(serial_tag))]
+pub fn derive_max_serialized_size(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    max_size::derive(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,219 @@
+use crate::attributes::{
+    find_serde_option,
+    has_serde_flag,
+    variant_tag,
+};
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    parse_quote,
+    Data,
+    DeriveInput,
+    Fields,
+    GenericParam,
+};
+
+// Fields encoded other than by their own `Serialize` implementations, or
+// spliced into the struct containing them, are not bounded by the sizes of
+// their types, so they are rejected.
+fn fields_size(fields: &Fields) -> syn::Result<TokenStream> {
+    let mut sizes = Vec::with_capacity(fields.len());
+    for field in fields {
+        if has_serde_flag(&field.attrs, &["skip", "skip_serializing"]) {
+            continue;
+        }
+        if let Some(option) = find_serde_option(&field.attrs, &[
+            "flatten",
+            "serialize_with",
+            "with",
+        ]) {
+            return Err(syn::Error::new(
+                option.span(),
+                format!(
+                    "MaxSerializedSize cannot bound the size of fields with \
+                     `#[serde({option})]`"
+                ),
+            ));
+        }
+        let ty = &field.ty;
+        sizes.push(quote! {
+            <#ty as ::serialization::MaxSerializedSize>::MAX_SERIALIZED_SIZE
+        });
+    }
+    Ok(quote! { 0 #(+ #sizes)* })
+}
+
+pub fn derive(input: &DeriveInput) -> syn::Result<TokenStream> {
+    // Types encoded as other types are not bounded by the sizes of their
+    // own fields.
+    if let Some(option) = find_serde_option(&input.attrs, &["into"]) {
+        return Err(syn::Error::new(
+            option.span(),
+            format!(
+                "MaxSerializedSize cannot bound the size of types with \
+                 `#[serde({option})]`"
+            ),
+        ));
+    }
+    let size = match &input.data {
+        Data::Struct(data) => fields_size(&data.fields)?,
+        Data::Enum(data) => {
+            // Internally and adjacently tagged enums are encoded as maps
+            // holding the names of their variants, whose sizes are not
+            // bounded by the sizes of the fields.
+            if let Some(option) =
+                find_serde_option(&input.attrs, &["content", "tag"])
+            {
+                return Err(syn::Error::new(
+                    option.span(),
+                    "MaxSerializedSize cannot be derived for internally or \
+                     adjacently tagged enums",
+                ));
+            }
+            // Skipped variants keep their positions, so the variants after
+            // them are encoded with the same indices as if they were not
+            // skipped.  Variants given tags by `#[serialization(tag = N)]` are
+            // encoded by their tags instead.
+            let mut variants = Vec::with_capacity(data.variants.len());
+            for (index, variant) in (0_u32..).zip(&data.variants) {
+                if !has_serde_flag(&variant.attrs, &[
+                    "skip",
+                    "skip_serializing",
+                ]) {
+                    variants.push((
+                        variant_tag(variant)?.unwrap_or(index),
+                        variant,
+                    ));
+                }
+            }
+            if let Some(largest_tag) =
+                variants.iter().map(|(tag, _)| u64::from(*tag)).max()
+            {
+                let mut largest = quote! { 0 };
+                for (_, variant) in &variants {
+                    let size = fields_size(&variant.fields)?;
+                    largest = quote! {
+                        ::serialization::__private::max(#largest, #size)
+                    };
+                }
+                quote! {
+                    ::serialization::__private::varint_size(#largest_tag)
+                        + #largest
+                }
+            } else {
+                quote! { 0 }
+            }
+        },
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "MaxSerializedSize cannot be derived for unions",
+            ));
+        },
+    };
+    let mut generics = input.generics.clone();
+    for param in &mut generics.params {
+        if let GenericParam::Type(param) = param {
+            param.bounds.push(parse_quote!(::serialization::MaxSerializedSize));
+        }
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let name = &input.ident;
+    Ok(quote! {
+        impl #impl_generics ::serialization::MaxSerializedSize
+            for #name #ty_generics #where_clause
+        {
+            const MAX_SERIALIZED_SIZE: usize = #size;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn derive_error(input: &DeriveInput) -> String {
+        derive(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn unbounded_fields_are_rejected() {
+        for input in &[
+            parse_quote! {
+                struct Outer {
+                    #[serde(flatten)]
+                    inner: Inner,
+                }
+            },
+            parse_quote! {
+                struct Text(#[serde(with = "text")] u8);
+            },
+            parse_quote! {
+                enum Message {
+                    Data {
+                        #[serde(serialize_with = "text")]
+                        value: u8,
+                    },
+                }
+            },
+        ] {
+            assert!(derive_error(input)
+                .starts_with("MaxSerializedSize cannot bound the size"));
+        }
+        assert!(derive(&parse_quote! {
+            struct Skipped {
+                #[serde(skip, with = "text")]
+                value: u8,
+            }
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn converted_types_are_rejected() {
+        for input in &[
+            parse_quote! {
+                #[serde(into = "String")]
+                struct Name(u8);
+            },
+            parse_quote! {
+                #[derive(Clone)]
+                #[serde(rename_all = "lowercase", into = "u8")]
+                enum Level {
+                    Low,
+                    High,
+                }
+            },
+        ] {
+            assert_eq!(
+                "MaxSerializedSize cannot bound the size of types with \
+                 `#[serde(into)]`",
+                derive_error(input)
+            );
+        }
+    }
+
+    #[test]
+    fn tagged_enums_are_rejected() {
+        for input in &[
+            parse_quote! {
+                #[serde(tag = "type")]
+                enum Internal {
+                    A { value: u8 },
+                }
+            },
+            parse_quote! {
+                #[serde(tag = "t", content = "c")]
+                enum Adjacent {
+                    A(u8),
+                }
+            },
+        ] {
+            assert_eq!(
+                "MaxSerializedSize cannot be derived for internally or \
+                 adjacently tagged enums",
+                derive_error(input)
+            );
+        }
+    }
+}
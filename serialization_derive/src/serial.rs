@@ -0,0 +1,440 @@
+use crate::attributes::{
+    find_serde_option,
+    has_serde_flag,
+};
+use proc_macro2::{
+    Span,
+    TokenStream,
+};
+use quote::{
+    quote,
+    ToTokens,
+};
+use syn::{
+    parse_quote,
+    punctuated::Punctuated,
+    Attribute,
+    Data,
+    DataEnum,
+    DeriveInput,
+    Fields,
+    Ident,
+    LitInt,
+    Path,
+    Token,
+};
+
+// Determine whether or not the given attribute is one of the attributes
+// rewritten by the `serialization` attribute macro, which may also be
+// written with its older name, `serial`.
+fn is_serialization_attr(attr: &Attribute) -> bool {
+    attr.path().is_ident("serialization") || attr.path().is_ident("serial")
+}
+
+// These are the encodings which can be selected for fields, with the
+// modules of the `serialization` crate implementing them.
+const ENCODINGS: &[(&str, &str)] = &[
+    ("bytes", "::serialization::with::bytes"),
+    ("fixed", "::serialization::with::fixed"),
+    ("lossy_f32", "::serialization::with::lossy_f32"),
+    ("skip_default", "::serialization::with::skip_default"),
+    ("zigzag", "::serialization::with::zigzag"),
+];
+
+// Replace any `#[serialization(...)]` attributes among the given ones with the
+// `#[serde(...)]` attributes they stand for: `with = "..."` for an
+// encoding, `skip` for `skip`, and `default` for `since = N`.  The version
+// given by `since`, if any, is returned.
+fn rewrite_attributes(
+    attrs: &mut Vec<Attribute>
+) -> syn::Result<Option<(u32, Span)>> {
+    let mut encoding: Option<(Ident, &str)> = None;
+    let mut skip = false;
+    let mut since = None;
+    let mut rewritten = Vec::with_capacity(attrs.len());
+    for attr in attrs.drain(..) {
+        if !is_serialization_attr(&attr) {
+            rewritten.push(attr);
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                return Ok(());
+            }
+            if meta.path.is_ident("since") {
+                if since.is_some() {
+                    return Err(meta.error("version already given for field"));
+                }
+                let version: LitInt = meta.value()?.parse()?;
+                since = Some((version.base10_parse::<u32>()?, version.span()));
+                return Ok(());
+            }
+            let name = meta.path.require_ident()?;
+            let Some((_, module)) =
+                ENCODINGS.iter().find(|(encoding, _)| name == encoding)
+            else {
+                return Err(meta.error(format!(
+                    "unknown option `{name}`; expected `skip`, `since = N`, \
+                     or one of the encodings: {}",
+                    ENCODINGS
+                        .iter()
+                        .map(|(encoding, _)| *encoding)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            };
+            if let Some((first, _)) = &encoding {
+                return Err(meta.error(format!(
+                    "encoding `{first}` already selected for field"
+                )));
+            }
+            encoding = Some((name.clone(), module));
+            Ok(())
+        })?;
+    }
+    if let Some((_, module)) = encoding {
+        rewritten.push(parse_quote!(#[serde(with = #module)]));
+    }
+    if skip {
+        rewritten.push(parse_quote!(#[serde(skip)]));
+    } else if since.is_some() {
+        rewritten.push(parse_quote!(#[serde(default)]));
+    }
+    *attrs = rewritten;
+    Ok(if skip {
+        None
+    } else {
+        since
+    })
+}
+
+// Reject fields which are encoded only some of the time, since the fields
+// of a value are not named in its encoding, so leaving one out shifts the
+// rest and the value cannot be decoded.
+fn check_shape(fields: &Fields) -> syn::Result<()> {
+    for field in fields {
+        if let Some(option) =
+            find_serde_option(&field.attrs, &["skip_serializing_if"])
+        {
+            return Err(syn::Error::new(
+                option.span(),
+                "`skip_serializing_if` changes the number of fields encoded, \
+                 which cannot be decoded; use `#[serialization(skip_default)]` \
+                 instead",
+            ));
+        }
+        if !has_serde_flag(&field.attrs, &["skip_deserializing"]) {
+            if let Some(option) =
+                find_serde_option(&field.attrs, &["skip_serializing"])
+            {
+                return Err(syn::Error::new(
+                    option.span(),
+                    "`skip_serializing` without `skip_deserializing` leaves \
+                     out a field which is expected when decoding; use `skip` \
+                     instead",
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Rewrite the `#[serialization(...)]` attributes of the given fields, checking
+// that fields given versions by `since = N` are named and come after the
+// fields of earlier versions, since only fields at the end of a struct can
+// be missing from its encoding.
+fn rewrite_fields(fields: &mut Fields) -> syn::Result<()> {
+    check_shape(fields)?;
+    let named = matches!(fields, Fields::Named(_));
+    let mut latest = 0;
+    for field in fields.iter_mut() {
+        match rewrite_attributes(&mut field.attrs)? {
+            Some((_, span)) if !named => {
+                return Err(syn::Error::new(
+                    span,
+                    "`since` can only be given to named fields",
+                ));
+            },
+            Some((version, span)) if version < latest => {
+                return Err(syn::Error::new(
+                    span,
+                    format!(
+                        "field added in version {version} must come before \
+                         fields added in version {latest}, since only fields \
+                         at the end of a struct can be missing"
+                    ),
+                ));
+            },
+            Some((version, _)) => latest = version,
+            None if latest > 0 && !has_serde_flag(&field.attrs, &["skip"]) => {
+                return Err(syn::Error::new_spanned(
+                    &field.ident,
+                    format!(
+                        "field must come before fields added in version \
+                         {latest}, or be given a version with `since`, since \
+                         only fields at the end of a struct can be missing"
+                    ),
+                ));
+            },
+            None => {},
+        }
+    }
+    Ok(())
+}
+
+// These are the tag and aliases selected for a variant of an enum by
+// `#[serialization(tag = N, alias = M)]` attributes.
+#[derive(Default)]
+struct VariantOptions {
+    tag: Option<(u32, Span)>,
+    aliases: Vec<(u32, Span)>,
+}
+
+// These are the tags of the variants of an enum, in order, and any aliases
+// of them, paired with the indices of the variants.
+struct Tags {
+    tags: Vec<u32>,
+    aliases: Vec<(u32, u32)>,
+}
+
+// Remove any `#[serialization(...)]` attributes from the given ones,
+// returning the tag and aliases they select.
+fn take_variant_options(
+    attrs: &mut Vec<Attribute>
+) -> syn::Result<VariantOptions> {
+    let mut options = VariantOptions::default();
+    let mut kept = Vec::with_capacity(attrs.len());
+    for attr in attrs.drain(..) {
+        if !is_serialization_attr(&attr) {
+            kept.push(attr);
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            let is_tag = meta.path.is_ident("tag");
+            if !is_tag && !meta.path.is_ident("alias") {
+                return Err(meta
+                    .error("expected `tag = N` or `alias = N` on a variant"));
+            }
+            let value: LitInt = meta.value()?.parse()?;
+            let value = (value.base10_parse::<u32>()?, value.span());
+            if !is_tag {
+                options.aliases.push(value);
+            } else if options.tag.is_some() {
+                return Err(meta.error("tag already selected for variant"));
+            } else {
+                options.tag = Some(value);
+            }
+            Ok(())
+        })?;
+    }
+    *attrs = kept;
+    Ok(options)
+}
+
+// Work out the tag of each variant of the given enum, and any aliases of
+// them, removing the `#[serialization(tag = N, alias = M)]` attributes
+// selecting them.  As with discriminants, a variant without a tag gets the
+// one after the variant before it, and the first gets zero.  Nothing is
+// returned if no variant has a tag or alias.
+fn take_tags(data: &mut DataEnum) -> syn::Result<Option<Tags>> {
+    let mut tags = Tags {
+        tags: Vec::with_capacity(data.variants.len()),
+        aliases: Vec::new(),
+    };
+    let mut used: Vec<u32> = Vec::new();
+    let mut check = |tag: u32, span: Span| {
+        if used.contains(&tag) {
+            return Err(syn::Error::new(
+                span,
+                format!("tag {tag} already used by another variant"),
+            ));
+        }
+        used.push(tag);
+        Ok(tag)
+    };
+    let mut explicit = false;
+    for (index, variant) in (0_u32..).zip(&mut data.variants) {
+        let options = take_variant_options(&mut variant.attrs)?;
+        explicit |= options.tag.is_some() || !options.aliases.is_empty();
+        let tag = if let Some((tag, span)) = options.tag {
+            check(tag, span)?
+        } else {
+            let span = variant.ident.span();
+            let tag = tags
+                .tags
+                .last()
+                .map_or(Some(0), |last| last.checked_add(1))
+                .ok_or_else(|| {
+                    syn::Error::new(span, "variant needs an explicit tag")
+                })?;
+            check(tag, span).map_err(|_| {
+                syn::Error::new(
+                    span,
+                    "variant needs an explicit tag, since the one after the \
+                     previous variant is taken",
+                )
+            })?
+        };
+        tags.tags.push(tag);
+        for (alias, span) in options.aliases {
+            tags.aliases.push((check(alias, span)?, index));
+        }
+    }
+    Ok(if explicit {
+        Some(tags)
+    } else {
+        None
+    })
+}
+
+// Return whether the given attributes derive the trait with the given name.
+fn derives(
+    attrs: &[Attribute],
+    name: &str,
+) -> syn::Result<bool> {
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("derive")) {
+        let paths = attr
+            .parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)?;
+        if paths.iter().any(|path| {
+            path.segments.last().is_some_and(|segment| segment.ident == name)
+        }) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+// Make the given enum, whose variants have the given tags, use the tags in
+// place of the indices of its variants.  The `Serialize` and `Deserialize`
+// implementations derived by `serde` are turned into inherent functions, and
+// implementations are added which call them with a serializer or
+// deserializer translating between indices and tags.  If `LayoutFingerprint`
+// or `MaxSerializedSize` is derived as well, the tags are left on the
+// variants for it to include.
+fn tag_enum(
+    input: &mut DeriveInput,
+    tags: &Tags,
+) -> syn::Result<TokenStream> {
+    let aliases =
+        tags.aliases.iter().map(|(alias, index)| quote!((#alias, #index)));
+    let tags = &tags.tags;
+    let serialize = derives(&input.attrs, "Serialize")?;
+    let deserialize = derives(&input.attrs, "Deserialize")?;
+    if derives(&input.attrs, "LayoutFingerprint")?
+        || derives(&input.attrs, "MaxSerializedSize")?
+    {
+        if let Data::Enum(data) = &mut input.data {
+            for (variant, tag) in data.variants.iter_mut().zip(tags) {
+                variant.attrs.push(parse_quote!(#[serial_tag(#tag)]));
+            }
+        }
+    }
+    input.attrs.push(parse_quote!(#[serde(remote = "Self")]));
+    let name = &input.ident;
+    let params = input
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect::<Vec<_>>();
+    let mut impls = TokenStream::new();
+    if serialize {
+        let mut generics = input.generics.clone();
+        for param in &params {
+            generics.make_where_clause().predicates.push(parse_quote!(
+                #param: ::serialization::__private::serde::Serialize
+            ));
+        }
+        let (impl_generics, ty_generics, where_clause) =
+            generics.split_for_impl();
+        impls.extend(quote! {
+            impl #impl_generics ::serialization::__private::serde::Serialize
+                for #name #ty_generics #where_clause
+            {
+                fn serialize<__S>(
+                    &self,
+                    serializer: __S,
+                ) -> ::core::result::Result<__S::Ok, __S::Error>
+                where
+                    __S: ::serialization::__private::serde::Serializer,
+                {
+                    #name::serialize(
+                        self,
+                        ::serialization::__private::TagSerializer::new(
+                            serializer,
+                            &[#(#tags),*],
+                        ),
+                    )
+                }
+            }
+        });
+    }
+    if deserialize {
+        let mut generics = input.generics.clone();
+        for param in &params {
+            generics.make_where_clause().predicates.push(parse_quote!(
+                #param: ::serialization::__private::serde::Deserialize<'de>
+            ));
+        }
+        let (_, ty_generics, _) = input.generics.split_for_impl();
+        generics.params.insert(0, parse_quote!('de));
+        let (impl_generics, _, where_clause) = generics.split_for_impl();
+        impls.extend(quote! {
+            impl #impl_generics
+                ::serialization::__private::serde::Deserialize<'de>
+                for #name #ty_generics #where_clause
+            {
+                fn deserialize<__D>(
+                    deserializer: __D,
+                ) -> ::core::result::Result<Self, __D::Error>
+                where
+                    __D: ::serialization::__private::serde::Deserializer<'de>,
+                {
+                    #name::deserialize(
+                        ::serialization::__private::TagDeserializer::new(
+                            deserializer,
+                            &[#(#tags),*],
+                            &[#(#aliases),*],
+                        ),
+                    )
+                }
+            }
+        });
+    }
+    Ok(impls)
+}
+
+pub fn expand(
+    args: &TokenStream,
+    mut input: DeriveInput,
+) -> syn::Result<TokenStream> {
+    if !args.is_empty() {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "#[serialization] on a type takes no arguments",
+        ));
+    }
+    let mut tags = None;
+    match &mut input.data {
+        Data::Struct(data) => rewrite_fields(&mut data.fields)?,
+        Data::Enum(data) => {
+            for variant in &mut data.variants {
+                rewrite_fields(&mut variant.fields)?;
+            }
+            tags = take_tags(data)?;
+        },
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span,
+                "#[serialization] cannot be used on unions",
+            ));
+        },
+    }
+    let impls = match tags {
+        Some(tags) => tag_enum(&mut input, &tags)?,
+        None => TokenStream::new(),
+    };
+    let mut output = input.into_token_stream();
+    output.extend(impls);
+    Ok(output)
+}
@@ -0,0 +1,471 @@
+use super::{
+    to_bytes,
+    to_value,
+    with::ZigZag,
+    Result,
+    Value,
+};
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+};
+
+/// This is an alternative encoding which [`advise_encodings`] may suggest
+/// for part of a value.
+///
+/// [`advise_encodings`]: fn.advise_encodings.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SuggestedEncoding {
+    /// Encode the integer in a fixed number of bytes, with
+    /// `#[serialization(fixed)]`, which is smaller when most values are
+    /// large.
+    Fixed,
+
+    /// Encode the signed integer by interleaving positive and negative
+    /// values, with `#[serialization(zigzag)]`.
+    ZigZag,
+
+    /// Encode the `f64` as an `f32`, with `#[serialization(lossy_f32)]`.
+    /// This is only suggested when every value seen survives the narrowing
+    /// exactly.
+    LossyF32,
+
+    /// Replace the strings, of which there are few distinct ones, with
+    /// indices into a dictionary of them, such as by making them an enum.
+    /// The estimate includes sending the dictionary once.
+    Dictionary,
+}
+
+impl std::fmt::Display for SuggestedEncoding {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match self {
+            SuggestedEncoding::Fixed => {
+                f.write_str("#[serialization(fixed)]")
+            },
+            SuggestedEncoding::ZigZag => {
+                f.write_str("#[serialization(zigzag)]")
+            },
+            SuggestedEncoding::LossyF32 => {
+                f.write_str("#[serialization(lossy_f32)]")
+            },
+            SuggestedEncoding::Dictionary => f.write_str("dictionary"),
+        }
+    }
+}
+
+/// This is a suggestion, made by [`advise_encodings`], to encode one part
+/// of a value differently.
+///
+/// [`advise_encodings`]: fn.advise_encodings.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recommendation {
+    path: String,
+    encoding: SuggestedEncoding,
+    current_bytes: usize,
+    estimated_bytes: usize,
+}
+
+impl Recommendation {
+    /// Return the number of bytes the part took in all the samples, as
+    /// currently encoded.
+    #[must_use]
+    pub fn current_bytes(&self) -> usize {
+        self.current_bytes
+    }
+
+    /// Return the suggested encoding.
+    #[must_use]
+    pub fn encoding(&self) -> SuggestedEncoding {
+        self.encoding
+    }
+
+    /// Return the number of bytes the part would take in all the samples
+    /// with the suggested encoding.
+    #[must_use]
+    pub fn estimated_bytes(&self) -> usize {
+        self.estimated_bytes
+    }
+
+    /// Return the location of the part, written as in a [`LayoutReport`],
+    /// except that newtype structs are not given their own level.
+    ///
+    /// [`LayoutReport`]: struct.LayoutReport.html
+    #[must_use]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Return the number of bytes the suggested encoding would save across
+    /// all the samples.
+    #[must_use]
+    pub fn savings(&self) -> usize {
+        self.current_bytes - self.estimated_bytes
+    }
+}
+
+/// This holds the suggestions made by [`advise_encodings`], with those
+/// saving the most bytes first.  Its `Display` output lists them one per
+/// line.
+///
+/// [`advise_encodings`]: fn.advise_encodings.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Advice {
+    samples: usize,
+    total_bytes: usize,
+    recommendations: Vec<Recommendation>,
+}
+
+impl Advice {
+    /// Return the suggestions, with those saving the most bytes first.
+    #[must_use]
+    pub fn recommendations(&self) -> &[Recommendation] {
+        &self.recommendations
+    }
+
+    /// Return the number of values examined.
+    #[must_use]
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Return the number of bytes taken by all the samples, as currently
+    /// encoded.
+    #[must_use]
+    pub fn total_bytes(&self) -> usize {
+        self.total_bytes
+    }
+}
+
+impl std::fmt::Display for Advice {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        writeln!(f, "{} samples, {} bytes", self.samples, self.total_bytes)?;
+        for recommendation in &self.recommendations {
+            writeln!(
+                f,
+                "{}: {}, {} -> {} bytes",
+                recommendation.path,
+                recommendation.encoding,
+                recommendation.current_bytes,
+                recommendation.estimated_bytes
+            )?;
+        }
+        Ok(())
+    }
+}
+
+// These are the sizes gathered for one part of the samples.  An estimate
+// of `None` means the encoding cannot be used for some value seen.
+#[derive(Default)]
+struct PartStats {
+    current_bytes: usize,
+    estimates: BTreeMap<SuggestedEncoding, Option<usize>>,
+    strings: Vec<String>,
+}
+
+impl PartStats {
+    fn estimate(
+        &mut self,
+        encoding: SuggestedEncoding,
+        bytes: Option<usize>,
+    ) {
+        let total = self.estimates.entry(encoding).or_insert(Some(0));
+        *total = total.and_then(|total| Some(total + bytes?));
+    }
+
+    fn recommend(
+        mut self,
+        path: String,
+    ) -> Option<Recommendation> {
+        if !self.strings.is_empty() {
+            let distinct: BTreeSet<&String> = self.strings.iter().collect();
+            let index_bytes = size(&distinct.len()).ok()?;
+            let dictionary_bytes =
+                distinct.iter().map(size).sum::<Result<usize>>().ok()?;
+            let estimate = dictionary_bytes + self.strings.len() * index_bytes;
+            self.estimates
+                .insert(SuggestedEncoding::Dictionary, Some(estimate));
+        }
+        let (encoding, estimated_bytes) = self
+            .estimates
+            .into_iter()
+            .filter_map(|(encoding, estimate)| Some((encoding, estimate?)))
+            .min_by_key(|(_, estimate)| *estimate)?;
+        if estimated_bytes < self.current_bytes {
+            Some(Recommendation {
+                path,
+                encoding,
+                current_bytes: self.current_bytes,
+                estimated_bytes,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+fn size<T>(value: &T) -> Result<usize>
+where
+    T: serde::Serialize,
+{
+    to_bytes(value).map(|bytes| bytes.len())
+}
+
+struct Advisor {
+    parts: BTreeMap<String, PartStats>,
+}
+
+impl Advisor {
+    fn examine(
+        &mut self,
+        path: &str,
+        value: &Value,
+    ) -> Result<()> {
+        match value {
+            Value::I16(v) => self.examine_signed(path, *v)?,
+            Value::I32(v) => self.examine_signed(path, *v)?,
+            Value::I64(v) => self.examine_signed(path, *v)?,
+            Value::U16(v) => self.examine_unsigned(path, *v)?,
+            Value::U32(v) => self.examine_unsigned(path, *v)?,
+            Value::U64(v) => self.examine_unsigned(path, *v)?,
+            #[allow(clippy::cast_possible_truncation)]
+            Value::F64(v) => {
+                let part = self.part(path);
+                part.current_bytes += 8;
+                let exact = f64::from(*v as f32).to_bits() == v.to_bits();
+                part.estimate(SuggestedEncoding::LossyF32, exact.then_some(4));
+            },
+            Value::Str(v) => {
+                let part = self.part(path);
+                part.current_bytes += size(v)?;
+                part.strings.push(v.clone());
+            },
+            Value::Option(Some(inner)) => {
+                self.examine(&format!("{path}?"), inner)?;
+            },
+            Value::Seq(elements) => {
+                for element in elements {
+                    self.examine(&format!("{path}[]"), element)?;
+                }
+            },
+            Value::Tuple(fields) => {
+                for (i, field) in fields.iter().enumerate() {
+                    self.examine(&format!("{path}.{i}"), field)?;
+                }
+            },
+            Value::Map(entries) => {
+                for (key, value) in entries {
+                    self.examine(&format!("{path}{{key}}"), key)?;
+                    self.examine(&format!("{path}{{value}}"), value)?;
+                }
+            },
+            Value::Struct(fields) => {
+                for (name, value) in fields {
+                    self.examine(&format!("{path}.{name}"), value)?;
+                }
+            },
+            Value::Variant {
+                name,
+                value,
+                ..
+            } => {
+                let path = format!("{path}::{name}");
+                match &**value {
+                    Value::Unit => {},
+                    Value::Tuple(_) | Value::Struct(_) => {
+                        self.examine(&path, value)?;
+                    },
+                    _ => self.examine(&format!("{path}.0"), value)?,
+                }
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+
+    fn examine_signed<T>(
+        &mut self,
+        path: &str,
+        value: T,
+    ) -> Result<()>
+    where
+        T: ZigZag + serde::Serialize,
+    {
+        let current_bytes = size(&value)?;
+        let zigzag_bytes = size(&value.zigzag())?;
+        let part = self.part(path);
+        part.current_bytes += current_bytes;
+        part.estimate(SuggestedEncoding::Fixed, Some(std::mem::size_of::<T>()));
+        part.estimate(SuggestedEncoding::ZigZag, Some(zigzag_bytes));
+        Ok(())
+    }
+
+    fn examine_unsigned<T>(
+        &mut self,
+        path: &str,
+        value: T,
+    ) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        let current_bytes = size(&value)?;
+        let part = self.part(path);
+        part.current_bytes += current_bytes;
+        part.estimate(SuggestedEncoding::Fixed, Some(std::mem::size_of::<T>()));
+        Ok(())
+    }
+
+    fn part(
+        &mut self,
+        path: &str,
+    ) -> &mut PartStats {
+        self.parts.entry(String::from(path)).or_default()
+    }
+}
+
+/// This function is used to suggest alternative encodings for parts of
+/// values of a type, by encoding the given sample of real values and
+/// totaling, for each part, how many bytes it takes now and how many it
+/// would take with each alternative.  The alternatives are fixed-width and
+/// interleaved integers, narrowing `f64` values which are exactly
+/// representable as `f32`, and dictionaries for strings which repeat.  A
+/// part is only suggested an alternative which would make it smaller, and
+/// then the one which would make it smallest.
+///
+/// The estimates are only as good as the sample, so it should be drawn
+/// from the values the application actually encodes.
+///
+/// # Errors
+///
+/// Any error from encoding the samples is returned.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     advise_encodings,
+///     SuggestedEncoding,
+/// };
+///
+/// #[derive(serde::Serialize)]
+/// struct Reading {
+///     hash: u32,
+///     sensor: String,
+/// }
+///
+/// let samples: Vec<Reading> = (0..100)
+///     .map(|i| Reading {
+///         hash: 0xF000_0000 | i,
+///         sensor: format!("sensor-{}", i % 4),
+///     })
+///     .collect();
+/// let advice = advise_encodings(&samples).unwrap();
+/// let suggestions: Vec<_> = advice
+///     .recommendations()
+///     .iter()
+///     .map(|recommendation| {
+///         (recommendation.path(), recommendation.encoding())
+///     })
+///     .collect();
+/// assert_eq!(
+///     vec![
+///         ("$.sensor", SuggestedEncoding::Dictionary),
+///         ("$.hash", SuggestedEncoding::Fixed),
+///     ],
+///     suggestions
+/// );
+/// ```
+pub fn advise_encodings<'a, I, T>(samples: I) -> Result<Advice>
+where
+    I: IntoIterator<Item = &'a T>,
+    T: 'a + serde::Serialize,
+{
+    let mut advisor = Advisor {
+        parts: BTreeMap::new(),
+    };
+    let mut count = 0;
+    let mut total_bytes = 0;
+    for sample in samples {
+        count += 1;
+        total_bytes += size(sample)?;
+        advisor.examine("$", &to_value(sample)?)?;
+    }
+    let mut recommendations: Vec<Recommendation> = advisor
+        .parts
+        .into_iter()
+        .filter_map(|(path, part)| part.recommend(path))
+        .collect();
+    recommendations.sort_by(|a, b| {
+        b.savings().cmp(&a.savings()).then_with(|| a.path.cmp(&b.path))
+    });
+    Ok(Advice {
+        samples: count,
+        total_bytes,
+        recommendations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Serialize)]
+    enum Event {
+        Moved {
+            delta: i32,
+        },
+        Renamed(String),
+        Stopped,
+    }
+
+    #[test]
+    fn nothing_to_suggest() {
+        let samples = vec![(1_u32, String::from("a")), (2, String::from("b"))];
+        let advice = advise_encodings(&samples);
+        assert!(advice.is_ok());
+        let advice = advice.unwrap();
+        assert_eq!(2, advice.samples());
+        assert_eq!(6, advice.total_bytes());
+        assert!(advice.recommendations().is_empty());
+        assert_eq!("2 samples, 6 bytes\n", advice.to_string());
+    }
+
+    #[test]
+    fn parts_within_variants() {
+        let mut samples = Vec::new();
+        for i in 0..20 {
+            samples.push(Event::Moved {
+                delta: -1_000_000_000 - i,
+            });
+            samples.push(Event::Renamed(String::from("the same long name")));
+            samples.push(Event::Stopped);
+        }
+        let advice = advise_encodings(&samples);
+        assert!(advice.is_ok());
+        let advice = advice.unwrap();
+        assert_eq!(
+            vec![
+                Recommendation {
+                    path: String::from("$::Renamed.0"),
+                    encoding: SuggestedEncoding::Dictionary,
+                    current_bytes: 380,
+                    estimated_bytes: 39,
+                },
+                Recommendation {
+                    path: String::from("$::Moved.delta"),
+                    encoding: SuggestedEncoding::Fixed,
+                    current_bytes: 100,
+                    estimated_bytes: 80,
+                },
+            ],
+            advice.recommendations()
+        );
+        assert_eq!(341, advice.recommendations()[0].savings());
+    }
+}
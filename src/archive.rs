@@ -0,0 +1,348 @@
+use super::{
+    from_bytes,
+    to_bytes,
+    Compressor,
+    Deserializer,
+    Error,
+    Result,
+};
+use std::{
+    convert::TryFrom,
+    io::Write,
+};
+
+// This is the size of the footer of an archive, which holds the offset of
+// the index as a big-endian 64-bit integer.
+const FOOTER_SIZE: usize = 8;
+
+// This locates one chunk of an archive.
+#[derive(
+    Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+struct Chunk {
+    first_record: u64,
+    offset: u64,
+    len: u64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Index {
+    chunks: Vec<Chunk>,
+    records: u64,
+}
+
+/// This writes a sequence of values as a compressed archive, from which
+/// individual values can later be decoded, using [`Archive`], without
+/// decompressing everything before them.  Values are encoded and gathered
+/// into chunks, which are compressed separately, and an index locating the
+/// chunks is written at the end of the archive.  Reading a value only
+/// requires decompressing the chunk holding it.
+///
+/// Larger chunks compress better, while smaller chunks make reading
+/// individual values cheaper.
+///
+/// [`Archive`]: struct.Archive.html
+pub struct ArchiveWriter<W, C> {
+    writer: W,
+    compressor: C,
+    chunk_size: usize,
+    chunk: Vec<u8>,
+    chunks: Vec<Chunk>,
+    records: u64,
+    chunk_first_record: u64,
+    offset: u64,
+}
+
+impl<W, C> ArchiveWriter<W, C>
+where
+    W: Write,
+    C: Compressor,
+{
+    /// This is the default size, in bytes, which the encodings of the
+    /// values in a chunk reach before the chunk is compressed and written.
+    pub const DEFAULT_CHUNK_SIZE: usize = 65536;
+
+    /// This function is used to add the given value to the end of the
+    /// archive.
+    ///
+    /// # Errors
+    ///
+    /// Any of the errors returned by [`to_bytes`] may be returned from
+    /// encoding the value, and any errors returned by the compressor are
+    /// passed through.  [`Error::Io`] is returned if the writer fails.
+    ///
+    /// [`to_bytes`]: fn.to_bytes.html
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    pub fn append<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        self.chunk.extend(to_bytes(value)?);
+        self.records += 1;
+        if self.chunk.len() >= self.chunk_size {
+            self.write_chunk()?;
+        }
+        Ok(())
+    }
+
+    /// This function is used to write out the last chunk and the index of
+    /// the archive, returning the writer.
+    ///
+    /// # Errors
+    ///
+    /// Any errors returned by the compressor are passed through.
+    /// [`Error::Io`] is returned if the writer fails.
+    ///
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    pub fn finish(mut self) -> Result<W> {
+        if !self.chunk.is_empty() {
+            self.write_chunk()?;
+        }
+        let index = to_bytes(&Index {
+            chunks: self.chunks,
+            records: self.records,
+        })?;
+        self.writer.write_all(&index).map_err(Error::Io)?;
+        self.writer.write_all(&self.offset.to_be_bytes()).map_err(Error::Io)?;
+        self.writer.flush().map_err(Error::Io)?;
+        Ok(self.writer)
+    }
+
+    /// Return a new archive writer which writes to the given writer, using
+    /// the given compressor, and the default chunk size.
+    #[must_use]
+    pub fn new(
+        writer: W,
+        compressor: C,
+    ) -> Self {
+        Self {
+            writer,
+            compressor,
+            chunk_size: Self::DEFAULT_CHUNK_SIZE,
+            chunk: Vec::new(),
+            chunks: Vec::new(),
+            records: 0,
+            chunk_first_record: 0,
+            offset: 0,
+        }
+    }
+
+    /// Set the size, in bytes, which the encodings of the values in a chunk
+    /// reach before the chunk is compressed and written.
+    #[must_use]
+    pub fn with_chunk_size(
+        mut self,
+        chunk_size: usize,
+    ) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    fn write_chunk(&mut self) -> Result<()> {
+        let compressed = self.compressor.compress(&self.chunk)?;
+        self.writer.write_all(&compressed).map_err(Error::Io)?;
+        let len = compressed.len() as u64;
+        self.chunks.push(Chunk {
+            first_record: self.chunk_first_record,
+            offset: self.offset,
+            len,
+        });
+        self.offset += len;
+        self.chunk_first_record = self.records;
+        self.chunk.clear();
+        Ok(())
+    }
+}
+
+/// This provides access to the values in an archive written by
+/// [`ArchiveWriter`], decompressing only the chunk holding each value
+/// read.
+///
+/// [`ArchiveWriter`]: struct.ArchiveWriter.html
+pub struct Archive<'a, C> {
+    bytes: &'a [u8],
+    compressor: C,
+    index: Index,
+}
+
+impl<'a, C> Archive<'a, C>
+where
+    C: Compressor,
+{
+    /// This function is used to decode the value with the given index (in
+    /// the order the values were added) from the archive.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`Error::ValueTruncated`] if the archive does
+    /// not contain the requested value, or if a chunk lies outside the
+    /// archive.  Any errors returned by the compressor are passed through,
+    /// and any of the errors returned by [`from_bytes`] may be returned from
+    /// decoding the values in the chunk holding the requested value, up to
+    /// and including it.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`from_bytes`]: fn.from_bytes.html
+    pub fn get<T>(
+        &self,
+        index: usize,
+    ) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let record = index as u64;
+        if record >= self.index.records {
+            return Err(Error::ValueTruncated);
+        }
+        let chunk = self.index.chunks[self
+            .index
+            .chunks
+            .partition_point(|chunk| chunk.first_record <= record)
+            - 1];
+        let start =
+            usize::try_from(chunk.offset).map_err(|_| Error::ValueTruncated)?;
+        let end = usize::try_from(chunk.offset + chunk.len)
+            .map_err(|_| Error::ValueTruncated)?;
+        let compressed =
+            self.bytes.get(start..end).ok_or(Error::ValueTruncated)?;
+        let decompressed = self.compressor.decompress(compressed)?;
+        #[allow(clippy::cast_possible_truncation)]
+        let position = (record - chunk.first_record) as usize;
+        Deserializer::new(&decompressed)
+            .iterate::<T>()
+            .nth(position)
+            .unwrap_or(Err(Error::ValueTruncated))
+    }
+
+    /// Determine whether or not the archive contains no values.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.index.records == 0
+    }
+
+    /// Return the number of values in the archive.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn len(&self) -> usize {
+        self.index.records as usize
+    }
+
+    /// This function is used to open the archive in the given bytes, which
+    /// was written using the given compressor.  Only the index of the
+    /// archive is decoded up front.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`Error::ValueTruncated`] if the bytes are too
+    /// short to hold the index of an archive.  Any of the errors returned by
+    /// [`from_bytes`] may be returned from decoding the index.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`from_bytes`]: fn.from_bytes.html
+    pub fn open(
+        bytes: &'a [u8],
+        compressor: C,
+    ) -> Result<Self> {
+        let footer_start = bytes
+            .len()
+            .checked_sub(FOOTER_SIZE)
+            .ok_or(Error::ValueTruncated)?;
+        let mut footer = [0; FOOTER_SIZE];
+        footer.copy_from_slice(&bytes[footer_start..]);
+        let index_start = usize::try_from(u64::from_be_bytes(footer))
+            .map_err(|_| Error::ValueTruncated)?;
+        let index = bytes
+            .get(index_start..footer_start)
+            .ok_or(Error::ValueTruncated)?;
+        let index: Index = from_bytes(index)?;
+        if index.records > 0
+            && index.chunks.first().map(|chunk| chunk.first_record) != Some(0)
+        {
+            return Err(Error::ValueTruncated);
+        }
+        Ok(Self {
+            bytes,
+            compressor,
+            index,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::tests::RunLength;
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Sample {
+        id: u32,
+        readings: Vec<u8>,
+    }
+
+    fn sample(id: u32) -> Sample {
+        Sample {
+            id,
+            readings: vec![(id % 7) as u8; (id % 13) as usize],
+        }
+    }
+
+    fn archive(count: u32) -> Vec<u8> {
+        let mut writer =
+            ArchiveWriter::new(Vec::new(), RunLength).with_chunk_size(64);
+        for id in 0..count {
+            assert!(writer.append(&sample(id)).is_ok());
+        }
+        let archive = writer.finish();
+        assert!(archive.is_ok());
+        archive.unwrap()
+    }
+
+    #[test]
+    fn records_are_read_individually() {
+        let bytes = archive(500);
+        let archive = Archive::open(&bytes, RunLength);
+        assert!(archive.is_ok());
+        let archive = archive.unwrap();
+        assert_eq!(500, archive.len());
+        assert!(archive.index.chunks.len() > 10);
+        for id in &[0, 1, 63, 64, 250, 499] {
+            let value = archive.get::<Sample>(*id as usize);
+            assert!(value.is_ok());
+            let value = value.unwrap();
+            assert_eq!(sample(*id), value);
+        }
+        assert!(matches!(
+            archive.get::<Sample>(500),
+            Err(Error::ValueTruncated)
+        ));
+    }
+
+    #[test]
+    fn empty_archive() {
+        let bytes = archive(0);
+        let archive = Archive::open(&bytes, RunLength);
+        assert!(archive.is_ok());
+        let archive = archive.unwrap();
+        assert!(archive.is_empty());
+        assert!(matches!(archive.get::<Sample>(0), Err(Error::ValueTruncated)));
+    }
+
+    #[test]
+    fn damaged_archives() {
+        let bytes = archive(100);
+        assert!(matches!(
+            Archive::open(&bytes[..4], RunLength),
+            Err(Error::ValueTruncated)
+        ));
+        let mut moved = bytes.clone();
+        let footer_start = moved.len() - FOOTER_SIZE;
+        moved[footer_start..].copy_from_slice(&u64::MAX.to_be_bytes());
+        assert!(matches!(
+            Archive::open(&moved, RunLength),
+            Err(Error::ValueTruncated)
+        ));
+    }
+}
@@ -0,0 +1,621 @@
+use super::{
+    from_bytes,
+    from_value,
+    schema::Variant,
+    to_bytes,
+    to_value,
+    Error,
+    Result,
+    Schema,
+    Value,
+};
+use arrow_array::{
+    cast::AsArray,
+    types::{
+        Decimal128Type,
+        Float32Type,
+        Float64Type,
+        Int16Type,
+        Int32Type,
+        Int64Type,
+        Int8Type,
+        UInt16Type,
+        UInt32Type,
+        UInt64Type,
+        UInt8Type,
+    },
+    Array,
+    ArrayRef,
+    BinaryArray,
+    BooleanArray,
+    Decimal128Array,
+    Float32Array,
+    Float64Array,
+    Int16Array,
+    Int32Array,
+    Int64Array,
+    Int8Array,
+    RecordBatch,
+    StringArray,
+    UInt16Array,
+    UInt32Array,
+    UInt64Array,
+    UInt8Array,
+};
+use arrow_schema::{
+    ArrowError,
+    DataType,
+};
+use std::{
+    convert::TryFrom,
+    sync::Arc,
+};
+
+// Arrow has no 128-bit integers, so `i128` and `u128` are kept as decimals
+// with no digits after the point, which hold integers of up to this many
+// digits.
+const DECIMAL_DIGITS: u8 = 38;
+
+fn unsupported(schema: &Schema) -> Error {
+    Error::Arrow(ArrowError::NotYetImplemented(format!(
+        "no Arrow column type for {schema:?}"
+    )))
+}
+
+fn mismatch(what: &str) -> Error {
+    Error::Arrow(ArrowError::InvalidArgumentError(format!(
+        "{what} does not match its schema"
+    )))
+}
+
+// Return the fields of the struct with the given schema, looking through
+// newtype structs wrapping it.
+fn struct_fields(schema: &Schema) -> Result<&[super::schema::Field]> {
+    match schema {
+        Schema::Struct {
+            fields,
+            ..
+        } => Ok(fields),
+        Schema::NewtypeStruct {
+            inner,
+            ..
+        } => struct_fields(inner),
+        _ => Err(unsupported(schema)),
+    }
+}
+
+// Return the schema of the values kept in a column for a field with the
+// given schema, which is what is left after looking through newtype
+// structs and one option, and whether or not values may be missing.
+fn column_schema(schema: &Schema) -> Result<(&Schema, bool)> {
+    match schema {
+        Schema::NewtypeStruct {
+            inner,
+            ..
+        } => column_schema(inner),
+        Schema::Option(inner) => match column_schema(inner)? {
+            (_, true) => Err(unsupported(schema)),
+            (inner, false) => Ok((inner, true)),
+        },
+        _ => Ok((schema, false)),
+    }
+}
+
+// Return the Arrow type of a column of values with the given schema.  Enums
+// whose variants have no fields are kept as the names of their variants.
+fn data_type(schema: &Schema) -> Result<DataType> {
+    Ok(match schema {
+        Schema::Bool => DataType::Boolean,
+        Schema::I8 => DataType::Int8,
+        Schema::I16 => DataType::Int16,
+        Schema::I32 => DataType::Int32,
+        Schema::I64 => DataType::Int64,
+        Schema::U8 => DataType::UInt8,
+        Schema::U16 => DataType::UInt16,
+        Schema::U32 => DataType::UInt32,
+        Schema::U64 => DataType::UInt64,
+        Schema::I128 | Schema::U128 => {
+            DataType::Decimal128(DECIMAL_DIGITS, 0)
+        },
+        Schema::F32 => DataType::Float32,
+        Schema::F64 => DataType::Float64,
+        Schema::Char | Schema::Str => DataType::Utf8,
+        Schema::Bytes => DataType::Binary,
+        Schema::Enum {
+            variants,
+            ..
+        } if variants.iter().all(|variant| {
+            variant.kind == super::schema::VariantKind::Unit
+        }) =>
+        {
+            DataType::Utf8
+        },
+        _ => return Err(unsupported(schema)),
+    })
+}
+
+macro_rules! primitive_column {
+    ($cells:expr, $variant:ident, $array:ty) => {
+        Arc::new(
+            $cells
+                .into_iter()
+                .map(|cell| {
+                    cell.map(|value| match value {
+                        Value::$variant(value) => Ok(value),
+                        _ => Err(mismatch("value")),
+                    })
+                    .transpose()
+                })
+                .collect::<Result<$array>>()?,
+        ) as ArrayRef
+    };
+}
+
+// Return the given 128-bit integer as it is kept in a decimal column.
+fn decimal(value: &Value) -> Result<i128> {
+    let value = match *value {
+        Value::I128(value) => value,
+        Value::U128(value) => {
+            i128::try_from(value).map_err(|_| Error::IntegerOverflow)?
+        },
+        _ => return Err(mismatch("value")),
+    };
+    if value.unsigned_abs() < 10_u128.pow(DECIMAL_DIGITS.into()) {
+        Ok(value)
+    } else {
+        Err(Error::IntegerOverflow)
+    }
+}
+
+// Make the column for the given values, with the given schema, with
+// `None` standing for missing values.
+fn column(
+    schema: &Schema,
+    cells: Vec<Option<Value>>,
+) -> Result<ArrayRef> {
+    Ok(match schema {
+        Schema::Bool => primitive_column!(cells, Bool, BooleanArray),
+        Schema::I8 => primitive_column!(cells, I8, Int8Array),
+        Schema::I16 => primitive_column!(cells, I16, Int16Array),
+        Schema::I32 => primitive_column!(cells, I32, Int32Array),
+        Schema::I64 => primitive_column!(cells, I64, Int64Array),
+        Schema::U8 => primitive_column!(cells, U8, UInt8Array),
+        Schema::U16 => primitive_column!(cells, U16, UInt16Array),
+        Schema::U32 => primitive_column!(cells, U32, UInt32Array),
+        Schema::U64 => primitive_column!(cells, U64, UInt64Array),
+        Schema::F32 => primitive_column!(cells, F32, Float32Array),
+        Schema::F64 => primitive_column!(cells, F64, Float64Array),
+        Schema::I128 | Schema::U128 => Arc::new(
+            cells
+                .into_iter()
+                .map(|cell| cell.as_ref().map(decimal).transpose())
+                .collect::<Result<Decimal128Array>>()?
+                .with_precision_and_scale(DECIMAL_DIGITS, 0)?,
+        ),
+        Schema::Bytes => primitive_column!(cells, Bytes, BinaryArray),
+        _ => Arc::new(
+            cells
+                .into_iter()
+                .map(|cell| {
+                    cell.map(|value| match value {
+                        Value::Str(value) => Ok(value),
+                        Value::Char(value) => Ok(value.to_string()),
+                        Value::Variant {
+                            name,
+                            ..
+                        } => Ok(name),
+                        _ => Err(mismatch("value")),
+                    })
+                    .transpose()
+                })
+                .collect::<Result<StringArray>>()?,
+        ),
+    })
+}
+
+// Return the value in the given row of the given column, whose values have
+// the given schema.
+fn cell(
+    schema: &Schema,
+    array: &dyn Array,
+    row: usize,
+) -> Result<Value> {
+    Ok(match schema {
+        Schema::Bool => Value::Bool(array.as_boolean().value(row)),
+        Schema::I8 => Value::I8(array.as_primitive::<Int8Type>().value(row)),
+        Schema::I16 => {
+            Value::I16(array.as_primitive::<Int16Type>().value(row))
+        },
+        Schema::I32 => {
+            Value::I32(array.as_primitive::<Int32Type>().value(row))
+        },
+        Schema::I64 => {
+            Value::I64(array.as_primitive::<Int64Type>().value(row))
+        },
+        Schema::U8 => Value::U8(array.as_primitive::<UInt8Type>().value(row)),
+        Schema::U16 => {
+            Value::U16(array.as_primitive::<UInt16Type>().value(row))
+        },
+        Schema::U32 => {
+            Value::U32(array.as_primitive::<UInt32Type>().value(row))
+        },
+        Schema::U64 => {
+            Value::U64(array.as_primitive::<UInt64Type>().value(row))
+        },
+        Schema::I128 => {
+            Value::I128(array.as_primitive::<Decimal128Type>().value(row))
+        },
+        Schema::U128 => Value::U128(
+            u128::try_from(array.as_primitive::<Decimal128Type>().value(row))
+                .map_err(|_| Error::IntegerOverflow)?,
+        ),
+        Schema::F32 => {
+            Value::F32(array.as_primitive::<Float32Type>().value(row))
+        },
+        Schema::F64 => {
+            Value::F64(array.as_primitive::<Float64Type>().value(row))
+        },
+        Schema::Bytes => {
+            Value::Bytes(array.as_binary::<i32>().value(row).to_vec())
+        },
+        Schema::Char => {
+            let mut chars = array.as_string::<i32>().value(row).chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Value::Char(c),
+                _ => return Err(mismatch("character")),
+            }
+        },
+        Schema::Enum {
+            variants,
+            ..
+        } => {
+            let name = array.as_string::<i32>().value(row);
+            let index = variants
+                .iter()
+                .position(|variant: &Variant| variant.name == name)
+                .ok_or_else(|| mismatch("variant name"))?;
+            Value::Variant {
+                index: u32::try_from(index)
+                    .map_err(|_| Error::IntegerOverflow)?,
+                name: name.to_owned(),
+                value: Box::new(Value::Unit),
+            }
+        },
+        _ => Value::Str(array.as_string::<i32>().value(row).to_owned()),
+    })
+}
+
+/// This function is used to convert an encoded sequence of structs of type
+/// `T`, such as a `Vec<T>` encoded by [`to_bytes`], into an Arrow
+/// [`RecordBatch`], with one row for each struct, and one column for each
+/// field, named after the field, so that analytics tools built on Arrow
+/// can take in payloads without a bespoke conversion.
+///
+/// The columns are laid out from the [`Schema`] of `T`.  Fields may be of
+/// any of the primitive types, with `i128` and `u128` as 38-digit decimals
+/// since Arrow has no 128-bit integers, `char` or string (as Arrow
+/// strings), byte strings, enums whose variants have no fields (as the
+/// names of the variants), newtype structs wrapping any of those, or
+/// options of any of those (as columns which may have nulls).  Other
+/// fields, such as nested structs and sequences, have no column type.
+///
+/// [`to_bytes`]: fn.to_bytes.html
+/// [`RecordBatch`]: https://docs.rs/arrow-array/57/arrow_array/struct.RecordBatch.html
+/// [`Schema`]: enum.Schema.html
+///
+/// # Errors
+///
+/// Returns [`Error::Arrow`] if `T` is not a struct, or any of its fields
+/// has no column type, [`Error::IntegerOverflow`] if a 128-bit integer has
+/// more than 38 digits, and otherwise any of the errors returned by
+/// [`from_bytes`] from decoding the sequence.
+///
+/// [`Error::Arrow`]: enum.Error.html#variant.Arrow
+/// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+/// [`from_bytes`]: fn.from_bytes.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use arrow_array::Array;
+/// use serialization::{
+///     from_record_batch,
+///     to_bytes,
+///     to_record_batch,
+/// };
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Reading {
+///     sensor: String,
+///     celsius: f64,
+/// }
+///
+/// let readings = vec![
+///     Reading {
+///         sensor: String::from("attic"),
+///         celsius: 31.5,
+///     },
+///     Reading {
+///         sensor: String::from("cellar"),
+///         celsius: 12.0,
+///     },
+/// ];
+/// let encoded = to_bytes(&readings).unwrap();
+/// let batch = to_record_batch::<Reading>(&encoded).unwrap();
+/// assert_eq!(2, batch.num_rows());
+/// assert_eq!(2, batch.column_by_name("celsius").unwrap().len());
+/// assert_eq!(encoded, from_record_batch::<Reading>(&batch).unwrap());
+/// ```
+pub fn to_record_batch<T>(encoded: &[u8]) -> Result<RecordBatch>
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    let schema = Schema::of::<T>()?;
+    let fields = struct_fields(&schema)?;
+    let mut arrow_fields = Vec::new();
+    for field in fields {
+        let (schema, nullable) = column_schema(&field.schema)?;
+        arrow_fields.push(arrow_schema::Field::new(
+            field.name,
+            data_type(schema)?,
+            nullable,
+        ));
+    }
+    let mut cells: Vec<Vec<Option<Value>>> = vec![Vec::new(); fields.len()];
+    for value in from_bytes::<Vec<T>>(encoded)? {
+        let Value::Struct(values) = to_value(&value)? else {
+            return Err(mismatch("struct"));
+        };
+        if values.len() != fields.len() {
+            return Err(mismatch("struct"));
+        }
+        for ((_, value), cells) in values.into_iter().zip(&mut cells) {
+            cells.push(match value {
+                Value::Option(value) => value.map(|value| *value),
+                value => Some(value),
+            });
+        }
+    }
+    let columns = fields
+        .iter()
+        .zip(cells)
+        .map(|(field, cells)| column(column_schema(&field.schema)?.0, cells))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(RecordBatch::try_new(
+        Arc::new(arrow_schema::Schema::new(arrow_fields)),
+        columns,
+    )?)
+}
+
+/// This function is used to convert an Arrow [`RecordBatch`] back into an
+/// encoded sequence of structs of type `T`, the same as encoding a `Vec<T>`
+/// with [`to_bytes`].  Columns are found by the names of the fields of
+/// `T`, and must have the types [`to_record_batch`] gives them; other
+/// columns are ignored.
+///
+/// [`RecordBatch`]: https://docs.rs/arrow-array/57/arrow_array/struct.RecordBatch.html
+/// [`to_bytes`]: fn.to_bytes.html
+/// [`to_record_batch`]: fn.to_record_batch.html
+///
+/// # Errors
+///
+/// Returns [`Error::Arrow`] if `T` is not a struct, or any of its fields
+/// has no column type, or its column is missing, has the wrong type, or
+/// has nulls where the field is not an option, and otherwise any of the
+/// errors returned by [`from_value`] from making the structs, or by
+/// [`to_bytes`] from encoding them.
+///
+/// [`Error::Arrow`]: enum.Error.html#variant.Arrow
+/// [`from_value`]: fn.from_value.html
+pub fn from_record_batch<T>(batch: &RecordBatch) -> Result<Vec<u8>>
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    let schema = Schema::of::<T>()?;
+    let fields = struct_fields(&schema)?;
+    let mut columns = Vec::new();
+    for field in fields {
+        let (schema, nullable) = column_schema(&field.schema)?;
+        let array = batch.column_by_name(field.name).ok_or_else(|| {
+            Error::Arrow(ArrowError::SchemaError(format!(
+                "no column for field {}",
+                field.name
+            )))
+        })?;
+        if array.data_type() != &data_type(schema)?
+            || (!nullable && array.null_count() > 0)
+        {
+            return Err(Error::Arrow(ArrowError::SchemaError(format!(
+                "column for field {} does not match its schema",
+                field.name
+            ))));
+        }
+        columns.push((field.name, schema, array));
+    }
+    let mut values = Vec::with_capacity(batch.num_rows());
+    for row in 0..batch.num_rows() {
+        let value = columns
+            .iter()
+            .zip(fields)
+            .map(|((name, schema, array), field)| {
+                let value = if array.is_null(row) {
+                    Value::Option(None)
+                } else if column_schema(&field.schema)?.1 {
+                    Value::Option(Some(Box::new(cell(schema, array, row)?)))
+                } else {
+                    cell(schema, array, row)?
+                };
+                Ok(((*name).to_owned(), value))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        values.push(from_value::<T>(Value::Struct(value))?);
+    }
+    to_bytes(&values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{
+        Deserialize,
+        Serialize,
+    };
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    enum Status {
+        Idle,
+        Busy,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Id(u64);
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Record {
+        id: Id,
+        name: String,
+        initial: char,
+        score: f32,
+        delta: i16,
+        active: bool,
+        status: Status,
+        note: Option<String>,
+        #[serde(with = "crate::with::bytes")]
+        blob: Vec<u8>,
+    }
+
+    #[test]
+    fn record_batches() {
+        let records = vec![
+            Record {
+                id: Id(1),
+                name: String::from("first"),
+                initial: 'é',
+                score: 0.5,
+                delta: -3,
+                active: true,
+                status: Status::Busy,
+                note: None,
+                blob: vec![1, 2],
+            },
+            Record {
+                id: Id(u64::MAX),
+                name: String::from("second"),
+                initial: 's',
+                score: 2.0,
+                delta: 300,
+                active: false,
+                status: Status::Idle,
+                note: Some(String::from("noted")),
+                blob: vec![],
+            },
+        ];
+        let encoded = to_bytes(&records).unwrap();
+        let batch = to_record_batch::<Record>(&encoded);
+        assert!(batch.is_ok());
+        let batch = batch.unwrap();
+        assert_eq!(2, batch.num_rows());
+        assert_eq!(9, batch.num_columns());
+        let schema = batch.schema();
+        let types: Vec<(&str, &DataType, bool)> = schema
+            .fields()
+            .iter()
+            .map(|field| {
+                (field.name().as_str(), field.data_type(), field.is_nullable())
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("id", &DataType::UInt64, false),
+                ("name", &DataType::Utf8, false),
+                ("initial", &DataType::Utf8, false),
+                ("score", &DataType::Float32, false),
+                ("delta", &DataType::Int16, false),
+                ("active", &DataType::Boolean, false),
+                ("status", &DataType::Utf8, false),
+                ("note", &DataType::Utf8, true),
+                ("blob", &DataType::Binary, false),
+            ],
+            types
+        );
+        let status = batch.column_by_name("status").unwrap().as_string::<i32>();
+        assert_eq!("Busy", status.value(0));
+        let note = batch.column_by_name("note").unwrap();
+        assert!(note.is_null(0));
+        assert_eq!("noted", note.as_string::<i32>().value(1));
+        let decoded = from_record_batch::<Record>(&batch);
+        assert!(decoded.is_ok());
+        assert_eq!(encoded, decoded.unwrap());
+    }
+
+    #[test]
+    fn wide_integers() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Totals {
+            count: u128,
+            balance: Option<i128>,
+        }
+        let records = vec![
+            Totals {
+                count: 10_u128.pow(38) - 1,
+                balance: Some(-(10_i128.pow(38) - 1)),
+            },
+            Totals {
+                count: 0,
+                balance: None,
+            },
+        ];
+        let encoded = to_bytes(&records).unwrap();
+        let batch = to_record_batch::<Totals>(&encoded);
+        assert!(batch.is_ok());
+        let batch = batch.unwrap();
+        assert_eq!(
+            &DataType::Decimal128(38, 0),
+            batch.column_by_name("count").unwrap().data_type()
+        );
+        let decoded = from_record_batch::<Totals>(&batch);
+        assert!(decoded.is_ok());
+        assert_eq!(encoded, decoded.unwrap());
+        let too_wide = to_bytes(&vec![Totals {
+            count: 10_u128.pow(38),
+            balance: None,
+        }])
+        .unwrap();
+        assert!(matches!(
+            to_record_batch::<Totals>(&too_wide),
+            Err(Error::IntegerOverflow)
+        ));
+    }
+
+    #[test]
+    fn unsupported_fields() {
+        #[derive(Deserialize, Serialize)]
+        struct Nested {
+            values: Vec<u32>,
+        }
+        #[derive(Deserialize, Serialize)]
+        struct Other {
+            missing: u8,
+        }
+        let encoded = to_bytes(&vec![Nested {
+            values: vec![1],
+        }])
+        .unwrap();
+        assert!(matches!(
+            to_record_batch::<Nested>(&encoded),
+            Err(Error::Arrow(ArrowError::NotYetImplemented(_)))
+        ));
+        assert!(matches!(
+            to_record_batch::<u32>(&[0]),
+            Err(Error::Arrow(ArrowError::NotYetImplemented(_)))
+        ));
+        let batch =
+            to_record_batch::<Record>(&to_bytes(&Vec::<Record>::new()).unwrap())
+                .unwrap();
+        assert!(matches!(
+            from_record_batch::<Other>(&batch),
+            Err(Error::Arrow(ArrowError::SchemaError(_)))
+        ));
+    }
+}
@@ -0,0 +1,322 @@
+use super::{
+    from_bytes,
+    Error,
+    IntCodec,
+    Result,
+    Serializer,
+    Varint,
+};
+use futures::io::{
+    AsyncRead,
+    AsyncReadExt,
+    AsyncWrite,
+    AsyncWriteExt,
+};
+
+fn truncated_at_eof(error: std::io::Error) -> Error {
+    if error.kind() == std::io::ErrorKind::UnexpectedEof {
+        Error::ValueTruncated
+    } else {
+        Error::Io(error)
+    }
+}
+
+/// This function is used to decode a value from an asynchronous reader,
+/// such as a socket, without blocking while the rest of the value arrives.
+/// As with [`from_reader`], strings and byte strings are copied rather than
+/// borrowed, so the value must own all its data.
+///
+/// The reader is one of the [`futures::io`] traits, which async-std, smol,
+/// and most other runtimes implement for their sockets and files, so this
+/// works under any of them.  Readers from tokio can be used with
+/// [`from_tokio_reader`] instead.
+///
+/// The value is read as a frame written by [`to_writer_async`], holding
+/// its encoding preceded by its length as a varint, the same as the frames
+/// of [`SerializationCodec`].  The whole frame is read before the value is
+/// decoded, so each byte is decoded only once however the frame arrives,
+/// and the reader is left just past the end of the frame, even if the
+/// value fails to decode, so a stream of values can be decoded by calling
+/// this again with the same reader.  The buffer for the frame grows as its
+/// bytes arrive, rather than all at once from its length, so a corrupted
+/// or hostile length cannot make this allocate more memory than the bytes
+/// actually sent.  The length is read a byte at a time, so sockets and
+/// files are best wrapped in a [`futures::io::BufReader`].
+///
+/// [`from_reader`]: fn.from_reader.html
+/// [`futures::io`]: https://docs.rs/futures/0.3/futures/io/index.html
+/// [`from_tokio_reader`]: fn.from_tokio_reader.html
+/// [`to_writer_async`]: fn.to_writer_async.html
+/// [`SerializationCodec`]: struct.SerializationCodec.html
+/// [`futures::io::BufReader`]: https://docs.rs/futures/0.3/futures/io/struct.BufReader.html
+///
+/// # Errors
+///
+/// Returns [`Error::ValueTruncated`] if the reader reaches its end before
+/// the frame does, [`Error::IntegerOverflow`] if the length of the frame
+/// is not a varint of at most ten bytes, or [`Error::Io`] if reading fails
+/// for any other reason, and otherwise any of the errors returned by
+/// [`from_bytes`].
+///
+/// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+/// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+/// [`Error::Io`]: enum.Error.html#variant.Io
+/// [`from_bytes`]: fn.from_bytes.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_reader_async,
+///     to_writer_async,
+/// };
+///
+/// # smol::block_on(async {
+/// let mut stream = Vec::new();
+/// to_writer_async(&mut stream, &(String::from("first"), 1_u8)).await.unwrap();
+/// to_writer_async(&mut stream, &(String::from("second"), 2_u8)).await.unwrap();
+/// let mut reader = smol::io::BufReader::new(&stream[..]);
+/// let first: (String, u8) = from_reader_async(&mut reader).await.unwrap();
+/// let second: (String, u8) = from_reader_async(&mut reader).await.unwrap();
+/// assert_eq!((String::from("first"), 1), first);
+/// assert_eq!((String::from("second"), 2), second);
+/// # });
+/// ```
+pub async fn from_reader_async<R, T>(reader: &mut R) -> Result<T>
+where
+    R: ?Sized + AsyncRead + Unpin,
+    T: serde::de::DeserializeOwned,
+{
+    // The length of any frame fits in the ten bytes a varint takes for
+    // `u64::MAX`, so reading stops there rather than taking padding without
+    // end.
+    let mut header = [0; 10];
+    let mut len = None;
+    for end in 1..=header.len() {
+        reader
+            .read_exact(&mut header[end - 1..end])
+            .await
+            .map_err(truncated_at_eof)?;
+        match Varint.decode_unsigned(&header[..end], u64::MAX) {
+            Ok((value, _)) => {
+                len = Some(value);
+                break;
+            },
+            Err(Error::ValueTruncated) => {},
+            Err(error) => return Err(error),
+        }
+    }
+    let len = len.ok_or(Error::IntegerOverflow)?;
+    let mut frame = Vec::new();
+    reader
+        .take(len)
+        .read_to_end(&mut frame)
+        .await
+        .map_err(Error::Io)?;
+    if (frame.len() as u64) < len {
+        return Err(Error::ValueTruncated);
+    }
+    from_bytes(&frame)
+}
+
+/// This function is used to encode a value and write it to an asynchronous
+/// writer, such as a socket, as a frame holding its encoding preceded by
+/// its length as a varint, which [`from_reader_async`] reads back.  The
+/// value is encoded in memory first, and then written all at once, so the
+/// writer is not polled while the value is being encoded.  The writer is
+/// not flushed.  As with [`from_reader_async`], the writer is one of the
+/// [`futures::io`] traits, and writers from tokio can be used with
+/// [`to_tokio_writer`] instead.
+///
+/// [`from_reader_async`]: fn.from_reader_async.html
+/// [`futures::io`]: https://docs.rs/futures/0.3/futures/io/index.html
+/// [`to_tokio_writer`]: fn.to_tokio_writer.html
+///
+/// # Errors
+///
+/// Returns [`Error::Io`] if the writer fails, and otherwise any of the
+/// errors returned by [`to_bytes`].  After an error writing, part of the
+/// frame may have been written.
+///
+/// [`Error::Io`]: enum.Error.html#variant.Io
+/// [`to_bytes`]: fn.to_bytes.html
+pub async fn to_writer_async<W, T>(
+    writer: &mut W,
+    value: &T,
+) -> Result<()>
+where
+    W: ?Sized + AsyncWrite + Unpin,
+    T: ?Sized + serde::Serialize,
+{
+    let mut frame = Vec::new();
+    value.serialize(&mut Serializer::new(&mut frame))?;
+    let mut buffer = Vec::with_capacity(frame.len() + 10);
+    Varint.encode_unsigned(frame.len() as u64, &mut buffer)?;
+    buffer.extend_from_slice(&frame);
+    writer.write_all(&buffer).await.map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_bytes;
+    use std::{
+        pin::Pin,
+        task::{
+            Context,
+            Poll,
+        },
+    };
+
+    // This reader hands out one byte per read, as a slow socket might.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl AsyncRead for Trickle<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _context: &mut Context<'_>,
+            buffer: &mut [u8],
+        ) -> Poll<std::io::Result<usize>> {
+            let len = buffer.len().min(self.0.len()).min(1);
+            buffer[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Poll::Ready(Ok(len))
+        }
+    }
+
+    fn frame<T>(value: &T) -> Vec<u8>
+    where
+        T: serde::Serialize,
+    {
+        let encoding = to_bytes(value).unwrap();
+        let mut frame = to_bytes(&(encoding.len() as u64)).unwrap();
+        frame.extend(encoding);
+        frame
+    }
+
+    #[test]
+    fn values_are_read_one_byte_at_a_time() {
+        smol::block_on(async {
+            let long = "x".repeat(1000);
+            let many = vec![300_u16; 20_000];
+            let mut stream = Vec::new();
+            assert!(to_writer_async(&mut stream, &(long.clone(), 7_u8))
+                .await
+                .is_ok());
+            assert!(to_writer_async(&mut stream, &many).await.is_ok());
+            assert!(to_writer_async(&mut stream, &9_u8).await.is_ok());
+            let mut expected = frame(&(long.clone(), 7_u8));
+            expected.extend(frame(&many));
+            expected.extend(frame(&9_u8));
+            assert_eq!(expected, stream);
+            // Each frame is read whole before it is decoded, so the long
+            // sequence is decoded once, rather than again after every byte.
+            let mut reader = Trickle(&stream);
+            let first =
+                from_reader_async::<_, (String, u8)>(&mut reader).await;
+            assert!(first.is_ok());
+            assert_eq!((long, 7), first.unwrap());
+            let second = from_reader_async::<_, Vec<u16>>(&mut reader).await;
+            assert!(second.is_ok());
+            assert_eq!(many, second.unwrap());
+            let third = from_reader_async::<_, u8>(&mut reader).await;
+            assert!(matches!(third, Ok(9)));
+            assert!(matches!(
+                from_reader_async::<_, u8>(&mut reader).await,
+                Err(Error::ValueTruncated)
+            ));
+        });
+    }
+
+    #[test]
+    fn errors() {
+        smol::block_on(async {
+            let encoding = frame(&String::from("truncated"));
+            let mut reader = &encoding[..5];
+            assert!(matches!(
+                from_reader_async::<_, String>(&mut reader).await,
+                Err(Error::ValueTruncated)
+            ));
+            // The length is not trusted to allocate the frame up front.
+            let mut reader = &[0xBF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F, 1][..];
+            assert!(matches!(
+                from_reader_async::<_, Vec<u8>>(&mut reader).await,
+                Err(Error::ValueTruncated)
+            ));
+            let mut reader = &[0x80; 11][..];
+            assert!(matches!(
+                from_reader_async::<_, u8>(&mut reader).await,
+                Err(Error::IntegerOverflow)
+            ));
+            // A frame which fails to decode is passed over, and the next one
+            // is decoded.
+            let mut reader = &[0x03, 0x84, 0x80, 0x00, 0x01, 0x09][..];
+            assert!(matches!(
+                from_reader_async::<_, u16>(&mut reader).await,
+                Err(Error::IntegerOverflow)
+            ));
+            assert!(matches!(
+                from_reader_async::<_, u16>(&mut reader).await,
+                Ok(9)
+            ));
+        });
+    }
+
+    #[test]
+    fn values_cross_async_std_sockets() {
+        async_std::task::block_on(async {
+            let listener =
+                async_std::net::TcpListener::bind("127.0.0.1:0").await;
+            assert!(listener.is_ok());
+            let listener = listener.unwrap();
+            let address = listener.local_addr().unwrap();
+            let sender = async_std::task::spawn(async move {
+                let mut stream =
+                    async_std::net::TcpStream::connect(address).await?;
+                for i in 0..100_u32 {
+                    to_writer_async(&mut stream, &(i, i.to_string())).await?;
+                }
+                Ok::<_, Error>(())
+            });
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut reader = async_std::io::BufReader::new(stream);
+            for i in 0..100_u32 {
+                let value =
+                    from_reader_async::<_, (u32, String)>(&mut reader).await;
+                assert!(value.is_ok());
+                assert_eq!((i, i.to_string()), value.unwrap());
+            }
+            assert!(sender.await.is_ok());
+            assert!(matches!(
+                from_reader_async::<_, u32>(&mut reader).await,
+                Err(Error::ValueTruncated)
+            ));
+        });
+    }
+
+    #[test]
+    fn values_cross_smol_sockets() {
+        smol::block_on(async {
+            let listener = smol::net::TcpListener::bind("127.0.0.1:0").await;
+            assert!(listener.is_ok());
+            let listener = listener.unwrap();
+            let address = listener.local_addr().unwrap();
+            let sender = smol::spawn(async move {
+                let mut stream = smol::net::TcpStream::connect(address).await?;
+                for i in 0..100_u32 {
+                    to_writer_async(&mut stream, &vec![i; 20]).await?;
+                }
+                Ok::<_, Error>(())
+            });
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut reader = smol::io::BufReader::new(stream);
+            for i in 0..100_u32 {
+                let value = from_reader_async::<_, Vec<u32>>(&mut reader).await;
+                assert!(value.is_ok());
+                assert_eq!(vec![i; 20], value.unwrap());
+            }
+            assert!(sender.await.is_ok());
+        });
+    }
+}
@@ -0,0 +1,327 @@
+use super::{
+    to_bytes,
+    to_value,
+    Result,
+    Value,
+};
+use std::convert::TryFrom;
+
+/// This is a reason the encoding of a value may differ from one run or
+/// machine to another, as found by [`audit_determinism`].
+///
+/// [`audit_determinism`]: fn.audit_determinism.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Nondeterminism {
+    /// The entries of a map are not in the order of their keys, as happens
+    /// with a `HashMap`, whose order depends on a random seed.
+    UnorderedMap {
+        /// This is where the map is within the value.
+        path: String,
+    },
+
+    /// A floating-point value is a NaN other than the canonical one, whose
+    /// bits can depend on the platform and the operations producing it.
+    NanPayload {
+        /// This is where the value is within the value being audited.
+        path: String,
+
+        /// These are the bits of the value.
+        bits: u64,
+    },
+
+    /// A sequence, map, string, or byte string is too long to be decoded
+    /// on platforms where `usize` has 32 bits.
+    LengthTooLarge {
+        /// This is where the container is within the value.
+        path: String,
+
+        /// This is the length of the container.
+        len: usize,
+    },
+
+    /// Encoding the value twice gave different bytes, such as when the
+    /// value holds state which changes when it is serialized.
+    UnstableEncoding,
+}
+
+impl std::fmt::Display for Nondeterminism {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match self {
+            Nondeterminism::UnorderedMap {
+                path,
+            } => write!(
+                f,
+                "{path}: map entries are not in order of their keys (use a \
+                 BTreeMap, or sort the entries)"
+            ),
+            Nondeterminism::NanPayload {
+                path,
+                bits,
+            } => write!(
+                f,
+                "{path}: NaN with non-canonical bits {bits:#X} (replace it \
+                 with the canonical NaN)"
+            ),
+            Nondeterminism::LengthTooLarge {
+                path,
+                len,
+            } => write!(
+                f,
+                "{path}: length {len} cannot be decoded where usize has 32 \
+                 bits"
+            ),
+            Nondeterminism::UnstableEncoding => {
+                write!(f, "value: encoding differs each time it is made")
+            },
+        }
+    }
+}
+
+struct Auditor {
+    findings: Vec<Nondeterminism>,
+}
+
+impl Auditor {
+    fn audit(
+        &mut self,
+        path: &str,
+        value: &Value,
+    ) {
+        let here = if path.is_empty() {
+            "value"
+        } else {
+            path
+        };
+        match value {
+            Value::F32(v)
+                if v.is_nan() && v.to_bits() != f32::NAN.to_bits() =>
+            {
+                self.findings.push(Nondeterminism::NanPayload {
+                    path: String::from(here),
+                    bits: u64::from(v.to_bits()),
+                });
+            },
+            Value::F64(v)
+                if v.is_nan() && v.to_bits() != f64::NAN.to_bits() =>
+            {
+                self.findings.push(Nondeterminism::NanPayload {
+                    path: String::from(here),
+                    bits: v.to_bits(),
+                });
+            },
+            Value::Str(v) => self.check_len(here, v.len()),
+            Value::Bytes(v) => self.check_len(here, v.len()),
+            Value::Option(Some(inner)) => self.audit(path, inner),
+            Value::Seq(elements) | Value::Tuple(elements) => {
+                if let Value::Seq(_) = value {
+                    self.check_len(here, elements.len());
+                }
+                for (i, element) in elements.iter().enumerate() {
+                    self.audit(&format!("{path}[{i}]"), element);
+                }
+            },
+            Value::Map(entries) => {
+                self.check_len(here, entries.len());
+                if entries.windows(2).any(|pair| pair[0].0 > pair[1].0) {
+                    self.findings.push(Nondeterminism::UnorderedMap {
+                        path: String::from(here),
+                    });
+                }
+                for (key, value) in entries {
+                    self.audit(&format!("{path}[{key}]"), value);
+                }
+            },
+            Value::Struct(fields) => {
+                for (name, value) in fields {
+                    self.audit(&format!("{path}.{name}"), value);
+                }
+            },
+            Value::Variant {
+                name,
+                value,
+                ..
+            } => self.audit(&format!("{path}::{name}"), value),
+            _ => {},
+        }
+    }
+
+    fn check_len(
+        &mut self,
+        path: &str,
+        len: usize,
+    ) {
+        if u32::try_from(len).is_err() {
+            self.findings.push(Nondeterminism::LengthTooLarge {
+                path: String::from(path),
+                len,
+            });
+        }
+    }
+}
+
+/// This function is used to look for reasons the encoding of the given
+/// value may not be the same on every run and every machine, for users who
+/// need byte-identical output, such as to compare hashes of encodings.
+/// Each finding names where in the value it was found, and its `Display`
+/// form suggests what to do about it.
+///
+/// Maps are checked for entries which are not in order of their keys,
+/// which catches `HashMap`.  Sets encode the same way as lists, so a
+/// `HashSet` cannot be told apart from a `Vec` whose order matters, and is
+/// not reported.  Lengths are encoded the same way on every platform, but
+/// those which do not fit in 32 bits are reported since they cannot be
+/// decoded everywhere.
+///
+/// # Errors
+///
+/// Any error from encoding the value is returned.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     audit_determinism,
+///     Nondeterminism,
+/// };
+/// use std::collections::{
+///     BTreeMap,
+///     HashMap,
+/// };
+///
+/// let scores: HashMap<String, u32> =
+///     (0..16).map(|i| (format!("player{i}"), i)).collect();
+/// let findings = audit_determinism(&scores).unwrap();
+/// assert_eq!(
+///     vec![Nondeterminism::UnorderedMap {
+///         path: String::from("value")
+///     }],
+///     findings
+/// );
+///
+/// let scores: BTreeMap<_, _> = scores.into_iter().collect();
+/// assert!(audit_determinism(&scores).unwrap().is_empty());
+/// ```
+pub fn audit_determinism<T>(value: &T) -> Result<Vec<Nondeterminism>>
+where
+    T: serde::Serialize,
+{
+    let mut auditor = Auditor {
+        findings: Vec::new(),
+    };
+    auditor.audit("", &to_value(value)?);
+    if to_bytes(value)? != to_bytes(value)? {
+        auditor.findings.push(Nondeterminism::UnstableEncoding);
+    }
+    Ok(auditor.findings)
+}
+
+#[cfg(all(test, feature = "float"))]
+mod tests {
+    use super::*;
+    use std::{
+        cell::Cell,
+        collections::BTreeMap,
+    };
+
+    #[derive(serde::Serialize)]
+    struct Reading {
+        sensor: String,
+        samples: Vec<f32>,
+        tags: Vec<(u8, f64)>,
+    }
+
+    // This type encodes differently each time it is serialized.
+    struct Counter(Cell<u8>);
+
+    impl serde::Serialize for Counter {
+        fn serialize<S>(
+            &self,
+            serializer: S,
+        ) -> std::result::Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            self.0.set(self.0.get() + 1);
+            serializer.serialize_u8(self.0.get())
+        }
+    }
+
+    #[test]
+    fn deterministic_values_have_no_findings() {
+        let mut map = BTreeMap::new();
+        map.insert(String::from("b10"), vec![f64::NAN]);
+        map.insert(String::from("b9"), vec![]);
+        map.insert(String::from("a"), vec![]);
+        let findings = audit_determinism(&map);
+        assert!(findings.is_ok());
+        assert!(findings.unwrap().is_empty());
+    }
+
+    #[test]
+    fn nan_payloads_are_located() {
+        let reading = Reading {
+            sensor: String::from("a"),
+            samples: vec![1.0, f32::from_bits(0x7FC0_0001)],
+            tags: vec![(1, f64::from_bits(0xFFF8_0000_0000_0000))],
+        };
+        let findings = audit_determinism(&reading).unwrap();
+        assert_eq!(
+            vec![
+                Nondeterminism::NanPayload {
+                    path: String::from(".samples[1]"),
+                    bits: 0x7FC0_0001,
+                },
+                Nondeterminism::NanPayload {
+                    path: String::from(".tags[0][1]"),
+                    bits: 0xFFF8_0000_0000_0000,
+                },
+            ],
+            findings
+        );
+        assert_eq!(
+            ".samples[1]: NaN with non-canonical bits 0x7FC00001 (replace it \
+             with the canonical NaN)",
+            findings[0].to_string()
+        );
+    }
+
+    #[test]
+    fn unordered_map_entries() {
+        let entries = vec![(300_u16, 1_u8), (2, 2)];
+        let findings = audit_determinism(&Wrapper(entries));
+        assert!(findings.is_ok());
+        assert_eq!(
+            vec![Nondeterminism::UnorderedMap {
+                path: String::from("value"),
+            }],
+            findings.unwrap()
+        );
+    }
+
+    // This serializes its entries as a map, in the order given.
+    struct Wrapper(Vec<(u16, u8)>);
+
+    impl serde::Serialize for Wrapper {
+        fn serialize<S>(
+            &self,
+            serializer: S,
+        ) -> std::result::Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            serializer.collect_map(self.0.iter().map(|(k, v)| (k, v)))
+        }
+    }
+
+    #[test]
+    fn unstable_encoding() {
+        let findings = audit_determinism(&Counter(Cell::new(0)));
+        assert!(findings.is_ok());
+        let findings = findings.unwrap();
+        assert_eq!(vec![Nondeterminism::UnstableEncoding], findings);
+    }
+}
@@ -0,0 +1,248 @@
+use std::{
+    convert::TryFrom,
+    fmt,
+    marker::PhantomData,
+};
+
+/// This is a vector holding at most `N` elements, for systems working
+/// within a fixed budget.  It is encoded just like a `Vec`, but decoding
+/// one fails, before any elements are decoded, if the encoding holds more
+/// than `N` elements, so a corrupt or hostile input cannot make the decoder
+/// allocate or decode more than the budget allows.
+///
+/// The same bound is put on an `ArrayVec` or a `SmallVec` by decoding it
+/// with [`with::arrayvec`] or [`with::smallvec`], when the `arrayvec` or
+/// `smallvec` feature is enabled.
+///
+/// [`with::arrayvec`]: with/arrayvec/index.html
+/// [`with::smallvec`]: with/smallvec/index.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_bytes,
+///     to_bytes,
+///     BoundedVec,
+/// };
+///
+/// let encoding = to_bytes(&vec![1_u8, 2, 3]).unwrap();
+/// let values: BoundedVec<u8, 4> = from_bytes(&encoding).unwrap();
+/// assert_eq!(&[1, 2, 3], values.as_slice());
+/// assert!(from_bytes::<BoundedVec<u8, 2>>(&encoding).is_err());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BoundedVec<T, const N: usize>(Vec<T>);
+
+impl<T, const N: usize> BoundedVec<T, N> {
+    /// This is the most elements the vector may hold.
+    pub const CAPACITY: usize = N;
+
+    /// Return the elements of the vector.
+    #[must_use]
+    pub fn as_slice(&self) -> &[T] {
+        &self.0
+    }
+
+    /// Return the elements of the vector, as a `Vec`.
+    #[must_use]
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+
+    /// Determine whether or not the vector holds no elements.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Determine whether or not the vector holds as many elements as it
+    /// may.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.0.len() >= N
+    }
+
+    /// Return the number of elements in the vector.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Return a new, empty vector.
+    #[must_use]
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    /// This function is used to add the given value to the end of the
+    /// vector.
+    ///
+    /// # Errors
+    ///
+    /// The value is returned back if the vector is already full.
+    pub fn try_push(
+        &mut self,
+        value: T,
+    ) -> Result<(), T> {
+        if self.is_full() {
+            Err(value)
+        } else {
+            self.0.push(value);
+            Ok(())
+        }
+    }
+}
+
+impl<T, const N: usize> Default for BoundedVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> std::ops::Deref for BoundedVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        &self.0
+    }
+}
+
+impl<T, const N: usize> TryFrom<Vec<T>> for BoundedVec<T, N> {
+    type Error = Vec<T>;
+
+    fn try_from(values: Vec<T>) -> Result<Self, Vec<T>> {
+        if values.len() > N {
+            Err(values)
+        } else {
+            Ok(Self(values))
+        }
+    }
+}
+
+impl<T, const N: usize> serde::Serialize for BoundedVec<T, N>
+where
+    T: serde::Serialize,
+{
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_seq(&self.0)
+    }
+}
+
+impl<'de, T, const N: usize> serde::Deserialize<'de> for BoundedVec<T, N>
+where
+    T: serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor<T, const N: usize>(PhantomData<T>);
+
+        impl<'de, T, const N: usize> serde::de::Visitor<'de> for Visitor<T, N>
+        where
+            T: serde::Deserialize<'de>,
+        {
+            type Value = BoundedVec<T, N>;
+
+            fn expecting(
+                &self,
+                f: &mut fmt::Formatter,
+            ) -> fmt::Result {
+                write!(f, "a sequence of at most {N} elements")
+            }
+
+            fn visit_seq<A>(
+                self,
+                mut seq: A,
+            ) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                // Refuse an encoding which declares too many elements
+                // before decoding any of them.
+                if let Some(len) = seq.size_hint() {
+                    if len > N {
+                        return Err(serde::de::Error::invalid_length(
+                            len, &self,
+                        ));
+                    }
+                }
+                let mut values =
+                    Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(value) = seq.next_element()? {
+                    if values.len() == N {
+                        return Err(serde::de::Error::invalid_length(
+                            N + 1,
+                            &self,
+                        ));
+                    }
+                    values.push(value);
+                }
+                Ok(BoundedVec(values))
+            }
+        }
+
+        deserializer.deserialize_seq(Visitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        from_bytes,
+        json,
+        to_bytes,
+        Error,
+    };
+
+    #[test]
+    fn decode_within_capacity() {
+        let decoded = from_bytes::<BoundedVec<u16, 3>>(&[3, 1, 2, 0x82, 0x00]);
+        assert!(decoded.is_ok());
+        let decoded = decoded.unwrap();
+        assert_eq!(&[1, 2, 256], decoded.as_slice());
+        assert!(decoded.is_full());
+        assert_eq!(vec![3, 1, 2, 0x82, 0x00], to_bytes(&decoded).unwrap());
+    }
+
+    #[test]
+    fn decode_over_capacity() {
+        // The count alone is enough to refuse the encoding, even though the
+        // elements are missing.
+        let decoded = from_bytes::<BoundedVec<u8, 3>>(&[0x83, 0x00]);
+        assert!(matches!(
+            decoded,
+            Err(Error::Message(message))
+                if message == "invalid length 384, expected a sequence of at most 3 elements"
+        ));
+
+        // Formats which do not declare the number of elements up front are
+        // stopped once one element too many is found.
+        let decoded = json::from_slice::<BoundedVec<u8, 2>>(b"[1,2,3]");
+        assert!(decoded.is_err());
+        let decoded = json::from_slice::<BoundedVec<u8, 3>>(b"[1,2,3]");
+        assert!(decoded.is_ok());
+    }
+
+    #[test]
+    fn push_within_capacity() {
+        let mut values = BoundedVec::<char, 2>::new();
+        assert_eq!(Ok(()), values.try_push('a'));
+        assert_eq!(Ok(()), values.try_push('b'));
+        assert_eq!(Err('c'), values.try_push('c'));
+        assert_eq!(vec!['a', 'b'], values.into_inner());
+        assert_eq!(
+            Err(vec![1, 2, 3]),
+            BoundedVec::<u8, 2>::try_from(vec![1, 2, 3])
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use super::{
+    from_bytes,
+    Result,
+};
+
+/// This is implemented by the messages received from message brokers, to
+/// decode their payloads without taking the bytes out first at every place
+/// messages are consumed.  With the `rdkafka` feature enabled, it is
+/// implemented for the messages of [`rdkafka`], and with the `lapin`
+/// feature enabled, for the deliveries of [`lapin`].
+///
+/// Nothing is needed to publish values, since the `Vec<u8>` returned by
+/// [`to_bytes`] is accepted as a payload by both clients as it is.
+///
+/// [`rdkafka`]: https://docs.rs/rdkafka/0.36
+/// [`lapin`]: https://docs.rs/lapin/2
+/// [`to_bytes`]: fn.to_bytes.html
+pub trait MessagePayload {
+    /// Return the bytes of the message's payload, or `None` if it has no
+    /// payload, such as a Kafka tombstone.
+    fn payload_bytes(&self) -> Option<&[u8]>;
+
+    /// Decode a value from the message's payload, or return `None` if it
+    /// has no payload.  Strings and byte strings may be borrowed from the
+    /// message.
+    ///
+    /// # Errors
+    ///
+    /// Any of the errors returned by [`from_bytes`] may be returned.
+    ///
+    /// [`from_bytes`]: fn.from_bytes.html
+    fn deserialize_payload<'a, T>(&'a self) -> Result<Option<T>>
+    where
+        T: serde::Deserialize<'a>,
+    {
+        self.payload_bytes().map(from_bytes).transpose()
+    }
+}
+
+#[cfg(feature = "rdkafka")]
+impl MessagePayload for rdkafka::message::BorrowedMessage<'_> {
+    fn payload_bytes(&self) -> Option<&[u8]> {
+        rdkafka::Message::payload(self)
+    }
+}
+
+#[cfg(feature = "rdkafka")]
+impl MessagePayload for rdkafka::message::OwnedMessage {
+    fn payload_bytes(&self) -> Option<&[u8]> {
+        rdkafka::Message::payload(self)
+    }
+}
+
+#[cfg(feature = "lapin")]
+impl MessagePayload for lapin::message::Delivery {
+    fn payload_bytes(&self) -> Option<&[u8]> {
+        Some(&self.data)
+    }
+}
+
+#[cfg(all(test, feature = "rdkafka"))]
+mod tests {
+    use super::*;
+    use crate::{
+        to_bytes,
+        Error,
+    };
+
+    #[test]
+    fn kafka_payloads() {
+        let message = |payload| {
+            rdkafka::message::OwnedMessage::new(
+                payload,
+                None,
+                String::from("events"),
+                rdkafka::Timestamp::NotAvailable,
+                0,
+                0,
+                None,
+            )
+        };
+        let value = (String::from("sensor"), 1000_u16);
+        let received = message(Some(to_bytes(&value).unwrap()));
+        let decoded = received.deserialize_payload::<(&str, u16)>();
+        assert!(decoded.is_ok());
+        assert_eq!(Some(("sensor", 1000)), decoded.unwrap());
+        let tombstone = message(None);
+        assert!(matches!(tombstone.deserialize_payload::<u8>(), Ok(None)));
+        let truncated = message(Some(vec![0x05, b's']));
+        assert!(matches!(
+            truncated.deserialize_payload::<String>(),
+            Err(Error::ValueTruncated)
+        ));
+    }
+}
@@ -0,0 +1,367 @@
+use super::{
+    to_bytes,
+    Error,
+    Result,
+};
+use std::{
+    collections::VecDeque,
+    future::poll_fn,
+    sync::{
+        Arc,
+        Mutex,
+        MutexGuard,
+    },
+    task::{
+        Context,
+        Poll,
+        Waker,
+    },
+};
+
+// This is the state shared by the two ends of a frame channel.
+struct Shared {
+    frames: VecDeque<Vec<u8>>,
+    queued_bytes: usize,
+    high_watermark: usize,
+    senders: usize,
+    receiver_alive: bool,
+    blocked_senders: Vec<Waker>,
+    blocked_receiver: Option<Waker>,
+}
+
+fn lock(shared: &Mutex<Shared>) -> MutexGuard<'_, Shared> {
+    // The state is never left inconsistent while locked, so it remains
+    // usable even if a thread panicked while holding the lock.
+    shared.lock().unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// This is the sending end of a channel made by [`frame_channel`], which
+/// encodes values into frames and queues them for the receiving end.
+/// Senders may be cloned so that several producers can share a channel.
+///
+/// [`frame_channel`]: fn.frame_channel.html
+pub struct FrameSender {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl FrameSender {
+    /// Return the number of bytes in frames queued in the channel and not
+    /// yet received.
+    #[must_use]
+    pub fn queued_bytes(&self) -> usize {
+        lock(&self.shared).queued_bytes
+    }
+
+    /// This function is used to encode the given value into a frame and
+    /// queue it for the receiving end of the channel.  If the frames
+    /// already queued reach the high watermark of the channel, the returned
+    /// future waits until enough of them have been received to bring the
+    /// queue back below the watermark.  The value is encoded before
+    /// waiting, so the encoding work of a producer overlaps the draining
+    /// of the queue.
+    ///
+    /// # Errors
+    ///
+    /// Any of the errors returned by [`to_bytes`] may be returned from
+    /// encoding the value.  [`Error::Io`] is returned, with the kind
+    /// `BrokenPipe`, if the receiving end of the channel has been dropped.
+    ///
+    /// [`to_bytes`]: fn.to_bytes.html
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    pub async fn send<T>(
+        &self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        let frame = to_bytes(value)?;
+        let mut frame = Some(frame);
+        poll_fn(|context| self.poll_send(context, &mut frame)).await
+    }
+
+    fn poll_send(
+        &self,
+        context: &mut Context<'_>,
+        frame: &mut Option<Vec<u8>>,
+    ) -> Poll<Result<()>> {
+        let mut shared = lock(&self.shared);
+        if !shared.receiver_alive {
+            return Poll::Ready(Err(Error::Io(
+                std::io::ErrorKind::BrokenPipe.into(),
+            )));
+        }
+
+        // A frame is always accepted into an empty queue, so that frames
+        // larger than the high watermark can still be sent.
+        if !shared.frames.is_empty()
+            && shared.queued_bytes >= shared.high_watermark
+        {
+            shared.blocked_senders.push(context.waker().clone());
+            return Poll::Pending;
+        }
+        if let Some(frame) = frame.take() {
+            shared.queued_bytes += frame.len();
+            shared.frames.push_back(frame);
+        }
+        if let Some(receiver) = shared.blocked_receiver.take() {
+            receiver.wake();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Clone for FrameSender {
+    fn clone(&self) -> Self {
+        lock(&self.shared).senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for FrameSender {
+    fn drop(&mut self) {
+        let mut shared = lock(&self.shared);
+        shared.senders -= 1;
+        if shared.senders == 0 {
+            if let Some(receiver) = shared.blocked_receiver.take() {
+                receiver.wake();
+            }
+        }
+    }
+}
+
+/// This is the receiving end of a channel made by [`frame_channel`], from
+/// which the frames queued by the sending ends are taken, in order, to be
+/// written to a socket, file, or other consumer.
+///
+/// [`frame_channel`]: fn.frame_channel.html
+pub struct FrameReceiver {
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl FrameReceiver {
+    fn poll_recv(
+        &self,
+        context: &mut Context<'_>,
+    ) -> Poll<Option<Vec<u8>>> {
+        let mut shared = lock(&self.shared);
+        if let Some(frame) = shared.frames.pop_front() {
+            shared.queued_bytes -= frame.len();
+            if shared.queued_bytes < shared.high_watermark {
+                for sender in shared.blocked_senders.drain(..) {
+                    sender.wake();
+                }
+            }
+            Poll::Ready(Some(frame))
+        } else if shared.senders == 0 {
+            Poll::Ready(None)
+        } else {
+            shared.blocked_receiver = Some(context.waker().clone());
+            Poll::Pending
+        }
+    }
+
+    /// Return the number of bytes in frames queued in the channel and not
+    /// yet received.
+    #[must_use]
+    pub fn queued_bytes(&self) -> usize {
+        lock(&self.shared).queued_bytes
+    }
+
+    /// This function is used to take the next frame queued in the channel,
+    /// waiting for one to be sent if the queue is empty.  The future
+    /// returns `None` once the queue is empty and every sending end of the
+    /// channel has been dropped.
+    pub async fn recv(&self) -> Option<Vec<u8>> {
+        poll_fn(|context| self.poll_recv(context)).await
+    }
+}
+
+impl Drop for FrameReceiver {
+    fn drop(&mut self) {
+        let mut shared = lock(&self.shared);
+        shared.receiver_alive = false;
+        for sender in shared.blocked_senders.drain(..) {
+            sender.wake();
+        }
+    }
+}
+
+/// This function is used to make a channel through which values, encoded
+/// into frames, are passed from asynchronous producers to an asynchronous
+/// consumer.  The channel exerts backpressure on producers: once the
+/// frames queued in the channel add up to at least the given high
+/// watermark, in bytes, sending waits until the consumer catches up, rather
+/// than letting encoded frames pile up in memory without bound.
+///
+/// The channel is built only on the `Future` and `Waker` types of the
+/// standard library, so it works the same under any async runtime
+/// (tokio, async-std, smol, or an executor of one's own), and no feature
+/// needs to be enabled to use it with any of them.
+///
+/// The channel only connects threads of one process.  Values are passed
+/// between processes through memory they share with an [`ShmSender`] and
+/// [`ShmReceiver`], when the `memmap2` feature is enabled.
+///
+/// [`ShmSender`]: struct.ShmSender.html
+/// [`ShmReceiver`]: struct.ShmReceiver.html
+///
+/// # Examples
+///
+/// Here, values are produced on a task of async-std and consumed on
+/// another, driven by smol.
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     frame_channel,
+///     from_bytes,
+/// };
+///
+/// let (sender, receiver) = frame_channel(4096);
+/// let producer = async_std::task::spawn(async move {
+///     for i in 0..100_u32 {
+///         sender.send(&i).await.unwrap();
+///     }
+/// });
+/// let total = smol::block_on(async {
+///     let mut total = 0;
+///     while let Some(frame) = receiver.recv().await {
+///         total += from_bytes::<u32>(&frame).unwrap();
+///     }
+///     total
+/// });
+/// async_std::task::block_on(producer);
+/// assert_eq!(4950, total);
+/// ```
+#[must_use]
+pub fn frame_channel(high_watermark: usize) -> (FrameSender, FrameReceiver) {
+    let shared = Arc::new(Mutex::new(Shared {
+        frames: VecDeque::new(),
+        queued_bytes: 0,
+        high_watermark,
+        senders: 1,
+        receiver_alive: true,
+        blocked_senders: Vec::new(),
+        blocked_receiver: None,
+    }));
+    (
+        FrameSender {
+            shared: shared.clone(),
+        },
+        FrameReceiver {
+            shared,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_bytes;
+    use std::{
+        future::Future,
+        sync::atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+        task::Wake,
+    };
+
+    #[derive(Default)]
+    struct CountingWaker(AtomicUsize);
+
+    impl Wake for CountingWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn poll<F>(
+        future: &mut std::pin::Pin<Box<F>>,
+        waker: &Arc<CountingWaker>,
+    ) -> Poll<F::Output>
+    where
+        F: Future,
+    {
+        let waker = Waker::from(waker.clone());
+        future.as_mut().poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn frames_are_received_in_order() {
+        let (sender, receiver) = frame_channel(1024);
+        let waker = Arc::new(CountingWaker::default());
+        for value in &[1_u16, 300, 7] {
+            let mut send = Box::pin(sender.send(value));
+            assert!(matches!(poll(&mut send, &waker), Poll::Ready(Ok(()))));
+        }
+        assert_eq!(4, receiver.queued_bytes());
+        drop(sender);
+        let mut values = Vec::new();
+        loop {
+            let mut recv = Box::pin(receiver.recv());
+            match poll(&mut recv, &waker) {
+                Poll::Ready(Some(frame)) => {
+                    values.push(from_bytes::<u16>(&frame).unwrap());
+                },
+                Poll::Ready(None) => break,
+                Poll::Pending => panic!("receiver blocked with {:?}", values),
+            }
+        }
+        assert_eq!(vec![1, 300, 7], values);
+    }
+
+    #[test]
+    fn senders_wait_at_high_watermark() {
+        let (sender, receiver) = frame_channel(8);
+        let sender_waker = Arc::new(CountingWaker::default());
+        let receiver_waker = Arc::new(CountingWaker::default());
+        let mut recv = Box::pin(receiver.recv());
+        assert!(poll(&mut recv, &receiver_waker).is_pending());
+
+        // The first frame is accepted even though it exceeds the watermark.
+        let mut first = Box::pin(sender.send(&"a long first frame"));
+        assert!(matches!(poll(&mut first, &sender_waker), Poll::Ready(Ok(()))));
+        assert_eq!(1, receiver_waker.0.load(Ordering::SeqCst));
+        let mut second = Box::pin(sender.send(&"second"));
+        assert!(poll(&mut second, &sender_waker).is_pending());
+        assert_eq!(0, sender_waker.0.load(Ordering::SeqCst));
+
+        // Receiving the first frame makes room for the second.
+        match poll(&mut recv, &receiver_waker) {
+            Poll::Ready(Some(frame)) => {
+                assert_eq!(
+                    "a long first frame",
+                    from_bytes::<&str>(&frame).unwrap()
+                );
+            },
+            _ => panic!("first frame not received"),
+        }
+        assert_eq!(1, sender_waker.0.load(Ordering::SeqCst));
+        assert!(matches!(
+            poll(&mut second, &sender_waker),
+            Poll::Ready(Ok(()))
+        ));
+        assert_eq!(7, receiver.queued_bytes());
+    }
+
+    #[test]
+    fn send_fails_without_receiver() {
+        let (sender, receiver) = frame_channel(8);
+        let waker = Arc::new(CountingWaker::default());
+        let mut first = Box::pin(sender.send(&[0_u8; 16]));
+        assert!(matches!(poll(&mut first, &waker), Poll::Ready(Ok(()))));
+        let mut second = Box::pin(sender.send(&5_u8));
+        assert!(poll(&mut second, &waker).is_pending());
+        drop(receiver);
+        assert_eq!(1, waker.0.load(Ordering::SeqCst));
+        assert!(matches!(
+            poll(&mut second, &waker),
+            Poll::Ready(Err(Error::Io(error)))
+                if error.kind() == std::io::ErrorKind::BrokenPipe
+        ));
+    }
+}
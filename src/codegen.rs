@@ -0,0 +1,720 @@
+use super::{
+    Error,
+    Result,
+    Schema,
+    VariantKind,
+};
+use std::{
+    collections::HashSet,
+    fmt::Write,
+};
+
+// This is the path, as seen from generated code, of the functions which
+// generated code calls to encode and decode primitive values.
+const RUNTIME: &str = "::serialization::__private::codegen";
+
+// These are the keywords which can't be used as plain identifiers.
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else",
+    "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop",
+    "match", "mod", "move", "mut", "pub", "ref", "return", "static", "struct",
+    "trait", "true", "try", "type", "unsafe", "use", "where", "while",
+];
+
+/// This type generates Rust source code for functions which encode and
+/// decode values of specific types, given their [`Schema`]s.  The generated
+/// functions work directly on the values, producing the same encoding as
+/// [`to_bytes`] and [`from_bytes`], but without going through the `serde`
+/// traits, so each one compiles to straight-line code specialized for its
+/// type.  This can pay off for hot protocols where the cost of the `serde`
+/// machinery itself shows up in profiles.
+///
+/// For each named type `Name` the generated code has a pair of functions:
+///
+/// * `encode_name(value: &Name, out: &mut Vec<u8>)`
+/// * `decode_name(de: &mut Deserializer) -> Result<Name>`
+///
+/// The source is typically generated by a build script and brought into a
+/// module with `include!`.  The module must have every named type in scope
+/// under the name by which `serde` knows it, and those types must not be
+/// generic.  Fields skipped by `serde` are not supported.
+///
+/// [`Schema`]: enum.Schema.html
+/// [`to_bytes`]: fn.to_bytes.html
+/// [`from_bytes`]: fn.from_bytes.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     Codegen,
+///     Schema,
+/// };
+///
+/// #[derive(serde::Deserialize)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// let mut codegen = Codegen::new();
+/// codegen.add(&Schema::of::<Point>().unwrap()).unwrap();
+/// let source = codegen.finish();
+/// assert!(source.contains("pub fn encode_point("));
+/// assert!(source.contains("pub fn decode_point("));
+/// ```
+#[derive(Debug, Default)]
+pub struct Codegen {
+    generated: HashSet<&'static str>,
+    pending: Vec<Schema>,
+    source: String,
+    variables: usize,
+}
+
+impl Codegen {
+    /// Generate the functions for encoding and decoding the type described
+    /// by the given schema, along with those for any other named types it
+    /// contains.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Message`] if the schema does not describe a named
+    /// type (a struct or enum), since the generated functions are named
+    /// after the types they handle.
+    ///
+    /// [`Error::Message`]: enum.Error.html#variant.Message
+    pub fn add(
+        &mut self,
+        schema: &Schema,
+    ) -> Result<&mut Self> {
+        if schema.name().is_none() {
+            return Err(Error::Message(String::from(
+                "only named types can have codecs generated",
+            )));
+        }
+        self.pending.push(schema.clone());
+        while let Some(schema) = self.pending.pop() {
+            if let Some(name) = schema.name() {
+                if self.generated.insert(name) {
+                    self.generate(name, &schema);
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    fn decode(
+        &mut self,
+        schema: &Schema,
+    ) -> String {
+        match schema {
+            Schema::Bool => format!("{RUNTIME}::read_bool(de)?"),
+            Schema::I8 => format!("{RUNTIME}::read_i8(de)?"),
+            Schema::I16 => format!("{RUNTIME}::read_i16(de)?"),
+            Schema::I32 => format!("{RUNTIME}::read_i32(de)?"),
+            Schema::I64 => format!("{RUNTIME}::read_i64(de)?"),
+            Schema::I128 => format!("{RUNTIME}::read_i128(de)?"),
+            Schema::U8 => format!("{RUNTIME}::read_u8(de)?"),
+            Schema::U16 => format!("{RUNTIME}::read_u16(de)?"),
+            Schema::U32 => format!("{RUNTIME}::read_u32(de)?"),
+            Schema::U64 => format!("{RUNTIME}::read_u64(de)?"),
+            Schema::U128 => format!("{RUNTIME}::read_u128(de)?"),
+            Schema::F32 => format!("{RUNTIME}::read_f32(de)?"),
+            Schema::F64 => format!("{RUNTIME}::read_f64(de)?"),
+            Schema::Char => format!("{RUNTIME}::read_char(de)?"),
+            Schema::Str => format!("{RUNTIME}::read_str(de)?.into()"),
+            Schema::Bytes => format!("{RUNTIME}::read_bytes(de)?.into()"),
+            Schema::Option(inner) => format!(
+                "if {}::read_option(de)? {{ Some({}) }} else {{ None }}",
+                RUNTIME,
+                self.decode(inner)
+            ),
+            Schema::Unit => String::from("()"),
+            Schema::Seq(element) => format!(
+                "{{ let len = {}::read_len(de)?; (0..len).map(|_| -> ::serialization::Result<_> {{ Ok({}) }}).collect::<::serialization::Result<_>>()? }}",
+                RUNTIME,
+                self.decode(element)
+            ),
+            Schema::Tuple(elements) => format!(
+                "({},)",
+                elements
+                    .iter()
+                    .map(|element| self.decode(element))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Schema::Array(elements) => format!(
+                "[{}]",
+                elements
+                    .iter()
+                    .map(|element| self.decode(element))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Schema::Map {
+                key,
+                value,
+            } => format!(
+                "{{ let len = {}::read_len(de)?; (0..len).map(|_| -> ::serialization::Result<_> {{ Ok(({}, {})) }}).collect::<::serialization::Result<_>>()? }}",
+                RUNTIME,
+                self.decode(key),
+                self.decode(value)
+            ),
+            Schema::UnitStruct(name)
+            | Schema::NewtypeStruct {
+                name,
+                ..
+            }
+            | Schema::TupleStruct {
+                name,
+                ..
+            }
+            | Schema::Struct {
+                name,
+                ..
+            }
+            | Schema::Enum {
+                name,
+                ..
+            }
+            | Schema::Recursive(name) => {
+                self.pending.push(schema.clone());
+                format!("decode_{}(de)?.into()", snake_case(name))
+            },
+        }
+    }
+
+    #[allow(clippy::too_many_lines)]
+    fn encode(
+        &mut self,
+        schema: &Schema,
+        value: &str,
+        depth: usize,
+    ) -> String {
+        let indent = "    ".repeat(depth);
+        match schema {
+            Schema::Bool => primitive(&indent, "bool", value),
+            Schema::I8 => primitive(&indent, "i8", value),
+            Schema::I16 => primitive(&indent, "i16", value),
+            Schema::I32 => primitive(&indent, "i32", value),
+            Schema::I64 => primitive(&indent, "i64", value),
+            Schema::I128 => primitive(&indent, "i128", value),
+            Schema::U8 => primitive(&indent, "u8", value),
+            Schema::U16 => primitive(&indent, "u16", value),
+            Schema::U32 => primitive(&indent, "u32", value),
+            Schema::U64 => primitive(&indent, "u64", value),
+            Schema::U128 => primitive(&indent, "u128", value),
+            Schema::F32 => primitive(&indent, "f32", value),
+            Schema::F64 => primitive(&indent, "f64", value),
+            Schema::Char => primitive(&indent, "char", value),
+            Schema::Str => {
+                format!("{indent}{RUNTIME}::write_str(out, {value});\n")
+            },
+            Schema::Bytes => {
+                format!("{indent}{RUNTIME}::write_bytes(out, {value});\n")
+            },
+            Schema::Option(inner) => {
+                let variable = self.variable();
+                format!(
+                    "{0}match {1} {{\n{0}    Some({2}) => {{\n{0}        {3}::write_option(out, true);\n{4}{0}    }},\n{0}    None => {3}::write_option(out, false),\n{0}}}\n",
+                    indent,
+                    value,
+                    variable,
+                    RUNTIME,
+                    self.encode(inner, &variable, depth + 2)
+                )
+            },
+            Schema::Unit => String::new(),
+            Schema::Seq(element) => {
+                let collection = self.variable();
+                let variable = self.variable();
+                format!(
+                    "{0}let {1} = {2};\n{0}{3}::write_len(out, {1}.len());\n{0}for {4} in {1} {{\n{5}{0}}}\n",
+                    indent,
+                    collection,
+                    value,
+                    RUNTIME,
+                    variable,
+                    self.encode(element, &variable, depth + 1)
+                )
+            },
+            Schema::Tuple(elements) => {
+                let tuple = self.variable();
+                let mut code = format!("{indent}let {tuple} = {value};\n");
+                for (index, element) in elements.iter().enumerate() {
+                    code += &self.encode(
+                        element,
+                        &format!("&{tuple}.{index}"),
+                        depth,
+                    );
+                }
+                code
+            },
+            Schema::Array(elements) => {
+                let array = self.variable();
+                let mut code = format!("{indent}let {array} = {value};\n");
+                for (index, element) in elements.iter().enumerate() {
+                    code += &self.encode(
+                        element,
+                        &format!("&{array}[{index}]"),
+                        depth,
+                    );
+                }
+                code
+            },
+            Schema::Map {
+                key,
+                value: element,
+            } => {
+                let collection = self.variable();
+                let key_variable = self.variable();
+                let variable = self.variable();
+                format!(
+                    "{0}let {1} = {2};\n{0}{3}::write_len(out, {1}.len());\n{0}for ({4}, {5}) in {1} {{\n{6}{7}{0}}}\n",
+                    indent,
+                    collection,
+                    value,
+                    RUNTIME,
+                    key_variable,
+                    variable,
+                    self.encode(key, &key_variable, depth + 1),
+                    self.encode(element, &variable, depth + 1)
+                )
+            },
+            Schema::UnitStruct(name)
+            | Schema::NewtypeStruct {
+                name,
+                ..
+            }
+            | Schema::TupleStruct {
+                name,
+                ..
+            }
+            | Schema::Struct {
+                name,
+                ..
+            }
+            | Schema::Enum {
+                name,
+                ..
+            }
+            | Schema::Recursive(name) => {
+                self.pending.push(schema.clone());
+                format!(
+                    "{}encode_{}({}, out);\n",
+                    indent,
+                    snake_case(name),
+                    value
+                )
+            },
+        }
+    }
+
+    /// Return the source code generated so far.
+    #[must_use]
+    pub fn finish(self) -> String {
+        self.source
+    }
+
+    fn generate(
+        &mut self,
+        name: &str,
+        schema: &Schema,
+    ) {
+        let (encode, decode) = match schema {
+            Schema::UnitStruct(_) => {
+                (String::from("    let _ = value;\n"), format!("Ok({name})"))
+            },
+            Schema::NewtypeStruct {
+                inner,
+                ..
+            } => (
+                self.encode(inner, "&value.0", 1),
+                format!("Ok({}({}))", name, self.decode(inner)),
+            ),
+            Schema::TupleStruct {
+                fields,
+                ..
+            } => {
+                let mut encode = String::new();
+                let mut decode = Vec::new();
+                for (index, field) in fields.iter().enumerate() {
+                    encode +=
+                        &self.encode(field, &format!("&value.{index}"), 1);
+                    decode.push(self.decode(field));
+                }
+                (encode, format!("Ok({}({}))", name, decode.join(", ")))
+            },
+            Schema::Struct {
+                fields,
+                ..
+            } => {
+                let mut encode = String::new();
+                let mut decode = Vec::new();
+                for field in fields {
+                    let field_name = identifier(field.name);
+                    encode += &self.encode(
+                        &field.schema,
+                        &format!("&value.{field_name}"),
+                        1,
+                    );
+                    decode.push(format!(
+                        "{}: {}",
+                        field_name,
+                        self.decode(&field.schema)
+                    ));
+                }
+                (encode, format!("Ok({} {{ {} }})", name, decode.join(", ")))
+            },
+            Schema::Enum {
+                variants,
+                ..
+            } => {
+                let mut encode = String::from("    match value {\n");
+                let mut decode = format!("match {RUNTIME}::read_len(de)? {{ ");
+                for (index, variant) in variants.iter().enumerate() {
+                    let path =
+                        format!("{}::{}", name, identifier(variant.name));
+                    let (pattern, body, construct) =
+                        self.generate_variant(&path, &variant.kind);
+                    let _ = write!(
+                        encode,
+                        "        {pattern} => {{\n            {RUNTIME}::write_len(out, {index});\n{body}        }},\n"
+                    );
+                    let _ = write!(decode, "{index} => Ok({construct}), ");
+                }
+                encode += "    }\n";
+                let _ = write!(
+                    decode,
+                    "index => Err({RUNTIME}::unknown_variant(\"{name}\", index)) }}"
+                );
+                (encode, decode)
+            },
+            _ => unreachable!("only named types are generated"),
+        };
+        let snake = snake_case(name);
+        let _ = write!(
+            self.source,
+            "#[allow(clippy::all, unused_variables)]\npub fn encode_{snake}(\n    value: &{name},\n    out: &mut Vec<u8>,\n) {{\n{encode}}}\n\n#[allow(clippy::all, unused_variables)]\npub fn decode_{snake}(\n    de: &mut ::serialization::Deserializer<'_>,\n) -> ::serialization::Result<{name}> {{\n    {decode}\n}}\n\n"
+        );
+    }
+
+    fn generate_variant(
+        &mut self,
+        path: &str,
+        kind: &VariantKind,
+    ) -> (String, String, String) {
+        match kind {
+            VariantKind::Unit => {
+                (path.to_string(), String::new(), path.to_string())
+            },
+            VariantKind::Newtype(inner) => (
+                format!("{path}(f0)"),
+                self.encode(inner, "f0", 3),
+                format!("{}({})", path, self.decode(inner)),
+            ),
+            VariantKind::Tuple(fields) => {
+                let mut bindings = Vec::new();
+                let mut body = String::new();
+                let mut decode = Vec::new();
+                for (index, field) in fields.iter().enumerate() {
+                    let binding = format!("f{index}");
+                    body += &self.encode(field, &binding, 3);
+                    decode.push(self.decode(field));
+                    bindings.push(binding);
+                }
+                (
+                    format!("{}({})", path, bindings.join(", ")),
+                    body,
+                    format!("{}({})", path, decode.join(", ")),
+                )
+            },
+            VariantKind::Struct(fields) => {
+                let mut bindings = Vec::new();
+                let mut body = String::new();
+                let mut decode = Vec::new();
+                for (index, field) in fields.iter().enumerate() {
+                    let field_name = identifier(field.name);
+                    let binding = format!("f{index}");
+                    body += &self.encode(&field.schema, &binding, 3);
+                    decode.push(format!(
+                        "{}: {}",
+                        field_name,
+                        self.decode(&field.schema)
+                    ));
+                    bindings.push(format!("{field_name}: {binding}"));
+                }
+                (
+                    format!("{} {{ {} }}", path, bindings.join(", ")),
+                    body,
+                    format!("{} {{ {} }}", path, decode.join(", ")),
+                )
+            },
+        }
+    }
+
+    /// Return a new code generator with nothing generated so far.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn variable(&mut self) -> String {
+        self.variables += 1;
+        format!("v{}", self.variables)
+    }
+}
+
+fn identifier(name: &str) -> String {
+    if KEYWORDS.contains(&name) {
+        format!("r#{name}")
+    } else {
+        name.to_string()
+    }
+}
+
+fn primitive(
+    indent: &str,
+    kind: &str,
+    value: &str,
+) -> String {
+    format!("{indent}{RUNTIME}::write_{kind}(out, *{value});\n")
+}
+
+pub(crate) fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (index, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if index > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+// These are the functions called by generated code.  They are public only
+// so that generated code can reach them, and are not part of the public
+// interface of the crate.
+#[doc(hidden)]
+#[allow(missing_docs, clippy::missing_errors_doc)]
+pub mod runtime {
+    use crate::{
+        Deserializer,
+        Error,
+        Result,
+        Serializer,
+    };
+    use serde::Serializer as _;
+
+    macro_rules! write_primitive {
+        ($write:ident, $serialize:ident, $type:ty) => {
+            pub fn $write(
+                out: &mut Vec<u8>,
+                value: $type,
+            ) {
+                let _ = Serializer::new(out).$serialize(value);
+            }
+        };
+    }
+
+    write_primitive!(write_bool, serialize_bool, bool);
+
+    write_primitive!(write_char, serialize_char, char);
+
+    write_primitive!(write_f32, serialize_f32, f32);
+
+    write_primitive!(write_f64, serialize_f64, f64);
+
+    write_primitive!(write_i8, serialize_i8, i8);
+
+    write_primitive!(write_i16, serialize_i16, i16);
+
+    write_primitive!(write_i32, serialize_i32, i32);
+
+    write_primitive!(write_i64, serialize_i64, i64);
+
+    write_primitive!(write_i128, serialize_i128, i128);
+
+    write_primitive!(write_u8, serialize_u8, u8);
+
+    write_primitive!(write_u16, serialize_u16, u16);
+
+    write_primitive!(write_u32, serialize_u32, u32);
+
+    write_primitive!(write_u64, serialize_u64, u64);
+
+    write_primitive!(write_u128, serialize_u128, u128);
+
+    write_primitive!(write_str, serialize_str, &str);
+
+    write_primitive!(write_bytes, serialize_bytes, &[u8]);
+
+    pub fn write_len(
+        out: &mut Vec<u8>,
+        len: usize,
+    ) {
+        write_u64(out, len as u64);
+    }
+
+    pub fn write_option(
+        out: &mut Vec<u8>,
+        some: bool,
+    ) {
+        out.push(u8::from(some));
+    }
+
+    pub fn read_bool(de: &mut Deserializer) -> Result<bool> {
+        de.parse_bool()
+    }
+
+    pub fn read_bytes<'de>(de: &mut Deserializer<'de>) -> Result<&'de [u8]> {
+        de.parse_bytes()
+    }
+
+    pub fn read_char(de: &mut Deserializer) -> Result<char> {
+        de.parse_char()
+    }
+
+    pub fn read_f32(de: &mut Deserializer) -> Result<f32> {
+        de.parse_f32()
+    }
+
+    pub fn read_f64(de: &mut Deserializer) -> Result<f64> {
+        de.parse_f64()
+    }
+
+    pub fn read_i8(de: &mut Deserializer) -> Result<i8> {
+        de.parse_i8()
+    }
+
+    pub fn read_i16(de: &mut Deserializer) -> Result<i16> {
+        de.parse_i16()
+    }
+
+    pub fn read_i32(de: &mut Deserializer) -> Result<i32> {
+        de.parse_i32()
+    }
+
+    pub fn read_i64(de: &mut Deserializer) -> Result<i64> {
+        de.parse_i64(None)
+    }
+
+    pub fn read_i128(de: &mut Deserializer) -> Result<i128> {
+        de.parse_i128()
+    }
+
+    pub fn read_len(de: &mut Deserializer) -> Result<usize> {
+        de.parse_usize()
+    }
+
+    pub fn read_option(de: &mut Deserializer) -> Result<bool> {
+        de.parse_option().map(|some| some.is_some())
+    }
+
+    pub fn read_str<'de>(de: &mut Deserializer<'de>) -> Result<&'de str> {
+        de.parse_str()
+    }
+
+    pub fn read_u8(de: &mut Deserializer) -> Result<u8> {
+        de.parse_u8()
+    }
+
+    pub fn read_u16(de: &mut Deserializer) -> Result<u16> {
+        de.parse_u16()
+    }
+
+    pub fn read_u32(de: &mut Deserializer) -> Result<u32> {
+        de.parse_u32()
+    }
+
+    pub fn read_u64(de: &mut Deserializer) -> Result<u64> {
+        de.parse_u64(None)
+    }
+
+    pub fn read_u128(de: &mut Deserializer) -> Result<u128> {
+        de.parse_u128()
+    }
+
+    #[must_use]
+    pub fn unknown_variant(
+        name: &str,
+        index: usize,
+    ) -> Error {
+        Error::Message(format!("unknown variant index {index} of {name}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Deserialize)]
+    #[allow(dead_code)]
+    struct Telemetry {
+        id: u32,
+        tags: Vec<String>,
+        kind: Kind,
+    }
+
+    #[derive(serde::Deserialize)]
+    #[allow(dead_code)]
+    enum Kind {
+        Idle,
+        Moving {
+            speed: f32,
+        },
+    }
+
+    #[test]
+    fn nested_named_types_are_generated_once() {
+        let mut codegen = Codegen::new();
+        let schema = Schema::of::<Telemetry>().unwrap();
+        assert!(codegen.add(&schema).is_ok());
+        assert!(codegen.add(&Schema::of::<Kind>().unwrap()).is_ok());
+        let source = codegen.finish();
+        assert_eq!(1, source.matches("pub fn encode_telemetry(").count());
+        assert_eq!(1, source.matches("pub fn decode_telemetry(").count());
+        assert_eq!(1, source.matches("pub fn encode_kind(").count());
+        assert_eq!(1, source.matches("pub fn decode_kind(").count());
+        assert!(source.contains("Kind::Moving { speed: f0 } =>"));
+        assert!(source.contains("encode_kind(&value.kind, out);"));
+    }
+
+    #[test]
+    fn wide_integers() {
+        #[derive(serde::Deserialize)]
+        #[allow(dead_code)]
+        struct Totals(u128, i128);
+
+        let mut codegen = Codegen::new();
+        assert!(codegen.add(&Schema::of::<Totals>().unwrap()).is_ok());
+        let source = codegen.finish();
+        assert!(source.contains("codegen::write_u128(out, *&value.0);"));
+        assert!(source.contains("codegen::write_i128(out, *&value.1);"));
+        assert!(source.contains(
+            "Ok(Totals(::serialization::__private::codegen::read_u128(de)?, \
+             ::serialization::__private::codegen::read_i128(de)?))"
+        ));
+    }
+
+    #[test]
+    fn unnamed_types_are_rejected() {
+        let mut codegen = Codegen::new();
+        assert!(matches!(
+            codegen.add(&Schema::of::<Vec<u8>>().unwrap()),
+            Err(Error::Message(_))
+        ));
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!("sensor_reading", snake_case("SensorReading"));
+        assert_eq!("r#type", identifier("type"));
+        assert_eq!("kind", identifier("kind"));
+    }
+}
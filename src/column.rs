@@ -0,0 +1,206 @@
+use super::{
+    skip::Skipper,
+    Deserializer,
+    Error,
+    Result,
+    Schema,
+};
+
+/// This function is used to decode a single field from every record in an
+/// encoded sequence of structs, such as a `Vec` of them encoded by
+/// [`to_bytes`] or a batch encoded by [`to_bytes_many`].  The given schema
+/// describes the records, and is used to skip over every other field
+/// without decoding it, which is much cheaper than decoding every record in
+/// full in order to read one of its fields.
+///
+/// [`to_bytes`]: fn.to_bytes.html
+/// [`to_bytes_many`]: fn.to_bytes_many.html
+///
+/// # Errors
+///
+/// This function returns [`Error::UnknownField`] if the schema does not
+/// describe a struct with a field of the given name.  Otherwise, any of the
+/// errors returned by [`from_bytes`] may be returned from decoding the
+/// number of records, the requested field, or the fields skipped over.
+///
+/// [`Error::UnknownField`]: enum.Error.html#variant.UnknownField
+/// [`from_bytes`]: fn.from_bytes.html
+pub fn extract_column<'de, T>(
+    record: &Schema,
+    bytes: &'de [u8],
+    field: &str,
+) -> Result<Vec<T>>
+where
+    T: serde::Deserialize<'de>,
+{
+    let Schema::Struct {
+        fields,
+        ..
+    } = record
+    else {
+        return Err(Error::UnknownField(String::from(field)));
+    };
+    let position = fields
+        .iter()
+        .position(|candidate| candidate.name == field)
+        .ok_or_else(|| Error::UnknownField(String::from(field)))?;
+    let mut deserializer = Deserializer::new(bytes);
+    let mut skipper = Skipper::new(record);
+    let len = deserializer.parse_usize()?;
+
+    // Every record takes at least one byte, except for records made only of
+    // zero-sized fields, so the buffer length limits how much to reserve up
+    // front, protecting against corrupted counts.
+    let mut values = Vec::with_capacity(len.min(bytes.len()));
+    for _ in 0..len {
+        for before in &fields[..position] {
+            skipper.skip(&mut deserializer, &before.schema)?;
+        }
+        values.push(T::deserialize(&mut deserializer)?);
+        for after in &fields[position + 1..] {
+            skipper.skip(&mut deserializer, &after.schema)?;
+        }
+    }
+    Ok(values)
+}
+
+#[cfg(all(test, feature = "float"))]
+mod tests {
+    use super::*;
+    use crate::{
+        to_bytes,
+        to_bytes_many,
+    };
+    use std::collections::HashMap;
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    enum Status {
+        Ok,
+        Degraded(String),
+        Failed {
+            code: i32,
+            retry: Option<u16>,
+        },
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Tree {
+        label: char,
+        children: Vec<Tree>,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Reading {
+        sensor: String,
+        samples: Vec<f32>,
+        timestamp: u64,
+        status: Status,
+        tags: HashMap<String, (i8, bool)>,
+        tree: Tree,
+        scale: f64,
+    }
+
+    fn readings() -> Vec<Reading> {
+        let mut tags = HashMap::new();
+        tags.insert(String::from("room"), (-3, true));
+        vec![
+            Reading {
+                sensor: String::from("a"),
+                samples: vec![1.0, 2.5],
+                timestamp: 1_600_000_000,
+                status: Status::Ok,
+                tags,
+                tree: Tree {
+                    label: 'x',
+                    children: vec![Tree {
+                        label: '\u{e9}',
+                        children: vec![],
+                    }],
+                },
+                scale: 0.5,
+            },
+            Reading {
+                sensor: String::from("b"),
+                samples: vec![],
+                timestamp: 1_600_000_060,
+                status: Status::Failed {
+                    code: -7,
+                    retry: Some(300),
+                },
+                tags: HashMap::new(),
+                tree: Tree {
+                    label: 'y',
+                    children: vec![],
+                },
+                scale: -2.0,
+            },
+            Reading {
+                sensor: String::from("c"),
+                samples: vec![-1.0],
+                timestamp: 1_600_000_120,
+                status: Status::Degraded(String::from("noisy")),
+                tags: HashMap::new(),
+                tree: Tree {
+                    label: 'z',
+                    children: vec![],
+                },
+                scale: 4.0,
+            },
+        ]
+    }
+
+    #[test]
+    fn extract_fields() {
+        let schema = Schema::of::<Reading>().unwrap();
+        let bytes = to_bytes(&readings()).unwrap();
+        let timestamps = extract_column::<u64>(&schema, &bytes, "timestamp");
+        assert!(timestamps.is_ok());
+        let timestamps = timestamps.unwrap();
+        assert_eq!(
+            vec![1_600_000_000, 1_600_000_060, 1_600_000_120],
+            timestamps
+        );
+        let sensors = extract_column::<&str>(&schema, &bytes, "sensor");
+        assert!(sensors.is_ok());
+        let sensors = sensors.unwrap();
+        assert_eq!(vec!["a", "b", "c"], sensors);
+        let scales = extract_column::<f64>(&schema, &bytes, "scale");
+        assert!(scales.is_ok());
+        let scales = scales.unwrap();
+        assert_eq!(vec![0.5, -2.0, 4.0], scales);
+    }
+
+    #[test]
+    fn extract_from_batch() {
+        let schema = Schema::of::<Reading>().unwrap();
+        let bytes = to_bytes_many(&readings()).unwrap();
+        let samples = extract_column::<Vec<f32>>(&schema, &bytes, "samples");
+        assert!(samples.is_ok());
+        let samples = samples.unwrap();
+        assert_eq!(vec![vec![1.0, 2.5], vec![], vec![-1.0]], samples);
+    }
+
+    #[test]
+    fn unknown_field() {
+        let schema = Schema::of::<Reading>().unwrap();
+        let bytes = to_bytes(&readings()).unwrap();
+        assert!(matches!(
+            extract_column::<u64>(&schema, &bytes, "time"),
+            Err(Error::UnknownField(field)) if field == "time"
+        ));
+        assert!(matches!(
+            extract_column::<u64>(&Schema::U64, &bytes, "timestamp"),
+            Err(Error::UnknownField(_))
+        ));
+    }
+
+    #[test]
+    fn truncated_records() {
+        let schema = Schema::of::<Reading>().unwrap();
+        let bytes = to_bytes(&readings()).unwrap();
+        assert!(matches!(
+            extract_column::<f64>(&schema, &bytes[..bytes.len() - 1], "scale"),
+            Err(Error::ValueTruncated)
+        ));
+    }
+}
@@ -0,0 +1,498 @@
+use super::{
+    from_bytes_projected,
+    to_writer,
+    Deserializer,
+    Error,
+    IntCodec,
+    Result,
+    Schema,
+    StreamDeserializer,
+    Varint,
+};
+use std::{
+    collections::BTreeMap,
+    convert::TryFrom,
+    io::Write,
+    ops::Range,
+};
+
+// This locates the record for one key in a compacted log.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct Entry<K> {
+    key: K,
+    offset: u64,
+    len: u64,
+}
+
+/// This locates the record for each key in a log compacted by
+/// [`compact_log`] or [`compact_many`], so that the record for a key can be
+/// read without decoding the records before it.  The index can itself be
+/// encoded, such as with [`to_writer`], to be kept in a file next to the
+/// compacted log, and decoded again with [`from_bytes`].
+///
+/// [`compact_log`]: fn.compact_log.html
+/// [`compact_many`]: fn.compact_many.html
+/// [`to_writer`]: fn.to_writer.html
+/// [`from_bytes`]: fn.from_bytes.html
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct LogIndex<K> {
+    entries: Vec<Entry<K>>,
+}
+
+impl<K> LogIndex<K>
+where
+    K: Ord,
+{
+    /// This function is used to decode the record for the given key from
+    /// the compacted log, returning `None` if there is no record for the
+    /// key.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`Error::ValueTruncated`] if the record lies
+    /// outside the given bytes.  Any of the errors returned by
+    /// [`from_bytes`] may be returned from decoding the record.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`from_bytes`]: fn.from_bytes.html
+    pub fn get<'de, T>(
+        &self,
+        key: &K,
+        compacted: &'de [u8],
+    ) -> Result<Option<T>>
+    where
+        T: serde::Deserialize<'de>,
+    {
+        let Some(range) = self.range(key) else {
+            return Ok(None);
+        };
+        let start =
+            usize::try_from(range.start).map_err(|_| Error::ValueTruncated)?;
+        let end =
+            usize::try_from(range.end).map_err(|_| Error::ValueTruncated)?;
+        let bytes = compacted.get(start..end).ok_or(Error::ValueTruncated)?;
+        let mut deserializer = Deserializer::new(bytes);
+        T::deserialize(&mut deserializer).map(Some)
+    }
+
+    /// Return the range of bytes holding the record for the given key in
+    /// the compacted log, or `None` if there is no record for the key.
+    #[must_use]
+    pub fn range(
+        &self,
+        key: &K,
+    ) -> Option<Range<u64>> {
+        self.entries
+            .binary_search_by(|entry| entry.key.cmp(key))
+            .ok()
+            .map(|index| {
+                let entry = &self.entries[index];
+                entry.offset..entry.offset + entry.len
+            })
+    }
+}
+
+impl<K> LogIndex<K> {
+    /// Determine whether or not the compacted log holds no records.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Return the keys of the records in the compacted log, in order.
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.entries.iter().map(|entry| &entry.key)
+    }
+
+    /// Return the number of records in the compacted log, which is the
+    /// number of distinct keys in the log it was compacted from.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+// Find the latest record for each key among the given records, stored back
+// to back, which are all the records in the given bytes if no count is
+// given.  The key and the range of the record are returned for each key,
+// in the order the records appear.
+fn latest_records<K>(
+    record: &Schema,
+    records: &[u8],
+    count: Option<u64>,
+) -> Result<Vec<(K, Range<usize>)>>
+where
+    K: serde::de::DeserializeOwned + Ord,
+{
+    let mut stream = StreamDeserializer::<()>::new(records);
+    let mut latest = BTreeMap::new();
+    let mut position = 0_u64;
+    loop {
+        match count {
+            Some(count) if position == count => break,
+            None if stream.remaining() == 0 => break,
+            _ => {},
+        }
+        let start = stream.offset();
+        if stream.skip_values(record, 1)? == 0 {
+            return Err(Error::ValueTruncated);
+        }
+        let range = start..stream.offset();
+        let key: K = from_bytes_projected(record, &records[range.clone()])?;
+        latest.insert(key, (position, range));
+        position += 1;
+    }
+    let mut latest = latest
+        .into_iter()
+        .map(|(key, (position, range))| (position, key, range))
+        .collect::<Vec<_>>();
+    latest.sort_unstable_by_key(|(position, _, _)| *position);
+    Ok(latest.into_iter().map(|(_, key, range)| (key, range)).collect())
+}
+
+// Write the given records of the log to the given writer, starting at the
+// given offset, and return the index of them.
+fn write_records<K, W>(
+    writer: &mut W,
+    log: &[u8],
+    records: Vec<(K, Range<usize>)>,
+    mut offset: u64,
+) -> Result<LogIndex<K>>
+where
+    K: Ord,
+    W: ?Sized + Write,
+{
+    let mut entries = Vec::with_capacity(records.len());
+    for (key, range) in records {
+        writer.write_all(&log[range.clone()])?;
+        let len = range.len() as u64;
+        entries.push(Entry {
+            key,
+            offset,
+            len,
+        });
+        offset += len;
+    }
+    entries.sort_unstable_by(|a, b| a.key.cmp(&b.key));
+    Ok(LogIndex {
+        entries,
+    })
+}
+
+/// This function is used to compact a log of records, which are structs
+/// described by the given schema stored back to back, as read by a
+/// [`StreamDeserializer`].  Only the latest record for each key is kept,
+/// and these are written to the given writer, in the order they appear in
+/// the log, forming a new log of the same kind.  An index locating the
+/// record for each key in the new log is returned.
+///
+/// The key of a record is decoded from it with [`from_bytes_projected`], so
+/// the key type `K` is a struct having some of the fields of the record,
+/// such as its identifier, with the same names.  Only the fields of the key
+/// are decoded; the rest of each record is skipped, and copied as it is.
+///
+/// [`StreamDeserializer`]: struct.StreamDeserializer.html
+/// [`from_bytes_projected`]: fn.from_bytes_projected.html
+///
+/// # Errors
+///
+/// Returns [`Error::Io`] if writing the new log fails, in which case what
+/// was written is incomplete.  Any of the errors returned by
+/// [`from_bytes_projected`] may be returned from finding the end of a
+/// record or decoding its key, in which case nothing is written.
+///
+/// [`Error::Io`]: enum.Error.html#variant.Io
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// # extern crate serde;
+/// use serialization::{
+///     compact_log,
+///     from_bytes,
+///     to_bytes,
+///     LogIndex,
+///     Schema,
+/// };
+///
+/// #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+/// struct Balance {
+///     account: String,
+///     cents: i64,
+/// }
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// #[derive(PartialEq, PartialOrd, Eq, Ord)]
+/// struct Account {
+///     account: String,
+/// }
+///
+/// let mut log = Vec::new();
+/// for (account, cents) in &[("alice", 5), ("bob", 7), ("alice", 2)] {
+///     log.extend(to_bytes(&Balance {
+///         account: String::from(*account),
+///         cents: *cents,
+///     }).unwrap());
+/// }
+/// let schema = Schema::of::<Balance>().unwrap();
+/// let mut compacted = Vec::new();
+/// let index: LogIndex<Account> =
+///     compact_log(&schema, &log, &mut compacted).unwrap();
+/// assert_eq!(2, index.len());
+///
+/// // The index is kept alongside the compacted log.
+/// let index = to_bytes(&index).unwrap();
+/// let index: LogIndex<Account> = from_bytes(&index).unwrap();
+/// let alice = Account {
+///     account: String::from("alice"),
+/// };
+/// assert_eq!(
+///     Some(Balance {
+///         account: String::from("alice"),
+///         cents: 2,
+///     }),
+///     index.get(&alice, &compacted).unwrap()
+/// );
+/// ```
+pub fn compact_log<K, W>(
+    record: &Schema,
+    log: &[u8],
+    writer: &mut W,
+) -> Result<LogIndex<K>>
+where
+    K: serde::de::DeserializeOwned + Ord,
+    W: ?Sized + Write,
+{
+    let records = latest_records(record, log, None)?;
+    write_records(writer, log, records, 0)
+}
+
+/// This function is used to compact a sequence of records, which are
+/// structs described by the given schema, made by [`to_bytes_many`] or
+/// [`append_many`], or encoded as a `Vec`.  This is the same as
+/// [`compact_log`], except that the new log written to the given writer is
+/// also a sequence, preceded by the number of records, so it can still be
+/// decoded as a `Vec` and extended with [`append_many`].  An empty buffer
+/// is taken to be an empty sequence.
+///
+/// [`to_bytes_many`]: fn.to_bytes_many.html
+/// [`append_many`]: fn.append_many.html
+/// [`compact_log`]: fn.compact_log.html
+///
+/// # Errors
+///
+/// The same errors are returned as by [`compact_log`], and
+/// [`Error::ValueTruncated`] if the sequence holds fewer records than its
+/// number of records says.
+///
+/// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+pub fn compact_many<K, W>(
+    record: &Schema,
+    encoding: &[u8],
+    writer: &mut W,
+) -> Result<LogIndex<K>>
+where
+    K: serde::de::DeserializeOwned + Ord,
+    W: ?Sized + Write,
+{
+    let (count, count_len) = if encoding.is_empty() {
+        (0, 0)
+    } else {
+        Varint.decode_unsigned(encoding, u64::MAX)?
+    };
+    let log = &encoding[count_len..];
+    let records = latest_records(record, log, Some(count))?;
+    let mut count_encoding = Vec::new();
+    to_writer(&mut count_encoding, &records.len())?;
+    writer.write_all(&count_encoding)?;
+    write_records(writer, log, records, count_encoding.len() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        append_many,
+        from_bytes,
+        to_bytes,
+        to_bytes_many,
+    };
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq, Clone)]
+    struct Setting {
+        scope: u8,
+        name: String,
+        value: Vec<u32>,
+    }
+
+    #[derive(
+        serde::Serialize,
+        serde::Deserialize,
+        Debug,
+        PartialEq,
+        PartialOrd,
+        Eq,
+        Ord,
+    )]
+    struct Key {
+        name: String,
+        scope: u8,
+    }
+
+    fn setting(
+        scope: u8,
+        name: &str,
+        value: u32,
+    ) -> Setting {
+        Setting {
+            scope,
+            name: String::from(name),
+            value: vec![value; usize::from(scope)],
+        }
+    }
+
+    fn key(
+        scope: u8,
+        name: &str,
+    ) -> Key {
+        Key {
+            name: String::from(name),
+            scope,
+        }
+    }
+
+    fn settings() -> Vec<Setting> {
+        vec![
+            setting(1, "color", 1),
+            setting(2, "color", 2),
+            setting(1, "size", 3),
+            setting(1, "color", 4),
+            setting(3, "font", 5),
+            setting(1, "size", 6),
+        ]
+    }
+
+    #[test]
+    fn latest_record_per_key_is_kept() {
+        let schema = Schema::of::<Setting>().unwrap();
+        let mut log = Vec::new();
+        for setting in settings() {
+            log.extend(to_bytes(&setting).unwrap());
+        }
+        let mut compacted = Vec::new();
+        let index = compact_log::<Key, _>(&schema, &log, &mut compacted);
+        assert!(index.is_ok());
+        let index = index.unwrap();
+
+        // The records keep the order of their latest appearance.
+        let records = StreamDeserializer::<Setting>::new(&compacted)
+            .collect::<Result<Vec<_>>>();
+        assert!(records.is_ok());
+        assert_eq!(
+            vec![
+                setting(2, "color", 2),
+                setting(1, "color", 4),
+                setting(3, "font", 5),
+                setting(1, "size", 6),
+            ],
+            records.unwrap()
+        );
+        assert_eq!(
+            vec![
+                &key(1, "color"),
+                &key(2, "color"),
+                &key(3, "font"),
+                &key(1, "size"),
+            ],
+            index.keys().collect::<Vec<_>>()
+        );
+
+        // The index survives being encoded and locates each record.
+        let index = from_bytes::<LogIndex<Key>>(&to_bytes(&index).unwrap());
+        assert!(index.is_ok());
+        let index = index.unwrap();
+        assert_eq!(4, index.len());
+        let record = index.get::<Setting>(&key(1, "size"), &compacted);
+        assert!(matches!(
+            record,
+            Ok(Some(record)) if record == setting(1, "size", 6)
+        ));
+        let record = index.get::<Setting>(&key(2, "size"), &compacted);
+        assert!(matches!(record, Ok(None)));
+        assert!(matches!(
+            index.get::<Setting>(&key(1, "size"), &compacted[..3]),
+            Err(Error::ValueTruncated)
+        ));
+        assert_eq!(
+            Some(0..to_bytes(&setting(2, "color", 2)).unwrap().len() as u64),
+            index.range(&key(2, "color"))
+        );
+
+        // Compacting again changes nothing.
+        let mut again = Vec::new();
+        let index = compact_log::<Key, _>(&schema, &compacted, &mut again);
+        assert!(index.is_ok());
+        assert_eq!(compacted, again);
+    }
+
+    #[test]
+    fn sequences_stay_sequences() {
+        let schema = Schema::of::<Setting>().unwrap();
+        let mut encoding = to_bytes_many(&settings()[..3]).unwrap();
+        append_many(&mut encoding, &settings()[3..]).unwrap();
+        let mut compacted = Vec::new();
+        let index = compact_many::<Key, _>(&schema, &encoding, &mut compacted);
+        assert!(index.is_ok());
+        let index = index.unwrap();
+        let records = from_bytes::<Vec<Setting>>(&compacted);
+        assert!(records.is_ok());
+        let records = records.unwrap();
+        assert_eq!(4, records.len());
+        for record in &records {
+            let key = key(record.scope, &record.name);
+            let found = index.get::<Setting>(&key, &compacted);
+            assert!(matches!(found, Ok(Some(found)) if found == *record));
+        }
+
+        // More compacted records can be appended.
+        assert!(append_many(&mut compacted, &[setting(1, "size", 7)]).is_ok());
+        let records = from_bytes::<Vec<Setting>>(&compacted);
+        assert!(matches!(records, Ok(records) if records.len() == 5));
+        let mut empty = Vec::new();
+        let index = compact_many::<Key, _>(&schema, &[0], &mut empty);
+        assert!(matches!(index, Ok(index) if index.is_empty()));
+        assert_eq!(vec![0], empty);
+    }
+
+    #[derive(serde::Deserialize, PartialEq, PartialOrd, Eq, Ord)]
+    struct Owner {
+        owner: String,
+    }
+
+    #[test]
+    fn errors() {
+        let schema = Schema::of::<Setting>().unwrap();
+        let encoding = to_bytes_many(&settings()).unwrap();
+        let mut compacted = Vec::new();
+        assert!(matches!(
+            compact_many::<Key, _>(
+                &schema,
+                &encoding[..encoding.len() - 1],
+                &mut compacted
+            ),
+            Err(Error::ValueTruncated)
+        ));
+        assert!(matches!(
+            compact_log::<Key, _>(&schema, &encoding[1..5], &mut compacted),
+            Err(Error::ValueTruncated)
+        ));
+        assert!(compacted.is_empty());
+
+        // Keys must be made of fields of the record.
+        assert!(matches!(
+            compact_log::<Owner, _>(&schema, &encoding[1..], &mut compacted),
+            Err(Error::UnknownField(field)) if field == "owner"
+        ));
+    }
+}
@@ -0,0 +1,504 @@
+use super::{
+    from_bytes,
+    registry::Bytes,
+    to_bytes,
+    Deserializer,
+    Result,
+};
+#[cfg(feature = "zstd")]
+use super::Error;
+
+/// This is implemented by compression algorithms used to compress the
+/// encodings of values.  Any compression library can be plugged in by
+/// implementing this trait around it.  With the `zstd` feature enabled,
+/// [`ZstdDictionary`] implements it with zstd and a dictionary shared by
+/// both ends, which suits the many small records a [`RecordCompressor`]
+/// would otherwise leave uncompressed.
+///
+/// [`ZstdDictionary`]: struct.ZstdDictionary.html
+/// [`RecordCompressor`]: struct.RecordCompressor.html
+pub trait Compressor {
+    /// Return the compression of the given bytes.
+    ///
+    /// # Errors
+    ///
+    /// An error should be returned if the bytes cannot be compressed.
+    fn compress(
+        &self,
+        bytes: &[u8],
+    ) -> Result<Vec<u8>>;
+
+    /// Return the decompression of the given bytes.
+    ///
+    /// # Errors
+    ///
+    /// An error should be returned if the bytes are not a valid
+    /// compression.
+    fn decompress(
+        &self,
+        compressed: &[u8],
+    ) -> Result<Vec<u8>>;
+}
+
+/// This encodes values as records which are compressed only when it pays
+/// off: records whose encodings are at least the threshold size are
+/// compressed, and kept compressed only if that makes them smaller.  Small
+/// records, which rarely compress well, don't pay the cost of trying,
+/// while large ones benefit automatically.
+///
+/// A record is encoded as a flag (as a `bool`) telling whether or not the
+/// record is compressed, followed by the compressed or uncompressed
+/// encoding of the value (as bytes).  Records may be concatenated into a
+/// stream or log, and decoded in turn with [`decode_stream`].
+///
+/// [`decode_stream`]: #method.decode_stream
+pub struct RecordCompressor<C> {
+    compressor: C,
+    threshold: usize,
+}
+
+impl<C> RecordCompressor<C>
+where
+    C: Compressor,
+{
+    /// This is the default size, in bytes, which the encoding of a value
+    /// must reach before compression of it is attempted.
+    pub const DEFAULT_THRESHOLD: usize = 128;
+
+    /// This function is used to decode a value from a record made by
+    /// [`encode`].
+    ///
+    /// [`encode`]: #method.encode
+    ///
+    /// # Errors
+    ///
+    /// Any errors returned by the compressor are passed through, and any
+    /// of the errors returned by [`from_bytes`] may be returned from
+    /// decoding the record or the value.
+    ///
+    /// [`from_bytes`]: fn.from_bytes.html
+    pub fn decode<T>(
+        &self,
+        record: &[u8],
+    ) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let (compressed, encoding): (bool, &[u8]) = from_bytes(record)?;
+        self.decode_encoding(compressed, encoding)
+    }
+
+    fn decode_encoding<T>(
+        &self,
+        compressed: bool,
+        encoding: &[u8],
+    ) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        if compressed {
+            from_bytes(&self.compressor.decompress(encoding)?)
+        } else {
+            from_bytes(encoding)
+        }
+    }
+
+    /// This function is used to decode every value in a stream of records
+    /// made by [`encode`] and concatenated.
+    ///
+    /// [`encode`]: #method.encode
+    ///
+    /// # Errors
+    ///
+    /// Any of the errors returned by [`decode`] may be returned from
+    /// decoding any of the records.
+    ///
+    /// [`decode`]: #method.decode
+    pub fn decode_stream<T>(
+        &self,
+        records: &[u8],
+    ) -> Result<Vec<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        Deserializer::new(records)
+            .iterate::<(bool, &[u8])>()
+            .map(|record| {
+                record.and_then(|(compressed, encoding)| {
+                    self.decode_encoding(compressed, encoding)
+                })
+            })
+            .collect()
+    }
+
+    /// This function is used to encode the given value into a record,
+    /// compressing the encoding if it is at least the threshold size and
+    /// compressing it makes it smaller.
+    ///
+    /// # Errors
+    ///
+    /// Any errors returned by the compressor are passed through, and any
+    /// of the errors returned by [`to_bytes`] may be returned from encoding
+    /// the value.
+    ///
+    /// [`to_bytes`]: fn.to_bytes.html
+    pub fn encode<T>(
+        &self,
+        value: &T,
+    ) -> Result<Vec<u8>>
+    where
+        T: serde::Serialize,
+    {
+        let encoding = to_bytes(value)?;
+        if encoding.len() >= self.threshold {
+            let compressed = self.compressor.compress(&encoding)?;
+            if compressed.len() < encoding.len() {
+                return to_bytes(&(true, Bytes(&compressed)));
+            }
+        }
+        to_bytes(&(false, Bytes(&encoding)))
+    }
+
+    /// Return a new record compressor which uses the given compressor, and
+    /// the default threshold.
+    #[must_use]
+    pub fn new(compressor: C) -> Self {
+        Self {
+            compressor,
+            threshold: Self::DEFAULT_THRESHOLD,
+        }
+    }
+
+    /// Set the size, in bytes, which the encoding of a value must reach
+    /// before compression of it is attempted.
+    #[must_use]
+    pub fn with_threshold(
+        mut self,
+        threshold: usize,
+    ) -> Self {
+        self.threshold = threshold;
+        self
+    }
+}
+
+/// This compresses with zstd, using a dictionary which both the compressing
+/// and decompressing ends must hold.  Small messages barely compress on
+/// their own, because they are too short to repeat anything, but they
+/// usually repeat the same field names, enumeration values and string
+/// fragments as each other, which a dictionary trained from sample
+/// messages lets zstd refer to instead.
+///
+/// The dictionary is either trained from sample values with [`train`], or
+/// given to [`new`], such as one trained earlier and shipped to the other
+/// end, which can get its bytes from [`dictionary`].  Decompression stops
+/// once the decompressed bytes pass a maximum length, which is 8 MiB unless
+/// changed with [`with_max_decompressed_len`], so that a small corrupted
+/// or hostile compression cannot take up unlimited memory.
+///
+/// [`train`]: #method.train
+/// [`new`]: #method.new
+/// [`dictionary`]: #method.dictionary
+/// [`with_max_decompressed_len`]: #method.with_max_decompressed_len
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     RecordCompressor,
+///     ZstdDictionary,
+/// };
+///
+/// let samples: Vec<(String, u32)> = (0..1000)
+///     .map(|i| (format!("temperature reading from sensor {}", i % 7), i))
+///     .collect();
+/// let dictionary = ZstdDictionary::train(&samples, 1024).unwrap();
+/// let records = RecordCompressor::new(dictionary).with_threshold(0);
+/// let value = (String::from("temperature reading from sensor 3"), 1234_u32);
+/// let record = records.encode(&value).unwrap();
+/// // The record is compressed, which only happens if that makes it smaller.
+/// assert_eq!(Some(&1), record.first());
+/// assert_eq!(value, records.decode::<(String, u32)>(&record).unwrap());
+/// ```
+#[cfg(feature = "zstd")]
+pub struct ZstdDictionary {
+    decoder: zstd::dict::DecoderDictionary<'static>,
+    dictionary: Vec<u8>,
+    encoder: zstd::dict::EncoderDictionary<'static>,
+    max_decompressed_len: usize,
+}
+
+#[cfg(feature = "zstd")]
+impl ZstdDictionary {
+    /// Return the bytes of the dictionary, to be stored or sent to the
+    /// other end and given there to [`new`].
+    ///
+    /// [`new`]: #method.new
+    #[must_use]
+    pub fn dictionary(&self) -> &[u8] {
+        &self.dictionary
+    }
+
+    /// Return the longest decompression, in bytes, which is returned
+    /// before decompression is abandoned.
+    #[must_use]
+    pub fn max_decompressed_len(&self) -> usize {
+        self.max_decompressed_len
+    }
+
+    /// Return a new compressor using the given dictionary, which should
+    /// have been trained by zstd, such as by [`train`], and the default
+    /// zstd compression level.
+    ///
+    /// [`train`]: #method.train
+    #[must_use]
+    pub fn new(dictionary: Vec<u8>) -> Self {
+        Self {
+            decoder: zstd::dict::DecoderDictionary::copy(&dictionary),
+            encoder: zstd::dict::EncoderDictionary::copy(
+                &dictionary,
+                zstd::DEFAULT_COMPRESSION_LEVEL,
+            ),
+            dictionary,
+            max_decompressed_len: 8 * 1024 * 1024,
+        }
+    }
+
+    /// Return a new compressor using a dictionary of up to the given number
+    /// of bytes, trained from the encodings of the given sample values.
+    /// The samples should be typical of the values to be compressed, and
+    /// there should be many of them; zstd suggests a total size of about a
+    /// hundred times the size of the dictionary.
+    ///
+    /// # Errors
+    ///
+    /// Any of the errors returned by [`to_bytes`] may be returned from
+    /// encoding the samples, and [`Error::Io`] is returned if zstd cannot
+    /// train a dictionary from them, such as when there are too few.
+    ///
+    /// [`to_bytes`]: fn.to_bytes.html
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    pub fn train<T>(
+        samples: &[T],
+        max_size: usize,
+    ) -> Result<Self>
+    where
+        T: serde::Serialize,
+    {
+        let encodings = samples
+            .iter()
+            .map(to_bytes)
+            .collect::<Result<Vec<Vec<u8>>>>()?;
+        Ok(Self::new(zstd::dict::from_samples(&encodings, max_size)?))
+    }
+
+    /// Return a copy of the compressor which compresses at the given zstd
+    /// compression level.
+    #[must_use]
+    pub fn with_level(
+        mut self,
+        level: i32,
+    ) -> Self {
+        self.encoder =
+            zstd::dict::EncoderDictionary::copy(&self.dictionary, level);
+        self
+    }
+
+    /// Return a copy of the compressor which abandons decompressions longer
+    /// than the given number of bytes.
+    #[must_use]
+    pub fn with_max_decompressed_len(
+        mut self,
+        max_decompressed_len: usize,
+    ) -> Self {
+        self.max_decompressed_len = max_decompressed_len;
+        self
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Compressor for ZstdDictionary {
+    fn compress(
+        &self,
+        bytes: &[u8],
+    ) -> Result<Vec<u8>> {
+        let mut compressor =
+            zstd::bulk::Compressor::with_prepared_dictionary(&self.encoder)?;
+        // The frame header would otherwise hold the dictionary's identifier
+        // and the length of the bytes, which together can take up as much as
+        // a small message compresses by.
+        compressor.set_parameter(zstd::stream::raw::CParameter::DictIdFlag(
+            false,
+        ))?;
+        compressor.set_parameter(
+            zstd::stream::raw::CParameter::ContentSizeFlag(false),
+        )?;
+        Ok(compressor.compress(bytes)?)
+    }
+
+    fn decompress(
+        &self,
+        compressed: &[u8],
+    ) -> Result<Vec<u8>> {
+        let decoder = zstd::stream::read::Decoder::with_prepared_dictionary(
+            compressed,
+            &self.decoder,
+        )?;
+        let mut decompressed = Vec::new();
+        std::io::Read::read_to_end(
+            &mut std::io::Read::take(
+                decoder,
+                self.max_decompressed_len as u64 + 1,
+            ),
+            &mut decompressed,
+        )?;
+        if decompressed.len() > self.max_decompressed_len {
+            return Err(Error::DecompressedTooLong(self.max_decompressed_len));
+        }
+        Ok(decompressed)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::Error;
+
+    // This is a run-length encoding, standing in for a real compression
+    // algorithm.
+    pub(crate) struct RunLength;
+
+    impl Compressor for RunLength {
+        fn compress(
+            &self,
+            bytes: &[u8],
+        ) -> Result<Vec<u8>> {
+            let mut compressed = Vec::new();
+            for byte in bytes {
+                match compressed.len().checked_sub(2) {
+                    Some(last)
+                        if compressed[last + 1] == *byte
+                            && compressed[last] < u8::MAX =>
+                    {
+                        compressed[last] += 1;
+                    },
+                    _ => compressed.extend(&[1, *byte]),
+                }
+            }
+            Ok(compressed)
+        }
+
+        fn decompress(
+            &self,
+            compressed: &[u8],
+        ) -> Result<Vec<u8>> {
+            if !compressed.len().is_multiple_of(2) {
+                return Err(Error::ValueTruncated);
+            }
+            Ok(compressed
+                .chunks(2)
+                .flat_map(|run| {
+                    std::iter::repeat_n(run[1], usize::from(run[0]))
+                })
+                .collect())
+        }
+    }
+
+    #[test]
+    fn small_records_are_not_compressed() {
+        let records = RecordCompressor::new(RunLength).with_threshold(8);
+        let record = records.encode(&[0_u8; 4]);
+        assert!(record.is_ok());
+        let record = record.unwrap();
+        assert_eq!(vec![0, 4, 0, 0, 0, 0], record);
+        assert_eq!([0_u8; 4], records.decode::<[u8; 4]>(&record).unwrap());
+    }
+
+    #[test]
+    fn large_records_are_compressed() {
+        let records = RecordCompressor::new(RunLength).with_threshold(8);
+        let record = records.encode(&[7_u8; 20]);
+        assert!(record.is_ok());
+        let record = record.unwrap();
+        assert_eq!(vec![1, 2, 20, 7], record);
+        assert_eq!([7_u8; 20], records.decode::<[u8; 20]>(&record).unwrap());
+    }
+
+    #[test]
+    fn incompressible_records_are_not_compressed() {
+        let records = RecordCompressor::new(RunLength).with_threshold(8);
+        let value: Vec<u8> = (0..10).collect();
+        let record = records.encode(&value).unwrap();
+        assert_eq!(Some(&0), record.first());
+        assert_eq!(value, records.decode::<Vec<u8>>(&record).unwrap());
+    }
+
+    #[test]
+    fn stream_of_records() {
+        let records = RecordCompressor::new(RunLength).with_threshold(8);
+        let values = vec![
+            String::from("a"),
+            "z".repeat(200),
+            String::from("abcdefghij"),
+        ];
+        let mut stream = Vec::new();
+        for value in &values {
+            stream.extend(records.encode(value).unwrap());
+        }
+        let decoded = records.decode_stream::<String>(&stream);
+        assert!(decoded.is_ok());
+        let decoded = decoded.unwrap();
+        assert_eq!(values, decoded);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_dictionary() {
+        #[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+        struct Event {
+            source: String,
+            kind: String,
+            count: u32,
+        }
+        let event = |i: u32| Event {
+            source: format!("gateway-{}.example.com", i % 5),
+            kind: String::from(["connected", "disconnected", "idle"]
+                [i as usize % 3]),
+            count: i,
+        };
+        let samples: Vec<Event> = (0..2000).map(event).collect();
+        let dictionary = ZstdDictionary::train(&samples, 2048);
+        assert!(dictionary.is_ok());
+        let dictionary = dictionary.unwrap();
+        assert!(!dictionary.dictionary().is_empty());
+        let encoding = to_bytes(&event(12345)).unwrap();
+        let compressed = dictionary.compress(&encoding).unwrap();
+        let without = zstd::bulk::compress(&encoding, 0).unwrap();
+        assert!(compressed.len() < encoding.len());
+        assert!(compressed.len() < without.len());
+        assert_eq!(encoding, dictionary.decompress(&compressed).unwrap());
+
+        // The other end can rebuild the compressor from the dictionary's
+        // bytes.
+        let records = RecordCompressor::new(ZstdDictionary::new(
+            dictionary.dictionary().to_vec(),
+        ))
+        .with_threshold(0);
+        let record = dictionary.compress(&encoding).unwrap();
+        let record = to_bytes(&(true, Bytes(&record))).unwrap();
+        assert_eq!(event(12345), records.decode::<Event>(&record).unwrap());
+
+        // Decompressions which are too long are abandoned.
+        let dictionary = dictionary.with_level(19).with_max_decompressed_len(8);
+        assert_eq!(8, dictionary.max_decompressed_len());
+        let compressed = dictionary.compress(&encoding).unwrap();
+        assert!(matches!(
+            dictionary.decompress(&compressed),
+            Err(Error::DecompressedTooLong(8))
+        ));
+        assert!(matches!(
+            ZstdDictionary::train(&samples[..1], 2048),
+            Err(Error::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,1139 @@
+use super::{
+    fingerprint::strip_fingerprint,
+    type_fingerprint,
+    Deserializer,
+    Input,
+    IntCodec,
+    Output,
+    Error,
+    Result,
+    Serializer,
+    Varint,
+};
+use alloc::vec::Vec;
+
+/// This is the version of the encoding produced by this crate, which peers
+/// exchange when negotiating how to communicate.
+pub const FORMAT_VERSION: u16 = 1;
+
+/// This is a set of optional features of the encoding, which peers
+/// negotiate using [`Hello`].  The low 16 bits are features of the encoding
+/// known to this crate, one for the [fingerprint] and one for each option
+/// of a [`Config`] which changes the encoding, so that the configuration
+/// chosen by negotiation encodes the same way for both peers.  Options
+/// which only limit what is accepted, such as [`Config::with_max_depth`],
+/// are left to each peer.  The high 16 bits are left for applications, which
+/// may use them for capabilities of their own, such as compressing or
+/// encrypting what they send, made with [`Features::application`].  Those
+/// are negotiated along with the others, and found with
+/// [`Hello::common_features`], but are left out of a [`Config`], since the
+/// crate does nothing with them.
+///
+/// Sets of features are encoded as a `u32`, so there is room for more of
+/// either kind without changing the layout of [`Hello`].
+///
+/// [`Hello`]: struct.Hello.html
+/// [fingerprint]: fn.to_bytes_with_fingerprint.html
+/// [`Config`]: struct.Config.html
+/// [`Config::with_max_depth`]: struct.Config.html#method.with_max_depth
+/// [`Hello::common_features`]: struct.Hello.html#method.common_features
+/// [`Features::application`]: #method.application
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// # #[cfg(feature = "std")]
+/// # {
+/// use serialization::{
+///     Features,
+///     Hello,
+/// };
+///
+/// const COMPRESSION: Features = Features::application(0);
+/// const ENCRYPTION: Features = Features::application(1);
+///
+/// let ours = Hello::new()
+///     .with_features(Features::ALL.union(COMPRESSION).union(ENCRYPTION));
+/// let theirs = Hello::new().with_features(COMPRESSION);
+/// let features = ours.common_features(&theirs);
+/// assert!(features.contains(COMPRESSION));
+/// assert!(!features.contains(ENCRYPTION));
+/// assert!(!features.contains(Features::FINGERPRINT));
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Features(u32);
+
+impl Features {
+    /// This is the set of every feature known to this crate.
+    pub const ALL: Features = Features(0x0000_03FF);
+    /// This is the set of every feature which applications may define.
+    pub const APPLICATION: Features = Features(0xFFFF_0000);
+    /// Repeated structs are encoded as references to earlier ones, as
+    /// selected by [`Config::with_deduplication`].
+    ///
+    /// [`Config::with_deduplication`]: struct.Config.html#method.with_deduplication
+    pub const DEDUPLICATION: Features = Features(0x0000_0080);
+    /// Fields are identified by hashes of their names, as selected by
+    /// [`Config::with_field_ids`].
+    ///
+    /// [`Config::with_field_ids`]: struct.Config.html#method.with_field_ids
+    pub const FIELD_IDS: Features = Features(0x0000_0004);
+    /// Fields are identified by their names, as selected by
+    /// [`Config::with_field_names`].
+    ///
+    /// [`Config::with_field_names`]: struct.Config.html#method.with_field_names
+    pub const FIELD_NAMES: Features = Features(0x0000_0008);
+    /// Top-level values are preceded by a fingerprint of the layout of
+    /// their type, as produced by [`to_bytes_with_fingerprint`].
+    ///
+    /// [`to_bytes_with_fingerprint`]: fn.to_bytes_with_fingerprint.html
+    pub const FINGERPRINT: Features = Features(0x0000_0001);
+    /// Names and string keys are written only once, as selected by
+    /// [`Config::with_interning`].
+    ///
+    /// [`Config::with_interning`]: struct.Config.html#method.with_interning
+    pub const INTERNING: Features = Features(0x0000_0100);
+    /// This is the empty set of features.
+    pub const NONE: Features = Features(0);
+    /// Characters are encoded as their Unicode scalar values, as selected
+    /// by [`CharEncoding::ScalarValue`].
+    ///
+    /// [`CharEncoding::ScalarValue`]: enum.CharEncoding.html#variant.ScalarValue
+    pub const SCALAR_VALUE_CHARS: Features = Features(0x0000_0200);
+    /// Every value is preceded by a tag giving its type, as selected by
+    /// [`Config::with_self_describing`].
+    ///
+    /// [`Config::with_self_describing`]: struct.Config.html#method.with_self_describing
+    pub const SELF_DESCRIBING: Features = Features(0x0000_0040);
+    /// The fields of each struct are preceded by the number of bytes they
+    /// take, as selected by [`Config::with_struct_framing`].
+    ///
+    /// [`Config::with_struct_framing`]: struct.Config.html#method.with_struct_framing
+    pub const STRUCT_FRAMING: Features = Features(0x0000_0002);
+    /// Variants are identified by hashes of their names, as selected by
+    /// [`Config::with_variant_ids`].
+    ///
+    /// [`Config::with_variant_ids`]: struct.Config.html#method.with_variant_ids
+    pub const VARIANT_IDS: Features = Features(0x0000_0010);
+    /// Variants are identified by their names, as selected by
+    /// [`Config::with_variant_names`].
+    ///
+    /// [`Config::with_variant_names`]: struct.Config.html#method.with_variant_names
+    pub const VARIANT_NAMES: Features = Features(0x0000_0020);
+
+    /// Return the set holding only the feature defined by an application
+    /// with the given number, from 0 to 15.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number is larger than 15.
+    #[must_use]
+    pub const fn application(number: u32) -> Self {
+        assert!(number < 16, "application features are numbered 0 to 15");
+        Self(0x0001_0000 << number)
+    }
+
+    /// Return the bits representing the set of features.
+    #[must_use]
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Determine whether or not every feature in the given set is also in
+    /// this set.
+    #[must_use]
+    pub const fn contains(
+        self,
+        other: Features,
+    ) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Return the set of features in this set but not in the given set.
+    #[must_use]
+    pub const fn difference(
+        self,
+        other: Features,
+    ) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// Return the set of features represented by the given bits, leaving
+    /// out any bits which do not represent features known to this crate.
+    #[must_use]
+    pub const fn from_bits_truncate(bits: u32) -> Self {
+        Self(bits & Self::ALL.0)
+    }
+
+    /// Return the set of features in both this set and the given set.
+    #[must_use]
+    pub const fn intersection(
+        self,
+        other: Features,
+    ) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    /// Determine whether or not the set has no features in it.
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Return the set of features in either this set or the given set.
+    #[must_use]
+    pub const fn union(
+        self,
+        other: Features,
+    ) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl serde::Serialize for Features {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> core::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u32(self.0)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Features {
+    fn deserialize<D>(deserializer: D) -> core::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        // Bits of features unknown to this crate are kept, so that they can
+        // be relayed and features defined by applications can be found, but
+        // the configuration chosen by negotiation always leaves them out.
+        u32::deserialize(deserializer).map(Features)
+    }
+}
+
+/// This selects how characters are encoded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CharEncoding {
+    /// Characters are encoded in UTF-8, taking one to four bytes, with the
+    /// length given by the first byte rather than a prefix.  This is the
+    /// default.
+    #[default]
+    Utf8,
+
+    /// Characters are encoded as their Unicode scalar values, in the same
+    /// way as `u32` values, which is simpler for decoders which do not
+    /// handle UTF-8.
+    ScalarValue,
+}
+
+/// This holds the options which select among variations of the encoding.
+/// Values encoded with one configuration must be decoded with the same
+/// configuration.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Config {
+    char_encoding: CharEncoding,
+    deduplication: bool,
+    field_ids: bool,
+    field_names: bool,
+    fingerprint: bool,
+    interning: bool,
+    max_depth: usize,
+    max_output_size: Option<usize>,
+    max_replay_ratio: usize,
+    missing_fields: bool,
+    self_describing: bool,
+    shape_validation: bool,
+    struct_framing: bool,
+    variant_ids: bool,
+    variant_names: bool,
+    version: u16,
+}
+
+impl Config {
+    /// Return how characters are encoded under the configuration.
+    #[must_use]
+    pub fn char_encoding(&self) -> CharEncoding {
+        self.char_encoding
+    }
+
+    /// Determine whether or not repeated structs are encoded as references
+    /// to earlier ones.
+    #[must_use]
+    pub fn deduplication(&self) -> bool {
+        self.deduplication
+    }
+
+    /// Return a new deserializer that decodes from the given input, such as
+    /// a slice of bytes or an [`IoInput`], using this configuration.
+    ///
+    /// [`IoInput`]: struct.IoInput.html
+    #[must_use]
+    pub fn deserializer<'de, I>(
+        &self,
+        input: I,
+    ) -> Deserializer<'de, Varint, I>
+    where
+        I: Input<'de>,
+    {
+        Deserializer::with_codec(input, *self, Varint)
+    }
+
+    /// Return a new deserializer that decodes from the given input using
+    /// this configuration, with integers decoded by the given codec.
+    #[must_use]
+    pub fn deserializer_with_codec<'de, C, I>(
+        &self,
+        input: I,
+        codec: C,
+    ) -> Deserializer<'de, C, I>
+    where
+        C: IntCodec,
+        I: Input<'de>,
+    {
+        Deserializer::with_codec(input, *self, codec)
+    }
+
+    /// Return the set of optional features enabled by the configuration,
+    /// which holds the feature for each option changing the encoding which
+    /// is selected.
+    #[must_use]
+    pub fn features(&self) -> Features {
+        [
+            (self.deduplication, Features::DEDUPLICATION),
+            (self.field_ids, Features::FIELD_IDS),
+            (self.field_names, Features::FIELD_NAMES),
+            (self.fingerprint, Features::FINGERPRINT),
+            (self.interning, Features::INTERNING),
+            (
+                self.char_encoding == CharEncoding::ScalarValue,
+                Features::SCALAR_VALUE_CHARS,
+            ),
+            (self.self_describing, Features::SELF_DESCRIBING),
+            (self.struct_framing, Features::STRUCT_FRAMING),
+            (self.variant_ids, Features::VARIANT_IDS),
+            (self.variant_names, Features::VARIANT_NAMES),
+        ]
+        .iter()
+        .filter(|(enabled, _)| *enabled)
+        .fold(Features::NONE, |features, (_, feature)| {
+            features.union(*feature)
+        })
+    }
+
+    /// Determine whether or not each field of a struct is preceded by an
+    /// identifier, rather than being identified by its position.
+    #[must_use]
+    pub fn field_ids(&self) -> bool {
+        self.field_ids
+    }
+
+    /// Determine whether or not each field of a struct is preceded by its
+    /// name, rather than being identified by its position.
+    #[must_use]
+    pub fn field_names(&self) -> bool {
+        self.field_names
+    }
+
+    // Determine whether or not fields are identified by their names or
+    // hashes of their names, rather than by their positions.
+    pub(crate) fn identifies_fields(&self) -> bool {
+        self.field_ids || self.field_names
+    }
+
+    /// Determine whether or not field names and map keys which are strings
+    /// are written only once, with later copies referring back to them.
+    #[must_use]
+    pub fn interning(&self) -> bool {
+        self.interning
+    }
+
+    // Determine whether or not strings are actually interned, which is not
+    // done with deduplication enabled.
+    pub(crate) fn interns(&self) -> bool {
+        self.interning && !self.deduplication
+    }
+
+    /// Return the deepest values may be nested when decoded without
+    /// knowing their types.
+    #[must_use]
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Return the largest number of bytes serializers using the
+    /// configuration may produce, if there is a limit.
+    #[must_use]
+    pub fn max_output_size(&self) -> Option<usize> {
+        self.max_output_size
+    }
+
+    /// Return how many times the number of bytes of the input decoded the
+    /// bytes of repeated structs decoded again may be.
+    #[must_use]
+    pub fn max_replay_ratio(&self) -> usize {
+        self.max_replay_ratio
+    }
+
+    /// Determine whether or not structs whose encodings end before all of
+    /// their fields are decoded are given defaults for the missing fields.
+    #[must_use]
+    pub fn missing_fields(&self) -> bool {
+        self.missing_fields
+    }
+
+    /// This function is used to decode a value from a sequence of bytes
+    /// using this configuration.
+    ///
+    /// # Errors
+    ///
+    /// Any error from [`from_bytes`] is returned, along with any error
+    /// from [`from_bytes_with_fingerprint`] if [`Features::FINGERPRINT`]
+    /// is enabled.
+    ///
+    /// [`from_bytes`]: fn.from_bytes.html
+    /// [`from_bytes_with_fingerprint`]: fn.from_bytes_with_fingerprint.html
+    /// [`Features::FINGERPRINT`]: struct.Features.html#associatedconstant.FINGERPRINT
+    pub fn from_bytes<'de, T>(
+        &self,
+        bytes: &'de [u8],
+    ) -> Result<T>
+    where
+        T: serde::Deserialize<'de>,
+    {
+        let bytes = if self.fingerprint {
+            strip_fingerprint::<T>(bytes)?
+        } else {
+            bytes
+        };
+        T::deserialize(&mut self.deserializer(bytes))
+            .map_err(|error| self.explain_truncation(error))
+    }
+
+    // Replace running out of bytes with a more specific error if fields
+    // may be missing from structs whose ends are not encoded, since that is
+    // the likely cause.
+    pub(crate) fn explain_truncation(
+        &self,
+        error: Error,
+    ) -> Error {
+        match error {
+            Error::ValueTruncated
+                if self.missing_fields
+                    && !self.struct_framing
+                    && !self.self_describing
+                    && !self.identifies_fields() =>
+            {
+                Error::MissingFieldsUnframed
+            },
+            error => error,
+        }
+    }
+
+    /// Return the default configuration, which uses the current format
+    /// version with no optional features enabled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            char_encoding: CharEncoding::Utf8,
+            deduplication: false,
+            field_ids: false,
+            field_names: false,
+            fingerprint: false,
+            interning: false,
+            max_depth: 128,
+            max_output_size: None,
+            max_replay_ratio: 1024,
+            missing_fields: false,
+            self_describing: false,
+            shape_validation: false,
+            struct_framing: false,
+            variant_ids: false,
+            variant_names: false,
+            version: FORMAT_VERSION,
+        }
+    }
+
+    /// Determine whether or not every value is preceded by a tag giving its
+    /// type, so that the encoding can be decoded without knowing its type.
+    #[must_use]
+    pub fn self_describing(&self) -> bool {
+        self.self_describing
+    }
+
+    /// Determine whether or not serializers using the configuration check
+    /// that every field of each struct is encoded.
+    #[must_use]
+    pub fn shape_validation(&self) -> bool {
+        self.shape_validation
+    }
+
+    /// Return a new serializer that encodes to the given output, such as a
+    /// `Vec<u8>`, using this configuration.
+    #[must_use]
+    pub fn serializer<'ser, O>(
+        &self,
+        output: &'ser mut O,
+    ) -> Serializer<'ser, Varint, O>
+    where
+        O: ?Sized + Output,
+    {
+        Serializer::with_output(output, *self, Varint)
+    }
+
+    /// Return a new serializer that encodes to the given output, such as a
+    /// `Vec<u8>`, using this configuration, with integers encoded by the
+    /// given codec.
+    #[must_use]
+    pub fn serializer_with_codec<'ser, C, O>(
+        &self,
+        output: &'ser mut O,
+        codec: C,
+    ) -> Serializer<'ser, C, O>
+    where
+        C: IntCodec,
+        O: ?Sized + Output,
+    {
+        Serializer::with_output(output, *self, codec)
+    }
+
+    /// Determine whether or not the fields of each struct are preceded by
+    /// the number of bytes they take.
+    #[must_use]
+    pub fn struct_framing(&self) -> bool {
+        self.struct_framing
+    }
+
+    /// This function is used to encode a value into a sequence of bytes
+    /// using this configuration.  Since the fingerprint of a type is found
+    /// from its `Deserialize` implementation, values are encoded with
+    /// [`Features::FINGERPRINT`] enabled using
+    /// [`to_bytes_with_fingerprint`] instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::FingerprintUnavailable`] if
+    /// [`Features::FINGERPRINT`] is enabled, and otherwise any error from
+    /// [`to_bytes`].
+    ///
+    /// [`Features::FINGERPRINT`]: struct.Features.html#associatedconstant.FINGERPRINT
+    /// [`to_bytes_with_fingerprint`]: #method.to_bytes_with_fingerprint
+    /// [`Error::FingerprintUnavailable`]: enum.Error.html#variant.FingerprintUnavailable
+    /// [`to_bytes`]: fn.to_bytes.html
+    pub fn to_bytes<T>(
+        &self,
+        value: &T,
+    ) -> Result<Vec<u8>>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        if self.fingerprint {
+            return Err(Error::FingerprintUnavailable);
+        }
+        let mut buffer = Vec::new();
+        serde::Serialize::serialize(value, &mut self.serializer(&mut buffer))?;
+        Ok(buffer)
+    }
+
+    /// This function is used to encode a value into a sequence of bytes
+    /// using this configuration, preceded by the fingerprint of the layout
+    /// of its type if [`Features::FINGERPRINT`] is enabled, so that
+    /// [`from_bytes`] can check it.
+    ///
+    /// # Errors
+    ///
+    /// Any error from [`to_bytes`] is returned, along with any error from
+    /// [`type_fingerprint`] if [`Features::FINGERPRINT`] is enabled.
+    ///
+    /// [`Features::FINGERPRINT`]: struct.Features.html#associatedconstant.FINGERPRINT
+    /// [`from_bytes`]: #method.from_bytes
+    /// [`to_bytes`]: fn.to_bytes.html
+    /// [`type_fingerprint`]: fn.type_fingerprint.html
+    pub fn to_bytes_with_fingerprint<T>(
+        &self,
+        value: &T,
+    ) -> Result<Vec<u8>>
+    where
+        T: serde::Serialize + serde::Deserialize<'static>,
+    {
+        let mut buffer = Vec::new();
+        if self.fingerprint {
+            buffer.extend(&type_fingerprint::<T>()?.to_be_bytes());
+        }
+        serde::Serialize::serialize(value, &mut self.serializer(&mut buffer))?;
+        Ok(buffer)
+    }
+
+    /// Determine whether or not each variant of an enum is identified by a
+    /// hash of its name, rather than by its index.
+    #[must_use]
+    pub fn variant_ids(&self) -> bool {
+        self.variant_ids
+    }
+
+    /// Determine whether or not each variant of an enum is identified by
+    /// its name, rather than by its index.
+    #[must_use]
+    pub fn variant_names(&self) -> bool {
+        self.variant_names
+    }
+
+    /// Return the version of the encoding selected by the configuration.
+    #[must_use]
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// Return a copy of the configuration which encodes characters in the
+    /// given way.
+    #[must_use]
+    pub fn with_char_encoding(
+        mut self,
+        char_encoding: CharEncoding,
+    ) -> Self {
+        self.char_encoding = char_encoding;
+        self
+    }
+
+    /// Return a copy of the configuration which, if the given flag is set,
+    /// encodes each struct whose encoding is the same as that of an earlier
+    /// struct in the same value as a reference to the earlier one, and
+    /// decodes such references back into full copies.  This makes values
+    /// with many repeated parts, which are not shared behind `Rc` or `Arc`,
+    /// much smaller, at the cost of holding back the encoding of each
+    /// outermost struct until it is finished, so that repeats found inside
+    /// it can be replaced.
+    ///
+    /// Each struct is preceded by a varint which is zero if the struct
+    /// follows, or one more than the number of the earlier struct it
+    /// repeats, where structs written in full are numbered from zero in
+    /// the order their encodings end.  Only structs with named fields are
+    /// deduplicated.  The change to the encoding is understood only by the
+    /// deserializer of this crate, not by the other tools which walk
+    /// encodings, such as [`from_bytes_projected`].  How much decoding
+    /// repeats may expand to is [limited].
+    ///
+    /// [`from_bytes_projected`]: fn.from_bytes_projected.html
+    /// [limited]: #method.with_max_replay_ratio
+    #[must_use]
+    pub fn with_deduplication(
+        mut self,
+        deduplication: bool,
+    ) -> Self {
+        self.deduplication = deduplication;
+        self
+    }
+
+    /// Return a copy of the configuration with exactly the given set of
+    /// optional features enabled, selecting the options which change the
+    /// encoding to match, and ignoring any features unknown to this crate.
+    /// Options whose features are not in the set are turned off, and
+    /// characters are encoded in UTF-8 unless
+    /// [`Features::SCALAR_VALUE_CHARS`] is in the set.
+    ///
+    /// [`Features::SCALAR_VALUE_CHARS`]: struct.Features.html#associatedconstant.SCALAR_VALUE_CHARS
+    #[must_use]
+    pub fn with_features(
+        mut self,
+        features: Features,
+    ) -> Self {
+        self.char_encoding = if features.contains(Features::SCALAR_VALUE_CHARS)
+        {
+            CharEncoding::ScalarValue
+        } else {
+            CharEncoding::Utf8
+        };
+        self.deduplication = features.contains(Features::DEDUPLICATION);
+        self.field_ids = features.contains(Features::FIELD_IDS);
+        self.field_names = features.contains(Features::FIELD_NAMES);
+        self.fingerprint = features.contains(Features::FINGERPRINT);
+        self.interning = features.contains(Features::INTERNING);
+        self.self_describing = features.contains(Features::SELF_DESCRIBING);
+        self.struct_framing = features.contains(Features::STRUCT_FRAMING);
+        self.variant_ids = features.contains(Features::VARIANT_IDS);
+        self.variant_names = features.contains(Features::VARIANT_NAMES);
+        self
+    }
+
+    /// Return a copy of the configuration which, if the given flag is set,
+    /// encodes the fields of each struct and struct variant as pairs of an
+    /// identifier and a value, preceded by the number of fields, rather
+    /// than by their positions.  Each identifier is a varint holding a
+    /// 32-bit [FNV-1a] hash of the name of the field, and each value is
+    /// preceded by a varint giving the number of bytes it takes.  Fields
+    /// can then be added, removed, and reordered without breaking the
+    /// decoding of earlier encodings: fields which the type being decoded
+    /// does not have are skipped, and fields missing from the encoding are
+    /// reported to the type as missing, so that `#[serde(default)]` fills
+    /// them in.  Renaming a field changes its identifier, unless its old
+    /// name is kept with `#[serde(rename)]`.
+    ///
+    /// The value of each field is held back until it ends, so that its
+    /// length can be put in front of it, which means integer codecs which
+    /// depend on earlier values cannot be used.  The change to the encoding
+    /// is understood only by the deserializer of this crate, not by the
+    /// other tools which walk encodings, such as
+    /// [`from_bytes_projected`].
+    ///
+    /// [FNV-1a]: http://www.isthe.com/chongo/tech/comp/fnv/
+    /// [`from_bytes_projected`]: fn.from_bytes_projected.html
+    #[must_use]
+    pub fn with_field_ids(
+        mut self,
+        field_ids: bool,
+    ) -> Self {
+        self.field_ids = field_ids;
+        self
+    }
+
+    /// Return a copy of the configuration which, if the given flag is set,
+    /// encodes the fields of each struct and struct variant as pairs of a
+    /// name and a value, in the same way as [`with_field_ids`] does, but
+    /// with the name of each field, encoded as a string, in place of its
+    /// hash.  This makes encodings larger, but easier to read, and free of
+    /// the chance of two names having the same hash.  It takes precedence
+    /// over [`with_field_ids`].
+    ///
+    /// [`with_field_ids`]: #method.with_field_ids
+    #[must_use]
+    pub fn with_field_names(
+        mut self,
+        field_names: bool,
+    ) -> Self {
+        self.field_names = field_names;
+        self
+    }
+
+    /// Return a copy of the configuration which, if the given flag is set,
+    /// writes each field name, if [field names] are enabled, each variant
+    /// name, if [variant names] are enabled, and each map key which is a
+    /// string, only the first time it occurs in an encoding.  Each such string
+    /// is preceded by a varint which is zero if the string follows, or one
+    /// more than the number of the earlier string it repeats, where strings
+    /// written in full are numbered from zero in the order they occur.
+    /// This makes encodings of many structs or maps with the same names
+    /// much smaller.
+    ///
+    /// Interning has no effect with [deduplication] enabled, since strings
+    /// first written inside structs which are replaced by references would
+    /// then be missing.  For the same reason, fields which are skipped
+    /// because their types do not have them must not hold interned strings,
+    /// unless the encoding is also [self-describing].
+    ///
+    /// [field names]: #method.with_field_names
+    /// [variant names]: #method.with_variant_names
+    /// [deduplication]: #method.with_deduplication
+    /// [self-describing]: #method.with_self_describing
+    #[must_use]
+    pub fn with_interning(
+        mut self,
+        interning: bool,
+    ) -> Self {
+        self.interning = interning;
+        self
+    }
+
+    /// Return a copy of the configuration which limits how deeply values
+    /// may be nested inside one another when they are decoded without
+    /// knowing their types, such as into a [`Value`], or skipped, which is
+    /// done only if the encoding is [self-describing].  Decoding stops with
+    /// [`Error::DepthLimitExceeded`] rather than running out of stack on
+    /// an encoding nested too deeply.  The default limit is 128.
+    ///
+    /// [`Value`]: enum.Value.html
+    /// [self-describing]: #method.with_self_describing
+    /// [`Error::DepthLimitExceeded`]: enum.Error.html#variant.DepthLimitExceeded
+    #[must_use]
+    pub fn with_max_depth(
+        mut self,
+        max_depth: usize,
+    ) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Return a copy of the configuration which limits the number of bytes
+    /// serializers may produce to the given size.  Encoding stops with
+    /// [`Error::OutputLimitExceeded`] as soon as the limit would be passed,
+    /// rather than after the whole encoding has been built.  Only the
+    /// bytes of the value count toward the limit, not any fingerprint
+    /// placed before it or bytes already in the serializer's buffer.
+    ///
+    /// [`Error::OutputLimitExceeded`]: enum.Error.html#variant.OutputLimitExceeded
+    #[must_use]
+    pub fn with_max_output_size(
+        mut self,
+        limit: usize,
+    ) -> Self {
+        self.max_output_size = Some(limit);
+        self
+    }
+
+    /// Return a copy of the configuration which limits the number of bytes
+    /// of repeated structs that deserializers decode again, when
+    /// [deduplication] is enabled, to the given multiple of the number of
+    /// bytes of the input decoded so far.  Since a repeated struct may
+    /// itself hold repeats, a small encoding could otherwise decode to a
+    /// value many orders of magnitude larger.  Decoding stops with
+    /// [`Error::ReplayLimitExceeded`] once the limit is passed.  The
+    /// default multiple is 1024.
+    ///
+    /// [deduplication]: #method.with_deduplication
+    /// [`Error::ReplayLimitExceeded`]: enum.Error.html#variant.ReplayLimitExceeded
+    #[must_use]
+    pub fn with_max_replay_ratio(
+        mut self,
+        max_replay_ratio: usize,
+    ) -> Self {
+        self.max_replay_ratio = max_replay_ratio;
+        self
+    }
+
+    /// Return a copy of the configuration which, if the given flag is set,
+    /// lets deserializers decode structs whose encodings end before all of
+    /// their fields, such as structs encoded before fields were added to
+    /// their ends.  The fields left out are reported to the type being
+    /// decoded as missing, so that those marked `#[serde(default)]` are
+    /// given their defaults, while any others still fail to decode.
+    ///
+    /// The end of a struct can only be found if the encoding says where it
+    /// is, which is the case if [struct framing] is enabled, if the
+    /// encoding is [self-describing], or if the struct is the last thing in
+    /// the encoding.  Fields are always given defaults when missing from
+    /// encodings whose fields are identified by [hash] or [name].  Other
+    /// structs missing fields take the values after them as those fields,
+    /// so decoding them usually runs out of bytes, which [`from_bytes`]
+    /// and [`StreamDeserializer`] report as
+    /// [`Error::MissingFieldsUnframed`].
+    ///
+    /// [struct framing]: #method.with_struct_framing
+    /// [self-describing]: #method.with_self_describing
+    /// [hash]: #method.with_field_ids
+    /// [name]: #method.with_field_names
+    /// [`from_bytes`]: #method.from_bytes
+    /// [`StreamDeserializer`]: struct.StreamDeserializer.html
+    /// [`Error::MissingFieldsUnframed`]: enum.Error.html#variant.MissingFieldsUnframed
+    #[must_use]
+    pub fn with_missing_fields(
+        mut self,
+        missing_fields: bool,
+    ) -> Self {
+        self.missing_fields = missing_fields;
+        self
+    }
+
+    /// Return a copy of the configuration which, if the given flag is set,
+    /// precedes every value with a one-byte tag giving its type, so that
+    /// it can be decoded through `deserialize_any`, such as into a
+    /// [`Value`], without knowing its type.  This makes encodings larger,
+    /// but lets tools which don't know the types of values decode them.
+    ///
+    /// The tags are:
+    ///
+    /// | Tag | Value                                                   |
+    /// |-----|---------------------------------------------------------|
+    /// | 1   | `bool`                                                  |
+    /// | 2   | `i8`                                                    |
+    /// | 3   | `i16`                                                   |
+    /// | 4   | `i32`                                                   |
+    /// | 5   | `i64`                                                   |
+    /// | 6   | `i128`                                                  |
+    /// | 7   | `u8`                                                    |
+    /// | 8   | `u16`                                                   |
+    /// | 9   | `u32`                                                   |
+    /// | 10  | `u64`                                                   |
+    /// | 11  | `u128`                                                  |
+    /// | 12  | `f32`                                                   |
+    /// | 13  | `f64`                                                   |
+    /// | 14  | `char`                                                  |
+    /// | 15  | string                                                  |
+    /// | 16  | byte array                                              |
+    /// | 17  | `None` (replacing the usual zero byte)                  |
+    /// | 18  | `Some`, followed by the value (replacing the one byte)  |
+    /// | 19  | unit or unit struct                                     |
+    /// | 20  | sequence                                                |
+    /// | 21  | tuple, followed by its length                           |
+    /// | 22  | map                                                     |
+    /// | 23  | struct, followed by its number of fields                |
+    /// | 24  | enum variant, followed by its index and its content     |
+    /// | 25  | content of a struct variant, followed by its number of  |
+    /// |     | fields                                                  |
+    ///
+    /// The content of a variant is tagged as a unit, tuple, or struct
+    /// variant, or is the tagged value of a newtype variant.  Otherwise the
+    /// encodings following the tags are unchanged, although bytes written
+    /// by [`Raw`] are not tagged.  The change to the encoding is understood
+    /// only by the deserializer of this crate, not by the other tools
+    /// which walk encodings, such as [`from_bytes_projected`].
+    ///
+    /// Since the tags tell how to skip over every value, values which are
+    /// ignored, such as by `IgnoredAny`, are skipped when decoding, as are
+    /// fields at the end of a struct which its type does not have, such as
+    /// fields added by newer writers, and the contents of variants which
+    /// are decoded as the `#[serde(other)]` variant of their enum.
+    ///
+    /// [`Value`]: enum.Value.html
+    /// [`Raw`]: struct.Raw.html
+    /// [`from_bytes_projected`]: fn.from_bytes_projected.html
+    #[must_use]
+    pub fn with_self_describing(
+        mut self,
+        self_describing: bool,
+    ) -> Self {
+        self.self_describing = self_describing;
+        self
+    }
+
+    /// Return a copy of the configuration which, if the given flag is set,
+    /// makes serializers fail with [`Error::FieldSkipped`] when a field of
+    /// a struct is left out, such as by `#[serde(skip_serializing_if)]`.
+    /// Since fields are identified only by their positions, an encoding
+    /// missing a field cannot be decoded, or worse, is decoded wrongly.
+    ///
+    /// [`Error::FieldSkipped`]: enum.Error.html#variant.FieldSkipped
+    #[must_use]
+    pub fn with_shape_validation(
+        mut self,
+        shape_validation: bool,
+    ) -> Self {
+        self.shape_validation = shape_validation;
+        self
+    }
+
+    /// Return a copy of the configuration which, if the given flag is set,
+    /// precedes the fields of each struct, and the contents of each enum
+    /// variant, with a varint giving the number of bytes they take.
+    /// Decoders then skip whatever is left of the fields once they have
+    /// decoded the ones their types have, so that fields added to the end
+    /// of a struct by newer writers are skipped by older readers.  Only
+    /// added fields are skipped, since fields are still identified by their
+    /// positions.  Likewise, the contents of variants added by newer
+    /// writers are skipped by older readers whose enums have a
+    /// `#[serde(other)]` variant to decode them as.
+    ///
+    /// The encoding of each struct is held back until it ends, so that its
+    /// length can be put in front of it, which means integer codecs which
+    /// depend on earlier values cannot be used.  If deduplication is also
+    /// enabled, the length comes before the tag saying whether the struct
+    /// follows or repeats an earlier one, and fields added to structs must
+    /// not hold structs themselves, unless the encoding is also
+    /// [self-describing], since skipping them without decoding them would
+    /// throw off the numbering of the structs which follow.  The change to the
+    /// encoding is understood only by the deserializer of this crate, not
+    /// by the other tools which walk encodings, such as
+    /// [`from_bytes_projected`].
+    ///
+    /// [self-describing]: #method.with_self_describing
+    /// [`from_bytes_projected`]: fn.from_bytes_projected.html
+    #[must_use]
+    pub fn with_struct_framing(
+        mut self,
+        struct_framing: bool,
+    ) -> Self {
+        self.struct_framing = struct_framing;
+        self
+    }
+
+    /// Return a copy of the configuration which, if the given flag is set,
+    /// identifies the variant of each enum value by a varint holding a
+    /// 32-bit [FNV-1a] hash of the name of the variant, rather than by its
+    /// index.  Variants can then be added, removed, and reordered without
+    /// breaking the decoding of earlier encodings, as long as the variants
+    /// in them are kept.  Renaming a variant changes its identifier, unless
+    /// its old name is kept with `#[serde(rename)]`.  Decoding a variant
+    /// whose hash matches none of the variants of the enum fails with
+    /// [`Error::VariantUnknown`], unless the enum has a `#[serde(other)]`
+    /// variant and the encoding is framed or self-describing.
+    ///
+    /// The change to the encoding is understood only by the deserializer of
+    /// this crate, not by the other tools which walk encodings, such as
+    /// [`from_bytes_projected`], and [`peek_variant_index`] then gives the
+    /// hash rather than the index.
+    ///
+    /// [FNV-1a]: http://www.isthe.com/chongo/tech/comp/fnv/
+    /// [`Error::VariantUnknown`]: enum.Error.html#variant.VariantUnknown
+    /// [`from_bytes_projected`]: fn.from_bytes_projected.html
+    /// [`peek_variant_index`]: struct.Deserializer.html#method.peek_variant_index
+    #[must_use]
+    pub fn with_variant_ids(
+        mut self,
+        variant_ids: bool,
+    ) -> Self {
+        self.variant_ids = variant_ids;
+        self
+    }
+
+    /// Return a copy of the configuration which, if the given flag is set,
+    /// identifies the variant of each enum value by its name, encoded as a
+    /// string, in the same way as [`with_variant_ids`] does with the hash
+    /// of the name.  This makes encodings larger, but easier to read, and
+    /// free of the chance of two names having the same hash.  Names are
+    /// [interned] along with field names and map keys, if that is enabled.
+    /// It takes precedence over [`with_variant_ids`].
+    ///
+    /// [`with_variant_ids`]: #method.with_variant_ids
+    /// [interned]: #method.with_interning
+    #[must_use]
+    pub fn with_variant_names(
+        mut self,
+        variant_names: bool,
+    ) -> Self {
+        self.variant_names = variant_names;
+        self
+    }
+
+    /// Return a copy of the configuration selecting the given version of
+    /// the encoding.
+    #[must_use]
+    pub fn with_version(
+        mut self,
+        version: u16,
+    ) -> Self {
+        self.version = version;
+        self
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        to_bytes,
+        Error,
+    };
+
+    #[test]
+    fn char_encodings() {
+        let config =
+            Config::new().with_char_encoding(CharEncoding::ScalarValue);
+        assert_eq!(CharEncoding::ScalarValue, config.char_encoding());
+        for (value, expected) in &[
+            ('X', &[0x58][..]),
+            ('€', &[0xC1, 0x2C][..]),
+            ('💩', &[0x87, 0xE9, 0x29][..]),
+        ] {
+            let encoding = config.to_bytes(value);
+            assert!(encoding.is_ok());
+            let encoding = encoding.unwrap();
+            assert_eq!(*expected, encoding);
+            let decoded: Result<char> = config.from_bytes(&encoding);
+            assert!(decoded.is_ok());
+            assert_eq!(*value, decoded.unwrap());
+        }
+        assert!(matches!(
+            config.from_bytes::<char>(&[0x83, 0xB0, 0x00]),
+            Err(Error::InvalidChar(0xD800))
+        ));
+        assert_eq!(
+            &[0xE2, 0x82, 0xAC][..],
+            Config::new().to_bytes(&'€').unwrap()
+        );
+    }
+
+    #[test]
+    fn default_config_matches_plain_encoding() {
+        let config = Config::default();
+        assert_eq!(FORMAT_VERSION, config.version());
+        assert_eq!(Features::NONE, config.features());
+        let value = (42_u16, String::from("hello"));
+        let encoding = config.to_bytes(&value);
+        assert!(encoding.is_ok());
+        let encoding = encoding.unwrap();
+        assert_eq!(to_bytes(&value).unwrap(), encoding);
+        let decoded: Result<(u16, String)> = config.from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!(value, decoded.unwrap());
+    }
+
+    #[test]
+    fn fingerprint_feature() {
+        let config = Config::new().with_features(Features::FINGERPRINT);
+        assert!(matches!(
+            config.to_bytes(&7_u8),
+            Err(Error::FingerprintUnavailable)
+        ));
+        let encoding = config.to_bytes_with_fingerprint(&7_u8).unwrap();
+        assert_eq!(crate::FINGERPRINT_SIZE + 1, encoding.len());
+        let decoded: Result<u8> = config.from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!(7, decoded.unwrap());
+        assert!(matches!(
+            config.from_bytes::<i8>(&encoding),
+            Err(Error::FingerprintMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn output_limit() {
+        let config = Config::new().with_max_output_size(4);
+        assert_eq!(Some(4), config.max_output_size());
+        let encoding = config.to_bytes(&(1_u8, String::from("ab")));
+        assert!(encoding.is_ok());
+        assert_eq!(&[0x01, 0x02, b'a', b'b'][..], encoding.unwrap());
+        assert!(matches!(
+            config.to_bytes(&(1_u8, String::from("abc"))),
+            Err(Error::OutputLimitExceeded(4))
+        ));
+        assert!(matches!(
+            config.to_bytes(&vec![0_u64; 1000]),
+            Err(Error::OutputLimitExceeded(4))
+        ));
+        let mut buffer = vec![0xFF; 8];
+        let mut serializer = config.serializer(&mut buffer);
+        assert!(serde::Serialize::serialize(&0x0FFF_FFFF_u32, &mut serializer)
+            .is_ok());
+        assert!(matches!(
+            serde::Serialize::serialize(&0_u8, &mut serializer),
+            Err(Error::OutputLimitExceeded(4))
+        ));
+        assert_eq!(12, buffer.len());
+        let fingerprinted = config.with_features(Features::FINGERPRINT);
+        assert!(fingerprinted
+            .to_bytes_with_fingerprint(&[1_u8, 2, 3, 4])
+            .is_ok());
+    }
+
+    #[test]
+    fn unknown_features_are_left_out() {
+        assert_eq!(Features::ALL, Features::from_bits_truncate(0xFFFF_FFFF));
+        let config = Config::new().with_features(Features(0x8000_0001));
+        assert_eq!(Features::FINGERPRINT, config.features());
+    }
+
+    #[test]
+    fn features_select_options() {
+        let features = Features::STRUCT_FRAMING
+            .union(Features::FIELD_NAMES)
+            .union(Features::SCALAR_VALUE_CHARS);
+        let config = Config::new().with_deduplication(true);
+        let config = config.with_features(features);
+        assert!(config.struct_framing());
+        assert!(config.field_names());
+        assert!(!config.field_ids());
+        assert!(!config.deduplication());
+        assert_eq!(CharEncoding::ScalarValue, config.char_encoding());
+        assert_eq!(features, config.features());
+        let config = Config::new()
+            .with_self_describing(true)
+            .with_variant_names(true)
+            .with_interning(true);
+        assert_eq!(
+            Features::SELF_DESCRIBING
+                .union(Features::VARIANT_NAMES)
+                .union(Features::INTERNING),
+            config.features()
+        );
+        assert_eq!(config, Config::new().with_features(config.features()));
+    }
+}
@@ -0,0 +1,341 @@
+/// This type builds the encoding of simple values in a `const` context, so
+/// that protocol constants and magic preambles can be written as `const`
+/// byte arrays, computed (and checked) at compile time.  It holds at most
+/// `N` bytes; pushing more than that fails to compile when used in a
+/// `const` item, or panics otherwise.
+///
+/// Values are encoded exactly as [`to_bytes`] would encode them, so the
+/// results can be decoded with [`from_bytes`] or compared against the
+/// output of the serializer.
+///
+/// [`to_bytes`]: fn.to_bytes.html
+/// [`from_bytes`]: fn.from_bytes.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_bytes,
+///     ConstBytes,
+/// };
+///
+/// const PREAMBLE: [u8; 7] = ConstBytes::<16>::new()
+///     .push_raw(b"RHY")
+///     .push_u64(300)
+///     .push_str("!")
+///     .into_array();
+///
+/// assert_eq!(b"RHY\x82\x2C\x01!", &PREAMBLE);
+/// let (version, tag): (u64, &str) = from_bytes(&PREAMBLE[3..]).unwrap();
+/// assert_eq!((300, "!"), (version, tag));
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConstBytes<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> ConstBytes<N> {
+    /// Return the bytes encoded so far.
+    #[must_use]
+    pub const fn as_slice(&self) -> &[u8] {
+        self.bytes.split_at(self.len).0
+    }
+
+    /// Return the bytes encoded so far, as an array of exactly `M` bytes.
+    ///
+    /// # Panics
+    ///
+    /// This function panics (or fails to compile, in a `const` context) if
+    /// the number of bytes encoded is not `M`.
+    #[must_use]
+    pub const fn into_array<const M: usize>(self) -> [u8; M] {
+        assert!(self.len == M, "encoding length does not match array length");
+        let mut array = [0; M];
+        let mut i = 0;
+        while i < M {
+            array[i] = self.bytes[i];
+            i += 1;
+        }
+        array
+    }
+
+    /// Determine whether or not no bytes have been encoded so far.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the number of bytes encoded so far.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Return a new builder with nothing encoded in it.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            bytes: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Append the encoding of a `bool`.
+    #[must_use]
+    pub const fn push_bool(
+        self,
+        value: bool,
+    ) -> Self {
+        self.push_byte(value as u8)
+    }
+
+    const fn push_byte(
+        mut self,
+        byte: u8,
+    ) -> Self {
+        self.bytes[self.len] = byte;
+        self.len += 1;
+        self
+    }
+
+    /// Append the encoding of a byte string, which is its length followed
+    /// by its bytes.
+    #[must_use]
+    pub const fn push_bytes(
+        self,
+        bytes: &[u8],
+    ) -> Self {
+        self.push_u64(bytes.len() as u64).push_raw(bytes)
+    }
+
+    /// Append the encoding of a `char`, which is its UTF-8 encoding.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn push_char(
+        self,
+        value: char,
+    ) -> Self {
+        let code = value as u32;
+        if code < 0x80 {
+            self.push_byte(code as u8)
+        } else if code < 0x800 {
+            self.push_byte(0xC0 | (code >> 6) as u8)
+                .push_byte(0x80 | (code & 0x3F) as u8)
+        } else if code < 0x10000 {
+            self.push_byte(0xE0 | (code >> 12) as u8)
+                .push_byte(0x80 | ((code >> 6) & 0x3F) as u8)
+                .push_byte(0x80 | (code & 0x3F) as u8)
+        } else {
+            self.push_byte(0xF0 | (code >> 18) as u8)
+                .push_byte(0x80 | ((code >> 12) & 0x3F) as u8)
+                .push_byte(0x80 | ((code >> 6) & 0x3F) as u8)
+                .push_byte(0x80 | (code & 0x3F) as u8)
+        }
+    }
+
+    /// Append the encoding of an `f32`.
+    #[must_use]
+    pub const fn push_f32(
+        self,
+        value: f32,
+    ) -> Self {
+        self.push_raw(&value.to_bits().to_be_bytes())
+    }
+
+    /// Append the encoding of an `f64`.
+    #[must_use]
+    pub const fn push_f64(
+        self,
+        value: f64,
+    ) -> Self {
+        self.push_raw(&value.to_bits().to_be_bytes())
+    }
+
+    /// Append the encoding of an `i8`.
+    #[must_use]
+    #[allow(clippy::cast_sign_loss)]
+    pub const fn push_i8(
+        self,
+        value: i8,
+    ) -> Self {
+        self.push_byte(value as u8)
+    }
+
+    /// Append the encoding of an `i64`, which is also the encoding of an
+    /// `i16` or `i32` with the same value.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn push_i64(
+        self,
+        value: i64,
+    ) -> Self {
+        let sign = if value < 0 {
+            0x40
+        } else {
+            0x00
+        };
+        let magnitude = value.unsigned_abs();
+        let mut groups = 0;
+        let mut first = magnitude;
+        while first & !0x3F != 0 {
+            first >>= 7;
+            groups += 1;
+        }
+        let more = if groups == 0 {
+            0x00
+        } else {
+            0x80
+        };
+        let mut builder = self.push_byte(first as u8 | sign | more);
+        while groups > 0 {
+            groups -= 1;
+            let more = if groups == 0 {
+                0x00
+            } else {
+                0x80
+            };
+            builder = builder
+                .push_byte(((magnitude >> (7 * groups)) & 0x7F) as u8 | more);
+        }
+        builder
+    }
+
+    /// Append the given bytes exactly as they are, such as to add a magic
+    /// number which is not itself an encoded value.
+    #[must_use]
+    pub const fn push_raw(
+        mut self,
+        bytes: &[u8],
+    ) -> Self {
+        let mut i = 0;
+        while i < bytes.len() {
+            self = self.push_byte(bytes[i]);
+            i += 1;
+        }
+        self
+    }
+
+    /// Append the encoding of a string, which is its length in bytes
+    /// followed by its UTF-8 encoding.
+    #[must_use]
+    pub const fn push_str(
+        self,
+        value: &str,
+    ) -> Self {
+        self.push_bytes(value.as_bytes())
+    }
+
+    /// Append the encoding of a `u8`.
+    #[must_use]
+    pub const fn push_u8(
+        self,
+        value: u8,
+    ) -> Self {
+        self.push_byte(value)
+    }
+
+    /// Append the encoding of a `u64`, which is also the encoding of a
+    /// `u16`, `u32`, or `usize` with the same value.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn push_u64(
+        self,
+        value: u64,
+    ) -> Self {
+        let mut groups = 0;
+        while groups < 9 && value >> (7 * (groups + 1)) != 0 {
+            groups += 1;
+        }
+        let mut builder = self;
+        loop {
+            let more = if groups == 0 {
+                0x00
+            } else {
+                0x80
+            };
+            builder = builder
+                .push_byte(((value >> (7 * groups)) & 0x7F) as u8 | more);
+            if groups == 0 {
+                break builder;
+            }
+            groups -= 1;
+        }
+    }
+}
+
+impl<const N: usize> Default for ConstBytes<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::non_ascii_literal)]
+mod tests {
+    use super::*;
+    use crate::to_bytes;
+
+    #[test]
+    fn integers_match_serializer() {
+        for value in
+            &[0, 1, 63, 64, 127, 128, 9001, u64::from(u32::MAX), u64::MAX]
+        {
+            assert_eq!(
+                to_bytes(value).unwrap(),
+                ConstBytes::<10>::new().push_u64(*value).as_slice()
+            );
+        }
+        for value in
+            &[0, 42, -42, 63, -64, 100, -4000, 70_000, i64::MIN, i64::MAX]
+        {
+            assert_eq!(
+                to_bytes(value).unwrap(),
+                ConstBytes::<10>::new().push_i64(*value).as_slice()
+            );
+        }
+        assert_eq!(
+            to_bytes(&(-5_i8, 200_u8)).unwrap(),
+            ConstBytes::<2>::new().push_i8(-5).push_u8(200).as_slice()
+        );
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn other_values_match_serializer() {
+        let value = (true, 'A', 'é', '€', '😀', 1.5_f32, -0.25_f64, "hi");
+        let builder = ConstBytes::<64>::new()
+            .push_bool(true)
+            .push_char('A')
+            .push_char('é')
+            .push_char('€')
+            .push_char('😀')
+            .push_f32(1.5)
+            .push_f64(-0.25)
+            .push_str("hi");
+        assert_eq!(to_bytes(&value).unwrap(), builder.as_slice());
+        assert_eq!(
+            &[0x03, 0x01, 0x02, 0x03][..],
+            ConstBytes::<4>::new().push_bytes(&[1, 2, 3]).as_slice()
+        );
+    }
+
+    #[test]
+    fn const_arrays() {
+        const MAGIC: [u8; 3] = ConstBytes::<8>::new()
+            .push_raw(&[0xCA, 0xFE])
+            .push_i64(-1)
+            .into_array();
+        assert_eq!([0xCA, 0xFE, 0x41], MAGIC);
+        let builder = ConstBytes::<8>::new();
+        assert!(builder.is_empty());
+        assert_eq!(1, builder.push_bool(false).len());
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn overflow_panics() {
+        let _ = ConstBytes::<1>::new().push_u64(128);
+    }
+}
@@ -1,17 +1,69 @@
 use super::{
+    int_codec::{
+        MAX_INT128_SIZE,
+        MAX_INT_SIZE,
+    },
+    name_hash::name_hash,
+    projection::project,
+    skip::Skipper,
+    tag::Tag,
+    CharEncoding,
+    Config,
     Error,
+    Input,
+    IntCodec,
     Result,
+    Schema,
+    Varint,
 };
-use std::marker::PhantomData;
+use alloc::{
+    borrow::Cow,
+    string::String,
+    vec::Vec,
+};
+use core::marker::PhantomData;
+
+// This is the most bytes of a string or byte string copied out of an input
+// at once, so that a corrupted length does not make the deserializer
+// allocate far more memory than the input holds.
+const CHUNK_SIZE: usize = 65536;
 
 /// This type implements [`serde::Deserializer`] in order to decode data
-/// from a sequence of bytes.
+/// from an [`Input`], which is a sequence of bytes unless another is given,
+/// with integers decoded by the given [`IntCodec`].  Strings and byte
+/// strings are borrowed from inputs held in memory, and copied out of
+/// others, such as readers.
 ///
 /// [`serde::Deserializer`]:
 /// https://docs.rs/serde/1.0/serde/trait.Deserializer.html
-pub struct Deserializer<'de> {
-    buffer: &'de [u8],
+/// [`Input`]: trait.Input.html
+/// [`IntCodec`]: trait.IntCodec.html
+pub struct Deserializer<'de, C = Varint, I = &'de [u8]> {
+    codec: C,
+    config: Config,
+    // This is how many more levels values decoded without knowing their
+    // types may be nested.
+    depth: usize,
+    // This is the offset at which the value being decoded ends, if it is
+    // preceded by its length, such as a framed struct.
+    end: Option<usize>,
+    input: I,
+    interned: Vec<Cow<'de, str>>,
+    key: bool,
     offset: usize,
+    // These are the bytes of the structs being remembered for
+    // deduplication, read from an input not held in memory, along with how
+    // many of those structs are being decoded.
+    recorded: Vec<u8>,
+    recording: usize,
+    // These are the bytes of a struct being decoded again, because it is
+    // repeated, along with how many of them have been decoded.
+    replay: Option<(Cow<'de, [u8]>, usize)>,
+    // This is how many bytes of repeated structs have been decoded again,
+    // and how many may be, which is set when the outermost repeat starts.
+    replay_limit: usize,
+    replayed: usize,
+    subtrees: Option<Vec<Cow<'de, [u8]>>>,
 }
 
 impl<'de> Deserializer<'de> {
@@ -23,6 +75,36 @@ impl<'de> Deserializer<'de> {
         StreamDeserializer {
             de: self,
             offset,
+            last_size: 0,
+            output: PhantomData,
+        }
+    }
+
+    /// Turn the given deserializer into an iterator which deserializes a
+    /// stream of structs described by the given schema, as values of type
+    /// `T`, leaving out the ones for which the given predicate returns
+    /// `false`.  The predicate is given each struct decoded as a projection
+    /// type `P`, having only some of the fields of the struct (see
+    /// [`from_bytes_projected`]), so only the fields needed to decide
+    /// whether to keep a struct are decoded.  Structs left out are skipped
+    /// over without being decoded any further.
+    ///
+    /// [`from_bytes_projected`]: fn.from_bytes_projected.html
+    #[must_use]
+    pub fn iterate_where<T, P, F>(
+        self,
+        record: Schema,
+        predicate: F,
+    ) -> FilteredStreamDeserializer<'de, T, P, F>
+    where
+        F: FnMut(&P) -> bool,
+    {
+        let offset = self.offset;
+        FilteredStreamDeserializer {
+            de: self,
+            offset,
+            record,
+            predicate,
             output: PhantomData,
         }
     }
@@ -30,10 +112,197 @@ impl<'de> Deserializer<'de> {
     /// Return a new deserializer that deserializes from the given buffer.
     #[must_use]
     pub fn new(buffer: &'de [u8]) -> Self {
-        Self {
-            buffer,
-            offset: 0,
+        Self::with_config(buffer, Config::new())
+    }
+
+    pub(crate) fn with_config(
+        buffer: &'de [u8],
+        config: Config,
+    ) -> Self {
+        Self::with_codec(buffer, config, Varint)
+    }
+
+    // Return where the deserializer is between values, so that it can go
+    // back there with `rewind` if the next value cannot be decoded.
+    fn checkpoint(&self) -> Checkpoint<'de> {
+        Checkpoint {
+            input: self.input,
+            interned: self.interned.len(),
+            offset: self.offset,
+            replayed: self.replayed,
+            subtrees: self.subtrees.as_ref().map_or(0, Vec::len),
+        }
+    }
+
+    // Go back to where the deserializer was when the given checkpoint was
+    // made, forgetting any names and structs remembered since then.
+    fn rewind(
+        &mut self,
+        checkpoint: Checkpoint<'de>,
+    ) {
+        self.input = checkpoint.input;
+        self.interned.truncate(checkpoint.interned);
+        self.offset = checkpoint.offset;
+        self.replayed = checkpoint.replayed;
+        if let Some(subtrees) = &mut self.subtrees {
+            subtrees.truncate(checkpoint.subtrees);
+        }
+    }
+}
+
+// This is where a deserializer is between values, as returned by
+// `Deserializer::checkpoint`.
+#[derive(Clone, Copy)]
+struct Checkpoint<'de> {
+    input: &'de [u8],
+    interned: usize,
+    offset: usize,
+    replayed: usize,
+    subtrees: usize,
+}
+
+impl<'de, C> Deserializer<'de, C>
+where
+    C: IntCodec,
+{
+    /// Return the number of bytes left for this deserializer to
+    /// deserialize.
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.available().map_or(0, <[u8]>::len)
+    }
+
+    /// Return the index of the variant of the enum value which is next
+    /// to be deserialized, without consuming it, so that the deserializer
+    /// can still be used to deserialize the whole value afterwards.  If
+    /// variants are identified by [name] or [hash], the hash of the name of
+    /// the variant is returned instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends before the
+    /// variant index does, or [`Error::IntegerOverflow`] if the variant
+    /// index is too large to be one.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+    /// [name]: struct.Config.html#method.with_variant_names
+    /// [hash]: struct.Config.html#method.with_variant_ids
+    pub fn peek_variant_index(&self) -> Result<u32>
+    where
+        C: Clone,
+    {
+        let mut peeker = Deserializer {
+            codec: self.codec.clone(),
+            config: self.config,
+            depth: self.depth,
+            end: self.end,
+            input: self.input,
+            interned: self.interned.clone(),
+            key: false,
+            offset: self.offset,
+            recorded: Vec::new(),
+            recording: 0,
+            replay: self.replay.clone(),
+            replay_limit: self.replay_limit,
+            replayed: self.replayed,
+            subtrees: None,
+        };
+        peeker.expect_tag(Tag::Variant)?;
+        if peeker.config.variant_names() {
+            Ok(name_hash(&peeker.parse_name()?))
+        } else {
+            peeker.parse_u32()
+        }
+    }
+
+    pub(crate) fn parse_bytes(&mut self) -> Result<&'de [u8]> {
+        let len = self.parse_usize()?;
+        self.borrow(len)
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn parse_str(&mut self) -> Result<&'de str> {
+        core::str::from_utf8(self.parse_bytes()?)
+            .map_err(|source| Error::InvalidUtf8(Some(source)))
+    }
+}
+
+impl<'de, C, I> Deserializer<'de, C, I>
+where
+    C: IntCodec,
+    I: Input<'de>,
+{
+    /// Return the configuration used by the deserializer.
+    #[must_use]
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    /// This function is used to deserialize a sequence, such as a `Vec` or
+    /// `HashSet`, one element at a time, handing each element to the given
+    /// function, without ever holding the whole sequence in memory.  The
+    /// number of elements is returned.
+    ///
+    /// # Errors
+    ///
+    /// Any of the errors returned by [`from_bytes`] may be returned from
+    /// deserializing the length of the sequence or any of its elements.
+    /// Any error returned by the given function stops the deserialization
+    /// and is passed through.
+    ///
+    /// [`from_bytes`]: fn.from_bytes.html
+    pub fn for_each_element<T, F>(
+        &mut self,
+        mut f: F,
+    ) -> Result<usize>
+    where
+        T: serde::Deserialize<'de>,
+        F: FnMut(T) -> Result<()>,
+    {
+        self.expect_tag(Tag::Seq)?;
+        let len = self.parse_usize()?;
+        for _ in 0..len {
+            f(T::deserialize(&mut *self)?)?;
+        }
+        Ok(len)
+    }
+
+    /// This function is used to deserialize a map, such as a `HashMap` or
+    /// `BTreeMap`, one entry at a time, handing each key and value to the
+    /// given function, without ever holding the whole map in memory.  The
+    /// number of entries is returned.
+    ///
+    /// # Errors
+    ///
+    /// Any of the errors returned by [`from_bytes`] may be returned from
+    /// deserializing the length of the map or any of its keys or values.
+    /// Any error returned by the given function stops the deserialization
+    /// and is passed through.
+    ///
+    /// [`from_bytes`]: fn.from_bytes.html
+    pub fn for_each_entry<K, V, F>(
+        &mut self,
+        mut f: F,
+    ) -> Result<usize>
+    where
+        K: serde::Deserialize<'de>,
+        V: serde::Deserialize<'de>,
+        F: FnMut(K, V) -> Result<()>,
+    {
+        self.expect_tag(Tag::Map)?;
+        let len = self.parse_usize()?;
+        for _ in 0..len {
+            let key = K::deserialize(&mut *self)?;
+            f(key, V::deserialize(&mut *self)?)?;
         }
+        Ok(len)
+    }
+
+    // Give back the input, left just past the bytes deserialized so far.
+    #[cfg(feature = "std")]
+    pub(crate) fn into_input(self) -> I {
+        self.input
     }
 
     /// Return the number of bytes deserialized so far by this deserializer.
@@ -42,238 +311,865 @@ impl<'de> Deserializer<'de> {
         self.offset
     }
 
-    fn parse_bool(&mut self) -> Result<bool> {
-        self.buffer.iter().next().map_or(Err(Error::ValueTruncated), |byte| {
-            self.buffer = &self.buffer[1..];
-            self.offset += 1;
-            Ok(*byte != 0)
-        })
+    pub(crate) fn with_codec(
+        input: I,
+        config: Config,
+        codec: C,
+    ) -> Self {
+        Self {
+            codec,
+            config,
+            depth: config.max_depth(),
+            end: None,
+            input,
+            interned: Vec::new(),
+            key: false,
+            offset: 0,
+            recorded: Vec::new(),
+            recording: 0,
+            replay: None,
+            replay_limit: 0,
+            replayed: 0,
+            subtrees: if config.deduplication() {
+                Some(Vec::new())
+            } else {
+                None
+            },
+        }
+    }
+
+    // Decode a struct encoded with deduplication enabled, which is preceded
+    // by a tag saying whether the struct follows or repeats an earlier one.
+    // Structs which follow are remembered, unless they are themselves
+    // inside a repeat, so that later repeats can be decoded from them.
+    #[allow(clippy::cast_possible_truncation)]
+    fn deserialize_deduplicated<V>(
+        &mut self,
+        fields: Fields,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let tag = self.parse_u64(None)?;
+        if tag == 0 {
+            if self.replay.is_some() {
+                return self.visit_fields(fields, visitor);
+            }
+            // The struct is borrowed from an input held in memory, and
+            // otherwise recorded as it is read.
+            let start = self.input.as_slice();
+            let recorded = self.recorded.len();
+            if start.is_none() {
+                self.recording += 1;
+            }
+            let value = self.visit_fields(fields, visitor);
+            let subtree = if let Some(start) = start {
+                let rest = self.input.as_slice().map_or(0, <[u8]>::len);
+                Cow::Borrowed(&start[..start.len() - rest])
+            } else {
+                self.recording -= 1;
+                let subtree = self.recorded[recorded..].to_vec();
+                if self.recording == 0 {
+                    self.recorded.clear();
+                }
+                Cow::Owned(subtree)
+            };
+            let value = value?;
+            if let Some(subtrees) = &mut self.subtrees {
+                subtrees.push(subtree);
+            }
+            return Ok(value);
+        }
+        let subtree = self
+            .subtrees
+            .as_ref()
+            .and_then(|subtrees| subtrees.get((tag - 1) as usize))
+            .cloned()
+            .ok_or(Error::InvalidReference(tag))?;
+        // Repeats may hold repeats, so the bytes decoded again are limited
+        // in proportion to the bytes of the input, to keep a small encoding
+        // from decoding to a huge value.
+        if self.replay.is_none() {
+            self.replay_limit =
+                self.offset.saturating_mul(self.config.max_replay_ratio());
+        }
+        self.replayed = self.replayed.saturating_add(subtree.len());
+        if self.replayed > self.replay_limit {
+            return Err(Error::ReplayLimitExceeded(self.replay_limit));
+        }
+        // The struct is decoded on its own, outside of the value being
+        // decoded, and then the input picks up where it left off.
+        let replay = self.replay.replace((subtree, 0));
+        let end = self.end.take();
+        let offset = self.offset;
+        let result = self.visit_fields(fields, visitor);
+        self.replay = replay;
+        self.end = end;
+        self.offset = offset;
+        result
+    }
+
+    // Decode the tag which precedes the next value, if the encoding is
+    // self-describing, and visit the value it says follows.
+    fn deserialize_tagged<V>(
+        &mut self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let byte = self.parse_u8()?;
+        let tag = Tag::from_byte(byte).ok_or(Error::UnexpectedTag(byte))?;
+        match tag {
+            Tag::Some
+            | Tag::Seq
+            | Tag::Tuple
+            | Tag::Map
+            | Tag::Struct
+            | Tag::Variant
+            | Tag::StructVariant => {
+                self.nested(|de| de.visit_tagged(tag, visitor))
+            },
+            _ => self.visit_tagged(tag, visitor),
+        }
+    }
+
+    // Visit the value which follows the given tag in a self-describing
+    // encoding.
+    fn visit_tagged<V>(
+        &mut self,
+        tag: Tag,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match tag {
+            Tag::Bool => visitor.visit_bool(self.parse_bool()?),
+            Tag::I8 => visitor.visit_i8(self.parse_i8()?),
+            Tag::I16 => visitor.visit_i16(self.parse_i16()?),
+            Tag::I32 => visitor.visit_i32(self.parse_i32()?),
+            Tag::I64 => visitor.visit_i64(self.parse_i64(None)?),
+            Tag::I128 => visitor.visit_i128(self.parse_i128()?),
+            Tag::U8 => visitor.visit_u8(self.parse_u8()?),
+            Tag::U16 => visitor.visit_u16(self.parse_u16()?),
+            Tag::U32 => visitor.visit_u32(self.parse_u32()?),
+            Tag::U64 => visitor.visit_u64(self.parse_u64(None)?),
+            Tag::U128 => visitor.visit_u128(self.parse_u128()?),
+            Tag::F32 => visitor.visit_f32(self.parse_f32()?),
+            Tag::F64 => visitor.visit_f64(self.parse_f64()?),
+            Tag::Char => visitor.visit_char(self.parse_char()?),
+            Tag::Str => visit_text(visitor, self.parse_string()?),
+            Tag::Bytes => visit_blob(visitor, self.parse_blob()?),
+            Tag::None => visitor.visit_none(),
+            Tag::Some => visitor.visit_some(self),
+            Tag::Unit => visitor.visit_unit(),
+            Tag::Seq | Tag::Tuple => {
+                let len = self.parse_usize()?;
+                self.visit_elements(len, visitor)
+            },
+            Tag::Map => {
+                let len = self.parse_usize()?;
+                self.visit_entries(len, visitor)
+            },
+            Tag::Struct => {
+                let fields = Fields {
+                    len: self.parse_usize()?,
+                    extra: 0,
+                    names: None,
+                };
+                self.framed(|de| de.visit_struct(fields, visitor))
+            },
+            Tag::Variant => self.visit_variant(None, visitor),
+            Tag::StructVariant => {
+                let fields = Fields {
+                    len: self.parse_usize()?,
+                    extra: 0,
+                    names: None,
+                };
+                self.visit_fields(fields, visitor)
+            },
+        }
+    }
+
+    // Decode the tag which precedes the next value, if the encoding is
+    // self-describing, and check that it is the expected one.
+    fn expect_tag(
+        &mut self,
+        tag: Tag,
+    ) -> Result<()> {
+        if self.config.self_describing() {
+            let byte = self.parse_u8()?;
+            if byte != tag as u8 {
+                return Err(Error::UnexpectedTag(byte));
+            }
+        }
+        Ok(())
+    }
+
+    // Decode the tag and number of fields which precede the next struct, if
+    // the encoding is self-describing, and check that there are at least
+    // the expected number of fields, unless fields are identified or may be
+    // missing.  The number of extra fields, such as ones added to the struct
+    // by a newer writer, is returned along with the names of the fields, so
+    // that they can be skipped, or the number of fields is cut down to the
+    // number encoded, so that the rest are reported as missing.
+    fn expect_fields(
+        &mut self,
+        tag: Tag,
+        names: &'static [&'static str],
+    ) -> Result<Fields> {
+        let mut fields = Fields {
+            len: names.len(),
+            extra: 0,
+            names: Some(names),
+        };
+        if self.config.self_describing() {
+            self.expect_tag(tag)?;
+            let actual = self.parse_usize()?;
+            if !self.config.identifies_fields() {
+                if actual >= fields.len {
+                    fields.extra = actual - fields.len;
+                } else if self.config.missing_fields() {
+                    fields.len = actual;
+                } else {
+                    return Err(Error::LengthMismatch {
+                        expected: fields.len,
+                        actual,
+                    });
+                }
+            }
+        }
+        Ok(fields)
+    }
+
+    // Decode the tag and number of elements which precede the next value,
+    // if the encoding is self-describing, and check that they are the
+    // expected ones.
+    fn expect_tag_with_len(
+        &mut self,
+        tag: Tag,
+        len: usize,
+    ) -> Result<()> {
+        if self.config.self_describing() {
+            self.expect_tag(tag)?;
+            let actual = self.parse_usize()?;
+            if actual != len {
+                return Err(Error::LengthMismatch {
+                    expected: len,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Decode the fields of a struct using the given function, limited to
+    // the number of bytes which precede them, if struct framing is enabled,
+    // and then skip whatever is left of them, such as fields added by newer
+    // writers.
+    fn framed<T, F>(
+        &mut self,
+        decode: F,
+    ) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        if !self.config.struct_framing() {
+            return decode(self);
+        }
+        let len = self.parse_usize()?;
+        self.within(len, decode)
+    }
+
+    // Decode a value holding other values using the given function, one
+    // level deeper than the value holding it, failing if that is deeper
+    // than the configured limit.
+    fn nested<T, F>(
+        &mut self,
+        decode: F,
+    ) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        self.depth = self
+            .depth
+            .checked_sub(1)
+            .ok_or(Error::DepthLimitExceeded(self.config.max_depth()))?;
+        let result = decode(self);
+        self.depth += 1;
+        result
+    }
+
+    // Pass over the given number of bytes, which must all be available.
+    fn advance(
+        &mut self,
+        len: usize,
+    ) -> Result<()> {
+        match &mut self.replay {
+            Some((_, position)) => *position += len,
+            None => self.input.skip_bytes(len)?,
+        }
+        self.offset += len;
+        Ok(())
+    }
+
+    // Determine whether or not there is nothing left to decode, either of
+    // the input or of the value being decoded, if its length is known.
+    pub(crate) fn at_end(&mut self) -> Result<bool> {
+        if self.end == Some(self.offset) {
+            return Ok(true);
+        }
+        match &self.replay {
+            Some((replay, position)) => Ok(*position == replay.len()),
+            None => self.input.at_end(),
+        }
+    }
+
+    // Return the bytes left to decode, if they are held in memory, up to
+    // the end of the value being decoded, if its length is known.
+    fn available(&self) -> Option<&[u8]> {
+        let bytes = match &self.replay {
+            Some((replay, position)) => &replay[*position..],
+            None => self.input.as_slice()?,
+        };
+        Some(self.limited(bytes))
+    }
+
+    // Take the given number of bytes, borrowed from the input, which must
+    // be held in memory.
+    fn borrow(
+        &mut self,
+        len: usize,
+    ) -> Result<&'de [u8]> {
+        self.check_limit(len)?;
+        let bytes = self
+            .borrowable()
+            .and_then(|bytes| bytes.get(..len))
+            .ok_or(Error::ValueTruncated)?;
+        self.advance(len)?;
+        Ok(bytes)
+    }
+
+    // Return the bytes left to decode, if they can be borrowed for as long
+    // as the input.
+    fn borrowable(&self) -> Option<&'de [u8]> {
+        match &self.replay {
+            Some((Cow::Borrowed(replay), position)) => {
+                Some(&replay[*position..])
+            },
+            Some((Cow::Owned(_), _)) => None,
+            None => self.input.as_slice(),
+        }
+    }
+
+    // Check that the given number of bytes do not go past the end of the
+    // value being decoded, if its length is known.
+    fn check_limit(
+        &self,
+        len: usize,
+    ) -> Result<()> {
+        match self.end {
+            Some(end) if end - self.offset < len => Err(Error::ValueTruncated),
+            _ => Ok(()),
+        }
+    }
+
+    // Decode an integer using the given function, which is given the bytes
+    // left to decode if they are held in memory, or otherwise as many bytes
+    // as it needs, read one at a time until it no longer finds them
+    // truncated, up to the given number of bytes.
+    fn decode_int<T, F>(
+        &mut self,
+        max_len: usize,
+        mut decode: F,
+    ) -> Result<T>
+    where
+        F: FnMut(&mut C, &[u8]) -> Result<(T, usize)>,
+    {
+        let bytes = match &self.replay {
+            Some((replay, position)) => Some(&replay[*position..]),
+            None => self.input.as_slice(),
+        };
+        if let Some(bytes) = bytes {
+            let bytes = self.limited(bytes);
+            let (value, len) = decode(&mut self.codec, bytes)?;
+            self.advance(len)?;
+            return Ok(value);
+        }
+        let mut bytes = [0; MAX_INT128_SIZE];
+        for len in 1..=max_len {
+            self.read(&mut bytes[len - 1..len])?;
+            match decode(&mut self.codec, &bytes[..len]) {
+                Err(Error::ValueTruncated) => {},
+                result => return result.map(|(value, _)| value),
+            }
+        }
+        Err(Error::IntegerOverflow)
+    }
+
+    // Cut the given bytes off at the end of the value being decoded, if its
+    // length is known.
+    fn limited<'a>(
+        &self,
+        bytes: &'a [u8],
+    ) -> &'a [u8] {
+        match self.end {
+            Some(end) => bytes.get(..end - self.offset).unwrap_or(bytes),
+            None => bytes,
+        }
+    }
+
+    // Fill the given buffer with the next bytes of the input, for values
+    // which take a fixed number of bytes.
+    fn read(
+        &mut self,
+        bytes: &mut [u8],
+    ) -> Result<()> {
+        self.check_limit(bytes.len())?;
+        if let Some((replay, position)) = &mut self.replay {
+            let source = replay
+                .get(*position..)
+                .and_then(|replay| replay.get(..bytes.len()))
+                .ok_or(Error::ValueTruncated)?;
+            bytes.copy_from_slice(source);
+            *position += bytes.len();
+        } else {
+            self.input.read_bytes(bytes)?;
+            if self.recording > 0 {
+                self.recorded.extend_from_slice(bytes);
+            }
+        }
+        self.offset += bytes.len();
+        Ok(())
+    }
+
+    // Pass over the given number of bytes.
+    fn skip(
+        &mut self,
+        len: usize,
+    ) -> Result<()> {
+        self.check_limit(len)?;
+        if let Some(bytes) = self.available() {
+            if bytes.len() < len {
+                return Err(Error::ValueTruncated);
+            }
+            return self.advance(len);
+        }
+        if self.recording == 0 {
+            self.input.skip_bytes(len)?;
+            self.offset += len;
+            return Ok(());
+        }
+        let mut buffer = [0; 256];
+        let mut len = len;
+        while len > 0 {
+            let chunk = len.min(buffer.len());
+            self.read(&mut buffer[..chunk])?;
+            len -= chunk;
+        }
+        Ok(())
+    }
+
+    // Take the given number of bytes, borrowed from the input if it is held
+    // in memory, or otherwise copied out of it.
+    fn take(
+        &mut self,
+        len: usize,
+    ) -> Result<Cow<'de, [u8]>> {
+        if self.borrowable().is_some() {
+            return self.borrow(len).map(Cow::Borrowed);
+        }
+        self.check_limit(len)?;
+        let mut bytes = Vec::new();
+        while bytes.len() < len {
+            let start = bytes.len();
+            bytes.resize(start + (len - start).min(CHUNK_SIZE), 0);
+            self.read(&mut bytes[start..])?;
+        }
+        Ok(Cow::Owned(bytes))
+    }
+
+    pub(crate) fn parse_bool(&mut self) -> Result<bool> {
+        Ok(self.parse_u8()? != 0)
     }
 
     #[allow(clippy::cast_possible_wrap)]
-    fn parse_i8(&mut self) -> Result<i8> {
-        self.buffer.iter().next().map_or(Err(Error::ValueTruncated), |byte| {
-            self.buffer = &self.buffer[1..];
-            self.offset += 1;
-            Ok(*byte as i8)
-        })
+    pub(crate) fn parse_i8(&mut self) -> Result<i8> {
+        Ok(self.parse_u8()? as i8)
     }
 
     #[allow(clippy::cast_possible_truncation)]
-    fn parse_i16(&mut self) -> Result<i16> {
-        Ok(self.parse_i64(Some(i64::from(std::i16::MAX)))? as i16)
+    pub(crate) fn parse_i16(&mut self) -> Result<i16> {
+        Ok(self.parse_i64(Some(i64::from(i16::MAX)))? as i16)
     }
 
     #[allow(clippy::cast_possible_truncation)]
-    fn parse_i32(&mut self) -> Result<i32> {
-        Ok(self.parse_i64(Some(i64::from(std::i32::MAX)))? as i32)
+    pub(crate) fn parse_i32(&mut self) -> Result<i32> {
+        Ok(self.parse_i64(Some(i64::from(i32::MAX)))? as i32)
     }
 
-    #[allow(clippy::cast_lossless)]
-    fn parse_i64(
+    pub(crate) fn parse_i64(
         &mut self,
         max: Option<i64>,
     ) -> Result<i64> {
-        let mut it = self.buffer.iter();
-        let first = it.next().ok_or(Error::ValueTruncated)?;
-        self.buffer = &self.buffer[1..];
-        self.offset += 1;
-        let mut more = (first & 0x80) != 0;
-        let negative = (first & 0x40) != 0;
-        let mut value = (first & 0x3F) as i64;
-        while more {
-            let next = it.next().ok_or(Error::ValueTruncated)?;
-            self.buffer = &self.buffer[1..];
-            self.offset += 1;
-            let lsb = next & 0x7F;
-            more = (next & 0x80) != 0;
-            // Special case: the negative of exactly one value, `i64::MIN`,
-            // won't fit in 64 bits, so we detect it here and return it
-            // early before we would overflow.
-            if !more
-                && negative
-                && lsb == 0
-                && value == 0x0100_0000_0000_0000_i64
-            {
-                return Ok(i64::MIN);
-            }
-            value = value.checked_mul(128).ok_or(Error::IntegerOverflow)?;
-            value += lsb as i64;
-            if let Some(max) = max {
-                if (negative && value > (max + 1)) || (!negative && value > max)
-                {
-                    return Err(Error::IntegerOverflow);
-                }
+        let value = self.decode_int(MAX_INT_SIZE, |codec, bytes| {
+            codec.decode_signed(bytes, max.unwrap_or(i64::MAX))
+        })?;
+        if let Some(max) = max {
+            if value > max || value < -max - 1 {
+                return Err(Error::IntegerOverflow);
             }
         }
-        Ok(if negative {
-            -value
-        } else {
-            value
-        })
+        Ok(value)
     }
 
-    fn parse_u8(&mut self) -> Result<u8> {
-        self.buffer.iter().next().map_or(Err(Error::ValueTruncated), |byte| {
-            self.buffer = &self.buffer[1..];
-            self.offset += 1;
-            Ok(*byte)
+    pub(crate) fn parse_i128(&mut self) -> Result<i128> {
+        self.decode_int(MAX_INT128_SIZE, |_, bytes| {
+            Varint::decode_signed128(bytes)
         })
     }
 
+    pub(crate) fn parse_u8(&mut self) -> Result<u8> {
+        let mut byte = [0];
+        self.read(&mut byte)?;
+        Ok(byte[0])
+    }
+
     #[allow(clippy::cast_possible_truncation)]
-    fn parse_u16(&mut self) -> Result<u16> {
-        Ok(self.parse_u64(Some(u64::from(std::u16::MAX)))? as u16)
+    pub(crate) fn parse_u16(&mut self) -> Result<u16> {
+        Ok(self.parse_u64(Some(u64::from(u16::MAX)))? as u16)
     }
 
     #[allow(clippy::cast_possible_truncation)]
-    fn parse_u32(&mut self) -> Result<u32> {
-        Ok(self.parse_u64(Some(u64::from(std::u32::MAX)))? as u32)
+    pub(crate) fn parse_u32(&mut self) -> Result<u32> {
+        Ok(self.parse_u64(Some(u64::from(u32::MAX)))? as u32)
     }
 
-    #[allow(clippy::cast_lossless)]
-    fn parse_u64(
+    pub(crate) fn parse_u64(
         &mut self,
         max: Option<u64>,
     ) -> Result<u64> {
-        let mut it = self.buffer.iter();
-        let first = it.next().ok_or(Error::ValueTruncated)?;
-        self.buffer = &self.buffer[1..];
-        self.offset += 1;
-        let mut more = (first & 0x80) != 0;
-        let mut value = (first & 0x7F) as u64;
-        while more {
-            let next = it.next().ok_or(Error::ValueTruncated)?;
-            self.buffer = &self.buffer[1..];
-            self.offset += 1;
-            let lsb = next & 0x7F;
-            more = (next & 0x80) != 0;
-            value = value.checked_mul(128).ok_or(Error::IntegerOverflow)?;
-            value += lsb as u64;
-            if let Some(max) = max {
-                if value > max {
-                    return Err(Error::IntegerOverflow);
-                }
-            }
+        let value = self.decode_int(MAX_INT_SIZE, |codec, bytes| {
+            codec.decode_unsigned(bytes, max.unwrap_or(u64::MAX))
+        })?;
+        if max.is_some_and(|max| value > max) {
+            return Err(Error::IntegerOverflow);
         }
         Ok(value)
     }
 
+    pub(crate) fn parse_u128(&mut self) -> Result<u128> {
+        self.decode_int(MAX_INT128_SIZE, |_, bytes| {
+            Varint::decode_unsigned128(bytes)
+        })
+    }
+
     #[allow(clippy::cast_possible_truncation)]
-    fn parse_usize(&mut self) -> Result<usize> {
+    pub(crate) fn parse_usize(&mut self) -> Result<usize> {
         Ok(self.parse_u64(None)? as usize)
     }
 
-    #[allow(clippy::cast_lossless)]
-    fn parse_f32(&mut self) -> Result<f32> {
-        if self.buffer.len() < 4 {
-            return Err(Error::ValueTruncated);
-        }
-        let mut value = 0_u32;
-        for _ in 0..4 {
-            value <<= 8;
-            value += self.buffer[0] as u32;
-            self.buffer = &self.buffer[1..];
-            self.offset += 1;
-        }
-        unsafe {
-            let value = *(&value as *const u32).cast::<f32>();
-            Ok(value)
+    pub(crate) fn parse_f32(&mut self) -> Result<f32> {
+        if !cfg!(feature = "float") {
+            return Err(Error::FloatUnsupported);
         }
+        let mut bytes = [0; 4];
+        self.read(&mut bytes)?;
+        Ok(f32::from_be_bytes(bytes))
     }
 
-    #[allow(clippy::cast_lossless)]
-    fn parse_f64(&mut self) -> Result<f64> {
-        if self.buffer.len() < 8 {
-            return Err(Error::ValueTruncated);
-        }
-        let mut value = 0_u64;
-        for _ in 0..8 {
-            value <<= 8;
-            value += self.buffer[0] as u64;
-            self.buffer = &self.buffer[1..];
-            self.offset += 1;
-        }
-        unsafe {
-            let value = *(&value as *const u64).cast::<f64>();
-            Ok(value)
+    pub(crate) fn parse_f64(&mut self) -> Result<f64> {
+        if !cfg!(feature = "float") {
+            return Err(Error::FloatUnsupported);
         }
+        let mut bytes = [0; 8];
+        self.read(&mut bytes)?;
+        Ok(f64::from_be_bytes(bytes))
     }
 
-    fn parse_char(&mut self) -> Result<char> {
-        let mut it = self.buffer.iter();
-        let byte1 = it.next().ok_or(Error::ValueTruncated)?;
-        let n = match byte1 {
+    pub(crate) fn parse_char(&mut self) -> Result<char> {
+        if self.config.char_encoding() == CharEncoding::ScalarValue {
+            let value = self.parse_u32()?;
+            return char::from_u32(value).ok_or(Error::InvalidChar(value));
+        }
+        let mut bytes = [0; 4];
+        self.read(&mut bytes[..1])?;
+        let n = match bytes[0] {
             0x00..=0x7F => 1,
             0xC0..=0xDF => 2,
             0xE0..=0xEF => 3,
             0xF0..=0xF7 => 4,
             _ => return Err(Error::InvalidUtf8(None)),
         };
-        if self.buffer.len() < n {
-            return Err(Error::ValueTruncated);
-        }
-        let ch = std::str::from_utf8(&self.buffer[0..n])
+        self.read(&mut bytes[1..n])?;
+        core::str::from_utf8(&bytes[..n])
             .map_err(|source| Error::InvalidUtf8(Some(source)))?
             .chars()
             .next()
-            .ok_or(Error::InvalidUtf8(None))?;
-        self.buffer = &self.buffer[n..];
-        self.offset += n;
-        Ok(ch)
+            .ok_or(Error::InvalidUtf8(None))
+    }
+
+    // Decode a string, borrowed from the input if it is held in memory.
+    fn parse_text(&mut self) -> Result<Cow<'de, str>> {
+        let len = self.parse_usize()?;
+        match self.take(len)? {
+            Cow::Borrowed(bytes) => core::str::from_utf8(bytes)
+                .map(Cow::Borrowed)
+                .map_err(|source| Error::InvalidUtf8(Some(source))),
+            Cow::Owned(bytes) => String::from_utf8(bytes)
+                .map(Cow::Owned)
+                .map_err(|error| Error::InvalidUtf8(Some(error.utf8_error()))),
+        }
+    }
+
+    // Decode a byte string, borrowed from the input if it is held in
+    // memory.
+    fn parse_blob(&mut self) -> Result<Cow<'de, [u8]>> {
+        let len = self.parse_usize()?;
+        self.take(len)
     }
 
+    // Decode a field name or map key, which may refer back to an earlier
+    // copy of it if it is interned.
     #[allow(clippy::cast_possible_truncation)]
-    fn parse_str(&mut self) -> Result<&'de str> {
-        let len = self.parse_u64(None)? as usize;
-        if self.buffer.len() < len {
-            Err(Error::ValueTruncated)
+    fn parse_name(&mut self) -> Result<Cow<'de, str>> {
+        if !self.config.interns() {
+            return self.parse_text();
+        }
+        let index = self.parse_u64(None)?;
+        if index == 0 {
+            let name = self.parse_text()?;
+            self.interned.push(name.clone());
+            Ok(name)
         } else {
-            let value = std::str::from_utf8(&self.buffer[0..len])
-                .map_err(|source| Error::InvalidUtf8(Some(source)))?;
-            self.buffer = &self.buffer[len..];
-            self.offset += len;
-            Ok(value)
+            self.interned
+                .get((index - 1) as usize)
+                .cloned()
+                .ok_or(Error::InvalidNameReference(index))
         }
     }
 
-    #[allow(clippy::cast_possible_truncation)]
-    fn parse_bytes(&mut self) -> Result<&'de [u8]> {
-        let len = self.parse_u64(None)? as usize;
-        if self.buffer.len() < len {
-            Err(Error::ValueTruncated)
+    // Decode a string, which is decoded as a name if it is a map key.
+    fn parse_string(&mut self) -> Result<Cow<'de, str>> {
+        if core::mem::take(&mut self.key) {
+            self.parse_name()
         } else {
-            let value = &self.buffer[0..len];
-            self.buffer = &self.buffer[len..];
-            self.offset += len;
-            Ok(value)
+            self.parse_text()
         }
     }
 
-    fn parse_option(&mut self) -> Result<Option<&mut Self>> {
-        let mut it = self.buffer.iter();
-        let next = it.next().ok_or(Error::ValueTruncated)?;
-        self.buffer = &self.buffer[1..];
-        self.offset += 1;
-        Ok(match next {
+    pub(crate) fn parse_option(&mut self) -> Result<Option<&mut Self>> {
+        let byte = self.parse_u8()?;
+        if self.config.self_describing() {
+            return match Tag::from_byte(byte) {
+                Some(Tag::None) => Ok(None),
+                Some(Tag::Some) => Ok(Some(self)),
+                _ => Err(Error::UnexpectedTag(byte)),
+            };
+        }
+        Ok(match byte {
             0 => None,
             _ => Some(self),
         })
     }
+
+    // Visit the given number of elements which follow, with nothing before
+    // them.
+    fn visit_elements<V>(
+        &mut self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_seq(Elements {
+            de: self,
+            len,
+            truncatable: false,
+        })
+    }
+
+    // Visit the given number of map entries which follow, with nothing
+    // before them.
+    fn visit_entries<V>(
+        &mut self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_map(Entries {
+            de: self,
+            len,
+        })
+    }
+
+    // Skip the given number of values which follow, which is only possible
+    // if the encoding is self-describing.
+    fn skip_values(
+        &mut self,
+        count: usize,
+    ) -> Result<()> {
+        for _ in 0..count {
+            serde::Deserializer::deserialize_ignored_any(
+                &mut *self,
+                serde::de::IgnoredAny,
+            )?;
+        }
+        Ok(())
+    }
+
+    // Visit the fields of a struct which follow, with nothing before them,
+    // and then skip any extra fields after them.  If fields may be missing,
+    // they end early if the input runs out.  If fields are identified, they
+    // are visited as a map instead, preceded by their number.
+    fn visit_fields<V>(
+        &mut self,
+        fields: Fields,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        if self.config.identifies_fields() {
+            let len = self.parse_usize()?;
+            return visitor.visit_map(IdentifiedFields {
+                de: self,
+                len,
+                names: fields.names,
+            });
+        }
+        let truncatable = self.config.missing_fields();
+        let value = visitor.visit_seq(Elements {
+            de: self,
+            len: fields.len,
+            truncatable,
+        })?;
+        self.skip_values(fields.extra)?;
+        Ok(value)
+    }
+
+    // Visit the fields of a struct, which follow any tag, skipping any
+    // extra fields after them.
+    fn visit_struct<V>(
+        &mut self,
+        fields: Fields,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        if self.subtrees.is_some() {
+            self.deserialize_deduplicated(fields, visitor)
+        } else {
+            self.visit_fields(fields, visitor)
+        }
+    }
+
+    // Decode a value using the given function, limited to the given number
+    // of bytes which follow, and then skip whatever is left of them.
+    fn within<T, F>(
+        &mut self,
+        len: usize,
+        decode: F,
+    ) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        self.check_limit(len)?;
+        if self.available().is_some_and(|bytes| bytes.len() < len) {
+            return Err(Error::ValueTruncated);
+        }
+        let end = self.offset.checked_add(len).ok_or(Error::ValueTruncated)?;
+        let outer = self.end.replace(end);
+        let value = decode(self);
+        self.end = outer;
+        let value = value?;
+        self.skip(end - self.offset)?;
+        Ok(value)
+    }
+
+    // Visit the variant of an enum, which follows any tag.  Variants
+    // identified by hashes of their names are given by name if the names
+    // of the variants are known, or otherwise by their hashes.
+    fn visit_variant<V>(
+        &mut self,
+        variants: Option<&'static [&'static str]>,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let variant = if self.config.variant_names() {
+            Variant::Name(self.parse_name()?)
+        } else if self.config.variant_ids() {
+            let id = self.parse_u32()?;
+            match variants {
+                Some(variants) => variants
+                    .iter()
+                    .copied()
+                    .find(|name| name_hash(name) == id)
+                    .map_or(Variant::Unknown(id), |name| {
+                        Variant::Name(Cow::Borrowed(name))
+                    }),
+                None => Variant::Index(id as usize),
+            }
+        } else {
+            Variant::Index(self.parse_usize()?)
+        };
+        // Unless the contents of a variant can be skipped, a variant which
+        // matches none of those of the enum cannot be decoded as its
+        // fallback variant, since its contents would be left behind to be
+        // misread as whatever follows.
+        let skippable =
+            self.config.struct_framing() || self.config.self_describing();
+        let variant = match (variants, variant) {
+            (Some(variants), Variant::Name(name))
+                if !skippable && !variants.contains(&name.as_ref()) =>
+            {
+                return Err(Error::VariantUnknown(name_hash(&name)));
+            },
+            (Some(_), Variant::Unknown(id)) if !skippable => {
+                return Err(Error::VariantUnknown(id));
+            },
+            (Some(variants), Variant::Index(index))
+                if !skippable && index >= variants.len() =>
+            {
+                Variant::Unlisted(index)
+            },
+            (_, variant) => variant,
+        };
+        visitor.visit_enum(Enum {
+            de: self,
+            variant,
+        })
+    }
 }
 
 #[allow(clippy::missing_errors_doc)]
-impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de, C, I> serde::Deserializer<'de> for &mut Deserializer<'de, C, I>
+where
+    C: IntCodec,
+    I: Input<'de>,
+{
     type Error = Error;
 
     fn deserialize_any<V>(
         self,
-        _visitor: V,
+        visitor: V,
     ) -> Result<V::Value>
     where
         V: serde::de::Visitor<'de>,
     {
-        Err(Error::TypeUnknown)
+        if self.config.self_describing() {
+            self.deserialize_tagged(visitor)
+        } else {
+            Err(Error::TypeUnknown)
+        }
     }
 
     fn deserialize_bool<V>(
@@ -283,6 +1179,7 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::Bool)?;
         visitor.visit_bool(self.parse_bool()?)
     }
 
@@ -293,6 +1190,7 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::I8)?;
         visitor.visit_i8(self.parse_i8()?)
     }
 
@@ -303,6 +1201,7 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::I16)?;
         visitor.visit_i16(self.parse_i16()?)
     }
 
@@ -313,6 +1212,7 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::I32)?;
         visitor.visit_i32(self.parse_i32()?)
     }
 
@@ -323,9 +1223,21 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::I64)?;
         visitor.visit_i64(self.parse_i64(None)?)
     }
 
+    fn deserialize_i128<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.expect_tag(Tag::I128)?;
+        visitor.visit_i128(self.parse_i128()?)
+    }
+
     fn deserialize_u8<V>(
         self,
         visitor: V,
@@ -333,6 +1245,7 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::U8)?;
         visitor.visit_u8(self.parse_u8()?)
     }
 
@@ -343,6 +1256,7 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::U16)?;
         visitor.visit_u16(self.parse_u16()?)
     }
 
@@ -353,6 +1267,7 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::U32)?;
         visitor.visit_u32(self.parse_u32()?)
     }
 
@@ -363,9 +1278,21 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::U64)?;
         visitor.visit_u64(self.parse_u64(None)?)
     }
 
+    fn deserialize_u128<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.expect_tag(Tag::U128)?;
+        visitor.visit_u128(self.parse_u128()?)
+    }
+
     fn deserialize_f32<V>(
         self,
         visitor: V,
@@ -373,6 +1300,7 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::F32)?;
         visitor.visit_f32(self.parse_f32()?)
     }
 
@@ -383,6 +1311,7 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::F64)?;
         visitor.visit_f64(self.parse_f64()?)
     }
 
@@ -393,6 +1322,7 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::Char)?;
         visitor.visit_char(self.parse_char()?)
     }
 
@@ -403,7 +1333,8 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
-        visitor.visit_borrowed_str(self.parse_str()?)
+        self.expect_tag(Tag::Str)?;
+        visit_text(visitor, self.parse_string()?)
     }
 
     fn deserialize_string<V>(
@@ -413,7 +1344,8 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
-        visitor.visit_borrowed_str(self.parse_str()?)
+        self.expect_tag(Tag::Str)?;
+        visit_text(visitor, self.parse_string()?)
     }
 
     fn deserialize_bytes<V>(
@@ -423,7 +1355,8 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
-        visitor.visit_borrowed_bytes(self.parse_bytes()?)
+        self.expect_tag(Tag::Bytes)?;
+        visit_blob(visitor, self.parse_blob()?)
     }
 
     fn deserialize_byte_buf<V>(
@@ -433,7 +1366,8 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
-        visitor.visit_borrowed_bytes(self.parse_bytes()?)
+        self.expect_tag(Tag::Bytes)?;
+        visit_blob(visitor, self.parse_blob()?)
     }
 
     fn deserialize_option<V>(
@@ -456,6 +1390,7 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::Unit)?;
         visitor.visit_unit()
     }
 
@@ -467,6 +1402,7 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::Unit)?;
         visitor.visit_unit()
     }
 
@@ -488,8 +1424,9 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
+        self.expect_tag(Tag::Seq)?;
         let len = self.parse_usize()?;
-        self.deserialize_tuple(len, visitor)
+        self.visit_elements(len, visitor)
     }
 
     fn deserialize_tuple<V>(
@@ -500,35 +1437,8 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
-        struct Seq<'a, 'de> {
-            de: &'a mut Deserializer<'de>,
-            len: usize,
-        }
-
-        impl<'a, 'de> serde::de::SeqAccess<'de> for Seq<'a, 'de> {
-            type Error = Error;
-
-            fn next_element_seed<T>(
-                &mut self,
-                seed: T,
-            ) -> Result<Option<T::Value>>
-            where
-                T: serde::de::DeserializeSeed<'de>,
-            {
-                self.len
-                    .checked_sub(1)
-                    .map(|len| {
-                        self.len = len;
-                        seed.deserialize(&mut *self.de)
-                    })
-                    .transpose()
-            }
-        }
-
-        visitor.visit_seq(Seq {
-            de: self,
-            len,
-        })
+        self.expect_tag_with_len(Tag::Tuple, len)?;
+        self.visit_elements(len, visitor)
     }
 
     fn deserialize_tuple_struct<V>(
@@ -550,44 +1460,9 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
-        struct Map<'a, 'de> {
-            de: &'a mut Deserializer<'de>,
-            len: usize,
-        }
-
-        impl<'a, 'de> serde::de::MapAccess<'de> for Map<'a, 'de> {
-            type Error = Error;
-
-            fn next_key_seed<K>(
-                &mut self,
-                seed: K,
-            ) -> Result<Option<K::Value>>
-            where
-                K: serde::de::DeserializeSeed<'de>,
-            {
-                self.len
-                    .checked_sub(1)
-                    .map(|_| seed.deserialize(&mut *self.de))
-                    .transpose()
-            }
-
-            fn next_value_seed<V>(
-                &mut self,
-                seed: V,
-            ) -> Result<V::Value>
-            where
-                V: serde::de::DeserializeSeed<'de>,
-            {
-                self.len -= 1;
-                seed.deserialize(&mut *self.de)
-            }
-        }
-
+        self.expect_tag(Tag::Map)?;
         let len = self.parse_usize()?;
-        visitor.visit_map(Map {
-            de: self,
-            len,
-        })
+        self.visit_entries(len, visitor)
     }
 
     fn deserialize_struct<V>(
@@ -599,50 +1474,21 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
-        self.deserialize_tuple(fields.len(), visitor)
+        let fields = self.expect_fields(Tag::Struct, fields)?;
+        self.framed(|de| de.visit_struct(fields, visitor))
     }
 
     fn deserialize_enum<V>(
         self,
         _name: &'static str,
-        _variants: &'static [&'static str],
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: serde::de::Visitor<'de>,
     {
-        struct Enum<'a, 'de> {
-            de: &'a mut Deserializer<'de>,
-            variant: usize,
-        }
-
-        impl<'a, 'de> serde::de::EnumAccess<'de> for Enum<'a, 'de> {
-            type Error = Error;
-            type Variant = &'a mut Deserializer<'de>;
-
-            fn variant_seed<V>(
-                self,
-                seed: V,
-            ) -> Result<(V::Value, Self::Variant)>
-            where
-                V: serde::de::DeserializeSeed<'de>,
-            {
-                Ok((
-                    seed.deserialize(
-                        serde::de::IntoDeserializer::into_deserializer(
-                            self.variant,
-                        ),
-                    )?,
-                    self.de,
-                ))
-            }
-        }
-
-        let variant = self.parse_usize()?;
-        visitor.visit_enum(Enum {
-            de: self,
-            variant,
-        })
+        self.expect_tag(Tag::Variant)?;
+        self.visit_variant(Some(variants), visitor)
     }
 
     fn deserialize_identifier<V>(
@@ -655,22 +1501,322 @@ impl<'a, 'de> serde::Deserializer<'de> for &'a mut Deserializer<'de> {
         Err(Error::IdentifierUnknown)
     }
 
-    fn deserialize_ignored_any<V>(
+    fn deserialize_ignored_any<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        if self.config.self_describing() {
+            self.deserialize_tagged(serde::de::IgnoredAny)?;
+            visitor.visit_unit()
+        } else {
+            Err(Error::TypeUnknown)
+        }
+    }
+}
+
+// This gives the elements of a sequence, tuple, or struct to a visitor.  If
+// the elements may be truncated, they end early if the input runs out.
+struct Elements<'a, 'de, C, I> {
+    de: &'a mut Deserializer<'de, C, I>,
+    len: usize,
+    truncatable: bool,
+}
+
+impl<'de, C, I> serde::de::SeqAccess<'de> for Elements<'_, 'de, C, I>
+where
+    C: IntCodec,
+    I: Input<'de>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        if self.truncatable && self.len > 0 && self.de.at_end()? {
+            self.len = 0;
+        }
+        self.len
+            .checked_sub(1)
+            .map(|len| {
+                self.len = len;
+                seed.deserialize(&mut *self.de)
+            })
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+
+// This gives the entries of a map to a visitor.
+struct Entries<'a, 'de, C, I> {
+    de: &'a mut Deserializer<'de, C, I>,
+    len: usize,
+}
+
+impl<'de, C, I> serde::de::MapAccess<'de> for Entries<'_, 'de, C, I>
+where
+    C: IntCodec,
+    I: Input<'de>,
+{
+    type Error = Error;
+
+    fn next_key_seed<K>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>>
+    where
+        K: serde::de::DeserializeSeed<'de>,
+    {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        self.de.key = self.de.config.interns();
+        let result = seed.deserialize(&mut *self.de);
+        self.de.key = false;
+        result.map(Some)
+    }
+
+    fn next_value_seed<V>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        self.len -= 1;
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+// This describes the fields of a struct expected by the type being decoded.
+#[derive(Clone, Copy)]
+struct Fields {
+    // This is the number of fields, which is taken from the encoding if the
+    // type is not known.
+    len: usize,
+
+    // This is the number of fields which the encoding has after those of
+    // the type, which are skipped.
+    extra: usize,
+
+    // These are the names of the fields, if the type is known.
+    names: Option<&'static [&'static str]>,
+}
+
+// This gives the fields of a struct, identified by their names or hashes
+// of them, to a visitor as the entries of a map.  If the names of the
+// fields are known, fields are given by name, and those with unknown names
+// are skipped, so that the visitor never sees them.  Otherwise fields are
+// given by their identifiers.
+struct IdentifiedFields<'a, 'de, C, I> {
+    de: &'a mut Deserializer<'de, C, I>,
+    len: usize,
+    names: Option<&'static [&'static str]>,
+}
+
+impl<'de, C, I> serde::de::MapAccess<'de> for IdentifiedFields<'_, 'de, C, I>
+where
+    C: IntCodec,
+    I: Input<'de>,
+{
+    type Error = Error;
+
+    fn next_key_seed<K>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>>
+    where
+        K: serde::de::DeserializeSeed<'de>,
+    {
+        while let Some(len) = self.len.checked_sub(1) {
+            self.len = len;
+            let name = if self.de.config.field_names() {
+                let name = self.de.parse_name()?;
+                let Some(names) = self.names else {
+                    return deserialize_name(seed, name).map(Some);
+                };
+                names.iter().copied().find(|known| *known == name)
+            } else {
+                let id = self.de.parse_u32()?;
+                let Some(names) = self.names else {
+                    return seed
+                        .deserialize(
+                            serde::de::IntoDeserializer::into_deserializer(id),
+                        )
+                        .map(Some);
+                };
+                names.iter().copied().find(|name| name_hash(name) == id)
+            };
+            if let Some(name) = name {
+                return seed
+                    .deserialize(
+                        serde::de::IntoDeserializer::into_deserializer(name),
+                    )
+                    .map(Some);
+            }
+            let len = self.de.parse_usize()?;
+            self.de.within(len, |_| Ok(()))?;
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        let len = self.de.parse_usize()?;
+        self.de.within(len, |de| seed.deserialize(de))
+    }
+}
+
+// This gives a name decoded by the deserializer to a seed, borrowed from the
+// input if it can be.
+fn deserialize_name<'de, T>(
+    seed: T,
+    name: Cow<'de, str>,
+) -> Result<T::Value>
+where
+    T: serde::de::DeserializeSeed<'de>,
+{
+    match name {
+        Cow::Borrowed(name) => seed
+            .deserialize(serde::de::value::BorrowedStrDeserializer::new(name)),
+        Cow::Owned(name) => seed.deserialize(serde::de::IntoDeserializer::<
+            Error,
+        >::into_deserializer(name)),
+    }
+}
+
+// This gives a byte string decoded by the deserializer to a visitor,
+// borrowed from the input if it can be.
+fn visit_blob<'de, V>(
+    visitor: V,
+    bytes: Cow<'de, [u8]>,
+) -> Result<V::Value>
+where
+    V: serde::de::Visitor<'de>,
+{
+    match bytes {
+        Cow::Borrowed(bytes) => visitor.visit_borrowed_bytes(bytes),
+        Cow::Owned(bytes) => visitor.visit_byte_buf(bytes),
+    }
+}
+
+// This gives a string decoded by the deserializer to a visitor, borrowed
+// from the input if it can be.
+fn visit_text<'de, V>(
+    visitor: V,
+    text: Cow<'de, str>,
+) -> Result<V::Value>
+where
+    V: serde::de::Visitor<'de>,
+{
+    match text {
+        Cow::Borrowed(text) => visitor.visit_borrowed_str(text),
+        Cow::Owned(text) => visitor.visit_string(text),
+    }
+}
+
+// This identifies the variant of an enum, by its index or its name, or by
+// the hash of a name which matches none of the variants of the enum, or by
+// an index past the variants of the enum, whose contents cannot be skipped.
+enum Variant<'de> {
+    Index(usize),
+    Name(Cow<'de, str>),
+    Unknown(u32),
+    Unlisted(usize),
+}
+
+// This gives the variant of an enum to a visitor.
+struct Enum<'a, 'de, C, I> {
+    de: &'a mut Deserializer<'de, C, I>,
+    variant: Variant<'de>,
+}
+
+impl<'a, 'de, C, I> serde::de::EnumAccess<'de> for Enum<'a, 'de, C, I>
+where
+    C: IntCodec,
+    I: Input<'de>,
+{
+    type Error = Error;
+    type Variant = Contents<'a, 'de, C, I>;
+
+    fn variant_seed<V>(
         self,
-        _visitor: V,
-    ) -> Result<V::Value>
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant)>
     where
-        V: serde::de::Visitor<'de>,
+        V: serde::de::DeserializeSeed<'de>,
     {
-        Err(Error::TypeUnknown)
+        let variant = match self.variant {
+            Variant::Index(index) => seed.deserialize(
+                serde::de::IntoDeserializer::<Error>::into_deserializer(index),
+            )?,
+            Variant::Name(name) => deserialize_name(seed, name)?,
+            // An index no enum can have is given, so that the variant is
+            // decoded as the fallback variant of the enum, if it has one.
+            Variant::Unknown(id) => seed
+                .deserialize(
+                    serde::de::IntoDeserializer::<Error>::into_deserializer(
+                        u64::MAX,
+                    ),
+                )
+                .map_err(|_| Error::VariantUnknown(id))?,
+            // An index past the variants of the enum may still be the tag
+            // of a variant given with `#[serialization(tag = N)]`.  It is
+            // given as a `u128`, which the code generated for such enums
+            // maps to the variant, and which other enums reject rather than
+            // taking it as their fallback variant.
+            #[allow(clippy::cast_possible_truncation)]
+            Variant::Unlisted(index) => seed
+                .deserialize(
+                    serde::de::IntoDeserializer::<Error>::into_deserializer(
+                        index as u128,
+                    ),
+                )
+                .map_err(|_| Error::VariantUnknown(index as u32))?,
+        };
+        Ok((variant, Contents {
+            de: self.de,
+        }))
     }
 }
 
-impl<'a, 'de> serde::de::VariantAccess<'de> for &'a mut Deserializer<'de> {
+// This gives the contents of a variant of an enum to a visitor.  Since the
+// fallback variant of an enum, which stands for the variants it does not
+// have, is a unit variant, the contents of unit variants are skipped, which
+// can only be done if the encoding is framed or self-describing.
+struct Contents<'a, 'de, C, I> {
+    de: &'a mut Deserializer<'de, C, I>,
+}
+
+impl<'de, C, I> serde::de::VariantAccess<'de> for Contents<'_, 'de, C, I>
+where
+    C: IntCodec,
+    I: Input<'de>,
+{
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
-        Ok(())
+        self.de.framed(|de| {
+            if de.config.self_describing() {
+                de.skip_values(1)
+            } else {
+                Ok(())
+            }
+        })
     }
 
     fn newtype_variant_seed<T>(
@@ -680,7 +1826,7 @@ impl<'a, 'de> serde::de::VariantAccess<'de> for &'a mut Deserializer<'de> {
     where
         T: serde::de::DeserializeSeed<'de>,
     {
-        seed.deserialize(self)
+        self.de.framed(|de| seed.deserialize(de))
     }
 
     fn tuple_variant<V>(
@@ -691,7 +1837,10 @@ impl<'a, 'de> serde::de::VariantAccess<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
-        <Self as serde::Deserializer>::deserialize_tuple(self, len, visitor)
+        self.de.framed(|de| {
+            de.expect_tag_with_len(Tag::Tuple, len)?;
+            de.visit_elements(len, visitor)
+        })
     }
 
     fn struct_variant<V>(
@@ -702,24 +1851,118 @@ impl<'a, 'de> serde::de::VariantAccess<'de> for &'a mut Deserializer<'de> {
     where
         V: serde::de::Visitor<'de>,
     {
-        <Self as serde::Deserializer>::deserialize_tuple(
-            self,
-            fields.len(),
-            visitor,
-        )
+        self.de.framed(|de| {
+            let fields = de.expect_fields(Tag::StructVariant, fields)?;
+            de.visit_fields(fields, visitor)
+        })
     }
 }
 
+/// This is an iterator which deserializes values of type `T` stored back to
+/// back in one sequence of bytes, yielding each one until the bytes run out.
+/// It keeps track of the offset of the next value, so that a stream which
+/// ends partway through a value can be resumed once more bytes arrive, by
+/// making a new iterator over the bytes from that offset on.  A value which
+/// cannot be decoded leaves the iterator at the start of that value, so
+/// calling [`next`] again tries the same value again.
+/// It is made by [`new`] or [`Deserializer::iterate`], and values can be
+/// decoded one at a time from a reader instead using a
+/// [`ReaderStreamDeserializer`].
+///
+/// [`new`]: #method.new
+/// [`next`]: #method.next
+/// [`Deserializer::iterate`]: struct.Deserializer.html#method.iterate
+/// [`ReaderStreamDeserializer`]: struct.ReaderStreamDeserializer.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     to_bytes,
+///     StreamDeserializer,
+/// };
+///
+/// let mut stream = to_bytes(&("first", 1_u8)).unwrap();
+/// stream.extend(to_bytes(&("second", 2_u8)).unwrap());
+/// let mut values = StreamDeserializer::<(&str, u8)>::new(&stream);
+/// assert_eq!(("first", 1), values.next().unwrap().unwrap());
+/// assert_eq!(7, values.offset());
+/// assert_eq!(("second", 2), values.next().unwrap().unwrap());
+/// assert!(values.next().is_none());
+/// ```
 pub struct StreamDeserializer<'de, T> {
     de: Deserializer<'de>,
     offset: usize,
+    last_size: usize,
     output: PhantomData<T>,
 }
 
 impl<'de, T> StreamDeserializer<'de, T> {
+    /// Return an iterator which deserializes values from the given bytes.
+    #[must_use]
+    pub fn new(bytes: &'de [u8]) -> Self {
+        Deserializer::new(bytes).iterate()
+    }
+}
+
+impl<T> StreamDeserializer<'_, T> {
+    /// Return the number of bytes in the encoding of the value most
+    /// recently deserialized from the stream, or zero if no value has been
+    /// deserialized yet.
+    #[must_use]
+    pub fn last_size(&self) -> usize {
+        self.last_size
+    }
+
+    /// Return the offset, from the start of the bytes, of the next value
+    /// in the stream, which is just past the end of the value most recently
+    /// deserialized or skipped.
+    #[must_use]
     pub fn offset(&self) -> usize {
         self.offset
     }
+
+    /// Return the number of bytes in the stream after the end of the value
+    /// most recently deserialized or skipped.
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.de.offset + self.de.remaining() - self.offset
+    }
+
+    /// Advance past the next `n` values in the stream without decoding
+    /// them, using the given schema, which describes the values, to find
+    /// where each one ends.  This is useful for resuming from a saved
+    /// position, counted in values, in a large log.  The number of values
+    /// skipped is returned, which is less than `n` only if the stream ends
+    /// first.
+    ///
+    /// # Errors
+    ///
+    /// Any of the errors returned by [`from_bytes`] may be returned from
+    /// finding the end of a value, in which case the stream is left at the
+    /// start of that value.
+    ///
+    /// [`from_bytes`]: fn.from_bytes.html
+    pub fn skip_values(
+        &mut self,
+        schema: &Schema,
+        n: usize,
+    ) -> Result<usize> {
+        let mut skipper = Skipper::new(schema);
+        for skipped in 0..n {
+            if self.de.remaining() == 0 {
+                return Ok(skipped);
+            }
+            let start = self.de.checkpoint();
+            if let Err(error) = skipper.skip(&mut self.de, schema) {
+                self.de.rewind(start);
+                return Err(error);
+            }
+            self.offset = self.de.offset();
+        }
+        Ok(n)
+    }
 }
 
 impl<'de, T> Iterator for StreamDeserializer<'de, T>
@@ -729,20 +1972,86 @@ where
     type Item = Result<T>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.de.buffer.is_empty() {
+        if self.de.remaining() == 0 {
             None
         } else {
+            let start = self.de.checkpoint();
             let next = T::deserialize(&mut self.de);
             if next.is_ok() {
+                self.last_size = self.de.offset() - self.offset;
                 self.offset = self.de.offset();
+            } else {
+                self.de.rewind(start);
+            }
+            let config = self.de.config;
+            Some(next.map_err(|error| config.explain_truncation(error)))
+        }
+    }
+}
+
+/// This is an iterator which deserializes the structs, stored back to back
+/// in one sequence of bytes, for which a predicate returns `true`.  It is
+/// made by [`Deserializer::iterate_where`].
+///
+/// [`Deserializer::iterate_where`]: struct.Deserializer.html#method.iterate_where
+pub struct FilteredStreamDeserializer<'de, T, P, F> {
+    de: Deserializer<'de>,
+    offset: usize,
+    record: Schema,
+    predicate: F,
+    output: PhantomData<(T, P)>,
+}
+
+impl<T, P, F> FilteredStreamDeserializer<'_, T, P, F> {
+    /// Return the offset, from the start of the bytes, of the next struct
+    /// in the stream.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl<'de, T, P, F> Iterator for FilteredStreamDeserializer<'de, T, P, F>
+where
+    T: serde::Deserialize<'de>,
+    P: serde::Deserialize<'de>,
+    F: FnMut(&P) -> bool,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.de.remaining() > 0 {
+            let start = (self.de.input, self.de.offset);
+            let projection = match project::<P>(&mut self.de, &self.record) {
+                Ok(projection) => projection,
+                Err(error) => return Some(Err(error)),
+            };
+            if (self.predicate)(&projection) {
+                // Go back to decode the whole struct.
+                self.de.input = start.0;
+                self.de.offset = start.1;
+                let next = T::deserialize(&mut self.de);
+                if next.is_ok() {
+                    self.offset = self.de.offset();
+                }
+                return Some(next);
             }
-            Some(next)
+            self.offset = self.de.offset();
         }
+        None
     }
 }
 
 /// This function is used to decode a value from a sequence of bytes
-/// using the deserializer implemented by this crate.
+/// using the deserializer implemented by this crate.  Strings and byte
+/// strings are handed to the value borrowed from the bytes, so fields of
+/// types such as `&str`, `&[u8]`, or `Cow<str>` marked with
+/// `#[serde(borrow)]` are decoded without allocating.  The deserializer
+/// itself allocates nothing, so values made only of such fields and plain
+/// numbers can be decoded on targets without a heap, such as the encodings
+/// written by [`to_slice`].
+///
+/// [`to_slice`]: fn.to_slice.html
 ///
 /// # Errors
 ///
@@ -750,8 +2059,12 @@ where
 /// kinds of errors, for reasons such as:
 /// * Running out of input bytes while still deserializing the data
 /// * Encountering corrupted input
-/// * Attempting to use a feature not supported by this deserializer, such as
-///   unbounded sequences
+/// * Decoding a value its type does not accept, such as an integer too
+///   large for the type or an enum variant the type does not have
+///
+/// Sequences and maps serialized without knowing their lengths up front
+/// are encoded with their lengths all the same, so they decode like any
+/// other.
 pub fn from_bytes<'de, T>(bytes: &'de [u8]) -> Result<T>
 where
     T: serde::Deserialize<'de>,
@@ -760,6 +2073,110 @@ where
     T::deserialize(&mut deserializer)
 }
 
+/// This function is used to decode a value from the front of a sequence of
+/// bytes which may hold more after it, such as several messages sharing one
+/// buffer.  Along with the value, the number of bytes making up its
+/// encoding is returned, which is where the next value begins.
+///
+/// # Errors
+///
+/// Any of the errors returned by [`from_bytes`] may be returned.
+///
+/// [`from_bytes`]: fn.from_bytes.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_bytes_partial,
+///     to_bytes,
+/// };
+///
+/// let mut buffer = to_bytes(&("first", 1_u8)).unwrap();
+/// buffer.extend(to_bytes(&300_u16).unwrap());
+/// let (first, len): ((&str, u8), usize) =
+///     from_bytes_partial(&buffer).unwrap();
+/// assert_eq!(("first", 1), first);
+/// let (second, _): (u16, usize) = from_bytes_partial(&buffer[len..]).unwrap();
+/// assert_eq!(300, second);
+/// ```
+pub fn from_bytes_partial<'de, T>(bytes: &'de [u8]) -> Result<(T, usize)>
+where
+    T: serde::Deserialize<'de>,
+{
+    let mut deserializer = Deserializer::new(bytes);
+    let value = T::deserialize(&mut deserializer)?;
+    Ok((value, deserializer.offset()))
+}
+
+/// This function is used to determine which variant of an enum is encoded
+/// in the given bytes, without decoding the rest of the value, so that
+/// code receiving messages can decide how to handle one (such as which
+/// handler to route it to) before decoding it in full.  The index is the
+/// position of the variant in the declaration of the enum, counting from
+/// zero.
+///
+/// # Errors
+///
+/// Returns [`Error::ValueTruncated`] if the bytes end before the variant
+/// index does, or [`Error::IntegerOverflow`] if the variant index is too
+/// large to be one.
+///
+/// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+/// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// # extern crate serde;
+/// use serialization::{
+///     peek_variant_index,
+///     to_bytes,
+/// };
+///
+/// #[derive(serde::Serialize)]
+/// enum Message {
+///     Ping,
+///     Upload(Vec<u8>),
+/// }
+///
+/// let encoding = to_bytes(&Message::Upload(vec![0; 1000])).unwrap();
+/// assert_eq!(1, peek_variant_index(&encoding).unwrap());
+/// ```
+pub fn peek_variant_index(bytes: &[u8]) -> Result<u32> {
+    Deserializer::new(bytes).peek_variant_index()
+}
+
+/// This function is used to decode a batch of values encoded by
+/// [`to_bytes_many`].
+///
+/// [`to_bytes_many`]: fn.to_bytes_many.html
+///
+/// # Errors
+///
+/// Any of the errors returned by [`from_bytes`] may be returned from
+/// decoding the number of values or any of the values themselves.
+///
+/// [`from_bytes`]: fn.from_bytes.html
+pub fn from_bytes_many<'de, T>(bytes: &'de [u8]) -> Result<Vec<T>>
+where
+    T: serde::Deserialize<'de>,
+{
+    let mut deserializer = Deserializer::new(bytes);
+    let len = deserializer.parse_usize()?;
+
+    // Every value takes at least one byte, except for values of zero-sized
+    // types, so the buffer length limits how much to reserve up front,
+    // protecting against corrupted counts.
+    let mut values = Vec::with_capacity(len.min(bytes.len()));
+    for _ in 0..len {
+        values.push(T::deserialize(&mut deserializer)?);
+    }
+    Ok(values)
+}
+
 #[cfg(test)]
 #[allow(clippy::non_ascii_literal)]
 mod tests {
@@ -770,7 +2187,7 @@ mod tests {
         for (expected, value) in &[(false, &[0][..]), (true, &[1][..])] {
             let deserialization = from_bytes(value);
             assert!(deserialization.is_ok());
-            let deserialization = deserialization.unwrap();
+            let deserialization: bool = deserialization.unwrap();
             assert_eq!(*expected, deserialization);
         }
     }
@@ -780,7 +2197,7 @@ mod tests {
         for (expected, value) in &[(42_i8, &[42][..]), (-42_i8, &[0xD6][..])] {
             let deserialization = from_bytes(value);
             assert!(deserialization.is_ok());
-            let deserialization = deserialization.unwrap();
+            let deserialization: i8 = deserialization.unwrap();
             assert_eq!(*expected, deserialization);
         }
     }
@@ -799,7 +2216,7 @@ mod tests {
         ] {
             let serialization = from_bytes(value);
             assert!(serialization.is_ok());
-            let serialization = serialization.unwrap();
+            let serialization: i16 = serialization.unwrap();
             assert_eq!(*expected, serialization);
         }
         let deserialization: Result<i16> = from_bytes(&[0xA0, 0xC6, 0xC9][..]);
@@ -824,7 +2241,7 @@ mod tests {
         ] {
             let deserialization = from_bytes(value);
             assert!(deserialization.is_ok());
-            let deserialization = deserialization.unwrap();
+            let deserialization: i32 = deserialization.unwrap();
             assert_eq!(*expected, deserialization);
         }
     }
@@ -857,7 +2274,7 @@ mod tests {
         ] {
             let deserialization = from_bytes(value);
             assert!(deserialization.is_ok());
-            let deserialization = deserialization.unwrap();
+            let deserialization: i64 = deserialization.unwrap();
             assert_eq!(*expected, deserialization);
         }
         let deserialization: Result<i64> = from_bytes(
@@ -871,7 +2288,7 @@ mod tests {
         for (expected, value) in &[(42_u8, &[42][..]), (255_u8, &[0xFF][..])] {
             let deserialization = from_bytes(value);
             assert!(deserialization.is_ok());
-            let deserialization = deserialization.unwrap();
+            let deserialization: u8 = deserialization.unwrap();
             assert_eq!(*expected, deserialization);
         }
     }
@@ -886,7 +2303,7 @@ mod tests {
         ] {
             let deserialization = from_bytes(value);
             assert!(deserialization.is_ok());
-            let deserialization = deserialization.unwrap();
+            let deserialization: u16 = deserialization.unwrap();
             assert_eq!(*expected, deserialization);
         }
         let deserialization: Result<u16> = from_bytes(&[0xA0, 0xC6, 0xC9][..]);
@@ -905,7 +2322,7 @@ mod tests {
         ] {
             let deserialization = from_bytes(value);
             assert!(deserialization.is_ok());
-            let deserialization = deserialization.unwrap();
+            let deserialization: u32 = deserialization.unwrap();
             assert_eq!(*expected, deserialization);
         }
     }
@@ -926,7 +2343,7 @@ mod tests {
         ] {
             let deserialization = from_bytes(value);
             assert!(deserialization.is_ok());
-            let deserialization = deserialization.unwrap();
+            let deserialization: u64 = deserialization.unwrap();
             assert_eq!(*expected, deserialization);
         }
         let deserialization: Result<u64> = from_bytes(
@@ -935,6 +2352,40 @@ mod tests {
         assert!(matches!(deserialization, Err(Error::IntegerOverflow)));
     }
 
+    #[test]
+    fn deserialize_128() {
+        let mut max = vec![0x83];
+        max.extend([0xFF; 17].iter());
+        max.push(0x7F);
+        let deserialization: Result<u128> = from_bytes(&max);
+        assert!(deserialization.is_ok());
+        assert_eq!(u128::MAX, deserialization.unwrap());
+        let deserialization: Result<u128> = from_bytes(&[0x82, 0x2C][..]);
+        assert!(deserialization.is_ok());
+        assert_eq!(300_u128, deserialization.unwrap());
+        let mut min = vec![0xC2];
+        min.extend([0x80; 17].iter());
+        min.push(0x00);
+        let deserialization: Result<i128> = from_bytes(&min);
+        assert!(deserialization.is_ok());
+        assert_eq!(i128::MIN, deserialization.unwrap());
+        let deserialization: Result<i128> = from_bytes(&[0xC4, 0xA2, 0x70][..]);
+        assert!(deserialization.is_ok());
+        assert_eq!(-70_000_i128, deserialization.unwrap());
+        // The magnitude of `i128::MIN` is one too many for a positive value.
+        min[0] = 0x82;
+        assert!(matches!(
+            from_bytes::<i128>(&min),
+            Err(Error::IntegerOverflow)
+        ));
+        max[0] = 0x84;
+        assert!(matches!(
+            from_bytes::<u128>(&max),
+            Err(Error::IntegerOverflow)
+        ));
+    }
+
+    #[cfg(feature = "float")]
     #[test]
     #[allow(clippy::float_cmp)]
     fn deserialize_f32() {
@@ -944,11 +2395,12 @@ mod tests {
         ] {
             let deserialization = from_bytes(value);
             assert!(deserialization.is_ok());
-            let deserialization = deserialization.unwrap();
+            let deserialization: f32 = deserialization.unwrap();
             assert_eq!(*expected, deserialization);
         }
     }
 
+    #[cfg(feature = "float")]
     #[test]
     #[allow(clippy::float_cmp)]
     fn deserialize_f64() {
@@ -962,7 +2414,7 @@ mod tests {
         ] {
             let deserialization = from_bytes(value);
             assert!(deserialization.is_ok());
-            let deserialization = deserialization.unwrap();
+            let deserialization: f64 = deserialization.unwrap();
             assert_eq!(*expected, deserialization);
         }
     }
@@ -977,7 +2429,7 @@ mod tests {
         ] {
             let deserialization = from_bytes(value);
             assert!(deserialization.is_ok());
-            let deserialization = deserialization.unwrap();
+            let deserialization: char = deserialization.unwrap();
             assert_eq!(*expected, deserialization);
         }
     }
@@ -1000,6 +2452,25 @@ mod tests {
         }
     }
 
+    #[test]
+    fn deserialize_borrowed() {
+        #[derive(serde::Deserialize)]
+        struct Message<'a> {
+            #[serde(borrow)]
+            name: std::borrow::Cow<'a, str>,
+            payload: &'a [u8],
+        }
+
+        let encoding = [0x02, b'h', b'i', 0x03, 0x01, 0x02, 0x03];
+        let message: Result<Message> = from_bytes(&encoding);
+        assert!(message.is_ok());
+        let message = message.unwrap();
+        assert!(matches!(message.name, std::borrow::Cow::Borrowed("hi")));
+        assert_eq!(&[1, 2, 3][..], message.payload);
+        assert_eq!(encoding[1..].as_ptr(), message.name.as_ptr());
+        assert_eq!(encoding[4..].as_ptr(), message.payload.as_ptr());
+    }
+
     #[test]
     fn deserialize_str() {
         for (expected, value) in &[
@@ -1237,10 +2708,362 @@ mod tests {
         );
     }
 
+    #[test]
+    fn deserialize_many() {
+        let values: Result<Vec<u16>> =
+            from_bytes_many(&[0x03, 0x01, 0x82, 0x2C, 0xC6, 0x29]);
+        assert!(values.is_ok());
+        let values = values.unwrap();
+        assert_eq!(vec![1, 300, 9001], values);
+        let values: Result<Vec<u16>> = from_bytes_many(&[0xFF, 0x7F, 0x01]);
+        assert!(matches!(values, Err(Error::ValueTruncated)));
+    }
+
+    #[test]
+    fn deserialize_for_each_element() {
+        let bytes = crate::to_bytes(&(vec![3_u16, 200, 1000], 9_u8)).unwrap();
+        let mut deserializer = Deserializer::new(&bytes);
+        let mut total = 0;
+        let count = deserializer.for_each_element(|element: u16| {
+            total += element;
+            Ok(())
+        });
+        assert!(matches!(count, Ok(3)));
+        assert_eq!(1203, total);
+        assert_eq!(
+            9_u8,
+            <u8 as serde::Deserialize>::deserialize(&mut deserializer).unwrap()
+        );
+        let count =
+            Deserializer::new(&bytes).for_each_element(|element: u16| {
+                if element > 100 {
+                    Err(Error::IntegerOverflow)
+                } else {
+                    Ok(())
+                }
+            });
+        assert!(matches!(count, Err(Error::IntegerOverflow)));
+        let count =
+            Deserializer::new(&bytes[..3]).for_each_element(|_: u16| Ok(()));
+        assert!(matches!(count, Err(Error::ValueTruncated)));
+    }
+
+    #[test]
+    fn deserialize_for_each_entry() {
+        let mut map = std::collections::BTreeMap::new();
+        map.insert("apples", 3_u32);
+        map.insert("pears", 5);
+        let bytes = crate::to_bytes(&map).unwrap();
+        let mut entries = Vec::new();
+        let count = Deserializer::new(&bytes).for_each_entry(
+            |key: &str, value: u32| {
+                entries.push((key, value));
+                Ok(())
+            },
+        );
+        assert!(matches!(count, Ok(2)));
+        assert_eq!(vec![("apples", 3), ("pears", 5)], entries);
+    }
+
+    #[test]
+    fn deserialize_partial() {
+        let bytes = [0x82, 0x2C, 0x03, b'a', b'b', b'c', 0xFF];
+        let first = from_bytes_partial::<u16>(&bytes);
+        assert!(first.is_ok());
+        let (first, len) = first.unwrap();
+        assert_eq!(300, first);
+        assert_eq!(2, len);
+        let second = from_bytes_partial::<&str>(&bytes[len..]);
+        assert!(second.is_ok());
+        assert_eq!(("abc", 4), second.unwrap());
+        assert!(matches!(
+            from_bytes_partial::<u16>(&bytes[6..]),
+            Err(Error::ValueTruncated)
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn deserialize_self_describing() {
+        let bytes =
+            [21, 3, 18, 8, 5, 15, 2, b'a', b'b', 24, 1, 25, 2, 7, 1, 7, 2];
+        let config = Config::new().with_self_describing(true);
+        for config in &[config, config.with_deduplication(true)] {
+            let value: Result<crate::Value> = config.from_bytes(&bytes);
+            assert!(value.is_ok());
+            assert_eq!(
+                crate::Value::Seq(vec![
+                    crate::Value::Option(Some(Box::new(crate::Value::U16(5)))),
+                    crate::Value::Str(String::from("ab")),
+                    crate::Value::Variant {
+                        index: 1,
+                        name: String::new(),
+                        value: Box::new(crate::Value::Seq(vec![
+                            crate::Value::U8(1),
+                            crate::Value::U8(2),
+                        ])),
+                    },
+                ]),
+                value.unwrap()
+            );
+        }
+        assert!(matches!(
+            config.from_bytes::<(Option<u8>, &str, u8)>(&bytes),
+            Err(Error::UnexpectedTag(8))
+        ));
+        assert!(matches!(
+            config.from_bytes::<(Option<u16>, &str)>(&bytes),
+            Err(Error::LengthMismatch {
+                expected: 2,
+                actual: 3,
+            })
+        ));
+        assert!(matches!(
+            config.from_bytes::<crate::Value>(&[99]),
+            Err(Error::UnexpectedTag(99))
+        ));
+        assert!(matches!(
+            from_bytes::<crate::Value>(&bytes),
+            Err(Error::TypeUnknown)
+        ));
+    }
+
+    #[test]
+    fn deserialize_skipping() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Old {
+            id: u8,
+        }
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        enum Event {
+            Moved {
+                id: u8,
+            },
+        }
+        let bytes = [21, 2, 23, 2, 7, 1, 15, 1, b'x', 7, 9];
+        let config = Config::new().with_self_describing(true);
+        let decoded = config.from_bytes::<(Old, u8)>(&bytes);
+        assert!(decoded.is_ok());
+        assert_eq!(
+            (
+                Old {
+                    id: 1
+                },
+                9
+            ),
+            decoded.unwrap()
+        );
+        let decoded = config.from_bytes::<(serde::de::IgnoredAny, u8)>(&bytes);
+        assert!(decoded.is_ok());
+        assert_eq!(9, decoded.unwrap().1);
+        let decoded =
+            config.from_bytes::<Event>(&[24, 0, 25, 3, 7, 1, 20, 1, 19, 17]);
+        assert!(decoded.is_ok());
+        assert_eq!(
+            Event::Moved {
+                id: 1
+            },
+            decoded.unwrap()
+        );
+        assert!(matches!(
+            config.from_bytes::<Old>(&[23, 0]),
+            Err(Error::LengthMismatch {
+                expected: 1,
+                actual: 0,
+            })
+        ));
+        assert!(matches!(
+            from_bytes::<(serde::de::IgnoredAny, u8)>(&[1, 2]),
+            Err(Error::TypeUnknown)
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn deserialize_nested_too_deeply() {
+        let mut bytes = [0x14, 0x01].repeat(1_000_000);
+        bytes.push(0x13);
+        let config = Config::new().with_self_describing(true);
+        assert!(matches!(
+            config.from_bytes::<crate::Value>(&bytes),
+            Err(Error::DepthLimitExceeded(128))
+        ));
+        assert!(matches!(
+            config.from_bytes::<serde::de::IgnoredAny>(&bytes),
+            Err(Error::DepthLimitExceeded(128))
+        ));
+        let nested = &bytes[bytes.len() - 7..];
+        let decoded: Result<crate::Value> = config.from_bytes(nested);
+        assert!(decoded.is_ok());
+        assert_eq!(
+            crate::Value::Seq(vec![crate::Value::Seq(vec![
+                crate::Value::Seq(vec![crate::Value::Unit])
+            ])]),
+            decoded.unwrap()
+        );
+        let config = config.with_max_depth(2);
+        assert_eq!(2, config.max_depth());
+        assert!(matches!(
+            config.from_bytes::<crate::Value>(nested),
+            Err(Error::DepthLimitExceeded(2))
+        ));
+        assert!(matches!(
+            config.from_bytes::<(serde::de::IgnoredAny, u8)>(&[
+                0x15, 0x02, 0x12, 0x12, 0x12, 0x13, 0x07, 0x01
+            ]),
+            Err(Error::DepthLimitExceeded(2))
+        ));
+    }
+
+    #[test]
+    fn deserialize_unknown_variants() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        enum New {
+            Stopped,
+            Moved(u16),
+            Turned {
+                angle: u8,
+            },
+        }
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        enum Old {
+            Stopped,
+            #[serde(other)]
+            Unknown,
+        }
+        let events = (
+            vec![New::Moved(300), New::Stopped, New::Turned {
+                angle: 9,
+            }],
+            7_u8,
+        );
+        let expected = (vec![Old::Unknown, Old::Stopped, Old::Unknown], 7);
+        for config in &[
+            Config::new().with_struct_framing(true),
+            Config::new().with_self_describing(true),
+            Config::new().with_struct_framing(true).with_variant_ids(true),
+            Config::new().with_self_describing(true).with_variant_names(true),
+        ] {
+            let encoding = config.to_bytes(&events);
+            assert!(encoding.is_ok());
+            let decoded: Result<(Vec<Old>, u8)> =
+                config.from_bytes(&encoding.unwrap());
+            assert!(decoded.is_ok(), "{:?}", config);
+            assert_eq!(expected, decoded.unwrap());
+        }
+        let config = Config::new().with_variant_ids(true);
+        assert!(matches!(
+            config.from_bytes::<New>(&config.to_bytes(&Old::Unknown).unwrap()),
+            Err(Error::VariantUnknown(0xCC20_0E59))
+        ));
+        // Without framing, the contents of an unknown variant cannot be
+        // skipped, so the value following it would be misread.
+        let events = (
+            New::Turned {
+                angle: 9,
+            },
+            42_u8,
+        );
+        for (config, id) in &[
+            (Config::new(), 2),
+            (Config::new().with_variant_ids(true), 0x4946_B02B),
+            (Config::new().with_variant_names(true), 0x4946_B02B),
+        ] {
+            let encoding = config.to_bytes(&events).unwrap();
+            let decoded = config.from_bytes::<(Old, u8)>(&encoding);
+            assert!(
+                matches!(decoded, Err(Error::VariantUnknown(found)) if found == *id),
+                "{:?}: {:?}",
+                config,
+                decoded
+            );
+        }
+    }
+
+    #[test]
+    fn deserialize_missing_fields() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Old {
+            id: u8,
+        }
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct New {
+            id: u8,
+            #[serde(default)]
+            count: u16,
+        }
+        let old = (
+            Old {
+                id: 1,
+            },
+            Old {
+                id: 2,
+            },
+        );
+        let expected = (
+            New {
+                id: 1,
+                count: 0,
+            },
+            New {
+                id: 2,
+                count: 0,
+            },
+        );
+        for config in &[
+            Config::new().with_struct_framing(true),
+            Config::new().with_self_describing(true),
+        ] {
+            let encoding = config.to_bytes(&old).unwrap();
+            assert!(config.from_bytes::<(New, New)>(&encoding).is_err());
+            let config = config.with_missing_fields(true);
+            let decoded: Result<(New, New)> = config.from_bytes(&encoding);
+            assert!(decoded.is_ok());
+            assert_eq!(expected, decoded.unwrap());
+        }
+        let config = Config::new().with_missing_fields(true);
+        let decoded: Result<New> = config.from_bytes(&[3]);
+        assert!(decoded.is_ok());
+        assert_eq!(
+            New {
+                id: 3,
+                count: 0,
+            },
+            decoded.unwrap()
+        );
+        assert!(matches!(from_bytes::<New>(&[3]), Err(Error::ValueTruncated)));
+        assert!(config.from_bytes::<(u8, u16)>(&[3]).is_err());
+        // Without framing, the first struct takes the second as its missing
+        // field, which is not a complete `u16`.
+        let encoding = config
+            .to_bytes(&(
+                Old {
+                    id: 4,
+                },
+                Old {
+                    id: 200,
+                },
+            ))
+            .unwrap();
+        assert!(matches!(
+            config.from_bytes::<(New, New)>(&encoding),
+            Err(Error::MissingFieldsUnframed)
+        ));
+        let mut values = config.deserializer(&encoding[..]).iterate::<New>();
+        assert!(matches!(
+            values.next(),
+            Some(Err(Error::MissingFieldsUnframed))
+        ));
+        assert!(matches!(
+            Config::new().from_bytes::<(New, New)>(&encoding),
+            Err(Error::ValueTruncated)
+        ));
+    }
+
     #[test]
     fn deserialize_iterate_complete() {
         let mut deserializer =
-            Deserializer::new(&[1, 0x81, 0x7F, 3][..]).iterate();
+            Deserializer::new(&[1, 0x81, 0x7F, 3][..]).iterate::<i32>();
         assert_eq!(0, deserializer.offset());
         assert_eq!(1, deserializer.next().unwrap().unwrap());
         assert_eq!(1, deserializer.offset());
@@ -1251,13 +3074,222 @@ mod tests {
         assert!(deserializer.next().is_none());
     }
 
+    #[test]
+    fn deserialize_iterate_sizes() {
+        let mut deserializer =
+            Deserializer::new(&[1, 0x81, 0x7F, 3, 0x80][..]).iterate::<u16>();
+        assert_eq!(0, deserializer.last_size());
+        assert_eq!(5, deserializer.remaining());
+        assert_eq!(1, deserializer.next().unwrap().unwrap());
+        assert_eq!(1, deserializer.last_size());
+        assert_eq!(4, deserializer.remaining());
+        assert_eq!(255, deserializer.next().unwrap().unwrap());
+        assert_eq!(2, deserializer.last_size());
+        assert_eq!(2, deserializer.remaining());
+        assert_eq!(3, deserializer.next().unwrap().unwrap());
+        assert_eq!(1, deserializer.last_size());
+        assert!(deserializer.next().unwrap().is_err());
+        assert_eq!(1, deserializer.last_size());
+        assert_eq!(1, deserializer.remaining());
+        let mut deserializer = Deserializer::new(&[0x81, 0x7F, 3][..]);
+        assert_eq!(3, deserializer.remaining());
+        assert!(
+            <u16 as serde::Deserialize>::deserialize(&mut deserializer).is_ok()
+        );
+        assert_eq!(2, deserializer.offset());
+        assert_eq!(1, deserializer.remaining());
+    }
+
     #[test]
     fn deserialize_iterate_incomplete() {
-        let mut deserializer = Deserializer::new(&[1, 0x81][..]).iterate();
+        let mut deserializer =
+            Deserializer::new(&[1, 0x81][..]).iterate::<i32>();
         assert_eq!(0, deserializer.offset());
         assert_eq!(1, deserializer.next().unwrap().unwrap());
         assert_eq!(1, deserializer.offset());
         assert!(deserializer.next().unwrap().is_err());
         assert_eq!(1, deserializer.offset());
     }
+
+    #[test]
+    fn deserialize_iterate_skip() {
+        let schema = Schema::of::<(String, u16)>().unwrap();
+        let mut bytes = Vec::new();
+        for record in &[("a", 0_u16), ("bb", 100), ("ccc", 200), ("dddd", 300)]
+        {
+            bytes.extend(crate::to_bytes(record).unwrap());
+        }
+        let mut deserializer = Deserializer::new(&bytes).iterate();
+        assert!(matches!(deserializer.skip_values(&schema, 2), Ok(2)));
+        assert_eq!(7, deserializer.offset());
+        assert_eq!(
+            (String::from("ccc"), 200_u16),
+            deserializer.next().unwrap().unwrap()
+        );
+        assert!(matches!(deserializer.skip_values(&schema, 5), Ok(1)));
+        assert!(deserializer.next().is_none());
+        let mut deserializer = Deserializer::new(&bytes[..bytes.len() - 1])
+            .iterate::<(String, u16)>();
+        assert!(matches!(
+            deserializer.skip_values(&schema, 4),
+            Err(Error::ValueTruncated)
+        ));
+        assert_eq!(13, deserializer.offset());
+        assert!(deserializer.next().unwrap().is_err());
+    }
+
+    #[test]
+    fn deserialize_iterate_resume() {
+        let mut bytes = Vec::new();
+        for record in &[("a", 0_u16), ("bb", 1000), ("ccc", 2)] {
+            bytes.extend(crate::to_bytes(record).unwrap());
+        }
+        let received = &bytes[..6];
+        let mut deserializer = Deserializer::new(received).iterate();
+        assert_eq!(("a", 0_u16), deserializer.next().unwrap().unwrap());
+        for _ in 0..2 {
+            assert!(matches!(
+                deserializer.next(),
+                Some(Err(Error::ValueTruncated))
+            ));
+            assert_eq!(3, deserializer.offset());
+            assert_eq!(3, deserializer.remaining());
+        }
+        let mut deserializer =
+            Deserializer::new(&bytes[deserializer.offset()..]).iterate();
+        assert_eq!(("bb", 1000_u16), deserializer.next().unwrap().unwrap());
+        assert_eq!(("ccc", 2_u16), deserializer.next().unwrap().unwrap());
+        assert!(deserializer.next().is_none());
+    }
+
+    #[test]
+    fn deserialize_after_peek_variant_index() {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        enum Command {
+            Stop,
+            Move(i8),
+        }
+
+        let bytes = [1, 0xFF, 0];
+        let mut deserializer = Deserializer::new(&bytes[..]).iterate();
+        assert!(matches!(peek_variant_index(&bytes), Ok(1)));
+        assert_eq!(Command::Move(-1), deserializer.next().unwrap().unwrap());
+        let mut deserializer = Deserializer::new(&bytes[2..]);
+        assert!(matches!(deserializer.peek_variant_index(), Ok(0)));
+        assert!(matches!(deserializer.peek_variant_index(), Ok(0)));
+        assert_eq!(0, deserializer.offset());
+        assert_eq!(
+            Command::Stop,
+            <Command as serde::Deserialize>::deserialize(&mut deserializer)
+                .unwrap()
+        );
+        assert!(matches!(
+            peek_variant_index(&[0xFF, 0xFF, 0xFF, 0xFF, 0x7F]),
+            Err(Error::IntegerOverflow)
+        ));
+        assert!(matches!(peek_variant_index(&[]), Err(Error::ValueTruncated)));
+    }
+
+    #[test]
+    fn peek_variant_index_of_interned_name() {
+        #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+        enum Command {
+            Stop,
+            Move(i8),
+        }
+
+        let config =
+            Config::new().with_variant_names(true).with_interning(true);
+        let mut bytes = Vec::new();
+        let mut serializer = config.serializer(&mut bytes);
+        for command in &[Command::Move(1), Command::Stop, Command::Move(2)] {
+            assert!(
+                serde::Serialize::serialize(command, &mut serializer).is_ok()
+            );
+        }
+        let mut deserializer = config.deserializer(&bytes[..]);
+        for expected in &[Command::Move(1), Command::Stop, Command::Move(2)] {
+            let peeked = deserializer.peek_variant_index();
+            assert!(peeked.is_ok());
+            assert_eq!(
+                name_hash(match expected {
+                    Command::Stop => "Stop",
+                    Command::Move(_) => "Move",
+                }),
+                peeked.unwrap()
+            );
+            let decoded =
+                <Command as serde::Deserialize>::deserialize(&mut deserializer);
+            assert!(decoded.is_ok());
+            assert_eq!(*expected, decoded.unwrap());
+        }
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Entry {
+        level: u8,
+        message: String,
+        fields: Vec<(String, i32)>,
+        code: u16,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Level {
+        level: u8,
+    }
+
+    fn entry(
+        level: u8,
+        message: &str,
+        code: u16,
+    ) -> Entry {
+        Entry {
+            level,
+            message: String::from(message),
+            fields: vec![(String::from("n"), i32::from(code))],
+            code,
+        }
+    }
+
+    #[test]
+    fn deserialize_iterate_where() {
+        let entries = vec![
+            entry(1, "starting", 10),
+            entry(3, "disk full", 300),
+            entry(2, "retrying", 20),
+            entry(3, "gave up", 301),
+        ];
+        let mut bytes = Vec::new();
+        for entry in &entries {
+            bytes.extend(crate::to_bytes(entry).unwrap());
+        }
+        let schema = Schema::of::<Entry>().unwrap();
+        let mut deserializer = Deserializer::new(&bytes)
+            .iterate_where(schema, |header: &Level| header.level >= 3);
+        let first: Result<Entry> = deserializer.next().unwrap();
+        assert!(first.is_ok());
+        let first = first.unwrap();
+        assert_eq!(entries[1], first);
+        let second = deserializer.next().unwrap();
+        assert!(second.is_ok());
+        let second = second.unwrap();
+        assert_eq!(entries[3], second);
+        assert_eq!(bytes.len(), deserializer.offset());
+        assert!(deserializer.next().is_none());
+    }
+
+    #[test]
+    fn deserialize_iterate_where_incomplete() {
+        let mut bytes = crate::to_bytes(&entry(1, "kept", 1)).unwrap();
+        let end = bytes.len();
+        bytes.extend(crate::to_bytes(&entry(0, "skipped", 2)).unwrap());
+        bytes.extend(&[1, 4, b'l', b'o']);
+        let schema = Schema::of::<Entry>().unwrap();
+        let mut deserializer = Deserializer::new(&bytes)
+            .iterate_where(schema, |header: &Level| header.level > 0);
+        let first: Result<Entry> = deserializer.next().unwrap();
+        assert!(first.is_ok());
+        assert_eq!(end, deserializer.offset());
+        assert!(deserializer.next().unwrap().is_err());
+    }
 }
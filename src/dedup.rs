@@ -0,0 +1,243 @@
+use super::{
+    IntCodec,
+    Varint,
+};
+use alloc::{
+    collections::BTreeMap,
+    vec::Vec,
+};
+
+// This marks where the encoding of one struct began, within both the plain
+// encoding and the deduplicated encoding being built.
+struct Frame {
+    plain_start: usize,
+    pending_start: usize,
+    first_subtree: usize,
+}
+
+// This is used by the serializer, when deduplication is enabled, to replace
+// each struct whose encoding matches that of an earlier struct with a
+// reference back to it.  Each struct is preceded by a varint tag, which is
+// zero if the struct follows, or one more than the index of the earlier
+// struct it repeats.  Structs are numbered in the order their encodings
+// end, counting only those written out in full, which is the order in which
+// the deserializer finds them.
+//
+// Structs are identified by their plain encodings, without tags or
+// references, since a repeat of a struct holding other structs encodes
+// differently from the first, with references where the first has the
+// structs in full.  While any struct is open, bytes are held back, so that
+// a repeat can be taken out and replaced with a reference.
+#[derive(Default)]
+pub(crate) struct Deduplicator {
+    frames: Vec<Frame>,
+    indices: BTreeMap<Vec<u8>, u64>,
+    pending: Vec<u8>,
+    plain: Vec<u8>,
+    subtrees: Vec<Vec<u8>>,
+}
+
+impl Deduplicator {
+    // Start the encoding of a struct.
+    pub(crate) fn begin(&mut self) {
+        self.frames.push(Frame {
+            plain_start: self.plain.len(),
+            pending_start: self.pending.len(),
+            first_subtree: self.subtrees.len(),
+        });
+        self.pending.push(0);
+    }
+
+    // Finish the encoding of a struct, returning the bytes to write out if
+    // it was the outermost struct.
+    pub(crate) fn end(&mut self) -> Option<Vec<u8>> {
+        let frame = self.frames.pop()?;
+        let plain = &self.plain[frame.plain_start..];
+        if let Some(&index) = self.indices.get(plain) {
+            // The structs inside this one were written out in full, but
+            // now will not be, so they can no longer be referenced.
+            for subtree in self.subtrees.drain(frame.first_subtree..) {
+                self.indices.remove(&subtree);
+            }
+            self.pending.truncate(frame.pending_start);
+            // Writing to a `Vec` cannot fail.
+            let _ = Varint.encode_unsigned(index + 1, &mut self.pending);
+        } else {
+            let plain = plain.to_vec();
+            self.indices.insert(plain.clone(), self.subtrees.len() as u64);
+            self.subtrees.push(plain);
+        }
+        if self.frames.is_empty() {
+            self.plain.clear();
+            Some(core::mem::take(&mut self.pending))
+        } else {
+            None
+        }
+    }
+
+    // Put the given bytes at a position marked earlier, such as the length
+    // of a sequence, which is known only once the sequence is encoded.
+    pub(crate) fn insert(
+        &mut self,
+        mark: (usize, usize),
+        bytes: &[u8],
+    ) {
+        let (pending, plain) = mark;
+        self.pending.splice(pending..pending, bytes.iter().copied());
+        self.plain.splice(plain..plain, bytes.iter().copied());
+    }
+
+    // Put the given bytes at a position marked earlier, such as the length
+    // of a struct, leaving them out of the plain encoding, since they
+    // depend on which structs inside it are repeats.
+    pub(crate) fn insert_pending(
+        &mut self,
+        mark: (usize, usize),
+        bytes: &[u8],
+    ) {
+        let (pending, _) = mark;
+        self.pending.splice(pending..pending, bytes.iter().copied());
+    }
+
+    // Determine whether or not a struct is being encoded, in which case
+    // bytes must be given to the deduplicator rather than written out.
+    pub(crate) fn is_open(&self) -> bool {
+        !self.frames.is_empty()
+    }
+
+    // Mark the current position in the encoding, for bytes to be inserted
+    // there later.
+    pub(crate) fn mark(&self) -> (usize, usize) {
+        (self.pending.len(), self.plain.len())
+    }
+
+    // Return the number of bytes held back.
+    pub(crate) fn pending_len(&self) -> usize {
+        self.pending.len()
+    }
+
+    // Return the number of bytes held back since the given position was
+    // marked.
+    pub(crate) fn pending_since(
+        &self,
+        mark: (usize, usize),
+    ) -> usize {
+        self.pending.len() - mark.0
+    }
+
+    // Add bytes to the encoding of the struct being encoded.
+    pub(crate) fn write(
+        &mut self,
+        bytes: &[u8],
+    ) {
+        self.pending.extend_from_slice(bytes);
+        self.plain.extend_from_slice(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Config,
+        Error,
+        Result,
+    };
+
+    #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Point {
+        x: u8,
+        y: u8,
+    }
+
+    #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Line {
+        from: Point,
+        to: Point,
+    }
+
+    #[test]
+    fn repeated_structs_are_referenced() {
+        let config = Config::new().with_deduplication(true);
+        let a = Point {
+            x: 1,
+            y: 2,
+        };
+        let b = Point {
+            x: 3,
+            y: 4,
+        };
+        let line = Line {
+            from: a.clone(),
+            to: b,
+        };
+        let value = (vec![line.clone(), line], a);
+        let encoding = config.to_bytes(&value);
+        assert!(encoding.is_ok());
+        let encoding = encoding.unwrap();
+        // The first line is written in full, with its points numbered 0 and
+        // 1 and itself 2.  The second line refers to the first, and the
+        // last point to the first point.
+        assert_eq!(
+            &[0x02, 0x00, 0x00, 0x01, 0x02, 0x00, 0x03, 0x04, 0x03, 0x01][..],
+            encoding
+        );
+        let decoded: Result<(Vec<Line>, Point)> = config.from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!(value, decoded.unwrap());
+    }
+
+    #[test]
+    fn references_inside_repeats_are_not_counted() {
+        let config = Config::new().with_deduplication(true);
+        let point = Point {
+            x: 5,
+            y: 5,
+        };
+        let line = Line {
+            from: point.clone(),
+            to: point,
+        };
+        let value = (line.clone(), line, Point {
+            x: 6,
+            y: 6,
+        });
+        let encoding = config.to_bytes(&value).unwrap();
+        let decoded: Result<(Line, Line, Point)> = config.from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!(value, decoded.unwrap());
+        assert!(matches!(
+            config.from_bytes::<Point>(&[0x01]),
+            Err(Error::InvalidReference(1))
+        ));
+    }
+
+    #[test]
+    fn nested_repeats_are_limited() {
+        #[derive(Debug, serde::Deserialize)]
+        struct Node {
+            #[allow(dead_code)]
+            children: Vec<Node>,
+        }
+
+        // Each node holds two references to the node before it, so the
+        // number of nodes decoded doubles with every four bytes.
+        let levels = 40_u8;
+        let mut encoding = vec![0x00, levels + 1, 0x00, 0x00];
+        for level in 1..=levels {
+            encoding.extend(&[0x00, 0x02, level, level]);
+        }
+        let config = Config::new().with_deduplication(true);
+        assert!(matches!(
+            config.from_bytes::<Node>(&encoding),
+            Err(Error::ReplayLimitExceeded(_))
+        ));
+        let shallow = &mut encoding[..4 * 4];
+        shallow[1] = 4;
+        let decoded = config.from_bytes::<Node>(shallow);
+        assert!(decoded.is_ok());
+        assert!(matches!(
+            config.with_max_replay_ratio(0).from_bytes::<Node>(shallow),
+            Err(Error::ReplayLimitExceeded(0))
+        ));
+    }
+}
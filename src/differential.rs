@@ -0,0 +1,127 @@
+// These tests check this crate against bincode and postcard, two other
+// binary serde formats, by encoding the same values with each of them and
+// checking that all three decode them back the same, so that a system moving
+// to this format from one of the others can count on it to carry the same
+// values.  Where this format and postcard are known to encode values the same
+// way, namely for bytes, booleans, options, unit and newtype variants, and
+// strings and sequences shorter than 128 elements, the encodings are compared
+// too.
+
+use super::{
+    from_bytes,
+    to_bytes,
+};
+use serde::{
+    de::DeserializeOwned,
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+};
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+enum Shape {
+    Empty,
+    Dot(u8),
+    Line(i32, i32),
+    Circle {
+        radius: f64,
+        label: Option<String>,
+    },
+}
+
+// Encode the value with each format, decode each encoding with the format
+// which made it, and check that all the formats give back the value.
+fn assert_formats_agree<T>(value: &T)
+where
+    T: Debug + DeserializeOwned + PartialEq + Serialize,
+{
+    let ours = to_bytes(value).unwrap();
+    assert_eq!(value, &from_bytes::<T>(&ours).unwrap(), "this crate");
+    let bincode = bincode::serialize(value).unwrap();
+    assert_eq!(value, &bincode::deserialize::<T>(&bincode).unwrap(), "bincode");
+    let postcard = postcard::to_stdvec(value).unwrap();
+    assert_eq!(
+        value,
+        &postcard::from_bytes::<T>(&postcard).unwrap(),
+        "postcard"
+    );
+}
+
+// Check that this crate and postcard encode the value the same way.
+fn assert_same_as_postcard<T>(value: &T)
+where
+    T: Debug + DeserializeOwned + PartialEq + Serialize,
+{
+    assert_formats_agree(value);
+    assert_eq!(
+        postcard::to_stdvec(value).unwrap(),
+        to_bytes(value).unwrap(),
+        "{value:?}"
+    );
+}
+
+#[test]
+fn primitives() {
+    assert_formats_agree(&true);
+    assert_formats_agree(&u8::MAX);
+    assert_formats_agree(&300_u16);
+    assert_formats_agree(&-70000_i32);
+    assert_formats_agree(&u64::MAX);
+    assert_formats_agree(&i64::MIN);
+    assert_formats_agree(&'é');
+    assert_formats_agree(&String::from("x").repeat(1000));
+    assert_formats_agree(&());
+    assert_same_as_postcard(&false);
+    assert_same_as_postcard(&0x7F_u8);
+    assert_same_as_postcard(&String::from("hello"));
+}
+
+#[test]
+fn options() {
+    assert_formats_agree(&Some(-5_i16));
+    assert_formats_agree(&None::<u64>);
+    assert_formats_agree(&Some(Some(String::from("nested"))));
+    assert_same_as_postcard(&Some(5_u8));
+    assert_same_as_postcard(&None::<u8>);
+    assert_same_as_postcard(&Some(None::<bool>));
+}
+
+#[test]
+fn sequences() {
+    assert_formats_agree(&vec![300_u16; 200]);
+    assert_formats_agree(&(1_u8, String::from("two"), 3_u64));
+    assert_formats_agree(&[-1_i64, 0, 1]);
+    assert_formats_agree(
+        &(0..10_u32).map(|i| (i, i.to_string())).collect::<BTreeMap<_, _>>(),
+    );
+    assert_formats_agree(&Vec::<Vec<u8>>::new());
+    assert_same_as_postcard(&vec![1_u8, 2, 3]);
+    assert_same_as_postcard(&(true, 9_u8, String::from("tuple")));
+    assert_same_as_postcard(&vec![vec![true], vec![], vec![false, true]]);
+}
+
+#[test]
+fn enums() {
+    assert_formats_agree(&Shape::Empty);
+    assert_formats_agree(&Shape::Dot(7));
+    assert_formats_agree(&Shape::Line(-1, 100_000));
+    assert_formats_agree(&vec![Shape::Dot(1), Shape::Empty]);
+    assert_same_as_postcard(&Shape::Empty);
+    assert_same_as_postcard(&Shape::Dot(7));
+    assert_same_as_postcard(&vec![Shape::Dot(1), Shape::Empty]);
+}
+
+#[cfg(feature = "float")]
+#[test]
+fn floats() {
+    assert_formats_agree(&1.5_f32);
+    assert_formats_agree(&-0.1_f64);
+    assert_formats_agree(&(1_u8, String::from("two"), 3.0_f64));
+    assert_formats_agree(&Shape::Circle {
+        radius: 2.5,
+        label: Some(String::from("wheel")),
+    });
+}
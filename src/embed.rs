@@ -0,0 +1,117 @@
+use super::{
+    to_bytes,
+    Error,
+    Result,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+/// This function is used in build scripts to encode a value into a file in
+/// the build output directory (`OUT_DIR`), from which it can be embedded in
+/// the crate being built using [`include_serialized!`].  Assets encoded this
+/// way are ready to decode with [`from_bytes`] when the program starts,
+/// without having to be parsed or converted first.
+///
+/// [`include_serialized!`]: macro.include_serialized.html
+/// [`from_bytes`]: fn.from_bytes.html
+///
+/// # Errors
+///
+/// Returns [`Error::Io`] if the `OUT_DIR` environment variable is not set
+/// (because the function was not called from a build script) or the file
+/// could not be written, and otherwise any error from [`to_bytes`].
+///
+/// [`Error::Io`]: enum.Error.html#variant.Io
+/// [`to_bytes`]: fn.to_bytes.html
+///
+/// # Examples
+///
+/// In `build.rs`:
+///
+/// ```rust,no_run
+/// # extern crate serialization;
+/// # #[derive(serde::Serialize)]
+/// # struct Level { name: &'static str, width: u16, height: u16 }
+/// fn main() {
+///     let level = Level {
+///         name: "Tutorial",
+///         width: 64,
+///         height: 32,
+///     };
+///     serialization::write_serialized("tutorial.level", &level).unwrap();
+/// }
+/// ```
+///
+/// In the crate:
+///
+/// ```rust,ignore
+/// static TUTORIAL: &[u8] = serialization::include_serialized!("tutorial.level");
+///
+/// let level: Level = serialization::from_bytes(TUTORIAL).unwrap();
+/// ```
+pub fn write_serialized<T>(
+    file_name: &str,
+    value: &T,
+) -> Result<PathBuf>
+where
+    T: serde::Serialize,
+{
+    let out_dir = std::env::var_os("OUT_DIR").ok_or_else(|| {
+        Error::Io(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            "OUT_DIR is not set; write_serialized must be called from a build \
+             script",
+        ))
+    })?;
+    write_serialized_in(Path::new(&out_dir), file_name, value)
+}
+
+fn write_serialized_in<T>(
+    dir: &Path,
+    file_name: &str,
+    value: &T,
+) -> Result<PathBuf>
+where
+    T: serde::Serialize,
+{
+    let path = dir.join(file_name);
+    std::fs::write(&path, to_bytes(value)?).map_err(Error::Io)?;
+    Ok(path)
+}
+
+/// This macro embeds, as a `&'static [u8]`, the encoding of a value written
+/// to the build output directory by [`write_serialized`] from the crate's
+/// build script.
+///
+/// [`write_serialized`]: fn.write_serialized.html
+#[macro_export]
+macro_rules! include_serialized {
+    ($file_name:expr) => {
+        include_bytes!(concat!(env!("OUT_DIR"), "/", $file_name)) as &[u8]
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_bytes;
+
+    #[test]
+    fn written_file_holds_encoding() {
+        let dir = std::env::temp_dir()
+            .join(format!("serialization-embed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let asset = (String::from("sprites"), vec![3_u8, 1, 4]);
+        let path = write_serialized_in(&dir, "sprites.bin", &asset);
+        assert!(path.is_ok());
+        let path = path.unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(to_bytes(&asset).unwrap(), bytes);
+        let decoded: Result<(String, Vec<u8>)> = from_bytes(&bytes);
+        assert!(decoded.is_ok());
+        assert_eq!(asset, decoded.unwrap());
+    }
+}
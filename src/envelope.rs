@@ -0,0 +1,313 @@
+use super::{
+    from_bytes,
+    registry::{
+        Payload,
+        TaggedValue,
+    },
+    to_bytes,
+    Error,
+    Named,
+    Result,
+};
+use std::collections::HashMap;
+
+/// This type pairs the encoding of a value with the identifier of the
+/// value's type, so that messages of many different types can be carried
+/// through one channel (such as a message bus or plugin interface) and
+/// decoded by a receiver which knows which types to expect.
+///
+/// An envelope is encoded exactly as [`to_bytes_tagged`] encodes a trait
+/// object, so the two can be used interchangeably.
+///
+/// [`to_bytes_tagged`]: fn.to_bytes_tagged.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnyEnvelope {
+    type_name: String,
+    payload: Vec<u8>,
+}
+
+impl AnyEnvelope {
+    /// Decode the value carried in the envelope, as type `T`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::TypeMismatch`] if the envelope carries a value of
+    /// some other type, or any error from decoding the payload.
+    ///
+    /// [`Error::TypeMismatch`]: enum.Error.html#variant.TypeMismatch
+    pub fn decode<'de, T>(&'de self) -> Result<T>
+    where
+        T: Named + serde::Deserialize<'de>,
+    {
+        if self.is::<T>() {
+            from_bytes(&self.payload)
+        } else {
+            Err(Error::TypeMismatch {
+                expected: T::TYPE_NAME,
+                found: self.type_name.clone(),
+            })
+        }
+    }
+
+    /// Return a new envelope holding the given identifier and payload,
+    /// which should be the encoding of a value of the identified type.
+    #[must_use]
+    pub fn from_parts<N, P>(
+        type_name: N,
+        payload: P,
+    ) -> Self
+    where
+        N: Into<String>,
+        P: Into<Vec<u8>>,
+    {
+        Self {
+            type_name: type_name.into(),
+            payload: payload.into(),
+        }
+    }
+
+    /// Determine whether or not the envelope carries a value of type `T`.
+    #[must_use]
+    pub fn is<T>(&self) -> bool
+    where
+        T: Named,
+    {
+        self.type_name == T::TYPE_NAME
+    }
+
+    /// Return a new envelope carrying the encoding of the given value.
+    ///
+    /// # Errors
+    ///
+    /// Any error from encoding the value is returned.
+    pub fn new<T>(value: &T) -> Result<Self>
+    where
+        T: Named + serde::Serialize,
+    {
+        Ok(Self {
+            type_name: String::from(T::TYPE_NAME),
+            payload: to_bytes(value)?,
+        })
+    }
+
+    /// Return the encoding of the value carried in the envelope.
+    #[must_use]
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    /// Return the identifier of the type of value carried in the envelope.
+    #[must_use]
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+}
+
+impl serde::Serialize for AnyEnvelope {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serde::Serialize::serialize(
+            &TaggedValue {
+                name: &self.type_name,
+                payload: &self.payload,
+            },
+            serializer,
+        )
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for AnyEnvelope {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (type_name, Payload(payload)) =
+            serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self {
+            type_name,
+            payload,
+        })
+    }
+}
+
+type Handler<R> = Box<dyn Fn(&[u8]) -> Result<R>>;
+
+/// This type routes envelopes to handlers registered for the types of
+/// values they carry, decoding each value into its registered type before
+/// passing it to the handler.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     AnyEnvelope,
+///     Dispatcher,
+///     Named,
+/// };
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Ping(u32);
+///
+/// impl Named for Ping {
+///     const TYPE_NAME: &'static str = "Ping";
+/// }
+///
+/// let mut dispatcher = Dispatcher::new();
+/// dispatcher.on(|ping: Ping| format!("pong {}", ping.0));
+/// let envelope = AnyEnvelope::new(&Ping(7)).unwrap();
+/// assert_eq!("pong 7", dispatcher.dispatch(&envelope).unwrap());
+/// ```
+pub struct Dispatcher<R> {
+    handlers: HashMap<&'static str, Handler<R>>,
+}
+
+impl<R> Dispatcher<R> {
+    /// Decode the value carried in the given envelope and pass it to the
+    /// handler registered for its type, returning whatever the handler
+    /// returns.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnknownType`] if no handler is registered for the
+    /// type of value in the envelope, or any error from decoding the value.
+    ///
+    /// [`Error::UnknownType`]: enum.Error.html#variant.UnknownType
+    pub fn dispatch(
+        &self,
+        envelope: &AnyEnvelope,
+    ) -> Result<R> {
+        self.handlers
+            .get(envelope.type_name())
+            .ok_or_else(|| Error::UnknownType(envelope.type_name.clone()))
+            .and_then(|handler| handler(envelope.payload()))
+    }
+
+    /// Determine whether or not a handler is registered for the type with
+    /// the given identifier.
+    #[must_use]
+    pub fn handles(
+        &self,
+        type_name: &str,
+    ) -> bool {
+        self.handlers.contains_key(type_name)
+    }
+
+    /// Return a new dispatcher with no handlers registered.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            handlers: HashMap::new(),
+        }
+    }
+
+    /// Register the given handler for values of type `T`, replacing any
+    /// handler previously registered for the type.
+    pub fn on<T, F>(
+        &mut self,
+        handler: F,
+    ) -> &mut Self
+    where
+        T: Named + serde::de::DeserializeOwned,
+        F: Fn(T) -> R + 'static,
+    {
+        self.handlers.insert(
+            T::TYPE_NAME,
+            Box::new(move |payload| from_bytes(payload).map(&handler)),
+        );
+        self
+    }
+}
+
+impl<R> Default for Dispatcher<R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_bytes_tagged;
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Login {
+        user: String,
+    }
+
+    impl Named for Login {
+        const TYPE_NAME: &'static str = "Login";
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Logout;
+
+    impl Named for Logout {
+        const TYPE_NAME: &'static str = "Logout";
+    }
+
+    #[test]
+    fn envelope_round_trip() {
+        let login = Login {
+            user: String::from("alice"),
+        };
+        let envelope = AnyEnvelope::new(&login);
+        assert!(envelope.is_ok());
+        let envelope = envelope.unwrap();
+        assert_eq!("Login", envelope.type_name());
+        assert!(envelope.is::<Login>());
+        assert!(!envelope.is::<Logout>());
+        let encoding = to_bytes(&envelope).unwrap();
+        assert_eq!(to_bytes_tagged(&login).unwrap(), encoding);
+        let decoded: Result<AnyEnvelope> = from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        let decoded = decoded.unwrap();
+        assert_eq!(envelope, decoded);
+        let decoded = decoded.decode::<Login>();
+        assert!(decoded.is_ok());
+        assert_eq!(login, decoded.unwrap());
+    }
+
+    #[test]
+    fn decode_wrong_type() {
+        let envelope = AnyEnvelope::new(&Logout).unwrap();
+        assert!(matches!(
+            envelope.decode::<Login>(),
+            Err(Error::TypeMismatch {
+                expected: "Login",
+                found,
+            }) if found == "Logout"
+        ));
+    }
+
+    #[test]
+    fn dispatch_to_registered_handlers() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher
+            .on(|login: Login| format!("hello, {}", login.user))
+            .on(|_: Logout| String::from("goodbye"));
+        assert!(dispatcher.handles("Login"));
+        let envelopes = [
+            AnyEnvelope::new(&Login {
+                user: String::from("bob"),
+            })
+            .unwrap(),
+            AnyEnvelope::new(&Logout).unwrap(),
+        ];
+        let replies = envelopes
+            .iter()
+            .map(|envelope| dispatcher.dispatch(envelope).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["hello, bob", "goodbye"], replies);
+        let unknown = AnyEnvelope::from_parts("Reboot", vec![]);
+        assert!(matches!(
+            dispatcher.dispatch(&unknown),
+            Err(Error::UnknownType(type_name)) if type_name == "Reboot"
+        ));
+    }
+}
@@ -1,4 +1,8 @@
-use std::str::Utf8Error;
+use alloc::string::{
+    String,
+    ToString,
+};
+use core::str::Utf8Error;
 
 /// This is the enumeration of all the different kinds of errors which this
 /// crate generates.
@@ -15,11 +19,14 @@ pub enum Error {
     #[error("ran out of bytes while deserializing value")]
     ValueTruncated,
 
-    /// This serializer does not support serializing unbounded sequences.
+    /// This was returned when serializing a sequence or map of unknown
+    /// length, which the serializer now supports, so it is no longer
+    /// returned.
     #[error("cannot serialize container of unknown length")]
     LengthRequired,
 
-    /// This serializer does not support deserializing values of unknown types.
+    /// This serializer does not support deserializing values of unknown
+    /// types, unless the encoding is self-describing.
     #[error("cannot deserialize without knowing type")]
     TypeUnknown,
 
@@ -37,12 +44,246 @@ pub enum Error {
     /// cannot determine them during deserialization.
     #[error("cannot determine identifiers in this format")]
     IdentifierUnknown,
+
+    /// A value encoded to more bytes than the maximum serialized size
+    /// declared for its type.
+    #[error("encoded value exceeds the maximum size of its type")]
+    RecordSizeExceeded,
+
+    /// A value was tagged with the identifier of a type which is not known
+    /// to the registry decoding it.
+    #[error("unknown type \"{0}\"")]
+    UnknownType(String),
+
+    /// A value of one type was expected, but a value of another type
+    /// was found.
+    #[error("expected value of type \"{expected}\" but found \"{found}\"")]
+    TypeMismatch {
+        /// This is the identifier of the type which was expected.
+        expected: &'static str,
+
+        /// This is the identifier of the type which was found.
+        found: String,
+    },
+
+    /// The schema of a type could not be determined, either because the
+    /// type is recursive with no finite values, or because tracing it did
+    /// not find all of its enum variants.
+    #[error("cannot determine the schema of type \"{0}\"")]
+    UntraceableType(&'static str),
+
+    /// A value was encoded with a fingerprint of a different type than the
+    /// one it is being decoded as.
+    #[error(
+        "type fingerprint mismatch (expected {expected:08X}, found {found:08X})"
+    )]
+    FingerprintMismatch {
+        /// This is the fingerprint of the type being decoded.
+        expected: u32,
+
+        /// This is the fingerprint found in the encoding.
+        found: u32,
+    },
+
+    /// Two peers could not agree on a version of the encoding to use,
+    /// because the ranges of versions they support do not overlap.
+    #[error("no version of the encoding is supported by both peers")]
+    NoCommonVersion,
+
+    /// An error occurred reading or writing a file or stream.
+    #[cfg(feature = "std")]
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+
+    /// The buffer given to [`to_slice`] is too short to hold the encoding,
+    /// or there is no room left for it in the ring of an [`ShmSender`].
+    ///
+    /// [`to_slice`]: fn.to_slice.html
+    /// [`ShmSender`]: struct.ShmSender.html
+    #[error("buffer is too short to hold the encoding")]
+    BufferFull,
+
+    /// An error of the given kind occurred reading or writing an
+    /// [`embedded_io`] stream through an [`EmbeddedOutput`] or
+    /// [`EmbeddedInput`].
+    ///
+    /// [`embedded_io`]: https://docs.rs/embedded-io/0.6
+    /// [`EmbeddedOutput`]: struct.EmbeddedOutput.html
+    /// [`EmbeddedInput`]: struct.EmbeddedInput.html
+    #[cfg(feature = "embedded-io")]
+    #[error("embedded I/O error: {0:?}")]
+    EmbeddedIo(embedded_io::ErrorKind),
+
+    /// Text being decoded as JSON is not valid JSON.  The offset of the
+    /// byte where the problem was found is given.
+    #[error("invalid JSON at offset {0}")]
+    JsonSyntax(usize),
+
+    /// A field was requested by name from a schema which does not describe
+    /// a struct with a field of that name.
+    #[error("no field \"{0}\" in schema")]
+    UnknownField(String),
+
+    /// A value was sealed using a key which the keyring opening it does
+    /// not hold.
+    #[error("no key with identifier {0}")]
+    UnknownKey(u32),
+
+    /// A value could not be sealed because no key was made the current key
+    /// of the keyring.
+    #[error("no current key to seal with")]
+    NoCurrentKey,
+
+    /// An event given to an event writer does not fit the structure of
+    /// the encoding being written.
+    #[error("event does not fit the encoding being written")]
+    UnexpectedEvent,
+
+    /// The encoding of a value would be longer than the maximum output size
+    /// configured for the serializer, which is given.
+    #[error("encoding exceeds the maximum output size of {0} bytes")]
+    OutputLimitExceeded(usize),
+
+    /// A character encoded as its Unicode scalar value was given a number
+    /// which is not a Unicode scalar value, which is given.
+    #[error("{0:#X} is not a Unicode scalar value")]
+    InvalidChar(u32),
+
+    /// An iterator being encoded produced a different number of items than
+    /// it reported it would, or a tuple in a self-describing encoding had a
+    /// different number of items than its type has, or a struct fewer.
+    #[error("expected {expected} items, but {actual} were produced")]
+    LengthMismatch {
+        /// This is the number of items the iterator reported, or the type
+        /// has.
+        expected: usize,
+
+        /// This is the number of items the iterator produced, or the
+        /// encoding has.
+        actual: usize,
+    },
+
+    /// A value of the type with the given identifier was found where only
+    /// other types are permitted.
+    #[error("type not allowed: {0}")]
+    TypeNotAllowed(String),
+
+    /// A floating-point value was encountered, but the crate was built
+    /// without the `float` feature.
+    #[error("floating-point values are not supported")]
+    FloatUnsupported,
+
+    /// A field of a struct with the given name was left out of its
+    /// encoding, which shape validation does not permit.
+    #[error("field skipped: {0}")]
+    FieldSkipped(String),
+
+    /// A struct encoded with deduplication enabled referred to an earlier
+    /// struct, numbered one less than the given number, which was not found.
+    #[error("reference to unknown struct {0}")]
+    InvalidReference(u64),
+
+    /// A self-describing encoding held the given tag where the tag of a
+    /// value of another type was expected, or a tag not known at all.
+    #[error("unexpected tag {0}")]
+    UnexpectedTag(u8),
+
+    /// An interned field name or map key referred to an earlier string,
+    /// numbered one less than the given number, which was not found.
+    #[error("reference to unknown name {0}")]
+    InvalidNameReference(u64),
+
+    /// An enum value encoded with variants identified by hashes of their
+    /// names held the given hash, which matches none of the variants of
+    /// the enum being decoded, or an enum value whose contents cannot be
+    /// skipped, since the encoding is neither framed nor self-describing,
+    /// held a variant index, or the hash of a variant name, matching none
+    /// of them.
+    #[error("unknown variant identifier {0}")]
+    VariantUnknown(u32),
+
+    /// Bytes being decoded as a versioned encoding do not start with the
+    /// magic number which such encodings start with.
+    #[error("magic number mismatch")]
+    MagicMismatch,
+
+    /// A versioned encoding was of the given version, which is later than
+    /// the version of the type being decoded.
+    #[error("version {0} is newer than the type being decoded")]
+    VersionTooNew(u32),
+
+    /// A versioned encoding could not be upgraded, because no migration
+    /// from the given version to the next was registered.
+    #[error("no migration from version {0}")]
+    MigrationMissing(u32),
+
+    /// A value decoded without knowing its type was nested inside more
+    /// values than the configured limit, which is given, allows.
+    #[error("values nested deeper than the limit of {0}")]
+    DepthLimitExceeded(usize),
+
+    /// Repeated structs in an encoding with deduplication enabled would be
+    /// decoded again for more than the given number of bytes, which is the
+    /// configured multiple of the bytes of the input decoded.
+    #[error("repeated structs expand beyond the limit of {0} bytes")]
+    ReplayLimitExceeded(usize),
+
+    /// A frame being encoded or decoded by a [`SerializationCodec`] was of
+    /// the given length, which is longer than the codec allows.
+    ///
+    /// [`SerializationCodec`]: struct.SerializationCodec.html
+    #[error("frame of {0} bytes is too long")]
+    FrameTooLong(u64),
+
+    /// A sequence of structs could not be converted to or from an Arrow
+    /// record batch, such as because a field has no Arrow column type, or
+    /// a column is missing.
+    #[cfg(feature = "arrow")]
+    #[error("Arrow conversion failed")]
+    Arrow(#[from] arrow_schema::ArrowError),
+
+    /// A compression being decompressed by a [`ZstdDictionary`] was longer,
+    /// once decompressed, than the given number of bytes, which is the most
+    /// the compressor allows.
+    ///
+    /// [`ZstdDictionary`]: struct.ZstdDictionary.html
+    #[error("decompression is longer than the limit of {0} bytes")]
+    DecompressedTooLong(usize),
+
+    /// A value was to be encoded with a fingerprint of its type by
+    /// [`Config::to_bytes`], which cannot find the fingerprint, instead of
+    /// [`Config::to_bytes_with_fingerprint`].
+    ///
+    /// [`Config::to_bytes`]: struct.Config.html#method.to_bytes
+    /// [`Config::to_bytes_with_fingerprint`]: struct.Config.html#method.to_bytes_with_fingerprint
+    #[error("cannot find the fingerprint of the type being encoded")]
+    FingerprintUnavailable,
+
+    /// The bytes ran out while decoding with missing fields allowed by
+    /// [`Config::with_missing_fields`], but without struct framing or
+    /// self-description.  The ends of structs are not encoded then, so a
+    /// struct encoded with fewer fields than it is decoded with, which is
+    /// not the last value in the encoding, takes the values after it as
+    /// its missing fields, leaving too few bytes for the rest.
+    ///
+    /// [`Config::with_missing_fields`]: struct.Config.html#method.with_missing_fields
+    #[error(
+        "ran out of bytes, possibly because fields are missing from a struct \
+         which is not last, and struct framing is not enabled"
+    )]
+    MissingFieldsUnframed,
+
+    /// A number in text being decoded as JSON is too large in magnitude
+    /// for the type it is decoded as, even as a float.  The offset of the
+    /// number is given.
+    #[error("JSON number at offset {0} is out of range")]
+    JsonNumberOutOfRange(usize),
 }
 
 impl serde::ser::Error for Error {
     fn custom<T>(msg: T) -> Self
     where
-        T: std::fmt::Display,
+        T: core::fmt::Display,
     {
         Error::Message(msg.to_string())
     }
@@ -51,7 +292,7 @@ impl serde::ser::Error for Error {
 impl serde::de::Error for Error {
     fn custom<T>(msg: T) -> Self
     where
-        T: std::fmt::Display,
+        T: core::fmt::Display,
     {
         Error::Message(msg.to_string())
     }
@@ -61,4 +302,4 @@ impl serde::de::Error for Error {
 /// `Result` type, when the `Error` type argument is our own [`Error`] type.
 ///
 /// [`Error`]: struct.Error.html
-pub type Result<T> = std::result::Result<T, Error>;
+pub type Result<T> = core::result::Result<T, Error>;
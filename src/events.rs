@@ -0,0 +1,885 @@
+use super::{
+    registry::Bytes,
+    Deserializer,
+    Error,
+    Result,
+    Schema,
+    Serializer,
+    Variant,
+    VariantKind,
+};
+use serde::{
+    Serialize,
+    Serializer as _,
+};
+
+/// This is one step in walking through an encoded value, as reported by an
+/// [`EventReader`].  Compound values are reported as an event marking
+/// their start, the events of their parts, and an event marking their
+/// end.  Newtype structs are reported as the value they wrap.
+///
+/// [`EventReader`]: struct.EventReader.html
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Event<'de> {
+    /// This is a `bool` value.
+    Bool(bool),
+
+    /// This is an `i8` value.
+    I8(i8),
+
+    /// This is an `i16`, `i32`, or `i64` value.
+    I64(i64),
+
+    /// This is an `i128` value.
+    I128(i128),
+
+    /// This is a `u8` value.
+    U8(u8),
+
+    /// This is a `u16`, `u32`, or `u64` value.
+    U64(u64),
+
+    /// This is a `u128` value.
+    U128(u128),
+
+    /// This is an `f32` value.
+    F32(f32),
+
+    /// This is an `f64` value.
+    F64(f64),
+
+    /// This is a `char` value.
+    Char(char),
+
+    /// This is a string, given as the bytes of its encoding, which is
+    /// meant to be UTF-8 but is not checked.
+    Str(&'de [u8]),
+
+    /// This is a byte string.
+    Bytes(&'de [u8]),
+
+    /// This is an absent optional value.
+    None,
+
+    /// This is a present optional value, whose events follow.
+    Some,
+
+    /// This is a unit value, unit struct, or the contents of a unit
+    /// variant.
+    Unit,
+
+    /// This starts a sequence with the given number of elements.
+    SeqStart(usize),
+
+    /// This ends a sequence.
+    SeqEnd,
+
+    /// This starts a tuple or tuple struct with the given number of
+    /// fields.
+    TupleStart(usize),
+
+    /// This ends a tuple or tuple struct.
+    TupleEnd,
+
+    /// This starts a map with the given number of entries, each given as
+    /// the events of the key followed by the events of the value.
+    MapStart(usize),
+
+    /// This ends a map.
+    MapEnd,
+
+    /// This starts a struct or struct variant with the given name.
+    StructStart(&'static str),
+
+    /// This precedes the events of the struct field with the given name.
+    Field(&'static str),
+
+    /// This ends a struct or struct variant.
+    StructEnd,
+
+    /// This is the variant of an enum value, whose contents follow as one
+    /// more value: unit for a unit variant, the wrapped value for a newtype
+    /// variant, a tuple for a tuple variant, or a struct for a struct
+    /// variant.
+    Variant {
+        /// This is the position of the variant in the declaration of the
+        /// enum, counting from zero.
+        index: u32,
+
+        /// This is the name of the variant.
+        name: &'static str,
+    },
+}
+
+// This is an item of work remaining for an event reader.
+enum Task<'de, 's> {
+    Value(&'s Schema),
+    Event(Event<'de>),
+    Elements {
+        element: &'s Schema,
+        remaining: usize,
+    },
+    Entries {
+        key: &'s Schema,
+        value: &'s Schema,
+        remaining: usize,
+    },
+    Leave,
+}
+
+/// This walks through a stream of encoded values, described by a schema,
+/// reporting what it finds as a series of [`Event`]s, without decoding the
+/// values into any Rust type.  This allows tools such as viewers,
+/// transcoders, and validators to handle encodings of any type generically.
+///
+/// [`Event`]: enum.Event.html
+pub struct EventReader<'de, 's> {
+    de: Deserializer<'de>,
+    schema: &'s Schema,
+    tasks: Vec<Task<'de, 's>>,
+    named: Vec<&'s Schema>,
+    failed: bool,
+}
+
+impl<'de, 's> EventReader<'de, 's> {
+    /// Return a new event reader which walks through the given stream of
+    /// encoded values, each described by the given schema.
+    #[must_use]
+    pub fn new(
+        schema: &'s Schema,
+        bytes: &'de [u8],
+    ) -> Self {
+        Self {
+            de: Deserializer::new(bytes),
+            schema,
+            tasks: Vec::new(),
+            named: Vec::new(),
+            failed: false,
+        }
+    }
+
+    /// Return the next event, or `None` once the end of the stream is
+    /// reached.
+    ///
+    /// # Errors
+    ///
+    /// Any of the errors returned by [`from_bytes`] may be returned from
+    /// decoding the part of the value reported by the event.  No more
+    /// events are returned after an error.
+    ///
+    /// [`from_bytes`]: fn.from_bytes.html
+    pub fn next_event(&mut self) -> Result<Option<Event<'de>>> {
+        if self.failed {
+            return Ok(None);
+        }
+        let event = self.read_event();
+        if event.is_err() {
+            self.failed = true;
+        }
+        event
+    }
+
+    /// Return the number of bytes walked through so far.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.de.offset()
+    }
+
+    fn read_event(&mut self) -> Result<Option<Event<'de>>> {
+        loop {
+            let task = match self.tasks.pop() {
+                Some(task) => task,
+                None if self.de.remaining() == 0 => return Ok(None),
+                None => Task::Value(self.schema),
+            };
+            match task {
+                Task::Value(schema) => {
+                    return self.read_value(schema).map(Some)
+                },
+                Task::Event(event) => return Ok(Some(event)),
+                Task::Elements {
+                    element,
+                    remaining,
+                } => {
+                    if let Some(remaining) = remaining.checked_sub(1) {
+                        self.tasks.push(Task::Elements {
+                            element,
+                            remaining,
+                        });
+                        self.tasks.push(Task::Value(element));
+                    }
+                },
+                Task::Entries {
+                    key,
+                    value,
+                    remaining,
+                } => {
+                    if let Some(remaining) = remaining.checked_sub(1) {
+                        self.tasks.push(Task::Entries {
+                            key,
+                            value,
+                            remaining,
+                        });
+                        self.tasks.push(Task::Value(value));
+                        self.tasks.push(Task::Value(key));
+                    }
+                },
+                Task::Leave => {
+                    self.named.pop();
+                },
+            }
+        }
+    }
+
+    fn push_fields<I>(
+        &mut self,
+        end: Event<'de>,
+        fields: I,
+    ) where
+        I: DoubleEndedIterator<Item = (Option<&'static str>, &'s Schema)>,
+    {
+        self.tasks.push(Task::Event(end));
+        for (name, schema) in fields.rev() {
+            self.tasks.push(Task::Value(schema));
+            if let Some(name) = name {
+                self.tasks.push(Task::Event(Event::Field(name)));
+            }
+        }
+    }
+
+    fn read_variant(
+        &mut self,
+        variants: &'s [Variant],
+    ) -> Result<Event<'de>> {
+        let index = self.de.parse_u32()?;
+        let variant = variants.get(index as usize).ok_or_else(|| {
+            <Error as serde::de::Error>::invalid_value(
+                serde::de::Unexpected::Unsigned(index.into()),
+                &"variant index",
+            )
+        })?;
+        match &variant.kind {
+            VariantKind::Unit => {
+                self.tasks.push(Task::Event(Event::Unit));
+            },
+            VariantKind::Newtype(inner) => {
+                self.tasks.push(Task::Value(inner));
+            },
+            VariantKind::Tuple(fields) => {
+                self.push_fields(
+                    Event::TupleEnd,
+                    fields.iter().map(|field| (None, field)),
+                );
+                self.tasks.push(Task::Event(Event::TupleStart(fields.len())));
+            },
+            VariantKind::Struct(fields) => {
+                self.push_fields(
+                    Event::StructEnd,
+                    fields
+                        .iter()
+                        .map(|field| (Some(field.name), &field.schema)),
+                );
+                self.tasks.push(Task::Event(Event::StructStart(variant.name)));
+            },
+        }
+        Ok(Event::Variant {
+            index,
+            name: variant.name,
+        })
+    }
+
+    fn read_value(
+        &mut self,
+        schema: &'s Schema,
+    ) -> Result<Event<'de>> {
+        if schema.name().is_some() {
+            self.named.push(schema);
+            self.tasks.push(Task::Leave);
+        }
+        let de = &mut self.de;
+        Ok(match schema {
+            Schema::Bool => Event::Bool(de.parse_bool()?),
+            Schema::I8 => Event::I8(de.parse_i8()?),
+            Schema::I16 => Event::I64(de.parse_i16()?.into()),
+            Schema::I32 => Event::I64(de.parse_i32()?.into()),
+            Schema::I64 => Event::I64(de.parse_i64(None)?),
+            Schema::I128 => Event::I128(de.parse_i128()?),
+            Schema::U8 => Event::U8(de.parse_u8()?),
+            Schema::U16 => Event::U64(de.parse_u16()?.into()),
+            Schema::U32 => Event::U64(de.parse_u32()?.into()),
+            Schema::U64 => Event::U64(de.parse_u64(None)?),
+            Schema::U128 => Event::U128(de.parse_u128()?),
+            Schema::F32 => Event::F32(de.parse_f32()?),
+            Schema::F64 => Event::F64(de.parse_f64()?),
+            Schema::Char => Event::Char(de.parse_char()?),
+            Schema::Str => Event::Str(de.parse_bytes()?),
+            Schema::Bytes => Event::Bytes(de.parse_bytes()?),
+            Schema::Option(inner) => {
+                if de.parse_option()?.is_some() {
+                    self.tasks.push(Task::Value(inner));
+                    Event::Some
+                } else {
+                    Event::None
+                }
+            },
+            Schema::Unit | Schema::UnitStruct(_) => Event::Unit,
+            Schema::NewtypeStruct {
+                inner,
+                ..
+            } => return self.read_value(inner),
+            Schema::Seq(element) => {
+                let len = de.parse_usize()?;
+                self.tasks.push(Task::Event(Event::SeqEnd));
+                self.tasks.push(Task::Elements {
+                    element,
+                    remaining: len,
+                });
+                Event::SeqStart(len)
+            },
+            Schema::Tuple(fields)
+            | Schema::Array(fields)
+            | Schema::TupleStruct {
+                fields,
+                ..
+            } => {
+                self.push_fields(
+                    Event::TupleEnd,
+                    fields.iter().map(|field| (None, field)),
+                );
+                Event::TupleStart(fields.len())
+            },
+            Schema::Map {
+                key,
+                value,
+            } => {
+                let len = de.parse_usize()?;
+                self.tasks.push(Task::Event(Event::MapEnd));
+                self.tasks.push(Task::Entries {
+                    key,
+                    value,
+                    remaining: len,
+                });
+                Event::MapStart(len)
+            },
+            Schema::Struct {
+                name,
+                fields,
+            } => {
+                self.push_fields(
+                    Event::StructEnd,
+                    fields
+                        .iter()
+                        .map(|field| (Some(field.name), &field.schema)),
+                );
+                Event::StructStart(name)
+            },
+            Schema::Enum {
+                variants,
+                ..
+            } => self.read_variant(variants)?,
+            Schema::Recursive(name) => {
+                let target = self
+                    .named
+                    .iter()
+                    .rev()
+                    .copied()
+                    .find(|schema| schema.name() == Some(name))
+                    .ok_or(Error::UntraceableType(name))?;
+                return self.read_value(target);
+            },
+        })
+    }
+}
+
+impl<'de> Iterator for EventReader<'de, '_> {
+    type Item = Result<Event<'de>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_event().transpose()
+    }
+}
+
+// This is a compound value which an event writer is in the middle of
+// writing.
+enum Frame {
+    Seq {
+        len: usize,
+        count: usize,
+    },
+    Tuple {
+        len: usize,
+        count: usize,
+    },
+    Map {
+        len: usize,
+        count: usize,
+    },
+    Struct,
+
+    // This is a present optional value or an enum value, either of which
+    // is complete once the one value it holds is complete.
+    Wrapper,
+}
+
+/// This builds the encoding of values from a series of [`Event`]s, as
+/// reported by an [`EventReader`], without needing any Rust type for the
+/// values.  Together with an event reader, this allows encodings to be
+/// constructed or rewritten programmatically, such as to leave out some
+/// fields or redact their contents.
+///
+/// The structure of the events is checked, so that the result is a valid
+/// encoding of some type: containers must be ended in the order they were
+/// started, and sequences, tuples, and maps must be given as many elements
+/// or entries as they were started with.  Since the encoding does not
+/// include the names of structs, fields, or variants, those are ignored.
+///
+/// [`Event`]: enum.Event.html
+/// [`EventReader`]: struct.EventReader.html
+#[derive(Default)]
+pub struct EventWriter {
+    output: Vec<u8>,
+    frames: Vec<Frame>,
+}
+
+impl EventWriter {
+    // Record the completion of a value in whatever contains it.
+    fn complete(&mut self) {
+        while let Some(Frame::Wrapper) = self.frames.last() {
+            self.frames.pop();
+        }
+        if let Some(
+            Frame::Seq {
+                count,
+                ..
+            }
+            | Frame::Tuple {
+                count,
+                ..
+            }
+            | Frame::Map {
+                count,
+                ..
+            },
+        ) = self.frames.last_mut()
+        {
+            *count += 1;
+        }
+    }
+
+    // Finish the container on top of the stack, if it is the one the given
+    // end event is for, and has all its parts.
+    fn end(
+        &mut self,
+        event: Event,
+    ) -> Result<()> {
+        let complete = match (self.frames.last(), event) {
+            (
+                Some(Frame::Seq {
+                    len,
+                    count,
+                }),
+                Event::SeqEnd,
+            )
+            | (
+                Some(Frame::Tuple {
+                    len,
+                    count,
+                }),
+                Event::TupleEnd,
+            ) => count == len,
+            (
+                Some(Frame::Map {
+                    len,
+                    count,
+                }),
+                Event::MapEnd,
+            ) => *count == len * 2,
+            (Some(Frame::Struct), Event::StructEnd) => true,
+            _ => false,
+        };
+        if !complete {
+            return Err(Error::UnexpectedEvent);
+        }
+        self.frames.pop();
+        self.complete();
+        Ok(())
+    }
+
+    /// This function is used to return the encoding built from the events
+    /// written.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`Error::UnexpectedEvent`] if a value was
+    /// started but not completed.
+    ///
+    /// [`Error::UnexpectedEvent`]: enum.Error.html#variant.UnexpectedEvent
+    pub fn finish(self) -> Result<Vec<u8>> {
+        if self.frames.is_empty() {
+            Ok(self.output)
+        } else {
+            Err(Error::UnexpectedEvent)
+        }
+    }
+
+    /// Return a new event writer which has written nothing.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Record the start of a value, checking that there is room for it.
+    fn start(&mut self) -> Result<()> {
+        let full = match self.frames.last() {
+            Some(
+                Frame::Seq {
+                    len,
+                    count,
+                }
+                | Frame::Tuple {
+                    len,
+                    count,
+                },
+            ) => count >= len,
+            Some(Frame::Map {
+                len,
+                count,
+            }) => *count >= len * 2,
+            _ => false,
+        };
+        if full {
+            Err(Error::UnexpectedEvent)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// This function is used to add the given event to the encoding being
+    /// built.  Any number of values may be written one after another, to
+    /// form a stream of values.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`Error::UnexpectedEvent`] if the event does
+    /// not fit the structure of what has been written so far.
+    ///
+    /// [`Error::UnexpectedEvent`]: enum.Error.html#variant.UnexpectedEvent
+    pub fn write(
+        &mut self,
+        event: Event,
+    ) -> Result<()> {
+        match event {
+            Event::SeqEnd
+            | Event::TupleEnd
+            | Event::MapEnd
+            | Event::StructEnd => {
+                return self.end(event);
+            },
+            Event::Field(_) => {
+                return match self.frames.last() {
+                    Some(Frame::Struct) => Ok(()),
+                    _ => Err(Error::UnexpectedEvent),
+                };
+            },
+            _ => self.start()?,
+        }
+        let mut serializer = Serializer::new(&mut self.output);
+        let mut frame = None;
+        match event {
+            Event::Bool(value) => serializer.serialize_bool(value)?,
+            Event::I8(value) => serializer.serialize_i8(value)?,
+            Event::I64(value) => serializer.serialize_i64(value)?,
+            Event::I128(value) => serializer.serialize_i128(value)?,
+            Event::U8(value) => serializer.serialize_u8(value)?,
+            Event::U64(value) => serializer.serialize_u64(value)?,
+            Event::U128(value) => serializer.serialize_u128(value)?,
+            Event::F32(value) => serializer.serialize_f32(value)?,
+            Event::F64(value) => serializer.serialize_f64(value)?,
+            Event::Char(value) => serializer.serialize_char(value)?,
+            Event::Str(value) | Event::Bytes(value) => {
+                Bytes(value).serialize(&mut serializer)?;
+            },
+            Event::None => serializer.serialize_u8(0)?,
+            Event::Some => {
+                serializer.serialize_u8(1)?;
+                frame = Some(Frame::Wrapper);
+            },
+            Event::Unit => {},
+            Event::SeqStart(len) => {
+                serializer.serialize_u64(len as u64)?;
+                frame = Some(Frame::Seq {
+                    len,
+                    count: 0,
+                });
+            },
+            Event::TupleStart(len) => {
+                frame = Some(Frame::Tuple {
+                    len,
+                    count: 0,
+                });
+            },
+            Event::MapStart(len) => {
+                serializer.serialize_u64(len as u64)?;
+                frame = Some(Frame::Map {
+                    len,
+                    count: 0,
+                });
+            },
+            Event::StructStart(_) => frame = Some(Frame::Struct),
+            Event::Variant {
+                index,
+                ..
+            } => {
+                serializer.serialize_u32(index)?;
+                frame = Some(Frame::Wrapper);
+            },
+            Event::SeqEnd
+            | Event::TupleEnd
+            | Event::MapEnd
+            | Event::StructEnd
+            | Event::Field(_) => unreachable!(),
+        }
+        if let Some(frame) = frame {
+            self.frames.push(frame);
+        } else {
+            self.complete();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_bytes;
+    use std::collections::BTreeMap;
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    enum Shape {
+        Point,
+        Circle(f32),
+        Line(i8, i8),
+        Label {
+            text: String,
+        },
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Node {
+        id: u16,
+        shapes: Vec<Shape>,
+        next: Option<Box<Node>>,
+    }
+
+    fn events(
+        schema: &Schema,
+        bytes: &[u8],
+    ) -> Vec<String> {
+        EventReader::new(schema, bytes)
+            .map(|event| format!("{:?}", event.unwrap()))
+            .collect()
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn events_of_nested_values() {
+        let node = Node {
+            id: 300,
+            shapes: vec![
+                Shape::Point,
+                Shape::Circle(0.5),
+                Shape::Line(-1, 2),
+                Shape::Label {
+                    text: String::from("hi"),
+                },
+            ],
+            next: Some(Box::new(Node {
+                id: 1,
+                shapes: vec![],
+                next: None,
+            })),
+        };
+        let schema = Schema::of::<Node>().unwrap();
+        assert_eq!(
+            vec![
+                "StructStart(\"Node\")",
+                "Field(\"id\")",
+                "U64(300)",
+                "Field(\"shapes\")",
+                "SeqStart(4)",
+                "Variant { index: 0, name: \"Point\" }",
+                "Unit",
+                "Variant { index: 1, name: \"Circle\" }",
+                "F32(0.5)",
+                "Variant { index: 2, name: \"Line\" }",
+                "TupleStart(2)",
+                "I8(-1)",
+                "I8(2)",
+                "TupleEnd",
+                "Variant { index: 3, name: \"Label\" }",
+                "StructStart(\"Label\")",
+                "Field(\"text\")",
+                "Str([104, 105])",
+                "StructEnd",
+                "SeqEnd",
+                "Field(\"next\")",
+                "Some",
+                "StructStart(\"Node\")",
+                "Field(\"id\")",
+                "U64(1)",
+                "Field(\"shapes\")",
+                "SeqStart(0)",
+                "SeqEnd",
+                "Field(\"next\")",
+                "None",
+                "StructEnd",
+                "StructEnd",
+            ],
+            events(&schema, &to_bytes(&node).unwrap())
+        );
+    }
+
+    #[test]
+    fn events_of_stream() {
+        let mut map = BTreeMap::new();
+        map.insert('a', -5_i32);
+        let mut bytes = to_bytes(&map).unwrap();
+        bytes.extend(to_bytes(&BTreeMap::<char, i32>::new()).unwrap());
+        let schema = Schema::of::<BTreeMap<char, i32>>().unwrap();
+        assert_eq!(
+            vec![
+                "MapStart(1)",
+                "Char('a')",
+                "I64(-5)",
+                "MapEnd",
+                "MapStart(0)",
+                "MapEnd"
+            ],
+            events(&schema, &bytes)
+        );
+    }
+
+    #[test]
+    fn events_of_wide_integers() {
+        let value = (u128::MAX, -1_i128);
+        let bytes = to_bytes(&value).unwrap();
+        let schema = Schema::of::<(u128, i128)>().unwrap();
+        assert_eq!(
+            vec![
+                String::from("TupleStart(2)"),
+                format!("U128({})", u128::MAX),
+                String::from("I128(-1)"),
+                String::from("TupleEnd"),
+            ],
+            events(&schema, &bytes)
+        );
+        let mut writer = EventWriter::new();
+        for event in EventReader::new(&schema, &bytes) {
+            assert!(writer.write(event.unwrap()).is_ok());
+        }
+        assert_eq!(bytes, writer.finish().unwrap());
+    }
+
+    #[test]
+    fn rewrite_events() {
+        let node = Node {
+            id: 5,
+            shapes: vec![
+                Shape::Label {
+                    text: String::from("secret"),
+                },
+                Shape::Line(3, 4),
+            ],
+            next: None,
+        };
+        let schema = Schema::of::<Node>().unwrap();
+        let bytes = to_bytes(&node).unwrap();
+        let mut writer = EventWriter::new();
+        for event in EventReader::new(&schema, &bytes) {
+            let event = match event.unwrap() {
+                Event::Str(_) => Event::Str(b"***"),
+                event => event,
+            };
+            assert!(writer.write(event).is_ok());
+        }
+        let rewritten = writer.finish();
+        assert!(rewritten.is_ok());
+        let rewritten = rewritten.unwrap();
+        let expected = Node {
+            shapes: vec![
+                Shape::Label {
+                    text: String::from("***"),
+                },
+                Shape::Line(3, 4),
+            ],
+            ..node
+        };
+        assert_eq!(to_bytes(&expected).unwrap(), rewritten);
+    }
+
+    #[test]
+    fn write_events_without_types() {
+        let mut writer = EventWriter::new();
+        for event in &[
+            Event::MapStart(2),
+            Event::Str(b"a"),
+            Event::Some,
+            Event::I64(-1),
+            Event::Str(b"b"),
+            Event::None,
+            Event::MapEnd,
+            Event::U64(300),
+        ] {
+            assert!(writer.write(*event).is_ok());
+        }
+        let bytes = writer.finish().unwrap();
+        let mut expected = BTreeMap::new();
+        expected.insert("a", Some(-1_i16));
+        expected.insert("b", None);
+        let mut expected_bytes = to_bytes(&expected).unwrap();
+        expected_bytes.extend(to_bytes(&300_u32).unwrap());
+        assert_eq!(expected_bytes, bytes);
+    }
+
+    #[test]
+    fn write_events_checks_structure() {
+        let mut writer = EventWriter::new();
+        assert!(writer.write(Event::SeqStart(1)).is_ok());
+        assert!(writer.write(Event::U8(1)).is_ok());
+        assert!(matches!(
+            writer.write(Event::U8(2)),
+            Err(Error::UnexpectedEvent)
+        ));
+        assert!(matches!(
+            writer.write(Event::TupleEnd),
+            Err(Error::UnexpectedEvent)
+        ));
+        assert!(matches!(
+            writer.write(Event::Field("x")),
+            Err(Error::UnexpectedEvent)
+        ));
+        assert!(writer.write(Event::SeqEnd).is_ok());
+        assert!(matches!(
+            writer.write(Event::SeqEnd),
+            Err(Error::UnexpectedEvent)
+        ));
+        assert!(writer.write(Event::TupleStart(2)).is_ok());
+        assert!(writer.write(Event::Bool(true)).is_ok());
+        assert!(matches!(
+            writer.write(Event::TupleEnd),
+            Err(Error::UnexpectedEvent)
+        ));
+        assert!(matches!(writer.finish(), Err(Error::UnexpectedEvent)));
+    }
+
+    #[test]
+    fn events_stop_at_error() {
+        let schema = Schema::of::<(u8, String)>().unwrap();
+        let mut reader = EventReader::new(&schema, &[7, 3, b'a']);
+        assert!(matches!(reader.next_event(), Ok(Some(Event::TupleStart(2)))));
+        assert!(matches!(reader.next_event(), Ok(Some(Event::U8(7)))));
+        assert!(matches!(reader.next_event(), Err(Error::ValueTruncated)));
+        assert!(matches!(reader.next_event(), Ok(None)));
+        assert_eq!(2, reader.offset());
+    }
+}
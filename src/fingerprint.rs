@@ -0,0 +1,198 @@
+use super::{
+    from_bytes,
+    Config,
+    Error,
+    Features,
+    Result,
+    Schema,
+};
+use alloc::vec::Vec;
+
+/// This is the number of bytes of fingerprint placed in front of values
+/// encoded by [`to_bytes_with_fingerprint`].
+///
+/// [`to_bytes_with_fingerprint`]: fn.to_bytes_with_fingerprint.html
+pub const FINGERPRINT_SIZE: usize = 4;
+
+/// Return the short fingerprint of the layout of type `T`, which is what
+/// [`to_bytes_with_fingerprint`] places in front of the encoding of values
+/// of the type.
+///
+/// [`to_bytes_with_fingerprint`]: fn.to_bytes_with_fingerprint.html
+///
+/// # Errors
+///
+/// Any error from determining the schema of the type with [`Schema::of`]
+/// is returned.
+///
+/// [`Schema::of`]: enum.Schema.html#method.of
+pub fn type_fingerprint<'de, T>() -> Result<u32>
+where
+    T: serde::Deserialize<'de>,
+{
+    let fingerprint = Schema::of::<T>()?.fingerprint();
+    #[allow(clippy::cast_possible_truncation)]
+    Ok((fingerprint ^ (fingerprint >> 32)) as u32)
+}
+
+/// This function is used to encode a value preceded by a fingerprint of
+/// the layout of its type.  Decoding the result with
+/// [`from_bytes_with_fingerprint`] as a type with a different layout fails
+/// immediately with [`Error::FingerprintMismatch`], rather than silently
+/// misinterpreting the bytes.
+///
+/// [`from_bytes_with_fingerprint`]: fn.from_bytes_with_fingerprint.html
+/// [`Error::FingerprintMismatch`]: enum.Error.html#variant.FingerprintMismatch
+///
+/// # Errors
+///
+/// Any error from [`type_fingerprint`] or [`to_bytes`] is returned.
+///
+/// [`type_fingerprint`]: fn.type_fingerprint.html
+/// [`to_bytes`]: fn.to_bytes.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_bytes_with_fingerprint,
+///     to_bytes_with_fingerprint,
+///     Error,
+/// };
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Celsius(i16);
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Count(u64);
+///
+/// let encoding = to_bytes_with_fingerprint(&Celsius(21)).unwrap();
+/// assert!(matches!(
+///     from_bytes_with_fingerprint::<Count>(&encoding),
+///     Err(Error::FingerprintMismatch { .. })
+/// ));
+/// ```
+pub fn to_bytes_with_fingerprint<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: serde::Serialize + serde::Deserialize<'static>,
+{
+    Config::new()
+        .with_features(Features::FINGERPRINT)
+        .to_bytes_with_fingerprint(value)
+}
+
+/// This function is used to decode a value encoded by
+/// [`to_bytes_with_fingerprint`], after checking that the fingerprint in
+/// front of it matches the layout of type `T`.
+///
+/// [`to_bytes_with_fingerprint`]: fn.to_bytes_with_fingerprint.html
+///
+/// # Errors
+///
+/// This function returns [`Error::FingerprintMismatch`] if the value was
+/// encoded from a type with a different layout, [`Error::ValueTruncated`]
+/// if the input is too short to hold a fingerprint, and otherwise any of
+/// the errors returned by [`type_fingerprint`] or [`from_bytes`].
+///
+/// [`Error::FingerprintMismatch`]: enum.Error.html#variant.FingerprintMismatch
+/// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+/// [`type_fingerprint`]: fn.type_fingerprint.html
+/// [`from_bytes`]: fn.from_bytes.html
+pub fn from_bytes_with_fingerprint<'de, T>(bytes: &'de [u8]) -> Result<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    strip_fingerprint::<T>(bytes).and_then(from_bytes)
+}
+
+// This checks the fingerprint in front of an encoded value against the layout
+// of type `T`, returning the encoding which follows it.
+pub(crate) fn strip_fingerprint<'de, T>(bytes: &[u8]) -> Result<&[u8]>
+where
+    T: serde::Deserialize<'de>,
+{
+    if bytes.len() < FINGERPRINT_SIZE {
+        return Err(Error::ValueTruncated);
+    }
+    let (fingerprint, bytes) = bytes.split_at(FINGERPRINT_SIZE);
+    let mut found = [0; FINGERPRINT_SIZE];
+    found.copy_from_slice(fingerprint);
+    let found = u32::from_be_bytes(found);
+    let expected = type_fingerprint::<T>()?;
+    if found != expected {
+        return Err(Error::FingerprintMismatch {
+            expected,
+            found,
+        });
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "float")]
+    use crate::to_bytes;
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Reading {
+        sensor: String,
+        value: f32,
+    }
+
+    #[cfg(feature = "float")]
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Event {
+        sensor: String,
+        value: u32,
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn round_trip_with_fingerprint() {
+        let reading = Reading {
+            sensor: String::from("porch"),
+            value: 12.5,
+        };
+        let encoding = to_bytes_with_fingerprint(&reading);
+        assert!(encoding.is_ok());
+        let encoding = encoding.unwrap();
+        assert_eq!(
+            &type_fingerprint::<Reading>().unwrap().to_be_bytes()[..],
+            &encoding[..FINGERPRINT_SIZE]
+        );
+        assert_eq!(to_bytes(&reading).unwrap(), &encoding[FINGERPRINT_SIZE..]);
+        let decoded: Result<Reading> = from_bytes_with_fingerprint(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!(reading, decoded.unwrap());
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn wrong_type_is_detected() {
+        let encoding = to_bytes_with_fingerprint(&Reading {
+            sensor: String::from("attic"),
+            value: 0.0,
+        })
+        .unwrap();
+        let expected = type_fingerprint::<Event>().unwrap();
+        let found = type_fingerprint::<Reading>().unwrap();
+        assert_ne!(expected, found);
+        assert!(matches!(
+            from_bytes_with_fingerprint::<Event>(&encoding),
+            Err(Error::FingerprintMismatch {
+                expected: e,
+                found: f,
+            }) if e == expected && f == found
+        ));
+    }
+
+    #[test]
+    fn missing_fingerprint() {
+        assert!(matches!(
+            from_bytes_with_fingerprint::<Reading>(&[0x01, 0x02]),
+            Err(Error::ValueTruncated)
+        ));
+    }
+}
@@ -0,0 +1,173 @@
+use super::{
+    from_bytes,
+    to_bytes,
+    Error,
+    MaxSerializedSize,
+    Result,
+};
+
+/// Return the offset, in bytes, of the record with the given index in a
+/// sequence of fixed-size records of type `T`, or `None` if the offset is
+/// too large to be represented.
+#[must_use]
+pub fn fixed_record_offset<T>(index: usize) -> Option<usize>
+where
+    T: MaxSerializedSize,
+{
+    index.checked_mul(T::MAX_SERIALIZED_SIZE)
+}
+
+/// Return the number of complete fixed-size records of type `T` contained
+/// in a sequence of bytes of the given length.
+#[must_use]
+pub fn fixed_record_count<T>(len: usize) -> usize
+where
+    T: MaxSerializedSize,
+{
+    len.checked_div(T::MAX_SERIALIZED_SIZE).unwrap_or(0)
+}
+
+/// This function is used to encode a value into a record whose size is
+/// always [`T::MAX_SERIALIZED_SIZE`] bytes, padding the encoding with zeros
+/// as needed.  A file consisting of such records can be indexed in constant
+/// time, using [`fixed_record_offset`] to locate record N.
+///
+/// [`T::MAX_SERIALIZED_SIZE`]: trait.MaxSerializedSize.html
+/// [`fixed_record_offset`]: fn.fixed_record_offset.html
+///
+/// # Errors
+///
+/// In addition to any errors returned by [`to_bytes`], this function
+/// returns [`Error::RecordSizeExceeded`] if the value encodes to more bytes
+/// than its type's maximum serialized size, which indicates a bad
+/// implementation of [`MaxSerializedSize`].
+///
+/// [`to_bytes`]: fn.to_bytes.html
+/// [`Error::RecordSizeExceeded`]: enum.Error.html#variant.RecordSizeExceeded
+/// [`MaxSerializedSize`]: trait.MaxSerializedSize.html
+pub fn to_fixed_record<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: serde::Serialize + MaxSerializedSize,
+{
+    let mut record = to_bytes(value)?;
+    if record.len() > T::MAX_SERIALIZED_SIZE {
+        return Err(Error::RecordSizeExceeded);
+    }
+    record.resize(T::MAX_SERIALIZED_SIZE, 0);
+    Ok(record)
+}
+
+/// This function is used to decode the record with the given index from a
+/// sequence of fixed-size records produced by [`to_fixed_record`].
+///
+/// [`to_fixed_record`]: fn.to_fixed_record.html
+///
+/// # Errors
+///
+/// This function returns [`Error::ValueTruncated`] if the sequence does not
+/// contain the requested record, and otherwise any of the errors returned by
+/// [`from_bytes`].
+///
+/// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+/// [`from_bytes`]: fn.from_bytes.html
+pub fn from_fixed_records<'de, T>(
+    records: &'de [u8],
+    index: usize,
+) -> Result<T>
+where
+    T: serde::Deserialize<'de> + MaxSerializedSize,
+{
+    let start = fixed_record_offset::<T>(index).ok_or(Error::ValueTruncated)?;
+    let end = start
+        .checked_add(T::MAX_SERIALIZED_SIZE)
+        .ok_or(Error::ValueTruncated)?;
+    if records.len() < end {
+        return Err(Error::ValueTruncated);
+    }
+    from_bytes(&records[start..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Sample {
+        id: u32,
+        level: i16,
+    }
+
+    impl MaxSerializedSize for Sample {
+        const MAX_SERIALIZED_SIZE: usize =
+            u32::MAX_SERIALIZED_SIZE + i16::MAX_SERIALIZED_SIZE;
+    }
+
+    #[test]
+    fn records_are_padded_to_maximum_size() {
+        let record = to_fixed_record(&Sample {
+            id: 1,
+            level: -1,
+        });
+        assert!(record.is_ok());
+        let record = record.unwrap();
+        assert_eq!(&[0x01, 0x41, 0, 0, 0, 0, 0, 0][..], record);
+    }
+
+    #[test]
+    fn records_are_indexed_in_constant_time() {
+        let samples = (0..5)
+            .map(|i| Sample {
+                id: 1000 * i,
+                level: 9001,
+            })
+            .collect::<Vec<_>>();
+        let records = samples
+            .iter()
+            .map(|sample| to_fixed_record(sample).unwrap())
+            .collect::<Vec<_>>()
+            .concat();
+        assert_eq!(5, fixed_record_count::<Sample>(records.len()));
+        assert_eq!(Some(24), fixed_record_offset::<Sample>(3));
+        let sample: Result<Sample> = from_fixed_records(&records, 3);
+        assert!(sample.is_ok());
+        assert_eq!(samples[3], sample.unwrap());
+        let sample: Result<Sample> = from_fixed_records(&records, 5);
+        assert!(matches!(sample, Err(Error::ValueTruncated)));
+    }
+
+    #[test]
+    fn records_beyond_addressable_memory() {
+        assert_eq!(None, fixed_record_offset::<Sample>(usize::MAX / 2));
+        assert!(matches!(
+            from_fixed_records::<u32>(&[0; 10], usize::MAX / 2),
+            Err(Error::ValueTruncated)
+        ));
+        assert!(matches!(
+            from_fixed_records::<u8>(&[0; 10], usize::MAX),
+            Err(Error::ValueTruncated)
+        ));
+    }
+
+    #[test]
+    fn oversized_record() {
+        struct Liar(u64);
+        impl serde::Serialize for Liar {
+            fn serialize<S>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                serializer.serialize_u64(self.0)
+            }
+        }
+        impl MaxSerializedSize for Liar {
+            const MAX_SERIALIZED_SIZE: usize = 1;
+        }
+        assert!(matches!(
+            to_fixed_record(&Liar(1000)),
+            Err(Error::RecordSizeExceeded)
+        ));
+    }
+}
@@ -0,0 +1,140 @@
+use super::{
+    from_bytes,
+    json,
+    to_bytes,
+    Result,
+};
+
+/// This identifies one of the encodings which [`encode`] and [`decode`] can
+/// use, so that the choice of encoding can be made at run time, such as
+/// using human-readable JSON during development and the binary format of
+/// this crate in production, with the same code path for both.
+///
+/// [`encode`]: fn.encode.html
+/// [`decode`]: fn.decode.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// This is the compact binary format of this crate, as used by
+    /// [`to_bytes`] and [`from_bytes`].
+    ///
+    /// [`to_bytes`]: fn.to_bytes.html
+    /// [`from_bytes`]: fn.from_bytes.html
+    Binary,
+
+    /// This is compact JSON text (UTF-8), following the same conventions
+    /// as `serde_json`: structs and maps are objects, enum variants are
+    /// either strings or single-entry objects keyed by variant name, and
+    /// byte strings are arrays of numbers.  Numbers follow the grammar of
+    /// RFC 8259, and 128-bit integers are written out in full, as
+    /// `serde_json` does.
+    Json,
+}
+
+/// This function is used to decode a value from bytes in the given format.
+///
+/// # Errors
+///
+/// Returns any error from [`from_bytes`] for [`Format::Binary`], and
+/// additionally [`Error::JsonSyntax`] for [`Format::Json`] if the input is
+/// not valid JSON.
+///
+/// [`from_bytes`]: fn.from_bytes.html
+/// [`Format::Binary`]: enum.Format.html#variant.Binary
+/// [`Format::Json`]: enum.Format.html#variant.Json
+/// [`Error::JsonSyntax`]: enum.Error.html#variant.JsonSyntax
+pub fn decode<'de, T>(
+    format: Format,
+    bytes: &'de [u8],
+) -> Result<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    match format {
+        Format::Binary => from_bytes(bytes),
+        Format::Json => json::from_slice(bytes),
+    }
+}
+
+/// This function is used to encode a value into bytes in the given format.
+///
+/// # Errors
+///
+/// Returns any error from [`to_bytes`] for [`Format::Binary`].  For
+/// [`Format::Json`], [`Error::Message`] is returned if a map has keys which
+/// are not strings, numbers, booleans, or unit variants.
+///
+/// [`to_bytes`]: fn.to_bytes.html
+/// [`Format::Binary`]: enum.Format.html#variant.Binary
+/// [`Format::Json`]: enum.Format.html#variant.Json
+/// [`Error::Message`]: enum.Error.html#variant.Message
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     decode,
+///     encode,
+///     Format,
+/// };
+///
+/// #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+/// struct Status {
+///     code: u16,
+///     text: String,
+/// }
+///
+/// let status = Status {
+///     code: 200,
+///     text: String::from("OK"),
+/// };
+/// let format = if cfg!(debug_assertions) {
+///     Format::Json
+/// } else {
+///     Format::Binary
+/// };
+/// let encoded = encode(format, &status).unwrap();
+/// if format == Format::Json {
+///     assert_eq!(br#"{"code":200,"text":"OK"}"#, &encoded[..]);
+/// }
+/// assert_eq!(status, decode(format, &encoded).unwrap());
+/// ```
+pub fn encode<T>(
+    format: Format,
+    value: &T,
+) -> Result<Vec<u8>>
+where
+    T: serde::Serialize,
+{
+    match format {
+        Format::Binary => to_bytes(value),
+        Format::Json => json::to_vec(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_formats_round_trip() {
+        let value = (String::from("abc"), vec![Some(1_i32), None], 'x');
+        for format in &[Format::Binary, Format::Json] {
+            let encoded = encode(*format, &value);
+            assert!(encoded.is_ok());
+            let encoded = encoded.unwrap();
+            let decoded: Result<(String, Vec<Option<i32>>, char)> =
+                decode(*format, &encoded);
+            assert!(decoded.is_ok());
+            assert_eq!(value, decoded.unwrap());
+        }
+        assert_eq!(
+            to_bytes(&value).unwrap(),
+            encode(Format::Binary, &value).unwrap()
+        );
+        assert_eq!(
+            br#"["abc",[1,null],"x"]"#.to_vec(),
+            encode(Format::Json, &value).unwrap()
+        );
+    }
+}
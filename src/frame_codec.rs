@@ -0,0 +1,355 @@
+use super::{
+    from_bytes,
+    Error,
+    IntCodec,
+    Result,
+    Serializer,
+    Varint,
+};
+use bytes::{
+    Buf,
+    BytesMut,
+};
+use std::marker::PhantomData;
+
+/// This is a codec for use with [`tokio_util::codec::Framed`] and its
+/// relatives, with the `tokio-util` feature, or with
+/// [`asynchronous_codec::Framed`], which works with the `futures::io`
+/// traits implemented by async-std, smol, and other runtimes, with the
+/// `asynchronous-codec` feature.  It sends values of type `T` as frames,
+/// each holding the encoding of one value preceded by its length as a
+/// varint, the same way byte strings are encoded.  Frames longer than the
+/// maximum length, which is 8 MiB unless changed with
+/// [`with_max_frame_len`], are refused, so that a corrupted or hostile
+/// length cannot make the decoder buffer without limit.
+///
+/// [`tokio_util::codec::Framed`]: https://docs.rs/tokio-util/0.7/tokio_util/codec/struct.Framed.html
+/// [`asynchronous_codec::Framed`]: https://docs.rs/asynchronous-codec/0.7/asynchronous_codec/struct.Framed.html
+/// [`with_max_frame_len`]: #method.with_max_frame_len
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// # #[cfg(feature = "tokio-util")] {
+/// use bytes::BytesMut;
+/// use serialization::SerializationCodec;
+/// use tokio_util::codec::{
+///     Decoder,
+///     Encoder,
+/// };
+///
+/// let mut codec = SerializationCodec::<(String, u8)>::new();
+/// let mut buffer = BytesMut::new();
+/// codec.encode((String::from("hello"), 7), &mut buffer).unwrap();
+/// assert_eq!(&[7, 5, b'h', b'e', b'l', b'l', b'o', 7][..], &buffer[..]);
+/// let decoded = codec.decode(&mut buffer).unwrap();
+/// assert_eq!(Some((String::from("hello"), 7)), decoded);
+/// assert!(buffer.is_empty());
+/// # }
+/// ```
+pub struct SerializationCodec<T> {
+    max_frame_len: usize,
+    value: PhantomData<fn(T) -> T>,
+}
+
+impl<T> SerializationCodec<T> {
+    #[allow(clippy::cast_possible_truncation)]
+    fn decode_frame(
+        &self,
+        src: &mut BytesMut,
+    ) -> Result<Option<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let (len, header_len) = match Varint.decode_unsigned(src, u64::MAX) {
+            Ok(header) => header,
+            Err(Error::ValueTruncated) => return Ok(None),
+            Err(error) => return Err(error),
+        };
+        if len > self.max_frame_len as u64 {
+            return Err(Error::FrameTooLong(len));
+        }
+        let frame_end = header_len + len as usize;
+        if src.len() < frame_end {
+            src.reserve(frame_end - src.len());
+            return Ok(None);
+        }
+        let value = from_bytes(&src[header_len..frame_end]);
+        // The frame is taken out of the buffer even if it fails to decode,
+        // so that the frames after it can still be decoded.
+        src.advance(frame_end);
+        value.map(Some)
+    }
+
+    fn encode_frame(
+        &self,
+        item: &T,
+        dst: &mut BytesMut,
+    ) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        let mut frame = Vec::new();
+        item.serialize(&mut Serializer::new(&mut frame))?;
+        if frame.len() > self.max_frame_len {
+            return Err(Error::FrameTooLong(frame.len() as u64));
+        }
+        let mut header = Vec::new();
+        Varint.encode_unsigned(frame.len() as u64, &mut header)?;
+        dst.reserve(header.len() + frame.len());
+        dst.extend_from_slice(&header);
+        dst.extend_from_slice(&frame);
+        Ok(())
+    }
+
+    /// Return the longest frame, not counting its length, which the codec
+    /// encodes or decodes.
+    #[must_use]
+    pub fn max_frame_len(&self) -> usize {
+        self.max_frame_len
+    }
+
+    /// Return a new codec for values of type `T`.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            max_frame_len: 8 * 1024 * 1024,
+            value: PhantomData,
+        }
+    }
+
+    /// Return a copy of the codec which encodes and decodes frames of up to
+    /// the given number of bytes, not counting their lengths.
+    #[must_use]
+    pub fn with_max_frame_len(
+        mut self,
+        max_frame_len: usize,
+    ) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+}
+
+impl<T> Clone for SerializationCodec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            max_frame_len: self.max_frame_len,
+            value: PhantomData,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for SerializationCodec<T> {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.debug_struct("SerializationCodec")
+            .field("max_frame_len", &self.max_frame_len)
+            .finish()
+    }
+}
+
+impl<T> Default for SerializationCodec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "tokio-util")]
+impl<T> tokio_util::codec::Decoder for SerializationCodec<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    type Error = Error;
+    type Item = T;
+
+    fn decode(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<T>> {
+        self.decode_frame(src)
+    }
+}
+
+#[cfg(feature = "tokio-util")]
+impl<T> tokio_util::codec::Encoder<T> for SerializationCodec<T>
+where
+    T: serde::Serialize,
+{
+    type Error = Error;
+
+    fn encode(
+        &mut self,
+        item: T,
+        dst: &mut BytesMut,
+    ) -> Result<()> {
+        self.encode_frame(&item, dst)
+    }
+}
+
+#[cfg(feature = "asynchronous-codec")]
+impl<T> asynchronous_codec::Decoder for SerializationCodec<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    type Error = Error;
+    type Item = T;
+
+    fn decode(
+        &mut self,
+        src: &mut BytesMut,
+    ) -> Result<Option<T>> {
+        self.decode_frame(src)
+    }
+}
+
+#[cfg(feature = "asynchronous-codec")]
+impl<T> asynchronous_codec::Encoder for SerializationCodec<T>
+where
+    T: serde::Serialize,
+{
+    type Error = Error;
+    type Item<'a> = T;
+
+    fn encode(
+        &mut self,
+        item: T,
+        dst: &mut BytesMut,
+    ) -> Result<()> {
+        self.encode_frame(&item, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_frames_wait_for_more_bytes() {
+        let codec = SerializationCodec::<Vec<u16>>::new();
+        let mut encoded = BytesMut::new();
+        assert!(codec.encode_frame(&vec![300; 100], &mut encoded).is_ok());
+        assert!(codec.encode_frame(&vec![1, 2], &mut encoded).is_ok());
+        // The first frame is 201 bytes long, so its length takes two bytes.
+        assert_eq!(&[0x81, 0x49, 0x64][..], &encoded[..3]);
+        let mut buffer = BytesMut::new();
+        let mut decoded = Vec::new();
+        for byte in &encoded {
+            buffer.extend_from_slice(&[*byte]);
+            let result = codec.decode_frame(&mut buffer);
+            assert!(result.is_ok());
+            decoded.extend(result.unwrap());
+        }
+        assert_eq!(vec![vec![300; 100], vec![1, 2]], decoded);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let codec = SerializationCodec::<u16>::new().with_max_frame_len(4);
+        assert_eq!(4, codec.max_frame_len());
+        let mut buffer = BytesMut::from(&[0x05][..]);
+        assert!(matches!(
+            codec.decode_frame(&mut buffer),
+            Err(Error::FrameTooLong(5))
+        ));
+        let codec = SerializationCodec::<String>::new().with_max_frame_len(4);
+        assert!(matches!(
+            codec.encode_frame(&String::from("long"), &mut buffer),
+            Err(Error::FrameTooLong(5))
+        ));
+        // A frame which fails to decode is dropped, and the next one is
+        // decoded.
+        let codec = SerializationCodec::<u16>::new();
+        let mut buffer =
+            BytesMut::from(&[0x03, 0x84, 0x80, 0x00, 0x01, 0x09][..]);
+        assert!(matches!(
+            codec.decode_frame(&mut buffer),
+            Err(Error::IntegerOverflow)
+        ));
+        assert!(matches!(codec.decode_frame(&mut buffer), Ok(Some(9))));
+        assert!(matches!(codec.decode_frame(&mut buffer), Ok(None)));
+    }
+
+    #[cfg(feature = "asynchronous-codec")]
+    #[test]
+    fn frames_cross_async_std_sockets() {
+        use futures::{
+            SinkExt,
+            StreamExt,
+        };
+
+        async_std::task::block_on(async {
+            let listener =
+                async_std::net::TcpListener::bind("127.0.0.1:0").await;
+            assert!(listener.is_ok());
+            let listener = listener.unwrap();
+            let address = listener.local_addr().unwrap();
+            let sender = async_std::task::spawn(async move {
+                let stream =
+                    async_std::net::TcpStream::connect(address).await?;
+                let mut frames = asynchronous_codec::Framed::new(
+                    stream,
+                    SerializationCodec::<(u32, String)>::new(),
+                );
+                for i in 0..100_u32 {
+                    frames.send((i, i.to_string())).await?;
+                }
+                frames.close().await
+            });
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut frames = asynchronous_codec::Framed::new(
+                stream,
+                SerializationCodec::<(u32, String)>::new(),
+            );
+            for i in 0..100_u32 {
+                let frame = frames.next().await;
+                assert!(matches!(frame, Some(Ok(_))));
+                assert_eq!((i, i.to_string()), frame.unwrap().unwrap());
+            }
+            assert!(sender.await.is_ok());
+            assert!(frames.next().await.is_none());
+        });
+    }
+
+    #[cfg(feature = "asynchronous-codec")]
+    #[test]
+    fn frames_cross_smol_sockets() {
+        use futures::{
+            SinkExt,
+            StreamExt,
+        };
+
+        smol::block_on(async {
+            let listener = smol::net::TcpListener::bind("127.0.0.1:0").await;
+            assert!(listener.is_ok());
+            let listener = listener.unwrap();
+            let address = listener.local_addr().unwrap();
+            let sender = smol::spawn(async move {
+                let stream = smol::net::TcpStream::connect(address).await?;
+                let mut frames = asynchronous_codec::Framed::new(
+                    stream,
+                    SerializationCodec::<Vec<u16>>::new(),
+                );
+                for i in 0..100_u16 {
+                    frames.send(vec![i; 50]).await?;
+                }
+                frames.close().await
+            });
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut frames = asynchronous_codec::Framed::new(
+                stream,
+                SerializationCodec::<Vec<u16>>::new(),
+            );
+            for i in 0..100_u16 {
+                let frame = frames.next().await;
+                assert!(matches!(frame, Some(Ok(_))));
+                assert_eq!(vec![i; 50], frame.unwrap().unwrap());
+            }
+            assert!(sender.await.is_ok());
+            assert!(frames.next().await.is_none());
+        });
+    }
+}
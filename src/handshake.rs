@@ -0,0 +1,288 @@
+use super::{
+    Config,
+    Error,
+    Features,
+    Result,
+    FORMAT_VERSION,
+};
+
+/// This is the message which peers exchange when they first connect, in
+/// order to agree on how to encode everything which follows.  Each peer
+/// sends its own `Hello` and then calls [`negotiate`] with the one it
+/// receives, and both arrive at the same [`Config`].
+///
+/// [`negotiate`]: #method.negotiate
+/// [`Config`]: struct.Config.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_bytes,
+///     to_bytes,
+///     Features,
+///     Hello,
+/// };
+///
+/// let ours = Hello::new();
+/// let message = to_bytes(&ours).unwrap();
+///
+/// // ...the peer receives our message and sends its own...
+/// let theirs = Hello::new().with_features(Features::NONE);
+/// let ours: Hello = from_bytes(&message).unwrap();
+/// let config = theirs.negotiate(&ours).unwrap();
+/// assert_eq!(Features::NONE, config.features());
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hello {
+    features: Features,
+    max_version: u16,
+    min_version: u16,
+}
+
+impl Hello {
+    /// Return the set of optional features supported by both the sender of
+    /// this `Hello` and the sender of the given one, including features
+    /// defined by applications, which are left out of the configuration
+    /// chosen by [`negotiate`].
+    ///
+    /// [`negotiate`]: #method.negotiate
+    #[must_use]
+    pub fn common_features(
+        &self,
+        peer: &Hello,
+    ) -> Features {
+        self.features.intersection(peer.features)
+    }
+
+    /// Return the set of optional features the sender supports.
+    #[must_use]
+    pub fn features(&self) -> Features {
+        self.features
+    }
+
+    /// Return a new `Hello` offering the version of the encoding produced by
+    /// this crate, along with exactly the features enabled by the given
+    /// configuration, so that negotiating with a peer which offers the same
+    /// features arrives at a configuration which encodes values the same
+    /// way.
+    #[must_use]
+    pub fn from_config(config: &Config) -> Self {
+        Self::new().with_features(config.features())
+    }
+
+    /// Return the newest version of the encoding the sender supports.
+    #[must_use]
+    pub fn max_version(&self) -> u16 {
+        self.max_version
+    }
+
+    /// Return the oldest version of the encoding the sender supports.
+    #[must_use]
+    pub fn min_version(&self) -> u16 {
+        self.min_version
+    }
+
+    /// This function is used to pick the configuration to use when
+    /// communicating with a peer, given the `Hello` received from the peer.
+    /// The newest version supported by both peers is selected, along with
+    /// every optional feature supported by both peers, which turns on each
+    /// option of the configuration changing the encoding only if both peers
+    /// offer it.  The result does not depend on which peer is making the
+    /// choice.  Options which only limit what is accepted, such as
+    /// [`Config::with_max_depth`], are left at their defaults, to be changed
+    /// by each peer as it sees fit.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoCommonVersion`] if there is no version of the
+    /// encoding which both peers support.
+    ///
+    /// [`Config::with_max_depth`]: struct.Config.html#method.with_max_depth
+    /// [`Error::NoCommonVersion`]: enum.Error.html#variant.NoCommonVersion
+    pub fn negotiate(
+        &self,
+        peer: &Hello,
+    ) -> Result<Config> {
+        let version = self.max_version.min(peer.max_version);
+        if version < self.min_version.max(peer.min_version) {
+            return Err(Error::NoCommonVersion);
+        }
+        Ok(Config::new()
+            .with_version(version)
+            .with_features(self.common_features(peer)))
+    }
+
+    /// Return a new `Hello` offering the version of the encoding produced by
+    /// this crate, along with [`Features::FINGERPRINT`].  The other features
+    /// choose between ways of encoding values, rather than adding checks,
+    /// so they are only offered if given to [`with_features`].
+    ///
+    /// [`Features::FINGERPRINT`]: struct.Features.html#associatedconstant.FINGERPRINT
+    /// [`with_features`]: #method.with_features
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            features: Features::FINGERPRINT,
+            max_version: FORMAT_VERSION,
+            min_version: FORMAT_VERSION,
+        }
+    }
+
+    /// Return a copy of the `Hello` offering only the given set of optional
+    /// features, such as to turn off features the application does not
+    /// want.
+    #[must_use]
+    pub fn with_features(
+        mut self,
+        features: Features,
+    ) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Return a copy of the `Hello` offering the given range of versions of
+    /// the encoding.
+    #[must_use]
+    pub fn with_versions(
+        mut self,
+        min_version: u16,
+        max_version: u16,
+    ) -> Self {
+        self.min_version = min_version;
+        self.max_version = max_version;
+        self
+    }
+}
+
+impl Default for Hello {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The layout of the `Hello` message must never change, since it's what
+// peers using different versions of the encoding rely on to find each other.
+impl serde::Serialize for Hello {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serde::Serialize::serialize(
+            &(self.min_version, self.max_version, self.features),
+            serializer,
+        )
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Hello {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (min_version, max_version, features) =
+            serde::Deserialize::deserialize(deserializer)?;
+        Ok(Self {
+            features,
+            max_version,
+            min_version,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        from_bytes,
+        to_bytes,
+    };
+
+    #[test]
+    fn hello_encoding() {
+        let hello = Hello::new().with_versions(1, 300);
+        let encoding = to_bytes(&hello);
+        assert!(encoding.is_ok());
+        let encoding = encoding.unwrap();
+        assert_eq!(&[0x01, 0x82, 0x2C, 0x01][..], encoding);
+        let decoded: Result<Hello> = from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!(hello, decoded.unwrap());
+    }
+
+    #[test]
+    fn negotiate_common_configuration() {
+        let old =
+            Hello::new().with_versions(1, 2).with_features(Features::NONE);
+        let new = Hello::new().with_versions(2, 5);
+        let config = old.negotiate(&new);
+        assert!(config.is_ok());
+        let config = config.unwrap();
+        assert_eq!(2, config.version());
+        assert_eq!(Features::NONE, config.features());
+        assert_eq!(config, new.negotiate(&old).unwrap());
+        let config = new.negotiate(&Hello::new().with_versions(1, 9)).unwrap();
+        assert_eq!(5, config.version());
+        assert_eq!(Features::FINGERPRINT, config.features());
+    }
+
+    #[test]
+    fn negotiate_application_features() {
+        let compression = Features::application(0);
+        let tracing = Features::application(15);
+        assert_eq!(0x8000_0000, tracing.bits());
+        let both = compression.union(tracing);
+        assert_eq!(compression, both.difference(tracing));
+        assert!(both.difference(both).is_empty());
+        let ours = Hello::new().with_features(Features::ALL.union(both));
+        let theirs = Hello::new().with_features(compression);
+        assert_eq!(compression, ours.common_features(&theirs));
+        assert_eq!(compression, theirs.common_features(&ours));
+        let config = ours.negotiate(&theirs);
+        assert!(config.is_ok());
+        assert_eq!(Features::NONE, config.unwrap().features());
+        let theirs = Hello::new().with_features(Features::ALL.union(tracing));
+        assert_eq!(Features::ALL.union(tracing), ours.common_features(&theirs));
+        assert_eq!(Features::ALL, ours.negotiate(&theirs).unwrap().features());
+    }
+
+    #[test]
+    fn negotiate_encoding_modes() {
+        let ours = Config::new()
+            .with_struct_framing(true)
+            .with_field_names(true)
+            .with_variant_names(true);
+        let theirs = Config::new()
+            .with_struct_framing(true)
+            .with_field_ids(true)
+            .with_variant_names(true);
+        let ours = Hello::from_config(&ours);
+        let theirs = Hello::from_config(&theirs);
+        assert_eq!(
+            Features::STRUCT_FRAMING.union(Features::VARIANT_NAMES),
+            ours.features().intersection(theirs.features())
+        );
+        let config = ours.negotiate(&theirs);
+        assert!(config.is_ok());
+        let config = config.unwrap();
+        assert_eq!(config, theirs.negotiate(&ours).unwrap());
+        assert!(config.struct_framing());
+        assert!(config.variant_names());
+        assert!(!config.field_names());
+        assert!(!config.field_ids());
+        let plain = Hello::from_config(&Config::new());
+        assert_eq!(Features::NONE, plain.features());
+        assert_eq!(Config::new(), ours.negotiate(&plain).unwrap());
+    }
+
+    #[test]
+    fn negotiate_without_common_version() {
+        let old = Hello::new().with_versions(1, 2);
+        let new = Hello::new().with_versions(3, 4);
+        assert!(matches!(old.negotiate(&new), Err(Error::NoCommonVersion)));
+    }
+}
@@ -0,0 +1,167 @@
+use super::{
+    Result,
+    Serializer,
+};
+
+/// This is the number of bytes shown by a [`HexDump`] made by
+/// [`to_bytes_hex_log`], which is enough to recognize a message without
+/// flooding a log with large payloads.
+///
+/// [`HexDump`]: struct.HexDump.html
+/// [`to_bytes_hex_log`]: fn.to_bytes_hex_log.html
+pub const HEX_LOG_BYTES: usize = 32;
+
+/// This adapts a sequence of bytes, such as an encoded payload, for display
+/// as hexadecimal, one pair of digits per byte separated by spaces.  The
+/// bytes are written directly to the formatter, so nothing is allocated,
+/// and when the display is truncated only the bytes shown are visited.
+///
+/// By default all the bytes are shown.  The number shown may be limited
+/// using [`with_max_bytes`], or by giving a precision in the format string,
+/// such as `{:.8}`, in which case the display ends with the number of bytes
+/// left out.
+///
+/// [`with_max_bytes`]: #method.with_max_bytes
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::HexDump;
+///
+/// let payload = [0x01, 0xAB, 0x00, 0x7F, 0x10];
+/// assert_eq!("01 AB 00 7F 10", HexDump::new(&payload).to_string());
+/// assert_eq!(
+///     "01 AB ... (+3 bytes)",
+///     HexDump::new(&payload).with_max_bytes(2).to_string()
+/// );
+/// assert_eq!(
+///     "01 AB 00 ... (+2 bytes)",
+///     format!("{:.3}", HexDump::new(&payload))
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HexDump<'a> {
+    bytes: &'a [u8],
+    max_bytes: Option<usize>,
+}
+
+impl<'a> HexDump<'a> {
+    /// Return the bytes being displayed, including any which are left out
+    /// of the display.
+    #[must_use]
+    pub fn bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Return the most bytes which will be shown, if the display is
+    /// truncated.
+    #[must_use]
+    pub fn max_bytes(&self) -> Option<usize> {
+        self.max_bytes
+    }
+
+    /// Make an adapter which displays all the given bytes.
+    #[must_use]
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            max_bytes: None,
+        }
+    }
+
+    /// Limit the display to at most the given number of bytes.
+    #[must_use]
+    pub fn with_max_bytes(
+        mut self,
+        max_bytes: usize,
+    ) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+}
+
+impl std::fmt::Display for HexDump<'_> {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        let max_bytes = f.precision().or(self.max_bytes);
+        let shown = max_bytes.map_or(self.bytes.len(), |max_bytes| {
+            max_bytes.min(self.bytes.len())
+        });
+        for (i, byte) in self.bytes[..shown].iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{byte:02X}")?;
+        }
+        let omitted = self.bytes.len() - shown;
+        if omitted > 0 {
+            if shown > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "... (+{omitted} bytes)")?;
+        }
+        Ok(())
+    }
+}
+
+/// This function is used to encode the given value into the given buffer,
+/// replacing its contents, and return an adapter for logging a preview of
+/// the encoding, limited to the first [`HEX_LOG_BYTES`] bytes.  Reusing the
+/// buffer lets a service log payloads without allocating for each one.
+///
+/// [`HEX_LOG_BYTES`]: constant.HEX_LOG_BYTES.html
+///
+/// # Errors
+///
+/// Any of the errors returned by [`to_bytes`] may be returned.
+///
+/// [`to_bytes`]: fn.to_bytes.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::to_bytes_hex_log;
+///
+/// let mut buffer = Vec::new();
+/// let preview = to_bytes_hex_log(&(7_u8, "hi"), &mut buffer).unwrap();
+/// assert_eq!("07 02 68 69", preview.to_string());
+/// ```
+pub fn to_bytes_hex_log<'a, T>(
+    value: &T,
+    buffer: &'a mut Vec<u8>,
+) -> Result<HexDump<'a>>
+where
+    T: ?Sized + serde::Serialize,
+{
+    buffer.clear();
+    value.serialize(&mut Serializer::new(buffer))?;
+    Ok(HexDump::new(buffer).with_max_bytes(HEX_LOG_BYTES))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncation() {
+        let bytes: Vec<u8> = (0..40).collect();
+        let dump = HexDump::new(&bytes).with_max_bytes(4);
+        assert_eq!("00 01 02 03 ... (+36 bytes)", dump.to_string());
+        assert_eq!("00 01 ... (+38 bytes)", format!("{dump:.2}"));
+        assert_eq!("... (+40 bytes)", format!("{dump:.0}"));
+        assert_eq!("", HexDump::new(&[]).to_string());
+        assert_eq!("FF", HexDump::new(&[0xFF]).with_max_bytes(1).to_string());
+        let mut buffer = vec![9; 100];
+        let preview = to_bytes_hex_log(&bytes, &mut buffer);
+        assert!(preview.is_ok());
+        let preview = preview.unwrap();
+        assert_eq!(41, preview.bytes().len());
+        assert_eq!(Some(HEX_LOG_BYTES), preview.max_bytes());
+        assert!(preview.to_string().starts_with("28 00 01 02"));
+        assert!(preview.to_string().ends_with("1E ... (+9 bytes)"));
+    }
+}
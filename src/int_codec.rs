@@ -0,0 +1,502 @@
+use super::{
+    Error,
+    Output,
+    Result,
+};
+use core::convert::TryFrom;
+
+// This is the most bytes the encoding of one integer may take.
+pub(crate) const MAX_INT_SIZE: usize = 16;
+
+// This is the most bytes the varint encoding of a 128-bit integer may take.
+pub(crate) const MAX_INT128_SIZE: usize = 19;
+
+/// This trait is implemented by the encodings of integers wider than a
+/// byte used by the [`Serializer`] and [`Deserializer`], which covers the
+/// values of `u16` through `u64` and `i16` through `i64`, and also the
+/// lengths of strings, sequences, and maps and the indices of enum
+/// variants.  The default is [`Varint`].
+///
+/// A codec is a value held by the serializer or deserializer, so a codec
+/// may keep state from one integer to the next, such as the previous value
+/// for delta encoding.  Integers are encoded and decoded in the order they
+/// appear in the encoding, except that the length of a sequence or map
+/// which was not known when it began is encoded after its elements, and put
+/// in front of them afterward, so a codec which keeps state cannot be used
+/// to encode such sequences and maps.
+///
+/// Integers are encoded into a buffer on the stack, so the serializer does
+/// not allocate for them, which limits the encoding of each integer to 16
+/// bytes.
+///
+/// [`Serializer`]: struct.Serializer.html
+/// [`Deserializer`]: struct.Deserializer.html
+/// [`Varint`]: struct.Varint.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     Config,
+///     Error,
+///     IntCodec,
+///     Output,
+///     Result,
+/// };
+///
+/// // Every integer takes eight bytes, least significant byte first.
+/// struct LittleEndian;
+///
+/// impl IntCodec for LittleEndian {
+///     fn decode_signed(
+///         &mut self,
+///         input: &[u8],
+///         _max: i64,
+///     ) -> Result<(i64, usize)> {
+///         let (value, len) = self.decode_unsigned(input, u64::MAX)?;
+///         Ok((value as i64, len))
+///     }
+///
+///     fn decode_unsigned(
+///         &mut self,
+///         input: &[u8],
+///         _max: u64,
+///     ) -> Result<(u64, usize)> {
+///         let mut bytes = [0; 8];
+///         bytes.copy_from_slice(input.get(..8).ok_or(Error::ValueTruncated)?);
+///         Ok((u64::from_le_bytes(bytes), 8))
+///     }
+///
+///     fn encode_signed<O>(
+///         &mut self,
+///         value: i64,
+///         output: &mut O,
+///     ) -> Result<()>
+///     where
+///         O: ?Sized + Output,
+///     {
+///         output.write_bytes(&value.to_le_bytes())
+///     }
+///
+///     fn encode_unsigned<O>(
+///         &mut self,
+///         value: u64,
+///         output: &mut O,
+///     ) -> Result<()>
+///     where
+///         O: ?Sized + Output,
+///     {
+///         output.write_bytes(&value.to_le_bytes())
+///     }
+/// }
+///
+/// let mut buffer = Vec::new();
+/// let mut serializer =
+///     Config::new().serializer_with_codec(&mut buffer, LittleEndian);
+/// serde::Serialize::serialize(&(-2_i16, "a"), &mut serializer).unwrap();
+/// assert_eq!(
+///     &[
+///         0xFE, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 1, 0, 0, 0, 0, 0,
+///         0, 0, b'a'
+///     ][..],
+///     buffer
+/// );
+/// let mut deserializer =
+///     Config::new().deserializer_with_codec(&buffer[..], LittleEndian);
+/// let decoded: (i16, &str) =
+///     serde::Deserialize::deserialize(&mut deserializer).unwrap();
+/// assert_eq!((-2, "a"), decoded);
+/// ```
+pub trait IntCodec {
+    /// Decode a signed integer from the start of the given bytes, returning
+    /// it along with the number of bytes it took.  The integer must be no
+    /// larger than the given maximum and no smaller than one less than its
+    /// negative, which are the limits of the type being decoded.  The
+    /// deserializer checks this afterwards as well, but a codec may stop as
+    /// soon as it finds the integer is out of range.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the bytes end before the
+    /// integer does, or [`Error::IntegerOverflow`] if the integer is out of
+    /// range.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+    fn decode_signed(
+        &mut self,
+        input: &[u8],
+        max: i64,
+    ) -> Result<(i64, usize)>;
+
+    /// Decode an unsigned integer from the start of the given bytes,
+    /// returning it along with the number of bytes it took.  The integer
+    /// must be no larger than the given maximum, which is the limit of the
+    /// type being decoded.  The deserializer checks this afterwards as
+    /// well, but a codec may stop as soon as it finds the integer is out of
+    /// range.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the bytes end before the
+    /// integer does, or [`Error::IntegerOverflow`] if the integer is out of
+    /// range.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+    fn decode_unsigned(
+        &mut self,
+        input: &[u8],
+        max: u64,
+    ) -> Result<(u64, usize)>;
+
+    /// Append the encoding of the given signed integer to the output.
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing to the output is returned.
+    fn encode_signed<O>(
+        &mut self,
+        value: i64,
+        output: &mut O,
+    ) -> Result<()>
+    where
+        O: ?Sized + Output;
+
+    /// Append the encoding of the given unsigned integer to the output.
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing to the output is returned.
+    fn encode_unsigned<O>(
+        &mut self,
+        value: u64,
+        output: &mut O,
+    ) -> Result<()>
+    where
+        O: ?Sized + Output;
+}
+
+/// This is the encoding of integers used by default.  Unsigned integers
+/// are split into groups of seven bits, most significant group first, each
+/// in a byte whose high bit is set unless it is the last.  Signed integers
+/// are encoded the same way, except that the first byte has six bits of
+/// the magnitude, with the sign in the bit above them.
+///
+/// Values of `i128` and `u128` are always encoded this way, whichever codec
+/// is used for narrower integers, and values which fit in 64 bits encode
+/// the same whether they are 128-bit integers or not.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Varint;
+
+impl Varint {
+    #[allow(clippy::cast_lossless)]
+    pub(crate) fn decode_signed128(input: &[u8]) -> Result<(i128, usize)> {
+        let mut it = input.iter();
+        let first = it.next().ok_or(Error::ValueTruncated)?;
+        let mut len = 1;
+        let mut more = (first & 0x80) != 0;
+        let negative = (first & 0x40) != 0;
+        // The magnitude is built unsigned, since that of `i128::MIN` does
+        // not fit in an `i128`.
+        let mut magnitude = (first & 0x3F) as u128;
+        while more {
+            let next = it.next().ok_or(Error::ValueTruncated)?;
+            len += 1;
+            more = (next & 0x80) != 0;
+            magnitude = magnitude
+                .checked_mul(128)
+                .and_then(|magnitude| {
+                    magnitude.checked_add((next & 0x7F) as u128)
+                })
+                .ok_or(Error::IntegerOverflow)?;
+        }
+        let value = if negative {
+            0_i128.checked_sub_unsigned(magnitude)
+        } else {
+            i128::try_from(magnitude).ok()
+        };
+        Ok((value.ok_or(Error::IntegerOverflow)?, len))
+    }
+
+    #[allow(clippy::cast_lossless)]
+    pub(crate) fn decode_unsigned128(input: &[u8]) -> Result<(u128, usize)> {
+        let mut it = input.iter();
+        let first = it.next().ok_or(Error::ValueTruncated)?;
+        let mut len = 1;
+        let mut more = (first & 0x80) != 0;
+        let mut value = (first & 0x7F) as u128;
+        while more {
+            let next = it.next().ok_or(Error::ValueTruncated)?;
+            len += 1;
+            more = (next & 0x80) != 0;
+            value = value
+                .checked_mul(128)
+                .and_then(|value| value.checked_add((next & 0x7F) as u128))
+                .ok_or(Error::IntegerOverflow)?;
+        }
+        Ok((value, len))
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn encode_signed128<O>(
+        value: i128,
+        output: &mut O,
+    ) -> Result<()>
+    where
+        O: ?Sized + Output,
+    {
+        let sign = if value >= 0 {
+            0x00_u8
+        } else {
+            0x40_u8
+        };
+        let mut abs = value.unsigned_abs();
+        let mut bytes = [0; MAX_INT128_SIZE];
+        let mut start = bytes.len();
+        let mut more = 0x00;
+        while abs & !0x3F != 0 {
+            start -= 1;
+            bytes[start] = (abs & 0x7F) as u8 | more;
+            more = 0x80;
+            abs >>= 7;
+        }
+        start -= 1;
+        bytes[start] = abs as u8 | sign | more;
+        output.write_bytes(&bytes[start..])
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    pub(crate) fn encode_unsigned128<O>(
+        mut value: u128,
+        output: &mut O,
+    ) -> Result<()>
+    where
+        O: ?Sized + Output,
+    {
+        let mut bytes = [0; MAX_INT128_SIZE];
+        let mut start = bytes.len() - 1;
+        bytes[start] = (value & 0x7F) as u8;
+        value >>= 7;
+        while value != 0 {
+            start -= 1;
+            bytes[start] = (value & 0x7F) as u8 | 0x80;
+            value >>= 7;
+        }
+        output.write_bytes(&bytes[start..])
+    }
+}
+
+impl IntCodec for Varint {
+    #[allow(clippy::cast_lossless)]
+    fn decode_signed(
+        &mut self,
+        input: &[u8],
+        max: i64,
+    ) -> Result<(i64, usize)> {
+        let mut it = input.iter();
+        let first = it.next().ok_or(Error::ValueTruncated)?;
+        let mut len = 1;
+        let mut more = (first & 0x80) != 0;
+        let negative = (first & 0x40) != 0;
+        let mut value = (first & 0x3F) as i64;
+        while more {
+            let next = it.next().ok_or(Error::ValueTruncated)?;
+            len += 1;
+            let lsb = next & 0x7F;
+            more = (next & 0x80) != 0;
+            // Special case: the negative of exactly one value, `i64::MIN`,
+            // won't fit in 64 bits, so we detect it here and return it
+            // early before we would overflow.
+            if !more
+                && negative
+                && lsb == 0
+                && value == 0x0100_0000_0000_0000_i64
+            {
+                return Ok((i64::MIN, len));
+            }
+            value = value.checked_mul(128).ok_or(Error::IntegerOverflow)?;
+            value += lsb as i64;
+            if (negative && value > max.saturating_add(1))
+                || (!negative && value > max)
+            {
+                return Err(Error::IntegerOverflow);
+            }
+        }
+        Ok((
+            if negative {
+                -value
+            } else {
+                value
+            },
+            len,
+        ))
+    }
+
+    #[allow(clippy::cast_lossless)]
+    fn decode_unsigned(
+        &mut self,
+        input: &[u8],
+        max: u64,
+    ) -> Result<(u64, usize)> {
+        let mut it = input.iter();
+        let first = it.next().ok_or(Error::ValueTruncated)?;
+        let mut len = 1;
+        let mut more = (first & 0x80) != 0;
+        let mut value = (first & 0x7F) as u64;
+        while more {
+            let next = it.next().ok_or(Error::ValueTruncated)?;
+            len += 1;
+            let lsb = next & 0x7F;
+            more = (next & 0x80) != 0;
+            value = value.checked_mul(128).ok_or(Error::IntegerOverflow)?;
+            value += lsb as u64;
+            if value > max {
+                return Err(Error::IntegerOverflow);
+            }
+        }
+        Ok((value, len))
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn encode_signed<O>(
+        &mut self,
+        value: i64,
+        output: &mut O,
+    ) -> Result<()>
+    where
+        O: ?Sized + Output,
+    {
+        let sign = if value >= 0 {
+            0x00_u8
+        } else {
+            0x40_u8
+        };
+        let mut abs = value.unsigned_abs();
+        let mut bytes = [0; 10];
+        let mut start = bytes.len();
+        let mut more = 0x00;
+        while abs & !0x3F != 0 {
+            start -= 1;
+            bytes[start] = (abs & 0x7F) as u8 | more;
+            more = 0x80;
+            abs >>= 7;
+        }
+        start -= 1;
+        bytes[start] = abs as u8 | sign | more;
+        output.write_bytes(&bytes[start..])
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn encode_unsigned<O>(
+        &mut self,
+        mut value: u64,
+        output: &mut O,
+    ) -> Result<()>
+    where
+        O: ?Sized + Output,
+    {
+        let mut bytes = [0; 10];
+        let mut start = bytes.len() - 1;
+        bytes[start] = (value & 0x7F) as u8;
+        value >>= 7;
+        while value != 0 {
+            start -= 1;
+            bytes[start] = (value & 0x7F) as u8 | 0x80;
+            value >>= 7;
+        }
+        output.write_bytes(&bytes[start..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    // This codec encodes each integer as its difference from the one
+    // before, to check that codecs can keep state between integers.
+    #[derive(Default)]
+    struct Delta {
+        previous: i64,
+    }
+
+    impl IntCodec for Delta {
+        fn decode_signed(
+            &mut self,
+            input: &[u8],
+            _max: i64,
+        ) -> Result<(i64, usize)> {
+            let (delta, len) = Varint.decode_signed(input, i64::MAX)?;
+            self.previous += delta;
+            Ok((self.previous, len))
+        }
+
+        #[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+        fn decode_unsigned(
+            &mut self,
+            input: &[u8],
+            _max: u64,
+        ) -> Result<(u64, usize)> {
+            let (value, len) = self.decode_signed(input, i64::MAX)?;
+            Ok((value as u64, len))
+        }
+
+        fn encode_signed<O>(
+            &mut self,
+            value: i64,
+            output: &mut O,
+        ) -> Result<()>
+        where
+            O: ?Sized + Output,
+        {
+            Varint.encode_signed(value - self.previous, output)?;
+            self.previous = value;
+            Ok(())
+        }
+
+        #[allow(clippy::cast_possible_wrap)]
+        fn encode_unsigned<O>(
+            &mut self,
+            value: u64,
+            output: &mut O,
+        ) -> Result<()>
+        where
+            O: ?Sized + Output,
+        {
+            self.encode_signed(value as i64, output)
+        }
+    }
+
+    #[test]
+    fn stateful_codec() {
+        let timestamps = vec![1_000_000_u32, 1_000_010, 1_000_015, 1_000_013];
+        let config = Config::new();
+        let mut buffer = Vec::new();
+        let mut serializer =
+            config.serializer_with_codec(&mut buffer, Delta::default());
+        assert!(
+            serde::Serialize::serialize(&timestamps, &mut serializer).is_ok()
+        );
+        // The length, 4, is a delta of 4 from zero, and the first timestamp
+        // is a delta from the length.
+        assert_eq!(&[0x04, 0xBD, 0x84, 0x3C, 0x0A, 0x05, 0x42][..], buffer);
+        let mut deserializer =
+            config.deserializer_with_codec(&buffer[..], Delta::default());
+        let decoded: Result<Vec<u32>> =
+            serde::Deserialize::deserialize(&mut deserializer);
+        assert!(decoded.is_ok());
+        assert_eq!(timestamps, decoded.unwrap());
+    }
+
+    #[test]
+    fn decoded_integers_are_range_checked() {
+        let config = Config::new();
+        let mut deserializer =
+            config.deserializer_with_codec(&[0x84, 0x80, 0x00][..], Varint);
+        let decoded: Result<u16> =
+            serde::Deserialize::deserialize(&mut deserializer);
+        assert!(matches!(decoded, Err(Error::IntegerOverflow)));
+    }
+}
@@ -0,0 +1,609 @@
+use super::{
+    Error,
+    Result,
+};
+use alloc::vec::Vec;
+
+// This is the most bytes skipped at once by inputs which read the bytes
+// they skip.
+const SKIP_CHUNK_SIZE: usize = 256;
+
+/// This trait is implemented by the destinations of encoded bytes, which
+/// the [`Serializer`] writes to.  It is implemented for `Vec<u8>`, which
+/// grows to fit, for `&mut [u8]`, which is filled from the front and
+/// shrinks to the part not yet written, and for any [`std::io::Write`]
+/// wrapped in an [`IoOutput`] when the `std` feature is enabled.  Other
+/// sinks, such as ring buffers or callbacks into other languages, can
+/// implement it directly, without going through `std::io`.
+///
+/// [`Serializer`]: struct.Serializer.html
+/// [`std::io::Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
+/// [`IoOutput`]: struct.IoOutput.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     Config,
+///     Output,
+///     Result,
+/// };
+///
+/// // Only the number of bytes is kept.
+/// struct Counter(usize);
+///
+/// impl Output for Counter {
+///     fn write_bytes(
+///         &mut self,
+///         bytes: &[u8],
+///     ) -> Result<()> {
+///         self.0 += bytes.len();
+///         Ok(())
+///     }
+/// }
+///
+/// let mut counter = Counter(0);
+/// serde::Serialize::serialize(
+///     &("hello", 1000_u16),
+///     &mut Config::new().serializer(&mut counter),
+/// )
+/// .unwrap();
+/// assert_eq!(8, counter.0);
+/// ```
+pub trait Output {
+    /// Write all the given bytes after those already written.
+    ///
+    /// # Errors
+    ///
+    /// An error is returned if the bytes could not all be written, such as
+    /// [`Error::BufferFull`] for a slice too short to hold them, or
+    /// [`Error::Io`] for a failure of the underlying stream.
+    ///
+    /// [`Error::BufferFull`]: enum.Error.html#variant.BufferFull
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    fn write_bytes(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<()>;
+}
+
+/// This trait is implemented by the sources of encoded bytes, which the
+/// [`Deserializer`] reads from.  It is implemented for `&[u8]`, which
+/// shrinks to the part not yet read, and for any [`std::io::Read`] wrapped
+/// in an [`IoInput`] when the `std` feature is enabled.  Other sources can
+/// implement it directly, without going through `std::io`.
+///
+/// Inputs whose bytes are all held in memory for the lifetime `'de` can
+/// hand them to the deserializer with [`as_slice`], so that strings and
+/// byte strings are borrowed from them rather than copied, and integers are
+/// decoded without reading them a byte at a time.
+///
+/// [`Deserializer`]: struct.Deserializer.html
+/// [`std::io::Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
+/// [`IoInput`]: struct.IoInput.html
+/// [`as_slice`]: #method.as_slice
+pub trait Input<'de> {
+    /// Return the bytes not yet read, if they are all held in memory.  An
+    /// input which returns them must go on returning the bytes not yet
+    /// read after each read or skip.
+    fn as_slice(&self) -> Option<&'de [u8]> {
+        None
+    }
+
+    /// Determine whether or not the input has ended, which may take
+    /// reading ahead.  Inputs which cannot tell without consuming bytes
+    /// say they have not ended.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error, such as [`Error::Io`], if reading ahead fails.
+    ///
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    fn at_end(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+
+    /// Read exactly enough bytes to fill the given buffer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends before the
+    /// buffer is filled, or another error, such as [`Error::Io`], if the
+    /// bytes could not be read.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    fn read_bytes(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<()>;
+
+    /// Pass over the given number of bytes without keeping them.
+    ///
+    /// # Errors
+    ///
+    /// Any of the errors returned by [`read_bytes`] may be returned.
+    ///
+    /// [`read_bytes`]: #tymethod.read_bytes
+    fn skip_bytes(
+        &mut self,
+        mut len: usize,
+    ) -> Result<()> {
+        let mut buffer = [0; SKIP_CHUNK_SIZE];
+        while len > 0 {
+            let chunk = len.min(SKIP_CHUNK_SIZE);
+            self.read_bytes(&mut buffer[..chunk])?;
+            len -= chunk;
+        }
+        Ok(())
+    }
+}
+
+/// This adapts any [`std::io::Write`], such as a file or socket, into an
+/// [`Output`].
+///
+/// Each write is passed straight to the writer, so writing many small
+/// values to a file costs a system call each unless the writer is
+/// buffered.  On Linux, files can instead be written through `io_uring`
+/// with a [`UringOutput`], when the `io-uring` feature is enabled.
+///
+/// [`std::io::Write`]: https://doc.rust-lang.org/std/io/trait.Write.html
+/// [`Output`]: trait.Output.html
+/// [`UringOutput`]: struct.UringOutput.html
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IoOutput<W>(W);
+
+#[cfg(feature = "std")]
+impl<W> IoOutput<W> {
+    /// Give back the writer being adapted.
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+
+    /// Adapt the given writer.
+    pub fn new(writer: W) -> Self {
+        Self(writer)
+    }
+}
+
+/// This adapts any [`std::io::Read`], such as a file or socket, into an
+/// [`Input`].  To find whether or not the reader has ended, a byte may be
+/// read ahead and held until the next read.
+///
+/// [`std::io::Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
+/// [`Input`]: trait.Input.html
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IoInput<R> {
+    peeked: Option<u8>,
+    reader: R,
+}
+
+#[cfg(feature = "std")]
+impl<R> IoInput<R> {
+    /// Give back the reader being adapted, which is missing any byte read
+    /// ahead to find whether or not it had ended.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Adapt the given reader.
+    pub fn new(reader: R) -> Self {
+        Self {
+            peeked: None,
+            reader,
+        }
+    }
+}
+
+/// This adapts any [`embedded_io::Write`], such as a UART or a socket on
+/// a device without `std`, into an [`Output`].
+///
+/// [`embedded_io::Write`]: https://docs.rs/embedded-io/0.6/embedded_io/trait.Write.html
+/// [`Output`]: trait.Output.html
+#[cfg(feature = "embedded-io")]
+#[derive(Debug)]
+pub struct EmbeddedOutput<W>(W);
+
+#[cfg(feature = "embedded-io")]
+impl<W> EmbeddedOutput<W> {
+    /// Give back the writer being adapted.
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+
+    /// Adapt the given writer.
+    pub fn new(writer: W) -> Self {
+        Self(writer)
+    }
+}
+
+/// This adapts any [`embedded_io::Read`] into an [`Input`].  As with
+/// [`IoInput`], a byte may be read ahead and held until the next read, to
+/// find whether or not the reader has ended.
+///
+/// [`embedded_io::Read`]: https://docs.rs/embedded-io/0.6/embedded_io/trait.Read.html
+/// [`Input`]: trait.Input.html
+/// [`IoInput`]: struct.IoInput.html
+#[cfg(feature = "embedded-io")]
+#[derive(Debug)]
+pub struct EmbeddedInput<R> {
+    peeked: Option<u8>,
+    reader: R,
+}
+
+#[cfg(feature = "embedded-io")]
+impl<R> EmbeddedInput<R> {
+    /// Give back the reader being adapted, which is missing any byte read
+    /// ahead to find whether or not it had ended.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Adapt the given reader.
+    pub fn new(reader: R) -> Self {
+        Self {
+            peeked: None,
+            reader,
+        }
+    }
+}
+
+impl Output for Vec<u8> {
+    fn write_bytes(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<()> {
+        self.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+impl Output for &mut [u8] {
+    // Nothing is written unless all the bytes fit.
+    fn write_bytes(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<()> {
+        if bytes.len() > self.len() {
+            return Err(Error::BufferFull);
+        }
+        let (written, rest) = core::mem::take(self).split_at_mut(bytes.len());
+        written.copy_from_slice(bytes);
+        *self = rest;
+        Ok(())
+    }
+}
+
+impl<O> Output for &mut O
+where
+    O: ?Sized + Output,
+{
+    fn write_bytes(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<()> {
+        (**self).write_bytes(bytes)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W> Output for IoOutput<W>
+where
+    W: std::io::Write,
+{
+    fn write_bytes(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<()> {
+        self.0.write_all(bytes).map_err(Error::Io)
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<W> Output for EmbeddedOutput<W>
+where
+    W: embedded_io::Write,
+{
+    // A writer which takes none of the bytes is treated as full, rather
+    // than being asked again forever.
+    fn write_bytes(
+        &mut self,
+        mut bytes: &[u8],
+    ) -> Result<()> {
+        while !bytes.is_empty() {
+            match self.0.write(bytes) {
+                Ok(0) => {
+                    return Err(Error::EmbeddedIo(
+                        embedded_io::ErrorKind::WriteZero,
+                    ))
+                },
+                Ok(written) => bytes = &bytes[written..],
+                Err(error) => {
+                    return Err(Error::EmbeddedIo(embedded_io::Error::kind(
+                        &error,
+                    )))
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'de> Input<'de> for &'de [u8] {
+    fn as_slice(&self) -> Option<&'de [u8]> {
+        Some(self)
+    }
+
+    fn at_end(&mut self) -> Result<bool> {
+        Ok(self.is_empty())
+    }
+
+    // Nothing is read unless the buffer can be filled.
+    fn read_bytes(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<()> {
+        if buffer.len() > self.len() {
+            return Err(Error::ValueTruncated);
+        }
+        let (read, rest) = self.split_at(buffer.len());
+        buffer.copy_from_slice(read);
+        *self = rest;
+        Ok(())
+    }
+
+    fn skip_bytes(
+        &mut self,
+        len: usize,
+    ) -> Result<()> {
+        *self = self.get(len..).ok_or(Error::ValueTruncated)?;
+        Ok(())
+    }
+}
+
+impl<'de, I> Input<'de> for &mut I
+where
+    I: ?Sized + Input<'de>,
+{
+    fn as_slice(&self) -> Option<&'de [u8]> {
+        (**self).as_slice()
+    }
+
+    fn at_end(&mut self) -> Result<bool> {
+        (**self).at_end()
+    }
+
+    fn read_bytes(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<()> {
+        (**self).read_bytes(buffer)
+    }
+
+    fn skip_bytes(
+        &mut self,
+        len: usize,
+    ) -> Result<()> {
+        (**self).skip_bytes(len)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R> Input<'_> for IoInput<R>
+where
+    R: std::io::Read,
+{
+    fn at_end(&mut self) -> Result<bool> {
+        if self.peeked.is_some() {
+            return Ok(false);
+        }
+        let mut byte = [0];
+        loop {
+            match self.reader.read(&mut byte) {
+                Ok(0) => return Ok(true),
+                Ok(_) => break,
+                Err(error)
+                    if error.kind() == std::io::ErrorKind::Interrupted => {},
+                Err(error) => return Err(Error::Io(error)),
+            }
+        }
+        self.peeked = Some(byte[0]);
+        Ok(false)
+    }
+
+    fn read_bytes(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<()> {
+        let rest = match (self.peeked, buffer.split_first_mut()) {
+            (Some(byte), Some((first, rest))) => {
+                *first = byte;
+                self.peeked = None;
+                rest
+            },
+            _ => buffer,
+        };
+        self.reader.read_exact(rest).map_err(|error| {
+            if error.kind() == std::io::ErrorKind::UnexpectedEof {
+                Error::ValueTruncated
+            } else {
+                Error::Io(error)
+            }
+        })
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<R> Input<'_> for EmbeddedInput<R>
+where
+    R: embedded_io::Read,
+{
+    fn at_end(&mut self) -> Result<bool> {
+        if self.peeked.is_some() {
+            return Ok(false);
+        }
+        let mut byte = [0];
+        loop {
+            match self.reader.read(&mut byte) {
+                Ok(0) => return Ok(true),
+                Ok(_) => break,
+                Err(error) => match embedded_io::Error::kind(&error) {
+                    embedded_io::ErrorKind::Interrupted => {},
+                    kind => return Err(Error::EmbeddedIo(kind)),
+                },
+            }
+        }
+        self.peeked = Some(byte[0]);
+        Ok(false)
+    }
+
+    fn read_bytes(
+        &mut self,
+        buffer: &mut [u8],
+    ) -> Result<()> {
+        let rest = match (self.peeked, buffer.split_first_mut()) {
+            (Some(byte), Some((first, rest))) => {
+                *first = byte;
+                self.peeked = None;
+                rest
+            },
+            _ => buffer,
+        };
+        self.reader.read_exact(rest).map_err(|error| match error {
+            embedded_io::ReadExactError::UnexpectedEof => Error::ValueTruncated,
+            embedded_io::ReadExactError::Other(error) => {
+                Error::EmbeddedIo(embedded_io::Error::kind(&error))
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn outputs_receive_same_bytes() {
+        let value = (String::from("abc"), 300_u16, vec![true, false]);
+        let config = Config::new();
+        let mut vec = Vec::new();
+        assert!(serde::Serialize::serialize(
+            &value,
+            &mut config.serializer(&mut vec)
+        )
+        .is_ok());
+        let mut array = [0; 16];
+        let mut slice = &mut array[..];
+        assert!(serde::Serialize::serialize(
+            &value,
+            &mut config.serializer(&mut slice)
+        )
+        .is_ok());
+        let unused = slice.len();
+        assert_eq!(vec, array[..16 - unused]);
+    }
+
+    #[test]
+    fn full_slice_output() {
+        let mut array = [0; 3];
+        let mut slice = &mut array[..];
+        let result = serde::Serialize::serialize(
+            &"abcd",
+            &mut Config::new().serializer(&mut slice),
+        );
+        assert!(matches!(result, Err(Error::BufferFull)));
+        assert_eq!(2, slice.len());
+    }
+
+    #[test]
+    fn inputs() {
+        let mut bytes = &[1, 2, 3][..];
+        let mut buffer = [0; 2];
+        assert!(bytes.read_bytes(&mut buffer).is_ok());
+        assert_eq!([1, 2], buffer);
+        assert!(matches!(
+            bytes.read_bytes(&mut buffer),
+            Err(Error::ValueTruncated)
+        ));
+        assert_eq!(&[3][..], bytes);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn io_adapters() {
+        let value = (String::from("abc"), 300_u16, vec![true, false]);
+        let config = Config::new();
+        let mut vec = Vec::new();
+        assert!(serde::Serialize::serialize(
+            &value,
+            &mut config.serializer(&mut vec)
+        )
+        .is_ok());
+        let mut writer = IoOutput::new(std::io::Cursor::new(Vec::new()));
+        assert!(serde::Serialize::serialize(
+            &value,
+            &mut config.serializer(&mut writer)
+        )
+        .is_ok());
+        assert_eq!(vec, writer.into_inner().into_inner());
+        let mut reader = IoInput::new(&[4, 5, 6][..]);
+        let mut buffer = [0; 2];
+        assert!(reader.read_bytes(&mut buffer).is_ok());
+        assert_eq!([4, 5], buffer);
+        assert!(matches!(
+            reader.read_bytes(&mut buffer),
+            Err(Error::ValueTruncated)
+        ));
+    }
+
+    #[cfg(feature = "embedded-io")]
+    #[test]
+    fn embedded_io_adapters() {
+        let value = (String::from("abc"), 300_u16, vec![true, false]);
+        let config = Config::new();
+        let mut vec = Vec::new();
+        assert!(serde::Serialize::serialize(
+            &value,
+            &mut config.serializer(&mut vec)
+        )
+        .is_ok());
+        let mut array = [0; 16];
+        let mut writer = EmbeddedOutput::new(&mut array[..]);
+        assert!(serde::Serialize::serialize(
+            &value,
+            &mut config.serializer(&mut writer)
+        )
+        .is_ok());
+        let unused = writer.into_inner().len();
+        assert_eq!(vec, array[..16 - unused]);
+        let mut array = [0; 3];
+        let mut writer = EmbeddedOutput::new(&mut array[..]);
+        assert!(matches!(
+            serde::Serialize::serialize(
+                &"abcd",
+                &mut config.serializer(&mut writer)
+            ),
+            Err(Error::EmbeddedIo(embedded_io::ErrorKind::WriteZero))
+        ));
+        let mut reader = EmbeddedInput::new(&vec[..]);
+        let decoded = serde::Deserialize::deserialize(
+            &mut config.deserializer(&mut reader),
+        );
+        assert!(decoded.is_ok());
+        assert_eq!(value, decoded.unwrap());
+        assert!(matches!(reader.at_end(), Ok(true)));
+        let mut reader = EmbeddedInput::new(&[4, 5, 6][..]);
+        let mut buffer = [0; 2];
+        assert!(matches!(reader.at_end(), Ok(false)));
+        assert!(reader.read_bytes(&mut buffer).is_ok());
+        assert_eq!([4, 5], buffer);
+        assert!(matches!(
+            reader.read_bytes(&mut buffer),
+            Err(Error::ValueTruncated)
+        ));
+    }
+}
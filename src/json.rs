@@ -0,0 +1,1640 @@
+// This is a small JSON serializer and deserializer, following the same
+// conventions as `serde_json`, so that applications can switch between
+// the binary format of this crate and a human-readable one without taking
+// on another dependency.  See [`Format`].
+//
+// [`Format`]: ../enum.Format.html
+
+use super::{
+    redacted::{
+        REDACTED_NAME,
+        REDACTED_TEXT,
+    },
+    Error,
+    Result,
+};
+use serde::de::IntoDeserializer;
+use std::borrow::Cow;
+
+struct Serializer<'a> {
+    out: &'a mut Vec<u8>,
+}
+
+impl Serializer<'_> {
+    fn write_str(
+        &mut self,
+        value: &str,
+    ) {
+        self.out.push(b'"');
+        let mut start = 0;
+        for (index, byte) in value.bytes().enumerate() {
+            let escape: Option<&[u8]> = match byte {
+                b'"' => Some(b"\\\""),
+                b'\\' => Some(b"\\\\"),
+                b'\n' => Some(b"\\n"),
+                b'\r' => Some(b"\\r"),
+                b'\t' => Some(b"\\t"),
+                0x08 => Some(b"\\b"),
+                0x0C => Some(b"\\f"),
+                0x00..=0x1F => None,
+                _ => continue,
+            };
+            self.out.extend_from_slice(&value.as_bytes()[start..index]);
+            start = index + 1;
+            if let Some(escape) = escape {
+                self.out.extend_from_slice(escape);
+            } else {
+                self.out.extend_from_slice(format!("\\u{byte:04x}").as_bytes());
+            }
+        }
+        self.out.extend_from_slice(&value.as_bytes()[start..]);
+        self.out.push(b'"');
+    }
+
+    fn write_display<T>(
+        &mut self,
+        value: T,
+    ) where
+        T: std::fmt::Display,
+    {
+        self.out.extend_from_slice(value.to_string().as_bytes());
+    }
+
+    fn write_float<T>(
+        &mut self,
+        value: T,
+        finite: bool,
+    ) where
+        T: std::fmt::Debug,
+    {
+        if finite {
+            self.out.extend_from_slice(format!("{value:?}").as_bytes());
+        } else {
+            self.out.extend_from_slice(b"null");
+        }
+    }
+}
+
+impl<'a, 'b> serde::Serializer for &'b mut Serializer<'a> {
+    type Error = Error;
+    type Ok = ();
+    type SerializeMap = Compound<'a, 'b>;
+    type SerializeSeq = Compound<'a, 'b>;
+    type SerializeStruct = Compound<'a, 'b>;
+    type SerializeStructVariant = Compound<'a, 'b>;
+    type SerializeTuple = Compound<'a, 'b>;
+    type SerializeTupleStruct = Compound<'a, 'b>;
+    type SerializeTupleVariant = Compound<'a, 'b>;
+
+    fn serialize_bool(
+        self,
+        v: bool,
+    ) -> Result<()> {
+        self.write_display(v);
+        Ok(())
+    }
+
+    fn serialize_i8(
+        self,
+        v: i8,
+    ) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i16(
+        self,
+        v: i16,
+    ) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i32(
+        self,
+        v: i32,
+    ) -> Result<()> {
+        self.serialize_i64(i64::from(v))
+    }
+
+    fn serialize_i64(
+        self,
+        v: i64,
+    ) -> Result<()> {
+        self.write_display(v);
+        Ok(())
+    }
+
+    fn serialize_i128(
+        self,
+        v: i128,
+    ) -> Result<()> {
+        self.write_display(v);
+        Ok(())
+    }
+
+    fn serialize_u8(
+        self,
+        v: u8,
+    ) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u16(
+        self,
+        v: u16,
+    ) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u32(
+        self,
+        v: u32,
+    ) -> Result<()> {
+        self.serialize_u64(u64::from(v))
+    }
+
+    fn serialize_u64(
+        self,
+        v: u64,
+    ) -> Result<()> {
+        self.write_display(v);
+        Ok(())
+    }
+
+    fn serialize_u128(
+        self,
+        v: u128,
+    ) -> Result<()> {
+        self.write_display(v);
+        Ok(())
+    }
+
+    fn serialize_f32(
+        self,
+        v: f32,
+    ) -> Result<()> {
+        if !cfg!(feature = "float") {
+            return Err(Error::FloatUnsupported);
+        }
+        self.write_float(v, v.is_finite());
+        Ok(())
+    }
+
+    fn serialize_f64(
+        self,
+        v: f64,
+    ) -> Result<()> {
+        if !cfg!(feature = "float") {
+            return Err(Error::FloatUnsupported);
+        }
+        self.write_float(v, v.is_finite());
+        Ok(())
+    }
+
+    fn serialize_char(
+        self,
+        v: char,
+    ) -> Result<()> {
+        self.write_str(v.encode_utf8(&mut [0; 4]));
+        Ok(())
+    }
+
+    fn serialize_str(
+        self,
+        v: &str,
+    ) -> Result<()> {
+        self.write_str(v);
+        Ok(())
+    }
+
+    fn serialize_bytes(
+        self,
+        v: &[u8],
+    ) -> Result<()> {
+        let mut seq = serde::Serializer::serialize_seq(self, Some(v.len()))?;
+        for byte in v {
+            serde::ser::SerializeSeq::serialize_element(&mut seq, byte)?;
+        }
+        serde::ser::SerializeSeq::end(seq)
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_some<T>(
+        self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.out.extend_from_slice(b"null");
+        Ok(())
+    }
+
+    fn serialize_unit_struct(
+        self,
+        _name: &'static str,
+    ) -> Result<()> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.write_str(variant);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        if name == REDACTED_NAME {
+            self.write_str(REDACTED_TEXT);
+            Ok(())
+        } else {
+            value.serialize(self)
+        }
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.out.push(b'{');
+        self.write_str(variant);
+        self.out.push(b':');
+        value.serialize(&mut *self)?;
+        self.out.push(b'}');
+        Ok(())
+    }
+
+    fn serialize_seq(
+        self,
+        _len: Option<usize>,
+    ) -> Result<Self::SerializeSeq> {
+        self.out.push(b'[');
+        Ok(Compound::new(self, b"]"))
+    }
+
+    fn serialize_tuple(
+        self,
+        len: usize,
+    ) -> Result<Self::SerializeTuple> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        self.out.push(b'{');
+        self.write_str(variant);
+        self.out.extend_from_slice(b":[");
+        Ok(Compound::new(self, b"]}"))
+    }
+
+    fn serialize_map(
+        self,
+        _len: Option<usize>,
+    ) -> Result<Self::SerializeMap> {
+        self.out.push(b'{');
+        Ok(Compound::new(self, b"}"))
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        self.out.push(b'{');
+        self.write_str(variant);
+        self.out.extend_from_slice(b":{");
+        Ok(Compound::new(self, b"}}"))
+    }
+}
+
+struct Compound<'a, 'b> {
+    ser: &'b mut Serializer<'a>,
+    first: bool,
+    close: &'static [u8],
+}
+
+impl<'a, 'b> Compound<'a, 'b> {
+    fn new(
+        ser: &'b mut Serializer<'a>,
+        close: &'static [u8],
+    ) -> Self {
+        Self {
+            ser,
+            first: true,
+            close,
+        }
+    }
+
+    fn element<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.separate();
+        value.serialize(&mut *self.ser)
+    }
+
+    fn finish(self) {
+        self.ser.out.extend_from_slice(self.close);
+    }
+
+    fn field<T>(
+        &mut self,
+        key: &str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.separate();
+        self.ser.write_str(key);
+        self.ser.out.push(b':');
+        value.serialize(&mut *self.ser)
+    }
+
+    fn separate(&mut self) {
+        if self.first {
+            self.first = false;
+        } else {
+            self.ser.out.push(b',');
+        }
+    }
+}
+
+impl serde::ser::SerializeSeq for Compound<'_, '_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_element<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeTuple for Compound<'_, '_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_element<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeTupleStruct for Compound<'_, '_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_field<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeTupleVariant for Compound<'_, '_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_field<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeMap for Compound<'_, '_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_key<T>(
+        &mut self,
+        key: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.separate();
+        key.serialize(KeySerializer {
+            ser: &mut *self.ser,
+        })?;
+        self.ser.out.push(b':');
+        Ok(())
+    }
+
+    fn serialize_value<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        value.serialize(&mut *self.ser)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeStruct for Compound<'_, '_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_field<T>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish();
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeStructVariant for Compound<'_, '_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_field<T>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.finish();
+        Ok(())
+    }
+}
+
+// Object keys in JSON are always strings, so this serializes map keys as
+// strings, quoting numbers and booleans.
+struct KeySerializer<'a, 'b> {
+    ser: &'b mut Serializer<'a>,
+}
+
+fn key_must_be_string() -> Error {
+    Error::Message(String::from("JSON object keys must be strings or numbers"))
+}
+
+macro_rules! serialize_key_display {
+    ($($serialize:ident: $type:ty),*) => {
+        $(
+            fn $serialize(
+                self,
+                v: $type,
+            ) -> Result<()> {
+                self.ser.write_str(&v.to_string());
+                Ok(())
+            }
+        )*
+    };
+}
+
+impl serde::Serializer for KeySerializer<'_, '_> {
+    type Error = Error;
+    type Ok = ();
+    type SerializeMap = serde::ser::Impossible<(), Error>;
+    type SerializeSeq = serde::ser::Impossible<(), Error>;
+    type SerializeStruct = serde::ser::Impossible<(), Error>;
+    type SerializeStructVariant = serde::ser::Impossible<(), Error>;
+    type SerializeTuple = serde::ser::Impossible<(), Error>;
+    type SerializeTupleStruct = serde::ser::Impossible<(), Error>;
+    type SerializeTupleVariant = serde::ser::Impossible<(), Error>;
+
+    serialize_key_display!(
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+        serialize_char: char,
+        serialize_str: &str
+    );
+
+    fn serialize_f32(
+        self,
+        _v: f32,
+    ) -> Result<()> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_f64(
+        self,
+        _v: f64,
+    ) -> Result<()> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_bytes(
+        self,
+        _v: &[u8],
+    ) -> Result<()> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_some<T>(
+        self,
+        _value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_unit_struct(
+        self,
+        _name: &'static str,
+    ) -> Result<()> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.ser.write_str(variant);
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_seq(
+        self,
+        _len: Option<usize>,
+    ) -> Result<Self::SerializeSeq> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_tuple(
+        self,
+        _len: usize,
+    ) -> Result<Self::SerializeTuple> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_map(
+        self,
+        _len: Option<usize>,
+    ) -> Result<Self::SerializeMap> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct> {
+        Err(key_must_be_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Err(key_must_be_string())
+    }
+}
+
+pub(crate) fn to_vec<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + serde::Serialize,
+{
+    let mut out = Vec::new();
+    value.serialize(&mut Serializer {
+        out: &mut out,
+    })?;
+    Ok(out)
+}
+
+// This is how deeply arrays and objects may be nested inside one another,
+// which keeps deeply nested input from overflowing the stack.
+const MAX_DEPTH: usize = 128;
+
+struct Deserializer<'de> {
+    input: &'de [u8],
+    offset: usize,
+    // This is how many more levels arrays and objects may be nested.
+    depth: usize,
+}
+
+#[derive(Clone, Copy)]
+enum Number {
+    Float(f64),
+    Negative(i64),
+    NegativeWide(i128),
+    Positive(u64),
+    PositiveWide(u128),
+}
+
+impl<'de> Deserializer<'de> {
+    fn error(&self) -> Error {
+        Error::JsonSyntax(self.offset)
+    }
+
+    fn expect(
+        &mut self,
+        byte: u8,
+    ) -> Result<()> {
+        if self.peek()? == byte {
+            self.offset += 1;
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn expect_literal(
+        &mut self,
+        literal: &[u8],
+    ) -> Result<()> {
+        self.peek()?;
+        if self.input[self.offset..].starts_with(literal) {
+            self.offset += literal.len();
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    // Decode an array or object, which starts at the current byte, using
+    // the given function, failing if it is nested too deeply.
+    fn nested<T, F>(
+        &mut self,
+        decode: F,
+    ) -> Result<T>
+    where
+        F: FnOnce(&mut Self) -> Result<T>,
+    {
+        self.depth = self.depth.checked_sub(1).ok_or_else(|| self.error())?;
+        self.offset += 1;
+        let result = decode(self);
+        self.depth += 1;
+        result
+    }
+
+    // Skip one or more decimal digits, which the grammar of JSON numbers
+    // requires after the sign, the decimal point and the exponent marker.
+    fn parse_digits(&mut self) -> Result<()> {
+        match self.input.get(self.offset) {
+            Some(b'0'..=b'9') => {},
+            Some(_) => return Err(self.error()),
+            None => return Err(Error::ValueTruncated),
+        }
+        while let Some(b'0'..=b'9') = self.input.get(self.offset) {
+            self.offset += 1;
+        }
+        Ok(())
+    }
+
+    fn parse_hex_escape(&mut self) -> Result<u16> {
+        let digits = self
+            .input
+            .get(self.offset..self.offset + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u16::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error())?;
+        self.offset += 4;
+        Ok(digits)
+    }
+
+    // Parse a number following the grammar of RFC 8259, which leaves out
+    // leading zeros and requires digits after the decimal point and the
+    // exponent marker.  Integers too large for 64 bits are kept as 128-bit
+    // integers, and only those too large for 128 bits become floats.
+    // Numbers too large even for a float are refused rather than taken to
+    // be infinite.
+    fn parse_number(&mut self) -> Result<Number> {
+        let start = self.offset;
+        let negative = self.input.get(self.offset) == Some(&b'-');
+        if negative {
+            self.offset += 1;
+        }
+        if self.input.get(self.offset) == Some(&b'0') {
+            self.offset += 1;
+        } else {
+            self.parse_digits()?;
+        }
+        let mut float = false;
+        if self.input.get(self.offset) == Some(&b'.') {
+            float = true;
+            self.offset += 1;
+            self.parse_digits()?;
+        }
+        if let Some(b'e' | b'E') = self.input.get(self.offset) {
+            float = true;
+            self.offset += 1;
+            if let Some(b'+' | b'-') = self.input.get(self.offset) {
+                self.offset += 1;
+            }
+            self.parse_digits()?;
+        }
+        let text = std::str::from_utf8(&self.input[start..self.offset])
+            .map_err(|_| self.error())?;
+        let integer = if float {
+            None
+        } else if negative {
+            text.parse()
+                .map(Number::Negative)
+                .or_else(|_| text.parse().map(Number::NegativeWide))
+                .ok()
+        } else {
+            text.parse()
+                .map(Number::Positive)
+                .or_else(|_| text.parse().map(Number::PositiveWide))
+                .ok()
+        };
+        match integer {
+            Some(integer) => Ok(integer),
+            None if cfg!(feature = "float") => {
+                let value: f64 =
+                    text.parse().map_err(|_| Error::JsonSyntax(start))?;
+                if value.is_finite() {
+                    Ok(Number::Float(value))
+                } else {
+                    Err(Error::JsonNumberOutOfRange(start))
+                }
+            },
+            None => Err(Error::FloatUnsupported),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<Cow<'de, str>> {
+        self.expect(b'"')?;
+        let start = self.offset;
+        let mut owned: Option<Vec<u8>> = None;
+        let mut run = start;
+        loop {
+            let byte =
+                *self.input.get(self.offset).ok_or_else(|| self.error())?;
+            match byte {
+                b'"' => {
+                    let end = self.offset;
+                    self.offset += 1;
+                    return match owned {
+                        None => std::str::from_utf8(&self.input[start..end])
+                            .map(Cow::Borrowed)
+                            .map_err(|source| Error::InvalidUtf8(Some(source))),
+                        Some(mut owned) => {
+                            owned.extend_from_slice(&self.input[run..end]);
+                            String::from_utf8(owned).map(Cow::Owned).map_err(
+                                |source| {
+                                    Error::InvalidUtf8(Some(
+                                        source.utf8_error(),
+                                    ))
+                                },
+                            )
+                        },
+                    };
+                },
+                b'\\' => {
+                    let owned = owned.get_or_insert_with(Vec::new);
+                    owned.extend_from_slice(&self.input[run..self.offset]);
+                    self.offset += 1;
+                    let escape = *self
+                        .input
+                        .get(self.offset)
+                        .ok_or_else(|| self.error())?;
+                    self.offset += 1;
+                    match escape {
+                        b'"' | b'\\' | b'/' => owned.push(escape),
+                        b'b' => owned.push(0x08),
+                        b'f' => owned.push(0x0C),
+                        b'n' => owned.push(b'\n'),
+                        b'r' => owned.push(b'\r'),
+                        b't' => owned.push(b'\t'),
+                        b'u' => {
+                            let mut code = u32::from(self.parse_hex_escape()?);
+                            if (0xD800..0xDC00).contains(&code) {
+                                self.expect_literal(b"\\u")?;
+                                let low = u32::from(self.parse_hex_escape()?);
+                                if !(0xDC00..0xE000).contains(&low) {
+                                    return Err(self.error());
+                                }
+                                code = 0x10000
+                                    + ((code - 0xD800) << 10)
+                                    + (low - 0xDC00);
+                            }
+                            let c = char::from_u32(code)
+                                .ok_or(Error::InvalidUtf8(None))?;
+                            owned.extend_from_slice(
+                                c.encode_utf8(&mut [0; 4]).as_bytes(),
+                            );
+                        },
+                        _ => return Err(Error::JsonSyntax(self.offset - 1)),
+                    }
+                    run = self.offset;
+                },
+                0x00..=0x1F => return Err(self.error()),
+                _ => self.offset += 1,
+            }
+        }
+    }
+
+    // Decode a number as a float no larger in magnitude than the given
+    // largest value of the float type.  The sign of `-0` is kept, though
+    // it is otherwise decoded as the integer zero.
+    fn deserialize_float<V>(
+        &mut self,
+        largest: f64,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        if !matches!(self.peek()?, b'-' | b'0'..=b'9') {
+            return serde::Deserializer::deserialize_any(self, visitor);
+        }
+        let start = self.offset;
+        match self.parse_number()? {
+            Number::Negative(0) => visitor.visit_f64(-0.0),
+            Number::Float(value) if value.abs() > largest => {
+                Err(Error::JsonNumberOutOfRange(start))
+            },
+            number => visit_number(number, visitor),
+        }
+    }
+
+    fn peek(&mut self) -> Result<u8> {
+        while let Some(byte) = self.input.get(self.offset) {
+            if !matches!(byte, b' ' | b'\t' | b'\n' | b'\r') {
+                return Ok(*byte);
+            }
+            self.offset += 1;
+        }
+        Err(Error::ValueTruncated)
+    }
+}
+
+fn visit_number<'de, V>(
+    number: Number,
+    visitor: V,
+) -> Result<V::Value>
+where
+    V: serde::de::Visitor<'de>,
+{
+    match number {
+        Number::Float(value) => visitor.visit_f64(value),
+        Number::Negative(value) => visitor.visit_i64(value),
+        Number::NegativeWide(value) => visitor.visit_i128(value),
+        Number::Positive(value) => visitor.visit_u64(value),
+        Number::PositiveWide(value) => visitor.visit_u128(value),
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for &mut Deserializer<'de> {
+    type Error = Error;
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 char str string
+        bytes byte_buf unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+
+    fn deserialize_f32<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.deserialize_float(f64::from(f32::MAX), visitor)
+    }
+
+    fn deserialize_f64<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.deserialize_float(f64::MAX, visitor)
+    }
+
+    fn deserialize_any<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.peek()? {
+            b'n' => {
+                self.expect_literal(b"null")?;
+                visitor.visit_unit()
+            },
+            b't' => {
+                self.expect_literal(b"true")?;
+                visitor.visit_bool(true)
+            },
+            b'f' => {
+                self.expect_literal(b"false")?;
+                visitor.visit_bool(false)
+            },
+            b'"' => match self.parse_string()? {
+                Cow::Borrowed(value) => visitor.visit_borrowed_str(value),
+                Cow::Owned(value) => visitor.visit_string(value),
+            },
+            b'[' => self.nested(|de| {
+                let value = visitor.visit_seq(Elements {
+                    de: &mut *de,
+                    first: true,
+                })?;
+                de.expect(b']')?;
+                Ok(value)
+            }),
+            b'{' => self.nested(|de| {
+                let value = visitor.visit_map(Elements {
+                    de: &mut *de,
+                    first: true,
+                })?;
+                de.expect(b'}')?;
+                Ok(value)
+            }),
+            b'-' | b'0'..=b'9' => visit_number(self.parse_number()?, visitor),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn deserialize_option<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        if self.peek()? == b'n' {
+            self.expect_literal(b"null")?;
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.expect_literal(b"null")?;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.peek()? {
+            b'"' => {
+                let variant = self.parse_string()?.into_owned();
+                visitor.visit_enum(variant.into_deserializer())
+            },
+            b'{' => self.nested(|de| {
+                let value = visitor.visit_enum(Variant {
+                    de: &mut *de,
+                })?;
+                de.expect(b'}')?;
+                Ok(value)
+            }),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn is_human_readable(&self) -> bool {
+        true
+    }
+}
+
+struct Elements<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+    first: bool,
+}
+
+impl Elements<'_, '_> {
+    fn has_next(
+        &mut self,
+        close: u8,
+    ) -> Result<bool> {
+        let next = self.de.peek()?;
+        if next == close {
+            return Ok(false);
+        }
+        if self.first {
+            self.first = false;
+        } else {
+            self.de.expect(b',')?;
+        }
+        Ok(true)
+    }
+}
+
+impl<'de> serde::de::SeqAccess<'de> for Elements<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        if self.has_next(b']')? {
+            seed.deserialize(&mut *self.de).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+impl<'de> serde::de::MapAccess<'de> for Elements<'_, 'de> {
+    type Error = Error;
+
+    fn next_key_seed<K>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>>
+    where
+        K: serde::de::DeserializeSeed<'de>,
+    {
+        if self.has_next(b'}')? {
+            let key = self.de.parse_string()?;
+            self.de.expect(b':')?;
+            seed.deserialize(KeyDeserializer {
+                key,
+            })
+            .map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn next_value_seed<V>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+struct Variant<'a, 'de> {
+    de: &'a mut Deserializer<'de>,
+}
+
+impl<'de> serde::de::EnumAccess<'de> for Variant<'_, 'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self)>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        let key = self.de.parse_string()?;
+        self.de.expect(b':')?;
+        let variant = seed.deserialize(KeyDeserializer {
+            key,
+        })?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> serde::de::VariantAccess<'de> for Variant<'_, 'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        serde::de::Deserialize::deserialize(&mut *self.de)
+    }
+
+    fn newtype_variant_seed<T>(
+        self,
+        seed: T,
+    ) -> Result<T::Value>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(&mut *self.de)
+    }
+
+    fn tuple_variant<V>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        serde::Deserializer::deserialize_any(&mut *self.de, visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        serde::Deserializer::deserialize_any(&mut *self.de, visitor)
+    }
+}
+
+// Object keys in JSON are always strings, so this deserializes map keys
+// from strings, parsing them if numbers or booleans are expected.
+struct KeyDeserializer<'de> {
+    key: Cow<'de, str>,
+}
+
+macro_rules! deserialize_key_parsed {
+    ($($deserialize:ident => $visit:ident),*) => {
+        $(
+            fn $deserialize<V>(
+                self,
+                visitor: V,
+            ) -> Result<V::Value>
+            where
+                V: serde::de::Visitor<'de>,
+            {
+                let value = self.key.parse().map_err(|_| {
+                    Error::Message(format!("invalid key \"{}\"", self.key))
+                })?;
+                visitor.$visit(value)
+            }
+        )*
+    };
+}
+
+impl<'de> serde::Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = Error;
+
+    serde::forward_to_deserialize_any! {
+        char str string bytes byte_buf option unit unit_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+
+    deserialize_key_parsed!(
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64
+    );
+
+    fn deserialize_any<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.key {
+            Cow::Borrowed(key) => visitor.visit_borrowed_str(key),
+            Cow::Owned(key) => visitor.visit_string(key),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        true
+    }
+}
+
+pub(crate) fn from_slice<'de, T>(input: &'de [u8]) -> Result<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    let mut deserializer = Deserializer {
+        input,
+        offset: 0,
+        depth: MAX_DEPTH,
+    };
+    let value = T::deserialize(&mut deserializer)?;
+    match deserializer.peek() {
+        Err(Error::ValueTruncated) => Ok(value),
+        _ => Err(deserializer.error()),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::non_ascii_literal)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[cfg(feature = "float")]
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Line(i32, i32),
+        Box {
+            width: u32,
+            height: u32,
+        },
+    }
+
+    #[cfg(feature = "float")]
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Scene {
+        name: String,
+        shapes: Vec<Shape>,
+        tags: BTreeMap<u16, Option<char>>,
+        unit: (),
+    }
+
+    #[test]
+    #[cfg(feature = "float")]
+    fn json_round_trip() {
+        let mut tags = BTreeMap::new();
+        tags.insert(7, Some('é'));
+        tags.insert(300, None);
+        let scene = Scene {
+            name: String::from("quote \" slash \\ tab \t bell \u{7}"),
+            shapes: vec![
+                Shape::Empty,
+                Shape::Circle(1.0),
+                Shape::Line(-1, 2),
+                Shape::Box {
+                    width: 3,
+                    height: 4,
+                },
+            ],
+            tags,
+            unit: (),
+        };
+        let json = to_vec(&scene);
+        assert!(json.is_ok());
+        let json = json.unwrap();
+        assert_eq!(
+            concat!(
+                r#"{"name":"quote \" slash \\ tab \t bell \u0007","#,
+                r#""shapes":["Empty",{"Circle":1.0},{"Line":[-1,2]},"#,
+                r#"{"Box":{"width":3,"height":4}}],"#,
+                r#""tags":{"7":"é","300":null},"unit":null}"#
+            ),
+            std::str::from_utf8(&json).unwrap()
+        );
+        let decoded: Result<Scene> = from_slice(&json);
+        assert!(decoded.is_ok());
+        assert_eq!(scene, decoded.unwrap());
+    }
+
+    #[test]
+    fn json_parsing() {
+        let decoded: Result<(String, Vec<i32>, bool)> = from_slice(
+            r#" [ "line\nfeed \u00e9 😀" , [1, -25, 3] , true ] "#.as_bytes(),
+        );
+        assert!(decoded.is_ok());
+        assert_eq!(
+            (String::from("line\nfeed é 😀"), vec![1, -25, 3], true),
+            decoded.unwrap()
+        );
+        assert!(matches!(
+            from_slice::<Vec<u8>>(b"[1, 2"),
+            Err(Error::ValueTruncated)
+        ));
+        assert!(matches!(
+            from_slice::<Vec<u8>>(b"[1; 2]"),
+            Err(Error::JsonSyntax(2))
+        ));
+        assert!(matches!(from_slice::<u8>(b"1 2"), Err(Error::JsonSyntax(2))));
+        assert!(from_slice::<u8>(b"256").is_err());
+    }
+
+    #[test]
+    fn json_number_grammar() {
+        assert!(matches!(from_slice::<u8>(b"0"), Ok(0)));
+        assert!(matches!(from_slice::<i8>(b"-0"), Ok(0)));
+        assert!(matches!(from_slice::<u8>(b"01"), Err(Error::JsonSyntax(1))));
+        assert!(matches!(from_slice::<i8>(b"-01"), Err(Error::JsonSyntax(2))));
+        assert!(matches!(
+            from_slice::<Vec<u8>>(b"[00]"),
+            Err(Error::JsonSyntax(2))
+        ));
+        assert!(matches!(from_slice::<i8>(b"-"), Err(Error::ValueTruncated)));
+        assert!(matches!(from_slice::<i8>(b"-a"), Err(Error::JsonSyntax(1))));
+        assert!(matches!(from_slice::<i8>(b"+1"), Err(Error::JsonSyntax(0))));
+        assert!(matches!(
+            from_slice::<Vec<u8>>(b"[1.]"),
+            Err(Error::JsonSyntax(3))
+        ));
+        assert!(matches!(
+            from_slice::<Vec<u8>>(b"[1e]"),
+            Err(Error::JsonSyntax(3))
+        ));
+        assert!(matches!(
+            from_slice::<Vec<u8>>(b"[1e+]"),
+            Err(Error::JsonSyntax(4))
+        ));
+        assert!(matches!(
+            from_slice::<Vec<u8>>(b"[.5]"),
+            Err(Error::JsonSyntax(1))
+        ));
+    }
+
+    #[test]
+    fn json_wide_integers() {
+        let values = (u128::MAX, i128::MIN, 1_u128 << 64);
+        let json = to_vec(&values);
+        assert!(json.is_ok());
+        let json = json.unwrap();
+        assert_eq!(
+            concat!(
+                "[340282366920938463463374607431768211455,",
+                "-170141183460469231731687303715884105728,",
+                "18446744073709551616]"
+            ),
+            std::str::from_utf8(&json).unwrap()
+        );
+        let decoded: Result<(u128, i128, u128)> = from_slice(&json);
+        assert!(decoded.is_ok());
+        assert_eq!(values, decoded.unwrap());
+        let decoded: Result<(i128, u128)> = from_slice(b"[-5, 7]");
+        assert!(decoded.is_ok());
+        assert_eq!((-5, 7), decoded.unwrap());
+        assert!(from_slice::<u64>(b"18446744073709551616").is_err());
+        let mut map = BTreeMap::new();
+        map.insert(u128::MAX, -1_i128);
+        let json = to_vec(&map).unwrap();
+        assert_eq!(
+            r#"{"340282366920938463463374607431768211455":-1}"#,
+            std::str::from_utf8(&json).unwrap()
+        );
+        let decoded: Result<BTreeMap<u128, i128>> = from_slice(&json);
+        assert!(decoded.is_ok());
+        assert_eq!(map, decoded.unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "float")]
+    fn json_float_parsing() {
+        let decoded: Result<Vec<f32>> = from_slice(b"[1, -2.5e1, 3]");
+        assert!(decoded.is_ok());
+        assert_eq!(vec![1.0, -25.0, 3.0], decoded.unwrap());
+
+        // Negative zero keeps its sign as a float, but not as an integer.
+        let decoded: Result<(f64, f32, f64, i8)> =
+            from_slice(b"[-0, -0, -0.0, -0]");
+        assert!(decoded.is_ok());
+        let (first, second, third, fourth) = decoded.unwrap();
+        assert!(first == 0.0 && first.is_sign_negative());
+        assert!(second == 0.0 && second.is_sign_negative());
+        assert!(third == 0.0 && third.is_sign_negative());
+        assert_eq!(0, fourth);
+        let decoded: Result<f64> = from_slice(b"0");
+        assert!(matches!(decoded, Ok(value) if value.is_sign_positive()));
+
+        // Numbers too large for the float type are refused, rather than
+        // taken to be infinite.
+        assert!(matches!(
+            from_slice::<f64>(b"1.5e400"),
+            Err(Error::JsonNumberOutOfRange(0))
+        ));
+        assert!(matches!(
+            from_slice::<Vec<f64>>(b"[-1.5e400]"),
+            Err(Error::JsonNumberOutOfRange(1))
+        ));
+        assert!(matches!(
+            from_slice::<crate::Value>(b"1e999"),
+            Err(Error::JsonNumberOutOfRange(0))
+        ));
+        assert!(matches!(
+            from_slice::<f32>(b"1e39"),
+            Err(Error::JsonNumberOutOfRange(0))
+        ));
+        let decoded: Result<(f32, f64)> = from_slice(b"[1e38, 1e308]");
+        assert!(decoded.is_ok());
+        assert_eq!((1e38, 1e308), decoded.unwrap());
+    }
+
+    #[test]
+    fn json_nesting_is_limited() {
+        let nested = |depth| {
+            let mut json = "[".repeat(depth);
+            json.push_str(&"]".repeat(depth));
+            json
+        };
+        assert!(from_slice::<serde::de::IgnoredAny>(
+            nested(MAX_DEPTH).as_bytes()
+        )
+        .is_ok());
+        assert!(matches!(
+            from_slice::<serde::de::IgnoredAny>(
+                nested(MAX_DEPTH + 1).as_bytes()
+            ),
+            Err(Error::JsonSyntax(MAX_DEPTH))
+        ));
+        assert!(matches!(
+            from_slice::<crate::Value>("[".repeat(1_000_000).as_bytes()),
+            Err(Error::JsonSyntax(MAX_DEPTH))
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "float")]
+    fn non_finite_floats_are_null() {
+        assert_eq!(
+            b"[null,null]".to_vec(),
+            to_vec(&[f64::NAN, f64::INFINITY]).unwrap()
+        );
+    }
+}
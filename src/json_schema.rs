@@ -0,0 +1,415 @@
+// This generates JSON Schema documents describing values as they appear
+// when encoded in JSON (see `Format::Json`), from the schemas of their
+// types.
+
+use super::{
+    json,
+    Field,
+    Schema,
+    VariantKind,
+};
+use std::collections::BTreeMap;
+
+// This is a JSON value, just enough to build JSON Schema documents and
+// encode them with the JSON serializer of this crate.
+enum Json {
+    Bool(bool),
+    Integer(i128),
+    Unsigned(u128),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(&'static str, Json)>),
+    Map(BTreeMap<String, Json>),
+}
+
+impl serde::Serialize for Json {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeMap;
+        match self {
+            Json::Bool(value) => serializer.serialize_bool(*value),
+            Json::Integer(value) => serializer.serialize_i128(*value),
+            Json::Unsigned(value) => serializer.serialize_u128(*value),
+            Json::String(value) => serializer.serialize_str(value),
+            Json::Array(values) => serializer.collect_seq(values),
+            Json::Object(entries) => {
+                let mut map = serializer.serialize_map(Some(entries.len()))?;
+                for (key, value) in entries {
+                    map.serialize_entry(key, value)?;
+                }
+                map.end()
+            },
+            Json::Map(entries) => serializer.collect_map(entries),
+        }
+    }
+}
+
+fn of_type(name: &str) -> Json {
+    Json::Object(vec![("type", Json::String(String::from(name)))])
+}
+
+fn integer(
+    min: i128,
+    max: i128,
+) -> Json {
+    bounded_integer(Json::Integer(min), Json::Integer(max))
+}
+
+// The maximum is given separately for `u128`, which can exceed any `i128`.
+fn bounded_integer(
+    min: Json,
+    max: Json,
+) -> Json {
+    Json::Object(vec![
+        ("type", Json::String(String::from("integer"))),
+        ("minimum", min),
+        ("maximum", max),
+    ])
+}
+
+fn array_of(items: Json) -> Json {
+    Json::Object(vec![
+        ("type", Json::String(String::from("array"))),
+        ("items", items),
+    ])
+}
+
+fn nullable(inner: Json) -> Json {
+    Json::Object(vec![("anyOf", Json::Array(vec![of_type("null"), inner]))])
+}
+
+fn single_entry(
+    name: &str,
+    value: Json,
+) -> Json {
+    let mut properties = BTreeMap::new();
+    properties.insert(String::from(name), value);
+    Json::Object(vec![
+        ("type", Json::String(String::from("object"))),
+        ("properties", Json::Map(properties)),
+        ("required", Json::Array(vec![Json::String(String::from(name))])),
+        ("additionalProperties", Json::Bool(false)),
+    ])
+}
+
+struct Generator {
+    definitions: BTreeMap<String, Json>,
+}
+
+impl Generator {
+    fn definition(
+        &mut self,
+        name: &'static str,
+        schema: &Schema,
+    ) -> Json {
+        if !self.definitions.contains_key(name) {
+            // Insert a placeholder first, so that recursive references to
+            // the type while generating its definition are not expanded.
+            self.definitions.insert(String::from(name), Json::Bool(true));
+            let definition = self.generate_unnamed(schema);
+            self.definitions.insert(String::from(name), definition);
+        }
+        reference(name)
+    }
+
+    fn fields(
+        &mut self,
+        fields: &[Field],
+    ) -> Json {
+        let properties = fields
+            .iter()
+            .map(|field| {
+                (String::from(field.name), self.generate(&field.schema))
+            })
+            .collect();
+        let required = fields
+            .iter()
+            .map(|field| Json::String(String::from(field.name)))
+            .collect();
+        Json::Object(vec![
+            ("type", Json::String(String::from("object"))),
+            ("properties", Json::Map(properties)),
+            ("required", Json::Array(required)),
+            ("additionalProperties", Json::Bool(false)),
+        ])
+    }
+
+    fn generate(
+        &mut self,
+        schema: &Schema,
+    ) -> Json {
+        match schema {
+            Schema::TupleStruct {
+                name,
+                ..
+            }
+            | Schema::Struct {
+                name,
+                ..
+            }
+            | Schema::Enum {
+                name,
+                ..
+            } => self.definition(name, schema),
+            Schema::Recursive(name) => reference(name),
+            _ => self.generate_unnamed(schema),
+        }
+    }
+
+    fn generate_unnamed(
+        &mut self,
+        schema: &Schema,
+    ) -> Json {
+        match schema {
+            Schema::Bool => of_type("boolean"),
+            Schema::I8 => integer(i8::MIN.into(), i8::MAX.into()),
+            Schema::I16 => integer(i16::MIN.into(), i16::MAX.into()),
+            Schema::I32 => integer(i32::MIN.into(), i32::MAX.into()),
+            Schema::I64 => integer(i64::MIN.into(), i64::MAX.into()),
+            Schema::I128 => integer(i128::MIN, i128::MAX),
+            Schema::U8 => integer(0, u8::MAX.into()),
+            Schema::U16 => integer(0, u16::MAX.into()),
+            Schema::U32 => integer(0, u32::MAX.into()),
+            Schema::U64 => integer(0, u64::MAX.into()),
+            Schema::U128 => {
+                bounded_integer(Json::Integer(0), Json::Unsigned(u128::MAX))
+            },
+            Schema::F32 | Schema::F64 => of_type("number"),
+            Schema::Char => Json::Object(vec![
+                ("type", Json::String(String::from("string"))),
+                ("minLength", Json::Integer(1)),
+                ("maxLength", Json::Integer(1)),
+            ]),
+            Schema::Str => of_type("string"),
+            Schema::Bytes => array_of(integer(0, u8::MAX.into())),
+            Schema::Option(inner) => nullable(self.generate(inner)),
+            Schema::Unit | Schema::UnitStruct(_) => of_type("null"),
+            Schema::NewtypeStruct {
+                inner,
+                ..
+            } => self.generate(inner),
+            Schema::Seq(element) => array_of(self.generate(element)),
+            Schema::Tuple(fields)
+            | Schema::Array(fields)
+            | Schema::TupleStruct {
+                fields,
+                ..
+            } => self.tuple(fields),
+            Schema::Map {
+                key,
+                value,
+            } => {
+                let mut entries = vec![
+                    ("type", Json::String(String::from("object"))),
+                    ("additionalProperties", self.generate(value)),
+                ];
+                if let Some(pattern) = key_pattern(key) {
+                    entries.push((
+                        "propertyNames",
+                        Json::Object(vec![(
+                            "pattern",
+                            Json::String(String::from(pattern)),
+                        )]),
+                    ));
+                }
+                Json::Object(entries)
+            },
+            Schema::Struct {
+                fields,
+                ..
+            } => self.fields(fields),
+            Schema::Enum {
+                variants,
+                ..
+            } => {
+                let variants = variants
+                    .iter()
+                    .map(|variant| match &variant.kind {
+                        VariantKind::Unit => Json::Object(vec![(
+                            "const",
+                            Json::String(String::from(variant.name)),
+                        )]),
+                        VariantKind::Newtype(inner) => {
+                            let inner = self.generate(inner);
+                            single_entry(variant.name, inner)
+                        },
+                        VariantKind::Tuple(fields) => {
+                            let fields = self.tuple(fields);
+                            single_entry(variant.name, fields)
+                        },
+                        VariantKind::Struct(fields) => {
+                            let fields = self.fields(fields);
+                            single_entry(variant.name, fields)
+                        },
+                    })
+                    .collect();
+                Json::Object(vec![("oneOf", Json::Array(variants))])
+            },
+            Schema::Recursive(name) => reference(name),
+        }
+    }
+
+    fn tuple(
+        &mut self,
+        fields: &[Schema],
+    ) -> Json {
+        let items = fields.iter().map(|field| self.generate(field)).collect();
+        Json::Object(vec![
+            ("type", Json::String(String::from("array"))),
+            ("prefixItems", Json::Array(items)),
+            ("items", Json::Bool(false)),
+            ("minItems", Json::Integer(fields.len() as i128)),
+        ])
+    }
+}
+
+fn reference(name: &str) -> Json {
+    Json::Object(vec![("$ref", Json::String(format!("#/$defs/{name}")))])
+}
+
+// Object keys in JSON are strings, so map keys which are not strings are
+// written as strings, and constrained by pattern.
+fn key_pattern(key: &Schema) -> Option<&'static str> {
+    match key {
+        Schema::I8
+        | Schema::I16
+        | Schema::I32
+        | Schema::I64
+        | Schema::I128 => Some("^-?[0-9]+$"),
+        Schema::U8
+        | Schema::U16
+        | Schema::U32
+        | Schema::U64
+        | Schema::U128 => Some("^[0-9]+$"),
+        Schema::Bool => Some("^(true|false)$"),
+        Schema::Char => Some("^.$"),
+        Schema::NewtypeStruct {
+            inner,
+            ..
+        } => key_pattern(inner),
+        _ => None,
+    }
+}
+
+pub(crate) fn json_schema(schema: &Schema) -> String {
+    let mut generator = Generator {
+        definitions: BTreeMap::new(),
+    };
+    let root = generator.generate(schema);
+    let mut document = vec![(
+        "$schema",
+        Json::String(String::from(
+            "https://json-schema.org/draft/2020-12/schema",
+        )),
+    )];
+    if let Some(name) = schema.name() {
+        document.push(("title", Json::String(String::from(name))));
+    }
+    if let Json::Object(entries) = root {
+        document.extend(entries);
+    }
+    if !generator.definitions.is_empty() {
+        document.push(("$defs", Json::Map(generator.definitions)));
+    }
+
+    // The document is made only of strings, numbers, and objects with
+    // string keys, all of which the JSON serializer accepts.
+    let bytes = json::to_vec(&Json::Object(document)).unwrap_or_default();
+    String::from_utf8(bytes).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f32),
+        Line(i8, i8),
+        Box {
+            width: u8,
+        },
+    }
+
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize)]
+    struct List {
+        value: char,
+        next: Option<Box<List>>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize)]
+    struct Drawing {
+        shapes: Vec<Shape>,
+        counts: HashMap<u16, bool>,
+        list: List,
+    }
+
+    #[test]
+    fn json_schema_document() {
+        let schema = Schema::of::<Drawing>().unwrap();
+        assert_eq!(
+            concat!(
+                r##"{"$schema":"https://json-schema.org/draft/2020-12/schema","##,
+                r##""title":"Drawing","$ref":"#/$defs/Drawing","$defs":{"##,
+                r##""Drawing":{"type":"object","properties":{"##,
+                r##""counts":{"type":"object","additionalProperties":{"type":"boolean"},"##,
+                r##""propertyNames":{"pattern":"^[0-9]+$"}},"##,
+                r##""list":{"$ref":"#/$defs/List"},"##,
+                r##""shapes":{"type":"array","items":{"$ref":"#/$defs/Shape"}}},"##,
+                r##""required":["shapes","counts","list"],"additionalProperties":false},"##,
+                r##""List":{"type":"object","properties":{"##,
+                r##""next":{"anyOf":[{"type":"null"},{"$ref":"#/$defs/List"}]},"##,
+                r##""value":{"type":"string","minLength":1,"maxLength":1}},"##,
+                r##""required":["value","next"],"additionalProperties":false},"##,
+                r##""Shape":{"oneOf":[{"const":"Empty"},"##,
+                r##"{"type":"object","properties":{"Circle":{"type":"number"}},"##,
+                r##""required":["Circle"],"additionalProperties":false},"##,
+                r##"{"type":"object","properties":{"Line":{"type":"array","prefixItems":["##,
+                r##"{"type":"integer","minimum":-128,"maximum":127},"##,
+                r##"{"type":"integer","minimum":-128,"maximum":127}],"##,
+                r##""items":false,"minItems":2}},"##,
+                r##""required":["Line"],"additionalProperties":false},"##,
+                r##"{"type":"object","properties":{"Box":{"type":"object","properties":{"##,
+                r##""width":{"type":"integer","minimum":0,"maximum":255}},"##,
+                r##""required":["width"],"additionalProperties":false}},"##,
+                r##""required":["Box"],"additionalProperties":false}]}}}"##,
+            ),
+            schema.to_json_schema()
+        );
+    }
+
+    #[test]
+    fn unnamed_types_are_inline() {
+        assert_eq!(
+            concat!(
+                r#"{"$schema":"https://json-schema.org/draft/2020-12/schema","#,
+                r#""type":"array","items":{"type":"integer","minimum":0,"#,
+                r#""maximum":18446744073709551615}}"#
+            ),
+            Schema::of::<Vec<u64>>().unwrap().to_json_schema()
+        );
+        assert_eq!(
+            concat!(
+                r#"{"$schema":"https://json-schema.org/draft/2020-12/schema","#,
+                r#""type":"array","prefixItems":["#,
+                r#"{"type":"integer","minimum":0,"#,
+                r#""maximum":340282366920938463463374607431768211455},"#,
+                r#"{"type":"integer","#,
+                r#""minimum":-170141183460469231731687303715884105728,"#,
+                r#""maximum":170141183460469231731687303715884105727}],"#,
+                r#""items":false,"minItems":2}"#
+            ),
+            Schema::of::<(u128, i128)>().unwrap().to_json_schema()
+        );
+    }
+}
@@ -0,0 +1,542 @@
+// This generates Kaitai Struct definitions (`.ksy` files) describing the
+// encodings of types, from their schemas, so that encoded values can be
+// inspected with Kaitai-based tools.
+
+use super::{
+    codegen::snake_case,
+    Field,
+    Schema,
+    VariantKind,
+};
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    fmt::Write,
+};
+
+// The largest number of groups in an encoded 64-bit integer.
+const MAX_VARINT_GROUPS: usize = 10;
+
+enum AttributeType {
+    Named(String),
+    Switch {
+        on: String,
+        cases: Vec<(usize, String)>,
+    },
+}
+
+struct Attribute {
+    id: String,
+    kind: AttributeType,
+    condition: Option<String>,
+    repeat: Option<String>,
+}
+
+impl Attribute {
+    fn new(
+        id: String,
+        kind: &str,
+    ) -> Self {
+        Self {
+            id,
+            kind: AttributeType::Named(String::from(kind)),
+            condition: None,
+            repeat: None,
+        }
+    }
+}
+
+// These are the types this generator relies on, defined only if used.
+const HELPERS: &[(&str, &str)] = &[
+    (
+        "bytes",
+        "    seq:\n      - id: len\n        type: varint\n      - id: data\n        \
+         size: len.value\n",
+    ),
+    (
+        "string",
+        "    seq:\n      - id: len\n        type: varint\n      - id: value\n        \
+         type: str\n        size: len.value\n        encoding: UTF-8\n",
+    ),
+    (
+        "utf8_char",
+        "    seq:\n      - id: lead\n        type: u1\n      - id: rest\n        \
+         size: 'lead >= 0xf0 ? 3 : lead >= 0xe0 ? 2 : lead >= 0xc0 ? 1 : 0'\n",
+    ),
+];
+
+struct Generator {
+    types: BTreeMap<String, Vec<Attribute>>,
+    helpers: BTreeSet<&'static str>,
+}
+
+impl Generator {
+    // Return the attributes which encode a value with the given schema, with
+    // the given identifier.
+    fn attributes(
+        &mut self,
+        id: &str,
+        schema: &Schema,
+    ) -> Vec<Attribute> {
+        match schema {
+            Schema::Unit | Schema::UnitStruct(_) => vec![],
+            Schema::Tuple(fields) | Schema::Array(fields) => fields
+                .iter()
+                .enumerate()
+                .flat_map(|(index, field)| {
+                    self.attributes(&format!("{id}_{index}"), field)
+                })
+                .collect(),
+            Schema::Option(inner) => {
+                let kind = self.type_of(inner);
+                vec![Attribute::new(format!("{id}_present"), "u1"), Attribute {
+                    condition: Some(format!("{id}_present == 1")),
+                    ..Attribute::new(String::from(id), &kind)
+                }]
+            },
+            Schema::Seq(element) => {
+                let kind = self.type_of(element);
+                vec![
+                    Attribute::new(format!("{id}_count"), "varint"),
+                    Attribute {
+                        repeat: Some(format!("{id}_count.value")),
+                        ..Attribute::new(String::from(id), &kind)
+                    },
+                ]
+            },
+            Schema::Map {
+                key,
+                value,
+            } => {
+                let name = format!("entry_{}_{}", ident(key), ident(value));
+                let kind = self.define(name, |generator| {
+                    let mut attributes = generator.attributes("key", key);
+                    attributes.extend(generator.attributes("value", value));
+                    attributes
+                });
+                vec![
+                    Attribute::new(format!("{id}_count"), "varint"),
+                    Attribute {
+                        repeat: Some(format!("{id}_count.value")),
+                        ..Attribute::new(String::from(id), &kind)
+                    },
+                ]
+            },
+            _ => vec![Attribute::new(String::from(id), &self.type_of(schema))],
+        }
+    }
+
+    fn define<F>(
+        &mut self,
+        name: String,
+        attributes: F,
+    ) -> String
+    where
+        F: FnOnce(&mut Self) -> Vec<Attribute>,
+    {
+        if !self.types.contains_key(&name) {
+            // Insert a placeholder first, so that recursive references to
+            // the type while generating its definition are not expanded.
+            self.types.insert(name.clone(), vec![]);
+            let attributes = attributes(self);
+            self.types.insert(name.clone(), attributes);
+        }
+        name
+    }
+
+    fn fields(
+        &mut self,
+        fields: &[Field],
+    ) -> Vec<Attribute> {
+        fields
+            .iter()
+            .flat_map(|field| {
+                self.attributes(&snake_case(field.name), &field.schema)
+            })
+            .collect()
+    }
+
+    fn helper(
+        &mut self,
+        name: &'static str,
+    ) -> String {
+        self.helpers.insert(name);
+        String::from(name)
+    }
+
+    // Return the name of the Kaitai type which encodes a value with the
+    // given schema, defining it if necessary.
+    fn type_of(
+        &mut self,
+        schema: &Schema,
+    ) -> String {
+        match schema {
+            Schema::Bool | Schema::U8 => String::from("u1"),
+            Schema::I8 => String::from("s1"),
+            Schema::I16 | Schema::I32 | Schema::I64 | Schema::I128 => {
+                String::from("signed_varint")
+            },
+            Schema::U16 | Schema::U32 | Schema::U64 | Schema::U128 => {
+                String::from("varint")
+            },
+            Schema::F32 => String::from("f4"),
+            Schema::F64 => String::from("f8"),
+            Schema::Char => self.helper("utf8_char"),
+            Schema::Str => self.helper("string"),
+            Schema::Bytes => self.helper("bytes"),
+            Schema::NewtypeStruct {
+                name,
+                inner,
+            } => self.define(snake_case(name), |generator| {
+                generator.attributes("value", inner)
+            }),
+            Schema::TupleStruct {
+                name,
+                fields,
+            } => self.define(snake_case(name), |generator| {
+                generator.attributes("field", &Schema::Tuple(fields.clone()))
+            }),
+            Schema::Struct {
+                name,
+                fields,
+            } => self
+                .define(snake_case(name), |generator| generator.fields(fields)),
+            Schema::Enum {
+                name,
+                variants,
+            } => self.define(snake_case(name), |generator| {
+                let mut cases = Vec::new();
+                for (index, variant) in variants.iter().enumerate() {
+                    let attributes = match &variant.kind {
+                        VariantKind::Unit => continue,
+                        VariantKind::Newtype(inner) => {
+                            generator.attributes("value", inner)
+                        },
+                        VariantKind::Tuple(fields) => generator.attributes(
+                            "field",
+                            &Schema::Tuple(fields.clone()),
+                        ),
+                        VariantKind::Struct(fields) => generator.fields(fields),
+                    };
+                    let kind = format!(
+                        "{}_{}",
+                        snake_case(name),
+                        snake_case(variant.name)
+                    );
+                    generator.types.insert(kind.clone(), attributes);
+                    cases.push((index, kind));
+                }
+                let mut attributes =
+                    vec![Attribute::new(String::from("index"), "varint")];
+                if !cases.is_empty() {
+                    attributes.push(Attribute {
+                        id: String::from("body"),
+                        kind: AttributeType::Switch {
+                            on: String::from("index.value"),
+                            cases,
+                        },
+                        condition: None,
+                        repeat: None,
+                    });
+                }
+                attributes
+            }),
+            Schema::Recursive(name) => snake_case(name),
+            _ => {
+                let kind = ident(schema);
+                self.define(kind, |generator| {
+                    generator.attributes("value", schema)
+                })
+            },
+        }
+    }
+}
+
+// Return an identifier naming the given schema, used to name the types
+// generated for unnamed composite types.
+fn ident(schema: &Schema) -> String {
+    match schema {
+        Schema::Bool => String::from("bool"),
+        Schema::I8 => String::from("i8"),
+        Schema::I16 => String::from("i16"),
+        Schema::I32 => String::from("i32"),
+        Schema::I64 => String::from("i64"),
+        Schema::I128 => String::from("i128"),
+        Schema::U8 => String::from("u8"),
+        Schema::U16 => String::from("u16"),
+        Schema::U32 => String::from("u32"),
+        Schema::U64 => String::from("u64"),
+        Schema::U128 => String::from("u128"),
+        Schema::F32 => String::from("f32"),
+        Schema::F64 => String::from("f64"),
+        Schema::Char => String::from("char"),
+        Schema::Str => String::from("string"),
+        Schema::Bytes => String::from("bytes"),
+        Schema::Option(inner) => format!("option_{}", ident(inner)),
+        Schema::Unit => String::from("unit"),
+        Schema::Seq(element) => format!("seq_{}", ident(element)),
+        Schema::Tuple(fields) => {
+            let mut ident = String::from("tuple");
+            for field in fields {
+                ident.push('_');
+                ident.push_str(&self::ident(field));
+            }
+            ident
+        },
+        Schema::Array(elements) => match elements.first() {
+            Some(element) => {
+                format!("array_{}_{}", elements.len(), ident(element))
+            },
+            None => String::from("array_0"),
+        },
+        Schema::Map {
+            key,
+            value,
+        } => format!("map_{}_{}", ident(key), ident(value)),
+        Schema::UnitStruct(name)
+        | Schema::NewtypeStruct {
+            name,
+            ..
+        }
+        | Schema::TupleStruct {
+            name,
+            ..
+        }
+        | Schema::Struct {
+            name,
+            ..
+        }
+        | Schema::Enum {
+            name,
+            ..
+        }
+        | Schema::Recursive(name) => snake_case(name),
+    }
+}
+
+// Return the expression for the value of a variable-length integer, given
+// the mask for the bits in its first group.
+fn varint_value(first_mask: &str) -> String {
+    let mut terms = Vec::new();
+    for group in 0..MAX_VARINT_GROUPS {
+        let index = if group == 0 {
+            String::from("groups.size - 1")
+        } else {
+            format!("groups.size - {}", group + 1)
+        };
+        let mask = format!("({index} == 0 ? {first_mask} : 0x7f)");
+        terms.push(format!(
+            "(groups.size > {group} ? (groups[{index}] & {mask}) << {} : 0)",
+            7 * group
+        ));
+    }
+    terms.join(" + ")
+}
+
+fn write_varint_types(out: &mut String) {
+    let groups =
+        "    seq:\n      - id: groups\n        type: u1\n        repeat: \
+                  until\n        repeat-until: '(_ & 0x80) == 0'\n";
+    let _ = write!(
+        out,
+        "  varint:\n{groups}    instances:\n      value:\n        value: '{}'\n",
+        varint_value("0x7f")
+    );
+    let _ = write!(
+        out,
+        "  signed_varint:\n{groups}    instances:\n      magnitude:\n        \
+         value: '{}'\n      value:\n        value: '(groups[0] & 0x40) != 0 ? \
+         -magnitude : magnitude'\n",
+        varint_value("0x3f")
+    );
+}
+
+fn write_attributes(
+    out: &mut String,
+    indent: &str,
+    attributes: &[Attribute],
+) {
+    if attributes.is_empty() {
+        let _ = writeln!(out, "{indent}seq: []");
+        return;
+    }
+    let _ = writeln!(out, "{indent}seq:");
+    for attribute in attributes {
+        let _ = writeln!(out, "{indent}  - id: {}", attribute.id);
+        match &attribute.kind {
+            AttributeType::Named(kind) => {
+                let _ = writeln!(out, "{indent}    type: {kind}");
+            },
+            AttributeType::Switch {
+                on,
+                cases,
+            } => {
+                let _ = writeln!(out, "{indent}    type:");
+                let _ = writeln!(out, "{indent}      switch-on: {on}");
+                let _ = writeln!(out, "{indent}      cases:");
+                for (index, kind) in cases {
+                    let _ = writeln!(out, "{indent}        {index}: {kind}");
+                }
+            },
+        }
+        if let Some(condition) = &attribute.condition {
+            let _ = writeln!(out, "{indent}    if: '{condition}'");
+        }
+        if let Some(repeat) = &attribute.repeat {
+            let _ = writeln!(out, "{indent}    repeat: expr");
+            let _ = writeln!(out, "{indent}    repeat-expr: '{repeat}'");
+        }
+    }
+}
+
+pub(crate) fn kaitai_struct(schema: &Schema) -> String {
+    let mut generator = Generator {
+        types: BTreeMap::new(),
+        helpers: BTreeSet::new(),
+    };
+    let root = generator.attributes("value", schema);
+    let id = schema.name().map_or_else(|| String::from("value"), snake_case);
+    let mut out = format!("meta:\n  id: {id}\n  endian: be\n");
+    write_attributes(&mut out, "", &root);
+    out.push_str("types:\n");
+    for (name, attributes) in &generator.types {
+        let _ = writeln!(out, "  {name}:");
+        write_attributes(&mut out, "    ", attributes);
+    }
+    for (name, definition) in HELPERS {
+        if generator.helpers.contains(name) {
+            let _ = write!(out, "  {name}:\n{definition}");
+        }
+    }
+    write_varint_types(&mut out);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f32),
+        Polygon {
+            points: Vec<(i16, i16)>,
+        },
+    }
+
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize)]
+    struct Drawing {
+        title: Option<String>,
+        shapes: Vec<Shape>,
+    }
+
+    #[test]
+    fn kaitai_definition() {
+        let ksy = Schema::of::<Drawing>().unwrap().to_kaitai_struct();
+        let (head, tail) = ksy.split_at(ksy.find("  varint:\n").unwrap());
+        assert_eq!(
+            concat!(
+                "meta:\n",
+                "  id: drawing\n",
+                "  endian: be\n",
+                "seq:\n",
+                "  - id: value\n",
+                "    type: drawing\n",
+                "types:\n",
+                "  drawing:\n",
+                "    seq:\n",
+                "      - id: title_present\n",
+                "        type: u1\n",
+                "      - id: title\n",
+                "        type: string\n",
+                "        if: 'title_present == 1'\n",
+                "      - id: shapes_count\n",
+                "        type: varint\n",
+                "      - id: shapes\n",
+                "        type: shape\n",
+                "        repeat: expr\n",
+                "        repeat-expr: 'shapes_count.value'\n",
+                "  shape:\n",
+                "    seq:\n",
+                "      - id: index\n",
+                "        type: varint\n",
+                "      - id: body\n",
+                "        type:\n",
+                "          switch-on: index.value\n",
+                "          cases:\n",
+                "            1: shape_circle\n",
+                "            2: shape_polygon\n",
+                "  shape_circle:\n",
+                "    seq:\n",
+                "      - id: value\n",
+                "        type: f4\n",
+                "  shape_polygon:\n",
+                "    seq:\n",
+                "      - id: points_count\n",
+                "        type: varint\n",
+                "      - id: points\n",
+                "        type: tuple_i16_i16\n",
+                "        repeat: expr\n",
+                "        repeat-expr: 'points_count.value'\n",
+                "  tuple_i16_i16:\n",
+                "    seq:\n",
+                "      - id: value_0\n",
+                "        type: signed_varint\n",
+                "      - id: value_1\n",
+                "        type: signed_varint\n",
+                "  string:\n",
+                "    seq:\n",
+                "      - id: len\n",
+                "        type: varint\n",
+                "      - id: value\n",
+                "        type: str\n",
+                "        size: len.value\n",
+                "        encoding: UTF-8\n",
+            ),
+            head
+        );
+        assert!(tail.starts_with(
+            "  varint:\n    seq:\n      - id: groups\n        type: u1\n"
+        ));
+        assert!(tail.contains("  signed_varint:\n"));
+        assert!(tail.contains(
+            "(groups.size > 1 ? (groups[groups.size - 2] & (groups.size - 2 \
+             == 0 ? 0x3f : 0x7f)) << 7 : 0)"
+        ));
+    }
+
+    #[test]
+    fn unnamed_root() {
+        let ksy = Schema::of::<(u8, char)>().unwrap().to_kaitai_struct();
+        assert!(ksy.starts_with(concat!(
+            "meta:\n",
+            "  id: value\n",
+            "  endian: be\n",
+            "seq:\n",
+            "  - id: value_0\n",
+            "    type: u1\n",
+            "  - id: value_1\n",
+            "    type: utf8_char\n",
+            "types:\n",
+            "  utf8_char:\n",
+        )));
+    }
+
+    #[test]
+    fn wide_integers() {
+        let ksy = Schema::of::<Vec<(u128, i128)>>().unwrap().to_kaitai_struct();
+        assert!(ksy.contains(concat!(
+            "  tuple_u128_i128:\n",
+            "    seq:\n",
+            "      - id: value_0\n",
+            "        type: varint\n",
+            "      - id: value_1\n",
+            "        type: signed_varint\n",
+        )));
+    }
+}
@@ -0,0 +1,704 @@
+use super::{
+    Error,
+    Result,
+};
+
+// Strings and byte sequences are terminated by this pair of bytes.  Any zero
+// byte in the content itself is escaped by following it with `ESCAPE`, which
+// keeps the encoding free of prefixes and sorts shorter content first.
+const TERMINATOR: [u8; 2] = [0x00, 0x01];
+const ESCAPE: u8 = 0xFF;
+
+// Because the encoding of every value is free of prefixes, complementing
+// every bit of a component's encoding exactly reverses its ordering while
+// keeping the terminators unambiguous, which is how descending components
+// are encoded.
+
+// Elements of sequences and entries of maps are each preceded by
+// `ELEMENT`, and the container is closed by `END`, so that a shorter
+// container sorts before a longer one sharing the same prefix.
+const ELEMENT: u8 = 0x01;
+const END: u8 = 0x00;
+
+/// This type implements [`serde::Serializer`] in order to encode data
+/// into a sequence of bytes whose lexicographic ordering matches the
+/// ordering of the values encoded.  Unlike the regular encoding, integers
+/// have fixed widths and are big-endian, signed values and floating-point
+/// numbers are transformed so that negative values sort first, and
+/// variable-length values are terminated rather than length-prefixed.
+///
+/// [`serde::Serializer`]:
+/// https://docs.rs/serde/1.0/serde/trait.Serializer.html
+struct KeySerializer<'ser> {
+    buffer: &'ser mut Vec<u8>,
+}
+
+impl KeySerializer<'_> {
+    fn serialize_terminated(
+        &mut self,
+        v: &[u8],
+    ) {
+        for byte in v {
+            self.buffer.push(*byte);
+            if *byte == 0x00 {
+                self.buffer.push(ESCAPE);
+            }
+        }
+        self.buffer.extend(&TERMINATOR);
+    }
+}
+
+impl serde::Serializer for &mut KeySerializer<'_> {
+    type Error = Error;
+    type Ok = ();
+    type SerializeMap = Self;
+    type SerializeSeq = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+
+    fn serialize_bool(
+        self,
+        v: bool,
+    ) -> Result<Self::Ok> {
+        self.buffer.push(u8::from(v));
+        Ok(())
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn serialize_i8(
+        self,
+        v: i8,
+    ) -> Result<Self::Ok> {
+        self.serialize_u8((v as u8) ^ 0x80)
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn serialize_i16(
+        self,
+        v: i16,
+    ) -> Result<Self::Ok> {
+        self.serialize_u16((v as u16) ^ 0x8000)
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn serialize_i32(
+        self,
+        v: i32,
+    ) -> Result<Self::Ok> {
+        self.serialize_u32((v as u32) ^ 0x8000_0000)
+    }
+
+    #[allow(clippy::cast_sign_loss)]
+    fn serialize_i64(
+        self,
+        v: i64,
+    ) -> Result<Self::Ok> {
+        self.serialize_u64((v as u64) ^ 0x8000_0000_0000_0000)
+    }
+
+    fn serialize_u8(
+        self,
+        v: u8,
+    ) -> Result<Self::Ok> {
+        self.buffer.push(v);
+        Ok(())
+    }
+
+    fn serialize_u16(
+        self,
+        v: u16,
+    ) -> Result<Self::Ok> {
+        self.buffer.extend(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u32(
+        self,
+        v: u32,
+    ) -> Result<Self::Ok> {
+        self.buffer.extend(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_u64(
+        self,
+        v: u64,
+    ) -> Result<Self::Ok> {
+        self.buffer.extend(&v.to_be_bytes());
+        Ok(())
+    }
+
+    fn serialize_f32(
+        self,
+        v: f32,
+    ) -> Result<Self::Ok> {
+        let bits = v.to_bits();
+        self.serialize_u32(if bits & 0x8000_0000 == 0 {
+            bits | 0x8000_0000
+        } else {
+            !bits
+        })
+    }
+
+    fn serialize_f64(
+        self,
+        v: f64,
+    ) -> Result<Self::Ok> {
+        let bits = v.to_bits();
+        self.serialize_u64(if bits & 0x8000_0000_0000_0000 == 0 {
+            bits | 0x8000_0000_0000_0000
+        } else {
+            !bits
+        })
+    }
+
+    fn serialize_char(
+        self,
+        v: char,
+    ) -> Result<Self::Ok> {
+        self.serialize_u32(u32::from(v))
+    }
+
+    fn serialize_str(
+        self,
+        v: &str,
+    ) -> Result<Self::Ok> {
+        self.serialize_terminated(v.as_bytes());
+        Ok(())
+    }
+
+    fn serialize_bytes(
+        self,
+        v: &[u8],
+    ) -> Result<Self::Ok> {
+        self.serialize_terminated(v);
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok> {
+        self.buffer.push(0x00);
+        Ok(())
+    }
+
+    fn serialize_some<T>(
+        self,
+        value: &T,
+    ) -> Result<Self::Ok>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.buffer.push(0x01);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok> {
+        Ok(())
+    }
+
+    fn serialize_unit_struct(
+        self,
+        _name: &'static str,
+    ) -> Result<Self::Ok> {
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<Self::Ok> {
+        self.serialize_u32(variant_index)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.buffer.extend(&variant_index.to_be_bytes());
+        value.serialize(self)
+    }
+
+    fn serialize_seq(
+        self,
+        _len: Option<usize>,
+    ) -> Result<Self::SerializeSeq> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(
+        self,
+        _len: usize,
+    ) -> Result<Self::SerializeTuple> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        self.buffer.extend(&variant_index.to_be_bytes());
+        Ok(self)
+    }
+
+    fn serialize_map(
+        self,
+        _len: Option<usize>,
+    ) -> Result<Self::SerializeMap> {
+        Ok(self)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        self.buffer.extend(&variant_index.to_be_bytes());
+        Ok(self)
+    }
+}
+
+impl serde::ser::SerializeMap for &mut KeySerializer<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_key<T>(
+        &mut self,
+        key: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.buffer.push(ELEMENT);
+        key.serialize(&mut **self)
+    }
+
+    fn serialize_value<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.buffer.push(END);
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeSeq for &mut KeySerializer<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_element<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.buffer.push(ELEMENT);
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        self.buffer.push(END);
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeStruct for &mut KeySerializer<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_field<T>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeStructVariant for &mut KeySerializer<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_field<T>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeTuple for &mut KeySerializer<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_element<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeTupleStruct for &mut KeySerializer<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_field<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(())
+    }
+}
+
+impl serde::ser::SerializeTupleVariant for &mut KeySerializer<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_field<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        value.serialize(&mut **self)
+    }
+
+    fn end(self) -> Result<Self::Ok> {
+        Ok(())
+    }
+}
+
+/// This type is used to build composite keys, such as those used in
+/// databases based on log-structured merge trees or B-trees, out of several
+/// components.  The bytes of the resulting key sort in the same order as the
+/// tuple of the components pushed onto it.
+///
+/// Components pushed with [`push_descending`] sort in the reverse order,
+/// which allows "newest-first" range scans over keys without any sorting
+/// after the fact.
+///
+/// Any error encountered while encoding a component is held until
+/// [`finish`] is called, so that components can be pushed fluently.
+///
+/// [`finish`]: #method.finish
+/// [`push_descending`]: #method.push_descending
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::KeyBuilder;
+///
+/// let tenant_id = 42_u32;
+/// let earlier = KeyBuilder::new().push(&tenant_id).push(&-10_i64).finish();
+/// let later = KeyBuilder::new().push(&tenant_id).push(&3_i64).finish();
+/// assert!(earlier.unwrap() < later.unwrap());
+///
+/// let newest_first =
+///     KeyBuilder::new().push(&tenant_id).push_descending(&3_i64);
+/// let oldest_last =
+///     KeyBuilder::new().push(&tenant_id).push_descending(&-10_i64);
+/// assert!(newest_first.finish().unwrap() < oldest_last.finish().unwrap());
+/// ```
+#[derive(Debug, Default)]
+pub struct KeyBuilder {
+    key: Vec<u8>,
+    error: Option<Error>,
+}
+
+impl KeyBuilder {
+    /// Return the key built from all the components pushed onto the builder.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error encountered while encoding any component,
+    /// such as a custom error emitted by the component's `Serialize`
+    /// implementation.
+    pub fn finish(self) -> Result<Vec<u8>> {
+        self.error.map_or(Ok(self.key), Err)
+    }
+
+    /// Return a new builder for an empty key.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append the order-preserving encoding of the given component
+    /// to the key.
+    #[must_use]
+    pub fn push<T>(
+        mut self,
+        component: &T,
+    ) -> Self
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        if self.error.is_none() {
+            if let Err(error) = component.serialize(&mut KeySerializer {
+                buffer: &mut self.key,
+            }) {
+                self.error = Some(error);
+            }
+        }
+        self
+    }
+
+    /// Append the order-preserving encoding of the given component to the
+    /// key, with every bit complemented so that the component sorts in
+    /// descending order.
+    #[must_use]
+    pub fn push_descending<T>(
+        mut self,
+        component: &T,
+    ) -> Self
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        let start = self.key.len();
+        self = self.push(component);
+        if self.error.is_none() {
+            for byte in &mut self.key[start..] {
+                *byte = !*byte;
+            }
+        }
+        self
+    }
+}
+
+/// This function is used to encode a value into a sequence of bytes whose
+/// lexicographic ordering matches the ordering of the values encoded, which
+/// is suitable for use as a key in an ordered key-value store.
+///
+/// # Errors
+///
+/// As with any `serde` serializer, this function may return an error
+/// if the value being serialized is in a bad state.
+pub fn to_key<T>(value: &T) -> Result<Vec<u8>>
+where
+    T: ?Sized + serde::Serialize,
+{
+    KeyBuilder::new().push(value).finish()
+}
+
+#[cfg(test)]
+#[allow(clippy::non_ascii_literal)]
+mod tests {
+    use super::*;
+
+    fn assert_ordered<T>(values: &[T])
+    where
+        T: serde::Serialize + std::fmt::Debug,
+    {
+        let keys = values
+            .iter()
+            .map(|value| to_key(value).unwrap())
+            .collect::<Vec<_>>();
+        for (i, pair) in keys.windows(2).enumerate() {
+            assert!(
+                pair[0] < pair[1],
+                "{:?} should sort before {:?}",
+                values[i],
+                values[i + 1]
+            );
+        }
+    }
+
+    #[test]
+    fn integers() {
+        assert_ordered(&[0_u64, 127, 128, 16383, 16384, u64::MAX]);
+        assert_ordered(&[i64::MIN, -16384, -1, 0, 1, 127, 128, i64::MAX]);
+        assert_ordered(&[i8::MIN, -1, 0, i8::MAX]);
+        assert_eq!(
+            &[0x80, 0x00, 0x00, 0x2A][..],
+            to_key(&42_i32).unwrap().as_slice()
+        );
+    }
+
+    #[test]
+    fn floats() {
+        assert_ordered(&[
+            f64::NEG_INFINITY,
+            -1e10,
+            -1.5,
+            -0.0,
+            0.0,
+            1e-10,
+            1.5,
+            f64::INFINITY,
+        ]);
+        assert_ordered(&[-2.5_f32, -0.5, 0.5, 2.5]);
+    }
+
+    #[test]
+    fn strings() {
+        assert_ordered(&[
+            "", "\0", "\0\0", "\u{1}", "a", "a\0", "ab", "b", "€",
+        ]);
+        assert_eq!(
+            &[0x61, 0x00, 0xFF, 0x62, 0x00, 0x01][..],
+            to_key("a\0b").unwrap().as_slice()
+        );
+    }
+
+    #[test]
+    fn sequences_and_options() {
+        assert_ordered(&[vec![], vec![0_u8], vec![0, 0], vec![1]]);
+        assert_ordered(&[None, Some(0_u16), Some(1)]);
+    }
+
+    #[test]
+    fn composite_keys_sort_as_tuples() {
+        assert_ordered(&[
+            ("a", 2_u32),
+            ("a", 10),
+            ("a\0", 0),
+            ("ab", 0),
+            ("b", 0),
+        ]);
+        let keys = [("tenant", 1_i64), ("tenant", 2), ("tenanted", 0)]
+            .iter()
+            .map(|(tenant, timestamp)| {
+                KeyBuilder::new()
+                    .push(*tenant)
+                    .push(timestamp)
+                    .finish()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert!(keys[0] < keys[1]);
+        assert!(keys[1] < keys[2]);
+    }
+
+    #[test]
+    fn descending_components() {
+        let keys = [
+            ("a", "zebra", 1_u8),
+            ("a", "apple", 0),
+            ("a", "apple", 1),
+            ("a", "", 0),
+            ("b", "zebra", 0),
+        ]
+        .iter()
+        .map(|(prefix, name, suffix)| {
+            KeyBuilder::new()
+                .push(*prefix)
+                .push_descending(*name)
+                .push(suffix)
+                .finish()
+                .unwrap()
+        })
+        .collect::<Vec<_>>();
+        for pair in keys.windows(2) {
+            assert!(pair[0] < pair[1]);
+        }
+        let newest_first = [i64::MAX, 1000, 0, -1, i64::MIN]
+            .iter()
+            .map(|timestamp| {
+                KeyBuilder::new().push_descending(timestamp).finish().unwrap()
+            })
+            .collect::<Vec<_>>();
+        for pair in newest_first.windows(2) {
+            assert!(pair[0] < pair[1]);
+        }
+    }
+
+    #[test]
+    fn errors_are_deferred_until_finish() {
+        struct Broken;
+        impl serde::Serialize for Broken {
+            fn serialize<S>(
+                &self,
+                _serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                Err(serde::ser::Error::custom("broken"))
+            }
+        }
+        let key =
+            KeyBuilder::new().push(&1_u8).push(&Broken).push(&2_u8).finish();
+        assert!(matches!(key, Err(Error::Message(_))));
+    }
+}
@@ -0,0 +1,564 @@
+use super::{
+    from_bytes,
+    layout_fingerprint::fingerprint_start,
+    registry::Bytes,
+    to_bytes,
+    with,
+    Error,
+    LayoutFingerprint,
+    Result,
+};
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+};
+
+/// This is implemented around a key and an AEAD (authenticated encryption
+/// with associated data) cipher, such as AES-GCM or ChaCha20-Poly1305 from
+/// whichever cryptography library is at hand, for a [`Keyring`] to seal
+/// and open values with.
+///
+/// The associated data given to [`encrypt`] is not encrypted, but must be
+/// authenticated along with the ciphertext, so that [`decrypt`] fails
+/// unless it is given the same associated data.  A keyring passes the
+/// identifier of the key as the associated data, so that a sealed value
+/// cannot be relabeled as sealed by a different key.
+///
+/// [`Keyring`]: struct.Keyring.html
+/// [`encrypt`]: #tymethod.encrypt
+/// [`decrypt`]: #tymethod.decrypt
+pub trait Cipher {
+    /// Return the decryption of the given ciphertext, which was encrypted
+    /// with the given associated data.
+    ///
+    /// # Errors
+    ///
+    /// An error should be returned if the ciphertext cannot be decrypted,
+    /// such as when it or the associated data fails authentication.
+    fn decrypt(
+        &self,
+        ciphertext: &[u8],
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>>;
+
+    /// Return the encryption of the given plaintext, authenticating the
+    /// given associated data along with it.
+    ///
+    /// # Errors
+    ///
+    /// An error should be returned if the plaintext cannot be encrypted.
+    fn encrypt(
+        &self,
+        plaintext: &[u8],
+        associated_data: &[u8],
+    ) -> Result<Vec<u8>>;
+}
+
+/// This holds a set of keys, in the form of [`Cipher`]s, each with an
+/// identifier, one of which is the current key.  Values are sealed using
+/// the current key, and the identifier of the key is stored along with the
+/// ciphertext, so that values sealed under any key still held by the
+/// keyring can be opened.  This allows keys to be rotated, with new values
+/// sealed using the new key, while values sealed long ago, such as in
+/// archives, remain readable as long as their keys are kept.
+///
+/// A sealed value is encoded as the key identifier (as a `u32`) followed by
+/// the ciphertext (as bytes) of the encoding of the value.  The key
+/// identifier, as four big-endian bytes, is the associated data given to
+/// the cipher, so tampering with it causes opening the value to fail.
+///
+/// [`Cipher`]: trait.Cipher.html
+#[derive(Default)]
+pub struct Keyring {
+    ciphers: HashMap<u32, Box<dyn Cipher + Send + Sync>>,
+    current: Option<u32>,
+}
+
+impl Keyring {
+    /// Return the identifier of the key used to seal new values, if any.
+    #[must_use]
+    pub fn current_key_id(&self) -> Option<u32> {
+        self.current
+    }
+
+    /// Return the identifier of the key which was used to seal the given
+    /// value, without opening it.
+    ///
+    /// # Errors
+    ///
+    /// Any of the errors returned by [`from_bytes`] may be returned from
+    /// decoding the sealed value.
+    ///
+    /// [`from_bytes`]: fn.from_bytes.html
+    pub fn key_id_of(sealed: &[u8]) -> Result<u32> {
+        let (key_id, _): (u32, &[u8]) = from_bytes(sealed)?;
+        Ok(key_id)
+    }
+
+    /// Return a new keyring holding no keys.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// This function is used to decrypt and decode a value sealed by
+    /// [`seal`], using whichever key held by the keyring sealed it.
+    ///
+    /// [`seal`]: #method.seal
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`Error::UnknownKey`] if the keyring does not
+    /// hold the key which sealed the value.  Any errors returned by the
+    /// cipher are passed through, and any of the errors returned by
+    /// [`from_bytes`] may be returned from decoding the sealed value or the
+    /// decrypted value.
+    ///
+    /// [`Error::UnknownKey`]: enum.Error.html#variant.UnknownKey
+    /// [`from_bytes`]: fn.from_bytes.html
+    pub fn open<T>(
+        &self,
+        sealed: &[u8],
+    ) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let plaintext = self.open_bytes(sealed)?;
+        from_bytes(&plaintext)
+    }
+
+    fn open_bytes(
+        &self,
+        sealed: &[u8],
+    ) -> Result<Vec<u8>> {
+        let (key_id, ciphertext): (u32, &[u8]) = from_bytes(sealed)?;
+        self.ciphers
+            .get(&key_id)
+            .ok_or(Error::UnknownKey(key_id))?
+            .decrypt(ciphertext, &key_id.to_be_bytes())
+    }
+
+    /// This function is used to seal again, using the current key, a value
+    /// sealed by [`seal`] using any key held by the keyring, without
+    /// decoding it.  This is useful for migrating stored values to a new
+    /// key, so that an old key can be retired.
+    ///
+    /// [`seal`]: #method.seal
+    ///
+    /// # Errors
+    ///
+    /// Any of the errors returned by [`open`] or [`seal`] may be returned.
+    ///
+    /// [`open`]: #method.open
+    pub fn reseal(
+        &self,
+        sealed: &[u8],
+    ) -> Result<Vec<u8>> {
+        let plaintext = self.open_bytes(sealed)?;
+        self.seal_bytes(&plaintext)
+    }
+
+    /// This function is used to encode the given value and encrypt the
+    /// encoding using the current key.
+    ///
+    /// # Errors
+    ///
+    /// This function returns [`Error::NoCurrentKey`] if no key has been
+    /// made the current key.  Any errors returned by the cipher are passed
+    /// through, and any of the errors returned by [`to_bytes`] may be
+    /// returned from encoding the value.
+    ///
+    /// [`Error::NoCurrentKey`]: enum.Error.html#variant.NoCurrentKey
+    /// [`to_bytes`]: fn.to_bytes.html
+    pub fn seal<T>(
+        &self,
+        value: &T,
+    ) -> Result<Vec<u8>>
+    where
+        T: serde::Serialize,
+    {
+        let plaintext = to_bytes(value)?;
+        self.seal_bytes(&plaintext)
+    }
+
+    fn seal_bytes(
+        &self,
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>> {
+        let key_id = self.current.ok_or(Error::NoCurrentKey)?;
+        let ciphertext = self
+            .ciphers
+            .get(&key_id)
+            .ok_or(Error::UnknownKey(key_id))?
+            .encrypt(plaintext, &key_id.to_be_bytes())?;
+        to_bytes(&(key_id, Bytes(&ciphertext)))
+    }
+
+    /// Return the keyring with the given key added, and made the current
+    /// key, so that new values are sealed using it.  Any key previously
+    /// made current is kept, for opening values sealed using it.
+    #[must_use]
+    pub fn with_current_key<C>(
+        mut self,
+        key_id: u32,
+        cipher: C,
+    ) -> Self
+    where
+        C: Cipher + Send + Sync + 'static,
+    {
+        self.ciphers.insert(key_id, Box::new(cipher));
+        self.current = Some(key_id);
+        self
+    }
+
+    /// Return the keyring with the given key added, only for opening
+    /// values sealed using it.  If a key with the same identifier is
+    /// already held, it is replaced.
+    #[must_use]
+    pub fn with_key<C>(
+        mut self,
+        key_id: u32,
+        cipher: C,
+    ) -> Self
+    where
+        C: Cipher + Send + Sync + 'static,
+    {
+        self.ciphers.insert(key_id, Box::new(cipher));
+        self
+    }
+}
+
+/// This holds a value of type `T` sealed by a [`Keyring`], for use as a
+/// field of a record which is otherwise left in plaintext, so that
+/// searchable metadata and sensitive payloads can be kept in the same
+/// record.  The sealed value is encoded as a byte string, so records
+/// holding it can be decoded without the key, leaving only the sealed
+/// value to be opened by those holding the key.
+///
+/// [`Keyring`]: struct.Keyring.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// # extern crate serde;
+/// use serialization::{
+///     from_bytes,
+///     to_bytes,
+///     Cipher,
+///     Encrypted,
+///     Keyring,
+///     Result,
+/// };
+///
+/// # struct Reverse;
+/// # impl Cipher for Reverse {
+/// #     fn decrypt(&self, ciphertext: &[u8], _: &[u8]) -> Result<Vec<u8>> {
+/// #         Ok(ciphertext.iter().rev().copied().collect())
+/// #     }
+/// #     fn encrypt(&self, plaintext: &[u8], _: &[u8]) -> Result<Vec<u8>> {
+/// #         Ok(plaintext.iter().rev().copied().collect())
+/// #     }
+/// # }
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Patient {
+///     id: u32,
+///     notes: Encrypted<String>,
+/// }
+///
+/// let keyring = Keyring::new().with_current_key(1, Reverse);
+/// let patient = Patient {
+///     id: 7,
+///     notes: Encrypted::seal(&keyring, &String::from("allergic")).unwrap(),
+/// };
+/// let encoding = to_bytes(&patient).unwrap();
+///
+/// // The record can be decoded without the key...
+/// let patient: Patient = from_bytes(&encoding).unwrap();
+/// assert_eq!(7, patient.id);
+///
+/// // ...but the key is needed to open the sealed field.
+/// assert_eq!("allergic", patient.notes.open(&keyring).unwrap());
+/// assert!(patient.notes.open(&Keyring::new()).is_err());
+/// ```
+pub struct Encrypted<T> {
+    sealed: Vec<u8>,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T> Encrypted<T> {
+    /// Return the sealed value with the given encoding, as produced by
+    /// [`Keyring::seal`] or returned by [`sealed`].
+    ///
+    /// [`Keyring::seal`]: struct.Keyring.html#method.seal
+    /// [`sealed`]: #method.sealed
+    #[must_use]
+    pub fn from_sealed(sealed: Vec<u8>) -> Self {
+        Self {
+            sealed,
+            value: PhantomData,
+        }
+    }
+
+    /// Return the identifier of the key which sealed the value, without
+    /// opening it.
+    ///
+    /// # Errors
+    ///
+    /// Any error from [`Keyring::key_id_of`] is returned.
+    ///
+    /// [`Keyring::key_id_of`]: struct.Keyring.html#method.key_id_of
+    pub fn key_id(&self) -> Result<u32> {
+        Keyring::key_id_of(&self.sealed)
+    }
+
+    /// This function is used to decrypt and decode the value, using
+    /// whichever key held by the given keyring sealed it.
+    ///
+    /// # Errors
+    ///
+    /// Any error from [`Keyring::open`] is returned.
+    ///
+    /// [`Keyring::open`]: struct.Keyring.html#method.open
+    pub fn open(
+        &self,
+        keyring: &Keyring,
+    ) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        keyring.open(&self.sealed)
+    }
+
+    /// This function is used to seal the value again, using the current key
+    /// of the given keyring, without decoding it.
+    ///
+    /// # Errors
+    ///
+    /// Any error from [`Keyring::reseal`] is returned.
+    ///
+    /// [`Keyring::reseal`]: struct.Keyring.html#method.reseal
+    pub fn reseal(
+        &self,
+        keyring: &Keyring,
+    ) -> Result<Self> {
+        keyring.reseal(&self.sealed).map(Self::from_sealed)
+    }
+
+    /// This function is used to encode the given value and encrypt the
+    /// encoding using the current key of the given keyring.
+    ///
+    /// # Errors
+    ///
+    /// Any error from [`Keyring::seal`] is returned.
+    ///
+    /// [`Keyring::seal`]: struct.Keyring.html#method.seal
+    pub fn seal(
+        keyring: &Keyring,
+        value: &T,
+    ) -> Result<Self>
+    where
+        T: serde::Serialize,
+    {
+        keyring.seal(value).map(Self::from_sealed)
+    }
+
+    /// Return the encoding of the sealed value, which holds the identifier
+    /// of the key which sealed it and the ciphertext.
+    #[must_use]
+    pub fn sealed(&self) -> &[u8] {
+        &self.sealed
+    }
+}
+
+impl<T> Clone for Encrypted<T> {
+    fn clone(&self) -> Self {
+        Self::from_sealed(self.sealed.clone())
+    }
+}
+
+impl<T> std::fmt::Debug for Encrypted<T> {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        write!(f, "Encrypted({} bytes)", self.sealed.len())
+    }
+}
+
+impl<T> PartialEq for Encrypted<T> {
+    fn eq(
+        &self,
+        other: &Self,
+    ) -> bool {
+        self.sealed == other.sealed
+    }
+}
+
+impl<T> Eq for Encrypted<T> {}
+
+impl<T> serde::Serialize for Encrypted<T> {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        with::bytes::serialize(&self.sealed, serializer)
+    }
+}
+
+impl<'de, T> serde::Deserialize<'de> for Encrypted<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        with::bytes::deserialize(deserializer).map(Self::from_sealed)
+    }
+}
+
+// Sealed values are encoded as byte strings, whatever they hold.
+impl<T> LayoutFingerprint for Encrypted<T> {
+    const LAYOUT_FINGERPRINT: u64 = fingerprint_start(14);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // This stands in for a real cipher, scrambling bytes with a key and
+    // appending a tag made from the key and the associated data, so that
+    // the use of the wrong key or associated data is detected.
+    struct XorCipher(u8);
+
+    impl XorCipher {
+        fn tag(
+            &self,
+            associated_data: &[u8],
+        ) -> u8 {
+            associated_data.iter().fold(self.0, |tag, byte| tag ^ byte)
+        }
+    }
+
+    impl Cipher for XorCipher {
+        fn decrypt(
+            &self,
+            ciphertext: &[u8],
+            associated_data: &[u8],
+        ) -> Result<Vec<u8>> {
+            match ciphertext.split_last() {
+                Some((tag, body)) if *tag == self.tag(associated_data) => {
+                    Ok(body.iter().map(|byte| byte ^ self.0).collect())
+                },
+                _ => Err(Error::Message(String::from("authentication failed"))),
+            }
+        }
+
+        fn encrypt(
+            &self,
+            plaintext: &[u8],
+            associated_data: &[u8],
+        ) -> Result<Vec<u8>> {
+            let mut ciphertext: Vec<u8> =
+                plaintext.iter().map(|byte| byte ^ self.0).collect();
+            ciphertext.push(self.tag(associated_data));
+            Ok(ciphertext)
+        }
+    }
+
+    #[test]
+    fn seal_and_open() {
+        let keyring = Keyring::new().with_current_key(1, XorCipher(0x5A));
+        let sealed = keyring.seal(&(String::from("secret"), 42_u16));
+        assert!(sealed.is_ok());
+        let sealed = sealed.unwrap();
+        assert_eq!(
+            vec![1, 9, 0x5C, 0x29, 0x3F, 0x39, 0x28, 0x3F, 0x2E, 0x70, 0x5B],
+            sealed
+        );
+        let opened = keyring.open::<(String, u16)>(&sealed);
+        assert!(opened.is_ok());
+        let opened = opened.unwrap();
+        assert_eq!((String::from("secret"), 42), opened);
+    }
+
+    #[test]
+    fn rotation() {
+        let old = Keyring::new().with_current_key(1, XorCipher(0x11));
+        let archived = old.seal(&"archived").unwrap();
+        let rotated = Keyring::new()
+            .with_key(1, XorCipher(0x11))
+            .with_current_key(2, XorCipher(0x22));
+        assert_eq!(Some(2), rotated.current_key_id());
+        let fresh = rotated.seal(&"fresh").unwrap();
+        assert!(matches!(Keyring::key_id_of(&archived), Ok(1)));
+        assert!(matches!(Keyring::key_id_of(&fresh), Ok(2)));
+        assert_eq!("archived", rotated.open::<String>(&archived).unwrap());
+        assert_eq!("fresh", rotated.open::<String>(&fresh).unwrap());
+        let resealed = rotated.reseal(&archived);
+        assert!(resealed.is_ok());
+        let resealed = resealed.unwrap();
+        assert!(matches!(Keyring::key_id_of(&resealed), Ok(2)));
+        let retired = Keyring::new().with_current_key(2, XorCipher(0x22));
+        assert_eq!("archived", retired.open::<String>(&resealed).unwrap());
+        assert!(matches!(
+            retired.open::<String>(&archived),
+            Err(Error::UnknownKey(1))
+        ));
+    }
+
+    #[test]
+    fn encrypted_fields() {
+        #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+        struct Record {
+            name: String,
+            secret: Encrypted<(u8, String)>,
+        }
+
+        let keyring = Keyring::new().with_current_key(1, XorCipher(0x5A));
+        let secret = Encrypted::seal(&keyring, &(7, String::from("x")));
+        assert!(secret.is_ok());
+        let record = Record {
+            name: String::from("a"),
+            secret: secret.unwrap(),
+        };
+        let encoding = to_bytes(&record).unwrap();
+        assert_eq!(vec![1, b'a', 6, 1, 4, 0x5D, 0x5B, 0x22, 0x5B], encoding);
+        let decoded: Record = from_bytes(&encoding).unwrap();
+        assert_eq!(record, decoded);
+        assert!(matches!(decoded.secret.key_id(), Ok(1)));
+        let opened = decoded.secret.open(&keyring);
+        assert!(opened.is_ok());
+        assert_eq!((7, String::from("x")), opened.unwrap());
+        let rotated = keyring.with_current_key(2, XorCipher(0x22));
+        let resealed = decoded.secret.reseal(&rotated).unwrap();
+        assert!(matches!(resealed.key_id(), Ok(2)));
+        assert_eq!((7, String::from("x")), resealed.open(&rotated).unwrap());
+    }
+
+    #[test]
+    fn failures() {
+        assert!(matches!(Keyring::new().seal(&1_u8), Err(Error::NoCurrentKey)));
+        let sealed = Keyring::new()
+            .with_current_key(3, XorCipher(0x33))
+            .seal(&1_u8)
+            .unwrap();
+        let impostor = Keyring::new().with_current_key(3, XorCipher(0x44));
+        assert!(matches!(impostor.open::<u8>(&sealed), Err(Error::Message(_))));
+
+        // The key identifier is authenticated, so a value cannot be passed
+        // off as sealed by another key, even one with the same secret.
+        let keyring = Keyring::new()
+            .with_key(4, XorCipher(0x33))
+            .with_current_key(3, XorCipher(0x33));
+        assert_eq!(1, keyring.open::<u8>(&sealed).unwrap());
+        let mut relabeled = sealed;
+        relabeled[0] = 4;
+        assert!(matches!(Keyring::key_id_of(&relabeled), Ok(4)));
+        assert!(matches!(
+            keyring.open::<u8>(&relabeled),
+            Err(Error::Message(_))
+        ));
+    }
+}
@@ -0,0 +1,263 @@
+use super::{
+    from_bytes,
+    to_bytes,
+};
+#[cfg(feature = "sled")]
+use super::{
+    Error,
+    Result,
+};
+#[cfg(feature = "sled")]
+use std::{
+    convert::TryFrom,
+    marker::PhantomData,
+};
+
+/// This marks values kept in embedded key-value stores encoded in the
+/// binary format of this crate.  With the `redb` feature enabled, it is a
+/// [`redb::Value`], so a table defined with `Stored<T>` as its value type
+/// takes and returns values of type `T` directly.  With the `sled` feature
+/// enabled, it can be made from the [`sled::IVec`] returned by a lookup.
+///
+/// A redb table cannot report a stored value which fails to decode, so
+/// reading one panics, as the table does for its own types; values which
+/// cannot be encoded, which happens only if their `Serialize`
+/// implementations fail, panic on being inserted.
+///
+/// [`redb::Value`]: https://docs.rs/redb/2/redb/trait.Value.html
+/// [`sled::IVec`]: https://docs.rs/sled/0.34/sled/struct.IVec.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// # #[cfg(feature = "redb")]
+/// # {
+/// use serialization::Stored;
+///
+/// const USERS: redb::TableDefinition<&str, Stored<(String, u32)>> =
+///     redb::TableDefinition::new("users");
+///
+/// let db = redb::Database::builder()
+///     .create_with_backend(redb::backends::InMemoryBackend::new())
+///     .unwrap();
+/// let write = db.begin_write().unwrap();
+/// write
+///     .open_table(USERS)
+///     .unwrap()
+///     .insert("alice", (String::from("Alice"), 42))
+///     .unwrap();
+/// write.commit().unwrap();
+/// let read = db.begin_read().unwrap();
+/// let users = read.open_table(USERS).unwrap();
+/// let user = users.get("alice").unwrap().unwrap();
+/// assert_eq!((String::from("Alice"), 42), user.value());
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Stored<T>(pub T);
+
+#[cfg(feature = "redb")]
+impl<T> redb::Value for Stored<T>
+where
+    T: std::fmt::Debug + serde::Serialize + serde::de::DeserializeOwned,
+{
+    type AsBytes<'a>
+        = Vec<u8>
+    where
+        Self: 'a;
+    type SelfType<'a>
+        = T
+    where
+        Self: 'a;
+
+    fn as_bytes<'a, 'b: 'a>(value: &'a T) -> Vec<u8>
+    where
+        Self: 'b,
+    {
+        to_bytes(value).expect("stored value could not be encoded")
+    }
+
+    fn fixed_width() -> Option<usize> {
+        None
+    }
+
+    fn from_bytes<'a>(data: &'a [u8]) -> T
+    where
+        Self: 'a,
+    {
+        from_bytes(data).expect("stored value could not be decoded")
+    }
+
+    fn type_name() -> redb::TypeName {
+        redb::TypeName::new(&format!(
+            "serialization::Stored<{}>",
+            std::any::type_name::<T>()
+        ))
+    }
+}
+
+#[cfg(feature = "sled")]
+impl<T> TryFrom<sled::IVec> for Stored<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    type Error = Error;
+
+    fn try_from(bytes: sled::IVec) -> Result<Self> {
+        from_bytes(&bytes).map(Stored)
+    }
+}
+
+/// This wraps a [`sled::Tree`] whose values are all of type `T`, encoded in
+/// the binary format of this crate, so that values are stored and loaded in
+/// one call, with errors from the tree and from decoding returned as the
+/// same [`Error`] type.
+///
+/// [`sled::Tree`]: https://docs.rs/sled/0.34/sled/struct.Tree.html
+/// [`Error`]: enum.Error.html
+#[cfg(feature = "sled")]
+#[derive(Clone, Debug)]
+pub struct SledTree<T> {
+    tree: sled::Tree,
+    value: PhantomData<fn(T) -> T>,
+}
+
+#[cfg(feature = "sled")]
+impl<T> SledTree<T>
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Load the value stored under the given key, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the tree cannot be read, and otherwise any
+    /// of the errors returned by [`from_bytes`].
+    ///
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    /// [`from_bytes`]: fn.from_bytes.html
+    pub fn get<K>(
+        &self,
+        key: K,
+    ) -> Result<Option<T>>
+    where
+        K: AsRef<[u8]>,
+    {
+        let bytes = self.tree.get(key).map_err(std::io::Error::from)?;
+        bytes.map(|bytes| from_bytes(&bytes)).transpose()
+    }
+
+    /// Store the given value under the given key, returning the value
+    /// stored there before, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the tree cannot be written, and otherwise
+    /// any of the errors returned by [`to_bytes`] or [`from_bytes`].
+    ///
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    /// [`to_bytes`]: fn.to_bytes.html
+    /// [`from_bytes`]: fn.from_bytes.html
+    pub fn insert<K>(
+        &self,
+        key: K,
+        value: &T,
+    ) -> Result<Option<T>>
+    where
+        K: AsRef<[u8]>,
+    {
+        let bytes = self
+            .tree
+            .insert(key, to_bytes(value)?)
+            .map_err(std::io::Error::from)?;
+        bytes.map(|bytes| from_bytes(&bytes)).transpose()
+    }
+
+    /// Give back the tree being wrapped.
+    #[must_use]
+    pub fn into_inner(self) -> sled::Tree {
+        self.tree
+    }
+
+    /// Wrap the given tree, whose values should all be encodings of values
+    /// of type `T`.
+    #[must_use]
+    pub fn new(tree: sled::Tree) -> Self {
+        Self {
+            tree,
+            value: PhantomData,
+        }
+    }
+
+    /// Remove the value stored under the given key, returning it, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the tree cannot be written, and otherwise
+    /// any of the errors returned by [`from_bytes`].
+    ///
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    /// [`from_bytes`]: fn.from_bytes.html
+    pub fn remove<K>(
+        &self,
+        key: K,
+    ) -> Result<Option<T>>
+    where
+        K: AsRef<[u8]>,
+    {
+        let bytes = self.tree.remove(key).map_err(std::io::Error::from)?;
+        bytes.map(|bytes| from_bytes(&bytes)).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn sled_trees() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let tree = SledTree::<(String, u16)>::new(db.open_tree("t").unwrap());
+        let value = (String::from("hello"), 1000);
+        assert!(matches!(tree.insert("k", &value), Ok(None)));
+        let loaded = tree.get("k");
+        assert!(loaded.is_ok());
+        assert_eq!(Some(value.clone()), loaded.unwrap());
+        let ivec = tree.clone().into_inner().get("k").unwrap().unwrap();
+        let stored = Stored::<(String, u16)>::try_from(ivec);
+        assert!(stored.is_ok());
+        assert_eq!(value, stored.unwrap().0);
+        let removed = tree.remove("k");
+        assert!(removed.is_ok());
+        assert_eq!(Some(value), removed.unwrap());
+        assert!(matches!(tree.get("k"), Ok(None)));
+        let raw = tree.clone().into_inner();
+        assert!(raw.insert("bad", &[0x05, b'h'][..]).is_ok());
+        assert!(matches!(tree.get("bad"), Err(Error::ValueTruncated)));
+    }
+
+    #[cfg(feature = "redb")]
+    #[test]
+    fn redb_tables() {
+        const TABLE: redb::TableDefinition<u32, Stored<Vec<String>>> =
+            redb::TableDefinition::new("table");
+        let db = redb::Database::builder()
+            .create_with_backend(redb::backends::InMemoryBackend::new())
+            .unwrap();
+        let value = vec![String::from("a"), String::from("b")];
+        let write = db.begin_write().unwrap();
+        {
+            let mut table = write.open_table(TABLE).unwrap();
+            assert!(table.insert(1, &value).is_ok());
+        }
+        assert!(write.commit().is_ok());
+        let read = db.begin_read().unwrap();
+        let table = read.open_table(TABLE).unwrap();
+        let loaded = table.get(1).unwrap();
+        assert!(loaded.is_some());
+        assert_eq!(value, loaded.unwrap().value());
+        assert!(table.get(2).unwrap().is_none());
+    }
+}
@@ -0,0 +1,554 @@
+use super::{
+    Result,
+    Schema,
+    VariantKind,
+};
+
+/// This identifies how a value is encoded on the wire, as reported in a
+/// [`LayoutReport`].
+///
+/// [`LayoutReport`]: struct.LayoutReport.html
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WireEncoding {
+    /// A single byte (`bool`, `u8`, `i8`).
+    Byte,
+
+    /// An unsigned variable-length integer, seven bits per byte, most
+    /// significant group first.
+    Varint,
+
+    /// A signed variable-length integer, with the sign in the first byte.
+    SignedVarint,
+
+    /// A fixed number of bytes, most significant byte first (floats).
+    BigEndian(usize),
+
+    /// The UTF-8 encoding of a `char`.
+    Utf8,
+
+    /// A length, as a [`WireEncoding::Varint`], followed by that many bytes
+    /// (strings and byte strings).
+    ///
+    /// [`WireEncoding::Varint`]: #variant.Varint
+    LengthPrefixed,
+
+    /// A byte which is 0 for `None` or 1 for `Some`, followed in the latter
+    /// case by the value.
+    OptionFlag,
+
+    /// A count of elements or entries, as a [`WireEncoding::Varint`],
+    /// followed by that many elements or entries.
+    ///
+    /// [`WireEncoding::Varint`]: #variant.Varint
+    CountPrefixed,
+
+    /// The fields, in order, with nothing before or between them.
+    Fields,
+
+    /// The index of the variant, as a [`WireEncoding::Varint`], followed by
+    /// the fields of the variant.
+    ///
+    /// [`WireEncoding::Varint`]: #variant.Varint
+    VariantIndex,
+
+    /// Nothing at all (unit values and unit variants).
+    Empty,
+
+    /// The same encoding as an enclosing type, which is recursive.
+    Recursive,
+}
+
+impl std::fmt::Display for WireEncoding {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match self {
+            WireEncoding::Byte => f.write_str("byte"),
+            WireEncoding::Varint => f.write_str("varint"),
+            WireEncoding::SignedVarint => f.write_str("signed varint"),
+            WireEncoding::BigEndian(bytes) => {
+                write!(f, "{bytes}-byte big-endian")
+            },
+            WireEncoding::Utf8 => f.write_str("UTF-8"),
+            WireEncoding::LengthPrefixed => f.write_str("length-prefixed"),
+            WireEncoding::OptionFlag => f.write_str("option flag"),
+            WireEncoding::CountPrefixed => f.write_str("count-prefixed"),
+            WireEncoding::Fields => f.write_str("fields in order"),
+            WireEncoding::VariantIndex => f.write_str("variant index"),
+            WireEncoding::Empty => f.write_str("empty"),
+            WireEncoding::Recursive => f.write_str("recursive"),
+        }
+    }
+}
+
+/// This describes how one part of a value is laid out on the wire, as
+/// reported in a [`LayoutReport`].
+///
+/// [`LayoutReport`]: struct.LayoutReport.html
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LayoutEntry {
+    path: String,
+    description: String,
+    encoding: WireEncoding,
+    size: Option<usize>,
+}
+
+impl LayoutEntry {
+    /// Return a description of the part, such as `u32` or `struct Point`.
+    #[must_use]
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Return how the part is encoded.
+    #[must_use]
+    pub fn encoding(&self) -> WireEncoding {
+        self.encoding
+    }
+
+    /// Return the location of the part within the value.  The value itself
+    /// is `$`, and parts within it are reached with `.name` for fields,
+    /// `.0`, `.1`, ... for unnamed fields, `[]` for elements of sequences,
+    /// `{key}` and `{value}` for keys and values of maps, `?` for the
+    /// contents of options, and `::Name` for enum variants.
+    #[must_use]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Return the number of bytes the part always takes, if it is fixed.
+    #[must_use]
+    pub fn size(&self) -> Option<usize> {
+        self.size
+    }
+}
+
+/// This is a description of how a type is laid out on the wire, obtained
+/// with [`describe`].  It lists every part of the type, in the order the
+/// parts are encoded, along with how each is encoded and its size if fixed.
+///
+/// Its `Display` output, one line per part, is intended to be kept and
+/// compared across releases, to catch accidental changes to the wire format.
+///
+/// [`describe`]: fn.describe.html
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LayoutReport {
+    entries: Vec<LayoutEntry>,
+    fingerprint: u64,
+}
+
+impl LayoutReport {
+    /// Return the parts of the type, in the order they are encoded.
+    #[must_use]
+    pub fn entries(&self) -> &[LayoutEntry] {
+        &self.entries
+    }
+
+    /// Return the fingerprint of the schema of the type, as given by
+    /// [`Schema::fingerprint`].
+    ///
+    /// [`Schema::fingerprint`]: enum.Schema.html#method.fingerprint
+    #[must_use]
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Return a report describing the type with the given schema.
+    #[must_use]
+    pub fn of_schema(schema: &Schema) -> Self {
+        let mut entries = Vec::new();
+        add_entries("$", schema, &mut entries);
+        Self {
+            entries,
+            fingerprint: schema.fingerprint(),
+        }
+    }
+}
+
+impl std::fmt::Display for LayoutReport {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        writeln!(f, "fingerprint {:016X}", self.fingerprint)?;
+        for entry in &self.entries {
+            write!(
+                f,
+                "{}: {}, {}",
+                entry.path, entry.description, entry.encoding
+            )?;
+            if let Some(size) = entry.size {
+                write!(f, ", size {size}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+fn fixed_size(schema: &Schema) -> Option<usize> {
+    match schema {
+        Schema::Bool | Schema::I8 | Schema::U8 => Some(1),
+        Schema::F32 => Some(4),
+        Schema::F64 => Some(8),
+        Schema::Unit | Schema::UnitStruct(_) => Some(0),
+        Schema::NewtypeStruct {
+            inner,
+            ..
+        } => fixed_size(inner),
+        Schema::Tuple(fields)
+        | Schema::Array(fields)
+        | Schema::TupleStruct {
+            fields,
+            ..
+        } => fields.iter().map(fixed_size).sum(),
+        Schema::Struct {
+            fields,
+            ..
+        } => fields.iter().map(|field| fixed_size(&field.schema)).sum(),
+
+        // Variant indexes below 128 take one byte, so enums with few enough
+        // variants, all of the same fixed size, have a fixed size.
+        Schema::Enum {
+            variants,
+            ..
+        } if variants.len() <= 128 => {
+            let mut sizes =
+                variants.iter().map(|variant| variant_size(&variant.kind));
+            let first = sizes.next()??;
+            if sizes.all(|size| size == Some(first)) {
+                Some(1 + first)
+            } else {
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+fn variant_size(kind: &VariantKind) -> Option<usize> {
+    match kind {
+        VariantKind::Unit => Some(0),
+        VariantKind::Newtype(inner) => fixed_size(inner),
+        VariantKind::Tuple(fields) => fields.iter().map(fixed_size).sum(),
+        VariantKind::Struct(fields) => {
+            fields.iter().map(|field| fixed_size(&field.schema)).sum()
+        },
+    }
+}
+
+fn describe_schema(schema: &Schema) -> (String, WireEncoding) {
+    match schema {
+        Schema::Bool => (String::from("bool"), WireEncoding::Byte),
+        Schema::I8 => (String::from("i8"), WireEncoding::Byte),
+        Schema::I16 => (String::from("i16"), WireEncoding::SignedVarint),
+        Schema::I32 => (String::from("i32"), WireEncoding::SignedVarint),
+        Schema::I64 => (String::from("i64"), WireEncoding::SignedVarint),
+        Schema::I128 => (String::from("i128"), WireEncoding::SignedVarint),
+        Schema::U8 => (String::from("u8"), WireEncoding::Byte),
+        Schema::U16 => (String::from("u16"), WireEncoding::Varint),
+        Schema::U32 => (String::from("u32"), WireEncoding::Varint),
+        Schema::U64 => (String::from("u64"), WireEncoding::Varint),
+        Schema::U128 => (String::from("u128"), WireEncoding::Varint),
+        Schema::F32 => (String::from("f32"), WireEncoding::BigEndian(4)),
+        Schema::F64 => (String::from("f64"), WireEncoding::BigEndian(8)),
+        Schema::Char => (String::from("char"), WireEncoding::Utf8),
+        Schema::Str => (String::from("string"), WireEncoding::LengthPrefixed),
+        Schema::Bytes => (String::from("bytes"), WireEncoding::LengthPrefixed),
+        Schema::Option(_) => (String::from("option"), WireEncoding::OptionFlag),
+        Schema::Unit => (String::from("unit"), WireEncoding::Empty),
+        Schema::UnitStruct(name) => {
+            (format!("unit struct {name}"), WireEncoding::Empty)
+        },
+        Schema::NewtypeStruct {
+            name,
+            ..
+        } => (format!("newtype struct {name}"), WireEncoding::Fields),
+        Schema::Seq(_) => {
+            (String::from("sequence"), WireEncoding::CountPrefixed)
+        },
+        Schema::Tuple(fields) => {
+            (format!("tuple of {}", fields.len()), WireEncoding::Fields)
+        },
+        Schema::Array(elements) => {
+            (format!("array of {}", elements.len()), WireEncoding::Fields)
+        },
+        Schema::TupleStruct {
+            name,
+            ..
+        } => (format!("tuple struct {name}"), WireEncoding::Fields),
+        Schema::Map {
+            ..
+        } => (String::from("map"), WireEncoding::CountPrefixed),
+        Schema::Struct {
+            name,
+            ..
+        } => (format!("struct {name}"), WireEncoding::Fields),
+        Schema::Enum {
+            name,
+            ..
+        } => (format!("enum {name}"), WireEncoding::VariantIndex),
+        Schema::Recursive(name) => {
+            ((*name).to_string(), WireEncoding::Recursive)
+        },
+    }
+}
+
+fn add_entries(
+    path: &str,
+    schema: &Schema,
+    entries: &mut Vec<LayoutEntry>,
+) {
+    let (description, encoding) = describe_schema(schema);
+    entries.push(LayoutEntry {
+        path: String::from(path),
+        description,
+        encoding,
+        size: fixed_size(schema),
+    });
+    match schema {
+        Schema::Option(inner) => {
+            add_entries(&format!("{path}?"), inner, entries);
+        },
+        Schema::NewtypeStruct {
+            inner,
+            ..
+        } => add_entries(&format!("{path}.0"), inner, entries),
+        Schema::Seq(element) => {
+            add_entries(&format!("{path}[]"), element, entries);
+        },
+        Schema::Tuple(fields)
+        | Schema::Array(fields)
+        | Schema::TupleStruct {
+            fields,
+            ..
+        } => add_unnamed_fields(path, fields, entries),
+        Schema::Map {
+            key,
+            value,
+        } => {
+            add_entries(&format!("{path}{{key}}"), key, entries);
+            add_entries(&format!("{path}{{value}}"), value, entries);
+        },
+        Schema::Struct {
+            fields,
+            ..
+        } => {
+            for field in fields {
+                add_entries(
+                    &format!("{path}.{}", field.name),
+                    &field.schema,
+                    entries,
+                );
+            }
+        },
+        Schema::Enum {
+            variants,
+            ..
+        } => {
+            for (index, variant) in variants.iter().enumerate() {
+                let path = format!("{path}::{}", variant.name);
+                entries.push(LayoutEntry {
+                    path: path.clone(),
+                    description: format!("variant {index}"),
+                    encoding: match variant.kind {
+                        VariantKind::Unit => WireEncoding::Empty,
+                        _ => WireEncoding::Fields,
+                    },
+                    size: variant_size(&variant.kind),
+                });
+                match &variant.kind {
+                    VariantKind::Unit => {},
+                    VariantKind::Newtype(inner) => {
+                        add_entries(&format!("{path}.0"), inner, entries);
+                    },
+                    VariantKind::Tuple(fields) => {
+                        add_unnamed_fields(&path, fields, entries);
+                    },
+                    VariantKind::Struct(fields) => {
+                        for field in fields {
+                            add_entries(
+                                &format!("{path}.{}", field.name),
+                                &field.schema,
+                                entries,
+                            );
+                        }
+                    },
+                }
+            }
+        },
+        _ => {},
+    }
+}
+
+fn add_unnamed_fields(
+    path: &str,
+    fields: &[Schema],
+    entries: &mut Vec<LayoutEntry>,
+) {
+    for (index, field) in fields.iter().enumerate() {
+        add_entries(&format!("{path}.{index}"), field, entries);
+    }
+}
+
+/// This function is used to describe how values of type `T` are laid out
+/// on the wire, using the schema of the type found by [`Schema::of`].
+///
+/// [`Schema::of`]: enum.Schema.html#method.of
+///
+/// # Errors
+///
+/// Returns any error from [`Schema::of`].
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::describe;
+///
+/// #[derive(serde::Deserialize)]
+/// struct Reading {
+///     sensor: u8,
+///     value: f32,
+///     note: Option<String>,
+/// }
+///
+/// let report = describe::<Reading>().unwrap();
+/// assert_eq!(
+///     concat!(
+///         "$: struct Reading, fields in order\n",
+///         "$.sensor: u8, byte, size 1\n",
+///         "$.value: f32, 4-byte big-endian, size 4\n",
+///         "$.note: option, option flag\n",
+///         "$.note?: string, length-prefixed\n",
+///     ),
+///     report.to_string().split_once('\n').unwrap().1
+/// );
+/// ```
+pub fn describe<'de, T>() -> Result<LayoutReport>
+where
+    T: serde::Deserialize<'de>,
+{
+    Ok(LayoutReport::of_schema(&Schema::of::<T>()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize)]
+    struct Meters(f64);
+
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize)]
+    enum Direction {
+        North,
+        South,
+    }
+
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize)]
+    enum Tree {
+        Leaf(i32),
+        Node(Vec<Tree>),
+    }
+
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize)]
+    struct Map {
+        heading: Direction,
+        scale: Meters,
+        origin: (u8, bool),
+        labels: BTreeMap<u16, char>,
+        tree: Tree,
+    }
+
+    #[test]
+    fn report_lists_parts_in_order() {
+        let report = describe::<Map>();
+        assert!(report.is_ok());
+        let report = report.unwrap();
+        assert_eq!(
+            Schema::of::<Map>().unwrap().fingerprint(),
+            report.fingerprint()
+        );
+        assert_eq!(
+            vec![
+                ("$", "struct Map", WireEncoding::Fields, None),
+                (
+                    "$.heading",
+                    "enum Direction",
+                    WireEncoding::VariantIndex,
+                    Some(1)
+                ),
+                ("$.heading::North", "variant 0", WireEncoding::Empty, Some(0)),
+                ("$.heading::South", "variant 1", WireEncoding::Empty, Some(0)),
+                (
+                    "$.scale",
+                    "newtype struct Meters",
+                    WireEncoding::Fields,
+                    Some(8)
+                ),
+                ("$.scale.0", "f64", WireEncoding::BigEndian(8), Some(8)),
+                ("$.origin", "tuple of 2", WireEncoding::Fields, Some(2)),
+                ("$.origin.0", "u8", WireEncoding::Byte, Some(1)),
+                ("$.origin.1", "bool", WireEncoding::Byte, Some(1)),
+                ("$.labels", "map", WireEncoding::CountPrefixed, None),
+                ("$.labels{key}", "u16", WireEncoding::Varint, None),
+                ("$.labels{value}", "char", WireEncoding::Utf8, None),
+                ("$.tree", "enum Tree", WireEncoding::VariantIndex, None),
+                ("$.tree::Leaf", "variant 0", WireEncoding::Fields, None),
+                ("$.tree::Leaf.0", "i32", WireEncoding::SignedVarint, None),
+                ("$.tree::Node", "variant 1", WireEncoding::Fields, None),
+                (
+                    "$.tree::Node.0",
+                    "sequence",
+                    WireEncoding::CountPrefixed,
+                    None
+                ),
+                ("$.tree::Node.0[]", "Tree", WireEncoding::Recursive, None),
+            ],
+            report
+                .entries()
+                .iter()
+                .map(|entry| (
+                    entry.path(),
+                    entry.description(),
+                    entry.encoding(),
+                    entry.size()
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn reports_differ_when_layout_changes() {
+        let before = describe::<(u8, u16)>().unwrap();
+        let after = describe::<(u8, u32)>().unwrap();
+        assert_ne!(before.to_string(), after.to_string());
+        assert_eq!(before, describe::<(u8, u16)>().unwrap());
+    }
+
+    #[test]
+    fn wide_integers() {
+        let report = describe::<(u128, i128)>();
+        assert!(report.is_ok());
+        assert_eq!(
+            vec![
+                ("$.0", "u128", WireEncoding::Varint),
+                ("$.1", "i128", WireEncoding::SignedVarint),
+            ],
+            report.unwrap().entries()[1..]
+                .iter()
+                .map(|entry| (
+                    entry.path(),
+                    entry.description(),
+                    entry.encoding()
+                ))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,474 @@
+use super::FORMAT_VERSION;
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+        HashMap,
+        HashSet,
+        VecDeque,
+    },
+    marker::PhantomData,
+};
+
+/// This trait is implemented by types with a fingerprint of their layout
+/// which is known at compile time.  As with [`Schema::fingerprint`], only
+/// the structure of the encoding contributes to the fingerprint, so
+/// renaming types and fields does not change it, but adding, removing,
+/// reordering, or changing the types of fields does.  The two fingerprints
+/// are computed differently, so they do not have the same values.
+///
+/// With the `derive` feature enabled, this trait can be derived for structs
+/// and enums whose fields all implement it, and [`assert_layout_unchanged`]
+/// can be used to make the build fail if the layout of a type changes.
+/// Deriving it also adds a `WIRE_FINGERPRINT` constant to the type, which
+/// includes the version of the encoding along with the layout, for peers to
+/// exchange in handshakes or for files to hold in their headers, so that
+/// disagreement about how the type is encoded is found before any values
+/// are decoded.
+/// Recursive types cannot implement it, since their fingerprints would
+/// depend on themselves.
+///
+/// [`Schema::fingerprint`]: enum.Schema.html#method.fingerprint
+/// [`assert_layout_unchanged`]: macro.assert_layout_unchanged.html
+pub trait LayoutFingerprint {
+    /// This is the fingerprint of the layout of the type.
+    const LAYOUT_FINGERPRINT: u64;
+}
+
+// These tag the kinds of layouts, and are the same as those used by
+// `Schema::fingerprint`.
+const UNIT: u8 = 16;
+const SEQ: u8 = 17;
+const FIELDS: u8 = 18;
+const MAP: u8 = 19;
+const ENUM: u8 = 20;
+
+/// Return the fingerprint of a layout with no parts, identified by the
+/// given tag.  This is provided for the benefit of the code generated by
+/// the `LayoutFingerprint` derive macro.
+#[must_use]
+pub const fn fingerprint_start(tag: u8) -> u64 {
+    (0xCBF2_9CE4_8422_2325 ^ tag as u64).wrapping_mul(0x0000_0100_0000_01B3)
+}
+
+/// Return the fingerprint of a layout extended by a part with the given
+/// fingerprint (or count of parts).  This is provided for the benefit of
+/// the code generated by the `LayoutFingerprint` derive macro.
+#[must_use]
+pub const fn fingerprint_add(
+    mut fingerprint: u64,
+    part: u64,
+) -> u64 {
+    let bytes = part.to_be_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        fingerprint ^= bytes[i] as u64;
+        fingerprint = fingerprint.wrapping_mul(0x0000_0100_0000_01B3);
+        i += 1;
+    }
+    fingerprint
+}
+
+/// Return the fingerprint of the layout of a unit value, unit struct, or
+/// unit variant.
+#[must_use]
+pub const fn unit_fingerprint() -> u64 {
+    fingerprint_start(UNIT)
+}
+
+/// Return the fingerprint of the layout of a struct, tuple, or variant
+/// with the given number of fields, to which the fingerprints of the fields
+/// are then added in order.
+#[must_use]
+pub const fn fields_fingerprint(len: usize) -> u64 {
+    fingerprint_add(fingerprint_start(FIELDS), len as u64)
+}
+
+/// Return the fingerprint of the layout of an enum with the given number of
+/// variants, to which the fingerprints of the variants are then added in
+/// order.
+#[must_use]
+pub const fn enum_fingerprint(len: usize) -> u64 {
+    fingerprint_add(fingerprint_start(ENUM), len as u64)
+}
+
+/// Return the fingerprint of the encoding of a type with the given layout
+/// fingerprint, using the version of the encoding produced by this crate.
+/// This is provided for the benefit of the code generated by the
+/// `LayoutFingerprint` derive macro, as the `WIRE_FINGERPRINT` constant.
+#[must_use]
+pub const fn wire_fingerprint(layout: u64) -> u64 {
+    fingerprint_add(layout, FORMAT_VERSION as u64)
+}
+
+/// This macro makes the build fail unless the [`LayoutFingerprint`] of the
+/// given type is the given value, so that changing the layout of a type
+/// whose values have been stored or sent elsewhere is caught before old
+/// data is misread.  When a change is intended, print the new value of
+/// `LAYOUT_FINGERPRINT` to update the assertion (and migrate old data).
+///
+/// [`LayoutFingerprint`]: trait.LayoutFingerprint.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// # #[cfg(feature = "derive")]
+/// # {
+/// use serialization::{
+///     assert_layout_unchanged,
+///     LayoutFingerprint,
+/// };
+///
+/// #[derive(serde::Serialize, serde::Deserialize, LayoutFingerprint)]
+/// struct SavedGame {
+///     level: u16,
+///     name: String,
+/// }
+///
+/// assert_layout_unchanged!(SavedGame, 0xFDB1_CF33_8EAE_6E21);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! assert_layout_unchanged {
+    ($ty:ty, $fingerprint:expr $(,)?) => {
+        const _: () = assert!(
+            <$ty as $crate::LayoutFingerprint>::LAYOUT_FINGERPRINT
+                == $fingerprint,
+            concat!(
+                "the layout of ",
+                stringify!($ty),
+                " has changed; check that old data can still be decoded, \
+                 then update its LAYOUT_FINGERPRINT"
+            )
+        );
+    };
+}
+
+macro_rules! impl_layout_fingerprint {
+    ($($tag:expr => $($ty:ty),+;)*) => {
+        $($(
+            impl LayoutFingerprint for $ty {
+                const LAYOUT_FINGERPRINT: u64 = fingerprint_start($tag);
+            }
+        )+)*
+    };
+}
+
+// The tags of primitive types are the same as those used by
+// `Schema::fingerprint`.  Sizes are encoded as 64-bit integers.
+impl_layout_fingerprint! {
+    1 => bool;
+    2 => i8;
+    3 => i16;
+    4 => i32;
+    5 => i64, isize;
+    6 => u8;
+    7 => u16;
+    8 => u32;
+    9 => u64, usize;
+    10 => f32;
+    11 => f64;
+    12 => char;
+    13 => str, String;
+    UNIT => ();
+}
+
+impl<T> LayoutFingerprint for PhantomData<T>
+where
+    T: ?Sized,
+{
+    const LAYOUT_FINGERPRINT: u64 = unit_fingerprint();
+}
+
+impl<T> LayoutFingerprint for Option<T>
+where
+    T: LayoutFingerprint,
+{
+    const LAYOUT_FINGERPRINT: u64 =
+        fingerprint_add(fingerprint_start(15), T::LAYOUT_FINGERPRINT);
+}
+
+impl<T> LayoutFingerprint for &T
+where
+    T: ?Sized + LayoutFingerprint,
+{
+    const LAYOUT_FINGERPRINT: u64 = T::LAYOUT_FINGERPRINT;
+}
+
+impl<T> LayoutFingerprint for Box<T>
+where
+    T: ?Sized + LayoutFingerprint,
+{
+    const LAYOUT_FINGERPRINT: u64 = T::LAYOUT_FINGERPRINT;
+}
+
+impl<T, const N: usize> LayoutFingerprint for [T; N]
+where
+    T: LayoutFingerprint,
+{
+    const LAYOUT_FINGERPRINT: u64 = {
+        let mut fingerprint = fields_fingerprint(N);
+        let mut i = 0;
+        while i < N {
+            fingerprint = fingerprint_add(fingerprint, T::LAYOUT_FINGERPRINT);
+            i += 1;
+        }
+        fingerprint
+    };
+}
+
+macro_rules! impl_layout_fingerprint_seq {
+    ($($ty:ident),+) => {
+        $(
+            impl<T> LayoutFingerprint for $ty<T>
+            where
+                T: LayoutFingerprint,
+            {
+                const LAYOUT_FINGERPRINT: u64 = fingerprint_add(
+                    fingerprint_start(SEQ),
+                    T::LAYOUT_FINGERPRINT,
+                );
+            }
+        )+
+    };
+}
+
+impl_layout_fingerprint_seq!(Vec, VecDeque, BTreeSet);
+
+impl<T, S> LayoutFingerprint for HashSet<T, S>
+where
+    T: LayoutFingerprint,
+{
+    const LAYOUT_FINGERPRINT: u64 = Vec::<T>::LAYOUT_FINGERPRINT;
+}
+
+impl<T> LayoutFingerprint for [T]
+where
+    T: LayoutFingerprint,
+{
+    const LAYOUT_FINGERPRINT: u64 =
+        fingerprint_add(fingerprint_start(SEQ), T::LAYOUT_FINGERPRINT);
+}
+
+macro_rules! impl_layout_fingerprint_map {
+    ($($ty:ident),+) => {
+        $(
+            impl<K, V> LayoutFingerprint for $ty<K, V>
+            where
+                K: LayoutFingerprint,
+                V: LayoutFingerprint,
+            {
+                const LAYOUT_FINGERPRINT: u64 = fingerprint_add(
+                    fingerprint_add(
+                        fingerprint_start(MAP),
+                        K::LAYOUT_FINGERPRINT,
+                    ),
+                    V::LAYOUT_FINGERPRINT,
+                );
+            }
+        )+
+    };
+}
+
+impl_layout_fingerprint_map!(BTreeMap);
+
+impl<K, V, S> LayoutFingerprint for HashMap<K, V, S>
+where
+    K: LayoutFingerprint,
+    V: LayoutFingerprint,
+{
+    const LAYOUT_FINGERPRINT: u64 = BTreeMap::<K, V>::LAYOUT_FINGERPRINT;
+}
+
+macro_rules! impl_layout_fingerprint_tuple {
+    ($len:expr => $($name:ident)+) => {
+        impl<$($name),+> LayoutFingerprint for ($($name,)+)
+        where
+            $($name: LayoutFingerprint,)+
+        {
+            const LAYOUT_FINGERPRINT: u64 = {
+                let fingerprint = fields_fingerprint($len);
+                $(
+                    let fingerprint =
+                        fingerprint_add(fingerprint, $name::LAYOUT_FINGERPRINT);
+                )+
+                fingerprint
+            };
+        }
+    };
+}
+
+impl_layout_fingerprint_tuple! { 1 => T0 }
+impl_layout_fingerprint_tuple! { 2 => T0 T1 }
+impl_layout_fingerprint_tuple! { 3 => T0 T1 T2 }
+impl_layout_fingerprint_tuple! { 4 => T0 T1 T2 T3 }
+impl_layout_fingerprint_tuple! { 5 => T0 T1 T2 T3 T4 }
+impl_layout_fingerprint_tuple! { 6 => T0 T1 T2 T3 T4 T5 }
+impl_layout_fingerprint_tuple! { 7 => T0 T1 T2 T3 T4 T5 T6 }
+impl_layout_fingerprint_tuple! { 8 => T0 T1 T2 T3 T4 T5 T6 T7 }
+impl_layout_fingerprint_tuple! { 9 => T0 T1 T2 T3 T4 T5 T6 T7 T8 }
+impl_layout_fingerprint_tuple! { 10 => T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 }
+impl_layout_fingerprint_tuple! { 11 => T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 }
+impl_layout_fingerprint_tuple! { 12 => T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "derive")]
+    use crate::LayoutFingerprint;
+
+    #[test]
+    fn compound_types() {
+        assert_eq!(
+            <(u32, String)>::LAYOUT_FINGERPRINT,
+            <(u32, &str)>::LAYOUT_FINGERPRINT
+        );
+        assert_ne!(
+            <(u32, String)>::LAYOUT_FINGERPRINT,
+            <(String, u32)>::LAYOUT_FINGERPRINT
+        );
+        assert_ne!(
+            <[u8; 2]>::LAYOUT_FINGERPRINT,
+            <[u8; 3]>::LAYOUT_FINGERPRINT
+        );
+        assert_eq!(
+            <[u8; 2]>::LAYOUT_FINGERPRINT,
+            <(u8, u8)>::LAYOUT_FINGERPRINT
+        );
+        assert_eq!(
+            <Vec<u8>>::LAYOUT_FINGERPRINT,
+            <HashSet<u8>>::LAYOUT_FINGERPRINT
+        );
+        assert_ne!(
+            <Vec<u8>>::LAYOUT_FINGERPRINT,
+            <Option<u8>>::LAYOUT_FINGERPRINT
+        );
+        assert_ne!(
+            <BTreeMap<u8, u16>>::LAYOUT_FINGERPRINT,
+            <BTreeMap<u16, u8>>::LAYOUT_FINGERPRINT
+        );
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derive_struct() {
+        #[derive(LayoutFingerprint)]
+        struct Saved {
+            _level: u16,
+            _name: String,
+        }
+        #[derive(LayoutFingerprint)]
+        struct Renamed {
+            _stage: u16,
+            _title: String,
+        }
+        #[derive(LayoutFingerprint)]
+        struct Reordered {
+            _name: String,
+            _level: u16,
+        }
+        #[derive(LayoutFingerprint)]
+        struct Retyped {
+            _level: u32,
+            _name: String,
+        }
+        #[derive(serde::Serialize, LayoutFingerprint)]
+        struct Skipping {
+            _level: u16,
+            #[serde(skip)]
+            _cached: Vec<u8>,
+            _name: String,
+        }
+        #[allow(dead_code)]
+        #[derive(LayoutFingerprint)]
+        struct Wrapper(Saved);
+        assert_eq!(Saved::LAYOUT_FINGERPRINT, Renamed::LAYOUT_FINGERPRINT);
+        assert_eq!(
+            Saved::LAYOUT_FINGERPRINT,
+            <(u16, String)>::LAYOUT_FINGERPRINT
+        );
+        assert_eq!(Saved::LAYOUT_FINGERPRINT, Skipping::LAYOUT_FINGERPRINT);
+        assert_eq!(Saved::LAYOUT_FINGERPRINT, Wrapper::LAYOUT_FINGERPRINT);
+        assert_ne!(Saved::LAYOUT_FINGERPRINT, Reordered::LAYOUT_FINGERPRINT);
+        assert_ne!(Saved::LAYOUT_FINGERPRINT, Retyped::LAYOUT_FINGERPRINT);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derive_enum() {
+        #[derive(LayoutFingerprint)]
+        enum Shape<T> {
+            _Empty,
+            _Circle(T),
+            _Line(T, T),
+            _Label {
+                text: String,
+            },
+        }
+        #[derive(LayoutFingerprint)]
+        enum Fewer {
+            _Empty,
+            _Circle(u8),
+            _Line(u8, u8),
+        }
+        assert_ne!(
+            <Shape<u8>>::LAYOUT_FINGERPRINT,
+            <Shape<u16>>::LAYOUT_FINGERPRINT
+        );
+        assert_ne!(<Shape<u8>>::LAYOUT_FINGERPRINT, Fewer::LAYOUT_FINGERPRINT);
+        crate::assert_layout_unchanged!(
+            Shape<u8>,
+            <Shape<u8> as LayoutFingerprint>::LAYOUT_FINGERPRINT
+        );
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn wire_fingerprint_of_derived_types() {
+        #[derive(LayoutFingerprint)]
+        struct Saved {
+            _level: u16,
+        }
+        const HEADER: u64 = Saved::WIRE_FINGERPRINT;
+        assert_eq!(wire_fingerprint(Saved::LAYOUT_FINGERPRINT), HEADER);
+        assert_ne!(Saved::LAYOUT_FINGERPRINT, HEADER);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn variant_tags_are_fingerprinted() {
+        use crate::serialization;
+
+        #[serialization]
+        #[derive(serde::Serialize, LayoutFingerprint)]
+        enum Command {
+            #[serialization(tag = 4)]
+            _Stop,
+            _Go(u8),
+        }
+        #[serialization]
+        #[derive(serde::Serialize, LayoutFingerprint)]
+        enum Reordered {
+            #[serialization(tag = 5)]
+            _Go(u8),
+            #[serialization(tag = 4)]
+            _Stop,
+        }
+        #[serialization]
+        #[derive(serde::Serialize, LayoutFingerprint)]
+        enum Retagged {
+            #[serialization(tag = 1)]
+            _Stop,
+            _Go(u8),
+        }
+        #[derive(serde::Serialize, LayoutFingerprint)]
+        enum Untagged {
+            _Stop,
+            _Go(u8),
+        }
+        assert_eq!(Command::WIRE_FINGERPRINT, Reordered::WIRE_FINGERPRINT);
+        assert_ne!(Command::WIRE_FINGERPRINT, Retagged::WIRE_FINGERPRINT);
+        assert_ne!(Command::WIRE_FINGERPRINT, Untagged::WIRE_FINGERPRINT);
+    }
+}
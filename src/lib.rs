@@ -4,6 +4,18 @@
 //! [`serde`]: https://docs.rs/serde
 //! [`bincode`]: https://docs.rs/bincode
 //!
+//! Without the `std` feature, which is enabled by default, the crate is
+//! `no_std` and needs only `alloc`.  Values can then still be encoded with
+//! [`to_slice`] or [`to_bytes`] and decoded with [`from_bytes`], under any
+//! [`Config`], but the parts built on the standard library, such as
+//! readers, writers, and [`Value`], are left out, as are the derive macros.
+//!
+//! [`to_slice`]: fn.to_slice.html
+//! [`to_bytes`]: fn.to_bytes.html
+//! [`from_bytes`]: fn.from_bytes.html
+//! [`Config`]: struct.Config.html
+//! [`Value`]: enum.Value.html
+//!
 //! # Examples
 //!
 //! ```rust
@@ -19,22 +31,411 @@
 //! assert_eq!(greeting, decoded_message);
 //! ```
 
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 #![warn(clippy::pedantic)]
 #![warn(missing_docs)]
 
+extern crate alloc;
+
+// This allows code generated by the derive macros, which refers to this crate
+// by name, to be used within the crate itself.
+extern crate self as serialization;
+
+#[cfg(feature = "std")]
+mod advisor;
+#[cfg(feature = "std")]
+mod archive;
+#[cfg(feature = "arrow")]
+mod arrow;
+#[cfg(feature = "futures")]
+mod async_io;
+#[cfg(feature = "std")]
+mod audit;
+#[cfg(feature = "std")]
+mod bounded;
+#[cfg(any(feature = "rdkafka", feature = "lapin"))]
+mod broker;
+#[cfg(feature = "std")]
+mod channel;
+#[cfg(feature = "std")]
+mod codegen;
+#[cfg(feature = "std")]
+mod column;
+#[cfg(feature = "std")]
+mod compaction;
+#[cfg(feature = "std")]
+mod compression;
+mod config;
+#[cfg(feature = "std")]
+mod const_bytes;
 mod de;
+mod dedup;
+#[cfg(test)]
+mod differential;
+#[cfg(feature = "std")]
+mod embed;
+#[cfg(feature = "std")]
+mod envelope;
 mod error;
+#[cfg(feature = "std")]
+mod events;
+mod fingerprint;
+#[cfg(feature = "std")]
+mod fixed;
+#[cfg(feature = "std")]
+mod format;
+#[cfg(any(feature = "asynchronous-codec", feature = "tokio-util"))]
+mod frame_codec;
+#[cfg(feature = "std")]
+mod handshake;
+#[cfg(feature = "std")]
+mod hex_dump;
+mod int_codec;
+mod io;
+#[cfg(feature = "std")]
+mod json;
+#[cfg(feature = "std")]
+mod json_schema;
+#[cfg(feature = "std")]
+mod kaitai;
+#[cfg(feature = "std")]
+mod key;
+#[cfg(feature = "std")]
+mod keyring;
+#[cfg(any(feature = "redb", feature = "sled"))]
+mod kv;
+#[cfg(feature = "std")]
+mod layout;
+#[cfg(feature = "std")]
+mod layout_fingerprint;
+#[cfg(feature = "std")]
+mod max_size;
+#[cfg(feature = "std")]
+mod migrations;
+mod name_hash;
+#[cfg(feature = "std")]
+mod nested;
+#[cfg(feature = "std")]
+mod pipeline;
+mod projection;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "std")]
+mod raw;
+#[cfg(feature = "std")]
+mod read;
+#[cfg(feature = "std")]
+mod redacted;
+#[cfg(feature = "std")]
+mod registry;
+mod schema;
 mod ser;
+#[cfg(feature = "memmap2")]
+mod shm;
+mod skip;
+#[cfg(feature = "std")]
+mod spill;
+mod tag;
+#[cfg(any(all(test, feature = "std"), feature = "testing"))]
+pub mod testing;
+#[cfg(feature = "tokio")]
+mod tokio_io;
+mod trace;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
+#[cfg(feature = "std")]
+mod value;
+#[cfg(feature = "std")]
+mod variant_tags;
+#[cfg(feature = "std")]
+mod versioned;
+#[cfg(any(feature = "axum", feature = "actix-web"))]
+mod web;
+#[cfg(feature = "std")]
+mod wire;
+#[cfg(feature = "std")]
+pub mod with;
 
+#[cfg(feature = "std")]
+pub use advisor::{
+    advise_encodings,
+    Advice,
+    Recommendation,
+    SuggestedEncoding,
+};
+#[cfg(feature = "std")]
+pub use archive::{
+    Archive,
+    ArchiveWriter,
+};
+#[cfg(feature = "arrow")]
+pub use arrow::{
+    from_record_batch,
+    to_record_batch,
+};
+#[cfg(feature = "futures")]
+pub use async_io::{
+    from_reader_async,
+    to_writer_async,
+};
+#[cfg(feature = "std")]
+pub use audit::{
+    audit_determinism,
+    Nondeterminism,
+};
+#[cfg(feature = "std")]
+pub use bounded::BoundedVec;
+#[cfg(any(feature = "rdkafka", feature = "lapin"))]
+pub use broker::MessagePayload;
+#[cfg(feature = "std")]
+pub use channel::{
+    frame_channel,
+    FrameReceiver,
+    FrameSender,
+};
+#[cfg(feature = "std")]
+pub use codegen::Codegen;
+#[cfg(feature = "std")]
+pub use column::extract_column;
+#[cfg(feature = "std")]
+pub use compaction::{
+    compact_log,
+    compact_many,
+    LogIndex,
+};
+#[cfg(feature = "zstd")]
+pub use compression::ZstdDictionary;
+#[cfg(feature = "std")]
+pub use compression::{
+    Compressor,
+    RecordCompressor,
+};
+pub use config::{
+    CharEncoding,
+    Config,
+    Features,
+    FORMAT_VERSION,
+};
+#[cfg(feature = "std")]
+pub use const_bytes::ConstBytes;
 pub use de::{
     from_bytes,
+    from_bytes_many,
+    from_bytes_partial,
+    peek_variant_index,
     Deserializer,
+    FilteredStreamDeserializer,
+    StreamDeserializer,
+};
+#[cfg(feature = "std")]
+pub use embed::write_serialized;
+#[cfg(feature = "std")]
+pub use envelope::{
+    AnyEnvelope,
+    Dispatcher,
 };
 pub use error::{
     Error,
     Result,
 };
+#[cfg(feature = "std")]
+pub use events::{
+    Event,
+    EventReader,
+    EventWriter,
+};
+pub use fingerprint::{
+    from_bytes_with_fingerprint,
+    to_bytes_with_fingerprint,
+    type_fingerprint,
+    FINGERPRINT_SIZE,
+};
+#[cfg(feature = "std")]
+pub use fixed::{
+    fixed_record_count,
+    fixed_record_offset,
+    from_fixed_records,
+    to_fixed_record,
+};
+#[cfg(feature = "std")]
+pub use format::{
+    decode,
+    encode,
+    Format,
+};
+#[cfg(any(feature = "asynchronous-codec", feature = "tokio-util"))]
+pub use frame_codec::SerializationCodec;
+#[cfg(feature = "std")]
+pub use handshake::Hello;
+#[cfg(feature = "std")]
+pub use hex_dump::{
+    to_bytes_hex_log,
+    HexDump,
+    HEX_LOG_BYTES,
+};
+pub use int_codec::{
+    IntCodec,
+    Varint,
+};
+#[cfg(feature = "embedded-io")]
+pub use io::{
+    EmbeddedInput,
+    EmbeddedOutput,
+};
+#[cfg(feature = "std")]
+pub use io::{
+    IoInput,
+    IoOutput,
+};
+pub use io::{
+    Input,
+    Output,
+};
+#[cfg(feature = "std")]
+pub use key::{
+    to_key,
+    KeyBuilder,
+};
+#[cfg(feature = "std")]
+pub use keyring::{
+    Cipher,
+    Encrypted,
+    Keyring,
+};
+#[cfg(feature = "sled")]
+pub use kv::SledTree;
+#[cfg(any(feature = "redb", feature = "sled"))]
+pub use kv::Stored;
+#[cfg(feature = "std")]
+pub use layout::{
+    describe,
+    LayoutEntry,
+    LayoutReport,
+    WireEncoding,
+};
+#[cfg(feature = "std")]
+pub use layout_fingerprint::LayoutFingerprint;
+#[cfg(feature = "std")]
+pub use max_size::MaxSerializedSize;
+#[cfg(feature = "std")]
+pub use migrations::Migrations;
+#[cfg(feature = "std")]
+pub use nested::Nested;
+#[cfg(feature = "std")]
+pub use pipeline::Pipeline;
+pub use projection::from_bytes_projected;
+#[cfg(feature = "python")]
+pub use python::{
+    add_python_functions,
+    value_from_python,
+    value_into_python,
+};
+#[cfg(feature = "std")]
+pub use raw::Raw;
+#[cfg(feature = "std")]
+pub use read::{
+    from_reader,
+    ReaderStreamDeserializer,
+};
+#[cfg(feature = "std")]
+pub use redacted::Redacted;
+#[cfg(feature = "std")]
+pub use registry::{
+    serialize_tagged,
+    to_bytes_tagged,
+    AllowList,
+    Named,
+    Registry,
+    Tagged,
+};
+pub use schema::{
+    Field,
+    Schema,
+    Variant,
+    VariantKind,
+};
+#[cfg(feature = "std")]
+pub use ser::to_writer;
 pub use ser::{
+    append_many,
     to_bytes,
+    to_bytes_from_iter,
+    to_bytes_from_pairs,
+    to_bytes_many,
+    to_slice,
     Serializer,
 };
+#[cfg(feature = "memmap2")]
+pub use shm::{
+    ShmReceiver,
+    ShmSender,
+};
+#[cfg(feature = "std")]
+pub use spill::{
+    SpillFile,
+    SpillOutput,
+    Spilled,
+};
+#[cfg(feature = "tokio")]
+pub use tokio_io::{
+    from_tokio_reader,
+    to_tokio_writer,
+};
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+pub use uring::UringOutput;
+#[cfg(feature = "std")]
+pub use value::{
+    from_value,
+    to_value,
+    Value,
+};
+#[cfg(feature = "std")]
+pub use versioned::{
+    from_bytes_versioned,
+    to_bytes_versioned,
+    VERSIONED_MAGIC,
+};
+#[cfg(any(feature = "axum", feature = "actix-web"))]
+pub use web::{
+    Serial,
+    SERIAL_CONTENT_TYPE,
+};
+#[cfg(feature = "std")]
+pub use wire::{
+    WireReader,
+    WireWriter,
+};
+
+#[cfg(feature = "derive")]
+pub use serialization_derive::{
+    serial,
+    serialization,
+    LayoutFingerprint,
+    MaxSerializedSize,
+};
+
+// These are used by code generated by the derive macros, and are not part of
+// the public interface of the crate.
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::{
+        codegen::runtime as codegen,
+        layout_fingerprint::{
+            enum_fingerprint,
+            fields_fingerprint,
+            fingerprint_add,
+            unit_fingerprint,
+            wire_fingerprint,
+        },
+        max_size::{
+            max,
+            varint_size,
+        },
+        variant_tags::{
+            TagDeserializer,
+            TagSerializer,
+        },
+    };
+    pub use serde;
+}
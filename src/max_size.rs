@@ -0,0 +1,382 @@
+use std::marker::PhantomData;
+
+/// This trait is implemented by types for which there is an upper bound,
+/// known at compile time, on the number of bytes the serializer of this
+/// crate can produce when encoding a value of the type.
+///
+/// Types which contain unbounded containers, such as `Vec` or `String`,
+/// cannot implement this trait.
+///
+/// The bound holds only for encodings made with the default [`Config`] and
+/// the [`Varint`] integer codec, such as by [`to_bytes`] or [`to_slice`].
+/// Options which add to the encoding, such as [self-describing] tags,
+/// [struct framing], field [ids] or [names], [variant names], and
+/// [deduplication], as well as integer codecs of one's own, can make an
+/// encoding longer than the bound, as does a fingerprint or version put in
+/// front of it.
+///
+/// With the `derive` feature enabled, this trait can be derived for structs
+/// and enums whose fields all implement it.  Since the derive only knows
+/// the types of fields, it refuses fields with `#[serde(flatten)]`,
+/// `#[serde(with = "...")]` or `#[serde(serialize_with = "...")]`,
+/// including those given encodings by [`serialization`], internally or
+/// adjacently tagged enums, and types encoded as other types with
+/// `#[serde(into = "...")]`.
+///
+/// [`Config`]: struct.Config.html
+/// [`Varint`]: struct.Varint.html
+/// [`to_bytes`]: fn.to_bytes.html
+/// [`to_slice`]: fn.to_slice.html
+/// [self-describing]: struct.Config.html#method.with_self_describing
+/// [struct framing]: struct.Config.html#method.with_struct_framing
+/// [ids]: struct.Config.html#method.with_field_ids
+/// [names]: struct.Config.html#method.with_field_names
+/// [variant names]: struct.Config.html#method.with_variant_names
+/// [deduplication]: struct.Config.html#method.with_deduplication
+/// [`serialization`]: attr.serialization.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// # #[cfg(feature = "derive")]
+/// # {
+/// use serialization::MaxSerializedSize;
+///
+/// #[derive(serde::Serialize, MaxSerializedSize)]
+/// struct Reading {
+///     sensor: u8,
+///     value: f32,
+/// }
+///
+/// const BUFFER_SIZE: usize = Reading::MAX_SERIALIZED_SIZE;
+/// assert_eq!(5, BUFFER_SIZE);
+/// # }
+/// ```
+pub trait MaxSerializedSize {
+    /// This is the largest number of bytes which can be produced by
+    /// encoding any value of the type.
+    const MAX_SERIALIZED_SIZE: usize;
+}
+
+/// Return the number of bytes needed to encode the given value using the
+/// variable-length encoding of unsigned integers used by this crate.
+#[must_use]
+pub const fn varint_size(mut v: u64) -> usize {
+    let mut size = 1;
+    while v > 0x7F {
+        v >>= 7;
+        size += 1;
+    }
+    size
+}
+
+/// Return the larger of two sizes.  This is provided for the benefit of the
+/// code generated by the `MaxSerializedSize` derive macro, since
+/// `std::cmp::max` cannot be used in constant expressions.
+#[must_use]
+pub const fn max(
+    a: usize,
+    b: usize,
+) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+macro_rules! impl_max_serialized_size {
+    ($($ty:ty => $size:expr),* $(,)?) => {
+        $(
+            impl MaxSerializedSize for $ty {
+                const MAX_SERIALIZED_SIZE: usize = $size;
+            }
+        )*
+    };
+}
+
+// Signed integers carry a sign bit and six bits of magnitude in their first
+// byte, and seven bits of magnitude in each byte after that.  The largest
+// magnitude is that of the minimum value, which is one more than the maximum.
+// Unsigned 128-bit integers take 19 bytes of seven bits each, as do signed
+// ones, whose largest magnitude takes 128 bits.
+impl_max_serialized_size! {
+    () => 0,
+    bool => 1,
+    i8 => 1,
+    i16 => 3,
+    i32 => 5,
+    i64 => 10,
+    i128 => 19,
+    isize => 10,
+    u8 => 1,
+    u16 => varint_size(u16::MAX as u64),
+    u32 => varint_size(u32::MAX as u64),
+    u64 => varint_size(u64::MAX),
+    u128 => 19,
+    usize => varint_size(u64::MAX),
+    f32 => 4,
+    f64 => 8,
+    char => 4,
+}
+
+impl<T> MaxSerializedSize for PhantomData<T>
+where
+    T: ?Sized,
+{
+    const MAX_SERIALIZED_SIZE: usize = 0;
+}
+
+impl<T> MaxSerializedSize for Option<T>
+where
+    T: MaxSerializedSize,
+{
+    const MAX_SERIALIZED_SIZE: usize = 1 + T::MAX_SERIALIZED_SIZE;
+}
+
+impl<T> MaxSerializedSize for &T
+where
+    T: ?Sized + MaxSerializedSize,
+{
+    const MAX_SERIALIZED_SIZE: usize = T::MAX_SERIALIZED_SIZE;
+}
+
+impl<T> MaxSerializedSize for Box<T>
+where
+    T: ?Sized + MaxSerializedSize,
+{
+    const MAX_SERIALIZED_SIZE: usize = T::MAX_SERIALIZED_SIZE;
+}
+
+impl<T, const N: usize> MaxSerializedSize for [T; N]
+where
+    T: MaxSerializedSize,
+{
+    const MAX_SERIALIZED_SIZE: usize = N * T::MAX_SERIALIZED_SIZE;
+}
+
+macro_rules! impl_max_serialized_size_tuple {
+    ($($name:ident)+) => {
+        impl<$($name),+> MaxSerializedSize for ($($name,)+)
+        where
+            $($name: MaxSerializedSize,)+
+        {
+            const MAX_SERIALIZED_SIZE: usize =
+                0 $(+ $name::MAX_SERIALIZED_SIZE)+;
+        }
+    };
+}
+
+impl_max_serialized_size_tuple! { T0 }
+impl_max_serialized_size_tuple! { T0 T1 }
+impl_max_serialized_size_tuple! { T0 T1 T2 }
+impl_max_serialized_size_tuple! { T0 T1 T2 T3 }
+impl_max_serialized_size_tuple! { T0 T1 T2 T3 T4 }
+impl_max_serialized_size_tuple! { T0 T1 T2 T3 T4 T5 }
+impl_max_serialized_size_tuple! { T0 T1 T2 T3 T4 T5 T6 }
+impl_max_serialized_size_tuple! { T0 T1 T2 T3 T4 T5 T6 T7 }
+impl_max_serialized_size_tuple! { T0 T1 T2 T3 T4 T5 T6 T7 T8 }
+impl_max_serialized_size_tuple! { T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 }
+impl_max_serialized_size_tuple! { T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 }
+impl_max_serialized_size_tuple! { T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 }
+impl_max_serialized_size_tuple! { T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12 }
+impl_max_serialized_size_tuple! {
+    T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12 T13
+}
+impl_max_serialized_size_tuple! {
+    T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12 T13 T14
+}
+impl_max_serialized_size_tuple! {
+    T0 T1 T2 T3 T4 T5 T6 T7 T8 T9 T10 T11 T12 T13 T14 T15
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        to_bytes,
+        MaxSerializedSize,
+    };
+
+    fn encoded_size<T>(value: &T) -> usize
+    where
+        T: serde::Serialize,
+    {
+        to_bytes(value).unwrap().len()
+    }
+
+    #[test]
+    fn varint_size_boundaries() {
+        for (value, expected) in &[
+            (0_u64, 1),
+            (0x7F, 1),
+            (0x80, 2),
+            (0x3FFF, 2),
+            (0x4000, 3),
+            (u64::MAX, 10),
+        ] {
+            assert_eq!(*expected, varint_size(*value));
+        }
+    }
+
+    #[test]
+    fn primitives_bound_their_extreme_values() {
+        assert_eq!(i16::MAX_SERIALIZED_SIZE, encoded_size(&i16::MIN));
+        assert_eq!(i32::MAX_SERIALIZED_SIZE, encoded_size(&i32::MIN));
+        assert_eq!(i64::MAX_SERIALIZED_SIZE, encoded_size(&i64::MIN));
+        assert_eq!(u16::MAX_SERIALIZED_SIZE, encoded_size(&u16::MAX));
+        assert_eq!(u32::MAX_SERIALIZED_SIZE, encoded_size(&u32::MAX));
+        assert_eq!(u64::MAX_SERIALIZED_SIZE, encoded_size(&u64::MAX));
+        assert_eq!(i128::MAX_SERIALIZED_SIZE, encoded_size(&i128::MIN));
+        assert_eq!(u128::MAX_SERIALIZED_SIZE, encoded_size(&u128::MAX));
+        assert_eq!(char::MAX_SERIALIZED_SIZE, encoded_size(&'💩'));
+    }
+
+    #[test]
+    fn compound_types() {
+        assert_eq!(6, <Option<u32>>::MAX_SERIALIZED_SIZE);
+        assert_eq!(12, <[u16; 4]>::MAX_SERIALIZED_SIZE);
+        assert_eq!(9, <(bool, f64)>::MAX_SERIALIZED_SIZE);
+    }
+
+    #[test]
+    fn tuples_of_up_to_sixteen_elements() {
+        type Wide = (
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u16,
+        );
+        assert_eq!(18, Wide::MAX_SERIALIZED_SIZE);
+        assert_eq!(
+            Wide::MAX_SERIALIZED_SIZE,
+            encoded_size(&(
+                0_u8,
+                0_u8,
+                0_u8,
+                0_u8,
+                0_u8,
+                0_u8,
+                0_u8,
+                0_u8,
+                0_u8,
+                0_u8,
+                0_u8,
+                0_u8,
+                0_u8,
+                0_u8,
+                0_u8,
+                u16::MAX
+            ))
+        );
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derive_struct() {
+        #[derive(serde::Serialize, MaxSerializedSize)]
+        struct Foo {
+            bar: u8,
+            baz: Option<i32>,
+            #[serde(skip)]
+            _ignored: String,
+        }
+        assert_eq!(7, Foo::MAX_SERIALIZED_SIZE);
+        assert_eq!(
+            Foo::MAX_SERIALIZED_SIZE,
+            encoded_size(&Foo {
+                bar: 1,
+                baz: Some(i32::MIN),
+                _ignored: String::from("not encoded"),
+            })
+        );
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derive_generic_tuple_struct() {
+        #[derive(MaxSerializedSize)]
+        struct Pair<T>(T, T);
+        assert_eq!(20, <Pair<u64>>::MAX_SERIALIZED_SIZE);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derive_enum() {
+        #[derive(serde::Serialize, MaxSerializedSize)]
+        enum Message {
+            _Ping,
+            _Data([u8; 3]),
+            Point {
+                x: i64,
+                y: i64,
+            },
+        }
+        assert_eq!(21, Message::MAX_SERIALIZED_SIZE);
+        assert_eq!(
+            Message::MAX_SERIALIZED_SIZE,
+            encoded_size(&Message::Point {
+                x: i64::MIN,
+                y: i64::MIN,
+            })
+        );
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derive_enum_with_skipped_variants() {
+        macro_rules! wide_enum {
+            ($($skipped:ident)*) => {
+                #[derive(serde::Serialize, MaxSerializedSize)]
+                enum Wide {
+                    $(
+                        #[serde(skip)]
+                        #[allow(dead_code)]
+                        $skipped,
+                    )*
+                    Last,
+                }
+            };
+        }
+        wide_enum!(
+            S0 S1 S2 S3 S4 S5 S6 S7 S8 S9 S10 S11 S12 S13 S14 S15 S16 S17 S18
+            S19 S20 S21 S22 S23 S24 S25 S26 S27 S28 S29 S30 S31 S32 S33 S34
+            S35 S36 S37 S38 S39 S40 S41 S42 S43 S44 S45 S46 S47 S48 S49 S50
+            S51 S52 S53 S54 S55 S56 S57 S58 S59 S60 S61 S62 S63 S64 S65 S66
+            S67 S68 S69 S70 S71 S72 S73 S74 S75 S76 S77 S78 S79 S80 S81 S82
+            S83 S84 S85 S86 S87 S88 S89 S90 S91 S92 S93 S94 S95 S96 S97 S98
+            S99 S100 S101 S102 S103 S104 S105 S106 S107 S108 S109 S110 S111
+            S112 S113 S114 S115 S116 S117 S118 S119 S120 S121 S122 S123 S124
+            S125 S126 S127
+        );
+        assert_eq!(2, Wide::MAX_SERIALIZED_SIZE);
+        assert_eq!(Wide::MAX_SERIALIZED_SIZE, encoded_size(&Wide::Last));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn derive_enum_with_tags() {
+        #[crate::serialization]
+        #[derive(serde::Serialize, MaxSerializedSize)]
+        enum Tagged {
+            #[serialization(tag = 1000)]
+            Old(u8),
+            _New,
+        }
+        assert_eq!(3, Tagged::MAX_SERIALIZED_SIZE);
+        assert_eq!(Tagged::MAX_SERIALIZED_SIZE, encoded_size(&Tagged::Old(7)));
+    }
+}
@@ -0,0 +1,279 @@
+use super::{
+    from_bytes,
+    to_bytes,
+    to_bytes_versioned,
+    versioned::strip_version,
+    Error,
+    Result,
+};
+use std::{
+    collections::HashMap,
+    marker::PhantomData,
+};
+
+// This upgrades the encoding of a value from one version to the encoding of
+// the value in the next version.
+type Migration = Box<dyn Fn(&[u8]) -> Result<Vec<u8>> + Send + Sync>;
+
+/// This is a table of functions which upgrade values encoded by
+/// [`to_bytes_versioned`] from each version of a type to the next, used to
+/// load values of type `T`, which is the latest version, from encodings of
+/// any earlier version.  Since the earlier versions of the type are usually
+/// different types, each function takes the encoding of a value in one
+/// version and returns its encoding in the next.
+///
+/// Migrations are added using [`register`], which decodes and encodes
+/// the values on either side of a function upgrading one version of the
+/// type to the next, or [`register_bytes`], which works on encodings
+/// directly.
+///
+/// [`to_bytes_versioned`]: fn.to_bytes_versioned.html
+/// [`register`]: #method.register
+/// [`register_bytes`]: #method.register_bytes
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     to_bytes_versioned,
+///     Migrations,
+/// };
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct SettingsV1 {
+///     volume: u8,
+/// }
+///
+/// #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+/// struct Settings {
+///     volume: u8,
+///     muted: bool,
+/// }
+///
+/// let mut migrations = Migrations::<Settings>::new(2);
+/// migrations.register(1, |old: SettingsV1| Settings {
+///     volume: old.volume,
+///     muted: old.volume == 0,
+/// });
+///
+/// let saved = to_bytes_versioned(
+///     &SettingsV1 {
+///         volume: 0,
+///     },
+///     1,
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     Settings {
+///         volume: 0,
+///         muted: true,
+///     },
+///     migrations.load(&saved).unwrap()
+/// );
+/// ```
+pub struct Migrations<T> {
+    upgrades: HashMap<u32, Migration>,
+    output: PhantomData<fn() -> T>,
+    version: u32,
+}
+
+impl<T> Migrations<T> {
+    /// Decode a value of type `T` from an encoding made by
+    /// [`to_bytes_versioned`], first upgrading it, one version at a time,
+    /// if it is of an earlier version than that of type `T`.
+    ///
+    /// [`to_bytes_versioned`]: fn.to_bytes_versioned.html
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::VersionTooNew`] if the encoding is of a later
+    /// version than that of type `T`, [`Error::MigrationMissing`] if no
+    /// migration has been registered for one of the versions between the
+    /// two, and otherwise any error from [`from_bytes_versioned`] or any of
+    /// the migrations.
+    ///
+    /// [`Error::VersionTooNew`]: enum.Error.html#variant.VersionTooNew
+    /// [`Error::MigrationMissing`]: enum.Error.html#variant.MigrationMissing
+    /// [`from_bytes_versioned`]: fn.from_bytes_versioned.html
+    pub fn load(
+        &self,
+        bytes: &[u8],
+    ) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let (version, payload) = strip_version(bytes)?;
+        if version > self.version {
+            return Err(Error::VersionTooNew(version));
+        }
+        if version == self.version {
+            return from_bytes(payload);
+        }
+        let mut payload = payload.to_vec();
+        for version in version..self.version {
+            let migration = self
+                .upgrades
+                .get(&version)
+                .ok_or(Error::MigrationMissing(version))?;
+            payload = migration(&payload)?;
+        }
+        from_bytes(&payload)
+    }
+
+    /// Return a new, empty table of migrations to the given version, which
+    /// is the version of type `T`.
+    #[must_use]
+    pub fn new(version: u32) -> Self {
+        Self {
+            upgrades: HashMap::new(),
+            output: PhantomData,
+            version,
+        }
+    }
+
+    /// Add the given function, which upgrades a value of type `Old`, which
+    /// is the given version of the type, to a value of type `New`, which is
+    /// the version after it.
+    pub fn register<Old, New, F>(
+        &mut self,
+        version: u32,
+        upgrade: F,
+    ) where
+        Old: serde::de::DeserializeOwned,
+        New: serde::Serialize,
+        F: Fn(Old) -> New + Send + Sync + 'static,
+    {
+        self.register_bytes(version, move |payload| {
+            to_bytes(&upgrade(from_bytes(payload)?))
+        });
+    }
+
+    /// Add the given function, which upgrades the encoding of a value in
+    /// the given version of the type to the encoding of the value in the
+    /// version after it.
+    pub fn register_bytes<F>(
+        &mut self,
+        version: u32,
+        upgrade: F,
+    ) where
+        F: Fn(&[u8]) -> Result<Vec<u8>> + Send + Sync + 'static,
+    {
+        self.upgrades.insert(version, Box::new(upgrade));
+    }
+
+    /// Encode the given value with [`to_bytes_versioned`], preceded by the
+    /// version of type `T`.
+    ///
+    /// [`to_bytes_versioned`]: fn.to_bytes_versioned.html
+    ///
+    /// # Errors
+    ///
+    /// Any error from [`to_bytes_versioned`] is returned.
+    pub fn save(
+        &self,
+        value: &T,
+    ) -> Result<Vec<u8>>
+    where
+        T: serde::Serialize,
+    {
+        to_bytes_versioned(value, self.version)
+    }
+
+    /// Return the version of type `T`, which values are upgraded to.
+    #[must_use]
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct PointV1 {
+        x: u8,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct PointV2 {
+        x: u8,
+        y: u8,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
+    struct Point {
+        x: u16,
+        y: u16,
+    }
+
+    fn migrations() -> Migrations<Point> {
+        let mut migrations = Migrations::new(3);
+        migrations.register(1, |old: PointV1| PointV2 {
+            x: old.x,
+            y: 0,
+        });
+        migrations.register(2, |old: PointV2| Point {
+            x: u16::from(old.x),
+            y: u16::from(old.y),
+        });
+        migrations
+    }
+
+    #[test]
+    fn load_from_every_version() {
+        let migrations = migrations();
+        let expected = Point {
+            x: 5,
+            y: 0,
+        };
+        let saved = to_bytes_versioned(
+            &PointV1 {
+                x: 5,
+            },
+            1,
+        )
+        .unwrap();
+        let loaded = migrations.load(&saved);
+        assert!(loaded.is_ok());
+        assert_eq!(expected, loaded.unwrap());
+        let saved = to_bytes_versioned(
+            &PointV2 {
+                x: 5,
+                y: 0,
+            },
+            2,
+        )
+        .unwrap();
+        assert_eq!(expected, migrations.load(&saved).unwrap());
+        let saved = migrations.save(&expected);
+        assert!(saved.is_ok());
+        let saved = saved.unwrap();
+        assert_eq!(expected, migrations.load(&saved).unwrap());
+    }
+
+    #[test]
+    fn missing_migrations_are_reported() {
+        let mut migrations = Migrations::<Point>::new(3);
+        migrations.register_bytes(2, |payload| Ok(payload.to_vec()));
+        let saved = to_bytes_versioned(&(1_u8, 2_u8), 1).unwrap();
+        assert!(matches!(
+            migrations.load(&saved),
+            Err(Error::MigrationMissing(1))
+        ));
+        let saved = to_bytes_versioned(&(1_u8, 2_u8), 4).unwrap();
+        assert!(matches!(
+            migrations.load(&saved),
+            Err(Error::VersionTooNew(4))
+        ));
+        let saved = to_bytes_versioned(&(1_u8, 2_u8), 2).unwrap();
+        assert_eq!(
+            Point {
+                x: 1,
+                y: 2,
+            },
+            migrations.load(&saved).unwrap()
+        );
+    }
+}
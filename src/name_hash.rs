@@ -0,0 +1,20 @@
+// This is a 32-bit FNV-1a hash of a name, such as that of a field, used to
+// identify it in encodings, chosen because it is simple and produces the
+// same results on every platform and in every release.
+pub(crate) fn name_hash(name: &str) -> u32 {
+    name.bytes().fold(0x811C_9DC5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_match_fnv1a() {
+        assert_eq!(0x811C_9DC5, name_hash(""));
+        assert_eq!(0xE40C_292C, name_hash("a"));
+        assert_eq!(0xBF9C_F968, name_hash("foobar"));
+    }
+}
@@ -0,0 +1,178 @@
+use super::{
+    from_bytes,
+    registry::Bytes,
+    to_bytes,
+    trace::NESTED_NAME,
+};
+use serde::Deserialize;
+use std::{
+    fmt,
+    marker::PhantomData,
+};
+
+/// This wraps a value which is encoded separately and placed in the
+/// enclosing value as a byte string, prefixed with its length.  Code which
+/// decodes the enclosing value can then skip over the nested value without
+/// knowing its type, and intermediaries can lift the encoding of the nested
+/// value out, by decoding it as a byte string (such as `&[u8]` or
+/// `Vec<u8>`), and pass it on to be decoded independently.
+///
+/// The nested value is always encoded with the serializer of this crate,
+/// whatever serializer encodes the enclosing value.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_bytes,
+///     to_bytes,
+///     Nested,
+/// };
+///
+/// #[derive(serde::Serialize)]
+/// struct Envelope {
+///     destination: String,
+///     body: Nested<(u32, String)>,
+/// }
+///
+/// // A router needs only the destination, and passes the body on as is.
+/// #[derive(serde::Deserialize)]
+/// struct Routing<'a> {
+///     destination: String,
+///     body: &'a [u8],
+/// }
+///
+/// let message = to_bytes(&Envelope {
+///     destination: String::from("billing"),
+///     body: Nested((42, String::from("invoice"))),
+/// })
+/// .unwrap();
+/// let routing: Routing = from_bytes(&message).unwrap();
+/// assert_eq!("billing", routing.destination);
+/// let body: (u32, String) = from_bytes(routing.body).unwrap();
+/// assert_eq!((42, String::from("invoice")), body);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Nested<T>(pub T);
+
+impl<T> serde::Serialize for Nested<T>
+where
+    T: serde::Serialize,
+{
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let encoding = to_bytes(&self.0).map_err(serde::ser::Error::custom)?;
+        serde::Serialize::serialize(&Bytes(&encoding), serializer)
+    }
+}
+
+impl<'de, T> serde::Deserialize<'de> for Nested<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor<T>(PhantomData<T>);
+
+        impl<'de, T> serde::de::Visitor<'de> for Visitor<T>
+        where
+            T: serde::Deserialize<'de>,
+        {
+            type Value = Nested<T>;
+
+            fn expecting(
+                &self,
+                f: &mut fmt::Formatter,
+            ) -> fmt::Result {
+                write!(f, "the encoding of a nested value")
+            }
+
+            fn visit_newtype_struct<D>(
+                self,
+                deserializer: D,
+            ) -> std::result::Result<Self::Value, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                let encoding = <&'de [u8]>::deserialize(deserializer)?;
+                from_bytes(encoding)
+                    .map(Nested)
+                    .map_err(serde::de::Error::custom)
+            }
+
+            // This is used only when tracing the schema of the type, to
+            // obtain a placeholder for the nested value.
+            fn visit_seq<A>(
+                self,
+                mut seq: A,
+            ) -> std::result::Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                seq.next_element()?
+                    .map(Nested)
+                    .ok_or_else(|| serde::de::Error::invalid_length(0, &self))
+            }
+        }
+
+        deserializer
+            .deserialize_newtype_struct(NESTED_NAME, Visitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        Error,
+        Schema,
+    };
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Message<'a> {
+        id: u8,
+        #[serde(borrow)]
+        body: Nested<(&'a str, i16)>,
+        trailer: bool,
+    }
+
+    #[test]
+    fn nested_values_are_length_prefixed() {
+        let message = Message {
+            id: 9,
+            body: Nested(("hi", -3)),
+            trailer: true,
+        };
+        let encoding = to_bytes(&message);
+        assert!(encoding.is_ok());
+        let encoding = encoding.unwrap();
+        assert_eq!(vec![9, 4, 2, b'h', b'i', 0x43, 1], encoding);
+        let decoded = from_bytes::<Message>(&encoding);
+        assert!(decoded.is_ok());
+        let decoded = decoded.unwrap();
+        assert_eq!(message, decoded);
+    }
+
+    #[test]
+    fn nested_values_are_skipped_as_bytes() {
+        let encoding = to_bytes(&(Nested(vec![1_u16, 2, 300]), 5_u8)).unwrap();
+        let (body, after) = from_bytes::<(&[u8], u8)>(&encoding).unwrap();
+        assert_eq!(5, after);
+        assert_eq!(vec![1_u16, 2, 300], from_bytes::<Vec<u16>>(body).unwrap());
+        assert_eq!(Schema::Bytes, Schema::of::<Nested<Vec<u16>>>().unwrap());
+    }
+
+    #[test]
+    fn damaged_nested_value() {
+        let decoded = from_bytes::<Nested<(u8, u8)>>(&[1, 7]);
+        assert!(matches!(decoded, Err(Error::Message(_))));
+    }
+}
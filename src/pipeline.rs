@@ -0,0 +1,279 @@
+use super::{
+    to_bytes,
+    Error,
+    Result,
+};
+use std::{
+    io::Write,
+    sync::mpsc::sync_channel,
+    thread,
+};
+
+// This is the type of the stage used to process encoded chunks when no
+// other stage is given, which passes them through unchanged.
+type Passthrough = fn(Vec<u8>) -> Result<Vec<u8>>;
+
+/// This is used to encode a large number of values and write them out,
+/// running the encoding, an optional processing stage (such as
+/// compression), and the writing on separate threads, connected by bounded
+/// queues.  This keeps the CPU busy encoding while earlier values are still
+/// being written, rather than alternating between the two, while limiting
+/// how much encoded data can pile up waiting to be written.
+///
+/// Values are encoded in order, one after another, as with [`to_bytes`],
+/// and gathered into chunks of at least the chunk size (except for the
+/// last chunk) before being passed to the processing stage.  Whatever the
+/// processing stage returns for each chunk is written out, in order.
+///
+/// [`to_bytes`]: fn.to_bytes.html
+pub struct Pipeline<C> {
+    chunk_size: usize,
+    queue_depth: usize,
+    stage: C,
+}
+
+impl Pipeline<Passthrough> {
+    /// This is the default size, in bytes, of the chunks of encoded values
+    /// passed between the stages of a pipeline.
+    pub const DEFAULT_CHUNK_SIZE: usize = 65536;
+    /// This is the default number of chunks which may be queued between the
+    /// stages of a pipeline before the earlier stage waits for the later one
+    /// to catch up.
+    pub const DEFAULT_QUEUE_DEPTH: usize = 4;
+
+    /// Return a new pipeline with the default chunk size and queue depth,
+    /// which writes out encoded values without processing them further.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            chunk_size: Self::DEFAULT_CHUNK_SIZE,
+            queue_depth: Self::DEFAULT_QUEUE_DEPTH,
+            stage: Ok,
+        }
+    }
+}
+
+impl Default for Pipeline<Passthrough> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> Pipeline<C>
+where
+    C: FnMut(Vec<u8>) -> Result<Vec<u8>> + Send,
+{
+    /// This function is used to encode the given values and write them to
+    /// the given writer through the pipeline, returning the writer once
+    /// everything has been written and flushed.
+    ///
+    /// # Errors
+    ///
+    /// Any of the errors returned by [`to_bytes`] may be returned from
+    /// encoding the values, and any errors returned by the processing stage
+    /// are passed through.  [`Error::Io`] is returned if the writer fails.
+    /// Once any stage fails, the other stages stop.
+    ///
+    /// [`to_bytes`]: fn.to_bytes.html
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    pub fn run<I, W>(
+        self,
+        values: I,
+        mut writer: W,
+    ) -> Result<W>
+    where
+        I: IntoIterator,
+        I::Item: serde::Serialize,
+        W: Write + Send,
+    {
+        let Self {
+            chunk_size,
+            queue_depth,
+            mut stage,
+        } = self;
+        let (chunk_sender, chunk_receiver) =
+            sync_channel::<Vec<u8>>(queue_depth);
+        let (output_sender, output_receiver) =
+            sync_channel::<Vec<u8>>(queue_depth);
+        thread::scope(|scope| {
+            let processor = scope.spawn(move || -> Result<()> {
+                for chunk in chunk_receiver {
+                    if output_sender.send(stage(chunk)?).is_err() {
+                        break;
+                    }
+                }
+                Ok(())
+            });
+            let outputter = scope.spawn(move || -> Result<W> {
+                for output in output_receiver {
+                    writer.write_all(&output).map_err(Error::Io)?;
+                }
+                writer.flush().map_err(Error::Io)?;
+                Ok(writer)
+            });
+
+            // A failure to send means a later stage has stopped, and the
+            // reason is reported by that stage.
+            let encoded = (|| -> Result<()> {
+                let mut chunk = Vec::new();
+                for value in values {
+                    chunk.extend(to_bytes(&value)?);
+                    if chunk.len() >= chunk_size
+                        && chunk_sender
+                            .send(std::mem::take(&mut chunk))
+                            .is_err()
+                    {
+                        return Ok(());
+                    }
+                }
+                if !chunk.is_empty() {
+                    let _ = chunk_sender.send(chunk);
+                }
+                Ok(())
+            })();
+            drop(chunk_sender);
+            let processed = processor
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            let written = outputter
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+            encoded?;
+            processed?;
+            written
+        })
+    }
+
+    /// Set the size, in bytes, which chunks of encoded values reach before
+    /// being passed to the processing stage.
+    #[must_use]
+    pub fn with_chunk_size(
+        mut self,
+        chunk_size: usize,
+    ) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    /// Set the number of chunks which may be queued between the stages of
+    /// the pipeline before the earlier stage waits for the later one to
+    /// catch up.
+    #[must_use]
+    pub fn with_queue_depth(
+        mut self,
+        queue_depth: usize,
+    ) -> Self {
+        self.queue_depth = queue_depth;
+        self
+    }
+
+    /// Return the pipeline with the given processing stage, which is
+    /// called on its own thread for each chunk of encoded values, and
+    /// returns the bytes to write out in place of the chunk.
+    #[must_use]
+    pub fn with_stage<D>(
+        self,
+        stage: D,
+    ) -> Pipeline<D>
+    where
+        D: FnMut(Vec<u8>) -> Result<Vec<u8>> + Send,
+    {
+        Pipeline {
+            chunk_size: self.chunk_size,
+            queue_depth: self.queue_depth,
+            stage,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::FaultyWriter,
+        Deserializer,
+    };
+
+    struct Encodable(bool);
+
+    impl serde::Serialize for Encodable {
+        fn serialize<S>(
+            &self,
+            serializer: S,
+        ) -> std::result::Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            if self.0 {
+                serializer.serialize_unit()
+            } else {
+                Err(serde::ser::Error::custom("not encodable"))
+            }
+        }
+    }
+
+    fn values() -> Vec<(u32, String)> {
+        (0..1000).map(|i| (i * 7, format!("value {i}"))).collect()
+    }
+
+    #[test]
+    fn pipeline_encodes_in_order() {
+        let written =
+            Pipeline::new().with_chunk_size(100).run(values(), Vec::new());
+        assert!(written.is_ok());
+        let written = written.unwrap();
+        let decoded = Deserializer::new(&written)
+            .iterate::<(u32, String)>()
+            .collect::<Result<Vec<_>>>();
+        assert!(decoded.is_ok());
+        let decoded = decoded.unwrap();
+        assert_eq!(values(), decoded);
+    }
+
+    #[test]
+    fn pipeline_stage_processes_chunks() {
+        // Prefix each chunk with its length, and check that every chunk but
+        // the last reached the chunk size.
+        let written = Pipeline::new()
+            .with_chunk_size(256)
+            .with_queue_depth(1)
+            .with_stage(|chunk| to_bytes(&chunk))
+            .run(values(), Vec::new());
+        assert!(written.is_ok());
+        let written = written.unwrap();
+        let chunks = Deserializer::new(&written)
+            .iterate::<Vec<u8>>()
+            .collect::<Result<Vec<_>>>();
+        assert!(chunks.is_ok());
+        let chunks = chunks.unwrap();
+        let (_, rest) = chunks.split_last().unwrap();
+        assert!(!rest.is_empty());
+        assert!(rest.iter().all(|chunk| chunk.len() >= 256));
+        let mut decoded = Vec::new();
+        for chunk in &chunks {
+            decoded.extend(
+                Deserializer::new(chunk)
+                    .iterate::<(u32, String)>()
+                    .map(Result::unwrap),
+            );
+        }
+        assert_eq!(values(), decoded);
+    }
+
+    #[test]
+    fn pipeline_failures_are_reported() {
+        let written = Pipeline::new().with_chunk_size(16).run(
+            values(),
+            FaultyWriter::new(Vec::new()).with_failure_after(100),
+        );
+        assert!(matches!(written, Err(Error::Io(_))));
+        let written = Pipeline::new()
+            .with_chunk_size(16)
+            .with_stage(|_| Err(Error::RecordSizeExceeded))
+            .run(values(), Vec::new());
+        assert!(matches!(written, Err(Error::RecordSizeExceeded)));
+        let written = Pipeline::new()
+            .run(vec![Encodable(true), Encodable(false)], Vec::new());
+        assert!(matches!(written, Err(Error::Message(_))));
+    }
+}
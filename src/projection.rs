@@ -0,0 +1,550 @@
+use super::{
+    skip::Skipper,
+    Deserializer,
+    Error,
+    Field,
+    Result,
+    Schema,
+};
+use alloc::string::String;
+use serde::de::IntoDeserializer;
+
+// This deserializer decodes a struct described by a schema as a projection
+// type, which has only some of the fields of the struct.  The fields of the
+// projection are decoded from wherever they appear in the encoding, and
+// every other field is skipped without being decoded.
+struct Projection<'a, 'de, 's> {
+    de: &'a mut Deserializer<'de>,
+    record: &'s Schema,
+}
+
+struct FieldAccess<'a, 'de, 's> {
+    de: &'a mut Deserializer<'de>,
+    skipper: Skipper<'s>,
+    fields: &'s [Field],
+    wanted: &'static [&'static str],
+    index: usize,
+}
+
+// This deserializer decodes the value of a field of a projection, guided by
+// the schema of the field.  Structs are decoded as projections themselves,
+// if they are wanted with fewer or differently ordered fields than they
+// have, and values which are ignored are skipped without being decoded.
+// Everything else is decoded as usual.
+struct FieldValue<'a, 'de, 's> {
+    de: &'a mut Deserializer<'de>,
+    schema: &'s Schema,
+    skipper: &'a Skipper<'s>,
+}
+
+// Decode the struct described by the given schema, whose fields are
+// described by the given skipper, as a projection having the given fields.
+fn project_fields<'de, 's, V>(
+    de: &mut Deserializer<'de>,
+    skipper: Skipper<'s>,
+    record: &'s Schema,
+    wanted: &'static [&'static str],
+    visitor: V,
+) -> Result<V::Value>
+where
+    V: serde::de::Visitor<'de>,
+{
+    let fields = match record {
+        Schema::Struct {
+            fields,
+            ..
+        } => fields.as_slice(),
+        _ => &[],
+    };
+    if let Some(missing) = wanted
+        .iter()
+        .find(|name| !fields.iter().any(|field| field.name == **name))
+    {
+        return Err(Error::UnknownField(String::from(*missing)));
+    }
+    visitor.visit_map(FieldAccess {
+        de,
+        skipper,
+        fields,
+        wanted,
+        index: 0,
+    })
+}
+
+impl<'de> serde::de::MapAccess<'de> for FieldAccess<'_, 'de, '_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>>
+    where
+        K: serde::de::DeserializeSeed<'de>,
+    {
+        while let Some(field) = self.fields.get(self.index) {
+            self.index += 1;
+            if self.wanted.contains(&field.name) {
+                return seed
+                    .deserialize(field.name.into_deserializer())
+                    .map(Some);
+            }
+            self.skipper.skip(self.de, &field.schema)?;
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<V>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        let schema = &self.fields[self.index - 1].schema;
+        seed.deserialize(FieldValue {
+            de: &mut *self.de,
+            schema,
+            skipper: &self.skipper,
+        })
+    }
+}
+
+impl<'de> serde::Deserializer<'de> for Projection<'_, 'de, '_> {
+    type Error = Error;
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier
+    }
+
+    fn deserialize_any<V>(
+        self,
+        _visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        Err(Error::TypeUnknown)
+    }
+
+    fn deserialize_ignored_any<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        Skipper::new(self.record).skip(self.de, self.record)?;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        wanted: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        project_fields(
+            self.de,
+            Skipper::new(self.record),
+            self.record,
+            wanted,
+            visitor,
+        )
+    }
+}
+
+macro_rules! forward_field_value {
+    ($($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method<V>(
+                self,
+                $($arg: $ty,)*
+                visitor: V,
+            ) -> Result<V::Value>
+            where
+                V: serde::de::Visitor<'de>,
+            {
+                serde::Deserializer::$method(self.de, $($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+impl<'de> serde::Deserializer<'de> for FieldValue<'_, 'de, '_> {
+    type Error = Error;
+
+    forward_field_value! {
+        deserialize_any();
+        deserialize_bool();
+        deserialize_i8();
+        deserialize_i16();
+        deserialize_i32();
+        deserialize_i64();
+        deserialize_i128();
+        deserialize_u8();
+        deserialize_u16();
+        deserialize_u32();
+        deserialize_u64();
+        deserialize_u128();
+        deserialize_f32();
+        deserialize_f64();
+        deserialize_char();
+        deserialize_str();
+        deserialize_string();
+        deserialize_bytes();
+        deserialize_byte_buf();
+        deserialize_unit();
+        deserialize_unit_struct(name: &'static str);
+        deserialize_newtype_struct(name: &'static str);
+        deserialize_seq();
+        deserialize_tuple(len: usize);
+        deserialize_tuple_struct(name: &'static str, len: usize);
+        deserialize_map();
+        deserialize_enum(
+            name: &'static str,
+            variants: &'static [&'static str]
+        );
+        deserialize_identifier();
+    }
+
+    fn deserialize_ignored_any<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.skipper.clone().skip(self.de, self.schema)?;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_option<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let Schema::Option(inner) = self.skipper.resolve(self.schema)? else {
+            return serde::Deserializer::deserialize_option(self.de, visitor);
+        };
+        match self.de.parse_option()? {
+            Some(de) => visitor.visit_some(FieldValue {
+                de,
+                schema: inner,
+                skipper: self.skipper,
+            }),
+            None => visitor.visit_none(),
+        }
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        wanted: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let record = self.skipper.resolve(self.schema)?;
+        match record {
+            Schema::Struct {
+                fields,
+                ..
+            } if !fields
+                .iter()
+                .map(|field| field.name)
+                .eq(wanted.iter().copied()) =>
+            {
+                project_fields(
+                    self.de,
+                    self.skipper.within(record),
+                    record,
+                    wanted,
+                    visitor,
+                )
+            },
+            _ => serde::Deserializer::deserialize_struct(
+                self.de, name, wanted, visitor,
+            ),
+        }
+    }
+}
+
+pub(crate) fn project<'de, T>(
+    deserializer: &mut Deserializer<'de>,
+    record: &Schema,
+) -> Result<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    T::deserialize(Projection {
+        de: deserializer,
+        record,
+    })
+}
+
+/// This function is used to decode a struct, described by the given
+/// schema, as a projection type having only some of its fields, with the
+/// same names.  Only the fields of the projection type are decoded; every
+/// other field of the struct is skipped over without being decoded, so
+/// reading a few fields of a large record avoids the cost of decoding the
+/// rest.  The same goes for fields of the projection which are themselves
+/// structs, or optional structs, wanted with only some of their fields, and
+/// for fields decoded as [`IgnoredAny`], which are skipped.
+///
+/// [`IgnoredAny`]: https://docs.rs/serde/1.0/serde/de/struct.IgnoredAny.html
+///
+/// # Errors
+///
+/// This function returns [`Error::UnknownField`] if the schema does not
+/// describe a struct having every field of the projection type, and
+/// [`Error::TypeUnknown`] if the projection type is not a struct.
+/// Otherwise, any of the errors returned by [`from_bytes`] may be returned
+/// from decoding the fields of the projection or from the fields skipped
+/// over.
+///
+/// [`Error::UnknownField`]: enum.Error.html#variant.UnknownField
+/// [`Error::TypeUnknown`]: enum.Error.html#variant.TypeUnknown
+/// [`from_bytes`]: fn.from_bytes.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// # extern crate serde;
+/// use serialization::{
+///     from_bytes_projected,
+///     to_bytes,
+///     Schema,
+/// };
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Order {
+///     id: u64,
+///     customer: String,
+///     items: Vec<String>,
+///     total: u32,
+/// }
+///
+/// #[derive(serde::Deserialize, Debug, PartialEq)]
+/// struct Summary {
+///     total: u32,
+///     id: u64,
+/// }
+///
+/// let order = Order {
+///     id: 42,
+///     customer: String::from("Alice"),
+///     items: vec![String::from("book"), String::from("pen")],
+///     total: 1250,
+/// };
+/// let encoding = to_bytes(&order).unwrap();
+/// let schema = Schema::of::<Order>().unwrap();
+/// let summary: Summary = from_bytes_projected(&schema, &encoding).unwrap();
+/// assert_eq!(
+///     Summary {
+///         total: 1250,
+///         id: 42,
+///     },
+///     summary
+/// );
+/// ```
+pub fn from_bytes_projected<'de, T>(
+    record: &Schema,
+    bytes: &'de [u8],
+) -> Result<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    let mut deserializer = Deserializer::new(bytes);
+    project(&mut deserializer, record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::to_bytes;
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    enum Kind {
+        Plain,
+        Tagged(Vec<String>),
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Event {
+        id: u32,
+        source: String,
+        kind: Kind,
+        payload: Vec<u8>,
+        severity: i8,
+        parent: Option<Box<Event>>,
+        timestamp: u64,
+    }
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct Header<'a> {
+        timestamp: u64,
+        source: &'a str,
+        severity: i8,
+    }
+
+    #[derive(serde::Deserialize, Debug, PartialEq)]
+    struct WithDefault {
+        id: u32,
+        #[serde(default)]
+        severity: i8,
+    }
+
+    fn event() -> Event {
+        Event {
+            id: 7,
+            source: String::from("disk"),
+            kind: Kind::Tagged(vec![String::from("io")]),
+            payload: vec![1, 2, 3],
+            severity: -2,
+            parent: Some(Box::new(Event {
+                id: 6,
+                source: String::from("bus"),
+                kind: Kind::Plain,
+                payload: vec![],
+                severity: 0,
+                parent: None,
+                timestamp: 99,
+            })),
+            timestamp: 100,
+        }
+    }
+
+    #[test]
+    fn decode_projection() {
+        let schema = Schema::of::<Event>().unwrap();
+        let bytes = to_bytes(&event()).unwrap();
+        let header = from_bytes_projected::<Header>(&schema, &bytes);
+        assert!(header.is_ok());
+        let header = header.unwrap();
+        assert_eq!(
+            Header {
+                timestamp: 100,
+                source: "disk",
+                severity: -2,
+            },
+            header
+        );
+        let with_default = from_bytes_projected::<WithDefault>(&schema, &bytes);
+        assert!(with_default.is_ok());
+        let with_default = with_default.unwrap();
+        assert_eq!(
+            WithDefault {
+                id: 7,
+                severity: -2,
+            },
+            with_default
+        );
+    }
+
+    #[test]
+    fn nested_and_ignored_fields() {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Parent {
+            timestamp: u64,
+        }
+
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Lineage {
+            kind: serde::de::IgnoredAny,
+            parent: Option<Box<Parent>>,
+            id: u32,
+        }
+
+        let schema = Schema::of::<Event>().unwrap();
+        let bytes = to_bytes(&event()).unwrap();
+        let lineage = from_bytes_projected::<Lineage>(&schema, &bytes);
+        assert!(lineage.is_ok());
+        let lineage = lineage.unwrap();
+        assert_eq!(
+            Lineage {
+                kind: serde::de::IgnoredAny,
+                parent: Some(Box::new(Parent {
+                    timestamp: 99,
+                })),
+                id: 7,
+            },
+            lineage
+        );
+        assert!(matches!(
+            from_bytes_projected::<serde::de::IgnoredAny>(&schema, &bytes),
+            Ok(serde::de::IgnoredAny)
+        ));
+    }
+
+    #[test]
+    fn projection_of_unknown_field() {
+        #[derive(serde::Deserialize, Debug)]
+        struct Other {
+            #[allow(dead_code)]
+            name: String,
+        }
+
+        let schema = Schema::of::<Event>().unwrap();
+        let bytes = to_bytes(&event()).unwrap();
+        assert!(matches!(
+            from_bytes_projected::<Other>(&schema, &bytes),
+            Err(Error::UnknownField(field)) if field == "name"
+        ));
+        assert!(matches!(
+            from_bytes_projected::<u32>(&schema, &bytes),
+            Err(Error::TypeUnknown)
+        ));
+    }
+
+    #[test]
+    fn truncated_projection() {
+        let schema = Schema::of::<Event>().unwrap();
+        let bytes = to_bytes(&event()).unwrap();
+        assert!(matches!(
+            from_bytes_projected::<Header>(&schema, &bytes[..bytes.len() - 1]),
+            Err(Error::ValueTruncated)
+        ));
+    }
+
+    #[test]
+    fn wide_integers_are_skipped() {
+        #[derive(serde::Deserialize, Debug, PartialEq)]
+        struct Last {
+            last: u8,
+        }
+
+        let schema = Schema::of::<(u128, i128, u8)>().unwrap();
+        let bytes = to_bytes(&(u128::MAX, i128::MIN, 9_u8)).unwrap();
+        let schema = match schema {
+            Schema::Tuple(fields) => Schema::Struct {
+                name: "Wide",
+                fields: ["first", "second", "last"]
+                    .iter()
+                    .zip(fields)
+                    .map(|(name, schema)| crate::Field {
+                        name,
+                        schema,
+                    })
+                    .collect(),
+            },
+            _ => unreachable!(),
+        };
+        let last = from_bytes_projected::<Last>(&schema, &bytes);
+        assert!(last.is_ok());
+        assert_eq!(
+            Last {
+                last: 9,
+            },
+            last.unwrap()
+        );
+    }
+}
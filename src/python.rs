@@ -0,0 +1,374 @@
+use super::{
+    Config,
+    Value,
+};
+use pyo3::{
+    exceptions::{
+        PyTypeError,
+        PyValueError,
+    },
+    ffi::c_str,
+    types::{
+        PyAnyMethods,
+        PyBool,
+        PyBoolMethods,
+        PyByteArray,
+        PyBytes,
+        PyCFunction,
+        PyDict,
+        PyDictMethods,
+        PyFloat,
+        PyInt,
+        PyList,
+        PyModule,
+        PyModuleMethods,
+        PyString,
+        PyTuple,
+        PyTupleMethods,
+        PyType,
+        PyTypeMethods,
+    },
+    Bound,
+    IntoPyObjectExt,
+    PyAny,
+    PyErr,
+    PyResult,
+    Python,
+};
+
+// Values are exchanged with Python in self-describing encodings, since
+// Python code has no Rust types to tell it what an encoding holds.
+fn config() -> Config {
+    Config::new().with_self_describing(true)
+}
+
+fn value_error(error: &super::Error) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+/// Convert the given Python object into a [`Value`], so that it can be
+/// encoded.  `None` becomes [`Value::Option`] holding nothing, `bool`,
+/// `int`, `float`, `str`, and `bytes` or `bytearray` become the values of
+/// the matching types, with integers kept as the first of `i64`, `u64`,
+/// `i128`, and `u128` they fit, lists become [`Value::Seq`], tuples
+/// [`Value::Tuple`], and dicts [`Value::Map`].  Instances of dataclasses
+/// become [`Value::Struct`], with their fields in the order the class
+/// declares them, so that they encode the same way as Rust structs with the
+/// same fields.
+///
+/// # Errors
+///
+/// Returns a Python `TypeError` for objects of any other type, and an
+/// `OverflowError` for integers which fit neither an `i128` nor a `u128`.
+///
+/// [`Value`]: enum.Value.html
+/// [`Value::Option`]: enum.Value.html#variant.Option
+/// [`Value::Seq`]: enum.Value.html#variant.Seq
+/// [`Value::Tuple`]: enum.Value.html#variant.Tuple
+/// [`Value::Map`]: enum.Value.html#variant.Map
+/// [`Value::Struct`]: enum.Value.html#variant.Struct
+pub fn value_from_python(object: &Bound<'_, PyAny>) -> PyResult<Value> {
+    if object.is_none() {
+        Ok(Value::Option(None))
+    } else if let Ok(value) = object.cast::<PyBool>() {
+        Ok(Value::Bool(value.is_true()))
+    } else if object.is_instance_of::<PyInt>() {
+        object
+            .extract()
+            .map(Value::I64)
+            .or_else(|_| object.extract().map(Value::U64))
+            .or_else(|_| object.extract().map(Value::I128))
+            .or_else(|_| object.extract().map(Value::U128))
+    } else if object.is_instance_of::<PyFloat>() {
+        object.extract().map(Value::F64)
+    } else if object.is_instance_of::<PyString>() {
+        object.extract().map(Value::Str)
+    } else if object.is_instance_of::<PyBytes>()
+        || object.is_instance_of::<PyByteArray>()
+    {
+        object.extract().map(Value::Bytes)
+    } else if let Ok(list) = object.cast::<PyList>() {
+        list.try_iter()?
+            .map(|item| value_from_python(&item?))
+            .collect::<PyResult<_>>()
+            .map(Value::Seq)
+    } else if let Ok(tuple) = object.cast::<PyTuple>() {
+        tuple
+            .iter()
+            .map(|item| value_from_python(&item))
+            .collect::<PyResult<_>>()
+            .map(Value::Tuple)
+    } else if let Ok(dict) = object.cast::<PyDict>() {
+        dict.iter()
+            .map(|(key, value)| {
+                Ok((value_from_python(&key)?, value_from_python(&value)?))
+            })
+            .collect::<PyResult<_>>()
+            .map(Value::Map)
+    } else {
+        let dataclasses = object.py().import("dataclasses")?;
+        if object.is_instance_of::<PyType>()
+            || !dataclasses
+                .call_method1("is_dataclass", (object,))?
+                .is_truthy()?
+        {
+            return Err(PyTypeError::new_err(format!(
+                "cannot encode object of type {}",
+                object.get_type().name()?
+            )));
+        }
+        dataclasses
+            .call_method1("fields", (object,))?
+            .try_iter()?
+            .map(|field| {
+                let name: String = field?.getattr("name")?.extract()?;
+                let value = value_from_python(&object.getattr(name.as_str())?)?;
+                Ok((name, value))
+            })
+            .collect::<PyResult<_>>()
+            .map(Value::Struct)
+    }
+}
+
+/// Convert the given [`Value`] into a Python object, such as after it has
+/// been decoded.  Numbers become `int` or `float`, characters and strings
+/// become `str`, bytes become `bytes`, options become `None` or the value
+/// they hold, the unit value becomes `None`, sequences become lists, tuples
+/// become tuples, and maps and structs become dicts, with structs keyed by
+/// the names of their fields.  A variant becomes a dict with one entry,
+/// keyed by the name of the variant, or by its index if it has no name,
+/// holding the content of the variant.
+///
+/// # Errors
+///
+/// Returns a Python `TypeError` if a map key cannot be used as a dict key,
+/// such as a sequence, and a `ValueError` for a [`Value::Redacted`].
+///
+/// [`Value`]: enum.Value.html
+/// [`Value::Redacted`]: enum.Value.html#variant.Redacted
+pub fn value_into_python<'py>(
+    py: Python<'py>,
+    value: &Value,
+) -> PyResult<Bound<'py, PyAny>> {
+    match value {
+        Value::Bool(value) => value.into_bound_py_any(py),
+        Value::I8(value) => value.into_bound_py_any(py),
+        Value::I16(value) => value.into_bound_py_any(py),
+        Value::I32(value) => value.into_bound_py_any(py),
+        Value::I64(value) => value.into_bound_py_any(py),
+        Value::I128(value) => value.into_bound_py_any(py),
+        Value::U8(value) => value.into_bound_py_any(py),
+        Value::U16(value) => value.into_bound_py_any(py),
+        Value::U32(value) => value.into_bound_py_any(py),
+        Value::U64(value) => value.into_bound_py_any(py),
+        Value::U128(value) => value.into_bound_py_any(py),
+        Value::F32(value) => value.into_bound_py_any(py),
+        Value::F64(value) => value.into_bound_py_any(py),
+        Value::Char(value) => value.into_bound_py_any(py),
+        Value::Str(value) => value.into_bound_py_any(py),
+        Value::Bytes(value) => PyBytes::new(py, value).into_bound_py_any(py),
+        Value::Option(None) | Value::Unit => Ok(py.None().into_bound(py)),
+        Value::Option(Some(value)) => value_into_python(py, value),
+        Value::Seq(values) => PyList::new(
+            py,
+            values
+                .iter()
+                .map(|value| value_into_python(py, value))
+                .collect::<PyResult<Vec<_>>>()?,
+        )?
+        .into_bound_py_any(py),
+        Value::Tuple(values) => PyTuple::new(
+            py,
+            values
+                .iter()
+                .map(|value| value_into_python(py, value))
+                .collect::<PyResult<Vec<_>>>()?,
+        )?
+        .into_bound_py_any(py),
+        Value::Map(entries) => {
+            let dict = PyDict::new(py);
+            for (key, value) in entries {
+                dict.set_item(
+                    value_into_python(py, key)?,
+                    value_into_python(py, value)?,
+                )?;
+            }
+            dict.into_bound_py_any(py)
+        },
+        Value::Struct(fields) => {
+            let dict = PyDict::new(py);
+            for (name, value) in fields {
+                dict.set_item(name, value_into_python(py, value)?)?;
+            }
+            dict.into_bound_py_any(py)
+        },
+        Value::Variant {
+            index,
+            name,
+            value,
+        } => {
+            let dict = PyDict::new(py);
+            let value = value_into_python(py, value)?;
+            if name.is_empty() {
+                dict.set_item(index, value)?;
+            } else {
+                dict.set_item(name, value)?;
+            }
+            dict.into_bound_py_any(py)
+        },
+        Value::Redacted => Err(PyValueError::new_err(
+            "redacted value has no contents",
+        )),
+    }
+}
+
+/// Add two functions to the given Python module, for Python code to read
+/// and write the encodings of this crate:
+///
+/// * `encode(object)` converts the object into a [`Value`] as
+///   [`value_from_python`] does, and returns its encoding as `bytes`.
+/// * `decode(data)` decodes a [`Value`] from the given `bytes`, and returns
+///   it converted into a Python object as [`value_into_python`] does.
+///
+/// Both use [self-describing] encodings, since those can be decoded without
+/// knowing the types of the values they hold, so the Rust programs
+/// exchanging data with Python code should use a [`Config`] with that
+/// option set.  Since such encodings do not hold the names of struct
+/// fields, or tell tuples from lists, structs, including dataclasses, and
+/// tuples are decoded as lists of their values.
+///
+/// This is meant to be called from the `#[pymodule]` function of an
+/// extension module built on top of this crate.  A decode failure is raised
+/// as a Python `ValueError`.
+///
+/// # Errors
+///
+/// Returns any error made by Python while creating or adding the
+/// functions.
+///
+/// [`Value`]: enum.Value.html
+/// [`value_from_python`]: fn.value_from_python.html
+/// [`value_into_python`]: fn.value_into_python.html
+/// [self-describing]: struct.Config.html#method.with_self_describing
+/// [`Config`]: struct.Config.html
+pub fn add_python_functions(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    let py = module.py();
+    module.add_function(PyCFunction::new_closure(
+        py,
+        Some(c_str!("encode")),
+        Some(c_str!("Encode a Python object as bytes.")),
+        |args, _kwargs| {
+            let (object,): (Bound<'_, PyAny>,) = args.extract()?;
+            let value = value_from_python(&object)?;
+            let bytes = config().to_bytes(&value).map_err(|e| value_error(&e))?;
+            PyBytes::new(args.py(), &bytes).into_py_any(args.py())
+        },
+    )?)?;
+    module.add_function(PyCFunction::new_closure(
+        py,
+        Some(c_str!("decode")),
+        Some(c_str!("Decode a Python object from bytes.")),
+        |args, _kwargs| {
+            let (data,): (Vec<u8>,) = args.extract()?;
+            let value: Value =
+                config().from_bytes(&data).map_err(|e| value_error(&e))?;
+            value_into_python(args.py(), &value)?.into_py_any(args.py())
+        },
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pyo3::types::PyStringMethods;
+
+    #[test]
+    fn python_round_trips() {
+        Python::initialize();
+        Python::attach(|py| {
+            let module = PyModule::new(py, "serialization").unwrap();
+            assert!(add_python_functions(&module).is_ok());
+            let object = py
+                .eval(
+                    c_str!(
+                        "[None, True, -5, 2**64 - 1, -2**100, 2**128 - 1, \
+                         1.5, 'a', b'b', {1: [2]}]"
+                    ),
+                    None,
+                    None,
+                )
+                .unwrap();
+            let encoded = module.call_method1("encode", (&object,)).unwrap();
+            let decoded = module.call_method1("decode", (encoded,)).unwrap();
+            assert!(decoded.eq(&object).unwrap());
+            let bytes = config()
+                .to_bytes(&(Some(3_u8), vec![String::from("x")]))
+                .unwrap();
+            let decoded = module.call_method1("decode", (bytes,)).unwrap();
+            assert_eq!(
+                "[3, ['x']]",
+                decoded.str().unwrap().to_str().unwrap()
+            );
+            let tuple = py.eval(c_str!("(1, 'a')"), None, None).unwrap();
+            let encoded = module.call_method1("encode", (tuple,)).unwrap();
+            let decoded = module.call_method1("decode", (encoded,)).unwrap();
+            assert_eq!("[1, 'a']", decoded.str().unwrap().to_str().unwrap());
+            let object = py.eval(c_str!("object()"), None, None).unwrap();
+            assert!(module
+                .call_method1("encode", (object,))
+                .unwrap_err()
+                .is_instance_of::<PyTypeError>(py));
+            assert!(module
+                .call_method1("decode", (vec![99_u8],))
+                .unwrap_err()
+                .is_instance_of::<PyValueError>(py));
+        });
+    }
+
+    #[test]
+    fn dataclasses() {
+        Python::initialize();
+        Python::attach(|py| {
+            let globals = PyDict::new(py);
+            py.run(
+                c_str!(
+                    "import dataclasses\n\
+                     @dataclasses.dataclass\n\
+                     class Point:\n    x: int\n    y: str\n\
+                     point = Point(7, 'seven')\n"
+                ),
+                Some(&globals),
+                None,
+            )
+            .unwrap();
+            let point = globals.get_item("point").unwrap().unwrap();
+            let value = value_from_python(&point);
+            assert!(value.is_ok());
+            assert_eq!(
+                Value::Struct(vec![
+                    (String::from("x"), Value::I64(7)),
+                    (String::from("y"), Value::Str(String::from("seven"))),
+                ]),
+                value.unwrap()
+            );
+            let class = globals.get_item("Point").unwrap().unwrap();
+            assert!(value_from_python(&class).is_err());
+            let object = value_into_python(
+                py,
+                &Value::Variant {
+                    index: 2,
+                    name: String::new(),
+                    value: Box::new(Value::Struct(vec![(
+                        String::from("x"),
+                        Value::U8(1),
+                    )])),
+                },
+            );
+            assert!(object.is_ok());
+            assert_eq!(
+                "{2: {'x': 1}}",
+                object.unwrap().str().unwrap().to_str().unwrap()
+            );
+        });
+    }
+}
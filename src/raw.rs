@@ -0,0 +1,104 @@
+use super::{
+    registry::Bytes,
+    ser::RAW_NAME,
+};
+
+/// This wraps bytes which are already encoded, such as a cached encoding of
+/// part of a message, so that they can be placed inside a larger value and
+/// serialized without being decoded and re-encoded.  With the serializer of
+/// this crate, the bytes are copied to the output exactly as they are, in
+/// place of the value they encode.  Other serializers see a newtype struct
+/// wrapping a byte string.
+///
+/// The bytes are not checked, so they must be a complete encoding of the
+/// value the receiver expects at their position.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_bytes,
+///     to_bytes,
+///     Raw,
+/// };
+///
+/// #[derive(serde::Serialize)]
+/// struct Outgoing<'a> {
+///     id: u32,
+///     body: Raw<&'a [u8]>,
+/// }
+///
+/// #[derive(serde::Deserialize, Debug, PartialEq)]
+/// struct Incoming {
+///     id: u32,
+///     body: (String, u8),
+/// }
+///
+/// let cached_body = to_bytes(&("status", 200_u8)).unwrap();
+/// let message = to_bytes(&Outgoing {
+///     id: 7,
+///     body: Raw(&cached_body),
+/// })
+/// .unwrap();
+/// assert_eq!(
+///     Incoming {
+///         id: 7,
+///         body: (String::from("status"), 200),
+///     },
+///     from_bytes(&message).unwrap()
+/// );
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Raw<B>(pub B);
+
+impl<B> serde::Serialize for Raw<B>
+where
+    B: AsRef<[u8]>,
+{
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_newtype_struct(RAW_NAME, &Bytes(self.0.as_ref()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        to_bytes,
+        Serializer,
+    };
+
+    #[test]
+    fn raw_bytes_are_spliced() {
+        let cached = to_bytes(&vec![1_u16, 2, 3]).unwrap();
+        let serialization =
+            to_bytes(&(Raw(cached.clone()), 4_u8, Raw(&cached)));
+        assert!(serialization.is_ok());
+        let serialization = serialization.unwrap();
+        assert_eq!(&[3, 1, 2, 3, 4, 3, 1, 2, 3][..], serialization);
+    }
+
+    #[test]
+    fn byte_strings_after_raw_bytes_keep_length() {
+        let serialization = to_bytes(&(Raw([0xAA]), Bytes(&[0xBB])));
+        assert!(serialization.is_ok());
+        assert_eq!(&[0xAA, 0x01, 0xBB][..], serialization.unwrap());
+    }
+
+    #[test]
+    fn append_raw() {
+        let mut buffer = Vec::new();
+        let mut serializer = Serializer::new(&mut buffer);
+        serde::Serialize::serialize(&5_u8, &mut serializer).unwrap();
+        assert!(serializer.append_raw(&[0x01, 0x02]).is_ok());
+        serde::Serialize::serialize(&"a", &mut serializer).unwrap();
+        assert_eq!(&[0x05, 0x01, 0x02, 0x01, b'a'][..], buffer);
+    }
+}
@@ -0,0 +1,297 @@
+use super::{
+    Config,
+    Deserializer,
+    IoInput,
+    Result,
+    Varint,
+};
+use std::{
+    io::Read,
+    marker::PhantomData,
+};
+
+/// This function is used to decode a value from a reader, such as a file
+/// or socket, reading only as much as the value takes, so that the whole
+/// encoding never needs to be held in memory.  Since the encoding is not
+/// held in memory, strings and byte strings are copied rather than
+/// borrowed, so the value must own all its data.  Reads which return fewer
+/// bytes than asked for are retried until the value is complete, and the
+/// reader is left just past the end of the value, so that a stream of
+/// values can be decoded by calling this again with the same reader.
+///
+/// The reader is read in many small pieces, so a reader which is not
+/// already buffered should be wrapped in a [`std::io::BufReader`].
+///
+/// [`std::io::BufReader`]: https://doc.rust-lang.org/std/io/struct.BufReader.html
+///
+/// # Errors
+///
+/// Returns [`Error::ValueTruncated`] if the reader reaches its end before
+/// the value does, or [`Error::Io`] if reading fails for any other reason,
+/// and otherwise any of the errors returned by [`from_bytes`].
+///
+/// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+/// [`Error::Io`]: enum.Error.html#variant.Io
+/// [`from_bytes`]: fn.from_bytes.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_reader,
+///     to_bytes,
+/// };
+///
+/// let mut stream = to_bytes(&(String::from("first"), 1_u8)).unwrap();
+/// stream.extend(to_bytes(&(String::from("second"), 2_u8)).unwrap());
+/// let mut reader = &stream[..];
+/// let first: (String, u8) = from_reader(&mut reader).unwrap();
+/// let second: (String, u8) = from_reader(&mut reader).unwrap();
+/// assert_eq!((String::from("first"), 1), first);
+/// assert_eq!((String::from("second"), 2), second);
+/// ```
+pub fn from_reader<R, T>(reader: R) -> Result<T>
+where
+    R: Read,
+    T: serde::de::DeserializeOwned,
+{
+    T::deserialize(&mut Config::new().deserializer(IoInput::new(reader)))
+}
+
+/// This is an iterator which deserializes values of type `T` stored back to
+/// back in a reader, such as a file or socket, yielding each one until the
+/// reader ends.  As with [`from_reader`], strings and byte strings are
+/// copied out of the reader, so `T` cannot borrow from it.
+///
+/// A reader which ends between values ends the iteration, while one which
+/// ends partway through a value yields [`Error::ValueTruncated`].  Since
+/// part of a value which fails to deserialize has already been read, the
+/// iteration ends after the first error.
+///
+/// [`from_reader`]: fn.from_reader.html
+/// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     to_bytes,
+///     ReaderStreamDeserializer,
+/// };
+///
+/// let mut stream = to_bytes(&(String::from("first"), 1_u8)).unwrap();
+/// stream.extend(to_bytes(&(String::from("second"), 2_u8)).unwrap());
+/// let values: Vec<(String, u8)> = ReaderStreamDeserializer::new(&stream[..])
+///     .collect::<Result<_, _>>()
+///     .unwrap();
+/// assert_eq!(
+///     vec![(String::from("first"), 1), (String::from("second"), 2)],
+///     values
+/// );
+/// ```
+pub struct ReaderStreamDeserializer<R, T> {
+    de: Deserializer<'static, Varint, IoInput<R>>,
+    failed: bool,
+    output: PhantomData<T>,
+}
+
+impl<R, T> ReaderStreamDeserializer<R, T>
+where
+    R: Read,
+{
+    /// Give back the reader, which is left just past the end of the value
+    /// most recently deserialized, unless a byte was read ahead to find
+    /// whether or not the reader had ended.
+    pub fn into_inner(self) -> R {
+        self.de.into_input().into_inner()
+    }
+
+    /// Return an iterator which deserializes values from the given reader.
+    /// The reader is read in many small pieces, so a reader which is not
+    /// already buffered should be wrapped in a [`std::io::BufReader`].
+    ///
+    /// [`std::io::BufReader`]: https://doc.rust-lang.org/std/io/struct.BufReader.html
+    pub fn new(reader: R) -> Self {
+        Self {
+            de: Config::new().deserializer(IoInput::new(reader)),
+            failed: false,
+            output: PhantomData,
+        }
+    }
+
+    /// Return the number of bytes of the reader making up the values
+    /// deserialized so far, which is the offset of the next value.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.de.offset()
+    }
+}
+
+impl<R, T> Iterator for ReaderStreamDeserializer<R, T>
+where
+    R: Read,
+    T: serde::de::DeserializeOwned,
+{
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let next = match self.de.at_end() {
+            Ok(true) => return None,
+            Ok(false) => T::deserialize(&mut self.de),
+            Err(error) => Err(error),
+        };
+        self.failed = next.is_err();
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        to_bytes,
+        Error,
+    };
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    enum Shape {
+        Point,
+        Circle(u32),
+        Rectangle {
+            width: u16,
+            height: u16,
+        },
+    }
+
+    // This returns at most one byte from each read, to check that short
+    // reads are retried.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl std::io::Read for Trickle<'_> {
+        fn read(
+            &mut self,
+            buf: &mut [u8],
+        ) -> std::io::Result<usize> {
+            if buf.is_empty() || self.0.is_empty() {
+                return Ok(0);
+            }
+            buf[0] = self.0[0];
+            self.0 = &self.0[1..];
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn decodes_same_as_from_bytes() {
+        let mut map = BTreeMap::new();
+        map.insert(String::from("key"), vec![Some('€'), None]);
+        let value = (
+            vec![Shape::Point, Shape::Circle(70_000), Shape::Rectangle {
+                width: 3,
+                height: 4,
+            }],
+            map,
+            -1_234_567_i64,
+            vec![0xFF_u8; 3],
+            true,
+        );
+        let encoding = to_bytes(&value).unwrap();
+        let decoded = from_reader(Trickle(&encoding));
+        assert!(decoded.is_ok());
+        assert_eq!(value, decoded.unwrap());
+    }
+
+    #[test]
+    fn wide_integers() {
+        let value = (i128::MIN, u128::MAX, -1_i128, 300_u128);
+        let encoding = to_bytes(&value).unwrap();
+        let decoded = from_reader(Trickle(&encoding));
+        assert!(decoded.is_ok());
+        assert_eq!(value, decoded.unwrap());
+    }
+
+    #[test]
+    fn end_of_input() {
+        let encoding = to_bytes(&String::from("truncated")).unwrap();
+        assert!(matches!(
+            from_reader::<_, String>(&encoding[..5]),
+            Err(Error::ValueTruncated)
+        ));
+        assert!(matches!(
+            from_reader::<_, u16>(&[0x84, 0x80, 0x00][..]),
+            Err(Error::IntegerOverflow)
+        ));
+        // A huge length is not trusted to allocate memory up front.
+        assert!(matches!(
+            from_reader::<_, Vec<u8>>(&[0x8F, 0xFF, 0xFF, 0xFF, 0x7F, 1][..]),
+            Err(Error::ValueTruncated)
+        ));
+    }
+
+    #[test]
+    fn configured_encodings() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Point {
+            x: u16,
+            y: u16,
+        }
+
+        let value = vec![
+            Point {
+                x: 1,
+                y: 2,
+            },
+            Point {
+                x: 1,
+                y: 2,
+            },
+            Point {
+                x: 3,
+                y: 4,
+            },
+        ];
+        let config = Config::new()
+            .with_deduplication(true)
+            .with_field_names(true)
+            .with_interning(true)
+            .with_struct_framing(true);
+        let encoding = config.to_bytes(&value).unwrap();
+        let mut deserializer =
+            config.deserializer(IoInput::new(Trickle(&encoding)));
+        let decoded: Result<Vec<Point>> =
+            serde::Deserialize::deserialize(&mut deserializer);
+        assert!(decoded.is_ok());
+        assert_eq!(value, decoded.unwrap());
+        assert_eq!(encoding.len(), deserializer.offset());
+    }
+
+    #[test]
+    fn stream_of_values() {
+        let mut stream = Vec::new();
+        for value in &[1_u16, 300, 9001] {
+            stream.extend(to_bytes(value).unwrap());
+        }
+        let mut values =
+            ReaderStreamDeserializer::<_, u16>::new(Trickle(&stream));
+        assert!(matches!(values.next(), Some(Ok(1))));
+        assert_eq!(1, values.offset());
+        assert!(matches!(values.next(), Some(Ok(300))));
+        assert_eq!(3, values.offset());
+        assert!(matches!(values.next(), Some(Ok(9001))));
+        assert_eq!(5, values.offset());
+        assert!(values.next().is_none());
+        let mut values = ReaderStreamDeserializer::<_, u16>::new(
+            &stream[..stream.len() - 1],
+        );
+        assert!(matches!(values.next(), Some(Ok(1))));
+        assert!(matches!(values.next(), Some(Ok(300))));
+        assert!(matches!(values.next(), Some(Err(Error::ValueTruncated))));
+        assert!(values.next().is_none());
+    }
+}
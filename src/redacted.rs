@@ -0,0 +1,192 @@
+use super::{
+    LayoutFingerprint,
+    MaxSerializedSize,
+};
+
+// This is the name of the newtype struct which [`Redacted`] serializes as,
+// which tells diagnostic renderings to mask the value it wraps.
+//
+// [`Redacted`]: ../struct.Redacted.html
+pub(crate) const REDACTED_NAME: &str = "\0serialization::Redacted";
+
+// This is what diagnostic renderings show in place of a redacted value.
+pub(crate) const REDACTED_TEXT: &str = "<redacted>";
+
+/// This wraps a sensitive value, such as a password or personal data, so
+/// that it is encoded as usual in the binary format of this crate, but
+/// masked wherever encodings are rendered for people to read, so that
+/// support tooling can share decoded payloads without leaking secrets.
+/// Its `Debug` form, [`Value`]s made from it with [`to_value`], and JSON
+/// made from it by [`encode`] all show `<redacted>` in place of the value.
+///
+/// Since the value is left out of those renderings, they cannot be decoded
+/// back into the original value.
+///
+/// [`Value`]: enum.Value.html
+/// [`to_value`]: fn.to_value.html
+/// [`encode`]: fn.encode.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_bytes,
+///     to_bytes,
+///     to_value,
+///     Redacted,
+/// };
+///
+/// #[derive(serde::Serialize, serde::Deserialize, Debug)]
+/// struct Login {
+///     user: String,
+///     password: Redacted<String>,
+/// }
+///
+/// let login = Login {
+///     user: String::from("alice"),
+///     password: Redacted(String::from("hunter2")),
+/// };
+/// let decoded: Login = from_bytes(&to_bytes(&login).unwrap()).unwrap();
+/// assert_eq!("hunter2", decoded.password.0);
+/// assert_eq!(
+///     "Login { user: \"alice\", password: <redacted> }",
+///     format!("{login:?}")
+/// );
+/// assert_eq!(
+///     "{ user: \"alice\", password: <redacted> }",
+///     to_value(&login).unwrap().to_string()
+/// );
+/// ```
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Redacted<T>(pub T);
+
+impl<T> std::fmt::Debug for Redacted<T> {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        f.write_str(REDACTED_TEXT)
+    }
+}
+
+impl<T> serde::Serialize for Redacted<T>
+where
+    T: serde::Serialize,
+{
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_newtype_struct(REDACTED_NAME, &self.0)
+    }
+}
+
+impl<'de, T> serde::Deserialize<'de> for Redacted<T>
+where
+    T: serde::Deserialize<'de>,
+{
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor<T>(std::marker::PhantomData<T>);
+
+        impl<'de, T> serde::de::Visitor<'de> for Visitor<T>
+        where
+            T: serde::Deserialize<'de>,
+        {
+            type Value = Redacted<T>;
+
+            fn expecting(
+                &self,
+                f: &mut std::fmt::Formatter,
+            ) -> std::fmt::Result {
+                write!(f, "a redacted value")
+            }
+
+            fn visit_newtype_struct<D>(
+                self,
+                deserializer: D,
+            ) -> std::result::Result<Redacted<T>, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                T::deserialize(deserializer).map(Redacted)
+            }
+        }
+
+        deserializer.deserialize_newtype_struct(
+            REDACTED_NAME,
+            Visitor(std::marker::PhantomData),
+        )
+    }
+}
+
+impl<T> LayoutFingerprint for Redacted<T>
+where
+    T: LayoutFingerprint,
+{
+    const LAYOUT_FINGERPRINT: u64 = T::LAYOUT_FINGERPRINT;
+}
+
+impl<T> MaxSerializedSize for Redacted<T>
+where
+    T: MaxSerializedSize,
+{
+    const MAX_SERIALIZED_SIZE: usize = T::MAX_SERIALIZED_SIZE;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        encode,
+        from_bytes,
+        to_bytes,
+        to_value,
+        Format,
+        Schema,
+        Value,
+    };
+
+    #[test]
+    fn binary_encoding_is_unchanged() {
+        let value = (Redacted(String::from("secret")), 7_u8);
+        let encoding = to_bytes(&value);
+        assert!(encoding.is_ok());
+        let encoding = encoding.unwrap();
+        assert_eq!(
+            to_bytes(&(String::from("secret"), 7_u8)).unwrap(),
+            encoding
+        );
+        let decoded: crate::Result<(Redacted<String>, u8)> =
+            from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!(value, decoded.unwrap());
+        assert_eq!(
+            Schema::of::<(String, u8)>().unwrap().fingerprint(),
+            Schema::of::<(Redacted<String>, u8)>().unwrap().fingerprint()
+        );
+    }
+
+    #[test]
+    fn renderings_are_masked() {
+        let value = (Redacted(vec![1_u16, 2]), 7_u8);
+        assert_eq!("(<redacted>, 7)", format!("{value:?}"));
+        let rendering = to_value(&value);
+        assert!(rendering.is_ok());
+        let rendering = rendering.unwrap();
+        assert_eq!(
+            Value::Tuple(vec![Value::Redacted, Value::U8(7)]),
+            rendering
+        );
+        assert_eq!("(<redacted>, 7)", rendering.to_string());
+        let json = encode(Format::Json, &value);
+        assert!(json.is_ok());
+        assert_eq!(&b"[\"<redacted>\",7]"[..], json.unwrap());
+    }
+}
@@ -0,0 +1,667 @@
+use super::{
+    from_bytes,
+    to_bytes,
+    Error,
+    Result,
+};
+use std::collections::HashMap;
+
+/// This trait is implemented by concrete types which can be serialized
+/// behind a trait object.  It provides the identifier written alongside the
+/// encoded value, which is used to find the right concrete type when the
+/// value is deserialized.  The identifier should be stable across releases,
+/// since it becomes part of the encoding.
+pub trait Named {
+    /// This is the identifier of the type in encodings.
+    const TYPE_NAME: &'static str;
+}
+
+/// This is the object-safe counterpart of [`Named`], which is implemented
+/// for all named, serializable types.  Make it a supertrait of any trait
+/// whose trait objects should be serializable.
+///
+/// [`Named`]: trait.Named.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     register_types,
+///     to_bytes_tagged,
+///     Named,
+///     Registry,
+///     Tagged,
+/// };
+///
+/// trait Shape: Tagged {
+///     fn area(&self) -> u32;
+/// }
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Square(u32);
+///
+/// impl Named for Square {
+///     const TYPE_NAME: &'static str = "Square";
+/// }
+///
+/// impl Shape for Square {
+///     fn area(&self) -> u32 {
+///         self.0 * self.0
+///     }
+/// }
+///
+/// let mut registry = Registry::<dyn Shape>::new();
+/// register_types!(registry, Square);
+/// let shape: Box<dyn Shape> = Box::new(Square(3));
+/// let encoding = to_bytes_tagged(&*shape).unwrap();
+/// let shape = registry.from_bytes(&encoding).unwrap();
+/// assert_eq!(9, shape.area());
+/// ```
+pub trait Tagged {
+    /// Return the identifier of the concrete type of the value.
+    fn type_name(&self) -> &'static str;
+
+    /// Encode the value using the serializer of this crate.
+    ///
+    /// # Errors
+    ///
+    /// Any error from serializing the value is returned.
+    fn encode(&self) -> Result<Vec<u8>>;
+}
+
+impl<T> Tagged for T
+where
+    T: Named + serde::Serialize,
+{
+    fn type_name(&self) -> &'static str {
+        T::TYPE_NAME
+    }
+
+    fn encode(&self) -> Result<Vec<u8>> {
+        to_bytes(self)
+    }
+}
+
+pub(crate) struct Payload(pub(crate) Vec<u8>);
+
+impl<'de> serde::Deserialize<'de> for Payload {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = Payload;
+
+            fn expecting(
+                &self,
+                f: &mut std::fmt::Formatter,
+            ) -> std::fmt::Result {
+                write!(f, "encoded payload")
+            }
+
+            fn visit_bytes<E>(
+                self,
+                v: &[u8],
+            ) -> std::result::Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(Payload(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E>(
+                self,
+                v: Vec<u8>,
+            ) -> std::result::Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(Payload(v))
+            }
+
+            fn visit_seq<A>(
+                self,
+                mut seq: A,
+            ) -> std::result::Result<Self::Value, A::Error>
+            where
+                A: serde::de::SeqAccess<'de>,
+            {
+                let mut payload = Vec::new();
+                while let Some(byte) = seq.next_element()? {
+                    payload.push(byte);
+                }
+                Ok(Payload(payload))
+            }
+        }
+
+        deserializer.deserialize_byte_buf(Visitor)
+    }
+}
+
+pub(crate) struct TaggedValue<'a> {
+    pub(crate) name: &'a str,
+    pub(crate) payload: &'a [u8],
+}
+
+impl serde::Serialize for TaggedValue<'_> {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeTuple;
+        let mut tuple = serializer.serialize_tuple(2)?;
+        tuple.serialize_element(self.name)?;
+        tuple.serialize_element(&Bytes(self.payload))?;
+        tuple.end()
+    }
+}
+
+pub(crate) struct Bytes<'a>(pub(crate) &'a [u8]);
+
+impl serde::Serialize for Bytes<'_> {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+/// Serialize a trait object as the identifier of its concrete type followed
+/// by its encoding.  This is intended to be called from the `Serialize`
+/// implementation of a boxed trait object.
+///
+/// # Errors
+///
+/// Any error from encoding the value, or from the given serializer,
+/// is returned.
+pub fn serialize_tagged<S>(
+    value: &dyn Tagged,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let payload = value.encode().map_err(serde::ser::Error::custom)?;
+    serde::Serialize::serialize(
+        &TaggedValue {
+            name: value.type_name(),
+            payload: &payload,
+        },
+        serializer,
+    )
+}
+
+/// This function is used to encode a trait object into a sequence of bytes
+/// which can be decoded by a [`Registry`] into which its concrete type has
+/// been registered.
+///
+/// [`Registry`]: struct.Registry.html
+///
+/// # Errors
+///
+/// Any error from encoding the value is returned.
+pub fn to_bytes_tagged(value: &dyn Tagged) -> Result<Vec<u8>> {
+    let payload = value.encode()?;
+    to_bytes(&TaggedValue {
+        name: value.type_name(),
+        payload: &payload,
+    })
+}
+
+type Decoder<T> = Box<dyn Fn(&[u8]) -> Result<Box<T>> + Send + Sync>;
+
+/// This is a table of concrete types which implement some trait, used to
+/// deserialize boxed trait objects of that trait from encodings made by
+/// [`to_bytes_tagged`] or [`serialize_tagged`].
+///
+/// Types are added to the registry using [`register`], or several at once
+/// using the [`register_types!`] macro.
+///
+/// [`to_bytes_tagged`]: fn.to_bytes_tagged.html
+/// [`serialize_tagged`]: fn.serialize_tagged.html
+/// [`register`]: #method.register
+/// [`register_types!`]: macro.register_types.html
+pub struct Registry<T: ?Sized> {
+    decoders: HashMap<&'static str, Decoder<T>>,
+}
+
+impl<T: ?Sized> Registry<T> {
+    /// Return a view of the registry which decodes only the types with the
+    /// given identifiers, for decoding untrusted input which should not be
+    /// able to produce every type in the registry.
+    #[must_use]
+    pub fn allowing<'r>(
+        &'r self,
+        type_names: &'r [&'r str],
+    ) -> AllowList<'r, T> {
+        AllowList {
+            registry: self,
+            type_names,
+        }
+    }
+
+    /// Determine whether or not a type with the given identifier has been
+    /// registered.
+    #[must_use]
+    pub fn contains(
+        &self,
+        type_name: &str,
+    ) -> bool {
+        self.decoders.contains_key(type_name)
+    }
+
+    /// Decode a trait object from the given payload, as the registered type
+    /// with the given identifier.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnknownType`] if no type with the given identifier
+    /// has been registered, or any error from decoding the payload.
+    ///
+    /// [`Error::UnknownType`]: enum.Error.html#variant.UnknownType
+    pub fn decode(
+        &self,
+        type_name: &str,
+        payload: &[u8],
+    ) -> Result<Box<T>> {
+        self.decoders
+            .get(type_name)
+            .ok_or_else(|| Error::UnknownType(type_name.to_string()))
+            .and_then(|decoder| decoder(payload))
+    }
+
+    /// Deserialize a trait object which was serialized by
+    /// [`serialize_tagged`].  This is intended to be called from the
+    /// `Deserialize` implementation of a boxed trait object.
+    ///
+    /// [`serialize_tagged`]: fn.serialize_tagged.html
+    ///
+    /// # Errors
+    ///
+    /// Any error from the given deserializer is returned, as well as any
+    /// error returned by [`decode`].
+    ///
+    /// [`decode`]: #method.decode
+    pub fn deserialize<'de, D>(
+        &self,
+        deserializer: D,
+    ) -> std::result::Result<Box<T>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (type_name, Payload(payload)): (String, Payload) =
+            serde::Deserialize::deserialize(deserializer)?;
+        self.decode(&type_name, &payload).map_err(serde::de::Error::custom)
+    }
+
+    /// Decode a trait object from a sequence of bytes produced by
+    /// [`to_bytes_tagged`].
+    ///
+    /// [`to_bytes_tagged`]: fn.to_bytes_tagged.html
+    ///
+    /// # Errors
+    ///
+    /// Any error from decoding the identifier or payload is returned, as
+    /// well as any error returned by [`decode`].
+    ///
+    /// [`decode`]: #method.decode
+    pub fn from_bytes(
+        &self,
+        bytes: &[u8],
+    ) -> Result<Box<T>> {
+        let (type_name, payload): (&str, &[u8]) = from_bytes(bytes)?;
+        self.decode(type_name, payload)
+    }
+
+    /// Return a new, empty registry.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            decoders: HashMap::new(),
+        }
+    }
+
+    /// Add the concrete type `C` to the registry.  The given function
+    /// converts a boxed value of the concrete type into a boxed trait
+    /// object, and is usually just the identity closure `|value| value`.
+    pub fn register<C>(
+        &mut self,
+        upcast: fn(Box<C>) -> Box<T>,
+    ) where
+        C: Named + serde::de::DeserializeOwned + 'static,
+        T: 'static,
+    {
+        self.decoders.insert(
+            C::TYPE_NAME,
+            Box::new(move |payload| {
+                from_bytes::<C>(payload).map(|value| upcast(Box::new(value)))
+            }),
+        );
+    }
+}
+
+/// This is a view of a [`Registry`] which decodes only the registered types
+/// which are on a list of permitted types, made by [`Registry::allowing`].
+/// Decoding any other type fails before its payload is decoded, so input
+/// from untrusted sources cannot produce arbitrary registered types.
+///
+/// [`Registry`]: struct.Registry.html
+/// [`Registry::allowing`]: struct.Registry.html#method.allowing
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     register_types,
+///     to_bytes_tagged,
+///     Error,
+///     Named,
+///     Registry,
+///     Tagged,
+/// };
+///
+/// trait Command: Tagged {}
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Status;
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct Shutdown;
+///
+/// impl Named for Status {
+///     const TYPE_NAME: &'static str = "Status";
+/// }
+///
+/// impl Named for Shutdown {
+///     const TYPE_NAME: &'static str = "Shutdown";
+/// }
+///
+/// impl Command for Status {}
+/// impl Command for Shutdown {}
+///
+/// let mut registry = Registry::<dyn Command>::new();
+/// register_types!(registry, Status, Shutdown);
+/// let public = registry.allowing(&["Status"]);
+/// assert!(public.from_bytes(&to_bytes_tagged(&Status).unwrap()).is_ok());
+/// assert!(matches!(
+///     public.from_bytes(&to_bytes_tagged(&Shutdown).unwrap()),
+///     Err(Error::TypeNotAllowed(type_name)) if type_name == "Shutdown"
+/// ));
+/// ```
+pub struct AllowList<'r, T: ?Sized> {
+    registry: &'r Registry<T>,
+    type_names: &'r [&'r str],
+}
+
+impl<T: ?Sized> AllowList<'_, T> {
+    /// Determine whether or not a type with the given identifier is both
+    /// permitted and registered.
+    #[must_use]
+    pub fn contains(
+        &self,
+        type_name: &str,
+    ) -> bool {
+        self.type_names.contains(&type_name)
+            && self.registry.contains(type_name)
+    }
+
+    /// Decode a trait object from the given payload, as the registered type
+    /// with the given identifier, if it is permitted.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::TypeNotAllowed`] if the type is not permitted, or
+    /// any error from [`Registry::decode`].
+    ///
+    /// [`Error::TypeNotAllowed`]: enum.Error.html#variant.TypeNotAllowed
+    /// [`Registry::decode`]: struct.Registry.html#method.decode
+    pub fn decode(
+        &self,
+        type_name: &str,
+        payload: &[u8],
+    ) -> Result<Box<T>> {
+        if self.type_names.contains(&type_name) {
+            self.registry.decode(type_name, payload)
+        } else {
+            Err(Error::TypeNotAllowed(type_name.to_string()))
+        }
+    }
+
+    /// Deserialize a trait object which was serialized by
+    /// [`serialize_tagged`], if its type is permitted.
+    ///
+    /// [`serialize_tagged`]: fn.serialize_tagged.html
+    ///
+    /// # Errors
+    ///
+    /// Any error from the given deserializer is returned, as well as any
+    /// error returned by [`decode`].
+    ///
+    /// [`decode`]: #method.decode
+    pub fn deserialize<'de, D>(
+        &self,
+        deserializer: D,
+    ) -> std::result::Result<Box<T>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let (type_name, Payload(payload)): (String, Payload) =
+            serde::Deserialize::deserialize(deserializer)?;
+        self.decode(&type_name, &payload).map_err(serde::de::Error::custom)
+    }
+
+    /// Decode a trait object from a sequence of bytes produced by
+    /// [`to_bytes_tagged`], if its type is permitted.
+    ///
+    /// [`to_bytes_tagged`]: fn.to_bytes_tagged.html
+    ///
+    /// # Errors
+    ///
+    /// Any error from decoding the identifier or payload is returned, as
+    /// well as any error returned by [`decode`].
+    ///
+    /// [`decode`]: #method.decode
+    pub fn from_bytes(
+        &self,
+        bytes: &[u8],
+    ) -> Result<Box<T>> {
+        let (type_name, payload): (&str, &[u8]) = from_bytes(bytes)?;
+        self.decode(type_name, payload)
+    }
+}
+
+impl<T: ?Sized> Default for Registry<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Add one or more concrete types to a [`Registry`] of trait objects.
+///
+/// [`Registry`]: struct.Registry.html
+#[macro_export]
+macro_rules! register_types {
+    ($registry:expr, $($ty:ty),+ $(,)?) => {{
+        let registry = &mut $registry;
+        $(
+            registry.register::<$ty>(|value| value);
+        )+
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    trait Animal: Tagged {
+        fn speak(&self) -> String;
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Dog {
+        name: String,
+    }
+
+    impl Named for Dog {
+        const TYPE_NAME: &'static str = "Dog";
+    }
+
+    impl Animal for Dog {
+        fn speak(&self) -> String {
+            format!("{} says woof", self.name)
+        }
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Cat {
+        lives: u8,
+    }
+
+    impl Named for Cat {
+        const TYPE_NAME: &'static str = "Cat";
+    }
+
+    impl Animal for Cat {
+        fn speak(&self) -> String {
+            format!("meow (x{})", self.lives)
+        }
+    }
+
+    fn registry() -> Registry<dyn Animal> {
+        let mut registry = Registry::<dyn Animal>::new();
+        register_types!(registry, Dog, Cat);
+        registry
+    }
+
+    #[test]
+    fn round_trip_trait_objects() {
+        let registry = registry();
+        let animals: Vec<Box<dyn Animal>> = vec![
+            Box::new(Dog {
+                name: String::from("Rex"),
+            }),
+            Box::new(Cat {
+                lives: 9,
+            }),
+        ];
+        for animal in animals {
+            let encoding = to_bytes_tagged(&*animal);
+            assert!(encoding.is_ok());
+            let decoded = registry.from_bytes(&encoding.unwrap());
+            assert!(decoded.is_ok());
+            assert_eq!(animal.speak(), decoded.unwrap().speak());
+        }
+    }
+
+    #[test]
+    fn encoding_is_type_name_then_payload() {
+        let encoding = to_bytes_tagged(&Cat {
+            lives: 9,
+        });
+        assert!(encoding.is_ok());
+        assert_eq!(&[3, b'C', b'a', b't', 1, 9][..], encoding.unwrap());
+    }
+
+    #[test]
+    fn unknown_type() {
+        let mut registry = Registry::<dyn Animal>::new();
+        register_types!(registry, Dog);
+        assert!(registry.contains("Dog"));
+        assert!(!registry.contains("Cat"));
+        let encoding = to_bytes_tagged(&Cat {
+            lives: 9,
+        })
+        .unwrap();
+        assert!(matches!(
+            registry.from_bytes(&encoding),
+            Err(Error::UnknownType(type_name)) if type_name == "Cat"
+        ));
+    }
+
+    #[test]
+    fn allow_list() {
+        let registry = registry();
+        let dogs_only = registry.allowing(&["Dog", "Wolf"]);
+        assert!(dogs_only.contains("Dog"));
+        assert!(!dogs_only.contains("Cat"));
+        assert!(!dogs_only.contains("Wolf"));
+        let dog = to_bytes_tagged(&Dog {
+            name: String::from("Rex"),
+        })
+        .unwrap();
+        let cat = to_bytes_tagged(&Cat {
+            lives: 9,
+        })
+        .unwrap();
+        let decoded = dogs_only.from_bytes(&dog);
+        assert!(decoded.is_ok());
+        assert_eq!("Rex says woof", decoded.unwrap().speak());
+        assert!(matches!(
+            dogs_only.from_bytes(&cat),
+            Err(Error::TypeNotAllowed(type_name)) if type_name == "Cat"
+        ));
+        assert!(matches!(
+            dogs_only.decode("Wolf", &[]),
+            Err(Error::UnknownType(type_name)) if type_name == "Wolf"
+        ));
+        let decoded =
+            dogs_only.deserialize(&mut crate::Deserializer::new(&cat));
+        assert!(decoded.is_err());
+        assert!(registry.from_bytes(&cat).is_ok());
+    }
+
+    #[test]
+    fn trait_objects_inside_other_values() {
+        struct Pet(Box<dyn Animal>);
+
+        impl serde::Serialize for Pet {
+            fn serialize<S>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                serialize_tagged(&*self.0, serializer)
+            }
+        }
+
+        impl<'de> serde::Deserialize<'de> for Pet {
+            fn deserialize<D>(
+                deserializer: D
+            ) -> std::result::Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                registry().deserialize(deserializer).map(Pet)
+            }
+        }
+
+        let pets = vec![
+            Pet(Box::new(Cat {
+                lives: 3,
+            })),
+            Pet(Box::new(Dog {
+                name: String::from("Fido"),
+            })),
+        ];
+        let encoding = to_bytes(&pets);
+        assert!(encoding.is_ok());
+        let decoded: Result<Vec<Pet>> = from_bytes(&encoding.unwrap());
+        assert!(decoded.is_ok());
+        let decoded = decoded.unwrap();
+        assert_eq!("meow (x3)", decoded[0].0.speak());
+        assert_eq!("Fido says woof", decoded[1].0.speak());
+    }
+}
@@ -0,0 +1,618 @@
+use super::{
+    trace,
+    Result,
+};
+#[cfg(feature = "std")]
+use super::{
+    json_schema,
+    kaitai,
+};
+use alloc::{
+    boxed::Box,
+    vec::Vec,
+};
+
+/// This describes the layout of the encoding of a Rust type, as seen through
+/// its `serde` implementation.  Schemas are obtained by tracing the
+/// `Deserialize` implementation of a type using [`Schema::of`].
+///
+/// [`Schema::of`]: #method.of
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Schema {
+    /// The encoding of a `bool`.
+    Bool,
+
+    /// The encoding of an `i8`.
+    I8,
+
+    /// The encoding of an `i16`.
+    I16,
+
+    /// The encoding of an `i32`.
+    I32,
+
+    /// The encoding of an `i64`.
+    I64,
+
+    /// The encoding of an `i128`.
+    I128,
+
+    /// The encoding of a `u8`.
+    U8,
+
+    /// The encoding of a `u16`.
+    U16,
+
+    /// The encoding of a `u32`.
+    U32,
+
+    /// The encoding of a `u64`.
+    U64,
+
+    /// The encoding of a `u128`.
+    U128,
+
+    /// The encoding of an `f32`.
+    F32,
+
+    /// The encoding of an `f64`.
+    F64,
+
+    /// The encoding of a `char`.
+    Char,
+
+    /// The encoding of a string.
+    Str,
+
+    /// The encoding of a sequence of bytes.
+    Bytes,
+
+    /// The encoding of an optional value.
+    Option(Box<Schema>),
+
+    /// The encoding of the unit value `()`.
+    Unit,
+
+    /// The encoding of a unit struct.
+    UnitStruct(&'static str),
+
+    /// The encoding of a newtype struct, which is the same as that of the
+    /// value it wraps.
+    NewtypeStruct {
+        /// This is the name of the struct.
+        name: &'static str,
+
+        /// This is the schema of the value the struct wraps.
+        inner: Box<Schema>,
+    },
+
+    /// The encoding of a sequence of elements of the same type, preceded
+    /// by the number of elements.
+    Seq(Box<Schema>),
+
+    /// The encoding of a tuple.
+    Tuple(Vec<Schema>),
+
+    /// The encoding of a fixed-size array, which is the same as that of a
+    /// tuple of its elements.
+    Array(Vec<Schema>),
+
+    /// The encoding of a tuple struct.
+    TupleStruct {
+        /// This is the name of the struct.
+        name: &'static str,
+
+        /// These are the schemas of the fields of the struct, in order.
+        fields: Vec<Schema>,
+    },
+
+    /// The encoding of a map, preceded by the number of entries.
+    Map {
+        /// This is the schema of the keys of the map.
+        key: Box<Schema>,
+
+        /// This is the schema of the values of the map.
+        value: Box<Schema>,
+    },
+
+    /// The encoding of a struct with named fields.
+    Struct {
+        /// This is the name of the struct.
+        name: &'static str,
+
+        /// These are the fields of the struct, in order.
+        fields: Vec<Field>,
+    },
+
+    /// The encoding of an enum, which is the index of the variant followed
+    /// by the encoding of the variant's fields.
+    Enum {
+        /// This is the name of the enum.
+        name: &'static str,
+
+        /// These are the variants of the enum, in order.
+        variants: Vec<Variant>,
+    },
+
+    /// This stands in for the named type which contains it, in the case of
+    /// recursive types.
+    Recursive(&'static str),
+}
+
+/// This describes one field of a struct or struct variant.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Field {
+    /// This is the name of the field.
+    pub name: &'static str,
+
+    /// This is the schema of the value of the field.
+    pub schema: Schema,
+}
+
+/// This describes one variant of an enum.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Variant {
+    /// This is the name of the variant.
+    pub name: &'static str,
+
+    /// This describes the fields of the variant.
+    pub kind: VariantKind,
+}
+
+/// This describes the fields of an enum variant.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VariantKind {
+    /// The variant has no fields.
+    Unit,
+
+    /// The variant wraps a single unnamed value.
+    Newtype(Schema),
+
+    /// The variant has unnamed fields.
+    Tuple(Vec<Schema>),
+
+    /// The variant has named fields.
+    Struct(Vec<Field>),
+}
+
+// This is a 64-bit FNV-1a hasher, chosen because it is simple and produces
+// the same results on every platform and in every release.
+struct Fingerprinter(u64);
+
+impl Fingerprinter {
+    fn write(
+        &mut self,
+        bytes: &[u8],
+    ) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+    }
+
+    fn write_len(
+        &mut self,
+        len: usize,
+    ) {
+        self.write(&(len as u64).to_be_bytes());
+    }
+
+    fn write_schemas<'a, I>(
+        &mut self,
+        schemas: I,
+    ) where
+        I: ExactSizeIterator<Item = &'a Schema>,
+    {
+        self.write_len(schemas.len());
+        for schema in schemas {
+            schema.fingerprint_into(self);
+        }
+    }
+}
+
+impl Schema {
+    /// Return a hash of the layout described by the schema.  Only the
+    /// structure of the encoding contributes to the hash, so renaming types
+    /// and fields does not change it, but adding, removing, reordering, or
+    /// changing the types of fields does.
+    #[must_use]
+    pub fn fingerprint(&self) -> u64 {
+        let mut fingerprinter = Fingerprinter(0xCBF2_9CE4_8422_2325);
+        self.fingerprint_into(&mut fingerprinter);
+        fingerprinter.0
+    }
+
+    fn fingerprint_into(
+        &self,
+        fingerprinter: &mut Fingerprinter,
+    ) {
+        // Structs and tuples are encoded the same way, as are newtype
+        // structs and the values they wrap, so they share fingerprints.
+        match self {
+            Schema::Bool => fingerprinter.write(&[1]),
+            Schema::I8 => fingerprinter.write(&[2]),
+            Schema::I16 => fingerprinter.write(&[3]),
+            Schema::I32 => fingerprinter.write(&[4]),
+            Schema::I64 => fingerprinter.write(&[5]),
+            Schema::U8 => fingerprinter.write(&[6]),
+            Schema::U16 => fingerprinter.write(&[7]),
+            Schema::U32 => fingerprinter.write(&[8]),
+            Schema::U64 => fingerprinter.write(&[9]),
+            Schema::F32 => fingerprinter.write(&[10]),
+            Schema::F64 => fingerprinter.write(&[11]),
+            Schema::Char => fingerprinter.write(&[12]),
+            Schema::Str => fingerprinter.write(&[13]),
+            Schema::Bytes => fingerprinter.write(&[14]),
+            Schema::Option(inner) => {
+                fingerprinter.write(&[15]);
+                inner.fingerprint_into(fingerprinter);
+            },
+            Schema::Unit | Schema::UnitStruct(_) => {
+                fingerprinter.write(&[16]);
+            },
+            Schema::NewtypeStruct {
+                inner,
+                ..
+            } => inner.fingerprint_into(fingerprinter),
+            Schema::Seq(element) => {
+                fingerprinter.write(&[17]);
+                element.fingerprint_into(fingerprinter);
+            },
+            Schema::Tuple(fields)
+            | Schema::Array(fields)
+            | Schema::TupleStruct {
+                fields,
+                ..
+            } => {
+                fingerprinter.write(&[18]);
+                fingerprinter.write_schemas(fields.iter());
+            },
+            Schema::Map {
+                key,
+                value,
+            } => {
+                fingerprinter.write(&[19]);
+                key.fingerprint_into(fingerprinter);
+                value.fingerprint_into(fingerprinter);
+            },
+            Schema::Struct {
+                fields,
+                ..
+            } => {
+                fingerprinter.write(&[18]);
+                fingerprinter
+                    .write_schemas(fields.iter().map(|field| &field.schema));
+            },
+            Schema::Enum {
+                variants,
+                ..
+            } => {
+                fingerprinter.write(&[20]);
+                fingerprinter.write_len(variants.len());
+                for variant in variants {
+                    match &variant.kind {
+                        VariantKind::Unit => fingerprinter.write(&[16]),
+                        VariantKind::Newtype(inner) => {
+                            inner.fingerprint_into(fingerprinter);
+                        },
+                        VariantKind::Tuple(fields) => {
+                            fingerprinter.write(&[18]);
+                            fingerprinter.write_schemas(fields.iter());
+                        },
+                        VariantKind::Struct(fields) => {
+                            fingerprinter.write(&[18]);
+                            fingerprinter.write_schemas(
+                                fields.iter().map(|field| &field.schema),
+                            );
+                        },
+                    }
+                }
+            },
+            Schema::Recursive(_) => fingerprinter.write(&[21]),
+            Schema::I128 => fingerprinter.write(&[22]),
+            Schema::U128 => fingerprinter.write(&[23]),
+        }
+    }
+
+    /// Return the name of the type the schema describes, if it is a named
+    /// type (a struct or enum).
+    #[must_use]
+    pub fn name(&self) -> Option<&'static str> {
+        match self {
+            Schema::UnitStruct(name)
+            | Schema::NewtypeStruct {
+                name,
+                ..
+            }
+            | Schema::TupleStruct {
+                name,
+                ..
+            }
+            | Schema::Struct {
+                name,
+                ..
+            }
+            | Schema::Enum {
+                name,
+                ..
+            } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Return the schema of type `T`, found by tracing the calls its
+    /// `Deserialize` implementation makes.  Enums are traced repeatedly
+    /// until every variant has been seen.
+    ///
+    /// # Errors
+    ///
+    /// Types whose `Deserialize` implementations need a self-describing
+    /// format (calling `deserialize_any`), or which reject the placeholder
+    /// values provided while tracing, cannot be traced.  Recursive types
+    /// which have no finite values return [`Error::UntraceableType`].
+    ///
+    /// [`Error::UntraceableType`]: enum.Error.html#variant.UntraceableType
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// # extern crate serialization;
+    /// use serialization::{
+    ///     Field,
+    ///     Schema,
+    /// };
+    ///
+    /// #[derive(serde::Deserialize)]
+    /// struct Point {
+    ///     x: i32,
+    ///     y: i32,
+    /// }
+    ///
+    /// assert_eq!(
+    ///     Schema::Struct {
+    ///         name: "Point",
+    ///         fields: vec![
+    ///             Field {
+    ///                 name: "x",
+    ///                 schema: Schema::I32,
+    ///             },
+    ///             Field {
+    ///                 name: "y",
+    ///                 schema: Schema::I32,
+    ///             },
+    ///         ],
+    ///     },
+    ///     Schema::of::<Point>().unwrap()
+    /// );
+    /// ```
+    pub fn of<'de, T>() -> Result<Self>
+    where
+        T: serde::Deserialize<'de>,
+    {
+        trace::trace::<T>()
+    }
+
+    /// Return a [JSON Schema] (draft 2020-12) document describing values
+    /// described by the schema, as they appear when encoded as JSON using
+    /// [`Format::Json`].  Structs, tuple structs, and enums are placed in
+    /// `$defs` under their names and referenced from where they are used.
+    ///
+    /// [JSON Schema]: https://json-schema.org
+    /// [`Format::Json`]: enum.Format.html#variant.Json
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn to_json_schema(&self) -> String {
+        json_schema::json_schema(self)
+    }
+
+    /// Return a [Kaitai Struct] definition (the contents of a `.ksy` file)
+    /// describing values described by the schema, as they appear when
+    /// encoded by this crate.  This allows encoded values to be inspected
+    /// with Kaitai-based tools, or decoded by parsers generated for any
+    /// language Kaitai supports.  Each variable-length integer is given as a
+    /// `varint` or `signed_varint` whose `value` instance holds the decoded
+    /// integer.  Since Kaitai's integers have 64 bits, the `value` of an
+    /// `i128` or `u128` is only right if it fits in 64 bits, but its
+    /// `groups` always hold the whole integer.
+    ///
+    /// [Kaitai Struct]: https://kaitai.io
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub fn to_kaitai_struct(&self) -> String {
+        kaitai::kaitai_struct(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+    use std::collections::HashMap;
+
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize)]
+    enum Shape {
+        Empty,
+        Circle(f32),
+        Rectangle(f32, f32),
+        Polygon {
+            points: Vec<(f32, f32)>,
+        },
+    }
+
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize)]
+    struct Node {
+        value: u8,
+        next: Option<Box<Node>>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize)]
+    enum List {
+        Cons(u8, Box<List>),
+        Nil,
+    }
+
+    #[allow(dead_code)]
+    #[derive(serde::Deserialize)]
+    enum Endless {
+        Left(Box<Endless>),
+        Right(Box<Endless>),
+    }
+
+    #[test]
+    fn primitives_and_containers() {
+        assert_eq!(Schema::U8, Schema::of::<u8>().unwrap());
+        assert_eq!(Schema::I128, Schema::of::<i128>().unwrap());
+        assert_eq!(Schema::U128, Schema::of::<u128>().unwrap());
+        assert_eq!(Schema::Str, Schema::of::<&str>().unwrap());
+        assert_eq!(Schema::Str, Schema::of::<String>().unwrap());
+        assert_eq!(
+            Schema::Seq(Box::new(Schema::Option(Box::new(Schema::F64)))),
+            Schema::of::<Vec<Option<f64>>>().unwrap()
+        );
+        assert_eq!(
+            Schema::Map {
+                key: Box::new(Schema::Str),
+                value: Box::new(Schema::Tuple(vec![
+                    Schema::Bool,
+                    Schema::Char
+                ])),
+            },
+            Schema::of::<HashMap<String, (bool, char)>>().unwrap()
+        );
+        assert_eq!(
+            Schema::Array(vec![Schema::U16, Schema::U16]),
+            Schema::of::<[u16; 2]>().unwrap()
+        );
+        assert_eq!(Schema::Array(vec![]), Schema::of::<[u16; 0]>().unwrap());
+        assert_eq!(
+            Schema::of::<(u16, u16)>().unwrap().fingerprint(),
+            Schema::of::<[u16; 2]>().unwrap().fingerprint()
+        );
+    }
+
+    #[test]
+    fn enums_are_traced_completely() {
+        assert_eq!(
+            Schema::Enum {
+                name: "Shape",
+                variants: vec![
+                    Variant {
+                        name: "Empty",
+                        kind: VariantKind::Unit,
+                    },
+                    Variant {
+                        name: "Circle",
+                        kind: VariantKind::Newtype(Schema::F32),
+                    },
+                    Variant {
+                        name: "Rectangle",
+                        kind: VariantKind::Tuple(vec![
+                            Schema::F32,
+                            Schema::F32
+                        ]),
+                    },
+                    Variant {
+                        name: "Polygon",
+                        kind: VariantKind::Struct(vec![Field {
+                            name: "points",
+                            schema: Schema::Seq(Box::new(Schema::Tuple(vec![
+                                Schema::F32,
+                                Schema::F32
+                            ]))),
+                        }]),
+                    },
+                ],
+            },
+            Schema::of::<Shape>().unwrap()
+        );
+    }
+
+    #[test]
+    fn recursive_types() {
+        assert_eq!(
+            Schema::Struct {
+                name: "Node",
+                fields: vec![
+                    Field {
+                        name: "value",
+                        schema: Schema::U8,
+                    },
+                    Field {
+                        name: "next",
+                        schema: Schema::Option(Box::new(Schema::Recursive(
+                            "Node"
+                        ))),
+                    },
+                ],
+            },
+            Schema::of::<Node>().unwrap()
+        );
+
+        assert_eq!(
+            Schema::Enum {
+                name: "List",
+                variants: vec![
+                    Variant {
+                        name: "Cons",
+                        kind: VariantKind::Tuple(vec![
+                            Schema::U8,
+                            Schema::Recursive("List")
+                        ]),
+                    },
+                    Variant {
+                        name: "Nil",
+                        kind: VariantKind::Unit,
+                    },
+                ],
+            },
+            Schema::of::<List>().unwrap()
+        );
+
+        assert!(matches!(
+            Schema::of::<Endless>(),
+            Err(Error::UntraceableType("Endless"))
+        ));
+    }
+
+    #[test]
+    fn fingerprints_follow_wire_structure() {
+        #[derive(serde::Deserialize)]
+        struct A {
+            _x: u32,
+            _y: String,
+        }
+        #[derive(serde::Deserialize)]
+        struct Renamed {
+            _first: u32,
+            _second: String,
+        }
+        #[derive(serde::Deserialize)]
+        struct Reordered {
+            _y: String,
+            _x: u32,
+        }
+        #[derive(serde::Deserialize)]
+        struct Retyped {
+            _x: u64,
+            _y: String,
+        }
+        let fingerprint = Schema::of::<A>().unwrap().fingerprint();
+        assert_eq!(fingerprint, Schema::of::<Renamed>().unwrap().fingerprint());
+        assert_eq!(
+            fingerprint,
+            Schema::of::<(u32, String)>().unwrap().fingerprint()
+        );
+        assert_ne!(
+            fingerprint,
+            Schema::of::<Reordered>().unwrap().fingerprint()
+        );
+        assert_ne!(fingerprint, Schema::of::<Retyped>().unwrap().fingerprint());
+        assert_ne!(
+            Schema::of::<u64>().unwrap().fingerprint(),
+            Schema::of::<u128>().unwrap().fingerprint()
+        );
+    }
+}
@@ -1,59 +1,518 @@
 use super::{
+    dedup::Deduplicator,
+    int_codec::{
+        MAX_INT128_SIZE,
+        MAX_INT_SIZE,
+    },
+    name_hash::name_hash,
+    tag::Tag,
+    CharEncoding,
+    Config,
     Error,
+    IntCodec,
+    Output,
     Result,
+    Varint,
 };
+#[cfg(feature = "std")]
+use super::IoOutput;
+use alloc::{
+    collections::BTreeMap,
+    string::{
+        String,
+        ToString,
+    },
+    vec::Vec,
+};
+use core::convert::TryFrom;
 
 /// This type implements [`serde::Serializer`] in order to encode data
-/// into a sequence of bytes.
+/// into a sequence of bytes written to the given [`Output`], with integers
+/// encoded by the given [`IntCodec`].
 ///
 /// [`serde::Serializer`]:
 /// https://docs.rs/serde/1.0/serde/trait.Serializer.html
-pub struct Serializer<'ser> {
-    buffer: &'ser mut Vec<u8>,
+/// [`Output`]: trait.Output.html
+/// [`IntCodec`]: trait.IntCodec.html
+pub struct Serializer<'ser, C = Varint, O = Vec<u8>>
+where
+    O: ?Sized,
+{
+    codec: C,
+    config: Config,
+    dedup: Option<Deduplicator>,
+    frames: Vec<Start>,
+    held: Vec<u8>,
+    holding: usize,
+    interned: BTreeMap<String, usize>,
+    key: bool,
+    output: &'ser mut O,
+    raw: bool,
+    written: usize,
+}
+
+/// This is used by the [`Serializer`] to encode a sequence or map, counting
+/// its elements if its length was not known when it began.
+///
+/// [`Serializer`]: struct.Serializer.html
+pub struct Compound<'a, 'ser, C, O>
+where
+    O: ?Sized,
+{
+    ser: &'a mut Serializer<'ser, C, O>,
+    length: Option<PendingLength>,
 }
 
+// This tracks a sequence or map whose length was not known when it began.
+// Its bytes are held back until it ends, and then its length is put in
+// front of them.
+#[derive(Clone, Copy)]
+struct PendingLength {
+    count: usize,
+    start: Start,
+}
+
+// This is where the length of a sequence or map whose length was not known
+// when it began, or of a struct whose fields are framed, is put once it
+// ends.
+#[derive(Clone, Copy)]
+enum Start {
+    // The sequence or map began inside a struct being deduplicated, so its
+    // bytes are held back by the deduplicator, and this marks where it
+    // began.
+    Deduplicated((usize, usize)),
+
+    // The bytes of the sequence or map are held back by the serializer, and
+    // this is where they begin.
+    Held(usize),
+}
+
+// This is the name of the newtype struct which [`Raw`] serializes as, which
+// tells the serializer to splice the bytes it wraps directly into its output.
+//
+// [`Raw`]: ../struct.Raw.html
+pub(crate) const RAW_NAME: &str = "\0serialization::Raw";
+
 impl<'ser> Serializer<'ser> {
-    fn new(buffer: &'ser mut Vec<u8>) -> Self {
-        Self {
-            buffer,
-        }
+    pub(crate) fn new(buffer: &'ser mut Vec<u8>) -> Self {
+        Self::with_config(buffer, Config::new())
     }
 
-    #[allow(clippy::cast_possible_truncation)]
-    fn serialize_usize(
+    pub(crate) fn with_config(
+        buffer: &'ser mut Vec<u8>,
+        config: Config,
+    ) -> Self {
+        Self::with_output(buffer, config, Varint)
+    }
+}
+
+impl<'ser, C, O> Serializer<'ser, C, O>
+where
+    C: IntCodec,
+    O: ?Sized + Output,
+{
+    /// Append to the output bytes which are already encoded, such as a
+    /// cached encoding of part of a message, without re-encoding them.
+    /// The bytes must be a complete encoding of whatever value the
+    /// receiver expects at this point, since they are not checked.  They
+    /// count toward any [maximum output size], though it is only checked
+    /// when more of the value is encoded.
+    ///
+    /// [maximum output size]: struct.Config.html#method.with_max_output_size
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing the bytes to the output is returned.
+    pub fn append_raw(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<()> {
+        self.emit(bytes)
+    }
+
+    // Begin a sequence or map, which is preceded by its length if it is
+    // known up front.
+    fn begin_compound<'a>(
+        &'a mut self,
+        len: Option<usize>,
+    ) -> Result<Compound<'a, 'ser, C, O>> {
+        let length = match len {
+            Some(len) => {
+                self.serialize_usize(len)?;
+                None
+            },
+            None => Some(self.begin_unknown_length()),
+        };
+        Ok(Compound {
+            ser: self,
+            length,
+        })
+    }
+
+    // Begin the fields of a struct, which are preceded by their number if
+    // they are identified.
+    fn begin_fields(
         &mut self,
-        mut v: usize,
-    ) {
-        let mut stack = Vec::new();
-        stack.reserve(8);
-        while v & !0x7F != 0 {
-            stack.push((v & 0x7F) as u8);
-            v >>= 7;
-            if v == 0 {
-                break;
+        len: usize,
+    ) -> Result<()> {
+        if self.config.identifies_fields() {
+            self.serialize_usize(len)?;
+        }
+        Ok(())
+    }
+
+    // Start holding back the bytes of a struct or field, which are to be
+    // preceded by the number of bytes they take.
+    fn begin_frame(&mut self) {
+        let start = self.begin_unknown_length().start;
+        self.frames.push(start);
+    }
+
+    // Start holding back the bytes of a sequence or map whose length is not
+    // known up front.
+    fn begin_unknown_length(&mut self) -> PendingLength {
+        let start = match &self.dedup {
+            Some(dedup) if dedup.is_open() => Start::Deduplicated(dedup.mark()),
+            _ => {
+                self.holding += 1;
+                Start::Held(self.held.len())
+            },
+        };
+        PendingLength {
+            count: 0,
+            start,
+        }
+    }
+
+    // Check that appending the given number of bytes to the output does not
+    // make the encoding longer than the configured maximum.
+    fn check_limit(
+        &self,
+        len: usize,
+    ) -> Result<()> {
+        if let Some(limit) = self.config.max_output_size() {
+            let pending = self
+                .dedup
+                .as_ref()
+                .map_or(0, Deduplicator::pending_len)
+                .saturating_add(self.held.len());
+            if self.written.saturating_add(pending).saturating_add(len) > limit
+            {
+                return Err(Error::OutputLimitExceeded(limit));
             }
         }
-        let more = if stack.is_empty() {
-            0x00
+        Ok(())
+    }
+
+    /// Return the configuration used by the serializer.
+    #[must_use]
+    pub fn config(&self) -> Config {
+        self.config
+    }
+
+    // Write the given bytes out, or hold them back if a sequence or map whose
+    // length is not known up front is being encoded.
+    fn deliver(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<()> {
+        if self.holding > 0 {
+            self.held.extend_from_slice(bytes);
         } else {
-            0x80
+            self.output.write_bytes(bytes)?;
+            self.written += bytes.len();
+        }
+        Ok(())
+    }
+
+    // Append the varint encoding of a 128-bit integer, made by the given
+    // function, to the output, as `encode_with` does for narrower integers.
+    #[allow(clippy::mut_mut)]
+    fn encode128_with<F>(
+        &mut self,
+        encode: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut &mut [u8]) -> Result<()>,
+    {
+        let mut bytes = [0; MAX_INT128_SIZE];
+        let mut unused = &mut bytes[..];
+        encode(&mut unused)?;
+        let len = MAX_INT128_SIZE - unused.len();
+        self.write(&bytes[..len])
+    }
+
+    // Append the encoding of an integer, made by the given function using
+    // the codec, to the output, unless doing so makes the encoding longer
+    // than the configured maximum.  The codec encodes into a buffer on the
+    // stack, so that the limit can be checked before anything is written,
+    // without allocating.
+    #[allow(clippy::mut_mut)]
+    fn encode_with<F>(
+        &mut self,
+        encode: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut C, &mut &mut [u8]) -> Result<()>,
+    {
+        let mut bytes = [0; MAX_INT_SIZE];
+        let mut unused = &mut bytes[..];
+        encode(&mut self.codec, &mut unused)?;
+        let len = MAX_INT_SIZE - unused.len();
+        self.write(&bytes[..len])
+    }
+
+    // Write the given bytes out, or give them to the deduplicator if a
+    // struct is being encoded with deduplication enabled.
+    fn emit(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<()> {
+        match &mut self.dedup {
+            Some(dedup) if dedup.is_open() => {
+                dedup.write(bytes);
+                Ok(())
+            },
+            _ => self.deliver(bytes),
+        }
+    }
+
+    // Encode a field of a struct, preceded by the name of the field or its
+    // hash and the number of bytes its value takes, if fields are
+    // identified.
+    fn encode_field<T>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        if self.config.field_names() {
+            self.write_name(key)?;
+        } else if self.config.field_ids() {
+            self.write_name_hash(key)?;
+        } else {
+            return value.serialize(self);
+        }
+        self.begin_frame();
+        value.serialize(&mut *self)?;
+        self.end_frame()
+    }
+
+    // Finish the bytes of a struct or field, putting the number of bytes
+    // they take in front of them.
+    fn end_frame(&mut self) -> Result<()> {
+        let Some(start) = self.frames.pop() else {
+            return Ok(());
         };
-        self.buffer.push((v as u8) | more);
-        while !stack.is_empty() {
-            let mut next = stack.pop().unwrap();
-            if !stack.is_empty() {
-                next |= 0x80;
+        let count = match start {
+            Start::Deduplicated(mark) => {
+                self.dedup.as_ref().map_or(0, |dedup| dedup.pending_since(mark))
+            },
+            Start::Held(start) => self.held.len() - start,
+        };
+        self.insert_length(start, count, false)
+    }
+
+    // Finish encoding the contents of an enum variant, putting the number
+    // of bytes they take in front of them if struct framing is enabled.
+    fn end_variant(&mut self) -> Result<()> {
+        if self.config.struct_framing() {
+            self.end_frame()?;
+        }
+        Ok(())
+    }
+
+    // Finish encoding a struct, writing out what was held back by the
+    // deduplicator once the outermost struct is finished.
+    fn end_struct(&mut self) -> Result<()> {
+        if let Some(bytes) = self.dedup.as_mut().and_then(Deduplicator::end) {
+            self.deliver(&bytes)?;
+        }
+        if self.config.struct_framing() {
+            self.end_frame()?;
+        }
+        Ok(())
+    }
+
+    // Finish encoding a sequence or map whose length was not known up front,
+    // putting its length in front of its elements.
+    fn end_unknown_length(
+        &mut self,
+        length: PendingLength,
+    ) -> Result<()> {
+        self.insert_length(length.start, length.count, true)
+    }
+
+    // Put the given length where it was not known, which is also put in the
+    // plain encoding of the struct being deduplicated, if any, when the
+    // given flag is set.  Bytes held back are written out once the
+    // outermost length is put.
+    fn insert_length(
+        &mut self,
+        start: Start,
+        count: usize,
+        plain: bool,
+    ) -> Result<()> {
+        let mut bytes = [0; MAX_INT_SIZE];
+        let mut unused = &mut bytes[..];
+        self.codec.encode_unsigned(count as u64, &mut unused)?;
+        let len = MAX_INT_SIZE - unused.len();
+        self.check_limit(len)?;
+        match start {
+            Start::Deduplicated(mark) => {
+                if let Some(dedup) = &mut self.dedup {
+                    if plain {
+                        dedup.insert(mark, &bytes[..len]);
+                    } else {
+                        dedup.insert_pending(mark, &bytes[..len]);
+                    }
+                }
+            },
+            Start::Held(start) => {
+                self.held.splice(start..start, bytes[..len].iter().copied());
+                self.holding -= 1;
+                if self.holding == 0 {
+                    let held = core::mem::take(&mut self.held);
+                    self.output.write_bytes(&held)?;
+                    self.written += held.len();
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn serialize_usize(
+        &mut self,
+        v: usize,
+    ) -> Result<()> {
+        self.encode_with(|codec, output| {
+            codec.encode_unsigned(v as u64, output)
+        })
+    }
+
+    // Write the given tag, if the encoding is self-describing.
+    fn tag(
+        &mut self,
+        tag: Tag,
+    ) -> Result<()> {
+        if self.config.self_describing() {
+            self.write(&[tag as u8])
+        } else {
+            Ok(())
+        }
+    }
+
+    // Write the given tag followed by the given number of elements, if the
+    // encoding is self-describing.
+    fn tag_with_len(
+        &mut self,
+        tag: Tag,
+        len: usize,
+    ) -> Result<()> {
+        if self.config.self_describing() {
+            self.write(&[tag as u8])?;
+            self.serialize_usize(len)?;
+        }
+        Ok(())
+    }
+
+    // Write the index, name, or hash of the name of an enum variant,
+    // preceded by its tag if the encoding is self-describing, and start
+    // holding back its contents if struct framing is enabled.
+    fn variant(
+        &mut self,
+        variant_index: u32,
+        variant: &str,
+    ) -> Result<()> {
+        self.tag(Tag::Variant)?;
+        if self.config.variant_names() {
+            self.write_name(variant)?;
+        } else if self.config.variant_ids() {
+            self.write_name_hash(variant)?;
+        } else {
+            self.serialize_usize(variant_index as usize)?;
+        }
+        if self.config.struct_framing() {
+            self.begin_frame();
+        }
+        Ok(())
+    }
+
+    pub(crate) fn with_output(
+        output: &'ser mut O,
+        config: Config,
+        codec: C,
+    ) -> Self {
+        Self {
+            codec,
+            config,
+            dedup: if config.deduplication() {
+                Some(Deduplicator::default())
+            } else {
+                None
+            },
+            frames: Vec::new(),
+            held: Vec::new(),
+            holding: 0,
+            interned: BTreeMap::new(),
+            key: false,
+            output,
+            raw: false,
+            written: 0,
+        }
+    }
+
+    // Append the given field name or map key to the output, preceded by its
+    // length, or refer back to an earlier copy of it if it is interned.
+    fn write_name(
+        &mut self,
+        name: &str,
+    ) -> Result<()> {
+        if self.config.interns() {
+            if let Some(&index) = self.interned.get(name) {
+                return self.serialize_usize(index + 1);
             }
-            self.buffer.push(next);
+            let index = self.interned.len();
+            self.interned.insert(name.to_string(), index);
+            self.serialize_usize(0)?;
         }
+        self.serialize_usize(name.len())?;
+        self.write(name.as_bytes())
+    }
+
+    // Append the hash of the given field or variant name to the output.
+    fn write_name_hash(
+        &mut self,
+        name: &str,
+    ) -> Result<()> {
+        let id = name_hash(name);
+        self.encode_with(|codec, output| {
+            codec.encode_unsigned(u64::from(id), output)
+        })
+    }
+
+    // Append the given bytes to the output, unless doing so would make the
+    // encoding longer than the configured maximum.
+    fn write(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<()> {
+        self.check_limit(bytes.len())?;
+        self.emit(bytes)
     }
 }
 
-impl<'a, 'ser> serde::Serializer for &'a mut Serializer<'ser> {
+impl<'a, 'ser, C, O> serde::Serializer for &'a mut Serializer<'ser, C, O>
+where
+    C: IntCodec,
+    O: ?Sized + Output,
+{
     type Error = Error;
     type Ok = ();
-    type SerializeMap = Self;
-    type SerializeSeq = Self;
+    type SerializeMap = Compound<'a, 'ser, C, O>;
+    type SerializeSeq = Compound<'a, 'ser, C, O>;
     type SerializeStruct = Self;
     type SerializeStructVariant = Self;
     type SerializeTuple = Self;
@@ -64,102 +523,97 @@ impl<'a, 'ser> serde::Serializer for &'a mut Serializer<'ser> {
         self,
         v: bool,
     ) -> Result<Self::Ok> {
-        self.buffer.push(if v {
-            1
-        } else {
-            0
-        });
-        Ok(())
+        self.tag(Tag::Bool)?;
+        self.write(&[u8::from(v)])
     }
 
     fn serialize_i8(
         self,
         v: i8,
     ) -> Result<Self::Ok> {
+        self.tag(Tag::I8)?;
         #[allow(clippy::cast_sign_loss)]
-        self.buffer.push(v as u8);
-        Ok(())
+        self.write(&[v as u8])
     }
 
     fn serialize_i16(
         self,
         v: i16,
     ) -> Result<Self::Ok> {
-        self.serialize_i64(i64::from(v))
+        self.tag(Tag::I16)?;
+        self.encode_with(|codec, output| {
+            codec.encode_signed(i64::from(v), output)
+        })
     }
 
     fn serialize_i32(
         self,
         v: i32,
     ) -> Result<Self::Ok> {
-        self.serialize_i64(i64::from(v))
+        self.tag(Tag::I32)?;
+        self.encode_with(|codec, output| {
+            codec.encode_signed(i64::from(v), output)
+        })
     }
 
-    #[allow(clippy::cast_possible_truncation)]
-    #[allow(clippy::cast_sign_loss)]
     fn serialize_i64(
         self,
         v: i64,
     ) -> Result<Self::Ok> {
-        let (sign, mut abs) = if v >= 0 {
-            (0x00_u8, v as u64)
-        } else {
-            (0x40_u8, (-v) as u64)
-        };
-        let mut stack = Vec::new();
-        stack.reserve(8);
-        while abs & !(0x3F_u64) != 0 {
-            stack.push((abs & 0x7F) as u8);
-            abs >>= 7;
-            if abs == 0 {
-                break;
-            }
-        }
-        let more = if stack.is_empty() {
-            0x00
-        } else {
-            0x80
-        };
-        self.buffer.push((abs as u8) | sign | more);
-        while !stack.is_empty() {
-            let mut next = stack.pop().unwrap();
-            if !stack.is_empty() {
-                next |= 0x80;
-            }
-            self.buffer.push(next);
-        }
-        Ok(())
+        self.tag(Tag::I64)?;
+        self.encode_with(|codec, output| codec.encode_signed(v, output))
+    }
+
+    fn serialize_i128(
+        self,
+        v: i128,
+    ) -> Result<Self::Ok> {
+        self.tag(Tag::I128)?;
+        self.encode128_with(|output| Varint::encode_signed128(v, output))
     }
 
     fn serialize_u8(
         self,
         v: u8,
     ) -> Result<Self::Ok> {
-        self.buffer.push(v as u8);
-        Ok(())
+        self.tag(Tag::U8)?;
+        self.write(&[v])
     }
 
     fn serialize_u16(
         self,
         v: u16,
     ) -> Result<Self::Ok> {
-        self.serialize_u64(u64::from(v))
+        self.tag(Tag::U16)?;
+        self.encode_with(|codec, output| {
+            codec.encode_unsigned(u64::from(v), output)
+        })
     }
 
     fn serialize_u32(
         self,
         v: u32,
     ) -> Result<Self::Ok> {
-        self.serialize_u64(u64::from(v))
+        self.tag(Tag::U32)?;
+        self.encode_with(|codec, output| {
+            codec.encode_unsigned(u64::from(v), output)
+        })
     }
 
-    #[allow(clippy::cast_possible_truncation)]
     fn serialize_u64(
         self,
         v: u64,
     ) -> Result<Self::Ok> {
-        self.serialize_usize(v as usize);
-        Ok(())
+        self.tag(Tag::U64)?;
+        self.encode_with(|codec, output| codec.encode_unsigned(v, output))
+    }
+
+    fn serialize_u128(
+        self,
+        v: u128,
+    ) -> Result<Self::Ok> {
+        self.tag(Tag::U128)?;
+        self.encode128_with(|output| Varint::encode_unsigned128(v, output))
     }
 
     #[allow(clippy::cast_possible_truncation)]
@@ -167,11 +621,12 @@ impl<'a, 'ser> serde::Serializer for &'a mut Serializer<'ser> {
         self,
         v: f32,
     ) -> Result<Self::Ok> {
-        let v = unsafe { *(&v as *const f32).cast::<u32>() };
-        for i in (0..4).rev() {
-            self.buffer.push(((v >> (i * 8)) & 0xFF) as u8);
+        if cfg!(feature = "float") {
+            self.tag(Tag::F32)?;
+            self.write(&v.to_bits().to_be_bytes())
+        } else {
+            Err(Error::FloatUnsupported)
         }
-        Ok(())
     }
 
     #[allow(clippy::cast_possible_truncation)]
@@ -179,125 +634,154 @@ impl<'a, 'ser> serde::Serializer for &'a mut Serializer<'ser> {
         self,
         v: f64,
     ) -> Result<Self::Ok> {
-        let v = unsafe { *(&v as *const f64).cast::<u64>() };
-        for i in (0..8).rev() {
-            self.buffer.push(((v >> (i * 8)) & 0xFF) as u8);
+        if cfg!(feature = "float") {
+            self.tag(Tag::F64)?;
+            self.write(&v.to_bits().to_be_bytes())
+        } else {
+            Err(Error::FloatUnsupported)
         }
-        Ok(())
     }
 
     fn serialize_char(
         self,
         v: char,
     ) -> Result<Self::Ok> {
+        self.tag(Tag::Char)?;
+        if self.config.char_encoding() == CharEncoding::ScalarValue {
+            return self.encode_with(|codec, output| {
+                codec.encode_unsigned(u64::from(u32::from(v)), output)
+            });
+        }
         let mut bytes = [0; 4];
         let slice = v.encode_utf8(&mut bytes);
-        self.buffer.extend(slice.as_bytes());
-        Ok(())
+        self.write(slice.as_bytes())
     }
 
     fn serialize_str(
         self,
         v: &str,
     ) -> Result<Self::Ok> {
-        let bytes = v.as_bytes();
-        self.serialize_usize(v.len());
-        self.buffer.extend(bytes);
-        Ok(())
+        self.tag(Tag::Str)?;
+        if core::mem::take(&mut self.key) {
+            return self.write_name(v);
+        }
+        self.serialize_usize(v.len())?;
+        self.write(v.as_bytes())
     }
 
     fn serialize_bytes(
         self,
         v: &[u8],
     ) -> Result<Self::Ok> {
-        self.serialize_usize(v.len());
-        self.buffer.extend(v);
-        Ok(())
+        if self.raw {
+            self.raw = false;
+            return self.write(v);
+        }
+        self.tag(Tag::Bytes)?;
+        self.serialize_usize(v.len())?;
+        self.write(v)
     }
 
     fn serialize_none(self) -> Result<Self::Ok> {
-        self.buffer.push(0x00);
-        Ok(())
+        if self.config.self_describing() {
+            self.write(&[Tag::None as u8])
+        } else {
+            self.write(&[0x00])
+        }
     }
 
-    fn serialize_some<T: ?Sized>(
+    fn serialize_some<T>(
         self,
         value: &T,
     ) -> Result<Self::Ok>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        self.buffer.push(0x01);
+        if self.config.self_describing() {
+            self.write(&[Tag::Some as u8])?;
+        } else {
+            self.write(&[0x01])?;
+        }
         value.serialize(self)
     }
 
     fn serialize_unit(self) -> Result<Self::Ok> {
-        Ok(())
+        self.tag(Tag::Unit)
     }
 
     fn serialize_unit_struct(
         self,
         _name: &'static str,
     ) -> Result<Self::Ok> {
-        Ok(())
+        self.tag(Tag::Unit)
     }
 
     fn serialize_unit_variant(
         self,
         _name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
     ) -> Result<Self::Ok> {
-        <Self as serde::Serializer>::serialize_u32(self, variant_index)
+        self.variant(variant_index, variant)?;
+        self.tag(Tag::Unit)?;
+        self.end_variant()
     }
 
-    fn serialize_newtype_struct<T: ?Sized>(
+    fn serialize_newtype_struct<T>(
         self,
-        _name: &'static str,
+        name: &'static str,
         value: &T,
     ) -> Result<Self::Ok>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        value.serialize(self)
+        if name == RAW_NAME {
+            self.raw = true;
+            let result = value.serialize(&mut *self);
+            self.raw = false;
+            result
+        } else {
+            value.serialize(self)
+        }
     }
 
-    fn serialize_newtype_variant<T: ?Sized>(
+    fn serialize_newtype_variant<T>(
         self,
         _name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         value: &T,
     ) -> Result<Self::Ok>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        self.serialize_usize(variant_index as usize);
-        value.serialize(self)
+        self.variant(variant_index, variant)?;
+        value.serialize(&mut *self)?;
+        self.end_variant()
     }
 
     fn serialize_seq(
         self,
         len: Option<usize>,
     ) -> Result<Self::SerializeSeq> {
-        len.map_or(Err(Error::LengthRequired), move |size| {
-            self.serialize_usize(size as usize);
-            Ok(self)
-        })
+        self.tag(Tag::Seq)?;
+        self.begin_compound(len)
     }
 
     fn serialize_tuple(
         self,
-        _len: usize,
+        len: usize,
     ) -> Result<Self::SerializeTuple> {
+        self.tag_with_len(Tag::Tuple, len)?;
         Ok(self)
     }
 
     fn serialize_tuple_struct(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
     ) -> Result<Self::SerializeTupleStruct> {
+        self.tag_with_len(Tag::Tuple, len)?;
         Ok(self)
     }
 
@@ -305,13 +789,11 @@ impl<'a, 'ser> serde::Serializer for &'a mut Serializer<'ser> {
         self,
         _name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        <&mut Serializer as serde::ser::Serializer>::serialize_u32(
-            self,
-            variant_index,
-        )?;
+        self.variant(variant_index, variant)?;
+        self.tag_with_len(Tag::Tuple, len)?;
         Ok(self)
     }
 
@@ -319,17 +801,23 @@ impl<'a, 'ser> serde::Serializer for &'a mut Serializer<'ser> {
         self,
         len: Option<usize>,
     ) -> Result<Self::SerializeMap> {
-        len.map_or(Err(Error::LengthRequired), move |size| {
-            self.serialize_usize(size as usize);
-            Ok(self)
-        })
+        self.tag(Tag::Map)?;
+        self.begin_compound(len)
     }
 
     fn serialize_struct(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
     ) -> Result<Self::SerializeStruct> {
+        self.tag_with_len(Tag::Struct, len)?;
+        if self.config.struct_framing() {
+            self.begin_frame();
+        }
+        if let Some(dedup) = &mut self.dedup {
+            dedup.begin();
+        }
+        self.begin_fields(len)?;
         Ok(self)
     }
 
@@ -337,115 +825,177 @@ impl<'a, 'ser> serde::Serializer for &'a mut Serializer<'ser> {
         self,
         _name: &'static str,
         variant_index: u32,
-        _variant: &'static str,
-        _len: usize,
+        variant: &'static str,
+        len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        <&mut Serializer as serde::ser::Serializer>::serialize_u32(
-            self,
-            variant_index,
-        )?;
+        self.variant(variant_index, variant)?;
+        self.tag_with_len(Tag::StructVariant, len)?;
+        self.begin_fields(len)?;
         Ok(self)
     }
 }
 
-impl<'a, 'ser> serde::ser::SerializeMap for &'a mut Serializer<'ser> {
+impl<C, O> serde::ser::SerializeMap for Compound<'_, '_, C, O>
+where
+    C: IntCodec,
+    O: ?Sized + Output,
+{
     type Error = Error;
     type Ok = ();
 
-    fn serialize_key<T: ?Sized>(
+    fn serialize_key<T>(
         &mut self,
         key: &T,
     ) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        key.serialize(&mut **self)
+        if let Some(length) = &mut self.length {
+            length.count += 1;
+        }
+        self.ser.key = self.ser.config.interns();
+        let result = key.serialize(&mut *self.ser);
+        self.ser.key = false;
+        result
     }
 
-    fn serialize_value<T: ?Sized>(
+    fn serialize_value<T>(
         &mut self,
         value: &T,
     ) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        value.serialize(&mut **self)
+        value.serialize(&mut *self.ser)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        Ok(())
+        serde::ser::SerializeSeq::end(self)
     }
 }
 
-impl<'a, 'ser> serde::ser::SerializeSeq for &'a mut Serializer<'ser> {
+impl<C, O> serde::ser::SerializeSeq for Compound<'_, '_, C, O>
+where
+    C: IntCodec,
+    O: ?Sized + Output,
+{
     type Error = Error;
     type Ok = ();
 
-    fn serialize_element<T: ?Sized>(
+    fn serialize_element<T>(
         &mut self,
         value: &T,
     ) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        value.serialize(&mut **self)
+        if let Some(length) = &mut self.length {
+            length.count += 1;
+        }
+        value.serialize(&mut *self.ser)
     }
 
     fn end(self) -> Result<Self::Ok> {
+        match self.length {
+            Some(length) => self.ser.end_unknown_length(length),
+            None => Ok(()),
+        }
+    }
+}
+
+// Fields are identified only by their positions, so one left out of an
+// encoding cannot be decoded.  Unless shape validation is enabled this is
+// permitted, as it always has been, for encodings which are never decoded.
+fn check_skipped_field<C, O>(
+    serializer: &Serializer<C, O>,
+    key: &'static str,
+) -> Result<()>
+where
+    O: ?Sized,
+{
+    if serializer.config.shape_validation() {
+        Err(Error::FieldSkipped(key.to_string()))
+    } else {
         Ok(())
     }
 }
 
-impl<'a, 'ser> serde::ser::SerializeStruct for &'a mut Serializer<'ser> {
+impl<C, O> serde::ser::SerializeStruct for &mut Serializer<'_, C, O>
+where
+    C: IntCodec,
+    O: ?Sized + Output,
+{
     type Error = Error;
     type Ok = ();
 
-    fn serialize_field<T: ?Sized>(
+    fn serialize_field<T>(
         &mut self,
-        _key: &'static str,
+        key: &'static str,
         value: &T,
     ) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        value.serialize(&mut **self)
+        self.encode_field(key, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        Ok(())
+        self.end_struct()
+    }
+
+    fn skip_field(
+        &mut self,
+        key: &'static str,
+    ) -> Result<()> {
+        check_skipped_field(self, key)
     }
 }
 
-impl<'a, 'ser> serde::ser::SerializeStructVariant for &'a mut Serializer<'ser> {
+impl<C, O> serde::ser::SerializeStructVariant for &mut Serializer<'_, C, O>
+where
+    C: IntCodec,
+    O: ?Sized + Output,
+{
     type Error = Error;
     type Ok = ();
 
-    fn serialize_field<T: ?Sized>(
+    fn serialize_field<T>(
         &mut self,
-        _key: &'static str,
+        key: &'static str,
         value: &T,
     ) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
-        value.serialize(&mut **self)
+        self.encode_field(key, value)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        Ok(())
+        self.end_variant()
+    }
+
+    fn skip_field(
+        &mut self,
+        key: &'static str,
+    ) -> Result<()> {
+        check_skipped_field(self, key)
     }
 }
 
-impl<'a, 'ser> serde::ser::SerializeTuple for &'a mut Serializer<'ser> {
+impl<C, O> serde::ser::SerializeTuple for &mut Serializer<'_, C, O>
+where
+    C: IntCodec,
+    O: ?Sized + Output,
+{
     type Error = Error;
     type Ok = ();
 
-    fn serialize_element<T: ?Sized>(
+    fn serialize_element<T>(
         &mut self,
         value: &T,
     ) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         value.serialize(&mut **self)
     }
@@ -455,16 +1005,20 @@ impl<'a, 'ser> serde::ser::SerializeTuple for &'a mut Serializer<'ser> {
     }
 }
 
-impl<'a, 'ser> serde::ser::SerializeTupleStruct for &'a mut Serializer<'ser> {
+impl<C, O> serde::ser::SerializeTupleStruct for &mut Serializer<'_, C, O>
+where
+    C: IntCodec,
+    O: ?Sized + Output,
+{
     type Error = Error;
     type Ok = ();
 
-    fn serialize_field<T: ?Sized>(
+    fn serialize_field<T>(
         &mut self,
         value: &T,
     ) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         value.serialize(&mut **self)
     }
@@ -474,22 +1028,26 @@ impl<'a, 'ser> serde::ser::SerializeTupleStruct for &'a mut Serializer<'ser> {
     }
 }
 
-impl<'a, 'ser> serde::ser::SerializeTupleVariant for &'a mut Serializer<'ser> {
+impl<C, O> serde::ser::SerializeTupleVariant for &mut Serializer<'_, C, O>
+where
+    C: IntCodec,
+    O: ?Sized + Output,
+{
     type Error = Error;
     type Ok = ();
 
-    fn serialize_field<T: ?Sized>(
+    fn serialize_field<T>(
         &mut self,
         value: &T,
     ) -> Result<()>
     where
-        T: serde::Serialize,
+        T: ?Sized + serde::Serialize,
     {
         value.serialize(&mut **self)
     }
 
     fn end(self) -> Result<Self::Ok> {
-        Ok(())
+        self.end_variant()
     }
 }
 
@@ -510,11 +1068,525 @@ where
     Ok(buffer)
 }
 
+/// This function is used to encode a value directly into the given writer,
+/// such as a file or socket, without first building the whole encoding in
+/// memory.  The encoding is written in many small pieces, so a writer which
+/// is not already buffered should be wrapped in a
+/// [`std::io::BufWriter`].
+///
+/// [`std::io::BufWriter`]: https://doc.rust-lang.org/std/io/struct.BufWriter.html
+///
+/// # Errors
+///
+/// Returns [`Error::Io`] if the writer fails, and otherwise any of the
+/// errors returned by [`to_bytes`].  After an error, part of the encoding
+/// may have been written.
+///
+/// [`Error::Io`]: enum.Error.html#variant.Io
+/// [`to_bytes`]: fn.to_bytes.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_bytes,
+///     to_writer,
+/// };
+///
+/// let mut file = std::io::Cursor::new(Vec::new());
+/// to_writer(&mut file, &("Hello", 42_u32)).unwrap();
+/// let decoded: (String, u32) = from_bytes(file.get_ref()).unwrap();
+/// assert_eq!((String::from("Hello"), 42), decoded);
+/// ```
+#[cfg(feature = "std")]
+pub fn to_writer<W, T>(
+    writer: &mut W,
+    value: &T,
+) -> Result<()>
+where
+    W: ?Sized + std::io::Write,
+    T: ?Sized + serde::Serialize,
+{
+    let mut output = IoOutput::new(writer);
+    value.serialize(&mut Serializer::with_output(
+        &mut output,
+        Config::new(),
+        Varint,
+    ))
+}
+
+/// This function is used to encode a value into the given buffer, such as
+/// one on the stack of a target without a heap, returning the number of
+/// bytes written at the front of the buffer.  The encoding is written
+/// without allocating, unless deduplication is enabled, which this function
+/// does not do.
+///
+/// # Errors
+///
+/// Returns [`Error::BufferFull`] if the encoding does not fit in the
+/// buffer, and otherwise any of the errors returned by [`to_bytes`].  After
+/// an error, part of the encoding may have been written.
+///
+/// [`Error::BufferFull`]: enum.Error.html#variant.BufferFull
+/// [`to_bytes`]: fn.to_bytes.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_bytes,
+///     to_slice,
+/// };
+///
+/// let mut buffer = [0; 16];
+/// let len = to_slice(&("Hello", 42_u32), &mut buffer).unwrap();
+/// assert_eq!(7, len);
+/// let decoded: (&str, u32) = from_bytes(&buffer[..len]).unwrap();
+/// assert_eq!(("Hello", 42), decoded);
+/// ```
+pub fn to_slice<T>(
+    value: &T,
+    buffer: &mut [u8],
+) -> Result<usize>
+where
+    T: ?Sized + serde::Serialize,
+{
+    let capacity = buffer.len();
+    let mut output = buffer;
+    value.serialize(&mut Serializer::with_output(
+        &mut output,
+        Config::new(),
+        Varint,
+    ))?;
+    Ok(capacity - output.len())
+}
+
+/// This function is used to encode a batch of values into one sequence of
+/// bytes, preceded by the number of values, so that the whole batch can be
+/// decoded with a single call to [`from_bytes_many`].  The encoding is the
+/// same as that of a `Vec` holding the values.
+///
+/// [`from_bytes_many`]: fn.from_bytes_many.html
+///
+/// # Errors
+///
+/// Any error from encoding one of the values is returned.
+pub fn to_bytes_many<T>(values: &[T]) -> Result<Vec<u8>>
+where
+    T: serde::Serialize,
+{
+    let mut buffer = Vec::new();
+    let mut serializer = Serializer::new(&mut buffer);
+    serializer.serialize_usize(values.len())?;
+    for value in values {
+        serde::Serialize::serialize(value, &mut serializer)?;
+    }
+    Ok(buffer)
+}
+
+/// This function is used to add values to the end of a sequence which is
+/// already encoded, such as by [`to_bytes_many`] or as a `Vec<T>`, so that
+/// batches of values can be accumulated without encoding again the values
+/// already in the sequence.  Only the new values are encoded, and the
+/// number of values at the front of the encoding is updated, which moves
+/// the values already there if the number takes more bytes than before.
+/// An empty buffer is taken to be an empty sequence.
+///
+/// The values already in the sequence are not checked, so the encoding must
+/// be of a sequence of values of the same type, made with the default
+/// configuration.  If an error is returned, the encoding is left as it was.
+/// A sequence of records which are updated by appending newer ones can be
+/// compacted with [`compact_many`], keeping only the latest record for
+/// each key.
+///
+/// [`to_bytes_many`]: fn.to_bytes_many.html
+/// [`compact_many`]: fn.compact_many.html
+///
+/// # Errors
+///
+/// Returns [`Error::ValueTruncated`] if the number of values at the front of
+/// the encoding is cut off, or [`Error::IntegerOverflow`] if the number of
+/// values would be too large.  Any error from encoding one of the values is
+/// also returned.
+///
+/// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+/// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     append_many,
+///     from_bytes,
+/// };
+///
+/// let mut encoding = Vec::new();
+/// append_many(&mut encoding, &["one", "two"]).unwrap();
+/// append_many(&mut encoding, &["three"]).unwrap();
+/// let decoded: Vec<&str> = from_bytes(&encoding).unwrap();
+/// assert_eq!(vec!["one", "two", "three"], decoded);
+/// ```
+pub fn append_many<T>(
+    encoding: &mut Vec<u8>,
+    values: &[T],
+) -> Result<()>
+where
+    T: serde::Serialize,
+{
+    let (count, count_len) = if encoding.is_empty() {
+        (0, 0)
+    } else {
+        Varint.decode_unsigned(encoding, u64::MAX)?
+    };
+    let count = usize::try_from(count)
+        .ok()
+        .and_then(|count| count.checked_add(values.len()))
+        .ok_or(Error::IntegerOverflow)?;
+    let end = encoding.len();
+    let mut serializer = Serializer::new(encoding);
+    let result = values.iter().try_for_each(|value| {
+        serde::Serialize::serialize(value, &mut serializer)
+    });
+    if let Err(error) = result {
+        encoding.truncate(end);
+        return Err(error);
+    }
+    let mut count_encoding = Vec::new();
+    Varint.encode_unsigned(count as u64, &mut count_encoding)?;
+    encoding.splice(..count_len, count_encoding);
+    Ok(())
+}
+
+// Encode the items of the given iterator, preceded by their number, using
+// the given function to encode each item.
+fn serialize_items<I, F>(
+    items: I,
+    mut serialize_item: F,
+) -> Result<Vec<u8>>
+where
+    I: ExactSizeIterator,
+    F: FnMut(&mut Serializer, I::Item) -> Result<()>,
+{
+    let mut buffer = Vec::new();
+    let mut serializer = Serializer::new(&mut buffer);
+    let expected = items.len();
+    serializer.serialize_usize(expected)?;
+    let mut actual = 0;
+    for item in items {
+        serialize_item(&mut serializer, item)?;
+        actual += 1;
+    }
+    if actual == expected {
+        Ok(buffer)
+    } else {
+        Err(Error::LengthMismatch {
+            expected,
+            actual,
+        })
+    }
+}
+
+/// This function is used to encode the values produced by an iterator as
+/// a sequence, without first collecting them into a collection.  The
+/// encoding is the same as that of a `Vec` holding the values, so it can
+/// be decoded as one.
+///
+/// # Errors
+///
+/// Any error from encoding one of the values is returned.  If the
+/// iterator produces a different number of values than it reported,
+/// [`Error::LengthMismatch`] is returned.
+///
+/// [`Error::LengthMismatch`]: enum.Error.html#variant.LengthMismatch
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_bytes,
+///     to_bytes_from_iter,
+/// };
+///
+/// let encoding = to_bytes_from_iter((1..=3_u16).map(|i| i * 100)).unwrap();
+/// let decoded: Vec<u16> = from_bytes(&encoding).unwrap();
+/// assert_eq!(vec![100, 200, 300], decoded);
+/// ```
+pub fn to_bytes_from_iter<I>(values: I) -> Result<Vec<u8>>
+where
+    I: IntoIterator,
+    I::IntoIter: ExactSizeIterator,
+    I::Item: serde::Serialize,
+{
+    serialize_items(values.into_iter(), |serializer, value| {
+        serde::Serialize::serialize(&value, serializer)
+    })
+}
+
+/// This function is used to encode the key-value pairs produced by an
+/// iterator as a map, without first collecting them into a map.  The
+/// encoding is the same as that of a map holding the pairs, in the order
+/// produced, so it can be decoded as one.
+///
+/// # Errors
+///
+/// Any error from encoding one of the keys or values is returned.  If the
+/// iterator produces a different number of pairs than it reported,
+/// [`Error::LengthMismatch`] is returned.
+///
+/// [`Error::LengthMismatch`]: enum.Error.html#variant.LengthMismatch
+pub fn to_bytes_from_pairs<I, K, V>(pairs: I) -> Result<Vec<u8>>
+where
+    I: IntoIterator<Item = (K, V)>,
+    I::IntoIter: ExactSizeIterator,
+    K: serde::Serialize,
+    V: serde::Serialize,
+{
+    serialize_items(pairs.into_iter(), |serializer, (key, value)| {
+        serde::Serialize::serialize(&key, &mut *serializer)?;
+        serde::Serialize::serialize(&value, serializer)
+    })
+}
+
 #[cfg(test)]
 #[allow(clippy::non_ascii_literal)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn serialize_many() {
+        let values = [1_u16, 300, 9001];
+        let serialization = to_bytes_many(&values);
+        assert!(serialization.is_ok());
+        let serialization = serialization.unwrap();
+        assert_eq!(&[0x03, 0x01, 0x82, 0x2C, 0xC6, 0x29][..], serialization);
+        assert_eq!(to_bytes(&values.to_vec()).unwrap(), serialization);
+    }
+
+    #[test]
+    fn append_to_sequence() {
+        let mut encoding = to_bytes_many(&[7_u16; 127]).unwrap();
+        assert_eq!(0x7F, encoding[0]);
+        assert!(append_many(&mut encoding, &[300_u16]).is_ok());
+        assert_eq!(&[0x81, 0x00][..], &encoding[..2]);
+        let mut expected = vec![7_u16; 127];
+        expected.push(300);
+        assert_eq!(to_bytes(&expected).unwrap(), encoding);
+        assert!(append_many::<u16>(&mut encoding, &[]).is_ok());
+        assert_eq!(to_bytes(&expected).unwrap(), encoding);
+        let mut truncated = vec![0x80];
+        assert!(matches!(
+            append_many(&mut truncated, &[1_u8]),
+            Err(Error::ValueTruncated)
+        ));
+        assert_eq!(vec![0x80], truncated);
+    }
+
+    #[test]
+    fn serialize_to_slice() {
+        let value = (vec![1_u16, 300], "text", Some(-7_i64));
+        let expected = to_bytes(&value).unwrap();
+        let mut buffer = [0xFF; 32];
+        let len = to_slice(&value, &mut buffer);
+        assert!(len.is_ok());
+        let len = len.unwrap();
+        assert_eq!(expected, buffer[..len]);
+        assert_eq!(0xFF, buffer[len]);
+        let mut buffer = [0; 8];
+        assert!(matches!(
+            to_slice(&value, &mut buffer),
+            Err(Error::BufferFull)
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn serialize_to_writer() {
+        struct Broken;
+
+        impl std::io::Write for Broken {
+            fn write(
+                &mut self,
+                _buf: &[u8],
+            ) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::ConnectionReset.into())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let value = (vec![1_u16, 300], "text", Some(-7_i64));
+        let mut writer = Vec::new();
+        assert!(to_writer(&mut writer, &value).is_ok());
+        assert_eq!(to_bytes(&value).unwrap(), writer);
+        let result = to_writer(&mut Broken, &value);
+        assert!(matches!(
+            result,
+            Err(Error::Io(error)) if error.kind() == std::io::ErrorKind::ConnectionReset
+        ));
+    }
+
+    // This serializes its elements as a sequence without giving its length
+    // up front, as some iterator adapters do.
+    #[derive(Clone)]
+    struct Streamed<T>(Vec<T>);
+
+    impl<T> serde::Serialize for Streamed<T>
+    where
+        T: serde::Serialize,
+    {
+        fn serialize<S>(
+            &self,
+            serializer: S,
+        ) -> std::result::Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            use serde::ser::SerializeSeq;
+            let mut seq = serializer.serialize_seq(None)?;
+            for element in &self.0 {
+                seq.serialize_element(element)?;
+            }
+            seq.end()
+        }
+    }
+
+    #[derive(Clone, serde::Serialize)]
+    struct Bag {
+        name: String,
+        items: Streamed<Streamed<u16>>,
+    }
+
+    fn encode<T>(
+        config: Config,
+        value: &T,
+    ) -> Result<Vec<u8>>
+    where
+        T: serde::Serialize,
+    {
+        let mut encoding = Vec::new();
+        value.serialize(&mut config.serializer(&mut encoding))?;
+        Ok(encoding)
+    }
+
+    #[test]
+    fn serialize_seq_of_unknown_length() {
+        let value = (
+            Streamed(vec![Streamed(vec![1_u16, 300]), Streamed(vec![])]),
+            Streamed((0..200_u8).collect()),
+        );
+        let expected =
+            (vec![vec![1_u16, 300], vec![]], (0..200_u8).collect::<Vec<_>>());
+        let serialization = to_bytes(&value);
+        assert!(serialization.is_ok());
+        assert_eq!(to_bytes(&expected).unwrap(), serialization.unwrap());
+        let bag = Bag {
+            name: String::from("bag"),
+            items: Streamed(vec![Streamed(vec![7]), Streamed(vec![7])]),
+        };
+        let config = Config::new().with_deduplication(true);
+        let serialization = encode(config, &(bag.clone(), bag));
+        assert!(serialization.is_ok());
+        let decoded: Result<(String, Vec<Vec<u16>>, u8)> =
+            config.from_bytes(&serialization.unwrap()[1..]);
+        assert!(decoded.is_ok());
+        assert_eq!(
+            (String::from("bag"), vec![vec![7], vec![7]], 1),
+            decoded.unwrap()
+        );
+        let config = Config::new().with_max_output_size(4);
+        assert!(encode(config, &Streamed(vec![1_u8, 2, 3])).is_ok());
+        assert!(matches!(
+            encode(config, &Streamed(vec![1_u8, 2, 3, 4])),
+            Err(Error::OutputLimitExceeded(4))
+        ));
+    }
+
+    // This serializes its entries as a map without giving its length up
+    // front.
+    struct StreamedMap(Vec<(&'static str, u16)>);
+
+    impl serde::Serialize for StreamedMap {
+        fn serialize<S>(
+            &self,
+            serializer: S,
+        ) -> std::result::Result<S::Ok, S::Error>
+        where
+            S: serde::Serializer,
+        {
+            use serde::ser::SerializeMap;
+            let mut map = serializer.serialize_map(None)?;
+            for (key, value) in &self.0 {
+                map.serialize_entry(key, value)?;
+            }
+            map.end()
+        }
+    }
+
+    type Map = std::collections::BTreeMap<String, u16>;
+
+    #[test]
+    fn serialize_map_of_unknown_length() {
+        let value = (
+            StreamedMap(vec![("one", 1), ("three hundred", 300)]),
+            Streamed(vec![StreamedMap(vec![]), StreamedMap(vec![("x", 0)])]),
+        );
+        let serialization = to_bytes(&value);
+        assert!(serialization.is_ok());
+        let serialization = serialization.unwrap();
+        let decoded: Result<(Map, Vec<Map>)> =
+            crate::from_bytes(&serialization);
+        assert!(decoded.is_ok());
+        let (map, maps) = decoded.unwrap();
+        assert_eq!(2, map.len());
+        assert_eq!(Some(&300), map.get("three hundred"));
+        assert_eq!(2, maps.len());
+        assert!(maps[0].is_empty());
+        assert_eq!(Some(&0), maps[1].get("x"));
+    }
+
+    #[test]
+    fn serialize_from_iterators() {
+        let serialization = to_bytes_from_iter(vec!["a", "bc"]);
+        assert!(serialization.is_ok());
+        let serialization = serialization.unwrap();
+        assert_eq!(&[0x02, 0x01, b'a', 0x02, b'b', b'c'][..], serialization);
+        let map: std::collections::BTreeMap<u8, bool> =
+            vec![(1, true), (2, false)].into_iter().collect();
+        let serialization = to_bytes_from_pairs(map.iter());
+        assert!(serialization.is_ok());
+        assert_eq!(to_bytes(&map).unwrap(), serialization.unwrap());
+        assert!(matches!(
+            to_bytes_from_iter(Liar(0..3)),
+            Err(Error::LengthMismatch {
+                expected: 5,
+                actual: 3
+            })
+        ));
+    }
+
+    // This iterator reports the wrong number of items.
+    struct Liar(std::ops::Range<u8>);
+
+    impl Iterator for Liar {
+        type Item = u8;
+
+        fn next(&mut self) -> Option<u8> {
+            self.0.next()
+        }
+    }
+
+    impl ExactSizeIterator for Liar {
+        fn len(&self) -> usize {
+            5
+        }
+    }
+
     #[test]
     fn serialize_bool() {
         for (value, expected) in &[(false, &[0][..]), (true, &[1][..])] {
@@ -594,6 +1666,10 @@ mod tests {
             (-2_000_000_000_i64, &[0xC7, 0xB9, 0xD6, 0xA8, 0x00][..]),
             (2_000_000_000_000_i64, &[0xBA, 0x9A, 0xCA, 0xA8, 0xC0, 0x00][..]),
             (-2_000_000_000_000_i64, &[0xFA, 0x9A, 0xCA, 0xA8, 0xC0, 0x00][..]),
+            (
+                i64::MIN,
+                &[0xC1, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x00][..],
+            ),
         ] {
             let serialization = to_bytes(value);
             assert!(serialization.is_ok());
@@ -665,6 +1741,29 @@ mod tests {
         }
     }
 
+    #[test]
+    fn serialize_128() {
+        let mut max = vec![0x83];
+        max.extend([0xFF; 17].iter());
+        max.push(0x7F);
+        let serialization = to_bytes(&u128::MAX);
+        assert!(serialization.is_ok());
+        assert_eq!(max, serialization.unwrap());
+        let mut min = vec![0xC2];
+        min.extend([0x80; 17].iter());
+        min.push(0x00);
+        let serialization = to_bytes(&i128::MIN);
+        assert!(serialization.is_ok());
+        assert_eq!(min, serialization.unwrap());
+        // Values which fit in 64 bits encode as they do in 64 bits.
+        assert_eq!(to_bytes(&9001_u64).unwrap(), to_bytes(&9001_u128).unwrap());
+        assert_eq!(
+            to_bytes(&-70_000_i64).unwrap(),
+            to_bytes(&-70_000_i128).unwrap()
+        );
+    }
+
+    #[cfg(feature = "float")]
     #[test]
     fn serialize_f32() {
         for (value, expected) in &[
@@ -678,6 +1777,7 @@ mod tests {
         }
     }
 
+    #[cfg(feature = "float")]
     #[test]
     fn serialize_f64() {
         for (value, expected) in &[
@@ -695,6 +1795,17 @@ mod tests {
         }
     }
 
+    #[cfg(not(feature = "float"))]
+    #[test]
+    fn floats_unsupported() {
+        assert!(matches!(to_bytes(&1.5_f32), Err(Error::FloatUnsupported)));
+        assert!(matches!(to_bytes(&1.5_f64), Err(Error::FloatUnsupported)));
+        assert!(matches!(
+            crate::from_bytes::<f64>(&[0; 8]),
+            Err(Error::FloatUnsupported)
+        ));
+    }
+
     #[test]
     fn serialize_char() {
         for (value, expected) in &[
@@ -898,7 +2009,45 @@ mod tests {
     }
 
     #[test]
-    #[allow(clippy::blacklisted_name)]
+    fn shape_validation() {
+        #[derive(serde::Serialize)]
+        struct Reading {
+            value: u8,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            note: Option<String>,
+        }
+        let reading = Reading {
+            value: 7,
+            note: None,
+        };
+        let serialization = to_bytes(&reading);
+        assert!(serialization.is_ok());
+        assert_eq!(&[7][..], serialization.unwrap());
+        let config = Config::new().with_shape_validation(true);
+        assert!(config.shape_validation());
+        let mut buffer = Vec::new();
+        assert!(matches!(
+            serde::Serialize::serialize(
+                &reading,
+                &mut config.serializer(&mut buffer)
+            ),
+            Err(Error::FieldSkipped(key)) if key == "note"
+        ));
+        let reading = Reading {
+            value: 7,
+            note: Some(String::from("ok")),
+        };
+        buffer.clear();
+        assert!(serde::Serialize::serialize(
+            &reading,
+            &mut config.serializer(&mut buffer)
+        )
+        .is_ok());
+        assert_eq!(&[7, 1, 2, b'o', b'k'][..], buffer);
+    }
+
+    #[test]
+    #[allow(clippy::disallowed_names)]
     fn serialize_struct_variant() {
         #[derive(serde::Serialize)]
         enum Foo {
@@ -916,4 +2065,430 @@ mod tests {
         let serialization = serialization.unwrap();
         assert_eq!(&[1, 16, 42][..], serialization);
     }
+
+    #[test]
+    fn serialize_self_describing() {
+        #[derive(
+            Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize,
+        )]
+        enum Shape {
+            Dot,
+            Point {
+                x: u8,
+                y: u8,
+            },
+        }
+        #[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+        struct Unpacked {
+            name: String,
+            items: Vec<Vec<u16>>,
+        }
+        let config = Config::new().with_self_describing(true);
+        let value = (Some(5_u16), "ab", Shape::Point {
+            x: 1,
+            y: 2,
+        });
+        let serialization = encode(config, &value);
+        assert!(serialization.is_ok());
+        let serialization = serialization.unwrap();
+        assert_eq!(
+            &[21, 3, 18, 8, 5, 15, 2, b'a', b'b', 24, 1, 25, 2, 7, 1, 7, 2][..],
+            serialization
+        );
+        let decoded: Result<(Option<u16>, &str, Shape)> =
+            config.from_bytes(&serialization);
+        assert!(decoded.is_ok());
+        assert_eq!(value, decoded.unwrap());
+        assert_eq!(
+            &[20, 2, 7, 1, 7, 2][..],
+            encode(config, &Streamed(vec![1_u8, 2])).unwrap()
+        );
+        let bag = Bag {
+            name: String::from("bag"),
+            items: Streamed(vec![Streamed(vec![7])]),
+        };
+        let config = config.with_deduplication(true);
+        let serialization = encode(config, &(bag.clone(), bag, Shape::Dot));
+        assert!(serialization.is_ok());
+        let decoded: Result<(Unpacked, Unpacked, Shape)> =
+            config.from_bytes(&serialization.unwrap());
+        assert!(decoded.is_ok());
+        let bag = Unpacked {
+            name: String::from("bag"),
+            items: vec![vec![7]],
+        };
+        assert_eq!((bag.clone(), bag, Shape::Dot), decoded.unwrap());
+    }
+
+    #[test]
+    fn serialize_framed_structs() {
+        #[derive(Clone, serde::Serialize)]
+        struct New {
+            id: u8,
+            name: &'static str,
+            size: u16,
+        }
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Old {
+            id: u8,
+            name: String,
+        }
+        #[derive(serde::Serialize)]
+        enum NewEvent {
+            Moved {
+                id: u8,
+                to: u16,
+            },
+        }
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        enum OldEvent {
+            Moved {
+                id: u8,
+            },
+        }
+        #[derive(serde::Serialize)]
+        struct Pair {
+            first: New,
+            second: New,
+        }
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct OldPair {
+            first: Old,
+            second: Old,
+        }
+        let config = Config::new().with_struct_framing(true);
+        let new = New {
+            id: 1,
+            name: "ab",
+            size: 300,
+        };
+        let serialization = encode(config, &(new.clone(), 7_u8));
+        assert!(serialization.is_ok());
+        let serialization = serialization.unwrap();
+        assert_eq!(
+            &[0x06, 0x01, 0x02, b'a', b'b', 0x82, 0x2C, 0x07][..],
+            serialization
+        );
+        let old = Old {
+            id: 1,
+            name: String::from("ab"),
+        };
+        let decoded: Result<(Old, u8)> = config.from_bytes(&serialization);
+        assert!(decoded.is_ok());
+        assert_eq!((old, 7), decoded.unwrap());
+        let serialization = encode(config, &NewEvent::Moved {
+            id: 1,
+            to: 300,
+        });
+        assert!(serialization.is_ok());
+        let serialization = serialization.unwrap();
+        assert_eq!(&[0x00, 0x03, 0x01, 0x82, 0x2C][..], serialization);
+        let decoded: Result<OldEvent> = config.from_bytes(&serialization);
+        assert!(decoded.is_ok());
+        assert_eq!(
+            OldEvent::Moved {
+                id: 1
+            },
+            decoded.unwrap()
+        );
+        let pair = Pair {
+            first: new.clone(),
+            second: new,
+        };
+        for config in &[
+            config.with_deduplication(true),
+            config.with_deduplication(true).with_self_describing(true),
+        ] {
+            let serialization = encode(*config, &(&pair, &pair));
+            assert!(serialization.is_ok());
+            let decoded: Result<(OldPair, OldPair)> =
+                config.from_bytes(&serialization.unwrap());
+            assert!(decoded.is_ok());
+            let decoded = decoded.unwrap();
+            assert_eq!(decoded.0, decoded.1);
+            assert_eq!(decoded.0.first, decoded.0.second);
+            assert_eq!(1, decoded.1.second.id);
+        }
+        assert!(matches!(
+            config.from_bytes::<Old>(&[0x03, 0x01, 0x02, b'a']),
+            Err(Error::ValueTruncated)
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn serialize_identified_fields() {
+        #[derive(Clone, serde::Serialize)]
+        struct First {
+            id: u8,
+            name: &'static str,
+        }
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Second {
+            #[serde(default)]
+            tag: u16,
+            name: String,
+        }
+        #[derive(serde::Serialize)]
+        enum FirstEvent {
+            Named {
+                id: u8,
+                name: &'static str,
+            },
+        }
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        enum SecondEvent {
+            Named {
+                name: String,
+            },
+        }
+        let config = Config::new().with_field_ids(true);
+        let first = First {
+            id: 1,
+            name: "ab",
+        };
+        let serialization = encode(config, &first);
+        assert!(serialization.is_ok());
+        let serialization = serialization.unwrap();
+        assert_eq!(
+            &[
+                0x02, 0x83, 0xB9, 0xE1, 0xD5, 0x60, 0x01, 0x01, 0x88, 0xE9,
+                0xE6, 0xFB, 0x66, 0x03, 0x02, b'a', b'b'
+            ][..],
+            serialization
+        );
+        let second = Second {
+            tag: 0,
+            name: String::from("ab"),
+        };
+        let decoded: Result<Second> = config.from_bytes(&serialization);
+        assert!(decoded.is_ok());
+        assert_eq!(second, decoded.unwrap());
+        let serialization = encode(config, &FirstEvent::Named {
+            id: 1,
+            name: "ab",
+        });
+        assert!(serialization.is_ok());
+        let decoded: Result<SecondEvent> =
+            config.from_bytes(&serialization.unwrap());
+        assert!(decoded.is_ok());
+        assert_eq!(
+            SecondEvent::Named {
+                name: String::from("ab")
+            },
+            decoded.unwrap()
+        );
+        for config in &[
+            config.with_deduplication(true),
+            config.with_deduplication(true).with_struct_framing(true),
+            config.with_self_describing(true),
+        ] {
+            let serialization = encode(*config, &(&first, &first));
+            assert!(serialization.is_ok());
+            let decoded: Result<(Second, Second)> =
+                config.from_bytes(&serialization.unwrap());
+            assert!(decoded.is_ok());
+            let decoded = decoded.unwrap();
+            assert_eq!(second, decoded.0);
+            assert_eq!(second, decoded.1);
+        }
+        let config = config.with_self_describing(true);
+        let value: Result<crate::Value> =
+            config.from_bytes(&encode(config, &first).unwrap());
+        assert!(value.is_ok());
+        assert_eq!(
+            crate::Value::Map(vec![
+                (crate::Value::U32(0x3738_6AE0), crate::Value::U8(1)),
+                (
+                    crate::Value::U32(0x8D39_BDE6),
+                    crate::Value::Str(String::from("ab"))
+                ),
+            ]),
+            value.unwrap()
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn serialize_named_fields() {
+        #[derive(Clone, serde::Serialize)]
+        struct Item {
+            name: &'static str,
+            count: u8,
+        }
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Reordered {
+            count: u8,
+            name: String,
+        }
+        let config = Config::new().with_field_names(true);
+        let item = Item {
+            name: "ab",
+            count: 7,
+        };
+        let serialization = encode(config, &item);
+        assert!(serialization.is_ok());
+        let serialization = serialization.unwrap();
+        assert_eq!(
+            &b"\x02\x04name\x03\x02ab\x05count\x01\x07"[..],
+            serialization
+        );
+        let reordered = Reordered {
+            count: 7,
+            name: String::from("ab"),
+        };
+        let decoded: Result<Reordered> = config.from_bytes(&serialization);
+        assert!(decoded.is_ok());
+        assert_eq!(reordered, decoded.unwrap());
+        let config = config.with_interning(true);
+        let serialization = encode(config, &vec![&item, &item]);
+        assert!(serialization.is_ok());
+        let serialization = serialization.unwrap();
+        assert_eq!(
+            &b"\x02\x02\x00\x04name\x03\x02ab\x00\x05count\x01\x07\
+               \x02\x01\x03\x02ab\x02\x01\x07"[..],
+            serialization
+        );
+        let decoded: Result<Vec<Reordered>> = config.from_bytes(&serialization);
+        assert!(decoded.is_ok());
+        assert_eq!(2, decoded.unwrap().len());
+        let mut map = std::collections::BTreeMap::new();
+        map.insert("k", 5_u8);
+        let serialization = encode(config, &vec![&map, &map]);
+        assert!(serialization.is_ok());
+        let serialization = serialization.unwrap();
+        assert_eq!(&b"\x02\x01\x00\x01k\x05\x01\x01\x05"[..], serialization);
+        let decoded: Result<Vec<Map>> = config.from_bytes(&serialization);
+        assert!(decoded.is_ok());
+        assert_eq!(2, decoded.unwrap().len());
+        assert!(matches!(
+            config.from_bytes::<Vec<Map>>(&[1, 1, 3, 5]),
+            Err(Error::InvalidNameReference(3))
+        ));
+        let deduplicated = config.with_deduplication(true);
+        assert_eq!(
+            encode(deduplicated.with_interning(false), &vec![&item, &item])
+                .unwrap(),
+            encode(deduplicated, &vec![&item, &item]).unwrap()
+        );
+        let config = config.with_self_describing(true);
+        let value: Result<crate::Value> =
+            config.from_bytes(&encode(config, &(&item, &map)).unwrap());
+        assert!(value.is_ok());
+        assert_eq!(
+            crate::Value::Seq(vec![
+                crate::Value::Map(vec![
+                    (
+                        crate::Value::Str(String::from("name")),
+                        crate::Value::Str(String::from("ab"))
+                    ),
+                    (
+                        crate::Value::Str(String::from("count")),
+                        crate::Value::U8(7)
+                    ),
+                ]),
+                crate::Value::Map(vec![(
+                    crate::Value::Str(String::from("k")),
+                    crate::Value::U8(5)
+                )]),
+            ]),
+            value.unwrap()
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn serialize_variant_names() {
+        #[derive(serde::Serialize)]
+        enum Shape {
+            Circle(u8),
+            Square {
+                side: u8,
+            },
+        }
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        enum Reordered {
+            Line,
+            Square {
+                side: u8,
+            },
+            Circle(u8),
+        }
+        let config = Config::new().with_variant_names(true);
+        let serialization = encode(config, &Shape::Circle(3));
+        assert!(serialization.is_ok());
+        let serialization = serialization.unwrap();
+        assert_eq!(&b"\x06Circle\x03"[..], serialization);
+        let decoded: Result<Reordered> = config.from_bytes(&serialization);
+        assert!(decoded.is_ok());
+        assert_eq!(Reordered::Circle(3), decoded.unwrap());
+        let config = config.with_interning(true);
+        let shapes = vec![
+            Shape::Square {
+                side: 2,
+            },
+            Shape::Square {
+                side: 5,
+            },
+        ];
+        let serialization = encode(config, &shapes);
+        assert!(serialization.is_ok());
+        let serialization = serialization.unwrap();
+        assert_eq!(&b"\x02\x00\x06Square\x02\x01\x05"[..], serialization);
+        let decoded: Result<Vec<Reordered>> = config.from_bytes(&serialization);
+        assert!(decoded.is_ok());
+        assert_eq!(
+            vec![
+                Reordered::Square {
+                    side: 2
+                },
+                Reordered::Square {
+                    side: 5
+                }
+            ],
+            decoded.unwrap()
+        );
+        let value: Result<crate::Value> =
+            config.with_self_describing(true).from_bytes(
+                &encode(config.with_self_describing(true), &shapes[0]).unwrap(),
+            );
+        assert!(value.is_ok());
+        assert_eq!(
+            crate::Value::Variant {
+                index: 0,
+                name: String::from("Square"),
+                value: Box::new(crate::Value::Seq(vec![crate::Value::U8(2)])),
+            },
+            value.unwrap()
+        );
+    }
+
+    #[test]
+    fn serialize_variant_ids() {
+        #[derive(serde::Serialize)]
+        enum Shape {
+            Circle(u8),
+        }
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        enum Reordered {
+            Line,
+            Circle(u8),
+        }
+        let config = Config::new().with_variant_ids(true);
+        let serialization = encode(config, &Shape::Circle(3));
+        assert!(serialization.is_ok());
+        let serialization = serialization.unwrap();
+        assert_eq!(&[0x88, 0x89, 0x9D, 0x8D, 0x29, 0x03][..], serialization);
+        let decoded: Result<Reordered> = config.from_bytes(&serialization);
+        assert!(decoded.is_ok());
+        assert_eq!(Reordered::Circle(3), decoded.unwrap());
+        assert!(matches!(
+            config.deserializer(&serialization[..]).peek_variant_index(),
+            Ok(0x8127_46A9)
+        ));
+        assert!(matches!(
+            config.from_bytes::<Reordered>(&[0x01, 0x03]),
+            Err(Error::VariantUnknown(1))
+        ));
+    }
 }
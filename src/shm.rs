@@ -0,0 +1,616 @@
+use super::{
+    from_bytes,
+    Config,
+    Error,
+    Output,
+    Result,
+};
+use memmap2::MmapRaw;
+use std::{
+    fs::{
+        File,
+        OpenOptions,
+    },
+    path::Path,
+    sync::atomic::{
+        AtomicU64,
+        Ordering,
+    },
+};
+
+// This marks a file as holding a ring made by `ShmSender::create`.
+const MAGIC: u64 = u64::from_le_bytes(*b"SerRing1");
+
+// These are the positions of the fields in the header at the start of the
+// shared memory, each of which is a `u64` accessed atomically.  The head
+// and tail each have a cache line of their own, so that the two ends do
+// not contend for one line as they move them.
+const MAGIC_AT: usize = 0;
+const CAPACITY_AT: usize = 8;
+const CLOSED_AT: usize = 16;
+const HEAD_AT: usize = 64;
+const TAIL_AT: usize = 128;
+const HEADER_LEN: usize = 192;
+
+// Each record in the ring is its encoding preceded by its length, as a
+// little-endian `u32`.
+const LEN_PREFIX: u64 = 4;
+
+fn invalid_ring() -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        "not a shared-memory ring",
+    ))
+}
+
+// This is the mapping of a ring, which is a header followed by the bytes of
+// the records, as seen by one end.  The head and tail are counts of the
+// bytes ever written to and read from the ring, which are taken modulo its
+// capacity to find where in the ring they point.  The sender only writes
+// the part of the ring from the head up to the tail plus the capacity, and
+// the receiver only reads the part from the tail up to the head, and each
+// publishes the part it is done with by moving its counter with `Release`
+// ordering, which the other loads with `Acquire` ordering.
+struct Ring {
+    map: MmapRaw,
+    capacity: u64,
+}
+
+impl Ring {
+    fn map(file: &File) -> Result<MmapRaw> {
+        let map = MmapRaw::map_raw(file).map_err(Error::Io)?;
+        if map.len() < HEADER_LEN {
+            return Err(invalid_ring());
+        }
+        Ok(map)
+    }
+
+    fn counter(
+        &self,
+        at: usize,
+    ) -> &AtomicU64 {
+        counter(&self.map, at)
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn index(
+        &self,
+        position: u64,
+    ) -> usize {
+        (position % self.capacity) as usize
+    }
+
+    // Copy the given bytes into the ring at the given position, wrapping
+    // around its end if need be.  The bytes must be in the part of the ring
+    // which the sender may write.
+    fn write(
+        &self,
+        position: u64,
+        bytes: &[u8],
+    ) {
+        let start = self.index(position);
+        let room = self.map.len() - HEADER_LEN - start;
+        let (first, second) = bytes.split_at(bytes.len().min(room));
+        // SAFETY: Both ranges are within the records of the map, and no
+        // part of them is read by the receiver until the sender publishes
+        // them by moving the head past them.
+        unsafe {
+            let records = self.map.as_mut_ptr().add(HEADER_LEN);
+            std::ptr::copy_nonoverlapping(
+                first.as_ptr(),
+                records.add(start),
+                first.len(),
+            );
+            std::ptr::copy_nonoverlapping(
+                second.as_ptr(),
+                records,
+                second.len(),
+            );
+        }
+    }
+
+    // Return the bytes of the ring from the given position, as two slices
+    // if they wrap around its end.  The bytes must be in the part of the
+    // ring which the receiver may read, and stay valid only until the
+    // receiver moves the tail past them.
+    fn read(
+        &self,
+        position: u64,
+        len: usize,
+    ) -> (&[u8], &[u8]) {
+        let start = self.index(position);
+        let first = len.min(self.map.len() - HEADER_LEN - start);
+        // SAFETY: Both ranges are within the records of the map, and the
+        // sender writes no part of them until the receiver releases them by
+        // moving the tail past them, which it does only once it is done
+        // with these slices.
+        unsafe {
+            let records = self.map.as_ptr().add(HEADER_LEN);
+            (
+                std::slice::from_raw_parts(records.add(start), first),
+                std::slice::from_raw_parts(records, len - first),
+            )
+        }
+    }
+}
+
+#[allow(clippy::cast_ptr_alignment)]
+fn counter(
+    map: &MmapRaw,
+    at: usize,
+) -> &AtomicU64 {
+    // SAFETY: The map is aligned to a page and holds the whole header, so
+    // the counter is in bounds and aligned, and both ends only ever access
+    // it atomically.
+    unsafe { &*map.as_mut_ptr().add(at).cast::<AtomicU64>() }
+}
+
+// This is an output over the part of a ring which the sender may write,
+// into which a value being sent is encoded in place, after the room left
+// for its length.
+struct Slot<'a> {
+    ring: &'a Ring,
+    position: u64,
+    len: u64,
+    room: u64,
+}
+
+impl Output for Slot<'_> {
+    fn write_bytes(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<()> {
+        let len = bytes.len() as u64;
+        if self.room - self.len < len {
+            return Err(Error::BufferFull);
+        }
+        self.ring.write(self.position + self.len, bytes);
+        self.len += len;
+        Ok(())
+    }
+}
+
+/// This is the sending end of a ring buffer in memory shared between
+/// processes, which encodes values in place into the ring for the
+/// [`ShmReceiver`] at the other end, without a copy and without a system
+/// call.  The ring is kept in a file, which is best placed in a
+/// memory-backed file system such as `/dev/shm`, so that it is never
+/// written out to disk.  This is available with the `memmap2` feature.
+///
+/// There is no lock around the ring, so only one sender and one receiver
+/// may use a ring at a time, and they wait for each other by polling.  The
+/// memory of the ring is trusted to be changed only by them, following the
+/// same protocol, so the file must not be changed or truncated by any other
+/// means while they use it.
+///
+/// [`ShmReceiver`]: struct.ShmReceiver.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     ShmReceiver,
+///     ShmSender,
+/// };
+///
+/// # let path = std::env::temp_dir()
+/// #     .join(format!("serialization-shm-doc-{}", std::process::id()));
+/// // This would usually be done by another process.
+/// let mut sender = ShmSender::create(&path, 4096).unwrap();
+/// let producer = std::thread::spawn(move || {
+///     for i in 0..1000_u32 {
+///         sender.send(&(i, i.to_string())).unwrap();
+///     }
+/// });
+///
+/// let mut receiver = ShmReceiver::open(&path).unwrap();
+/// let mut total = 0;
+/// while let Some((i, text)) = receiver.recv::<(u32, String)>().unwrap() {
+///     assert_eq!(i.to_string(), text);
+///     total += i;
+/// }
+/// producer.join().unwrap();
+/// assert_eq!(499_500, total);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct ShmSender {
+    ring: Ring,
+}
+
+impl ShmSender {
+    /// This function is used to make a ring able to hold the given number
+    /// of bytes of records, in the file at the given path, which is created
+    /// if it does not exist, and map it as the sending end of the ring.
+    /// Each record holds the encoding of a value along with four bytes for
+    /// its length.  The sender must be made before the receiver opens the
+    /// ring.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the file could not be made or mapped.
+    ///
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    pub fn create<P>(
+        path: P,
+        capacity: usize,
+    ) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let capacity = capacity.max(1);
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(Error::Io)?;
+        file.set_len((HEADER_LEN + capacity) as u64).map_err(Error::Io)?;
+        let ring = Ring {
+            map: Ring::map(&file)?,
+            capacity: capacity as u64,
+        };
+        ring.counter(MAGIC_AT).store(0, Ordering::Relaxed);
+        ring.counter(CAPACITY_AT).store(capacity as u64, Ordering::Relaxed);
+        ring.counter(CLOSED_AT).store(0, Ordering::Relaxed);
+        ring.counter(HEAD_AT).store(0, Ordering::Relaxed);
+        ring.counter(TAIL_AT).store(0, Ordering::Relaxed);
+        ring.counter(MAGIC_AT).store(MAGIC, Ordering::Release);
+        Ok(Self {
+            ring,
+        })
+    }
+
+    /// Return the number of bytes in records queued in the ring and not yet
+    /// received.
+    #[must_use]
+    pub fn queued_bytes(&self) -> usize {
+        queued_bytes(&self.ring)
+    }
+
+    /// This function is used to encode the given value into the ring,
+    /// waiting for the receiver to make room for it if need be.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::BufferFull`] if the value does not fit even in the
+    /// empty ring, and otherwise any of the errors returned by
+    /// [`to_bytes`].
+    ///
+    /// [`Error::BufferFull`]: enum.Error.html#variant.BufferFull
+    /// [`to_bytes`]: fn.to_bytes.html
+    pub fn send<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        loop {
+            match self.try_send(value) {
+                Err(Error::BufferFull) if self.queued_bytes() > 0 => {
+                    std::thread::yield_now();
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// This function is used to encode the given value into the ring, if
+    /// there is room for it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::BufferFull`] if the value does not fit in the part
+    /// of the ring not taken by records yet to be received, and otherwise
+    /// any of the errors returned by [`to_bytes`].  Nothing is sent if an
+    /// error is returned.
+    ///
+    /// [`Error::BufferFull`]: enum.Error.html#variant.BufferFull
+    /// [`to_bytes`]: fn.to_bytes.html
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn try_send<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        let ring = &self.ring;
+        let head = ring.counter(HEAD_AT).load(Ordering::Relaxed);
+        let tail = ring.counter(TAIL_AT).load(Ordering::Acquire);
+        let free = ring.capacity - (head - tail);
+        if free < LEN_PREFIX {
+            return Err(Error::BufferFull);
+        }
+        let mut slot = Slot {
+            ring,
+            position: head + LEN_PREFIX,
+            len: 0,
+            room: (free - LEN_PREFIX).min(u64::from(u32::MAX)),
+        };
+        value.serialize(&mut Config::new().serializer(&mut slot))?;
+        let len = slot.len;
+        ring.write(head, &(len as u32).to_le_bytes());
+        ring.counter(HEAD_AT)
+            .store(head + LEN_PREFIX + len, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for ShmSender {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.debug_struct("ShmSender")
+            .field("capacity", &self.ring.capacity)
+            .field("queued_bytes", &self.queued_bytes())
+            .finish()
+    }
+}
+
+impl Drop for ShmSender {
+    fn drop(&mut self) {
+        self.ring.counter(CLOSED_AT).store(1, Ordering::Release);
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn queued_bytes(ring: &Ring) -> usize {
+    let tail = ring.counter(TAIL_AT).load(Ordering::Acquire);
+    let head = ring.counter(HEAD_AT).load(Ordering::Acquire);
+    head.saturating_sub(tail) as usize
+}
+
+/// This is the receiving end of a ring buffer in memory shared between
+/// processes, from which the values sent by the [`ShmSender`] at the other
+/// end are decoded.  Values are decoded in place in the ring, except for
+/// those wrapping around its end, which are first gathered into one buffer.
+/// This is available with the `memmap2` feature.
+///
+/// [`ShmSender`]: struct.ShmSender.html
+pub struct ShmReceiver {
+    ring: Ring,
+}
+
+impl ShmReceiver {
+    /// This function is used to map the ring made by an [`ShmSender`] in
+    /// the file at the given path, as the receiving end of the ring.
+    ///
+    /// [`ShmSender`]: struct.ShmSender.html
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the file could not be opened or mapped, or,
+    /// with the kind `InvalidData`, if it does not hold a ring.
+    ///
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    pub fn open<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(Error::Io)?;
+        let map = Ring::map(&file)?;
+        if counter(&map, MAGIC_AT).load(Ordering::Acquire) != MAGIC {
+            return Err(invalid_ring());
+        }
+        let capacity = counter(&map, CAPACITY_AT).load(Ordering::Relaxed);
+        if capacity == 0 || capacity != (map.len() - HEADER_LEN) as u64 {
+            return Err(invalid_ring());
+        }
+        Ok(Self {
+            ring: Ring {
+                map,
+                capacity,
+            },
+        })
+    }
+
+    /// Return the number of bytes in records queued in the ring and not yet
+    /// received.
+    #[must_use]
+    pub fn queued_bytes(&self) -> usize {
+        queued_bytes(&self.ring)
+    }
+
+    /// This function is used to decode the next value sent through the
+    /// ring, waiting for one to be sent if the ring is empty.  It returns
+    /// `None` once the ring is empty and the sender has been dropped.
+    ///
+    /// # Errors
+    ///
+    /// The same errors are returned as by [`try_recv`].
+    ///
+    /// [`try_recv`]: #method.try_recv
+    pub fn recv<T>(&mut self) -> Result<Option<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        loop {
+            let closed =
+                self.ring.counter(CLOSED_AT).load(Ordering::Acquire) != 0;
+            if let Some(value) = self.try_recv()? {
+                return Ok(Some(value));
+            }
+            if closed {
+                return Ok(None);
+            }
+            std::thread::yield_now();
+        }
+    }
+
+    /// This function is used to decode the next value sent through the
+    /// ring, if there is one.  The record holding the value is taken out of
+    /// the ring even if the value fails to decode, so that the records
+    /// after it can still be received.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`], with the kind `InvalidData`, if the ring has
+    /// been corrupted, and otherwise any of the errors returned by
+    /// [`from_bytes`].
+    ///
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    /// [`from_bytes`]: fn.from_bytes.html
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn try_recv<T>(&mut self) -> Result<Option<T>>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        let ring = &self.ring;
+        let tail = ring.counter(TAIL_AT).load(Ordering::Relaxed);
+        let head = ring.counter(HEAD_AT).load(Ordering::Acquire);
+        let queued = head.wrapping_sub(tail);
+        if queued == 0 {
+            return Ok(None);
+        }
+        if queued < LEN_PREFIX || queued > ring.capacity {
+            return Err(invalid_ring());
+        }
+        let mut prefix = [0; LEN_PREFIX as usize];
+        let (first, second) = ring.read(tail, prefix.len());
+        prefix[..first.len()].copy_from_slice(first);
+        prefix[first.len()..].copy_from_slice(second);
+        let len = u64::from(u32::from_le_bytes(prefix));
+        if len > queued - LEN_PREFIX {
+            return Err(invalid_ring());
+        }
+        let value = match ring.read(tail + LEN_PREFIX, len as usize) {
+            (encoding, []) => from_bytes(encoding),
+            (first, second) => from_bytes(&[first, second].concat()),
+        };
+        ring.counter(TAIL_AT)
+            .store(tail + LEN_PREFIX + len, Ordering::Release);
+        value.map(Some)
+    }
+}
+
+impl std::fmt::Debug for ShmReceiver {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.debug_struct("ShmReceiver")
+            .field("capacity", &self.ring.capacity)
+            .field("queued_bytes", &self.queued_bytes())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "serialization-shm-{name}-{}",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn records_wrap_around_the_ring() {
+        let path = temp_path("wrap");
+        let sender = ShmSender::create(&path, 32);
+        assert!(sender.is_ok());
+        let mut sender = sender.unwrap();
+        let receiver = ShmReceiver::open(&path);
+        assert!(receiver.is_ok());
+        let mut receiver = receiver.unwrap();
+        assert!(matches!(receiver.try_recv::<u8>(), Ok(None)));
+        // Each record takes 4 bytes for its length and 8 for the string, so
+        // the ring holds two at a time, and the records after the first two
+        // wrap around its end at different places.
+        for i in 0..20_u8 {
+            let text = format!("value{i:02}");
+            assert!(sender.try_send(&text).is_ok());
+            assert_eq!(12, sender.queued_bytes());
+            if i % 2 == 0 {
+                assert!(sender.try_send(&text).is_ok());
+                assert!(matches!(
+                    sender.try_send(&text),
+                    Err(Error::BufferFull)
+                ));
+                assert_eq!(24, receiver.queued_bytes());
+                let first = receiver.try_recv::<String>();
+                assert!(matches!(&first, Ok(Some(value)) if *value == text));
+            }
+            let value = receiver.try_recv::<String>();
+            assert!(matches!(&value, Ok(Some(value)) if *value == text));
+            assert!(matches!(receiver.try_recv::<String>(), Ok(None)));
+        }
+        // A value which does not fit in the empty ring is refused at once.
+        assert!(matches!(
+            sender.send(&"this is too long to fit in the ring at all"),
+            Err(Error::BufferFull)
+        ));
+        assert!(matches!(receiver.try_recv::<String>(), Ok(None)));
+        drop(sender);
+        assert!(matches!(receiver.recv::<String>(), Ok(None)));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn values_cross_between_threads() {
+        let path = temp_path("threads");
+        let sender = ShmSender::create(&path, 100);
+        assert!(sender.is_ok());
+        let mut sender = sender.unwrap();
+        let producer = std::thread::spawn(move || {
+            for i in 0..10_000_u32 {
+                sender.send(&vec![i; (i % 10) as usize])?;
+            }
+            Ok::<_, Error>(())
+        });
+        let receiver = ShmReceiver::open(&path);
+        assert!(receiver.is_ok());
+        let mut receiver = receiver.unwrap();
+        let mut i = 0_u32;
+        loop {
+            let value = receiver.recv::<Vec<u32>>();
+            assert!(value.is_ok());
+            let Some(value) = value.unwrap() else {
+                break;
+            };
+            assert_eq!(vec![i; (i % 10) as usize], value);
+            i += 1;
+        }
+        assert_eq!(10_000, i);
+        assert!(matches!(producer.join(), Ok(Ok(()))));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn errors() {
+        let path = temp_path("errors");
+        std::fs::write(&path, vec![0; 4096]).unwrap();
+        assert!(matches!(
+            ShmReceiver::open(&path),
+            Err(Error::Io(error))
+                if error.kind() == std::io::ErrorKind::InvalidData
+        ));
+        let sender = ShmSender::create(&path, 16);
+        assert!(sender.is_ok());
+        let mut sender = sender.unwrap();
+        let receiver = ShmReceiver::open(&path);
+        assert!(receiver.is_ok());
+        let mut receiver = receiver.unwrap();
+        // A record which fails to decode is taken out of the ring, and the
+        // next one is received.
+        assert!(sender.try_send(&[0xFF_u8]).is_ok());
+        assert!(sender.try_send("ok").is_ok());
+        assert!(receiver.try_recv::<String>().is_err());
+        assert!(matches!(
+            receiver.try_recv::<String>(),
+            Ok(Some(value)) if value == "ok"
+        ));
+        assert!(matches!(receiver.try_recv::<String>(), Ok(None)));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,195 @@
+// This skips over encoded values, guided by their schemas, without
+// decoding them.
+
+use super::{
+    Deserializer,
+    Error,
+    Result,
+    Schema,
+    VariantKind,
+};
+use alloc::{
+    vec,
+    vec::Vec,
+};
+
+#[derive(Clone)]
+pub(crate) struct Skipper<'s> {
+    // These are the named schemas enclosing the value being skipped, used
+    // to resolve recursive references.
+    named: Vec<&'s Schema>,
+}
+
+impl<'s> Skipper<'s> {
+    // Return a skipper for values within a value described by the given
+    // schema, so that recursive references back to it can be resolved.
+    pub(crate) fn new(root: &'s Schema) -> Self {
+        Self {
+            named: vec![root],
+        }
+    }
+
+    // Return the schema to which the given one refers, if it is a recursive
+    // reference, or the given schema otherwise.
+    pub(crate) fn resolve(
+        &self,
+        schema: &'s Schema,
+    ) -> Result<&'s Schema> {
+        if let Schema::Recursive(name) = schema {
+            self.named
+                .iter()
+                .rev()
+                .copied()
+                .find(|schema| schema.name() == Some(name))
+                .ok_or(Error::UntraceableType(name))
+        } else {
+            Ok(schema)
+        }
+    }
+
+    // Return a skipper for values within a value described by the given
+    // schema, which is within the values enclosing this skipper.
+    pub(crate) fn within(
+        &self,
+        schema: &'s Schema,
+    ) -> Self {
+        let mut skipper = self.clone();
+        if schema.name().is_some() {
+            skipper.named.push(schema);
+        }
+        skipper
+    }
+
+    pub(crate) fn skip(
+        &mut self,
+        deserializer: &mut Deserializer,
+        schema: &'s Schema,
+    ) -> Result<()> {
+        let named = schema.name().is_some();
+        if named {
+            self.named.push(schema);
+        }
+        let result = self.skip_value(deserializer, schema);
+        if named {
+            self.named.pop();
+        }
+        result
+    }
+
+    fn skip_all<I>(
+        &mut self,
+        deserializer: &mut Deserializer,
+        schemas: I,
+    ) -> Result<()>
+    where
+        I: IntoIterator<Item = &'s Schema>,
+    {
+        for schema in schemas {
+            self.skip(deserializer, schema)?;
+        }
+        Ok(())
+    }
+
+    fn skip_value(
+        &mut self,
+        deserializer: &mut Deserializer,
+        schema: &'s Schema,
+    ) -> Result<()> {
+        match schema {
+            Schema::Bool | Schema::I8 | Schema::U8 => {
+                deserializer.parse_u8()?;
+            },
+            Schema::I16 | Schema::I32 | Schema::I64 => {
+                deserializer.parse_i64(None)?;
+            },
+            Schema::U16 | Schema::U32 | Schema::U64 => {
+                deserializer.parse_u64(None)?;
+            },
+            Schema::I128 => {
+                deserializer.parse_i128()?;
+            },
+            Schema::U128 => {
+                deserializer.parse_u128()?;
+            },
+            Schema::F32 => {
+                deserializer.parse_f32()?;
+            },
+            Schema::F64 => {
+                deserializer.parse_f64()?;
+            },
+            Schema::Char => {
+                deserializer.parse_char()?;
+            },
+            Schema::Str | Schema::Bytes => {
+                deserializer.parse_bytes()?;
+            },
+            Schema::Option(inner) => {
+                if let Some(deserializer) = deserializer.parse_option()? {
+                    self.skip(deserializer, inner)?;
+                }
+            },
+            Schema::Unit | Schema::UnitStruct(_) => {},
+            Schema::NewtypeStruct {
+                inner,
+                ..
+            } => self.skip(deserializer, inner)?,
+            Schema::Seq(element) => {
+                for _ in 0..deserializer.parse_usize()? {
+                    self.skip(deserializer, element)?;
+                }
+            },
+            Schema::Tuple(fields)
+            | Schema::Array(fields)
+            | Schema::TupleStruct {
+                fields,
+                ..
+            } => self.skip_all(deserializer, fields)?,
+            Schema::Map {
+                key,
+                value,
+            } => {
+                for _ in 0..deserializer.parse_usize()? {
+                    self.skip(deserializer, key)?;
+                    self.skip(deserializer, value)?;
+                }
+            },
+            Schema::Struct {
+                fields,
+                ..
+            } => self.skip_all(
+                deserializer,
+                fields.iter().map(|field| &field.schema),
+            )?,
+            Schema::Enum {
+                variants,
+                ..
+            } => {
+                let index = deserializer.parse_usize()?;
+                let variant = variants.get(index).ok_or_else(|| {
+                    <Error as serde::de::Error>::invalid_value(
+                        serde::de::Unexpected::Unsigned(index as u64),
+                        &"variant index",
+                    )
+                })?;
+                match &variant.kind {
+                    VariantKind::Unit => {},
+                    VariantKind::Newtype(inner) => {
+                        self.skip(deserializer, inner)?;
+                    },
+                    VariantKind::Tuple(fields) => {
+                        self.skip_all(deserializer, fields)?;
+                    },
+                    VariantKind::Struct(fields) => self.skip_all(
+                        deserializer,
+                        fields.iter().map(|field| &field.schema),
+                    )?,
+                }
+            },
+            Schema::Recursive(_) => {
+                let target = self.resolve(schema)?;
+                self.skip_value(deserializer, target)?;
+            },
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,341 @@
+use super::{
+    Error,
+    Output,
+    Result,
+};
+use std::{
+    fs::{
+        File,
+        OpenOptions,
+    },
+    io::{
+        BufWriter,
+        Read,
+        Seek,
+        SeekFrom,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::atomic::{
+        AtomicUsize,
+        Ordering,
+    },
+};
+
+// This is used to give each spill file made by the process a different
+// name.
+static SPILL_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// This is an [`Output`] which holds the encoding in memory until it grows
+/// past a threshold, and from then on writes it to a temporary file, so
+/// that encoding an occasional huge value does not exhaust the memory of a
+/// service sized for the common case.  When encoding is finished, the
+/// encoding is given back by [`finish`], either in memory or as a
+/// [`SpillFile`] from which it can be read, such as to upload or stream it.
+///
+/// [`Output`]: trait.Output.html
+/// [`finish`]: #method.finish
+/// [`SpillFile`]: struct.SpillFile.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     Config,
+///     SpillOutput,
+///     Spilled,
+/// };
+/// use std::io::Read;
+///
+/// let mut output = SpillOutput::new(1024);
+/// serde::Serialize::serialize(
+///     &vec![7_u8; 4096],
+///     &mut Config::new().serializer(&mut output),
+/// )
+/// .unwrap();
+/// match output.finish().unwrap() {
+///     Spilled::Memory(_) => unreachable!(),
+///     Spilled::File(mut file) => {
+///         assert_eq!(4098, file.len());
+///         let mut encoding = Vec::new();
+///         file.read_to_end(&mut encoding).unwrap();
+///         assert_eq!(&[0xA0, 0x00, 7, 7][..], &encoding[..4]);
+///     },
+/// }
+/// ```
+pub struct SpillOutput {
+    dir: PathBuf,
+    file: Option<(BufWriter<File>, PathBuf)>,
+    len: u64,
+    memory: Vec<u8>,
+    threshold: usize,
+}
+
+impl SpillOutput {
+    /// Finish writing, and give back the encoding.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the encoding was spilled and the temporary
+    /// file could not be flushed or rewound.
+    ///
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    pub fn finish(mut self) -> Result<Spilled> {
+        let Some((writer, path)) = self.file.take() else {
+            return Ok(Spilled::Memory(std::mem::take(&mut self.memory)));
+        };
+        // The handle is made first, so that the file is removed even if
+        // finishing it fails.
+        let mut spill_file = SpillFile {
+            file: None,
+            len: self.len,
+            path,
+        };
+        let mut file = writer
+            .into_inner()
+            .map_err(|error| Error::Io(error.into_error()))?;
+        file.seek(SeekFrom::Start(0)).map_err(Error::Io)?;
+        spill_file.file = Some(file);
+        Ok(Spilled::File(spill_file))
+    }
+
+    /// Determine whether or not nothing has been written yet.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Determine whether or not the encoding has been spilled to a
+    /// temporary file.
+    #[must_use]
+    pub fn is_spilled(&self) -> bool {
+        self.file.is_some()
+    }
+
+    /// Return the number of bytes written so far.
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Return a new output which holds up to the given number of bytes in
+    /// memory, spilling to a file in the system's temporary directory
+    /// beyond that.
+    #[must_use]
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            dir: std::env::temp_dir(),
+            file: None,
+            len: 0,
+            memory: Vec::new(),
+            threshold,
+        }
+    }
+
+    // Move what is held in memory to a new temporary file, which is written
+    // from then on.
+    fn spill(&mut self) -> std::io::Result<()> {
+        let path = self.dir.join(format!(
+            "serialization-spill-{}-{}",
+            std::process::id(),
+            SPILL_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        let mut writer = BufWriter::new(file);
+        let result = writer.write_all(&self.memory);
+        self.file = Some((writer, path));
+        self.memory = Vec::new();
+        result
+    }
+
+    /// Return a copy of the output which puts its temporary file in the
+    /// given directory rather than the system's temporary directory.
+    #[must_use]
+    pub fn with_dir<P>(
+        mut self,
+        dir: P,
+    ) -> Self
+    where
+        P: Into<PathBuf>,
+    {
+        self.dir = dir.into();
+        self
+    }
+}
+
+impl Drop for SpillOutput {
+    // A temporary file is removed if the output is never finished.
+    fn drop(&mut self) {
+        if let Some((writer, path)) = self.file.take() {
+            drop(writer);
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+impl Output for SpillOutput {
+    fn write_bytes(
+        &mut self,
+        bytes: &[u8],
+    ) -> Result<()> {
+        if self.file.is_none()
+            && self.memory.len().saturating_add(bytes.len()) > self.threshold
+        {
+            self.spill().map_err(Error::Io)?;
+        }
+        match &mut self.file {
+            Some((writer, _)) => writer.write_all(bytes).map_err(Error::Io)?,
+            None => self.memory.extend_from_slice(bytes),
+        }
+        self.len += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+/// This is an encoding written by a [`SpillOutput`].
+///
+/// [`SpillOutput`]: struct.SpillOutput.html
+#[derive(Debug)]
+pub enum Spilled {
+    /// The encoding fit within the threshold, and is held in memory.
+    Memory(Vec<u8>),
+
+    /// The encoding grew past the threshold, and is held in a temporary
+    /// file.
+    File(SpillFile),
+}
+
+/// This is a temporary file holding an encoding written by a
+/// [`SpillOutput`].  The encoding is read from the start of the file using
+/// [`std::io::Read`], and the file is removed when the handle is dropped.
+///
+/// [`SpillOutput`]: struct.SpillOutput.html
+/// [`std::io::Read`]: https://doc.rust-lang.org/std/io/trait.Read.html
+#[derive(Debug)]
+pub struct SpillFile {
+    file: Option<File>,
+    len: u64,
+    path: PathBuf,
+}
+
+impl SpillFile {
+    /// Determine whether or not the encoding is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the number of bytes in the encoding.
+    #[must_use]
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Return the path of the file, such as to hand it to something which
+    /// opens files itself.  The file is still removed when the handle is
+    /// dropped.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Read for SpillFile {
+    fn read(
+        &mut self,
+        buf: &mut [u8],
+    ) -> std::io::Result<usize> {
+        match &mut self.file {
+            Some(file) => file.read(buf),
+            None => Err(std::io::ErrorKind::NotFound.into()),
+        }
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        self.file = None;
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        to_bytes,
+        Config,
+    };
+
+    fn encode<T>(
+        value: &T,
+        threshold: usize,
+    ) -> Spilled
+    where
+        T: serde::Serialize,
+    {
+        let mut output = SpillOutput::new(threshold);
+        assert!(serde::Serialize::serialize(
+            value,
+            &mut Config::new().serializer(&mut output)
+        )
+        .is_ok());
+        let spilled = output.finish();
+        assert!(spilled.is_ok());
+        spilled.unwrap()
+    }
+
+    #[test]
+    fn small_encodings_stay_in_memory() {
+        let value = (String::from("small"), 12_u32);
+        match encode(&value, 64) {
+            Spilled::Memory(encoding) => {
+                assert_eq!(to_bytes(&value).unwrap(), encoding);
+            },
+            Spilled::File(_) => panic!("small encoding was spilled"),
+        }
+    }
+
+    #[test]
+    fn large_encodings_spill_to_file() {
+        let value: Vec<u32> = (0..10_000).collect();
+        let mut file = match encode(&value, 64) {
+            Spilled::Memory(_) => panic!("large encoding was not spilled"),
+            Spilled::File(file) => file,
+        };
+        let expected = to_bytes(&value).unwrap();
+        assert_eq!(expected.len() as u64, file.len());
+        let path = file.path().to_path_buf();
+        assert!(path.exists());
+        let mut encoding = Vec::new();
+        assert!(file.read_to_end(&mut encoding).is_ok());
+        assert_eq!(expected, encoding);
+        drop(file);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn unfinished_spill_files_are_removed() {
+        let dir = std::env::temp_dir()
+            .join(format!("serialization-spill-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut output = SpillOutput::new(4).with_dir(&dir);
+        assert!(output.write_bytes(&[1, 2, 3]).is_ok());
+        assert!(!output.is_spilled());
+        assert!(output.write_bytes(&[4, 5]).is_ok());
+        assert!(output.is_spilled());
+        assert_eq!(5, output.len());
+        assert_eq!(1, std::fs::read_dir(&dir).unwrap().count());
+        drop(output);
+        assert_eq!(0, std::fs::read_dir(&dir).unwrap().count());
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
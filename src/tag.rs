@@ -0,0 +1,64 @@
+// This identifies the kind of value which follows, when every value in an
+// encoding is preceded by one so that the encoding describes itself.  See
+// `Config::with_self_describing`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Tag {
+    Bool = 1,
+    I8 = 2,
+    I16 = 3,
+    I32 = 4,
+    I64 = 5,
+    I128 = 6,
+    U8 = 7,
+    U16 = 8,
+    U32 = 9,
+    U64 = 10,
+    U128 = 11,
+    F32 = 12,
+    F64 = 13,
+    Char = 14,
+    Str = 15,
+    Bytes = 16,
+    None = 17,
+    Some = 18,
+    Unit = 19,
+    Seq = 20,
+    Tuple = 21,
+    Map = 22,
+    Struct = 23,
+    Variant = 24,
+    StructVariant = 25,
+}
+
+impl Tag {
+    pub(crate) fn from_byte(byte: u8) -> Option<Self> {
+        Some(match byte {
+            1 => Tag::Bool,
+            2 => Tag::I8,
+            3 => Tag::I16,
+            4 => Tag::I32,
+            5 => Tag::I64,
+            6 => Tag::I128,
+            7 => Tag::U8,
+            8 => Tag::U16,
+            9 => Tag::U32,
+            10 => Tag::U64,
+            11 => Tag::U128,
+            12 => Tag::F32,
+            13 => Tag::F64,
+            14 => Tag::Char,
+            15 => Tag::Str,
+            16 => Tag::Bytes,
+            17 => Tag::None,
+            18 => Tag::Some,
+            19 => Tag::Unit,
+            20 => Tag::Seq,
+            21 => Tag::Tuple,
+            22 => Tag::Map,
+            23 => Tag::Struct,
+            24 => Tag::Variant,
+            25 => Tag::StructVariant,
+            _ => return None,
+        })
+    }
+}
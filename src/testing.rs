@@ -0,0 +1,67 @@
+//! This module provides support for testing code which uses this crate, such
+//! as recording encoded values so that they can be replayed in regression
+//! tests, generating random encodings of types for property tests and
+//! fuzzing, and recording how types serialize in terms of the serde data
+//! model.  It is only available with the `testing` feature enabled.
+//!
+//! Values of any type which can be decoded can also be generated by a
+//! fuzzer, through [`Generated`], with the `arbitrary` feature, and by a
+//! property test, through [`value_strategy`], with the `proptest` feature.
+//! Either feature enables the `testing` feature.
+//!
+//! [`Generated`]: struct.Generated.html
+//! [`value_strategy`]: fn.value_strategy.html
+
+#[cfg(feature = "arbitrary")]
+mod arbitrary;
+mod capture;
+mod fault;
+mod fuzz;
+#[cfg(feature = "proptest")]
+mod proptest;
+mod random;
+mod roundtrip;
+mod snapshot;
+mod tokens;
+
+#[cfg(feature = "arbitrary")]
+pub use self::arbitrary::{
+    arbitrary_bytes,
+    arbitrary_value,
+    Generated,
+};
+pub use capture::{
+    Capture,
+    Record,
+};
+pub use fault::{
+    FaultyReader,
+    FaultyWriter,
+};
+pub use fuzz::{
+    fuzz_corpus,
+    fuzz_dictionary,
+};
+#[cfg(feature = "proptest")]
+pub use self::proptest::{
+    bytes_strategy,
+    value_strategy,
+};
+pub use random::{
+    random_bytes,
+    random_value,
+    roundtrip_property,
+    Rng,
+    ROUNDTRIP_CASES,
+    SEED_VAR,
+};
+pub use roundtrip::assert_roundtrip;
+pub use snapshot::{
+    assert_snapshot,
+    UPDATE_SNAPSHOTS_VAR,
+};
+pub use tokens::{
+    assert_ser_tokens,
+    tokens_of,
+    Token,
+};
@@ -0,0 +1,185 @@
+use super::random::{
+    generate_bytes,
+    Entropy,
+};
+use crate::{
+    from_bytes,
+    Schema,
+};
+use arbitrary::Unstructured;
+
+// This draws the choices made in generating an encoding from the input of a
+// fuzzer, so that the fuzzer steers the values generated by mutating its
+// input.  Once the input runs out, every choice is zero, which gives the
+// smallest values.
+impl Entropy for Unstructured<'_> {
+    fn below(
+        &mut self,
+        bound: u64,
+    ) -> u64 {
+        match bound {
+            0 => 0,
+            _ => self.int_in_range(0..=bound - 1).unwrap_or(0),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.arbitrary().unwrap_or(0)
+    }
+}
+
+/// This function is used to generate a buffer holding a valid encoding of
+/// a value with the given schema, making each choice, such as of a number,
+/// length, or enum variant, from the given fuzzer input.  This is the same
+/// as [`random_bytes`], except that a fuzzer can steer the encodings
+/// generated.  This is available with the `arbitrary` feature.
+///
+/// [`random_bytes`]: fn.random_bytes.html
+///
+/// # Errors
+///
+/// Returns `IncorrectFormat` if the schema is of a recursive type with no
+/// way to stop recursing.
+pub fn arbitrary_bytes(
+    schema: &Schema,
+    input: &mut Unstructured,
+) -> arbitrary::Result<Vec<u8>> {
+    generate_bytes(schema, input)
+        .map_err(|_| arbitrary::Error::IncorrectFormat)
+}
+
+/// This function is used to generate a value of type `T` from the given
+/// fuzzer input, by decoding a buffer generated by [`arbitrary_bytes`] from
+/// the schema of the type.  This is available with the `arbitrary` feature.
+///
+/// [`arbitrary_bytes`]: fn.arbitrary_bytes.html
+///
+/// # Errors
+///
+/// Returns `IncorrectFormat` if the schema of the type cannot be traced,
+/// or the value does not decode, which can happen if the `Deserialize`
+/// implementation of the type rejects some values of its fields.
+pub fn arbitrary_value<T>(input: &mut Unstructured) -> arbitrary::Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let schema =
+        Schema::of::<T>().map_err(|_| arbitrary::Error::IncorrectFormat)?;
+    from_bytes(&arbitrary_bytes(&schema, input)?)
+        .map_err(|_| arbitrary::Error::IncorrectFormat)
+}
+
+/// This holds a value of type `T` generated by [`arbitrary_value`], so that
+/// values of any type which can be decoded can be taken as the input of a
+/// fuzz target, without implementing `Arbitrary` for the type.  This is
+/// available with the `arbitrary` feature.
+///
+/// [`arbitrary_value`]: fn.arbitrary_value.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use arbitrary::{
+///     Arbitrary,
+///     Unstructured,
+/// };
+/// use serialization::{
+///     from_bytes,
+///     testing::Generated,
+///     to_bytes,
+/// };
+///
+/// #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+/// enum Command {
+///     Move { x: i32, y: i32 },
+///     Say(String),
+/// }
+///
+/// // This is the body of a fuzz target, such as one taking its input with
+/// // `libfuzzer_sys::fuzz_target!(|command: Generated<Command>| ...)`.
+/// fn check(Generated(command): Generated<Command>) {
+///     let encoding = to_bytes(&command).unwrap();
+///     assert_eq!(command, from_bytes::<Command>(&encoding).unwrap());
+/// }
+///
+/// let input = [7, 3, 1, 4, 1, 5, 9, 2, 6, 5, 3, 5, 8, 9, 7, 9];
+/// let mut input = Unstructured::new(&input);
+/// check(Generated::arbitrary(&mut input).unwrap());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Generated<T>(pub T);
+
+impl<'a, T> arbitrary::Arbitrary<'a> for Generated<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    fn arbitrary(input: &mut Unstructured<'a>) -> arbitrary::Result<Self> {
+        arbitrary_value(input).map(Generated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        to_bytes,
+        Deserializer,
+    };
+    use arbitrary::Arbitrary;
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    enum Shape {
+        Point,
+        Circle(u32),
+        Polygon(Vec<(i16, i16)>),
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Scene {
+        name: String,
+        shapes: Vec<Shape>,
+        parent: Option<Box<Scene>>,
+    }
+
+    #[test]
+    fn input_steers_the_values() {
+        let schema = Schema::of::<Scene>().unwrap();
+        let mut seen = Vec::new();
+        for seed in 0..50_u8 {
+            let input = (0..64_u8)
+                .map(|i| seed.wrapping_mul(31).wrapping_add(i.wrapping_mul(17)))
+                .collect::<Vec<_>>();
+            let bytes =
+                arbitrary_bytes(&schema, &mut Unstructured::new(&input));
+            assert!(bytes.is_ok());
+            let bytes = bytes.unwrap();
+            let mut deserializer = Deserializer::new(&bytes);
+            let value: crate::Result<Scene> =
+                serde::Deserialize::deserialize(&mut deserializer);
+            assert!(value.is_ok());
+            assert_eq!(bytes.len(), deserializer.offset());
+
+            // The same input gives the same value.
+            let again =
+                Generated::<Scene>::arbitrary(&mut Unstructured::new(&input));
+            assert!(again.is_ok());
+            assert_eq!(value.unwrap(), again.unwrap().0);
+            seen.push(bytes);
+        }
+        seen.sort();
+        seen.dedup();
+        assert!(seen.len() > 1);
+    }
+
+    #[test]
+    fn empty_input_gives_the_smallest_value() {
+        let value = arbitrary_value::<Scene>(&mut Unstructured::new(&[]));
+        assert!(matches!(
+            &value,
+            Ok(Scene { name, shapes, parent: None })
+                if name.is_empty() && shapes.is_empty()
+        ));
+        assert_eq!(vec![0, 0, 0], to_bytes(&value.unwrap()).unwrap());
+    }
+}
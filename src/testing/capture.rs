@@ -0,0 +1,224 @@
+use crate::{
+    from_bytes,
+    from_bytes_many,
+    registry::Bytes,
+    to_bytes,
+    to_bytes_many,
+    Error,
+    Result,
+};
+use serde::ser::SerializeTuple;
+use std::path::Path;
+
+/// This is one value recorded in a [`Capture`]: the name of its type and
+/// its encoding.
+///
+/// [`Capture`]: struct.Capture.html
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    type_name: String,
+    bytes: Vec<u8>,
+}
+
+impl Record {
+    /// Return the encoding of the recorded value.
+    #[must_use]
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Return the name of the type of the recorded value, as given by
+    /// [`std::any::type_name`].
+    #[must_use]
+    pub fn type_name(&self) -> &str {
+        &self.type_name
+    }
+}
+
+impl serde::Serialize for Record {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut tuple = serializer.serialize_tuple(2)?;
+        tuple.serialize_element(&self.type_name)?;
+        tuple.serialize_element(&Bytes(&self.bytes))?;
+        tuple.end()
+    }
+}
+
+/// This records values as they are encoded, along with the names of their
+/// types, so that the capture can be saved to a file and replayed later,
+/// such as to build regression and soak tests from real traffic.
+///
+/// Use [`record`] in place of [`to_bytes`] where values are encoded, then
+/// [`save`] the capture.  Later, [`load`] it and [`replay`] the values of
+/// each type of interest.
+///
+/// [`record`]: #method.record
+/// [`to_bytes`]: ../fn.to_bytes.html
+/// [`save`]: #method.save
+/// [`load`]: #method.load
+/// [`replay`]: #method.replay
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capture {
+    records: Vec<Record>,
+}
+
+impl Capture {
+    /// Read a capture previously written by [`save`].
+    ///
+    /// [`save`]: #method.save
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the file could not be read, or any error
+    /// from [`from_bytes`] if its contents are not a capture.
+    ///
+    /// [`Error::Io`]: ../enum.Error.html#variant.Io
+    /// [`from_bytes`]: ../fn.from_bytes.html
+    pub fn load<P>(path: P) -> Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let bytes = std::fs::read(path).map_err(Error::Io)?;
+        let records = from_bytes_many::<(String, Vec<u8>)>(&bytes)?
+            .into_iter()
+            .map(|(type_name, bytes)| Record {
+                type_name,
+                bytes,
+            })
+            .collect();
+        Ok(Self {
+            records,
+        })
+    }
+
+    /// Return a new capture with nothing recorded in it.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encode the given value as [`to_bytes`] does, recording the encoding
+    /// along with the name of the value's type.
+    ///
+    /// [`to_bytes`]: ../fn.to_bytes.html
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from [`to_bytes`], in which case nothing is
+    /// recorded.
+    pub fn record<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<Vec<u8>>
+    where
+        T: serde::Serialize,
+    {
+        let bytes = to_bytes(value)?;
+        self.records.push(Record {
+            type_name: String::from(std::any::type_name::<T>()),
+            bytes: bytes.clone(),
+        });
+        Ok(bytes)
+    }
+
+    /// Return everything recorded, in the order it was recorded.
+    #[must_use]
+    pub fn records(&self) -> &[Record] {
+        &self.records
+    }
+
+    /// Decode, in the order they were recorded, all recorded values of the
+    /// given type.
+    pub fn replay<'de, T>(&'de self) -> impl Iterator<Item = Result<T>> + 'de
+    where
+        T: serde::Deserialize<'de>,
+    {
+        let type_name = std::any::type_name::<T>();
+        self.records
+            .iter()
+            .filter(move |record| record.type_name == type_name)
+            .map(|record| from_bytes(&record.bytes))
+    }
+
+    /// Write everything recorded to the given file, replacing it if it
+    /// already exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the file could not be written.
+    ///
+    /// [`Error::Io`]: ../enum.Error.html#variant.Io
+    pub fn save<P>(
+        &self,
+        path: P,
+    ) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        std::fs::write(path, to_bytes_many(&self.records)?).map_err(Error::Io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Request {
+        id: u32,
+        path: String,
+    }
+
+    #[test]
+    fn record_save_load_replay() {
+        let mut capture = Capture::new();
+        let requests = vec![
+            Request {
+                id: 1,
+                path: String::from("/"),
+            },
+            Request {
+                id: 2,
+                path: String::from("/index.html"),
+            },
+        ];
+        for request in &requests {
+            let bytes = capture.record(request);
+            assert!(bytes.is_ok());
+            assert_eq!(to_bytes(request).unwrap(), bytes.unwrap());
+            capture.record(&200_u16).unwrap();
+        }
+        assert_eq!(4, capture.records().len());
+        assert_eq!("u16", capture.records()[1].type_name());
+        assert_eq!(&[0x81, 0x48][..], capture.records()[1].bytes());
+
+        let path = std::env::temp_dir()
+            .join(format!("serialization-capture-{}", std::process::id()));
+        assert!(capture.save(&path).is_ok());
+        let loaded = Capture::load(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(loaded.is_ok());
+        let loaded = loaded.unwrap();
+        assert_eq!(capture, loaded);
+
+        let replayed = loaded.replay::<Request>().collect::<Result<Vec<_>>>();
+        assert!(replayed.is_ok());
+        assert_eq!(requests, replayed.unwrap());
+        let statuses = loaded.replay::<u16>().collect::<Result<Vec<_>>>();
+        assert_eq!(vec![200, 200], statuses.unwrap());
+    }
+
+    #[test]
+    fn load_missing_file() {
+        assert!(matches!(
+            Capture::load("/nonexistent/serialization-capture"),
+            Err(Error::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,253 @@
+use std::io::{
+    Read,
+    Write,
+};
+
+fn injected_failure() -> std::io::Error {
+    std::io::Error::other("injected failure")
+}
+
+// This tracks the faults to inject into a stream of bytes, and how far
+// through the stream it is.
+#[derive(Clone, Debug, Default)]
+struct Faults {
+    fail_after: Option<usize>,
+    bit_flips: Vec<usize>,
+    position: usize,
+}
+
+impl Faults {
+    // Return how many of the next `len` bytes may pass before a failure,
+    // or `None` if the failure is due now.
+    fn allowance(
+        &self,
+        len: usize,
+    ) -> Option<usize> {
+        match self.fail_after {
+            Some(limit) if self.position >= limit => None,
+            Some(limit) => Some(len.min(limit - self.position)),
+            None => Some(len),
+        }
+    }
+
+    // Flip any bits due to be flipped in the given bytes, which are the next
+    // bytes of the stream.
+    fn flip(
+        &self,
+        bytes: &mut [u8],
+    ) {
+        let start = self.position * 8;
+        let end = start + bytes.len() * 8;
+        for bit in &self.bit_flips {
+            if (start..end).contains(bit) {
+                let offset = bit - start;
+                bytes[offset / 8] ^= 0x80 >> (offset % 8);
+            }
+        }
+    }
+}
+
+/// This wraps a writer, injecting faults into what is written through it,
+/// so that error handling can be tested without contriving real I/O
+/// failures.  Without any faults added, it passes everything through
+/// unchanged.
+#[derive(Debug)]
+pub struct FaultyWriter<W> {
+    inner: W,
+    faults: Faults,
+}
+
+impl<W> FaultyWriter<W> {
+    /// Return the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Return a new wrapper around the given writer, with no faults added.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            faults: Faults::default(),
+        }
+    }
+
+    /// Make the writer invert the bit at the given offset (in bits, counting
+    /// from the most significant bit of the first byte) of what is written
+    /// through it.
+    #[must_use]
+    pub fn with_bit_flip(
+        mut self,
+        bit: usize,
+    ) -> Self {
+        self.faults.bit_flips.push(bit);
+        self
+    }
+
+    /// Make the writer accept only `limit` bytes in total, after which
+    /// every write fails.  A write straddling the limit is cut short.
+    #[must_use]
+    pub fn with_failure_after(
+        mut self,
+        limit: usize,
+    ) -> Self {
+        self.faults.fail_after = Some(limit);
+        self
+    }
+}
+
+impl<W> Write for FaultyWriter<W>
+where
+    W: Write,
+{
+    fn write(
+        &mut self,
+        buf: &[u8],
+    ) -> std::io::Result<usize> {
+        let len =
+            self.faults.allowance(buf.len()).ok_or_else(injected_failure)?;
+        let mut bytes = buf[..len].to_vec();
+        self.faults.flip(&mut bytes);
+        let written = self.inner.write(&bytes)?;
+        self.faults.position += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// This wraps a reader, injecting faults into what is read through it, so
+/// that error handling can be tested without contriving real I/O failures.
+/// Without any faults added, it passes everything through unchanged.
+#[derive(Debug)]
+pub struct FaultyReader<R> {
+    inner: R,
+    faults: Faults,
+    truncate_after: Option<usize>,
+}
+
+impl<R> FaultyReader<R> {
+    /// Return the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Return a new wrapper around the given reader, with no faults added.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            faults: Faults::default(),
+            truncate_after: None,
+        }
+    }
+
+    /// Make the reader invert the bit at the given offset (in bits, counting
+    /// from the most significant bit of the first byte) of what is read
+    /// through it.
+    #[must_use]
+    pub fn with_bit_flip(
+        mut self,
+        bit: usize,
+    ) -> Self {
+        self.faults.bit_flips.push(bit);
+        self
+    }
+
+    /// Make the reader provide only `limit` bytes in total, after which
+    /// every read fails.  A read straddling the limit is cut short.
+    #[must_use]
+    pub fn with_failure_after(
+        mut self,
+        limit: usize,
+    ) -> Self {
+        self.faults.fail_after = Some(limit);
+        self
+    }
+
+    /// Make the reader provide only `limit` bytes in total, after which it
+    /// reports the end of the stream, as if the input was truncated.
+    #[must_use]
+    pub fn with_truncation_after(
+        mut self,
+        limit: usize,
+    ) -> Self {
+        self.truncate_after = Some(limit);
+        self
+    }
+}
+
+impl<R> Read for FaultyReader<R>
+where
+    R: Read,
+{
+    fn read(
+        &mut self,
+        buf: &mut [u8],
+    ) -> std::io::Result<usize> {
+        let mut len = buf.len();
+        if let Some(limit) = self.truncate_after {
+            len = len.min(limit.saturating_sub(self.faults.position));
+            if len == 0 {
+                return Ok(0);
+            }
+        }
+        let len = self.faults.allowance(len).ok_or_else(injected_failure)?;
+        let read = self.inner.read(&mut buf[..len])?;
+        self.faults.flip(&mut buf[..read]);
+        self.faults.position += read;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        from_bytes,
+        to_bytes,
+        Error,
+        Result,
+    };
+
+    #[test]
+    fn writer_fails_after_limit() {
+        let mut writer = FaultyWriter::new(Vec::new()).with_failure_after(3);
+        assert_eq!(3, writer.write(&[1, 2, 3, 4]).unwrap());
+        assert!(writer.write(&[4]).is_err());
+        assert!(writer.write_all(&[4]).is_err());
+        assert_eq!(vec![1, 2, 3], writer.into_inner());
+    }
+
+    #[test]
+    fn writer_flips_bits() {
+        let mut writer =
+            FaultyWriter::new(Vec::new()).with_bit_flip(0).with_bit_flip(15);
+        writer.write_all(&[0x00]).unwrap();
+        writer.write_all(&[0x00, 0x00]).unwrap();
+        assert_eq!(vec![0x80, 0x01, 0x00], writer.into_inner());
+    }
+
+    #[test]
+    fn truncated_read_is_detected() {
+        let encoding = to_bytes(&("hello", 42_u32)).unwrap();
+        let mut reader =
+            FaultyReader::new(&encoding[..]).with_truncation_after(4);
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).unwrap();
+        assert_eq!(4, bytes.len());
+        let decoded: Result<(&str, u32)> = from_bytes(&bytes);
+        assert!(matches!(decoded, Err(Error::ValueTruncated)));
+    }
+
+    #[test]
+    fn reader_fails_and_flips_bits() {
+        let mut reader = FaultyReader::new(&[0x01, 0x02, 0x03][..])
+            .with_bit_flip(9)
+            .with_failure_after(2);
+        let mut bytes = [0; 3];
+        assert_eq!(2, reader.read(&mut bytes).unwrap());
+        assert_eq!([0x01, 0x42, 0x00], bytes);
+        assert!(reader.read(&mut bytes).is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use super::{
+    random_bytes,
+    Rng,
+};
+use crate::{
+    to_bytes,
+    Result,
+    Schema,
+    Variant,
+    VariantKind,
+};
+use std::fmt::Write as _;
+
+// These are the lengths whose prefixes are put in a dictionary: the
+// smallest, those at the edges of the one- and two-byte varint encodings,
+// and those far too large for any input.
+const LENGTHS: [u64; 7] = [0, 1, 127, 128, 16383, 16384, u64::MAX];
+
+// This collects the entries of a dictionary, keeping only the first
+// occurrence of each.
+#[derive(Default)]
+struct Dictionary {
+    entries: Vec<(String, Vec<u8>)>,
+}
+
+impl Dictionary {
+    fn add(
+        &mut self,
+        name: &str,
+        bytes: Vec<u8>,
+    ) {
+        if !bytes.is_empty()
+            && !self.entries.iter().any(|(_, existing)| *existing == bytes)
+        {
+            self.entries.push((String::from(name), bytes));
+        }
+    }
+
+    fn add_value<T>(
+        &mut self,
+        name: &str,
+        value: &T,
+    ) where
+        T: serde::Serialize,
+    {
+        if let Ok(bytes) = to_bytes(value) {
+            self.add(name, bytes);
+        }
+    }
+
+    fn add_values<T>(
+        &mut self,
+        name: &str,
+        values: &[T],
+    ) where
+        T: serde::Serialize,
+    {
+        for value in values {
+            self.add_value(name, value);
+        }
+    }
+
+    fn add_fields<'s, I>(
+        &mut self,
+        schemas: I,
+    ) where
+        I: IntoIterator<Item = &'s Schema>,
+    {
+        for schema in schemas {
+            self.add_schema(schema);
+        }
+    }
+
+    fn add_schema(
+        &mut self,
+        schema: &Schema,
+    ) {
+        match schema {
+            Schema::Bool => self.add_values("bool", &[false, true]),
+            Schema::I8 | Schema::U8 => {
+                self.add_values("byte", &[0x00_u8, 0x7F, 0x80, 0xFF]);
+            },
+            Schema::I16 => {
+                self.add_values("i16", &[i16::MIN, -65, -64, -1, 63, 64]);
+                self.add_value("i16", &i16::MAX);
+            },
+            Schema::I32 => {
+                self.add_values("i32", &[i32::MIN, -65, -64, -1, 63, 64]);
+                self.add_value("i32", &i32::MAX);
+            },
+            Schema::I64 => {
+                self.add_values("i64", &[i64::MIN, -65, -64, -1, 63, 64]);
+                self.add_value("i64", &i64::MAX);
+            },
+            Schema::I128 => {
+                self.add_values("i128", &[i128::MIN, -65, -64, -1, 63, 64]);
+                self.add_value("i128", &i128::MAX);
+            },
+            Schema::U16 => {
+                self.add_values("u16", &[127_u16, 128, 16383, 16384]);
+                self.add_value("u16", &u16::MAX);
+            },
+            Schema::U32 => {
+                self.add_values("u32", &[127_u32, 128, 16383, 16384]);
+                self.add_value("u32", &u32::MAX);
+            },
+            Schema::U64 => {
+                self.add_values("u64", &[127_u64, 128, 16383, 16384]);
+                self.add_value("u64", &u64::MAX);
+            },
+            Schema::U128 => {
+                self.add_values("u128", &[127_u128, 128, 16383, 16384]);
+                self.add_value("u128", &u128::MAX);
+            },
+            Schema::F32 => self.add_values("f32", &[
+                f32::NAN,
+                f32::INFINITY,
+                f32::NEG_INFINITY,
+                -0.0,
+            ]),
+            Schema::F64 => self.add_values("f64", &[
+                f64::NAN,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                -0.0,
+            ]),
+            Schema::Char => {
+                self.add_values("char", &['\u{7F}', '\u{80}', '\u{10FFFF}']);
+                self.add_utf8();
+            },
+            Schema::Str => {
+                self.add_lengths();
+                self.add_utf8();
+            },
+            Schema::Bytes => self.add_lengths(),
+            Schema::Option(inner) => {
+                self.add_values("option", &[0_u8, 1, 2]);
+                self.add_schema(inner);
+            },
+            Schema::Unit | Schema::UnitStruct(_) | Schema::Recursive(_) => {},
+            Schema::NewtypeStruct {
+                inner,
+                ..
+            } => self.add_schema(inner),
+            Schema::Seq(element) => {
+                self.add_lengths();
+                self.add_schema(element);
+            },
+            Schema::Tuple(fields)
+            | Schema::Array(fields)
+            | Schema::TupleStruct {
+                fields,
+                ..
+            } => self.add_fields(fields),
+            Schema::Map {
+                key,
+                value,
+            } => {
+                self.add_lengths();
+                self.add_schema(key);
+                self.add_schema(value);
+            },
+            Schema::Struct {
+                fields,
+                ..
+            } => self.add_fields(fields.iter().map(|field| &field.schema)),
+            Schema::Enum {
+                variants,
+                ..
+            } => self.add_variants(variants),
+        }
+    }
+
+    fn add_lengths(&mut self) {
+        self.add_values("length", &LENGTHS);
+    }
+
+    fn add_utf8(&mut self) {
+        // These are a lone continuation byte, a truncated sequence, an
+        // encoded surrogate, and an overlong encoding.
+        self.add("utf8", vec![0x80]);
+        self.add("utf8", vec![0xE2, 0x82]);
+        self.add("utf8", vec![0xED, 0xA0, 0x80]);
+        self.add("utf8", vec![0xC0, 0xAF]);
+    }
+
+    fn add_variants(
+        &mut self,
+        variants: &[Variant],
+    ) {
+        // The index one past the last variant is included, since it is the
+        // smallest invalid one.
+        #[allow(clippy::cast_possible_truncation)]
+        for index in 0..=variants.len() as u32 {
+            self.add_value("variant", &index);
+        }
+        for variant in variants {
+            match &variant.kind {
+                VariantKind::Unit => {},
+                VariantKind::Newtype(inner) => self.add_schema(inner),
+                VariantKind::Tuple(fields) => self.add_fields(fields),
+                VariantKind::Struct(fields) => {
+                    self.add_fields(fields.iter().map(|field| &field.schema));
+                },
+            }
+        }
+    }
+
+    fn render(&self) -> String {
+        let mut output = String::new();
+        for (i, (name, bytes)) in self.entries.iter().enumerate() {
+            let _ = write!(output, "{name}_{i}=\"");
+            for byte in bytes {
+                match byte {
+                    b'"' | b'\\' => {
+                        let _ = write!(output, "\\{}", char::from(*byte));
+                    },
+                    0x20..=0x7E => output.push(char::from(*byte)),
+                    _ => {
+                        let _ = write!(output, "\\x{byte:02X}");
+                    },
+                }
+            }
+            output.push_str("\"\n");
+        }
+        output
+    }
+}
+
+/// This function is used to make a dictionary for fuzzing decoders of
+/// values with the given schema, in the format read by `libFuzzer` and AFL
+/// (`-dict=` and `-x`).  The dictionary holds encodings a fuzzer is
+/// unlikely to find on its own: integers at the edges of the varint
+/// encodings, length prefixes which are empty, on the edges of the varint
+/// encodings, or far too large, the variant indices of each enum (and the
+/// first invalid one), option tags, special floats, and malformed UTF-8.
+#[must_use]
+pub fn fuzz_dictionary(schema: &Schema) -> String {
+    let mut dictionary = Dictionary::default();
+    dictionary.add_schema(schema);
+    dictionary.render()
+}
+
+/// This function is used to make the given number of distinct inputs for
+/// seeding the corpus of a fuzzer of decoders of values with the given
+/// schema.  Each input is a valid encoding, generated by [`random_bytes`],
+/// so that the fuzzer starts from inputs which reach deep into the decoder
+/// rather than being rejected at the first byte.  Fewer inputs are returned
+/// if the schema has fewer distinct encodings than were asked for.
+///
+/// [`random_bytes`]: fn.random_bytes.html
+///
+/// # Errors
+///
+/// Returns any error from [`random_bytes`].
+pub fn fuzz_corpus(
+    schema: &Schema,
+    rng: &mut Rng,
+    count: usize,
+) -> Result<Vec<Vec<u8>>> {
+    let mut corpus: Vec<Vec<u8>> = Vec::new();
+    let mut attempts = 0;
+    while corpus.len() < count && attempts < count * 4 {
+        attempts += 1;
+        let input = random_bytes(schema, rng)?;
+        if !corpus.contains(&input) {
+            corpus.push(input);
+        }
+    }
+    Ok(corpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::from_bytes;
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    enum Command {
+        Stop,
+        Move {
+            x: i16,
+            y: i16,
+        },
+        Say(Option<String>),
+    }
+
+    #[test]
+    fn dictionary_covers_schema() {
+        let schema = Schema::of::<Vec<Command>>().unwrap();
+        let dictionary = fuzz_dictionary(&schema);
+        let lines = dictionary.lines().collect::<Vec<_>>();
+        assert!(lines.contains(&"length_0=\"\\x00\""));
+        assert!(lines.contains(&"length_1=\"\\x01\""));
+        assert!(lines.contains(&"length_3=\"\\x81\\x00\""));
+        assert!(lines.contains(&"variant_8=\"\\x03\""));
+        assert!(lines.contains(&"i16_15=\"\\x81\\xFF\\x7F\""));
+        assert!(lines.contains(&"utf8_16=\"\\x80\""));
+        assert!(!dictionary.contains("f32"));
+        assert!(lines.iter().all(|line| {
+            line.ends_with('"')
+                && line.split('=').nth(1).unwrap().starts_with('"')
+        }));
+    }
+
+    #[test]
+    fn dictionary_covers_wide_integers() {
+        let schema = Schema::of::<(u128, i128)>().unwrap();
+        let dictionary = fuzz_dictionary(&schema);
+        let lines = dictionary.lines().collect::<Vec<_>>();
+        assert!(lines.contains(&"u128_1=\"\\x81\\x00\""));
+        assert!(lines.iter().any(|line| line.starts_with("i128_")));
+    }
+
+    #[test]
+    fn dictionary_escapes_quotes() {
+        let mut dictionary = Dictionary::default();
+        dictionary.add("text", b"a\"b\\".to_vec());
+        dictionary.add("text", b"a\"b\\".to_vec());
+        dictionary.add("text", vec![0x7F, b' ']);
+        assert_eq!(
+            "text_0=\"a\\\"b\\\\\"\ntext_1=\"\\x7F \"\n",
+            dictionary.render()
+        );
+    }
+
+    #[test]
+    fn corpus_is_distinct_and_valid() {
+        let schema = Schema::of::<Vec<Command>>().unwrap();
+        let mut rng = Rng::new(7);
+        let corpus = fuzz_corpus(&schema, &mut rng, 16);
+        assert!(corpus.is_ok());
+        let corpus = corpus.unwrap();
+        assert_eq!(16, corpus.len());
+        for (i, input) in corpus.iter().enumerate() {
+            assert!(from_bytes::<Vec<Command>>(input).is_ok());
+            assert!(!corpus[..i].contains(input));
+        }
+        let mut rng = Rng::new(7);
+        let corpus = fuzz_corpus(&Schema::Bool, &mut rng, 16).unwrap();
+        assert_eq!(2, corpus.len());
+    }
+}
@@ -0,0 +1,172 @@
+use super::random::{
+    generate_bytes,
+    Entropy,
+};
+use crate::{
+    from_bytes,
+    Schema,
+};
+use proptest::{
+    collection::vec,
+    prelude::any,
+    strategy::Strategy,
+};
+
+// This is the most choices drawn from a property test for one value.  Any
+// choices made beyond these are zero, which gives the smallest values.
+const MAX_CHOICES: usize = 256;
+
+// This replays choices drawn from a property test in generating an
+// encoding.  Each choice is scaled rather than reduced to its range, so
+// that shrinking the choice toward zero shrinks what is chosen along with
+// it.
+struct Tape<'a>(std::slice::Iter<'a, u64>);
+
+impl Entropy for Tape<'_> {
+    #[allow(clippy::cast_possible_truncation)]
+    fn below(
+        &mut self,
+        bound: u64,
+    ) -> u64 {
+        ((u128::from(self.next_u64()) * u128::from(bound)) >> 64) as u64
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.next().copied().unwrap_or(0)
+    }
+}
+
+/// This function is used to make a `proptest` strategy for buffers holding
+/// valid encodings of values with the given schema.  This is the same as
+/// [`random_bytes`], except that failing encodings shrink toward smaller
+/// ones, with fewer elements, nearer the first enum variants, and with
+/// numbers nearer zero.  This is available with the `proptest` feature.
+///
+/// Encodings of a recursive type with no way to stop recursing are
+/// rejected, so the strategy gives up if there are too many of them.
+///
+/// [`random_bytes`]: fn.random_bytes.html
+pub fn bytes_strategy(schema: Schema) -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u64>(), 0..=MAX_CHOICES).prop_filter_map(
+        "the schema recurses without end",
+        move |choices| generate_bytes(&schema, &mut Tape(choices.iter())).ok(),
+    )
+}
+
+/// This function is used to make a `proptest` strategy for values of type
+/// `T`, by decoding buffers made by [`bytes_strategy`] from the schema of
+/// the type.  Values which fail to decode, which can happen if the
+/// `Deserialize` implementation of the type rejects some values of its
+/// fields, are rejected.  This is available with the `proptest` feature.
+///
+/// [`bytes_strategy`]: fn.bytes_strategy.html
+///
+/// # Panics
+///
+/// This function panics if the schema of the type cannot be traced.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use proptest::prelude::*;
+/// use serialization::{
+///     from_bytes,
+///     testing::value_strategy,
+///     to_bytes,
+/// };
+///
+/// #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+/// enum Command {
+///     Move { x: i32, y: i32 },
+///     Say(String),
+/// }
+///
+/// // This would usually be marked as a test.
+/// proptest! {
+///     # #![proptest_config(ProptestConfig::with_cases(16))]
+///     fn commands_roundtrip(command in value_strategy::<Command>()) {
+///         let encoding = to_bytes(&command).unwrap();
+///         prop_assert_eq!(command, from_bytes::<Command>(&encoding).unwrap());
+///     }
+/// }
+/// # commands_roundtrip();
+/// ```
+pub fn value_strategy<T>() -> impl Strategy<Value = T>
+where
+    T: serde::de::DeserializeOwned + std::fmt::Debug,
+{
+    let schema = Schema::of::<T>().unwrap_or_else(|error| {
+        let type_name = std::any::type_name::<T>();
+        let message = format!("unable to trace schema of {type_name}: {error}");
+        panic!("{}", message)
+    });
+    bytes_strategy(schema)
+        .prop_filter_map("the value does not decode", |bytes| {
+            from_bytes(&bytes).ok()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        to_bytes,
+        Deserializer,
+    };
+    use proptest::test_runner::{
+        TestError,
+        TestRunner,
+    };
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    enum Shape {
+        Point,
+        Circle(u32),
+        Polygon(Vec<(i16, i16)>),
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Scene {
+        name: String,
+        shapes: Vec<Shape>,
+        parent: Option<Box<Scene>>,
+    }
+
+    #[test]
+    fn generated_bytes_decode() {
+        let schema = Schema::of::<Scene>().unwrap();
+        let mut runner = TestRunner::deterministic();
+        let result = runner.run(&bytes_strategy(schema), |bytes| {
+            let mut deserializer = Deserializer::new(&bytes);
+            let value: crate::Result<Scene> =
+                serde::Deserialize::deserialize(&mut deserializer);
+            assert!(value.is_ok());
+            assert_eq!(bytes.len(), deserializer.offset());
+            Ok(())
+        });
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn failures_shrink() {
+        let mut runner = TestRunner::deterministic();
+        let result = runner.run(&value_strategy::<Scene>(), |scene| {
+            let encoding = to_bytes(&scene).unwrap();
+            assert_eq!(scene, from_bytes::<Scene>(&encoding).unwrap());
+            proptest::prop_assert!(scene.shapes.len() < 2);
+            Ok(())
+        });
+        let Err(TestError::Fail(_, scene)) = result else {
+            panic!("expected a failing scene");
+        };
+        assert_eq!(
+            Scene {
+                name: String::new(),
+                shapes: vec![Shape::Point, Shape::Point],
+                parent: None,
+            },
+            scene
+        );
+    }
+}
@@ -0,0 +1,588 @@
+use crate::{
+    from_bytes,
+    to_bytes,
+    Error,
+    Result,
+    Schema,
+    VariantKind,
+};
+
+/// This is the name of the environment variable which, if set to a number,
+/// is used by [`roundtrip_property`] as the seed for generating values,
+/// such as to reproduce a failure.
+///
+/// [`roundtrip_property`]: fn.roundtrip_property.html
+pub const SEED_VAR: &str = "SERIALIZATION_SEED";
+
+/// This is the number of values [`roundtrip_property`] generates.
+///
+/// [`roundtrip_property`]: fn.roundtrip_property.html
+pub const ROUNDTRIP_CASES: usize = 256;
+
+// Beyond this depth, generated sequences, maps, and options are empty, and
+// enum variants which do not recurse are chosen where possible, so that
+// values of recursive types stay small.
+const SHALLOW_DEPTH: usize = 4;
+
+// Beyond this depth, generation gives up, for recursive types which have no
+// way to stop recursing.
+const MAX_DEPTH: usize = 64;
+
+// This is the largest number of elements in generated sequences, maps,
+// strings, and byte strings.
+const MAX_LEN: u64 = 8;
+
+/// This is a small, fast pseudo-random number generator (`SplitMix64`) used
+/// to generate test inputs.  It is seeded explicitly so that failures can be
+/// reproduced.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Return a number less than `bound`, or zero if `bound` is zero.
+    pub fn below(
+        &mut self,
+        bound: u64,
+    ) -> u64 {
+        if bound == 0 {
+            0
+        } else {
+            self.next_u64() % bound
+        }
+    }
+
+    /// Return a new generator with the given seed.
+    #[must_use]
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    /// Return the next pseudo-random number.
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+// This is a source of the choices made in generating an encoding, which is
+// usually an `Rng`, but may be the input of a fuzzer or of a property test,
+// so that the choices can be replayed and shrunk.
+pub(crate) trait Entropy {
+    // Return a number less than `bound`, or zero if `bound` is zero.
+    fn below(
+        &mut self,
+        bound: u64,
+    ) -> u64 {
+        if bound == 0 {
+            0
+        } else {
+            self.next_u64() % bound
+        }
+    }
+
+    fn next_u64(&mut self) -> u64;
+}
+
+impl Entropy for Rng {
+    fn below(
+        &mut self,
+        bound: u64,
+    ) -> u64 {
+        Rng::below(self, bound)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        Rng::next_u64(self)
+    }
+}
+
+struct Generator<'a, 's, E: ?Sized> {
+    rng: &'a mut E,
+    out: Vec<u8>,
+    named: Vec<&'s Schema>,
+}
+
+impl<'s, E> Generator<'_, 's, E>
+where
+    E: ?Sized + Entropy,
+{
+    fn append<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: serde::Serialize,
+    {
+        self.out.extend(to_bytes(value)?);
+        Ok(())
+    }
+
+    fn chance(&mut self) -> bool {
+        self.rng.below(2) == 1
+    }
+
+    fn char(&mut self) -> char {
+        let code = if self.chance() {
+            self.rng.below(0x80)
+        } else {
+            self.rng.below(0x11_0000)
+        };
+        #[allow(clippy::cast_possible_truncation)]
+        std::char::from_u32(code as u32).unwrap_or('\u{FFFD}')
+    }
+
+    fn generate(
+        &mut self,
+        schema: &'s Schema,
+        depth: usize,
+    ) -> Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(Error::UntraceableType(
+                self.named
+                    .last()
+                    .and_then(|schema| schema.name())
+                    .unwrap_or(""),
+            ));
+        }
+        let shallow = depth < SHALLOW_DEPTH;
+        let named = schema.name().is_some();
+        if named {
+            self.named.push(schema);
+        }
+        self.generate_value(schema, depth, shallow)?;
+        if named {
+            self.named.pop();
+        }
+        Ok(())
+    }
+
+    fn generate_fields<I>(
+        &mut self,
+        schemas: I,
+        depth: usize,
+    ) -> Result<()>
+    where
+        I: IntoIterator<Item = &'s Schema>,
+    {
+        for schema in schemas {
+            self.generate(schema, depth + 1)?;
+        }
+        Ok(())
+    }
+
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap,
+        clippy::cast_precision_loss,
+        clippy::too_many_lines
+    )]
+    fn generate_value(
+        &mut self,
+        schema: &'s Schema,
+        depth: usize,
+        shallow: bool,
+    ) -> Result<()> {
+        let bits = self.rng.next_u64();
+        match schema {
+            Schema::Bool => self.append(&(bits & 1 == 1)),
+            Schema::I8 => self.append(&(bits as i8)),
+            Schema::I16 => self.append(&(bits as i16)),
+            Schema::I32 => self.append(&(bits as i32)),
+            Schema::I64 => self.append(&(bits as i64)),
+            Schema::I128 => {
+                let wide = self.wide(bits);
+                self.append(&(wide as i128))
+            },
+            Schema::U8 => self.append(&(bits as u8)),
+            Schema::U16 => self.append(&(bits as u16)),
+            Schema::U32 => self.append(&(bits as u32)),
+            Schema::U64 => self.append(&bits),
+            Schema::U128 => {
+                let wide = self.wide(bits);
+                self.append(&wide)
+            },
+
+            // Floats are kept finite so that values compare equal to
+            // themselves.
+            Schema::F32 => self.append(&((bits as i32) as f32 / 1024.0)),
+            Schema::F64 => self.append(&((bits as i64) as f64 / 1024.0)),
+            Schema::Char => {
+                let c = self.char();
+                self.append(&c)
+            },
+            Schema::Str => {
+                let len = self.len(shallow);
+                let text = (0..len).map(|_| self.char()).collect::<String>();
+                self.append(&text)
+            },
+            Schema::Bytes => {
+                let len = self.len(shallow);
+                let bytes = (0..len)
+                    .map(|_| self.rng.next_u64() as u8)
+                    .collect::<Vec<_>>();
+                self.append(&bytes)
+            },
+            Schema::Option(inner) => {
+                if shallow && self.chance() {
+                    self.append(&1_u8)?;
+                    self.generate(inner, depth + 1)
+                } else {
+                    self.append(&0_u8)
+                }
+            },
+            Schema::Unit | Schema::UnitStruct(_) => Ok(()),
+            Schema::NewtypeStruct {
+                inner,
+                ..
+            } => self.generate(inner, depth + 1),
+            Schema::Seq(element) => {
+                let len = self.len(shallow);
+                self.append(&len)?;
+                for _ in 0..len {
+                    self.generate(element, depth + 1)?;
+                }
+                Ok(())
+            },
+            Schema::Tuple(fields)
+            | Schema::Array(fields)
+            | Schema::TupleStruct {
+                fields,
+                ..
+            } => self.generate_fields(fields, depth),
+            Schema::Map {
+                key,
+                value,
+            } => {
+                let len = self.len(shallow);
+                self.append(&len)?;
+                for _ in 0..len {
+                    self.generate(key, depth + 1)?;
+                    self.generate(value, depth + 1)?;
+                }
+                Ok(())
+            },
+            Schema::Struct {
+                fields,
+                ..
+            } => self.generate_fields(
+                fields.iter().map(|field| &field.schema),
+                depth,
+            ),
+            Schema::Enum {
+                variants,
+                ..
+            } => {
+                let candidates = (0..variants.len())
+                    .filter(|index| {
+                        shallow || !variant_recurses(&variants[*index].kind)
+                    })
+                    .collect::<Vec<_>>();
+                let index = if candidates.is_empty() {
+                    self.rng.below(variants.len() as u64) as usize
+                } else {
+                    candidates[self.rng.below(candidates.len() as u64) as usize]
+                };
+                self.append(&(index as u32))?;
+                match &variants[index].kind {
+                    VariantKind::Unit => Ok(()),
+                    VariantKind::Newtype(inner) => {
+                        self.generate(inner, depth + 1)
+                    },
+                    VariantKind::Tuple(fields) => {
+                        self.generate_fields(fields, depth)
+                    },
+                    VariantKind::Struct(fields) => self.generate_fields(
+                        fields.iter().map(|field| &field.schema),
+                        depth,
+                    ),
+                }
+            },
+            Schema::Recursive(name) => {
+                let target = self
+                    .named
+                    .iter()
+                    .rev()
+                    .copied()
+                    .find(|schema| schema.name() == Some(name))
+                    .ok_or(Error::UntraceableType(name))?;
+                self.generate_value(target, depth, shallow)
+            },
+        }
+    }
+
+    fn len(
+        &mut self,
+        shallow: bool,
+    ) -> u64 {
+        if shallow {
+            self.rng.below(MAX_LEN + 1)
+        } else {
+            0
+        }
+    }
+
+    // Return 128 random bits, the high half of which are the given bits.
+    fn wide(
+        &mut self,
+        bits: u64,
+    ) -> u128 {
+        u128::from(bits) << 64 | u128::from(self.rng.next_u64())
+    }
+}
+
+fn recurses(schema: &Schema) -> bool {
+    match schema {
+        Schema::Recursive(_) => true,
+        Schema::Option(inner)
+        | Schema::NewtypeStruct {
+            inner,
+            ..
+        }
+        | Schema::Seq(inner) => recurses(inner),
+        Schema::Tuple(fields)
+        | Schema::Array(fields)
+        | Schema::TupleStruct {
+            fields,
+            ..
+        } => fields.iter().any(recurses),
+        Schema::Map {
+            key,
+            value,
+        } => recurses(key) || recurses(value),
+        Schema::Struct {
+            fields,
+            ..
+        } => fields.iter().any(|field| recurses(&field.schema)),
+        Schema::Enum {
+            variants,
+            ..
+        } => variants.iter().any(|variant| variant_recurses(&variant.kind)),
+        _ => false,
+    }
+}
+
+fn variant_recurses(kind: &VariantKind) -> bool {
+    match kind {
+        VariantKind::Unit => false,
+        VariantKind::Newtype(inner) => recurses(inner),
+        VariantKind::Tuple(fields) => fields.iter().any(recurses),
+        VariantKind::Struct(fields) => {
+            fields.iter().any(|field| recurses(&field.schema))
+        },
+    }
+}
+
+/// This function is used to generate a random buffer holding a valid
+/// encoding of a value with the given schema.  Every integer, float,
+/// character, enum variant, and length is chosen at random, though lengths
+/// are kept small and floats are kept finite.
+///
+/// # Errors
+///
+/// Returns [`Error::UntraceableType`] if the schema is of a recursive type
+/// with no way to stop recursing.
+///
+/// [`Error::UntraceableType`]: ../enum.Error.html#variant.UntraceableType
+pub fn random_bytes(
+    schema: &Schema,
+    rng: &mut Rng,
+) -> Result<Vec<u8>> {
+    generate_bytes(schema, rng)
+}
+
+// Generate a valid encoding of a value with the given schema, making each
+// choice with the given source of entropy.
+pub(crate) fn generate_bytes<E>(
+    schema: &Schema,
+    entropy: &mut E,
+) -> Result<Vec<u8>>
+where
+    E: ?Sized + Entropy,
+{
+    let mut generator = Generator {
+        rng: entropy,
+        out: Vec::new(),
+        named: Vec::new(),
+    };
+    generator.generate(schema, 0)?;
+    Ok(generator.out)
+}
+
+/// This function is used to generate a random value of type `T`, by
+/// decoding a buffer generated by [`random_bytes`] from the schema of the
+/// type.
+///
+/// [`random_bytes`]: fn.random_bytes.html
+///
+/// # Errors
+///
+/// Returns any error from [`Schema::of`] or [`random_bytes`], or from
+/// decoding the value, which can happen if the `Deserialize` implementation
+/// of the type rejects some values of its fields.
+///
+/// [`Schema::of`]: ../enum.Schema.html#method.of
+pub fn random_value<T>(rng: &mut Rng) -> Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let schema = Schema::of::<T>()?;
+    from_bytes(&random_bytes(&schema, rng)?)
+}
+
+/// This function is used to check that values of type `T` survive being
+/// encoded and decoded.  It generates [`ROUNDTRIP_CASES`] random encodings
+/// of the type with [`random_bytes`], and for each one that decodes, checks
+/// that encoding and decoding the value again gives an equal value.
+///
+/// The seed is taken from the environment variable named by [`SEED_VAR`]
+/// if it is set, so that failures (which report their seed) can be
+/// reproduced.
+///
+/// [`ROUNDTRIP_CASES`]: constant.ROUNDTRIP_CASES.html
+/// [`random_bytes`]: fn.random_bytes.html
+/// [`SEED_VAR`]: constant.SEED_VAR.html
+///
+/// # Panics
+///
+/// This function panics if the schema of the type cannot be traced, if
+/// none of the generated encodings decode, or if any value does not
+/// survive being encoded and decoded.
+pub fn roundtrip_property<T>()
+where
+    T: serde::Serialize
+        + serde::de::DeserializeOwned
+        + PartialEq
+        + std::fmt::Debug,
+{
+    if let Err(message) = check_roundtrips::<T>() {
+        panic!("{}", message);
+    }
+}
+
+fn check_roundtrips<T>() -> std::result::Result<(), String>
+where
+    T: serde::Serialize
+        + serde::de::DeserializeOwned
+        + PartialEq
+        + std::fmt::Debug,
+{
+    let type_name = std::any::type_name::<T>();
+    let schema = Schema::of::<T>().map_err(|error| {
+        format!("unable to trace schema of {type_name}: {error}")
+    })?;
+    let seed = std::env::var(SEED_VAR)
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or(0x5EED);
+    let mut rng = Rng::new(seed);
+    let mut decoded = 0;
+    for case in 0..ROUNDTRIP_CASES {
+        let bytes = random_bytes(&schema, &mut rng).map_err(|error| {
+            format!("unable to generate {type_name} (seed {seed}): {error}")
+        })?;
+        let value: T = match from_bytes(&bytes) {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+        decoded += 1;
+        let reencoded = to_bytes(&value).map_err(|error| {
+            format!("unable to encode {value:?} (seed {seed}, case {case}): {error}")
+        })?;
+        let redecoded = from_bytes::<T>(&reencoded);
+        if !matches!(&redecoded, Ok(redecoded) if *redecoded == value) {
+            return Err(format!(
+                "{value:?} decoded as {redecoded:?} (seed {seed}, case {case})"
+            ));
+        }
+    }
+    if decoded == 0 {
+        return Err(format!(
+            "no generated encoding of {type_name} decoded (seed {seed})"
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "float")]
+    use std::collections::HashMap;
+
+    #[cfg(feature = "float")]
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    enum Expr {
+        Literal(i32),
+        Negate(Box<Expr>),
+        Add(Box<Expr>, Box<Expr>),
+        Call {
+            name: String,
+            args: Vec<Expr>,
+        },
+    }
+
+    #[cfg(feature = "float")]
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Record {
+        id: u64,
+        label: Option<String>,
+        scores: HashMap<char, f32>,
+        data: (bool, i8, f64, ()),
+        body: Expr,
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn generated_bytes_decode() {
+        let schema = Schema::of::<Record>().unwrap();
+        let mut rng = Rng::new(42);
+        for _ in 0..100 {
+            let bytes = random_bytes(&schema, &mut rng);
+            assert!(bytes.is_ok());
+            let bytes = bytes.unwrap();
+            let mut deserializer = crate::Deserializer::new(&bytes);
+            let value: Result<Record> =
+                serde::Deserialize::deserialize(&mut deserializer);
+            assert!(value.is_ok());
+            assert_eq!(bytes.len(), deserializer.offset());
+        }
+    }
+
+    #[test]
+    fn random_values_vary() {
+        let mut rng = Rng::new(1);
+        let values = (0..10)
+            .map(|_| random_value::<(u32, String)>(&mut rng).unwrap())
+            .collect::<Vec<_>>();
+        assert!(values.iter().any(|value| *value != values[0]));
+    }
+
+    #[test]
+    fn wide_integers() {
+        let mut rng = Rng::new(3);
+        let values = (0..10)
+            .map(|_| random_value::<(u128, i128)>(&mut rng).unwrap())
+            .collect::<Vec<_>>();
+        assert!(values.iter().any(|(value, _)| *value > u128::from(u64::MAX)));
+        assert!(values.iter().any(|(_, value)| *value < 0));
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn roundtrip() {
+        roundtrip_property::<Record>();
+        roundtrip_property::<Vec<Option<Expr>>>();
+    }
+
+    #[test]
+    fn rng_is_reproducible() {
+        let mut first = Rng::new(7);
+        let mut second = Rng::new(7);
+        assert_eq!(first.next_u64(), second.next_u64());
+        assert!(first.below(10) < 10);
+        assert_eq!(0, first.below(0));
+    }
+}
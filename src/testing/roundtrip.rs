@@ -0,0 +1,286 @@
+use crate::{
+    from_bytes,
+    to_bytes,
+    to_value,
+    Value,
+};
+
+/// This function is used to check that a value survives being encoded and
+/// decoded.  If the decoded value differs from the original, the panic
+/// message lists where they differ, found by comparing the two as
+/// [`Value`]s.
+///
+/// [`Value`]: ../enum.Value.html
+///
+/// # Panics
+///
+/// This function panics if the value cannot be encoded or decoded, or if
+/// the decoded value is not equal to the original.
+pub fn assert_roundtrip<T>(value: &T)
+where
+    T: serde::Serialize
+        + serde::de::DeserializeOwned
+        + PartialEq
+        + std::fmt::Debug,
+{
+    let type_name = std::any::type_name::<T>();
+    let bytes = to_bytes(value).unwrap_or_else(|error| {
+        panic!("unable to encode {type_name} {value:?}: {}", error)
+    });
+    let decoded: T = from_bytes(&bytes).unwrap_or_else(|error| {
+        panic!(
+            "unable to decode {type_name} {value:?} from {bytes:02X?}: {}",
+            error
+        )
+    });
+    if decoded == *value {
+        return;
+    }
+    let mut differences = Vec::new();
+    match (to_value(value), to_value(&decoded)) {
+        (Ok(expected), Ok(actual)) => {
+            diff("", &expected, &actual, &mut differences);
+        },
+        _ => differences.push(format!("{value:?} != {decoded:?}")),
+    }
+    if differences.is_empty() {
+        differences.push(format!(
+            "{value:?} != {decoded:?} (same serialized form, but not equal)"
+        ));
+    }
+    panic!(
+        "{type_name} changed when encoded and decoded:\n  {}",
+        differences.join("\n  ")
+    );
+}
+
+fn diff(
+    path: &str,
+    expected: &Value,
+    actual: &Value,
+    differences: &mut Vec<String>,
+) {
+    if expected == actual {
+        return;
+    }
+    let here = if path.is_empty() {
+        "value"
+    } else {
+        path
+    };
+    match (expected, actual) {
+        (Value::Seq(expected), Value::Seq(actual))
+        | (Value::Tuple(expected), Value::Tuple(actual)) => {
+            if expected.len() != actual.len() {
+                differences.push(format!(
+                    "{here}: expected {} elements, found {}",
+                    expected.len(),
+                    actual.len()
+                ));
+            }
+            for (i, (expected, actual)) in
+                expected.iter().zip(actual).enumerate()
+            {
+                diff(&format!("{path}[{i}]"), expected, actual, differences);
+            }
+        },
+        (Value::Struct(expected), Value::Struct(actual)) => {
+            for (name, expected_field) in expected {
+                match actual.iter().find(|(actual_name, _)| actual_name == name)
+                {
+                    Some((_, actual_field)) => diff(
+                        &format!("{path}.{name}"),
+                        expected_field,
+                        actual_field,
+                        differences,
+                    ),
+                    None => differences.push(format!("{path}.{name}: missing")),
+                }
+            }
+        },
+        (Value::Map(expected), Value::Map(actual)) => {
+            for (key, expected_value) in expected {
+                match actual.iter().find(|(actual_key, _)| actual_key == key) {
+                    Some((_, actual_value)) => diff(
+                        &format!("{path}[{key}]"),
+                        expected_value,
+                        actual_value,
+                        differences,
+                    ),
+                    None => differences.push(format!("{path}[{key}]: missing")),
+                }
+            }
+            for (key, actual_value) in actual {
+                if !expected.iter().any(|(expected_key, _)| expected_key == key)
+                {
+                    differences.push(format!(
+                        "{path}[{key}]: unexpected entry {actual_value}"
+                    ));
+                }
+            }
+        },
+        (Value::Option(Some(expected)), Value::Option(Some(actual))) => {
+            diff(path, expected, actual, differences);
+        },
+        (
+            Value::Variant {
+                index: expected_index,
+                name,
+                value: expected,
+            },
+            Value::Variant {
+                index: actual_index,
+                value: actual,
+                ..
+            },
+        ) if expected_index == actual_index => {
+            diff(&format!("{path}::{name}"), expected, actual, differences);
+        },
+        _ => differences
+            .push(format!("{here}: expected {expected}, found {actual}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Settings {
+        name: String,
+        limits: HashMap<String, u32>,
+        mode: Mode,
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    enum Mode {
+        Fast,
+        Careful {
+            retries: u8,
+        },
+    }
+
+    // This type loses its `cached` field when encoded, so it does not
+    // survive being encoded and decoded unless the field is empty, and the
+    // difference cannot be seen through its serialized form.
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Lossy {
+        id: u8,
+        #[serde(skip)]
+        cached: Vec<u8>,
+        mode: Mode,
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn values_which_roundtrip() {
+        let mut limits = HashMap::new();
+        limits.insert(String::from("files"), 10);
+        limits.insert(String::from("depth"), 3);
+        assert_roundtrip(&Settings {
+            name: String::from("default"),
+            limits,
+            mode: Mode::Careful {
+                retries: 2,
+            },
+        });
+        assert_roundtrip(&(1_u8, 'x', Some(-2.5_f64)));
+    }
+
+    #[test]
+    fn differences_are_located() {
+        let mut differences = Vec::new();
+        let expected = to_value(&Settings {
+            name: String::from("a"),
+            limits: [(String::from("x"), 1), (String::from("y"), 2)]
+                .iter()
+                .cloned()
+                .collect(),
+            mode: Mode::Careful {
+                retries: 1,
+            },
+        })
+        .unwrap();
+        let actual = to_value(&Settings {
+            name: String::from("b"),
+            limits: [(String::from("x"), 5), (String::from("z"), 2)]
+                .iter()
+                .cloned()
+                .collect(),
+            mode: Mode::Careful {
+                retries: 2,
+            },
+        })
+        .unwrap();
+        diff("", &expected, &actual, &mut differences);
+        differences.sort();
+        assert_eq!(
+            vec![
+                ".limits[\"x\"]: expected 1, found 5",
+                ".limits[\"y\"]: missing",
+                ".limits[\"z\"]: unexpected entry 2",
+                ".mode::Careful.retries: expected 1, found 2",
+                ".name: expected \"a\", found \"b\"",
+            ],
+            differences
+        );
+    }
+
+    #[test]
+    fn wide_integers() {
+        assert_roundtrip(&(u128::MAX, i128::MIN));
+        let mut differences = Vec::new();
+        diff(
+            "",
+            &to_value(&(1_u128, -1_i128)).unwrap(),
+            &to_value(&(1_u128, 1_i128)).unwrap(),
+            &mut differences,
+        );
+        assert_eq!(vec!["[1]: expected -1, found 1"], differences);
+    }
+
+    // This type doubles its count when decoded.
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Doubling {
+        #[serde(deserialize_with = "deserialize_doubled")]
+        count: u8,
+    }
+
+    fn deserialize_doubled<'de, D>(
+        deserializer: D
+    ) -> std::result::Result<u8, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        <u8 as serde::Deserialize>::deserialize(deserializer)
+            .map(|count| count * 2)
+    }
+
+    fn panic_message<F>(f: F) -> String
+    where
+        F: FnOnce() + std::panic::UnwindSafe,
+    {
+        let failure = std::panic::catch_unwind(f);
+        assert!(failure.is_err());
+        failure.unwrap_err().downcast_ref::<String>().unwrap().clone()
+    }
+
+    #[test]
+    fn failure_reports_differences() {
+        let message = panic_message(|| {
+            assert_roundtrip(&vec![Doubling {
+                count: 2,
+            }]);
+        });
+        assert!(message.contains("[0].count: expected 2, found 4"));
+        let message = panic_message(|| {
+            assert_roundtrip(&Lossy {
+                id: 1,
+                cached: vec![1, 2],
+                mode: Mode::Fast,
+            });
+        });
+        assert!(message.contains("same serialized form"));
+    }
+}
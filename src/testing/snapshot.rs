@@ -0,0 +1,123 @@
+use crate::to_bytes;
+use std::path::Path;
+
+/// This is the name of the environment variable which, when set to anything
+/// other than `0` or an empty string, makes [`assert_snapshot`] write new
+/// golden files rather than comparing against them.
+///
+/// [`assert_snapshot`]: fn.assert_snapshot.html
+pub const UPDATE_SNAPSHOTS_VAR: &str = "SERIALIZATION_UPDATE_SNAPSHOTS";
+
+fn updating_snapshots() -> bool {
+    std::env::var_os(UPDATE_SNAPSHOTS_VAR)
+        .is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// This function is used to check that the encoding of a value has not
+/// changed, by comparing it against a "golden" file holding the expected
+/// encoding.  This catches accidental changes to the wire format of types,
+/// such as reordering fields or changing their types.
+///
+/// When the environment variable named by [`UPDATE_SNAPSHOTS_VAR`] is set,
+/// the golden file is written (along with any missing parent directories)
+/// instead, so that intended changes can be accepted by running the tests
+/// once with the variable set.
+///
+/// [`UPDATE_SNAPSHOTS_VAR`]: constant.UPDATE_SNAPSHOTS_VAR.html
+///
+/// # Panics
+///
+/// This function panics if the value cannot be encoded, the golden file
+/// cannot be read (or written, when updating), or the encoding of the value
+/// differs from the contents of the golden file.
+#[allow(clippy::needless_pass_by_value)]
+pub fn assert_snapshot<T, P>(
+    value: T,
+    path: P,
+) where
+    T: serde::Serialize,
+    P: AsRef<Path>,
+{
+    let path = path.as_ref();
+    let actual = to_bytes(&value).unwrap_or_else(|error| {
+        panic!(
+            "unable to encode value for snapshot {}: {error}",
+            path.display()
+        )
+    });
+    if updating_snapshots() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap_or_else(|error| {
+                panic!(
+                    "unable to create directory {}: {error}",
+                    parent.display()
+                )
+            });
+        }
+        std::fs::write(path, &actual).unwrap_or_else(|error| {
+            panic!("unable to write snapshot {}: {error}", path.display())
+        });
+        return;
+    }
+    let expected = std::fs::read(path).unwrap_or_else(|error| {
+        panic!(
+            "unable to read snapshot {} ({error}); set \
+             {UPDATE_SNAPSHOTS_VAR}=1 to create it",
+            path.display()
+        )
+    });
+    if let Some(offset) = first_difference(&expected, &actual) {
+        panic!(
+            "encoding differs from snapshot {} at offset {offset} \
+             (expected {} bytes, found {}); set {UPDATE_SNAPSHOTS_VAR}=1 to \
+             accept the new encoding\n  expected: {expected:02X?}\n     \
+             found: {actual:02X?}",
+            path.display(),
+            expected.len(),
+            actual.len(),
+        );
+    }
+}
+
+fn first_difference(
+    expected: &[u8],
+    actual: &[u8],
+) -> Option<usize> {
+    expected
+        .iter()
+        .zip(actual)
+        .position(|(expected, actual)| expected != actual)
+        .or_else(|| {
+            if expected.len() == actual.len() {
+                None
+            } else {
+                Some(expected.len().min(actual.len()))
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn differences() {
+        assert_eq!(None, first_difference(&[1, 2, 3], &[1, 2, 3]));
+        assert_eq!(Some(1), first_difference(&[1, 2, 3], &[1, 4, 3]));
+        assert_eq!(Some(2), first_difference(&[1, 2], &[1, 2, 3]));
+        assert_eq!(Some(0), first_difference(&[1], &[]));
+    }
+
+    #[test]
+    fn matching_and_mismatched_snapshots() {
+        let path = std::env::temp_dir()
+            .join(format!("serialization-snapshot-{}", std::process::id()));
+        std::fs::write(&path, [0x02, 0x68, 0x69, 0x07]).unwrap();
+        assert_snapshot(("hi", 7_u8), &path);
+        let mismatch = std::panic::catch_unwind(|| {
+            assert_snapshot(("hi", 8_u8), &path);
+        });
+        std::fs::remove_file(&path).unwrap();
+        assert!(mismatch.is_err());
+    }
+}
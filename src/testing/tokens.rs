@@ -0,0 +1,899 @@
+use crate::{
+    Error,
+    Result,
+};
+use serde::ser::{
+    Serialize,
+    SerializeMap,
+    SerializeSeq,
+    SerializeStruct,
+    SerializeStructVariant,
+    SerializeTuple,
+    SerializeTupleStruct,
+    SerializeTupleVariant,
+};
+
+/// This is one call made by a type to a serializer, as recorded by
+/// [`tokens_of`].  Compound values are recorded as a token starting them,
+/// the tokens of their parts, and a token ending them.
+///
+/// [`tokens_of`]: fn.tokens_of.html
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    /// A `bool`.
+    Bool(bool),
+
+    /// An `i8`.
+    I8(i8),
+
+    /// An `i16`.
+    I16(i16),
+
+    /// An `i32`.
+    I32(i32),
+
+    /// An `i64`.
+    I64(i64),
+
+    /// An `i128`.
+    I128(i128),
+
+    /// A `u8`.
+    U8(u8),
+
+    /// A `u16`.
+    U16(u16),
+
+    /// A `u32`.
+    U32(u32),
+
+    /// A `u64`.
+    U64(u64),
+
+    /// A `u128`.
+    U128(u128),
+
+    /// An `f32`.
+    F32(f32),
+
+    /// An `f64`.
+    F64(f64),
+
+    /// A `char`.
+    Char(char),
+
+    /// A string.
+    Str(String),
+
+    /// A byte string.
+    Bytes(Vec<u8>),
+
+    /// An absent optional value.
+    None,
+
+    /// A present optional value; the tokens of its value follow.
+    Some,
+
+    /// The unit value `()`.
+    Unit,
+
+    /// A struct with no fields.
+    UnitStruct {
+        /// The name of the type.
+        name: &'static str,
+    },
+
+    /// An enum variant with no fields.
+    UnitVariant {
+        /// The name of the type.
+        name: &'static str,
+
+        /// The name of the variant.
+        variant: &'static str,
+    },
+
+    /// A struct wrapping one value; the tokens of its value follow.
+    NewtypeStruct {
+        /// The name of the type.
+        name: &'static str,
+    },
+
+    /// An enum variant wrapping one value; the tokens of its value follow.
+    NewtypeVariant {
+        /// The name of the type.
+        name: &'static str,
+
+        /// The name of the variant.
+        variant: &'static str,
+    },
+
+    /// The start of a sequence.
+    Seq {
+        /// The number of elements, if known in advance.
+        len: Option<usize>,
+    },
+
+    /// The end of a sequence.
+    SeqEnd,
+
+    /// The start of a tuple.
+    Tuple {
+        /// The number of elements.
+        len: usize,
+    },
+
+    /// The end of a tuple.
+    TupleEnd,
+
+    /// The start of a tuple struct.
+    TupleStruct {
+        /// The name of the type.
+        name: &'static str,
+
+        /// The number of fields.
+        len: usize,
+    },
+
+    /// The end of a tuple struct.
+    TupleStructEnd,
+
+    /// The start of an enum variant with unnamed fields.
+    TupleVariant {
+        /// The name of the type.
+        name: &'static str,
+
+        /// The name of the variant.
+        variant: &'static str,
+
+        /// The number of fields.
+        len: usize,
+    },
+
+    /// The end of an enum variant with unnamed fields.
+    TupleVariantEnd,
+
+    /// The start of a map, whose keys and values alternate.
+    Map {
+        /// The number of entries, if known in advance.
+        len: Option<usize>,
+    },
+
+    /// The end of a map.
+    MapEnd,
+
+    /// The start of a struct.
+    Struct {
+        /// The name of the type.
+        name: &'static str,
+
+        /// The number of fields to be serialized.
+        len: usize,
+    },
+
+    /// The end of a struct.
+    StructEnd,
+
+    /// The start of an enum variant with named fields.
+    StructVariant {
+        /// The name of the type.
+        name: &'static str,
+
+        /// The name of the variant.
+        variant: &'static str,
+
+        /// The number of fields to be serialized.
+        len: usize,
+    },
+
+    /// The end of an enum variant with named fields.
+    StructVariantEnd,
+
+    /// This marks a struct field by name; the tokens of its value follow.
+    Field(&'static str),
+
+    /// This marks a struct field which the type chose not to serialize.
+    SkippedField(&'static str),
+}
+
+/// This function is used to record the sequence of calls the given value
+/// makes to a serializer, so that tests can check how a type serializes in
+/// terms of the serde data model rather than the bytes of any one format.
+///
+/// # Errors
+///
+/// This function returns any error reported by the value's own
+/// `Serialize` implementation.
+pub fn tokens_of<T>(value: &T) -> Result<Vec<Token>>
+where
+    T: Serialize + ?Sized,
+{
+    let mut recorder = Recorder::default();
+    value.serialize(&mut recorder)?;
+    Ok(recorder.tokens)
+}
+
+/// This function is used to check that the given value makes exactly the
+/// given sequence of calls to a serializer.
+///
+/// # Panics
+///
+/// This function panics if the value fails to serialize, or if the calls
+/// it makes differ from the expected tokens.
+pub fn assert_ser_tokens<T>(
+    value: &T,
+    expected: &[Token],
+) where
+    T: Serialize + ?Sized,
+{
+    let type_name = std::any::type_name::<T>();
+    let tokens = tokens_of(value).unwrap_or_else(|error| {
+        panic!("unable to serialize {type_name}: {}", error)
+    });
+    if tokens == expected {
+        return;
+    }
+    let position = tokens
+        .iter()
+        .zip(expected)
+        .position(|(actual, expected)| actual != expected)
+        .unwrap_or_else(|| tokens.len().min(expected.len()));
+    panic!(
+        "{type_name} serialized differently at token {position}:\n  \
+         expected: {:?}\n  found: {:?}",
+        expected.get(position),
+        tokens.get(position)
+    );
+}
+
+#[derive(Default)]
+struct Recorder {
+    tokens: Vec<Token>,
+}
+
+impl<'a> serde::Serializer for &'a mut Recorder {
+    type Error = Error;
+    type Ok = ();
+    type SerializeMap = Compound<'a>;
+    type SerializeSeq = Compound<'a>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+
+    fn serialize_bool(
+        self,
+        v: bool,
+    ) -> Result<()> {
+        self.tokens.push(Token::Bool(v));
+        Ok(())
+    }
+
+    fn serialize_i8(
+        self,
+        v: i8,
+    ) -> Result<()> {
+        self.tokens.push(Token::I8(v));
+        Ok(())
+    }
+
+    fn serialize_i16(
+        self,
+        v: i16,
+    ) -> Result<()> {
+        self.tokens.push(Token::I16(v));
+        Ok(())
+    }
+
+    fn serialize_i32(
+        self,
+        v: i32,
+    ) -> Result<()> {
+        self.tokens.push(Token::I32(v));
+        Ok(())
+    }
+
+    fn serialize_i64(
+        self,
+        v: i64,
+    ) -> Result<()> {
+        self.tokens.push(Token::I64(v));
+        Ok(())
+    }
+
+    fn serialize_i128(
+        self,
+        v: i128,
+    ) -> Result<()> {
+        self.tokens.push(Token::I128(v));
+        Ok(())
+    }
+
+    fn serialize_u8(
+        self,
+        v: u8,
+    ) -> Result<()> {
+        self.tokens.push(Token::U8(v));
+        Ok(())
+    }
+
+    fn serialize_u16(
+        self,
+        v: u16,
+    ) -> Result<()> {
+        self.tokens.push(Token::U16(v));
+        Ok(())
+    }
+
+    fn serialize_u32(
+        self,
+        v: u32,
+    ) -> Result<()> {
+        self.tokens.push(Token::U32(v));
+        Ok(())
+    }
+
+    fn serialize_u64(
+        self,
+        v: u64,
+    ) -> Result<()> {
+        self.tokens.push(Token::U64(v));
+        Ok(())
+    }
+
+    fn serialize_u128(
+        self,
+        v: u128,
+    ) -> Result<()> {
+        self.tokens.push(Token::U128(v));
+        Ok(())
+    }
+
+    fn serialize_f32(
+        self,
+        v: f32,
+    ) -> Result<()> {
+        self.tokens.push(Token::F32(v));
+        Ok(())
+    }
+
+    fn serialize_f64(
+        self,
+        v: f64,
+    ) -> Result<()> {
+        self.tokens.push(Token::F64(v));
+        Ok(())
+    }
+
+    fn serialize_char(
+        self,
+        v: char,
+    ) -> Result<()> {
+        self.tokens.push(Token::Char(v));
+        Ok(())
+    }
+
+    fn serialize_str(
+        self,
+        v: &str,
+    ) -> Result<()> {
+        self.tokens.push(Token::Str(String::from(v)));
+        Ok(())
+    }
+
+    fn serialize_bytes(
+        self,
+        v: &[u8],
+    ) -> Result<()> {
+        self.tokens.push(Token::Bytes(v.to_vec()));
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.tokens.push(Token::None);
+        Ok(())
+    }
+
+    fn serialize_some<T>(
+        self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.tokens.push(Token::Some);
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.tokens.push(Token::Unit);
+        Ok(())
+    }
+
+    fn serialize_unit_struct(
+        self,
+        name: &'static str,
+    ) -> Result<()> {
+        self.tokens.push(Token::UnitStruct {
+            name,
+        });
+        Ok(())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.tokens.push(Token::UnitVariant {
+            name,
+            variant,
+        });
+        Ok(())
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.tokens.push(Token::NewtypeStruct {
+            name,
+        });
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.tokens.push(Token::NewtypeVariant {
+            name,
+            variant,
+        });
+        value.serialize(self)
+    }
+
+    fn serialize_seq(
+        self,
+        len: Option<usize>,
+    ) -> Result<Compound<'a>> {
+        self.tokens.push(Token::Seq {
+            len,
+        });
+        Ok(Compound {
+            recorder: self,
+            end: Token::SeqEnd,
+        })
+    }
+
+    fn serialize_tuple(
+        self,
+        len: usize,
+    ) -> Result<Compound<'a>> {
+        self.tokens.push(Token::Tuple {
+            len,
+        });
+        Ok(Compound {
+            recorder: self,
+            end: Token::TupleEnd,
+        })
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>> {
+        self.tokens.push(Token::TupleStruct {
+            name,
+            len,
+        });
+        Ok(Compound {
+            recorder: self,
+            end: Token::TupleStructEnd,
+        })
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>> {
+        self.tokens.push(Token::TupleVariant {
+            name,
+            variant,
+            len,
+        });
+        Ok(Compound {
+            recorder: self,
+            end: Token::TupleVariantEnd,
+        })
+    }
+
+    fn serialize_map(
+        self,
+        len: Option<usize>,
+    ) -> Result<Compound<'a>> {
+        self.tokens.push(Token::Map {
+            len,
+        });
+        Ok(Compound {
+            recorder: self,
+            end: Token::MapEnd,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>> {
+        self.tokens.push(Token::Struct {
+            name,
+            len,
+        });
+        Ok(Compound {
+            recorder: self,
+            end: Token::StructEnd,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a>> {
+        self.tokens.push(Token::StructVariant {
+            name,
+            variant,
+            len,
+        });
+        Ok(Compound {
+            recorder: self,
+            end: Token::StructVariantEnd,
+        })
+    }
+}
+
+// This records the parts of a compound value, followed by the token which
+// ends it.
+struct Compound<'a> {
+    recorder: &'a mut Recorder,
+    end: Token,
+}
+
+impl Compound<'_> {
+    fn element<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        value.serialize(&mut *self.recorder)
+    }
+
+    fn end(self) {
+        self.recorder.tokens.push(self.end);
+    }
+
+    fn field<T>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.recorder.tokens.push(Token::Field(key));
+        self.element(value)
+    }
+
+    fn skip_field(
+        &mut self,
+        key: &'static str,
+    ) {
+        self.recorder.tokens.push(Token::SkippedField(key));
+    }
+}
+
+impl SerializeSeq for Compound<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_element<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self);
+        Ok(())
+    }
+}
+
+impl SerializeTuple for Compound<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_element<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self);
+        Ok(())
+    }
+}
+
+impl SerializeTupleStruct for Compound<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_field<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self);
+        Ok(())
+    }
+}
+
+impl SerializeTupleVariant for Compound<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_field<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self);
+        Ok(())
+    }
+}
+
+impl SerializeMap for Compound<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_key<T>(
+        &mut self,
+        key: &T,
+    ) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(key)
+    }
+
+    fn serialize_value<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self);
+        Ok(())
+    }
+}
+
+impl SerializeStruct for Compound<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_field<T>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.field(key, value)
+    }
+
+    fn skip_field(
+        &mut self,
+        key: &'static str,
+    ) -> Result<()> {
+        Compound::skip_field(self, key);
+        Ok(())
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self);
+        Ok(())
+    }
+}
+
+impl SerializeStructVariant for Compound<'_> {
+    type Error = Error;
+    type Ok = ();
+
+    fn serialize_field<T>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+    {
+        self.field(key, value)
+    }
+
+    fn skip_field(
+        &mut self,
+        key: &'static str,
+    ) -> Result<()> {
+        Compound::skip_field(self, key);
+        Ok(())
+    }
+
+    fn end(self) -> Result<()> {
+        Compound::end(self);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(serde::Serialize)]
+    struct Account {
+        id: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        nickname: Option<String>,
+        roles: Vec<Role>,
+    }
+
+    #[derive(serde::Serialize)]
+    enum Role {
+        Guest,
+        Member(u16),
+        Admin {
+            level: i8,
+        },
+    }
+
+    #[test]
+    fn struct_tokens() {
+        assert_ser_tokens(
+            &Account {
+                id: 7,
+                nickname: None,
+                roles: vec![Role::Guest, Role::Member(3), Role::Admin {
+                    level: -1,
+                }],
+            },
+            &[
+                Token::Struct {
+                    name: "Account",
+                    len: 2,
+                },
+                Token::Field("id"),
+                Token::U32(7),
+                Token::SkippedField("nickname"),
+                Token::Field("roles"),
+                Token::Seq {
+                    len: Some(3),
+                },
+                Token::UnitVariant {
+                    name: "Role",
+                    variant: "Guest",
+                },
+                Token::NewtypeVariant {
+                    name: "Role",
+                    variant: "Member",
+                },
+                Token::U16(3),
+                Token::StructVariant {
+                    name: "Role",
+                    variant: "Admin",
+                    len: 1,
+                },
+                Token::Field("level"),
+                Token::I8(-1),
+                Token::StructVariantEnd,
+                Token::SeqEnd,
+                Token::StructEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn primitive_and_map_tokens() {
+        let mut map = BTreeMap::new();
+        map.insert('a', Some(1.5_f32));
+        map.insert('b', None);
+        let tokens = tokens_of(&(map, "x", ()));
+        assert!(tokens.is_ok());
+        let tokens = tokens.unwrap();
+        assert_eq!(
+            vec![
+                Token::Tuple {
+                    len: 3,
+                },
+                Token::Map {
+                    len: Some(2),
+                },
+                Token::Char('a'),
+                Token::Some,
+                Token::F32(1.5),
+                Token::Char('b'),
+                Token::None,
+                Token::MapEnd,
+                Token::Str(String::from("x")),
+                Token::Unit,
+                Token::TupleEnd,
+            ],
+            tokens
+        );
+    }
+
+    #[test]
+    fn mismatch_is_reported() {
+        let failure = std::panic::catch_unwind(|| {
+            assert_ser_tokens(&(1_u8, 2_u8), &[
+                Token::Tuple {
+                    len: 2,
+                },
+                Token::U8(1),
+                Token::U16(2),
+                Token::TupleEnd,
+            ]);
+        });
+        assert!(failure.is_err());
+        let message =
+            failure.unwrap_err().downcast_ref::<String>().unwrap().clone();
+        assert!(message.contains("at token 2"));
+        assert!(message.contains("expected: Some(U16(2))"));
+        assert!(message.contains("found: Some(U8(2))"));
+    }
+}
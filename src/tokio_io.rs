@@ -0,0 +1,177 @@
+use super::{
+    from_reader_async,
+    to_writer_async,
+    Result,
+};
+use std::{
+    pin::Pin,
+    task::{
+        Context,
+        Poll,
+    },
+};
+
+// This lends a reader or writer from tokio to the functions built on the
+// `futures::io` traits, by passing each call through to the matching one
+// of tokio.
+struct Tokio<'a, T: ?Sized>(&'a mut T);
+
+impl<T> futures::io::AsyncRead for Tokio<'_, T>
+where
+    T: ?Sized + tokio::io::AsyncRead + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        context: &mut Context<'_>,
+        buffer: &mut [u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let mut buffer = tokio::io::ReadBuf::new(buffer);
+        Pin::new(&mut *self.get_mut().0)
+            .poll_read(context, &mut buffer)
+            .map_ok(|()| buffer.filled().len())
+    }
+}
+
+impl<T> futures::io::AsyncWrite for Tokio<'_, T>
+where
+    T: ?Sized + tokio::io::AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        context: &mut Context<'_>,
+        buffer: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut *self.get_mut().0).poll_write(context, buffer)
+    }
+
+    fn poll_flush(
+        self: Pin<&mut Self>,
+        context: &mut Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut *self.get_mut().0).poll_flush(context)
+    }
+
+    fn poll_close(
+        self: Pin<&mut Self>,
+        context: &mut Context<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut *self.get_mut().0).poll_shutdown(context)
+    }
+}
+
+/// This function is used to decode a value from an asynchronous reader of
+/// tokio, such as a [`tokio::net::TcpStream`], which is best wrapped in a
+/// [`tokio::io::BufReader`].  It is the same as [`from_reader_async`],
+/// which takes readers implementing the `futures::io` traits instead.
+///
+/// [`tokio::net::TcpStream`]: https://docs.rs/tokio/1/tokio/net/struct.TcpStream.html
+/// [`tokio::io::BufReader`]: https://docs.rs/tokio/1/tokio/io/struct.BufReader.html
+/// [`from_reader_async`]: fn.from_reader_async.html
+///
+/// # Errors
+///
+/// The same errors are returned as by [`from_reader_async`].
+///
+/// [`from_reader_async`]: fn.from_reader_async.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_tokio_reader,
+///     to_tokio_writer,
+/// };
+///
+/// # tokio::runtime::Builder::new_current_thread()
+/// #     .build()
+/// #     .unwrap()
+/// #     .block_on(async {
+/// let mut stream = Vec::new();
+/// to_tokio_writer(&mut stream, &(String::from("first"), 1_u8)).await.unwrap();
+/// to_tokio_writer(&mut stream, &(String::from("second"), 2_u8)).await.unwrap();
+/// let mut reader = tokio::io::BufReader::new(&stream[..]);
+/// let first: (String, u8) = from_tokio_reader(&mut reader).await.unwrap();
+/// let second: (String, u8) = from_tokio_reader(&mut reader).await.unwrap();
+/// assert_eq!((String::from("first"), 1), first);
+/// assert_eq!((String::from("second"), 2), second);
+/// # });
+/// ```
+pub async fn from_tokio_reader<R, T>(reader: &mut R) -> Result<T>
+where
+    R: ?Sized + tokio::io::AsyncRead + Unpin,
+    T: serde::de::DeserializeOwned,
+{
+    from_reader_async(&mut Tokio(reader)).await
+}
+
+/// This function is used to encode a value and write it to an asynchronous
+/// writer of tokio, such as a [`tokio::net::TcpStream`].  It is the same as
+/// [`to_writer_async`], which takes writers implementing the `futures::io`
+/// traits instead.
+///
+/// [`tokio::net::TcpStream`]: https://docs.rs/tokio/1/tokio/net/struct.TcpStream.html
+/// [`to_writer_async`]: fn.to_writer_async.html
+///
+/// # Errors
+///
+/// The same errors are returned as by [`to_writer_async`].
+///
+/// [`to_writer_async`]: fn.to_writer_async.html
+pub async fn to_tokio_writer<W, T>(
+    writer: &mut W,
+    value: &T,
+) -> Result<()>
+where
+    W: ?Sized + tokio::io::AsyncWrite + Unpin,
+    T: ?Sized + serde::Serialize,
+{
+    to_writer_async(&mut Tokio(writer), value).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[tokio::test]
+    async fn values_are_read_one_at_a_time() {
+        let (mut writer, reader) = tokio::io::duplex(16);
+        let sender = tokio::spawn(async move {
+            for i in 0..50_u16 {
+                to_tokio_writer(&mut writer, &(i, vec![300_u16; 10])).await?;
+            }
+            Ok::<_, Error>(())
+        });
+        // The buffer holds only a few bytes at a time, so each frame is
+        // split across many fills of the buffer.
+        let mut reader = tokio::io::BufReader::with_capacity(3, reader);
+        for i in 0..50_u16 {
+            let value =
+                from_tokio_reader::<_, (u16, Vec<u16>)>(&mut reader).await;
+            assert!(value.is_ok());
+            assert_eq!((i, vec![300; 10]), value.unwrap());
+        }
+        assert!(matches!(sender.await, Ok(Ok(()))));
+        assert!(matches!(
+            from_tokio_reader::<_, u8>(&mut reader).await,
+            Err(Error::ValueTruncated)
+        ));
+    }
+
+    #[tokio::test]
+    async fn errors() {
+        let mut encoding = Vec::new();
+        assert!(to_tokio_writer(&mut encoding, "truncated").await.is_ok());
+        let mut reader = &encoding[..5];
+        assert!(matches!(
+            from_tokio_reader::<_, String>(&mut reader).await,
+            Err(Error::ValueTruncated)
+        ));
+        let mut reader = &[0x03, 0x84, 0x80, 0x00][..];
+        assert!(matches!(
+            from_tokio_reader::<_, u16>(&mut reader).await,
+            Err(Error::IntegerOverflow)
+        ));
+    }
+}
@@ -0,0 +1,729 @@
+use super::{
+    schema::{
+        Field,
+        Schema,
+        Variant,
+        VariantKind,
+    },
+    Error,
+    Result,
+};
+use alloc::{
+    boxed::Box,
+    collections::BTreeMap,
+    format,
+    vec,
+    vec::Vec,
+};
+use core::convert::TryFrom;
+use serde::de::{
+    Expected,
+    IntoDeserializer,
+};
+
+// This limits how many times a type is traced while searching for all the
+// variants of its enums, in case a type never takes the same path twice.
+const MAX_PASSES: usize = 1000;
+
+// This is the name given to the newtype struct through which nested values
+// are deserialized, so that tracing recognizes them.
+pub(crate) const NESTED_NAME: &str = "\0serialization::Nested";
+
+#[derive(Default)]
+struct Tracer {
+    // For each enum found so far, this holds the kind of each variant which
+    // has been traced completely.
+    enums: BTreeMap<&'static str, Vec<Option<VariantKind>>>,
+
+    // These are the variants being traced at the moment.
+    in_progress: Vec<(&'static str, usize)>,
+
+    // These are the names of the containers being traced at the moment.
+    stack: Vec<&'static str>,
+
+    // This is the number of containers in the stack which are also further
+    // down in the stack.  While it's nonzero, the tracer is re-entering a
+    // recursive type, so it takes the shortest path out of it and records
+    // nothing.
+    reentries: usize,
+}
+
+impl Tracer {
+    fn choose_variant(
+        &self,
+        name: &'static str,
+        count: usize,
+    ) -> Result<usize> {
+        let explored = &self.enums[name];
+        let fresh = |index: &usize| {
+            explored[*index].is_none()
+                && !self.in_progress.contains(&(name, *index))
+        };
+        let unexplored = (0..count).find(fresh);
+        let complete = (0..count).find(|index| explored[*index].is_some());
+        if self.reentries == 0 {
+            unexplored.or(complete)
+        } else {
+            complete.or(unexplored)
+        }
+        .ok_or(Error::UntraceableType(name))
+    }
+
+    fn enter(
+        &mut self,
+        name: &'static str,
+    ) -> bool {
+        let reentry = self.stack.contains(&name);
+        if reentry {
+            self.reentries += 1;
+        }
+        self.stack.push(name);
+        reentry
+    }
+
+    fn is_complete(&self) -> bool {
+        self.enums.values().all(|variants| variants.iter().all(Option::is_some))
+    }
+
+    fn leave(
+        &mut self,
+        reentry: bool,
+    ) {
+        self.stack.pop();
+        if reentry {
+            self.reentries -= 1;
+        }
+    }
+
+    fn resolve(
+        &self,
+        schema: Schema,
+        resolving: &mut Vec<&'static str>,
+    ) -> Schema {
+        match schema {
+            Schema::Option(inner) => {
+                Schema::Option(Box::new(self.resolve(*inner, resolving)))
+            },
+            Schema::NewtypeStruct {
+                name,
+                inner,
+            } => Schema::NewtypeStruct {
+                name,
+                inner: Box::new(self.resolve(*inner, resolving)),
+            },
+            Schema::Seq(element) => {
+                Schema::Seq(Box::new(self.resolve(*element, resolving)))
+            },
+            Schema::Tuple(fields) => {
+                Schema::Tuple(self.resolve_all(fields, resolving))
+            },
+            Schema::Array(elements) => {
+                Schema::Array(self.resolve_all(elements, resolving))
+            },
+            Schema::TupleStruct {
+                name,
+                fields,
+            } => Schema::TupleStruct {
+                name,
+                fields: self.resolve_all(fields, resolving),
+            },
+            Schema::Map {
+                key,
+                value,
+            } => Schema::Map {
+                key: Box::new(self.resolve(*key, resolving)),
+                value: Box::new(self.resolve(*value, resolving)),
+            },
+            Schema::Struct {
+                name,
+                fields,
+            } => Schema::Struct {
+                name,
+                fields: self.resolve_fields(fields, resolving),
+            },
+            Schema::Enum {
+                name,
+                variants,
+            } => {
+                if resolving.contains(&name) {
+                    return Schema::Recursive(name);
+                }
+                resolving.push(name);
+                let variants = variants
+                    .into_iter()
+                    .zip(&self.enums[name])
+                    .map(|(variant, kind)| Variant {
+                        name: variant.name,
+                        kind: match kind.clone().unwrap_or(VariantKind::Unit) {
+                            VariantKind::Unit => VariantKind::Unit,
+                            VariantKind::Newtype(inner) => {
+                                VariantKind::Newtype(
+                                    self.resolve(inner, resolving),
+                                )
+                            },
+                            VariantKind::Tuple(fields) => VariantKind::Tuple(
+                                self.resolve_all(fields, resolving),
+                            ),
+                            VariantKind::Struct(fields) => VariantKind::Struct(
+                                self.resolve_fields(fields, resolving),
+                            ),
+                        },
+                    })
+                    .collect();
+                resolving.pop();
+                Schema::Enum {
+                    name,
+                    variants,
+                }
+            },
+            schema => schema,
+        }
+    }
+
+    fn resolve_all(
+        &self,
+        schemas: Vec<Schema>,
+        resolving: &mut Vec<&'static str>,
+    ) -> Vec<Schema> {
+        schemas
+            .into_iter()
+            .map(|schema| self.resolve(schema, resolving))
+            .collect()
+    }
+
+    fn resolve_fields(
+        &self,
+        fields: Vec<Field>,
+        resolving: &mut Vec<&'static str>,
+    ) -> Vec<Field> {
+        fields
+            .into_iter()
+            .map(|field| Field {
+                name: field.name,
+                schema: self.resolve(field.schema, resolving),
+            })
+            .collect()
+    }
+}
+
+// This deserializer hands placeholder values to the visitors it's given,
+// while recording in `schema` the kind of value each visitor asked for.
+struct TraceDeserializer<'t> {
+    tracer: &'t mut Tracer,
+    schema: &'t mut Schema,
+}
+
+impl<'t> TraceDeserializer<'t> {
+    fn new(
+        tracer: &'t mut Tracer,
+        schema: &'t mut Schema,
+    ) -> Self {
+        Self {
+            tracer,
+            schema,
+        }
+    }
+
+    fn trace_container<V, F>(
+        self,
+        name: &'static str,
+        visit: F,
+    ) -> Result<V>
+    where
+        F: FnOnce(&mut Tracer) -> Result<(V, Schema)>,
+    {
+        let reentry = self.tracer.enter(name);
+        let result = visit(self.tracer);
+        self.tracer.leave(reentry);
+        let (value, schema) = result?;
+        *self.schema = if reentry {
+            Schema::Recursive(name)
+        } else {
+            schema
+        };
+        Ok(value)
+    }
+}
+
+macro_rules! trace_primitive {
+    ($deserialize:ident, $visit:ident, $schema:ident, $value:expr) => {
+        fn $deserialize<V>(
+            self,
+            visitor: V,
+        ) -> Result<V::Value>
+        where
+            V: serde::de::Visitor<'de>,
+        {
+            *self.schema = Schema::$schema;
+            visitor.$visit($value)
+        }
+    };
+}
+
+impl<'de> serde::Deserializer<'de> for TraceDeserializer<'_> {
+    type Error = Error;
+
+    trace_primitive!(deserialize_bool, visit_bool, Bool, false);
+
+    trace_primitive!(deserialize_i8, visit_i8, I8, 0);
+
+    trace_primitive!(deserialize_i16, visit_i16, I16, 0);
+
+    trace_primitive!(deserialize_i32, visit_i32, I32, 0);
+
+    trace_primitive!(deserialize_i64, visit_i64, I64, 0);
+
+    trace_primitive!(deserialize_i128, visit_i128, I128, 0);
+
+    trace_primitive!(deserialize_u8, visit_u8, U8, 0);
+
+    trace_primitive!(deserialize_u16, visit_u16, U16, 0);
+
+    trace_primitive!(deserialize_u32, visit_u32, U32, 0);
+
+    trace_primitive!(deserialize_u64, visit_u64, U64, 0);
+
+    trace_primitive!(deserialize_u128, visit_u128, U128, 0);
+
+    trace_primitive!(deserialize_f32, visit_f32, F32, 0.0);
+
+    trace_primitive!(deserialize_f64, visit_f64, F64, 0.0);
+
+    trace_primitive!(deserialize_char, visit_char, Char, '\0');
+
+    trace_primitive!(deserialize_str, visit_borrowed_str, Str, "");
+
+    trace_primitive!(deserialize_string, visit_borrowed_str, Str, "");
+
+    trace_primitive!(deserialize_bytes, visit_borrowed_bytes, Bytes, &[]);
+
+    trace_primitive!(deserialize_byte_buf, visit_borrowed_bytes, Bytes, &[]);
+
+    fn deserialize_any<V>(
+        self,
+        _visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        Err(Error::TypeUnknown)
+    }
+
+    fn deserialize_option<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let mut inner = Schema::Unit;
+        let value = if self.tracer.reentries == 0 {
+            visitor.visit_some(TraceDeserializer::new(self.tracer, &mut inner))
+        } else {
+            visitor.visit_none()
+        }?;
+        *self.schema = Schema::Option(Box::new(inner));
+        Ok(value)
+    }
+
+    fn deserialize_unit<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        *self.schema = Schema::Unit;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        *self.schema = Schema::UnitStruct(name);
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        if name == NESTED_NAME {
+            // A nested value is encoded as a byte string, but a placeholder
+            // for the nested value is still needed, which is obtained by
+            // tracing the type of the nested value as if it were the only
+            // element of a sequence.
+            let mut elements = TraceSeq::new(self.tracer, 1);
+            let value = visitor.visit_seq(&mut elements)?;
+            *self.schema = Schema::Bytes;
+            return Ok(value);
+        }
+        self.trace_container(name, |tracer| {
+            let mut inner = Schema::Unit;
+            let value = visitor.visit_newtype_struct(
+                TraceDeserializer::new(tracer, &mut inner),
+            )?;
+            Ok((value, Schema::NewtypeStruct {
+                name,
+                inner: Box::new(inner),
+            }))
+        })
+    }
+
+    fn deserialize_seq<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let len = usize::from(self.tracer.reentries == 0);
+        let mut elements = TraceSeq::new(self.tracer, len);
+        let value = visitor.visit_seq(&mut elements)?;
+        *self.schema = Schema::Seq(Box::new(
+            elements.schemas.pop().unwrap_or(Schema::Unit),
+        ));
+        Ok(value)
+    }
+
+    fn deserialize_tuple<V>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        // Arrays and tuples are both deserialized as tuples, so they are
+        // told apart by what their visitors expect.
+        let expected = format!("{}", &visitor as &dyn Expected);
+        let array = expected.starts_with("an array of length ")
+            || expected == "an empty array";
+        let mut elements = TraceSeq::new(self.tracer, len);
+        let value = visitor.visit_seq(&mut elements)?;
+        *self.schema = if array {
+            Schema::Array(elements.schemas)
+        } else {
+            Schema::Tuple(elements.schemas)
+        };
+        Ok(value)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.trace_container(name, |tracer| {
+            let mut elements = TraceSeq::new(tracer, len);
+            let value = visitor.visit_seq(&mut elements)?;
+            Ok((value, Schema::TupleStruct {
+                name,
+                fields: elements.schemas,
+            }))
+        })
+    }
+
+    fn deserialize_map<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let mut entries = TraceMap {
+            remaining: self.tracer.reentries == 0,
+            tracer: self.tracer,
+            key: Schema::Unit,
+            value: Schema::Unit,
+        };
+        let value = visitor.visit_map(&mut entries)?;
+        *self.schema = Schema::Map {
+            key: Box::new(entries.key),
+            value: Box::new(entries.value),
+        };
+        Ok(value)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.trace_container(name, |tracer| {
+            let mut elements = TraceSeq::new(tracer, fields.len());
+            let value = visitor.visit_seq(&mut elements)?;
+            Ok((value, Schema::Struct {
+                name,
+                fields: zip_fields(fields, elements.schemas),
+            }))
+        })
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.trace_container(name, |tracer| {
+            tracer
+                .enums
+                .entry(name)
+                .or_insert_with(|| vec![None; variants.len()]);
+            let index = tracer.choose_variant(name, variants.len())?;
+            tracer.in_progress.push((name, index));
+            let mut kind = VariantKind::Unit;
+            let record = tracer.reentries == 0;
+            let result = visitor.visit_enum(TraceEnum {
+                tracer: &mut *tracer,
+                index,
+                kind: &mut kind,
+            });
+            tracer.in_progress.pop();
+            let value = result?;
+            if record {
+                tracer.enums.get_mut(name).unwrap()[index] = Some(kind);
+            }
+            Ok((value, Schema::Enum {
+                name,
+                variants: variants
+                    .iter()
+                    .map(|name| Variant {
+                        name,
+                        kind: VariantKind::Unit,
+                    })
+                    .collect(),
+            }))
+        })
+    }
+
+    fn deserialize_identifier<V>(
+        self,
+        _visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        Err(Error::IdentifierUnknown)
+    }
+
+    fn deserialize_ignored_any<V>(
+        self,
+        _visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        Err(Error::TypeUnknown)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+}
+
+fn zip_fields(
+    names: &'static [&'static str],
+    schemas: Vec<Schema>,
+) -> Vec<Field> {
+    names
+        .iter()
+        .zip(schemas)
+        .map(|(name, schema)| Field {
+            name,
+            schema,
+        })
+        .collect()
+}
+
+struct TraceSeq<'t> {
+    tracer: &'t mut Tracer,
+    len: usize,
+    schemas: Vec<Schema>,
+}
+
+impl<'t> TraceSeq<'t> {
+    fn new(
+        tracer: &'t mut Tracer,
+        len: usize,
+    ) -> Self {
+        Self {
+            tracer,
+            len,
+            schemas: Vec::with_capacity(len),
+        }
+    }
+}
+
+impl<'de> serde::de::SeqAccess<'de> for TraceSeq<'_> {
+    type Error = Error;
+
+    fn next_element_seed<T>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        if self.schemas.len() == self.len {
+            return Ok(None);
+        }
+        let mut schema = Schema::Unit;
+        let value =
+            seed.deserialize(TraceDeserializer::new(self.tracer, &mut schema))?;
+        self.schemas.push(schema);
+        Ok(Some(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.schemas.len())
+    }
+}
+
+struct TraceMap<'t> {
+    tracer: &'t mut Tracer,
+    remaining: bool,
+    key: Schema,
+    value: Schema,
+}
+
+impl<'de> serde::de::MapAccess<'de> for TraceMap<'_> {
+    type Error = Error;
+
+    fn next_key_seed<K>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>>
+    where
+        K: serde::de::DeserializeSeed<'de>,
+    {
+        if !self.remaining {
+            return Ok(None);
+        }
+        self.remaining = false;
+        seed.deserialize(TraceDeserializer::new(self.tracer, &mut self.key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(TraceDeserializer::new(self.tracer, &mut self.value))
+    }
+}
+
+struct TraceEnum<'t> {
+    tracer: &'t mut Tracer,
+    index: usize,
+    kind: &'t mut VariantKind,
+}
+
+impl<'de> serde::de::EnumAccess<'de> for TraceEnum<'_> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant)>
+    where
+        V: serde::de::DeserializeSeed<'de>,
+    {
+        let index =
+            u32::try_from(self.index).map_err(|_| Error::IntegerOverflow)?;
+        let variant = seed
+            .deserialize(IntoDeserializer::<Error>::into_deserializer(index))?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> serde::de::VariantAccess<'de> for TraceEnum<'_> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        *self.kind = VariantKind::Unit;
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(
+        self,
+        seed: T,
+    ) -> Result<T::Value>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        let mut inner = Schema::Unit;
+        let value =
+            seed.deserialize(TraceDeserializer::new(self.tracer, &mut inner))?;
+        *self.kind = VariantKind::Newtype(inner);
+        Ok(value)
+    }
+
+    fn tuple_variant<V>(
+        self,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let mut elements = TraceSeq::new(self.tracer, len);
+        let value = visitor.visit_seq(&mut elements)?;
+        *self.kind = VariantKind::Tuple(elements.schemas);
+        Ok(value)
+    }
+
+    fn struct_variant<V>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        let mut elements = TraceSeq::new(self.tracer, fields.len());
+        let value = visitor.visit_seq(&mut elements)?;
+        *self.kind = VariantKind::Struct(zip_fields(fields, elements.schemas));
+        Ok(value)
+    }
+}
+
+pub fn trace<'de, T>() -> Result<Schema>
+where
+    T: serde::Deserialize<'de>,
+{
+    let mut tracer = Tracer::default();
+    for _ in 0..MAX_PASSES {
+        let mut schema = Schema::Unit;
+        T::deserialize(TraceDeserializer::new(&mut tracer, &mut schema))?;
+        if tracer.is_complete() {
+            return Ok(tracer.resolve(schema, &mut Vec::new()));
+        }
+    }
+    Err(Error::UntraceableType(core::any::type_name::<T>()))
+}
@@ -0,0 +1,500 @@
+use super::{
+    Error,
+    Output,
+    Result,
+};
+use io_uring::{
+    opcode,
+    types,
+    IoUring,
+};
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{
+        Seek,
+        SeekFrom,
+    },
+    os::unix::io::AsRawFd,
+};
+
+// The kernel refuses to register buffers larger than this.
+const MAX_BUFFER_LEN: usize = 1 << 30;
+
+// This is the most buffers which may be registered, so that the index of
+// each fits in the submission entries which name it.
+const MAX_BUFFER_COUNT: usize = 1024;
+
+// This is one of the buffers registered with the ring, which is filled with
+// encoded bytes and then written, while the next buffer is filled.
+struct Buffer {
+    bytes: Box<[u8]>,
+
+    // This is the number of bytes held in the buffer.
+    len: usize,
+
+    // This is the number of bytes held which have been written so far,
+    // while the buffer is in flight.
+    written: usize,
+
+    // This is the position in the file where the bytes held are written.
+    offset: u64,
+
+    // This is set while the kernel may be reading the buffer, from when a
+    // write of it is submitted until the write completes.
+    in_flight: bool,
+}
+
+// This is the ring along with the buffers registered with it.  The kernel
+// reads the buffers during writes, after the calls submitting them have
+// returned, so they must not be freed while any write is in flight, nor
+// while they remain registered with the ring.
+struct Ring {
+    // This is dropped before the buffers, as fields are dropped in the
+    // order they are declared, so the buffers outlive their registration.
+    ring: IoUring,
+    buffers: Vec<Buffer>,
+}
+
+impl Ring {
+    #[allow(clippy::cast_possible_truncation)]
+    fn new(
+        buffer_len: usize,
+        buffer_count: usize,
+    ) -> Result<Self> {
+        let ring = IoUring::new(buffer_count as u32).map_err(Error::Io)?;
+        let buffers = (0..buffer_count)
+            .map(|_| Buffer {
+                bytes: vec![0; buffer_len].into_boxed_slice(),
+                len: 0,
+                written: 0,
+                offset: 0,
+                in_flight: false,
+            })
+            .collect::<Vec<_>>();
+        let iovecs = buffers
+            .iter()
+            .map(|buffer| libc::iovec {
+                iov_base: buffer.bytes.as_ptr() as *mut libc::c_void,
+                iov_len: buffer.bytes.len(),
+            })
+            .collect::<Vec<_>>();
+        // SAFETY: The buffers are boxed, so they stay where they are while
+        // the ring holds them, and the ring is dropped before they are, as
+        // declared in `Ring`.
+        unsafe { ring.submitter().register_buffers(&iovecs) }
+            .map_err(Error::Io)?;
+        Ok(Self {
+            ring,
+            buffers,
+        })
+    }
+
+    fn in_flight(&self) -> bool {
+        self.buffers.iter().any(|buffer| buffer.in_flight)
+    }
+
+    // Queue a write of the bytes held in the given buffer which have not
+    // yet been written.
+    #[allow(clippy::cast_possible_truncation)]
+    fn push(
+        &mut self,
+        index: usize,
+        file: &File,
+    ) -> Result<()> {
+        let buffer = &mut self.buffers[index];
+        let unwritten = &buffer.bytes[buffer.written..buffer.len];
+        let entry = opcode::WriteFixed::new(
+            types::Fd(file.as_raw_fd()),
+            unwritten.as_ptr(),
+            unwritten.len() as u32,
+            index as u16,
+        )
+        .offset(buffer.offset + buffer.written as u64)
+        .build()
+        .user_data(index as u64);
+        buffer.in_flight = true;
+        // SAFETY: The write reads only from the registered buffer, which
+        // is not touched again until the write completes, and which is not
+        // freed while it is in flight, as ensured by dropping `Ring`.
+        unsafe { self.ring.submission().push(&entry) }.map_err(|_| {
+            buffer.in_flight = false;
+            Error::Io(std::io::ErrorKind::WouldBlock.into())
+        })?;
+        self.ring.submit().map_err(Error::Io)?;
+        Ok(())
+    }
+
+    // Wait for at least one write to complete, and note the writes which
+    // have, queueing the rest of those which were cut short.
+    fn wait(
+        &mut self,
+        file: &File,
+    ) -> Result<()> {
+        loop {
+            match self.ring.submit_and_wait(1) {
+                Ok(_) => break,
+                Err(error)
+                    if error.kind() == std::io::ErrorKind::Interrupted => {},
+                Err(error) => return Err(Error::Io(error)),
+            }
+        }
+        let completions = self
+            .ring
+            .completion()
+            .map(|entry| (entry.user_data(), entry.result()))
+            .collect::<Vec<_>>();
+        let mut result = Ok(());
+        for (index, written) in completions {
+            let index = usize::try_from(index).unwrap_or(usize::MAX);
+            let Some(buffer) = self.buffers.get_mut(index) else {
+                continue;
+            };
+            buffer.in_flight = false;
+            let error = match usize::try_from(written) {
+                Ok(0) => std::io::ErrorKind::WriteZero.into(),
+                Ok(written) => {
+                    buffer.written += written;
+                    if buffer.written < buffer.len {
+                        if let Err(error) = self.push(index, file) {
+                            result = result.and(Err(error));
+                        }
+                    } else {
+                        buffer.len = 0;
+                    }
+                    continue;
+                },
+                Err(_) => std::io::Error::from_raw_os_error(-written),
+            };
+            // The bytes which could not be written are dropped, so that the
+            // buffer can be used again.
+            buffer.len = 0;
+            result = result.and(Err(Error::Io(error)));
+        }
+        result
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        while self.in_flight() {
+            if self.ring.submit_and_wait(1).is_err() {
+                break;
+            }
+            for entry in self.ring.completion() {
+                if let Ok(index) = usize::try_from(entry.user_data()) {
+                    if let Some(buffer) = self.buffers.get_mut(index) {
+                        buffer.in_flight = false;
+                    }
+                }
+            }
+        }
+        // If the kernel might still read any buffer, it is leaked rather
+        // than freed.
+        if self.in_flight() {
+            for buffer in self.buffers.drain(..) {
+                Box::leak(buffer.bytes);
+            }
+        }
+    }
+}
+
+/// This is an [`Output`] which writes to a file through `io_uring`, on
+/// Linux with the `io-uring` feature enabled.  Encoded bytes are gathered
+/// into buffers registered with the kernel, and each buffer is submitted
+/// to be written once it is full, while the next one is filled, so that
+/// writing many small values costs a system call per buffer rather than
+/// per value, and encoding overlaps writing.
+///
+/// The buffers are written at increasing positions in the file, starting
+/// from its position when the first buffer is written.  Call [`finish`] to
+/// write the last buffer, wait for every write to complete, and get the
+/// file back, positioned just past the bytes written.  Bytes not yet
+/// written when the output is dropped without calling [`finish`] are lost.
+///
+/// [`Output`]: trait.Output.html
+/// [`finish`]: #method.finish
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     to_bytes,
+///     Config,
+///     UringOutput,
+/// };
+/// use std::io::{
+///     Read,
+///     Seek,
+/// };
+///
+/// # let path = std::env::temp_dir()
+/// #     .join(format!("serialization-uring-doc-{}", std::process::id()));
+/// let file = std::fs::OpenOptions::new()
+///     .create(true)
+///     .truncate(true)
+///     .read(true)
+///     .write(true)
+///     .open(&path)
+///     .unwrap();
+/// let mut output = UringOutput::new(file).with_buffer_len(4096);
+/// let config = Config::new();
+/// let mut expected = Vec::new();
+/// for i in 0..10_000_u32 {
+///     serde::Serialize::serialize(&i, &mut config.serializer(&mut output))
+///         .unwrap();
+///     expected.extend(to_bytes(&i).unwrap());
+/// }
+/// let mut file = output.finish().unwrap();
+/// file.rewind().unwrap();
+/// let mut encoding = Vec::new();
+/// file.read_to_end(&mut encoding).unwrap();
+/// assert_eq!(expected, encoding);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+pub struct UringOutput {
+    // This is dropped before the file, so that every write is waited for
+    // while the file is still open.
+    ring: Option<Ring>,
+    file: File,
+    buffer_len: usize,
+    buffer_count: usize,
+    current: usize,
+    offset: Option<u64>,
+}
+
+impl UringOutput {
+    /// This is the default length, in bytes, of each buffer.
+    pub const DEFAULT_BUFFER_LEN: usize = 64 * 1024;
+
+    /// This is the default number of buffers.
+    pub const DEFAULT_BUFFER_COUNT: usize = 4;
+
+    /// This function is used to write any bytes still buffered, and wait
+    /// for every write to complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the ring could not be set up, or a write
+    /// failed.  Bytes which could not be written are dropped.
+    ///
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(ring) = self.ring.as_ref() {
+            if ring.buffers[self.current].len > 0 {
+                self.submit()?;
+            }
+        }
+        if let Some(ring) = self.ring.as_mut() {
+            while ring.in_flight() {
+                ring.wait(&self.file)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// This function is used to write any bytes still buffered, wait for
+    /// every write to complete, and give back the file, positioned just
+    /// past the bytes written.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the ring could not be set up, a write
+    /// failed, or the file could not be positioned.
+    ///
+    /// [`Error::Io`]: enum.Error.html#variant.Io
+    pub fn finish(mut self) -> Result<File> {
+        self.flush()?;
+        if let Some(offset) = self.offset {
+            self.file.seek(SeekFrom::Start(offset)).map_err(Error::Io)?;
+        }
+        drop(self.ring.take());
+        Ok(self.file)
+    }
+
+    /// Return a new output which writes to the given file, using the
+    /// default number and length of buffers.  The ring and its buffers are
+    /// set up when the first bytes are written.
+    #[must_use]
+    pub fn new(file: File) -> Self {
+        Self {
+            ring: None,
+            file,
+            buffer_len: Self::DEFAULT_BUFFER_LEN,
+            buffer_count: Self::DEFAULT_BUFFER_COUNT,
+            current: 0,
+            offset: None,
+        }
+    }
+
+    /// Set the number of buffers, which is also the most writes in flight
+    /// at once.  It is at least one and at most 1024.  This has no effect
+    /// once bytes have been written.
+    #[must_use]
+    pub fn with_buffer_count(
+        mut self,
+        buffer_count: usize,
+    ) -> Self {
+        self.buffer_count = buffer_count.clamp(1, MAX_BUFFER_COUNT);
+        self
+    }
+
+    /// Set the length, in bytes, of each buffer, which is filled before it
+    /// is written.  It is at least one byte and at most 1 GiB.  This has no
+    /// effect once bytes have been written.
+    #[must_use]
+    pub fn with_buffer_len(
+        mut self,
+        buffer_len: usize,
+    ) -> Self {
+        self.buffer_len = buffer_len.clamp(1, MAX_BUFFER_LEN);
+        self
+    }
+
+    // Submit the buffer being filled, and wait until the next one is free.
+    fn submit(&mut self) -> Result<()> {
+        let offset = match self.offset {
+            Some(offset) => offset,
+            None => (&self.file).stream_position().map_err(Error::Io)?,
+        };
+        let Some(ring) = self.ring.as_mut() else {
+            return Ok(());
+        };
+        let buffer = &mut ring.buffers[self.current];
+        buffer.offset = offset;
+        buffer.written = 0;
+        self.offset = Some(offset + buffer.len as u64);
+        ring.push(self.current, &self.file)?;
+        self.current = (self.current + 1) % ring.buffers.len();
+        while ring.buffers[self.current].in_flight {
+            ring.wait(&self.file)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for UringOutput {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        f.debug_struct("UringOutput")
+            .field("file", &self.file)
+            .field("buffer_len", &self.buffer_len)
+            .field("buffer_count", &self.buffer_count)
+            .field("offset", &self.offset)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Output for UringOutput {
+    fn write_bytes(
+        &mut self,
+        mut bytes: &[u8],
+    ) -> Result<()> {
+        while !bytes.is_empty() {
+            let Some(ring) = self.ring.as_mut() else {
+                let ring = Ring::new(self.buffer_len, self.buffer_count)?;
+                self.ring = Some(ring);
+                continue;
+            };
+            let buffer = &mut ring.buffers[self.current];
+            let len = (buffer.bytes.len() - buffer.len).min(bytes.len());
+            buffer.bytes[buffer.len..buffer.len + len]
+                .copy_from_slice(&bytes[..len]);
+            buffer.len += len;
+            bytes = &bytes[len..];
+            if buffer.len == buffer.bytes.len() {
+                self.submit()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        to_bytes,
+        Config,
+    };
+    use std::io::Read;
+
+    fn temp_file(name: &str) -> (std::path::PathBuf, File) {
+        let path = std::env::temp_dir().join(format!(
+            "serialization-uring-{name}-{}",
+            std::process::id()
+        ));
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        (path, file)
+    }
+
+    #[test]
+    fn many_small_values() {
+        let (path, file) = temp_file("many");
+        // The buffers are small and few, so that writes are cut between
+        // values, and buffers are waited for before being filled again.
+        let mut output =
+            UringOutput::new(file).with_buffer_len(100).with_buffer_count(2);
+        let config = Config::new();
+        let mut expected = Vec::new();
+        for i in 0..10_000_u32 {
+            let value = (i, format!("record {i}"));
+            assert!(serde::Serialize::serialize(
+                &value,
+                &mut config.serializer(&mut output)
+            )
+            .is_ok());
+            expected.extend(to_bytes(&value).unwrap());
+        }
+        let file = output.finish();
+        assert!(file.is_ok());
+        let mut file = file.unwrap();
+        assert_eq!(expected.len() as u64, file.stream_position().unwrap());
+        file.rewind().unwrap();
+        let mut encoding = Vec::new();
+        file.read_to_end(&mut encoding).unwrap();
+        assert_eq!(expected, encoding);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn writing_starts_at_the_file_position() {
+        let (path, mut file) = temp_file("position");
+        std::io::Write::write_all(&mut file, b"head").unwrap();
+        let mut output = UringOutput::new(file);
+        assert!(output.write_bytes(&[1, 2, 3]).is_ok());
+        assert!(output.flush().is_ok());
+        assert!(output.write_bytes(&[4]).is_ok());
+        let file = output.finish();
+        assert!(file.is_ok());
+        let mut file = file.unwrap();
+        std::io::Write::write_all(&mut file, b"tail").unwrap();
+        assert_eq!(
+            &b"head\x01\x02\x03\x04tail"[..],
+            &std::fs::read(&path).unwrap()[..]
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn write_errors_are_returned() {
+        let (path, file) = temp_file("read-only");
+        drop(file);
+        let file = File::open(&path).unwrap();
+        let mut output = UringOutput::new(file).with_buffer_len(4);
+        assert!(matches!(
+            output.write_bytes(&[0; 4]).and_then(|()| output.flush()),
+            Err(Error::Io(_))
+        ));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
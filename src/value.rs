@@ -0,0 +1,1517 @@
+use super::{
+    redacted::{
+        REDACTED_NAME,
+        REDACTED_TEXT,
+    },
+    Error,
+    Result,
+};
+use std::{
+    convert::TryFrom,
+    fmt::Write,
+};
+
+// This is the message of the error made by serializing or deserializing
+// from a redacted value, whose contents were left out.
+const REDACTED_MESSAGE: &str = "redacted value has no contents";
+
+// This is the most elements set aside room for before a sequence or map is
+// deserialized, since the length an encoding gives is not to be trusted
+// until that many elements have actually been decoded.
+const MAX_PREALLOCATION: usize = 4096;
+
+/// This is a dynamically-typed value, holding anything which can be
+/// represented in the data model of this crate.  Values keep the exact
+/// integer and float types they were made from, since those determine their
+/// encodings, but newtype structs are represented by the values they wrap,
+/// and unit structs by [`Value::Unit`], since they encode the same way.
+///
+/// Values are obtained from other types using [`to_value`], and converted
+/// back into them using [`from_value`].
+///
+/// [`Value::Unit`]: #variant.Unit
+/// [`to_value`]: fn.to_value.html
+/// [`from_value`]: fn.from_value.html
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub enum Value {
+    /// A `bool`.
+    Bool(bool),
+
+    /// An `i8`.
+    I8(i8),
+
+    /// An `i16`.
+    I16(i16),
+
+    /// An `i32`.
+    I32(i32),
+
+    /// An `i64`.
+    I64(i64),
+
+    /// An `i128`.
+    I128(i128),
+
+    /// A `u8`.
+    U8(u8),
+
+    /// A `u16`.
+    U16(u16),
+
+    /// A `u32`.
+    U32(u32),
+
+    /// A `u64`.
+    U64(u64),
+
+    /// A `u128`.
+    U128(u128),
+
+    /// An `f32`.
+    F32(f32),
+
+    /// An `f64`.
+    F64(f64),
+
+    /// A `char`.
+    Char(char),
+
+    /// A string.
+    Str(String),
+
+    /// A sequence of bytes.
+    Bytes(Vec<u8>),
+
+    /// An optional value.
+    Option(Option<Box<Value>>),
+
+    /// The unit value `()`, or a unit struct.
+    Unit,
+
+    /// A sequence of values, such as a `Vec`.
+    Seq(Vec<Value>),
+
+    /// A fixed number of values, such as a tuple or tuple struct.
+    Tuple(Vec<Value>),
+
+    /// A map, with its entries in the order they were given.
+    Map(Vec<(Value, Value)>),
+
+    /// A struct with named fields, in order.
+    Struct(Vec<(String, Value)>),
+
+    /// A variant of an enum.
+    Variant {
+        /// This is the index of the variant within the enum.
+        index: u32,
+
+        /// This is the name of the variant.
+        name: String,
+
+        /// This is the content of the variant: [`Value::Unit`] for unit
+        /// variants, the wrapped value for newtype variants,
+        /// [`Value::Tuple`] for tuple variants, and [`Value::Struct`] for
+        /// struct variants.
+        ///
+        /// [`Value::Unit`]: #variant.Unit
+        /// [`Value::Tuple`]: #variant.Tuple
+        /// [`Value::Struct`]: #variant.Struct
+        value: Box<Value>,
+    },
+
+    /// A value wrapped in [`Redacted`], which is left out.
+    ///
+    /// [`Redacted`]: struct.Redacted.html
+    Redacted,
+}
+
+fn write_list<'a, I>(
+    f: &mut std::fmt::Formatter,
+    open: &str,
+    values: I,
+    close: &str,
+) -> std::fmt::Result
+where
+    I: IntoIterator<Item = &'a Value>,
+{
+    f.write_str(open)?;
+    for (i, value) in values.into_iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        write!(f, "{value}")?;
+    }
+    f.write_str(close)
+}
+
+fn write_fields(
+    f: &mut std::fmt::Formatter,
+    fields: &[(String, Value)],
+) -> std::fmt::Result {
+    f.write_str("{")?;
+    for (i, (name, value)) in fields.iter().enumerate() {
+        if i > 0 {
+            f.write_char(',')?;
+        }
+        write!(f, " {name}: {value}")?;
+    }
+    f.write_str(" }")
+}
+
+/// Values are displayed much as they would be written in Rust, but with
+/// the names of structs left out.
+impl std::fmt::Display for Value {
+    fn fmt(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        match self {
+            Value::Bool(value) => write!(f, "{value}"),
+            Value::I8(value) => write!(f, "{value}"),
+            Value::I16(value) => write!(f, "{value}"),
+            Value::I32(value) => write!(f, "{value}"),
+            Value::I64(value) => write!(f, "{value}"),
+            Value::I128(value) => write!(f, "{value}"),
+            Value::U8(value) => write!(f, "{value}"),
+            Value::U16(value) => write!(f, "{value}"),
+            Value::U32(value) => write!(f, "{value}"),
+            Value::U64(value) => write!(f, "{value}"),
+            Value::U128(value) => write!(f, "{value}"),
+            Value::F32(value) => write!(f, "{value:?}"),
+            Value::F64(value) => write!(f, "{value:?}"),
+            Value::Char(value) => write!(f, "{value:?}"),
+            Value::Str(value) => write!(f, "{value:?}"),
+            Value::Bytes(value) => {
+                write!(f, "b{:?}", String::from_utf8_lossy(value))
+            },
+            Value::Option(None) => f.write_str("None"),
+            Value::Option(Some(value)) => write!(f, "Some({value})"),
+            Value::Unit => f.write_str("()"),
+            Value::Seq(values) => write_list(f, "[", values, "]"),
+            Value::Tuple(values) => write_list(f, "(", values, ")"),
+            Value::Map(entries) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{key}: {value}")?;
+                }
+                f.write_str("}")
+            },
+            Value::Struct(fields) => write_fields(f, fields),
+            Value::Variant {
+                name,
+                value,
+                ..
+            } => match value.as_ref() {
+                Value::Unit => f.write_str(name),
+                Value::Tuple(values) => {
+                    f.write_str(name)?;
+                    write_list(f, "(", values, ")")
+                },
+                Value::Struct(fields) => {
+                    write!(f, "{name} ")?;
+                    write_fields(f, fields)
+                },
+                value => write!(f, "{name}({value})"),
+            },
+            Value::Redacted => f.write_str(REDACTED_TEXT),
+        }
+    }
+}
+
+// This serializes the elements of a tuple, tuple struct, or tuple variant.
+fn serialize_elements<'a, S, I>(
+    mut tuple: S,
+    values: I,
+) -> std::result::Result<S::Ok, S::Error>
+where
+    S: serde::ser::SerializeTupleVariant,
+    I: IntoIterator<Item = &'a Value>,
+{
+    for value in values {
+        tuple.serialize_field(value)?;
+    }
+    tuple.end()
+}
+
+/// A value serializes as the data it holds, so its encoding is the same as
+/// that of the value it was made from.  Since serializers are given the
+/// names of struct fields and variants only as `&'static str`, which a
+/// value cannot hold, structs are serialized as tuples, and variants with
+/// empty names; the encoding of this crate leaves names out anyway.
+impl serde::Serialize for Value {
+    fn serialize<S>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::{
+            Error as _,
+            SerializeTuple,
+        };
+        match self {
+            Value::Bool(value) => serializer.serialize_bool(*value),
+            Value::I8(value) => serializer.serialize_i8(*value),
+            Value::I16(value) => serializer.serialize_i16(*value),
+            Value::I32(value) => serializer.serialize_i32(*value),
+            Value::I64(value) => serializer.serialize_i64(*value),
+            Value::I128(value) => serializer.serialize_i128(*value),
+            Value::U8(value) => serializer.serialize_u8(*value),
+            Value::U16(value) => serializer.serialize_u16(*value),
+            Value::U32(value) => serializer.serialize_u32(*value),
+            Value::U64(value) => serializer.serialize_u64(*value),
+            Value::U128(value) => serializer.serialize_u128(*value),
+            Value::F32(value) => serializer.serialize_f32(*value),
+            Value::F64(value) => serializer.serialize_f64(*value),
+            Value::Char(value) => serializer.serialize_char(*value),
+            Value::Str(value) => serializer.serialize_str(value),
+            Value::Bytes(value) => serializer.serialize_bytes(value),
+            Value::Option(None) => serializer.serialize_none(),
+            Value::Option(Some(value)) => serializer.serialize_some(value),
+            Value::Unit => serializer.serialize_unit(),
+            Value::Seq(values) => serializer.collect_seq(values),
+            Value::Tuple(values) => {
+                let mut tuple = serializer.serialize_tuple(values.len())?;
+                for value in values {
+                    tuple.serialize_element(value)?;
+                }
+                tuple.end()
+            },
+            Value::Map(entries) => serializer
+                .collect_map(entries.iter().map(|(key, value)| (key, value))),
+            Value::Struct(fields) => {
+                let mut tuple = serializer.serialize_tuple(fields.len())?;
+                for (_, value) in fields {
+                    tuple.serialize_element(value)?;
+                }
+                tuple.end()
+            },
+            Value::Variant {
+                index,
+                value,
+                ..
+            } => match value.as_ref() {
+                Value::Unit => {
+                    serializer.serialize_unit_variant("", *index, "")
+                },
+                Value::Tuple(values) => serialize_elements(
+                    serializer.serialize_tuple_variant(
+                        "",
+                        *index,
+                        "",
+                        values.len(),
+                    )?,
+                    values,
+                ),
+                Value::Struct(fields) => serialize_elements(
+                    serializer.serialize_tuple_variant(
+                        "",
+                        *index,
+                        "",
+                        fields.len(),
+                    )?,
+                    fields.iter().map(|(_, value)| value),
+                ),
+                value => {
+                    serializer.serialize_newtype_variant("", *index, "", value)
+                },
+            },
+            Value::Redacted => Err(S::Error::custom(REDACTED_MESSAGE)),
+        }
+    }
+}
+
+struct Visitor;
+
+macro_rules! visit_primitive {
+    ($($visit:ident: $type:ty => $variant:ident),*) => {
+        $(
+            fn $visit<E>(
+                self,
+                v: $type,
+            ) -> std::result::Result<Value, E> {
+                Ok(Value::$variant(v.into()))
+            }
+        )*
+    };
+}
+
+impl<'de> serde::de::Visitor<'de> for Visitor {
+    type Value = Value;
+
+    visit_primitive!(
+        visit_bool: bool => Bool,
+        visit_i8: i8 => I8,
+        visit_i16: i16 => I16,
+        visit_i32: i32 => I32,
+        visit_i64: i64 => I64,
+        visit_i128: i128 => I128,
+        visit_u8: u8 => U8,
+        visit_u16: u16 => U16,
+        visit_u32: u32 => U32,
+        visit_u64: u64 => U64,
+        visit_u128: u128 => U128,
+        visit_f32: f32 => F32,
+        visit_f64: f64 => F64,
+        visit_char: char => Char,
+        visit_str: &str => Str,
+        visit_string: String => Str,
+        visit_bytes: &[u8] => Bytes,
+        visit_byte_buf: Vec<u8> => Bytes
+    );
+
+    fn expecting(
+        &self,
+        formatter: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        formatter.write_str("any value")
+    }
+
+    fn visit_none<E>(self) -> std::result::Result<Value, E> {
+        Ok(Value::Option(None))
+    }
+
+    fn visit_some<D>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(Value::Option(Some(Box::new(serde::Deserialize::deserialize(
+            deserializer,
+        )?))))
+    }
+
+    fn visit_unit<E>(self) -> std::result::Result<Value, E> {
+        Ok(Value::Unit)
+    }
+
+    fn visit_newtype_struct<D>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        serde::Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_seq<A>(
+        self,
+        mut seq: A,
+    ) -> std::result::Result<Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut values = Vec::with_capacity(
+            seq.size_hint().unwrap_or(0).min(MAX_PREALLOCATION),
+        );
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Value::Seq(values))
+    }
+
+    fn visit_map<A>(
+        self,
+        mut map: A,
+    ) -> std::result::Result<Value, A::Error>
+    where
+        A: serde::de::MapAccess<'de>,
+    {
+        let mut entries = Vec::with_capacity(
+            map.size_hint().unwrap_or(0).min(MAX_PREALLOCATION),
+        );
+        while let Some(entry) = map.next_entry()? {
+            entries.push(entry);
+        }
+        Ok(Value::Map(entries))
+    }
+
+    fn visit_enum<A>(
+        self,
+        data: A,
+    ) -> std::result::Result<Value, A::Error>
+    where
+        A: serde::de::EnumAccess<'de>,
+    {
+        // Variants are identified by their indexes, or by their names if
+        // the encoding holds them.
+        let (identifier, variant) = data.variant::<Value>()?;
+        let (index, name) = match identifier {
+            Value::Str(name) => (0, name),
+            Value::U64(index) => (
+                u32::try_from(index).map_err(|_| {
+                    serde::de::Error::custom("variant index out of range")
+                })?,
+                String::new(),
+            ),
+            _ => {
+                return Err(serde::de::Error::custom(
+                    "variant is not identified by index or name",
+                ))
+            },
+        };
+        Ok(Value::Variant {
+            index,
+            name,
+            value: Box::new(serde::de::VariantAccess::newtype_variant(
+                variant,
+            )?),
+        })
+    }
+}
+
+/// Since the encoding of this crate does not describe itself unless it is
+/// [made self-describing], a value can only be deserialized from such
+/// encodings or from formats which do, such as JSON.  Since these do not
+/// tell sequences, tuples, and structs apart, all of them become
+/// [`Value::Seq`], except that in JSON structs become [`Value::Map`].
+/// Variants are given empty names, unless the encoding identifies them by
+/// [name], in which case they are given index zero.
+///
+/// [made self-describing]: struct.Config.html#method.with_self_describing
+/// [name]: struct.Config.html#method.with_variant_names
+/// [`Value::Seq`]: #variant.Seq
+/// [`Value::Map`]: #variant.Map
+impl<'de> serde::Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+struct Serializer;
+
+macro_rules! serialize_primitive {
+    ($($serialize:ident: $type:ty => $variant:ident),*) => {
+        $(
+            fn $serialize(
+                self,
+                v: $type,
+            ) -> Result<Value> {
+                Ok(Value::$variant(v.into()))
+            }
+        )*
+    };
+}
+
+impl serde::Serializer for Serializer {
+    type Error = Error;
+    type Ok = Value;
+    type SerializeMap = SerializeMap;
+    type SerializeSeq = SerializeValues;
+    type SerializeStruct = SerializeStruct;
+    type SerializeStructVariant = SerializeStruct;
+    type SerializeTuple = SerializeValues;
+    type SerializeTupleStruct = SerializeValues;
+    type SerializeTupleVariant = SerializeValues;
+
+    serialize_primitive!(
+        serialize_bool: bool => Bool,
+        serialize_i8: i8 => I8,
+        serialize_i16: i16 => I16,
+        serialize_i32: i32 => I32,
+        serialize_i64: i64 => I64,
+        serialize_i128: i128 => I128,
+        serialize_u8: u8 => U8,
+        serialize_u16: u16 => U16,
+        serialize_u32: u32 => U32,
+        serialize_u64: u64 => U64,
+        serialize_u128: u128 => U128,
+        serialize_char: char => Char,
+        serialize_str: &str => Str,
+        serialize_bytes: &[u8] => Bytes
+    );
+
+    fn serialize_f32(
+        self,
+        v: f32,
+    ) -> Result<Value> {
+        if cfg!(feature = "float") {
+            Ok(Value::F32(v))
+        } else {
+            Err(Error::FloatUnsupported)
+        }
+    }
+
+    fn serialize_f64(
+        self,
+        v: f64,
+    ) -> Result<Value> {
+        if cfg!(feature = "float") {
+            Ok(Value::F64(v))
+        } else {
+            Err(Error::FloatUnsupported)
+        }
+    }
+
+    fn serialize_none(self) -> Result<Value> {
+        Ok(Value::Option(None))
+    }
+
+    fn serialize_some<T>(
+        self,
+        value: &T,
+    ) -> Result<Value>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        Ok(Value::Option(Some(Box::new(value.serialize(self)?))))
+    }
+
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Unit)
+    }
+
+    fn serialize_unit_struct(
+        self,
+        _name: &'static str,
+    ) -> Result<Value> {
+        Ok(Value::Unit)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        Ok(Value::Variant {
+            index: variant_index,
+            name: String::from(variant),
+            value: Box::new(Value::Unit),
+        })
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Value>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        if name == REDACTED_NAME {
+            Ok(Value::Redacted)
+        } else {
+            value.serialize(self)
+        }
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        Ok(Value::Variant {
+            index: variant_index,
+            name: String::from(variant),
+            value: Box::new(value.serialize(self)?),
+        })
+    }
+
+    fn serialize_seq(
+        self,
+        len: Option<usize>,
+    ) -> Result<Self::SerializeSeq> {
+        Ok(SerializeValues::new(len.unwrap_or(0), Value::Seq))
+    }
+
+    fn serialize_tuple(
+        self,
+        len: usize,
+    ) -> Result<Self::SerializeTuple> {
+        Ok(SerializeValues::new(len, Value::Tuple))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Ok(SerializeValues::new(len, Value::Tuple))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        let mut values = SerializeValues::new(len, Value::Tuple);
+        values.variant = Some((variant_index, variant));
+        Ok(values)
+    }
+
+    fn serialize_map(
+        self,
+        len: Option<usize>,
+    ) -> Result<Self::SerializeMap> {
+        Ok(SerializeMap {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct> {
+        Ok(SerializeStruct {
+            fields: Vec::with_capacity(len),
+            variant: None,
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(SerializeStruct {
+            fields: Vec::with_capacity(len),
+            variant: Some((variant_index, variant)),
+        })
+    }
+}
+
+fn wrap_variant(
+    variant: Option<(u32, &'static str)>,
+    value: Value,
+) -> Value {
+    match variant {
+        Some((index, name)) => Value::Variant {
+            index,
+            name: String::from(name),
+            value: Box::new(value),
+        },
+        None => value,
+    }
+}
+
+struct SerializeValues {
+    values: Vec<Value>,
+    make: fn(Vec<Value>) -> Value,
+    variant: Option<(u32, &'static str)>,
+}
+
+impl SerializeValues {
+    fn new(
+        len: usize,
+        make: fn(Vec<Value>) -> Value,
+    ) -> Self {
+        Self {
+            values: Vec::with_capacity(len),
+            make,
+            variant: None,
+        }
+    }
+
+    fn element<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.values.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Value {
+        wrap_variant(self.variant, (self.make)(self.values))
+    }
+}
+
+impl serde::ser::SerializeSeq for SerializeValues {
+    type Error = Error;
+    type Ok = Value;
+
+    fn serialize_element<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(self.finish())
+    }
+}
+
+impl serde::ser::SerializeTuple for SerializeValues {
+    type Error = Error;
+    type Ok = Value;
+
+    fn serialize_element<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(self.finish())
+    }
+}
+
+impl serde::ser::SerializeTupleStruct for SerializeValues {
+    type Error = Error;
+    type Ok = Value;
+
+    fn serialize_field<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(self.finish())
+    }
+}
+
+impl serde::ser::SerializeTupleVariant for SerializeValues {
+    type Error = Error;
+    type Ok = Value;
+
+    fn serialize_field<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(self.finish())
+    }
+}
+
+struct SerializeMap {
+    entries: Vec<(Value, Value)>,
+    key: Option<Value>,
+}
+
+impl serde::ser::SerializeMap for SerializeMap {
+    type Error = Error;
+    type Ok = Value;
+
+    fn serialize_key<T>(
+        &mut self,
+        key: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.key = Some(key.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(
+        &mut self,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        let key = self.key.take().ok_or_else(|| {
+            Error::Message(String::from("map value serialized before its key"))
+        })?;
+        self.entries.push((key, value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::Map(self.entries))
+    }
+}
+
+struct SerializeStruct {
+    fields: Vec<(String, Value)>,
+    variant: Option<(u32, &'static str)>,
+}
+
+impl SerializeStruct {
+    fn field<T>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.fields.push((String::from(key), value.serialize(Serializer)?));
+        Ok(())
+    }
+
+    fn finish(self) -> Value {
+        wrap_variant(self.variant, Value::Struct(self.fields))
+    }
+}
+
+impl serde::ser::SerializeStruct for SerializeStruct {
+    type Error = Error;
+    type Ok = Value;
+
+    fn serialize_field<T>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(self.finish())
+    }
+}
+
+impl serde::ser::SerializeStructVariant for SerializeStruct {
+    type Error = Error;
+    type Ok = Value;
+
+    fn serialize_field<T>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(self.finish())
+    }
+}
+
+// This deserializes types from the value it holds.
+struct Deserializer(Value);
+
+impl serde::de::IntoDeserializer<'_, Error> for Deserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+fn visit_values<'de, V>(
+    values: Vec<Value>,
+    visitor: V,
+) -> Result<V::Value>
+where
+    V: serde::de::Visitor<'de>,
+{
+    let mut seq = serde::de::value::SeqDeserializer::new(
+        values.into_iter().map(Deserializer),
+    );
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+fn visit_entries<'de, I, V>(
+    entries: I,
+    visitor: V,
+) -> Result<V::Value>
+where
+    I: Iterator<Item = (Value, Value)>,
+    V: serde::de::Visitor<'de>,
+{
+    let mut map = serde::de::value::MapDeserializer::new(
+        entries.map(|(key, value)| (Deserializer(key), Deserializer(value))),
+    );
+    let value = visitor.visit_map(&mut map)?;
+    map.end()?;
+    Ok(value)
+}
+
+impl<'de> serde::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+
+    fn deserialize_any<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.0 {
+            Value::Bool(value) => visitor.visit_bool(value),
+            Value::I8(value) => visitor.visit_i8(value),
+            Value::I16(value) => visitor.visit_i16(value),
+            Value::I32(value) => visitor.visit_i32(value),
+            Value::I64(value) => visitor.visit_i64(value),
+            Value::I128(value) => visitor.visit_i128(value),
+            Value::U8(value) => visitor.visit_u8(value),
+            Value::U16(value) => visitor.visit_u16(value),
+            Value::U32(value) => visitor.visit_u32(value),
+            Value::U64(value) => visitor.visit_u64(value),
+            Value::U128(value) => visitor.visit_u128(value),
+            Value::F32(value) => visitor.visit_f32(value),
+            Value::F64(value) => visitor.visit_f64(value),
+            Value::Char(value) => visitor.visit_char(value),
+            Value::Str(value) => visitor.visit_string(value),
+            Value::Bytes(value) => visitor.visit_byte_buf(value),
+            Value::Option(None) => visitor.visit_none(),
+            Value::Option(Some(value)) => {
+                visitor.visit_some(Deserializer(*value))
+            },
+            Value::Unit => visitor.visit_unit(),
+            Value::Seq(values) | Value::Tuple(values) => {
+                visit_values(values, visitor)
+            },
+            Value::Map(entries) => visit_entries(entries.into_iter(), visitor),
+            Value::Struct(fields) => visit_entries(
+                fields
+                    .into_iter()
+                    .map(|(name, value)| (Value::Str(name), value)),
+                visitor,
+            ),
+            value @ Value::Variant {
+                ..
+            } => visitor.visit_enum(Deserializer(value)),
+            Value::Redacted => {
+                Err(Error::Message(String::from(REDACTED_MESSAGE)))
+            },
+        }
+    }
+
+    fn deserialize_option<V>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        match self.0 {
+            Value::Option(None) => visitor.visit_none(),
+            Value::Option(Some(value)) => {
+                visitor.visit_some(Deserializer(*value))
+            },
+            value => visitor.visit_some(Deserializer(value)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.deserialize_any(visitor)
+    }
+}
+
+impl<'de> serde::de::EnumAccess<'de> for Deserializer {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<T>(
+        self,
+        seed: T,
+    ) -> Result<(T::Value, Self)>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        let Value::Variant {
+            index,
+            name,
+            value,
+        } = self.0
+        else {
+            return Err(Error::Message(String::from("value is not a variant")));
+        };
+        // Variants are identified by name, unless the name was not known
+        // when the value was made.
+        let variant = if name.is_empty() {
+            seed.deserialize(Deserializer(Value::U32(index)))?
+        } else {
+            seed.deserialize(Deserializer(Value::Str(name)))?
+        };
+        Ok((variant, Deserializer(*value)))
+    }
+}
+
+impl<'de> serde::de::VariantAccess<'de> for Deserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        serde::Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T>(
+        self,
+        seed: T,
+    ) -> Result<T::Value>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        serde::Deserializer::deserialize_any(self, visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        serde::Deserializer::deserialize_any(self, visitor)
+    }
+}
+
+/// This function is used to convert a value of any serializable type into
+/// a [`Value`].
+///
+/// [`Value`]: enum.Value.html
+///
+/// # Errors
+///
+/// Returns any error made by the `Serialize` implementation of the type.
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     to_value,
+///     Value,
+/// };
+///
+/// #[derive(serde::Serialize)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// let value = to_value(&Point {
+///     x: 1,
+///     y: -2,
+/// })
+/// .unwrap();
+/// assert_eq!(
+///     Value::Struct(vec![
+///         (String::from("x"), Value::I32(1)),
+///         (String::from("y"), Value::I32(-2)),
+///     ]),
+///     value
+/// );
+/// assert_eq!("{ x: 1, y: -2 }", value.to_string());
+/// ```
+pub fn to_value<T>(value: &T) -> Result<Value>
+where
+    T: ?Sized + serde::Serialize,
+{
+    value.serialize(Serializer)
+}
+
+/// This function is used to convert a [`Value`] into a value of any type
+/// which can be deserialized, such as after a value obtained from
+/// [`to_value`] has been examined or changed.  Fields of structs and
+/// variants of enums are found by their names, and a field left out of a
+/// struct is an error unless the type gives it a default.
+///
+/// [`Value`]: enum.Value.html
+/// [`to_value`]: fn.to_value.html
+///
+/// # Errors
+///
+/// Returns any error made by the `Deserialize` implementation of the type,
+/// such as if the value does not have the shape the type expects, or
+/// [`Error::Message`] if the value holds a [`Value::Redacted`].
+///
+/// [`Error::Message`]: enum.Error.html#variant.Message
+/// [`Value::Redacted`]: enum.Value.html#variant.Redacted
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_value,
+///     to_value,
+///     Value,
+/// };
+///
+/// #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// let mut value = to_value(&Point {
+///     x: 1,
+///     y: -2,
+/// })
+/// .unwrap();
+/// if let Value::Struct(fields) = &mut value {
+///     fields[1].1 = Value::I32(5);
+/// }
+/// let point: Point = from_value(value).unwrap();
+/// assert_eq!(
+///     Point {
+///         x: 1,
+///         y: 5,
+///     },
+///     point
+/// );
+/// ```
+pub fn from_value<T>(value: Value) -> Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    T::deserialize(Deserializer(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "float")]
+    use std::collections::BTreeMap;
+
+    #[cfg(feature = "float")]
+    #[derive(serde::Serialize)]
+    struct Unit;
+
+    #[cfg(feature = "float")]
+    #[derive(serde::Serialize)]
+    struct Meters(f64);
+
+    #[cfg(feature = "float")]
+    #[derive(serde::Serialize)]
+    enum Shape {
+        Empty,
+        Circle(Meters),
+        Line(i8, u16),
+        Box {
+            width: u32,
+            height: u64,
+        },
+    }
+
+    #[cfg(feature = "float")]
+    #[test]
+    fn values_of_every_kind() {
+        let mut map = BTreeMap::new();
+        map.insert('k', Some(b"ab".to_vec()));
+        map.insert('z', None);
+        let value = to_value(&(
+            (true, Unit, ()),
+            vec![
+                Shape::Empty,
+                Shape::Circle(Meters(1.5)),
+                Shape::Line(-1, 2),
+                Shape::Box {
+                    width: 3,
+                    height: 4,
+                },
+            ],
+            map,
+            ("s", -7_i64, 8_u8, 2.5_f32),
+        ));
+        assert!(value.is_ok());
+        let value = value.unwrap();
+        assert_eq!(
+            Value::Tuple(vec![
+                Value::Tuple(vec![Value::Bool(true), Value::Unit, Value::Unit]),
+                Value::Seq(vec![
+                    Value::Variant {
+                        index: 0,
+                        name: String::from("Empty"),
+                        value: Box::new(Value::Unit),
+                    },
+                    Value::Variant {
+                        index: 1,
+                        name: String::from("Circle"),
+                        value: Box::new(Value::F64(1.5)),
+                    },
+                    Value::Variant {
+                        index: 2,
+                        name: String::from("Line"),
+                        value: Box::new(Value::Tuple(vec![
+                            Value::I8(-1),
+                            Value::U16(2)
+                        ])),
+                    },
+                    Value::Variant {
+                        index: 3,
+                        name: String::from("Box"),
+                        value: Box::new(Value::Struct(vec![
+                            (String::from("width"), Value::U32(3)),
+                            (String::from("height"), Value::U64(4)),
+                        ])),
+                    },
+                ]),
+                Value::Map(vec![
+                    (
+                        Value::Char('k'),
+                        Value::Option(Some(Box::new(Value::Seq(vec![
+                            Value::U8(b'a'),
+                            Value::U8(b'b'),
+                        ]))))
+                    ),
+                    (Value::Char('z'), Value::Option(None)),
+                ]),
+                Value::Tuple(vec![
+                    Value::Str(String::from("s")),
+                    Value::I64(-7),
+                    Value::U8(8),
+                    Value::F32(2.5),
+                ]),
+            ]),
+            value
+        );
+        assert_eq!(
+            concat!(
+                "((true, (), ()), [Empty, Circle(1.5), Line(-1, 2), ",
+                "Box { width: 3, height: 4 }], ",
+                "{'k': Some([97, 98]), 'z': None}, (\"s\", -7, 8, 2.5))"
+            ),
+            value.to_string()
+        );
+    }
+
+    #[test]
+    fn bytes() {
+        struct Raw;
+        impl serde::Serialize for Raw {
+            fn serialize<S>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error>
+            where
+                S: serde::Serializer,
+            {
+                serializer.serialize_bytes(b"hi")
+            }
+        }
+        let value = to_value(&Raw).unwrap();
+        assert_eq!(Value::Bytes(b"hi".to_vec()), value);
+        assert_eq!("b\"hi\"", value.to_string());
+    }
+
+    #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    enum Command {
+        Stop,
+        Move(i16, i16),
+        Say(String),
+        Wait {
+            seconds: u32,
+            reason: Option<String>,
+        },
+    }
+
+    #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Script {
+        name: String,
+        commands: Vec<Command>,
+        labels: std::collections::BTreeMap<u8, (bool, char)>,
+        data: Vec<u8>,
+    }
+
+    fn script() -> Script {
+        let mut labels = std::collections::BTreeMap::new();
+        labels.insert(1, (true, 'a'));
+        labels.insert(9, (false, 'z'));
+        Script {
+            name: String::from("demo"),
+            commands: vec![
+                Command::Move(-3, 4),
+                Command::Say(String::from("hi")),
+                Command::Wait {
+                    seconds: 70_000,
+                    reason: None,
+                },
+                Command::Stop,
+            ],
+            labels,
+            data: vec![1, 2, 3],
+        }
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let value = to_value(&script());
+        assert!(value.is_ok());
+        let decoded = from_value::<Script>(value.unwrap());
+        assert!(decoded.is_ok());
+        assert_eq!(script(), decoded.unwrap());
+        assert!(matches!(
+            from_value::<Script>(Value::Str(String::from("demo"))),
+            Err(Error::Message(_))
+        ));
+        assert!(matches!(
+            from_value::<u8>(Value::Redacted),
+            Err(Error::Message(_))
+        ));
+    }
+
+    #[test]
+    fn values_encode_as_their_sources() {
+        let value = to_value(&script()).unwrap();
+        let encoding = crate::to_bytes(&value);
+        assert!(encoding.is_ok());
+        assert_eq!(crate::to_bytes(&script()).unwrap(), encoding.unwrap());
+        assert!(matches!(
+            crate::to_bytes(&Value::Redacted),
+            Err(Error::Message(_))
+        ));
+    }
+
+    #[test]
+    fn wide_integers() {
+        let value = to_value(&(5_u128, i128::MIN));
+        assert!(value.is_ok());
+        let value = value.unwrap();
+        assert_eq!(
+            Value::Tuple(vec![Value::U128(5), Value::I128(i128::MIN)]),
+            value
+        );
+        assert_eq!(format!("(5, {})", i128::MIN), value.to_string());
+        assert_eq!(
+            crate::to_bytes(&(5_u128, i128::MIN)).unwrap(),
+            crate::to_bytes(&value).unwrap()
+        );
+        let decoded = from_value::<(u128, i128)>(value);
+        assert!(decoded.is_ok());
+        assert_eq!((5, i128::MIN), decoded.unwrap());
+        let config = crate::Config::new().with_self_describing(true);
+        let encoding = config.to_bytes(&(u128::MAX, -1_i128)).unwrap();
+        let value: Result<Value> = config.from_bytes(&encoding);
+        assert!(value.is_ok());
+        assert_eq!(
+            Value::Seq(vec![Value::U128(u128::MAX), Value::I128(-1)]),
+            value.unwrap()
+        );
+    }
+
+    #[test]
+    fn lengths_are_not_trusted() {
+        // This declares a sequence of more elements than could ever be
+        // held, which must be refused rather than set aside room for.
+        let config = crate::Config::new().with_self_describing(true);
+        let encoding =
+            [20, 0x8F, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x7F];
+        assert!(config.from_bytes::<Value>(&encoding).is_err());
+    }
+
+    #[test]
+    fn values_from_self_describing_formats() {
+        #[derive(serde::Deserialize, serde::Serialize)]
+        enum Color {
+            Black,
+            Rgb(u8, u8, u8),
+            Named(String),
+        }
+        let value: Result<Value> =
+            crate::json::from_slice(br#"{"a": [1, -2, "x", null, true]}"#);
+        assert!(value.is_ok());
+        assert_eq!(
+            Value::Map(vec![(
+                Value::Str(String::from("a")),
+                Value::Seq(vec![
+                    Value::U64(1),
+                    Value::I64(-2),
+                    Value::Str(String::from("x")),
+                    Value::Unit,
+                    Value::Bool(true),
+                ])
+            )]),
+            value.unwrap()
+        );
+        let config = crate::Config::new().with_self_describing(true);
+        let encoding = config
+            .to_bytes(&(Some(Color::Rgb(1, 2, 3)), vec![
+                Color::Black,
+                Color::Named(String::from("red")),
+            ]))
+            .unwrap();
+        let value: Result<Value> = config.from_bytes(&encoding);
+        assert!(value.is_ok());
+        let variant = |index, value| Value::Variant {
+            index,
+            name: String::new(),
+            value: Box::new(value),
+        };
+        assert_eq!(
+            Value::Seq(vec![
+                Value::Option(Some(Box::new(variant(
+                    1,
+                    Value::Seq(vec![Value::U8(1), Value::U8(2), Value::U8(3)])
+                )))),
+                Value::Seq(vec![
+                    variant(0, Value::Unit),
+                    variant(2, Value::Str(String::from("red"))),
+                ]),
+            ]),
+            value.unwrap()
+        );
+    }
+}
@@ -0,0 +1,629 @@
+// These adapt serializers and deserializers so that the variants of an enum
+// are identified on the wire by tags chosen with
+// `#[serialization(tag = N)]`, rather than by their positions in the
+// declaration of the enum.  The code generated by the `serialization`
+// attribute macro wraps the serializer or deserializer given to the enum's
+// `Serialize` or `Deserialize` implementation in one of these, and passes it
+// on to the implementation derived by `serde`, which sees only the positions
+// of variants.  Tags given with `#[serialization(alias = N)]` are accepted
+// when decoding, in place of the tags of the variants they are given to.
+
+use serde::de::{
+    DeserializeSeed,
+    Unexpected,
+};
+use std::convert::TryFrom;
+
+/// This is a serializer which passes everything on to another serializer,
+/// except that the indices of enum variants are replaced by their tags.
+pub struct TagSerializer<S> {
+    inner: S,
+    tags: &'static [u32],
+}
+
+impl<S> TagSerializer<S> {
+    /// Return a serializer which passes everything on to the given one,
+    /// replacing the index of each variant with the tag at that index.
+    pub fn new(
+        inner: S,
+        tags: &'static [u32],
+    ) -> Self {
+        Self {
+            inner,
+            tags,
+        }
+    }
+
+    fn tag(
+        &self,
+        variant_index: u32,
+    ) -> u32 {
+        self.tags[variant_index as usize]
+    }
+}
+
+macro_rules! forward_serialize {
+    ($($method:ident($($arg:ident: $ty:ty),*) -> $ok:ty;)*) => {
+        $(
+            fn $method(
+                self,
+                $($arg: $ty),*
+            ) -> Result<$ok, S::Error> {
+                self.inner.$method($($arg),*)
+            }
+        )*
+    };
+}
+
+impl<S> serde::Serializer for TagSerializer<S>
+where
+    S: serde::Serializer,
+{
+    type Error = S::Error;
+    type Ok = S::Ok;
+    type SerializeMap = S::SerializeMap;
+    type SerializeSeq = S::SerializeSeq;
+    type SerializeStruct = S::SerializeStruct;
+    type SerializeStructVariant = S::SerializeStructVariant;
+    type SerializeTuple = S::SerializeTuple;
+    type SerializeTupleStruct = S::SerializeTupleStruct;
+    type SerializeTupleVariant = S::SerializeTupleVariant;
+
+    forward_serialize! {
+        serialize_bool(v: bool) -> S::Ok;
+        serialize_i8(v: i8) -> S::Ok;
+        serialize_i16(v: i16) -> S::Ok;
+        serialize_i32(v: i32) -> S::Ok;
+        serialize_i64(v: i64) -> S::Ok;
+        serialize_u8(v: u8) -> S::Ok;
+        serialize_u16(v: u16) -> S::Ok;
+        serialize_u32(v: u32) -> S::Ok;
+        serialize_u64(v: u64) -> S::Ok;
+        serialize_f32(v: f32) -> S::Ok;
+        serialize_f64(v: f64) -> S::Ok;
+        serialize_char(v: char) -> S::Ok;
+        serialize_str(v: &str) -> S::Ok;
+        serialize_bytes(v: &[u8]) -> S::Ok;
+        serialize_none() -> S::Ok;
+        serialize_unit() -> S::Ok;
+        serialize_unit_struct(name: &'static str) -> S::Ok;
+        serialize_seq(len: Option<usize>) -> S::SerializeSeq;
+        serialize_tuple(len: usize) -> S::SerializeTuple;
+        serialize_tuple_struct(
+            name: &'static str,
+            len: usize
+        ) -> S::SerializeTupleStruct;
+        serialize_map(len: Option<usize>) -> S::SerializeMap;
+        serialize_struct(
+            name: &'static str,
+            len: usize
+        ) -> S::SerializeStruct;
+    }
+
+    fn serialize_some<T>(
+        self,
+        value: &T,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.inner.serialize_some(value)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        self.inner.serialize_newtype_struct(name, value)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        let tag = self.tag(variant_index);
+        self.inner.serialize_unit_variant(name, tag, variant)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error>
+    where
+        T: ?Sized + serde::Serialize,
+    {
+        let tag = self.tag(variant_index);
+        self.inner.serialize_newtype_variant(name, tag, variant, value)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<S::SerializeTupleVariant, S::Error> {
+        let tag = self.tag(variant_index);
+        self.inner.serialize_tuple_variant(name, tag, variant, len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<S::SerializeStructVariant, S::Error> {
+        let tag = self.tag(variant_index);
+        self.inner.serialize_struct_variant(name, tag, variant, len)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+macro_rules! forward_deserialize {
+    ($wrap:expr; $($method:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            fn $method<V>(
+                self,
+                $($arg: $ty,)*
+                visitor: V,
+            ) -> Result<V::Value, D::Error>
+            where
+                V: serde::de::Visitor<'de>,
+            {
+                #[allow(clippy::redundant_closure_call)]
+                let visitor = ($wrap)(visitor, self.tags);
+                self.inner.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+macro_rules! impl_forward_deserializer {
+    ($ty:ident, $wrap:expr, $enum:item) => {
+        impl<'de, D> serde::Deserializer<'de> for $ty<D>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            type Error = D::Error;
+
+            forward_deserialize! {
+                $wrap;
+                deserialize_any();
+                deserialize_bool();
+                deserialize_i8();
+                deserialize_i16();
+                deserialize_i32();
+                deserialize_i64();
+                deserialize_u8();
+                deserialize_u16();
+                deserialize_u32();
+                deserialize_u64();
+                deserialize_f32();
+                deserialize_f64();
+                deserialize_char();
+                deserialize_str();
+                deserialize_string();
+                deserialize_bytes();
+                deserialize_byte_buf();
+                deserialize_option();
+                deserialize_unit();
+                deserialize_unit_struct(name: &'static str);
+                deserialize_newtype_struct(name: &'static str);
+                deserialize_seq();
+                deserialize_tuple(len: usize);
+                deserialize_tuple_struct(name: &'static str, len: usize);
+                deserialize_map();
+                deserialize_struct(
+                    name: &'static str,
+                    fields: &'static [&'static str]
+                );
+                deserialize_identifier();
+                deserialize_ignored_any();
+            }
+
+            $enum
+
+            fn is_human_readable(&self) -> bool {
+                self.inner.is_human_readable()
+            }
+        }
+    };
+}
+
+// These are the tags of the variants of an enum, in order, along with any
+// older tags which are accepted as aliases of them, paired with the indices
+// of the variants.
+#[derive(Clone, Copy)]
+struct Tags {
+    tags: &'static [u32],
+    aliases: &'static [(u32, u32)],
+}
+
+impl Tags {
+    fn index(
+        self,
+        tag: u32,
+    ) -> Option<u32> {
+        self.tags
+            .iter()
+            .position(|known| *known == tag)
+            .and_then(|index| u32::try_from(index).ok())
+            .or_else(|| {
+                self.aliases
+                    .iter()
+                    .find(|(alias, _)| *alias == tag)
+                    .map(|(_, index)| *index)
+            })
+    }
+}
+
+/// This is a deserializer which passes everything on to another
+/// deserializer, except that the tags of enum variants (or their aliases)
+/// are replaced by their indices.
+pub struct TagDeserializer<D> {
+    inner: D,
+    tags: Tags,
+}
+
+impl<D> TagDeserializer<D> {
+    /// Return a deserializer which passes everything on to the given one,
+    /// replacing the tag of each variant with its index in the given tags,
+    /// and each of the given aliases with the index paired with it.
+    pub fn new(
+        inner: D,
+        tags: &'static [u32],
+        aliases: &'static [(u32, u32)],
+    ) -> Self {
+        Self {
+            inner,
+            tags: Tags {
+                tags,
+                aliases,
+            },
+        }
+    }
+}
+
+impl_forward_deserializer!(
+    TagDeserializer,
+    |visitor, _| visitor,
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, D::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.inner.deserialize_enum(name, variants, EnumVisitor {
+            inner: visitor,
+            tags: self.tags,
+        })
+    }
+);
+
+// This passes on the enum given to a visitor, so that the identifier of its
+// variant is read through an `IdDeserializer`.
+struct EnumVisitor<V> {
+    inner: V,
+    tags: Tags,
+}
+
+impl<'de, V> serde::de::Visitor<'de> for EnumVisitor<V>
+where
+    V: serde::de::Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        self.inner.expecting(f)
+    }
+
+    fn visit_enum<A>(
+        self,
+        data: A,
+    ) -> Result<V::Value, A::Error>
+    where
+        A: serde::de::EnumAccess<'de>,
+    {
+        self.inner.visit_enum(EnumAccess {
+            inner: data,
+            tags: self.tags,
+        })
+    }
+}
+
+struct EnumAccess<A> {
+    inner: A,
+    tags: Tags,
+}
+
+impl<'de, A> serde::de::EnumAccess<'de> for EnumAccess<A>
+where
+    A: serde::de::EnumAccess<'de>,
+{
+    type Error = A::Error;
+    type Variant = A::Variant;
+
+    fn variant_seed<T>(
+        self,
+        seed: T,
+    ) -> Result<(T::Value, A::Variant), A::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.inner.variant_seed(IdSeed {
+            inner: seed,
+            tags: self.tags,
+        })
+    }
+}
+
+struct IdSeed<T> {
+    inner: T,
+    tags: Tags,
+}
+
+impl<'de, T> DeserializeSeed<'de> for IdSeed<T>
+where
+    T: DeserializeSeed<'de>,
+{
+    type Value = T::Value;
+
+    fn deserialize<D>(
+        self,
+        deserializer: D,
+    ) -> Result<T::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        self.inner.deserialize(IdDeserializer {
+            inner: deserializer,
+            tags: self.tags,
+        })
+    }
+}
+
+// This reads the identifier of a variant, replacing its tag with its index.
+struct IdDeserializer<D> {
+    inner: D,
+    tags: Tags,
+}
+
+impl_forward_deserializer!(
+    IdDeserializer,
+    |visitor, tags| IdVisitor {
+        inner: visitor,
+        tags,
+    },
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, D::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        self.inner.deserialize_enum(name, variants, IdVisitor {
+            inner: visitor,
+            tags: self.tags,
+        })
+    }
+);
+
+struct IdVisitor<V> {
+    inner: V,
+    tags: Tags,
+}
+
+impl<'de, V> serde::de::Visitor<'de> for IdVisitor<V>
+where
+    V: serde::de::Visitor<'de>,
+{
+    type Value = V::Value;
+
+    fn expecting(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        self.inner.expecting(f)
+    }
+
+    fn visit_u64<E>(
+        self,
+        v: u64,
+    ) -> Result<V::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        let index = u32::try_from(v)
+            .ok()
+            .and_then(|tag| self.tags.index(tag))
+            .ok_or_else(|| {
+                E::invalid_value(Unexpected::Unsigned(v), &"variant tag")
+            })?;
+        self.inner.visit_u64(u64::from(index))
+    }
+
+    // Tags past the number of variants of the enum are given this way by
+    // the deserializer of this crate, if the contents of the variant could
+    // not be skipped were it unknown.
+    fn visit_u128<E>(
+        self,
+        v: u128,
+    ) -> Result<V::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        let v = u64::try_from(v).map_err(|_| {
+            E::invalid_value(Unexpected::Other("u128"), &"variant tag")
+        })?;
+        self.visit_u64(v)
+    }
+
+    fn visit_str<E>(
+        self,
+        v: &str,
+    ) -> Result<V::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.inner.visit_str(v)
+    }
+
+    fn visit_borrowed_str<E>(
+        self,
+        v: &'de str,
+    ) -> Result<V::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.inner.visit_borrowed_str(v)
+    }
+
+    fn visit_bytes<E>(
+        self,
+        v: &[u8],
+    ) -> Result<V::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.inner.visit_bytes(v)
+    }
+
+    fn visit_borrowed_bytes<E>(
+        self,
+        v: &'de [u8],
+    ) -> Result<V::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        self.inner.visit_borrowed_bytes(v)
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use crate::{
+        from_bytes,
+        serialization,
+        to_bytes,
+        Error,
+    };
+
+    #[serialization]
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    enum Command {
+        #[serialization(tag = 7)]
+        Stop,
+        Go(u8),
+        #[serialization(tag = 2)]
+        Turn {
+            #[serialization(zigzag)]
+            degrees: i16,
+        },
+        Say(String, bool),
+    }
+
+    #[test]
+    fn variants_are_encoded_by_tag() {
+        for (command, expected) in &[
+            (Command::Stop, &[7][..]),
+            (Command::Go(1), &[8, 1][..]),
+            (
+                Command::Turn {
+                    degrees: -1,
+                },
+                &[2, 1][..],
+            ),
+            (Command::Say(String::from("a"), true), &[3, 1, b'a', 1][..]),
+        ] {
+            let encoding = to_bytes(command);
+            assert!(encoding.is_ok());
+            let encoding = encoding.unwrap();
+            assert_eq!(*expected, encoding);
+            let decoded: crate::Result<Command> = from_bytes(&encoding);
+            assert!(decoded.is_ok());
+            assert_eq!(*command, decoded.unwrap());
+        }
+        assert!(matches!(
+            from_bytes::<Command>(&[0]),
+            Err(Error::Message(message)) if message.contains("variant tag")
+        ));
+    }
+
+    // This is `Command` with `Turn` removed, and the others reordered.
+    #[serialization]
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    enum Reordered {
+        #[serialization(tag = 3)]
+        Say(String, bool),
+        #[serialization(tag = 8)]
+        Go(u8),
+        #[serialization(tag = 7)]
+        Stop,
+    }
+
+    #[test]
+    fn variants_can_be_reordered_and_removed() {
+        let encoding = to_bytes(&Command::Go(9)).unwrap();
+        assert_eq!(Reordered::Go(9), from_bytes(&encoding).unwrap());
+        let encoding = to_bytes(&Reordered::Stop).unwrap();
+        assert_eq!(Command::Stop, from_bytes(&encoding).unwrap());
+        let encoding = to_bytes(&Command::Turn {
+            degrees: 1,
+        })
+        .unwrap();
+        assert!(from_bytes::<Reordered>(&encoding).is_err());
+    }
+
+    // This is `Command` after `Go` and `Say` have been merged into `Act`,
+    // and `Stop` has been renumbered.  It uses the older name of the
+    // attribute, which is still accepted.
+    #[crate::serial]
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    enum Merged {
+        #[serial(tag = 9, alias = 7)]
+        Stop,
+        #[serial(tag = 3, alias = 8)]
+        Act(String, bool),
+    }
+
+    #[test]
+    fn aliases_are_decoded() {
+        let encoding = to_bytes(&Command::Stop).unwrap();
+        assert_eq!(Merged::Stop, from_bytes(&encoding).unwrap());
+        let encoding = to_bytes(&Command::Say(String::from("a"), true));
+        assert_eq!(
+            Merged::Act(String::from("a"), true),
+            from_bytes(&encoding.unwrap()).unwrap()
+        );
+        assert_eq!(&[9][..], to_bytes(&Merged::Stop).unwrap());
+        assert!(from_bytes::<Merged>(&[2, 1]).is_err());
+    }
+
+    #[test]
+    fn other_formats_see_tags() {
+        let value = crate::to_value(&vec![Command::Go(5)]);
+        assert!(value.is_ok());
+        assert_eq!("[Go(5)]", value.unwrap().to_string());
+    }
+}
@@ -0,0 +1,129 @@
+use super::{
+    from_bytes,
+    from_bytes_partial,
+    to_bytes,
+    Error,
+    Result,
+};
+
+/// This is the magic number placed in front of values encoded by
+/// [`to_bytes_versioned`], which identifies the bytes as such an encoding.
+///
+/// [`to_bytes_versioned`]: fn.to_bytes_versioned.html
+pub const VERSIONED_MAGIC: [u8; 4] = *b"SRLV";
+
+/// This function is used to encode a value preceded by [`VERSIONED_MAGIC`]
+/// and then the given version, as a varint, so that a reader can tell
+/// which version of the type of the value was encoded before decoding it.
+/// The version belongs to the caller, such as the version of a file
+/// format or schema, and is unrelated to the version of the encoding
+/// selected by a [`Config`].
+///
+/// [`VERSIONED_MAGIC`]: constant.VERSIONED_MAGIC.html
+/// [`Config`]: struct.Config.html
+///
+/// # Errors
+///
+/// Any error from [`to_bytes`] is returned.
+///
+/// [`to_bytes`]: fn.to_bytes.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     from_bytes_versioned,
+///     to_bytes_versioned,
+/// };
+///
+/// let encoding = to_bytes_versioned(&("settings", 7_u8), 3).unwrap();
+/// let (version, settings): (u32, (&str, u8)) =
+///     from_bytes_versioned(&encoding).unwrap();
+/// assert_eq!(3, version);
+/// assert_eq!(("settings", 7), settings);
+/// ```
+pub fn to_bytes_versioned<T>(
+    value: &T,
+    version: u32,
+) -> Result<Vec<u8>>
+where
+    T: serde::Serialize,
+{
+    let mut buffer = VERSIONED_MAGIC.to_vec();
+    buffer.extend(to_bytes(&version)?);
+    buffer.extend(to_bytes(value)?);
+    Ok(buffer)
+}
+
+/// This function is used to decode a value encoded by
+/// [`to_bytes_versioned`], after checking the magic number in front of it,
+/// returning the version which was encoded with it along with the value.
+///
+/// [`to_bytes_versioned`]: fn.to_bytes_versioned.html
+///
+/// # Errors
+///
+/// This function returns [`Error::MagicMismatch`] if the input does not
+/// start with [`VERSIONED_MAGIC`], [`Error::ValueTruncated`] if the input
+/// ends before the version does, and otherwise any of the errors returned
+/// by [`from_bytes`].
+///
+/// [`Error::MagicMismatch`]: enum.Error.html#variant.MagicMismatch
+/// [`VERSIONED_MAGIC`]: constant.VERSIONED_MAGIC.html
+/// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+/// [`from_bytes`]: fn.from_bytes.html
+pub fn from_bytes_versioned<'de, T>(bytes: &'de [u8]) -> Result<(u32, T)>
+where
+    T: serde::Deserialize<'de>,
+{
+    let (version, bytes) = strip_version(bytes)?;
+    Ok((version, from_bytes(bytes)?))
+}
+
+// This checks the magic number in front of a versioned encoding, returning
+// the version which follows it and the encoding of the value after that.
+pub(crate) fn strip_version(bytes: &[u8]) -> Result<(u32, &[u8])> {
+    if !bytes.starts_with(&VERSIONED_MAGIC) {
+        return Err(if VERSIONED_MAGIC.starts_with(bytes) {
+            Error::ValueTruncated
+        } else {
+            Error::MagicMismatch
+        });
+    }
+    let bytes = &bytes[VERSIONED_MAGIC.len()..];
+    let (version, len) = from_bytes_partial(bytes)?;
+    Ok((version, &bytes[len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip_with_version() {
+        let encoding = to_bytes_versioned(&"porch", 300);
+        assert!(encoding.is_ok());
+        let encoding = encoding.unwrap();
+        assert_eq!(&b"SRLV\x82\x2C\x05porch"[..], encoding);
+        let decoded: Result<(u32, String)> = from_bytes_versioned(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!((300, String::from("porch")), decoded.unwrap());
+    }
+
+    #[test]
+    fn bad_headers_are_detected() {
+        assert!(matches!(
+            from_bytes_versioned::<u8>(b"SRLX\x01\x02"),
+            Err(Error::MagicMismatch)
+        ));
+        assert!(matches!(
+            from_bytes_versioned::<u8>(b"SR"),
+            Err(Error::ValueTruncated)
+        ));
+        assert!(matches!(
+            from_bytes_versioned::<u8>(b"SRLV\x82"),
+            Err(Error::ValueTruncated)
+        ));
+    }
+}
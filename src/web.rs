@@ -0,0 +1,276 @@
+use super::{
+    from_bytes,
+    to_bytes,
+};
+
+/// This is the media type given in the `Content-Type` header of HTTP
+/// requests and responses whose bodies are carried by [`Serial`].
+///
+/// [`Serial`]: struct.Serial.html
+pub const SERIAL_CONTENT_TYPE: &str = "application/x-serialization";
+
+/// This wraps a value carried in the body of an HTTP request or response,
+/// encoded in the binary format of this crate rather than as JSON.  With the
+/// `axum` or `actix-web` feature enabled, it is an extractor and a
+/// responder for that framework, used just like the framework's own `Json`,
+/// so that a handler is switched over to this format by changing the
+/// wrapper around its body.
+///
+/// As an extractor, it refuses requests whose `Content-Type` is not
+/// [`SERIAL_CONTENT_TYPE`] with status 415, and bodies which cannot be
+/// decoded with status 400.  As a responder, it sets the `Content-Type` to
+/// [`SERIAL_CONTENT_TYPE`], or responds with status 500 if the value cannot
+/// be encoded.
+///
+/// [`SERIAL_CONTENT_TYPE`]: constant.SERIAL_CONTENT_TYPE.html
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Serial<T>(pub T);
+
+// Determine whether or not the given `Content-Type` header value is the
+// one for this format, ignoring any parameters.
+fn is_serial_content_type(content_type: Option<&[u8]>) -> bool {
+    content_type
+        .and_then(|content_type| content_type.split(|b| *b == b';').next())
+        .is_some_and(|media_type| {
+            media_type.trim_ascii().eq_ignore_ascii_case(
+                SERIAL_CONTENT_TYPE.as_bytes(),
+            )
+        })
+}
+
+#[cfg(feature = "axum")]
+impl<S, T> axum_core::extract::FromRequest<S> for Serial<T>
+where
+    S: Send + Sync,
+    T: serde::de::DeserializeOwned,
+{
+    type Rejection = axum_core::response::Response;
+
+    async fn from_request(
+        req: axum_core::extract::Request,
+        state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        use axum_core::response::IntoResponse;
+        if !is_serial_content_type(
+            req.headers()
+                .get(http::header::CONTENT_TYPE)
+                .map(http::HeaderValue::as_bytes),
+        ) {
+            return Err(
+                http::StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response()
+            );
+        }
+        let body = bytes::Bytes::from_request(req, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        from_bytes(&body).map(Serial).map_err(|error| {
+            (http::StatusCode::BAD_REQUEST, error.to_string()).into_response()
+        })
+    }
+}
+
+#[cfg(feature = "axum")]
+impl<T> axum_core::response::IntoResponse for Serial<T>
+where
+    T: serde::Serialize,
+{
+    fn into_response(self) -> axum_core::response::Response {
+        match to_bytes(&self.0) {
+            Ok(body) => (
+                [(
+                    http::header::CONTENT_TYPE,
+                    http::HeaderValue::from_static(SERIAL_CONTENT_TYPE),
+                )],
+                body,
+            )
+                .into_response(),
+            Err(error) => {
+                (http::StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
+                    .into_response()
+            },
+        }
+    }
+}
+
+#[cfg(feature = "actix-web")]
+impl<T> actix_web::FromRequest for Serial<T>
+where
+    T: serde::de::DeserializeOwned + 'static,
+{
+    type Error = actix_web::Error;
+    type Future = std::pin::Pin<
+        Box<dyn std::future::Future<Output = Result<Self, Self::Error>>>,
+    >;
+
+    fn from_request(
+        req: &actix_web::HttpRequest,
+        payload: &mut actix_web::dev::Payload,
+    ) -> Self::Future {
+        if !is_serial_content_type(
+            req.headers()
+                .get(actix_web::http::header::CONTENT_TYPE)
+                .map(actix_web::http::header::HeaderValue::as_bytes),
+        ) {
+            return Box::pin(std::future::ready(Err(
+                actix_web::error::ErrorUnsupportedMediaType(
+                    "expected serialization content type",
+                ),
+            )));
+        }
+        let body = actix_web::web::Bytes::from_request(req, payload);
+        Box::pin(async move {
+            from_bytes(&body.await?)
+                .map(Serial)
+                .map_err(actix_web::error::ErrorBadRequest)
+        })
+    }
+}
+
+#[cfg(feature = "actix-web")]
+impl<T> actix_web::Responder for Serial<T>
+where
+    T: serde::Serialize,
+{
+    type Body = actix_web::body::BoxBody;
+
+    fn respond_to(
+        self,
+        _req: &actix_web::HttpRequest,
+    ) -> actix_web::HttpResponse {
+        match to_bytes(&self.0) {
+            Ok(body) => actix_web::HttpResponse::Ok()
+                .content_type(SERIAL_CONTENT_TYPE)
+                .body(body),
+            Err(error) => actix_web::HttpResponse::InternalServerError()
+                .body(error.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_types() {
+        assert!(is_serial_content_type(Some(
+            b"application/x-serialization"
+        )));
+        assert!(is_serial_content_type(Some(
+            b"Application/X-Serialization; charset=binary"
+        )));
+        assert!(!is_serial_content_type(Some(b"application/json")));
+        assert!(!is_serial_content_type(None));
+    }
+
+    #[cfg(feature = "axum")]
+    #[tokio::test]
+    async fn axum_extractor_and_responder() {
+        use axum_core::{
+            extract::{
+                FromRequest,
+                Request,
+            },
+            response::IntoResponse,
+        };
+        let value = (String::from("hello"), 1000_u16);
+        let response = Serial(value.clone()).into_response();
+        assert_eq!(http::StatusCode::OK, response.status());
+        assert_eq!(
+            Some(SERIAL_CONTENT_TYPE.as_bytes()),
+            response
+                .headers()
+                .get(http::header::CONTENT_TYPE)
+                .map(http::HeaderValue::as_bytes)
+        );
+        let request = Request::builder()
+            .header(http::header::CONTENT_TYPE, SERIAL_CONTENT_TYPE)
+            .body(response.into_body())
+            .unwrap();
+        let extracted =
+            Serial::<(String, u16)>::from_request(request, &()).await;
+        assert!(extracted.is_ok());
+        assert_eq!(value, extracted.unwrap().0);
+        let request = Request::builder()
+            .header(http::header::CONTENT_TYPE, "application/json")
+            .body(axum_core::body::Body::from(to_bytes(&value).unwrap()))
+            .unwrap();
+        let rejection = Serial::<(String, u16)>::from_request(request, &())
+            .await
+            .map(|_| ());
+        assert_eq!(
+            Err(http::StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            rejection.map_err(|response| response.status())
+        );
+        let request = Request::builder()
+            .header(http::header::CONTENT_TYPE, SERIAL_CONTENT_TYPE)
+            .body(axum_core::body::Body::from(vec![0x05, b'h']))
+            .unwrap();
+        let rejection = Serial::<(String, u16)>::from_request(request, &())
+            .await
+            .map(|_| ());
+        assert_eq!(
+            Err(http::StatusCode::BAD_REQUEST),
+            rejection.map_err(|response| response.status())
+        );
+    }
+
+    #[cfg(feature = "actix-web")]
+    #[tokio::test]
+    async fn actix_web_extractor_and_responder() {
+        use actix_web::{
+            http::{
+                header::CONTENT_TYPE,
+                StatusCode,
+            },
+            test::TestRequest,
+            FromRequest,
+            Responder,
+        };
+        let value = (String::from("hello"), 1000_u16);
+        let request = TestRequest::default().to_http_request();
+        let response = Serial(value.clone()).respond_to(&request);
+        assert_eq!(StatusCode::OK, response.status());
+        assert_eq!(
+            Some(SERIAL_CONTENT_TYPE.as_bytes()),
+            response
+                .headers()
+                .get(CONTENT_TYPE)
+                .map(actix_web::http::header::HeaderValue::as_bytes)
+        );
+        let body = actix_web::body::to_bytes(response.into_body()).await;
+        assert!(body.is_ok());
+        let (request, mut payload) = TestRequest::default()
+            .insert_header((CONTENT_TYPE, SERIAL_CONTENT_TYPE))
+            .set_payload(body.unwrap())
+            .to_http_parts();
+        let extracted =
+            Serial::<(String, u16)>::from_request(&request, &mut payload).await;
+        assert!(extracted.is_ok());
+        assert_eq!(value, extracted.unwrap().0);
+        let (request, mut payload) = TestRequest::default()
+            .insert_header((CONTENT_TYPE, "application/json"))
+            .set_payload(to_bytes(&value).unwrap())
+            .to_http_parts();
+        let rejection =
+            Serial::<(String, u16)>::from_request(&request, &mut payload).await;
+        assert_eq!(
+            Some(StatusCode::UNSUPPORTED_MEDIA_TYPE),
+            rejection
+                .err()
+                .map(|error| error.as_response_error().status_code())
+        );
+        let (request, mut payload) = TestRequest::default()
+            .insert_header((CONTENT_TYPE, SERIAL_CONTENT_TYPE))
+            .set_payload(vec![0x05, b'h'])
+            .to_http_parts();
+        let rejection =
+            Serial::<(String, u16)>::from_request(&request, &mut payload).await;
+        assert_eq!(
+            Some(StatusCode::BAD_REQUEST),
+            rejection
+                .err()
+                .map(|error| error.as_response_error().status_code())
+        );
+    }
+}
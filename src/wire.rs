@@ -0,0 +1,561 @@
+use super::{
+    Config,
+    Deserializer,
+    Output,
+    Result,
+    Serializer,
+    Varint,
+};
+use serde::Serializer as _;
+
+/// This writes the primitive parts of the encoding one at a time, for code
+/// which encodes values by hand rather than through `serde`, such as
+/// performance-critical paths or shims for other languages.  What it writes
+/// is the same as what the serializer writes for the same values, so the
+/// result can be decoded with [`from_bytes`] or a [`WireReader`].
+///
+/// Structs and tuples are encoded as their fields in order, with nothing
+/// around them.  Sequences and maps are encoded as their length, written
+/// with [`write_len`], followed by their elements, or keys and values.
+/// Enums are encoded as the index of the variant, written with
+/// [`write_variant_index`], followed by the fields of the variant.  The
+/// narrower integer types are encoded the same way as `u64` and `i64`.
+///
+/// [`from_bytes`]: fn.from_bytes.html
+/// [`WireReader`]: struct.WireReader.html
+/// [`write_len`]: #method.write_len
+/// [`write_variant_index`]: #method.write_variant_index
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     to_bytes,
+///     WireWriter,
+/// };
+///
+/// #[derive(serde::Serialize)]
+/// struct Reading {
+///     sensor: &'static str,
+///     samples: Vec<u16>,
+///     note: Option<i32>,
+/// }
+///
+/// let mut buffer = Vec::new();
+/// let mut writer = WireWriter::new(&mut buffer);
+/// writer.write_str("north").unwrap();
+/// writer.write_len(2).unwrap();
+/// writer.write_u64(500).unwrap();
+/// writer.write_u64(505).unwrap();
+/// writer.write_option_tag(true).unwrap();
+/// writer.write_i64(-3).unwrap();
+/// assert_eq!(
+///     to_bytes(&Reading {
+///         sensor: "north",
+///         samples: vec![500, 505],
+///         note: Some(-3),
+///     })
+///     .unwrap(),
+///     buffer
+/// );
+/// ```
+pub struct WireWriter<'w, O = Vec<u8>>
+where
+    O: ?Sized,
+{
+    serializer: Serializer<'w, Varint, O>,
+}
+
+impl<'w, O> WireWriter<'w, O>
+where
+    O: ?Sized + Output,
+{
+    /// Return a new writer which writes to the given output.
+    #[must_use]
+    pub fn new(output: &'w mut O) -> Self {
+        Self {
+            serializer: Serializer::with_output(output, Config::new(), Varint),
+        }
+    }
+
+    /// Write a `bool`, as one byte which is 1 for `true` or 0 for `false`.
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing to the output is returned.
+    pub fn write_bool(
+        &mut self,
+        value: bool,
+    ) -> Result<()> {
+        (&mut self.serializer).serialize_bool(value)
+    }
+
+    /// Write a byte string, as its length followed by its bytes.
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing to the output is returned.
+    pub fn write_bytes(
+        &mut self,
+        value: &[u8],
+    ) -> Result<()> {
+        (&mut self.serializer).serialize_bytes(value)
+    }
+
+    /// Write an `f32`, as the four bytes of its bits, most significant
+    /// first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::FloatUnsupported`] if the crate was built without
+    /// the `float` feature, and otherwise any error from writing to the
+    /// output.
+    ///
+    /// [`Error::FloatUnsupported`]: enum.Error.html#variant.FloatUnsupported
+    pub fn write_f32(
+        &mut self,
+        value: f32,
+    ) -> Result<()> {
+        (&mut self.serializer).serialize_f32(value)
+    }
+
+    /// Write an `f64`, as the eight bytes of its bits, most significant
+    /// first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::FloatUnsupported`] if the crate was built without
+    /// the `float` feature, and otherwise any error from writing to the
+    /// output.
+    ///
+    /// [`Error::FloatUnsupported`]: enum.Error.html#variant.FloatUnsupported
+    pub fn write_f64(
+        &mut self,
+        value: f64,
+    ) -> Result<()> {
+        (&mut self.serializer).serialize_f64(value)
+    }
+
+    /// Write an `i8`, as one byte.
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing to the output is returned.
+    pub fn write_i8(
+        &mut self,
+        value: i8,
+    ) -> Result<()> {
+        (&mut self.serializer).serialize_i8(value)
+    }
+
+    /// Write a signed integer of any width above one byte, as a varint.
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing to the output is returned.
+    pub fn write_i64(
+        &mut self,
+        value: i64,
+    ) -> Result<()> {
+        (&mut self.serializer).serialize_i64(value)
+    }
+
+    /// Write the number of elements of a sequence or entries of a map which
+    /// are written next.
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing to the output is returned.
+    pub fn write_len(
+        &mut self,
+        len: usize,
+    ) -> Result<()> {
+        (&mut self.serializer).serialize_u64(len as u64)
+    }
+
+    /// Write the tag of an `Option`, which is followed by the value inside
+    /// it if there is one.
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing to the output is returned.
+    pub fn write_option_tag(
+        &mut self,
+        is_some: bool,
+    ) -> Result<()> {
+        (&mut self.serializer).serialize_u8(u8::from(is_some))
+    }
+
+    /// Write a string, as the length of its UTF-8 encoding followed by the
+    /// encoding.
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing to the output is returned.
+    pub fn write_str(
+        &mut self,
+        value: &str,
+    ) -> Result<()> {
+        (&mut self.serializer).serialize_str(value)
+    }
+
+    /// Write a `u8`, as one byte.
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing to the output is returned.
+    pub fn write_u8(
+        &mut self,
+        value: u8,
+    ) -> Result<()> {
+        (&mut self.serializer).serialize_u8(value)
+    }
+
+    /// Write an unsigned integer of any width above one byte, as a varint.
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing to the output is returned.
+    pub fn write_u64(
+        &mut self,
+        value: u64,
+    ) -> Result<()> {
+        (&mut self.serializer).serialize_u64(value)
+    }
+
+    /// Write the index of the variant of an enum, which is followed by the
+    /// fields of the variant.
+    ///
+    /// # Errors
+    ///
+    /// Any error from writing to the output is returned.
+    pub fn write_variant_index(
+        &mut self,
+        index: u32,
+    ) -> Result<()> {
+        (&mut self.serializer).serialize_u32(index)
+    }
+}
+
+/// This reads the primitive parts of the encoding one at a time, for code
+/// which decodes values by hand rather than through `serde`.  It reads the
+/// encodings written by a [`WireWriter`] or the serializer, with the same
+/// checks as the deserializer.  Strings and byte strings are borrowed from
+/// the input rather than copied.
+///
+/// [`WireWriter`]: struct.WireWriter.html
+///
+/// # Examples
+///
+/// ```rust
+/// # extern crate serialization;
+/// use serialization::{
+///     to_bytes,
+///     WireReader,
+/// };
+///
+/// let encoding = to_bytes(&("north", vec![500_u16, 505])).unwrap();
+/// let mut reader = WireReader::new(&encoding);
+/// assert_eq!("north", reader.read_str().unwrap());
+/// let len = reader.read_len().unwrap();
+/// let samples: Vec<u16> =
+///     (0..len).map(|_| reader.read_u16().unwrap()).collect();
+/// assert_eq!(vec![500, 505], samples);
+/// assert_eq!(0, reader.remaining());
+/// ```
+pub struct WireReader<'de> {
+    deserializer: Deserializer<'de>,
+}
+
+impl<'de> WireReader<'de> {
+    /// Return a new reader which reads from the given bytes.
+    #[must_use]
+    pub fn new(input: &'de [u8]) -> Self {
+        Self {
+            deserializer: Deserializer::new(input),
+        }
+    }
+
+    /// Return the number of bytes read so far.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.deserializer.offset()
+    }
+
+    /// Read a `bool`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    pub fn read_bool(&mut self) -> Result<bool> {
+        self.deserializer.parse_bool()
+    }
+
+    /// Read a byte string, borrowed from the input.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first, or
+    /// [`Error::IntegerOverflow`] if the length is too large.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+    pub fn read_bytes(&mut self) -> Result<&'de [u8]> {
+        self.deserializer.parse_bytes()
+    }
+
+    /// Read an `f32`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first, or
+    /// [`Error::FloatUnsupported`] if the crate was built without the
+    /// `float` feature.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::FloatUnsupported`]: enum.Error.html#variant.FloatUnsupported
+    pub fn read_f32(&mut self) -> Result<f32> {
+        self.deserializer.parse_f32()
+    }
+
+    /// Read an `f64`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first, or
+    /// [`Error::FloatUnsupported`] if the crate was built without the
+    /// `float` feature.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::FloatUnsupported`]: enum.Error.html#variant.FloatUnsupported
+    pub fn read_f64(&mut self) -> Result<f64> {
+        self.deserializer.parse_f64()
+    }
+
+    /// Read an `i16`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first, or
+    /// [`Error::IntegerOverflow`] if the integer does not fit.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+    pub fn read_i16(&mut self) -> Result<i16> {
+        self.deserializer.parse_i16()
+    }
+
+    /// Read an `i32`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first, or
+    /// [`Error::IntegerOverflow`] if the integer does not fit.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+    pub fn read_i32(&mut self) -> Result<i32> {
+        self.deserializer.parse_i32()
+    }
+
+    /// Read an `i64`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first, or
+    /// [`Error::IntegerOverflow`] if the integer does not fit.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+    pub fn read_i64(&mut self) -> Result<i64> {
+        self.deserializer.parse_i64(None)
+    }
+
+    /// Read an `i8`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    pub fn read_i8(&mut self) -> Result<i8> {
+        self.deserializer.parse_i8()
+    }
+
+    /// Read the number of elements of a sequence or entries of a map which
+    /// follow.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first, or
+    /// [`Error::IntegerOverflow`] if the length is too large.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+    pub fn read_len(&mut self) -> Result<usize> {
+        self.deserializer.parse_usize()
+    }
+
+    /// Read the tag of an `Option`, returning whether or not a value
+    /// follows.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    pub fn read_option_tag(&mut self) -> Result<bool> {
+        Ok(self.deserializer.parse_option()?.is_some())
+    }
+
+    /// Read a string, borrowed from the input.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first,
+    /// [`Error::IntegerOverflow`] if the length is too large, or
+    /// [`Error::InvalidUtf8`] if the string is not valid UTF-8.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+    /// [`Error::InvalidUtf8`]: enum.Error.html#variant.InvalidUtf8
+    pub fn read_str(&mut self) -> Result<&'de str> {
+        self.deserializer.parse_str()
+    }
+
+    /// Read a `u16`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first, or
+    /// [`Error::IntegerOverflow`] if the integer does not fit.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+    pub fn read_u16(&mut self) -> Result<u16> {
+        self.deserializer.parse_u16()
+    }
+
+    /// Read a `u32`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first, or
+    /// [`Error::IntegerOverflow`] if the integer does not fit.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+    pub fn read_u32(&mut self) -> Result<u32> {
+        self.deserializer.parse_u32()
+    }
+
+    /// Read a `u64`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first, or
+    /// [`Error::IntegerOverflow`] if the integer does not fit.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+    pub fn read_u64(&mut self) -> Result<u64> {
+        self.deserializer.parse_u64(None)
+    }
+
+    /// Read a `u8`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    pub fn read_u8(&mut self) -> Result<u8> {
+        self.deserializer.parse_u8()
+    }
+
+    /// Read the index of the variant of an enum, which is followed by the
+    /// fields of the variant.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::ValueTruncated`] if the input ends first, or
+    /// [`Error::IntegerOverflow`] if the index is too large.
+    ///
+    /// [`Error::ValueTruncated`]: enum.Error.html#variant.ValueTruncated
+    /// [`Error::IntegerOverflow`]: enum.Error.html#variant.IntegerOverflow
+    pub fn read_variant_index(&mut self) -> Result<u32> {
+        self.deserializer.parse_u32()
+    }
+
+    /// Return the number of bytes left to read.
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.deserializer.remaining()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        from_bytes,
+        to_bytes,
+        Error,
+    };
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    enum Shape {
+        Point,
+        Circle {
+            radius: u32,
+        },
+    }
+
+    type Mixed<'a> =
+        (bool, i8, Vec<Shape>, Option<String>, Option<u64>, &'a [u8]);
+
+    #[test]
+    fn matches_serde_encoding() {
+        let value = (
+            true,
+            -5_i8,
+            vec![Shape::Point, Shape::Circle {
+                radius: 1000,
+            }],
+            Some(String::from("label")),
+            None::<u64>,
+            &b"\x01\x02"[..],
+        );
+        let mut buffer = Vec::new();
+        let mut writer = WireWriter::new(&mut buffer);
+        assert!(writer.write_bool(true).is_ok());
+        assert!(writer.write_i8(-5).is_ok());
+        assert!(writer.write_len(2).is_ok());
+        assert!(writer.write_variant_index(0).is_ok());
+        assert!(writer.write_variant_index(1).is_ok());
+        assert!(writer.write_u64(1000).is_ok());
+        assert!(writer.write_option_tag(true).is_ok());
+        assert!(writer.write_str("label").is_ok());
+        assert!(writer.write_option_tag(false).is_ok());
+        assert!(writer.write_bytes(&[1, 2]).is_ok());
+        assert_eq!(to_bytes(&value).unwrap(), buffer);
+        let decoded: Result<Mixed> = from_bytes(&buffer);
+        assert!(decoded.is_ok());
+        assert_eq!(value, decoded.unwrap());
+    }
+
+    #[test]
+    fn reads_with_serde_checks() {
+        let encoding = to_bytes(&(70_000_u32, "hi")).unwrap();
+        let mut reader = WireReader::new(&encoding);
+        assert!(matches!(reader.read_u16(), Err(Error::IntegerOverflow)));
+        let mut reader = WireReader::new(&encoding);
+        assert_eq!(70_000, reader.read_u32().unwrap());
+        assert_eq!(3, reader.offset());
+        assert_eq!("hi", reader.read_str().unwrap());
+        assert!(matches!(reader.read_u8(), Err(Error::ValueTruncated)));
+    }
+}
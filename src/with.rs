@@ -0,0 +1,133 @@
+//! This module provides alternative encodings of fields, for use with the
+//! `with` attribute of `serde`, such as
+//! `#[serde(with = "serialization::with::zigzag")]`.  With the `derive`
+//! feature enabled, the [`serialization`] attribute macro offers shorter
+//! names for them, such as `#[serialization(zigzag)]`.
+//!
+//! [`serialization`]: ../attr.serialization.html
+
+#[cfg(feature = "arrayvec")]
+pub mod arrayvec;
+pub mod bytes;
+pub mod fixed;
+pub mod lossy_f32;
+pub mod skip_default;
+#[cfg(feature = "smallvec")]
+pub mod smallvec;
+pub mod zigzag;
+
+pub use fixed::FixedWidth;
+pub use zigzag::ZigZag;
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use crate::{
+        from_bytes,
+        serialization,
+        to_bytes,
+        Config,
+    };
+
+    #[serialization]
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Record {
+        #[serialization(fixed)]
+        id: u32,
+        #[serialization(zigzag)]
+        #[serde(rename = "d")]
+        delta: i16,
+        #[serialization(bytes)]
+        data: Vec<u8>,
+        #[serialization(skip_default)]
+        note: String,
+    }
+
+    #[serialization]
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    enum Change {
+        Move(#[serialization(zigzag)] i32),
+        Tag {
+            #[serialization(fixed)]
+            hash: u16,
+        },
+    }
+
+    #[derive(serde::Serialize)]
+    struct ReadingV1 {
+        sensor: u8,
+    }
+
+    #[serialization]
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Reading {
+        sensor: u8,
+        #[serialization(skip)]
+        cached: Option<u8>,
+        #[serialization(since = 2, zigzag)]
+        offset: i8,
+    }
+
+    #[test]
+    fn serial_attributes_select_encodings() {
+        let record = Record {
+            id: 1,
+            delta: -1,
+            data: vec![7],
+            note: String::new(),
+        };
+        let encoding = to_bytes(&record);
+        assert!(encoding.is_ok());
+        let encoding = encoding.unwrap();
+        assert_eq!(&[0, 0, 0, 1, 0x01, 0x01, 7, 0x00][..], encoding);
+        let decoded: crate::Result<Record> = from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!(record, decoded.unwrap());
+        let changes = vec![Change::Move(-2), Change::Tag {
+            hash: 0xABCD,
+        }];
+        let encoding = to_bytes(&changes);
+        assert!(encoding.is_ok());
+        let encoding = encoding.unwrap();
+        assert_eq!(&[0x02, 0x00, 0x03, 0x01, 0xAB, 0xCD][..], encoding);
+        let decoded: crate::Result<Vec<Change>> = from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!(changes, decoded.unwrap());
+    }
+
+    #[test]
+    fn serial_attributes_skip_and_version_fields() {
+        let reading = Reading {
+            sensor: 3,
+            cached: Some(9),
+            offset: -1,
+        };
+        let encoding = to_bytes(&reading);
+        assert!(encoding.is_ok());
+        let encoding = encoding.unwrap();
+        assert_eq!(&[0x03, 0x01][..], encoding);
+        let decoded: crate::Result<Reading> = from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!(
+            Reading {
+                cached: None,
+                ..reading
+            },
+            decoded.unwrap()
+        );
+        let config = Config::new().with_missing_fields(true);
+        let old = to_bytes(&ReadingV1 {
+            sensor: 3,
+        })
+        .unwrap();
+        let decoded: crate::Result<Reading> = config.from_bytes(&old);
+        assert!(decoded.is_ok());
+        assert_eq!(
+            Reading {
+                sensor: 3,
+                cached: None,
+                offset: 0,
+            },
+            decoded.unwrap()
+        );
+    }
+}
@@ -0,0 +1,171 @@
+//! This decodes a sequence straight into an [`ArrayVec`], which holds its
+//! elements inline, up to a capacity fixed by its type.  Decoding fails,
+//! before any elements are decoded, if the encoding declares more elements
+//! than the capacity, and otherwise as soon as one element too many is
+//! found, so no input can make the decoder allocate or decode more than the
+//! capacity allows.  The sequence is encoded just like a `Vec`.  This is
+//! available with the `arrayvec` feature.
+//!
+//! [`ArrayVec`]: https://docs.rs/arrayvec/0.7/arrayvec/struct.ArrayVec.html
+//!
+//! # Examples
+//!
+//! ```rust
+//! # extern crate serialization;
+//! use arrayvec::ArrayVec;
+//! use serialization::{
+//!     from_bytes,
+//!     to_bytes,
+//! };
+//!
+//! #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+//! struct Packet {
+//!     #[serde(with = "serialization::with::arrayvec")]
+//!     samples: ArrayVec<u16, 4>,
+//! }
+//!
+//! let encoding = to_bytes(&vec![1_u16, 2, 3]).unwrap();
+//! let packet: Packet = from_bytes(&encoding).unwrap();
+//! assert_eq!(&[1, 2, 3], packet.samples.as_slice());
+//! assert_eq!(encoding, to_bytes(&packet).unwrap());
+//! let encoding = to_bytes(&vec![1_u16, 2, 3, 4, 5]).unwrap();
+//! assert!(from_bytes::<Packet>(&encoding).is_err());
+//! ```
+
+use arrayvec::ArrayVec;
+use std::marker::PhantomData;
+
+/// Encode the elements of the given vector as a sequence.
+///
+/// # Errors
+///
+/// Any error from the serializer is returned.
+pub fn serialize<T, S, const CAP: usize>(
+    values: &ArrayVec<T, CAP>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    T: serde::Serialize,
+    S: serde::Serializer,
+{
+    serializer.collect_seq(values)
+}
+
+struct Visitor<T, const CAP: usize>(PhantomData<T>);
+
+impl<'de, T, const CAP: usize> serde::de::Visitor<'de> for Visitor<T, CAP>
+where
+    T: serde::Deserialize<'de>,
+{
+    type Value = ArrayVec<T, CAP>;
+
+    fn expecting(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        write!(f, "a sequence of at most {CAP} elements")
+    }
+
+    fn visit_seq<A>(
+        self,
+        mut seq: A,
+    ) -> Result<Self::Value, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        if let Some(len) = seq.size_hint() {
+            if len > CAP {
+                return Err(serde::de::Error::invalid_length(len, &self));
+            }
+        }
+        let mut values = ArrayVec::new();
+        while let Some(value) = seq.next_element()? {
+            if values.try_push(value).is_err() {
+                return Err(serde::de::Error::invalid_length(CAP + 1, &self));
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// Decode a sequence into a vector holding at most as many elements as its
+/// capacity.
+///
+/// # Errors
+///
+/// An error is returned if the sequence holds more elements than the
+/// capacity of the vector, and otherwise any error from the deserializer.
+pub fn deserialize<'de, T, D, const CAP: usize>(
+    deserializer: D,
+) -> Result<ArrayVec<T, CAP>, D::Error>
+where
+    T: serde::Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    deserializer.deserialize_seq(Visitor(PhantomData))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        from_bytes,
+        json,
+        to_bytes,
+        Error,
+    };
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Record {
+        #[serde(with = "crate::with::arrayvec")]
+        names: ArrayVec<String, 2>,
+        #[serde(with = "crate::with::arrayvec")]
+        empty: ArrayVec<u8, 0>,
+    }
+
+    #[test]
+    fn decode_within_capacity() {
+        let mut names = ArrayVec::new();
+        names.push(String::from("a"));
+        names.push(String::from("bc"));
+        let record = Record {
+            names,
+            empty: ArrayVec::new(),
+        };
+        let encoding = to_bytes(&record);
+        assert!(encoding.is_ok());
+        let encoding = encoding.unwrap();
+        assert_eq!(&[0x02, 0x01, b'a', 0x02, b'b', b'c', 0x00][..], encoding);
+        let decoded: crate::Result<Record> = from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!(record, decoded.unwrap());
+    }
+
+    #[test]
+    fn decode_over_capacity() {
+        // The count alone is enough to refuse the encoding, even though the
+        // elements are missing.
+        let decoded = from_bytes::<Record>(&[0x83, 0x00]);
+        assert!(matches!(
+            decoded,
+            Err(Error::Message(message))
+                if message == "invalid length 384, expected a sequence of at most 2 elements"
+        ));
+        let decoded = from_bytes::<Record>(&[0x00, 0x01, 0x07]);
+        assert!(matches!(
+            decoded,
+            Err(Error::Message(message))
+                if message == "invalid length 1, expected a sequence of at most 0 elements"
+        ));
+
+        // Formats which do not declare the number of elements up front are
+        // stopped once one element too many is found.
+        let decoded = json::from_slice::<Record>(
+            br#"{"names":["a","b","c"],"empty":[]}"#,
+        );
+        assert!(decoded.is_err());
+        let decoded =
+            json::from_slice::<Record>(br#"{"names":["a","b"],"empty":[]}"#);
+        assert!(decoded.is_ok());
+    }
+}
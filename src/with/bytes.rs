@@ -0,0 +1,128 @@
+//! This encodes a sequence of bytes, such as a `Vec<u8>`, as a byte string
+//! rather than as a sequence of `u8` values.  The encoding produced by this
+//! crate is the same either way, but encoding and decoding are faster, and
+//! other formats may encode byte strings more compactly than sequences.
+
+use std::marker::PhantomData;
+
+/// Encode the given bytes as a byte string.
+///
+/// # Errors
+///
+/// Any error from the serializer is returned.
+pub fn serialize<T, S>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    T: AsRef<[u8]>,
+    S: serde::Serializer,
+{
+    serializer.serialize_bytes(value.as_ref())
+}
+
+struct Visitor<T>(PhantomData<T>);
+
+impl<'de, T> serde::de::Visitor<'de> for Visitor<T>
+where
+    T: From<Vec<u8>>,
+{
+    type Value = T;
+
+    fn expecting(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        write!(f, "a byte string")
+    }
+
+    fn visit_bytes<E>(
+        self,
+        v: &[u8],
+    ) -> Result<T, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(T::from(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(
+        self,
+        v: Vec<u8>,
+    ) -> Result<T, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(T::from(v))
+    }
+
+    // Formats without byte strings may encode them as sequences.
+    fn visit_seq<A>(
+        self,
+        mut seq: A,
+    ) -> Result<T, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(T::from(bytes))
+    }
+}
+
+/// Decode bytes encoded as a byte string.
+///
+/// # Errors
+///
+/// Any error from the deserializer is returned.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: From<Vec<u8>>,
+    D: serde::Deserializer<'de>,
+{
+    deserializer.deserialize_byte_buf(Visitor(PhantomData))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        from_bytes,
+        testing::{
+            tokens_of,
+            Token,
+        },
+        to_bytes,
+    };
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Blob {
+        #[serde(with = "crate::with::bytes")]
+        data: Vec<u8>,
+    }
+
+    #[test]
+    fn bytes_are_a_byte_string() {
+        let blob = Blob {
+            data: vec![1, 2, 3],
+        };
+        assert_eq!(
+            vec![
+                Token::Struct {
+                    name: "Blob",
+                    len: 1,
+                },
+                Token::Field("data"),
+                Token::Bytes(vec![1, 2, 3]),
+                Token::StructEnd,
+            ],
+            tokens_of(&blob).unwrap()
+        );
+        let encoding = to_bytes(&blob).unwrap();
+        assert_eq!(to_bytes(&vec![1_u8, 2, 3]).unwrap(), encoding);
+        let decoded: crate::Result<Blob> = from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!(blob, decoded.unwrap());
+    }
+}
@@ -0,0 +1,171 @@
+//! This encodes an integer in a fixed number of bytes, most significant
+//! byte first, rather than in a variable number of bytes.  This takes more
+//! space for small values but less for large ones, such as hashes and
+//! random identifiers, and keeps records the same size whatever the value.
+
+use std::marker::PhantomData;
+
+/// This trait is implemented by integer types which can be encoded in a
+/// fixed number of bytes.
+pub trait FixedWidth: Copy {
+    /// This is the number of bytes in the encoding of the type.
+    const WIDTH: usize;
+
+    /// Return the bits of the value, zero-extended to 64 bits.
+    fn to_bits(self) -> u64;
+
+    /// Return the value with the given bits, ignoring all but the lowest
+    /// [`WIDTH`] bytes.
+    ///
+    /// [`WIDTH`]: #associatedconstant.WIDTH
+    fn from_bits(bits: u64) -> Self;
+}
+
+macro_rules! impl_fixed_width {
+    ($($ty:ty => $unsigned:ty),* $(,)?) => {
+        $(
+            #[allow(
+                clippy::cast_possible_truncation,
+                clippy::cast_possible_wrap,
+                clippy::cast_sign_loss
+            )]
+            impl FixedWidth for $ty {
+                const WIDTH: usize = std::mem::size_of::<$ty>();
+
+                fn to_bits(self) -> u64 {
+                    u64::from(self as $unsigned)
+                }
+
+                fn from_bits(bits: u64) -> Self {
+                    bits as $unsigned as $ty
+                }
+            }
+        )*
+    };
+}
+
+impl_fixed_width! {
+    i8 => u8,
+    i16 => u16,
+    i32 => u32,
+    i64 => u64,
+    u8 => u8,
+    u16 => u16,
+    u32 => u32,
+    u64 => u64,
+}
+
+/// Encode the given integer in a fixed number of bytes.
+///
+/// # Errors
+///
+/// Any error from the serializer is returned.
+pub fn serialize<T, S>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    T: FixedWidth,
+    S: serde::Serializer,
+{
+    use serde::ser::SerializeTuple;
+    let bytes = value.to_bits().to_be_bytes();
+    let mut tuple = serializer.serialize_tuple(T::WIDTH)?;
+    for byte in &bytes[bytes.len() - T::WIDTH..] {
+        tuple.serialize_element(byte)?;
+    }
+    tuple.end()
+}
+
+struct Visitor<T>(PhantomData<T>);
+
+impl<'de, T> serde::de::Visitor<'de> for Visitor<T>
+where
+    T: FixedWidth,
+{
+    type Value = T;
+
+    fn expecting(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        write!(f, "{} bytes", T::WIDTH)
+    }
+
+    fn visit_seq<A>(
+        self,
+        mut seq: A,
+    ) -> Result<T, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut bits = 0;
+        for i in 0..T::WIDTH {
+            let byte: u8 = seq
+                .next_element()?
+                .ok_or_else(|| serde::de::Error::invalid_length(i, &self))?;
+            bits = (bits << 8) | u64::from(byte);
+        }
+        Ok(T::from_bits(bits))
+    }
+}
+
+/// Decode an integer encoded in a fixed number of bytes.
+///
+/// # Errors
+///
+/// Any error from the deserializer is returned.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: FixedWidth,
+    D: serde::Deserializer<'de>,
+{
+    deserializer.deserialize_tuple(T::WIDTH, Visitor(PhantomData))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        from_bytes,
+        to_bytes,
+    };
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Record {
+        #[serde(with = "crate::with::fixed")]
+        id: u32,
+        #[serde(with = "crate::with::fixed")]
+        offset: i16,
+        count: u32,
+    }
+
+    #[test]
+    fn integers_have_fixed_width() {
+        for (record, expected) in &[
+            (
+                Record {
+                    id: 1,
+                    offset: -2,
+                    count: 1,
+                },
+                &[0x00, 0x00, 0x00, 0x01, 0xFF, 0xFE, 0x01][..],
+            ),
+            (
+                Record {
+                    id: u32::MAX,
+                    offset: i16::MIN,
+                    count: 300,
+                },
+                &[0xFF, 0xFF, 0xFF, 0xFF, 0x80, 0x00, 0x82, 0x2C][..],
+            ),
+        ] {
+            let encoding = to_bytes(record);
+            assert!(encoding.is_ok());
+            let encoding = encoding.unwrap();
+            assert_eq!(*expected, encoding);
+            let decoded: crate::Result<Record> = from_bytes(&encoding);
+            assert!(decoded.is_ok());
+            assert_eq!(*record, decoded.unwrap());
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! This encodes a double-precision floating-point field in single
+//! precision, halving its size, for values whose consumers do not need
+//! the extra precision.  The value is rounded to the nearest single-precision
+//! value when it is encoded, and widened back to double precision when it is
+//! decoded, so it may not decode to exactly the value which was encoded.
+//! Values too large for single precision become infinite.
+
+/// Encode the given value in single precision.
+///
+/// # Errors
+///
+/// Any error from the serializer is returned.
+#[allow(clippy::trivially_copy_pass_by_ref)]
+pub fn serialize<S>(
+    value: &f64,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    #[allow(clippy::cast_possible_truncation)]
+    serializer.serialize_f32(*value as f32)
+}
+
+/// Decode a value encoded in single precision.
+///
+/// # Errors
+///
+/// Any error from the deserializer is returned.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    <f32 as serde::Deserialize>::deserialize(deserializer).map(f64::from)
+}
+
+#[cfg(all(test, feature = "float"))]
+mod tests {
+    use crate::{
+        from_bytes,
+        to_bytes,
+    };
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Sample {
+        #[serde(with = "crate::with::lossy_f32")]
+        level: f64,
+    }
+
+    #[test]
+    fn values_are_narrowed() {
+        let sample = Sample {
+            level: 0.5,
+        };
+        let encoding = to_bytes(&sample);
+        assert!(encoding.is_ok());
+        let encoding = encoding.unwrap();
+        assert_eq!(&[0x3F, 0x00, 0x00, 0x00][..], encoding);
+        let decoded: crate::Result<Sample> = from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!(sample, decoded.unwrap());
+        let sample = Sample {
+            level: 0.1,
+        };
+        let decoded: Sample = from_bytes(&to_bytes(&sample).unwrap()).unwrap();
+        assert_ne!(sample, decoded);
+        assert!((decoded.level - 0.1).abs() < 1e-7);
+        let sample = Sample {
+            level: 1e300,
+        };
+        let decoded: Sample = from_bytes(&to_bytes(&sample).unwrap()).unwrap();
+        assert!(decoded.level.is_infinite());
+    }
+}
@@ -0,0 +1,85 @@
+//! This encodes a field whose value is usually its default with a single
+//! byte in that case.  Since the fields of structs are identified only by
+//! their positions, they cannot be left out of the encoding altogether, so
+//! the field is encoded as an optional value which is absent when the field
+//! has its default value.
+
+/// Encode the given value as an optional value which is absent if the
+/// value is the default for its type.
+///
+/// # Errors
+///
+/// Any error from the serializer is returned.
+pub fn serialize<T, S>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    T: Default + PartialEq + serde::Serialize,
+    S: serde::Serializer,
+{
+    if *value == T::default() {
+        serializer.serialize_none()
+    } else {
+        serializer.serialize_some(value)
+    }
+}
+
+/// Decode a value encoded as an optional value, giving the default for its
+/// type if it is absent.
+///
+/// # Errors
+///
+/// Any error from the deserializer is returned.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: Default + serde::Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    <Option<T> as serde::Deserialize>::deserialize(deserializer)
+        .map(Option::unwrap_or_default)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        from_bytes,
+        to_bytes,
+    };
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Settings {
+        #[serde(with = "crate::with::skip_default")]
+        name: String,
+        #[serde(with = "crate::with::skip_default")]
+        retries: u8,
+    }
+
+    #[test]
+    fn defaults_take_one_byte() {
+        for (settings, expected) in &[
+            (
+                Settings {
+                    name: String::new(),
+                    retries: 0,
+                },
+                &[0x00, 0x00][..],
+            ),
+            (
+                Settings {
+                    name: String::from("a"),
+                    retries: 3,
+                },
+                &[0x01, 0x01, b'a', 0x01, 0x03][..],
+            ),
+        ] {
+            let encoding = to_bytes(settings);
+            assert!(encoding.is_ok());
+            let encoding = encoding.unwrap();
+            assert_eq!(*expected, encoding);
+            let decoded: crate::Result<Settings> = from_bytes(&encoding);
+            assert!(decoded.is_ok());
+            assert_eq!(*settings, decoded.unwrap());
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! This decodes a sequence straight into a [`SmallVec`], holding no more
+//! elements than fit inline, so that it never spills onto the heap.
+//! Decoding fails, before any elements are decoded, if the encoding
+//! declares more elements than the inline capacity, and otherwise as soon
+//! as one element too many is found.  The sequence is encoded just like a
+//! `Vec`, and a vector which has spilled is still encoded whole.  This is
+//! available with the `smallvec` feature.
+//!
+//! [`SmallVec`]: https://docs.rs/smallvec/1/smallvec/struct.SmallVec.html
+//!
+//! # Examples
+//!
+//! ```rust
+//! # extern crate serialization;
+//! use serialization::{
+//!     from_bytes,
+//!     to_bytes,
+//! };
+//! use smallvec::SmallVec;
+//!
+//! #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+//! struct Packet {
+//!     #[serde(with = "serialization::with::smallvec")]
+//!     samples: SmallVec<[u16; 4]>,
+//! }
+//!
+//! let encoding = to_bytes(&vec![1_u16, 2, 3]).unwrap();
+//! let packet: Packet = from_bytes(&encoding).unwrap();
+//! assert_eq!(&[1, 2, 3], packet.samples.as_slice());
+//! assert!(!packet.samples.spilled());
+//! assert_eq!(encoding, to_bytes(&packet).unwrap());
+//! let encoding = to_bytes(&vec![1_u16, 2, 3, 4, 5]).unwrap();
+//! assert!(from_bytes::<Packet>(&encoding).is_err());
+//! ```
+
+use smallvec::{
+    Array,
+    SmallVec,
+};
+use std::marker::PhantomData;
+
+/// Encode the elements of the given vector as a sequence.
+///
+/// # Errors
+///
+/// Any error from the serializer is returned.
+pub fn serialize<A, S>(
+    values: &SmallVec<A>,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    A: Array,
+    A::Item: serde::Serialize,
+    S: serde::Serializer,
+{
+    serializer.collect_seq(values)
+}
+
+struct Visitor<A>(PhantomData<A>);
+
+impl<'de, A> serde::de::Visitor<'de> for Visitor<A>
+where
+    A: Array,
+    A::Item: serde::Deserialize<'de>,
+{
+    type Value = SmallVec<A>;
+
+    fn expecting(
+        &self,
+        f: &mut std::fmt::Formatter,
+    ) -> std::fmt::Result {
+        write!(f, "a sequence of at most {} elements", A::size())
+    }
+
+    fn visit_seq<S>(
+        self,
+        mut seq: S,
+    ) -> Result<Self::Value, S::Error>
+    where
+        S: serde::de::SeqAccess<'de>,
+    {
+        if let Some(len) = seq.size_hint() {
+            if len > A::size() {
+                return Err(serde::de::Error::invalid_length(len, &self));
+            }
+        }
+        let mut values = SmallVec::new();
+        while let Some(value) = seq.next_element()? {
+            if values.len() == A::size() {
+                return Err(serde::de::Error::invalid_length(
+                    A::size() + 1,
+                    &self,
+                ));
+            }
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+/// Decode a sequence into a vector holding at most as many elements as fit
+/// inline.
+///
+/// # Errors
+///
+/// An error is returned if the sequence holds more elements than the
+/// inline capacity of the vector, and otherwise any error from the
+/// deserializer.
+pub fn deserialize<'de, A, D>(deserializer: D) -> Result<SmallVec<A>, D::Error>
+where
+    A: Array,
+    A::Item: serde::Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    deserializer.deserialize_seq(Visitor(PhantomData))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        from_bytes,
+        json,
+        to_bytes,
+        Error,
+    };
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Record {
+        #[serde(with = "crate::with::smallvec")]
+        names: SmallVec<[String; 2]>,
+        #[serde(with = "crate::with::smallvec")]
+        empty: SmallVec<[u8; 0]>,
+    }
+
+    #[test]
+    fn decode_within_capacity() {
+        let record = Record {
+            names: SmallVec::from_vec(vec![
+                String::from("a"),
+                String::from("bc"),
+            ]),
+            empty: SmallVec::new(),
+        };
+        let encoding = to_bytes(&record);
+        assert!(encoding.is_ok());
+        let encoding = encoding.unwrap();
+        assert_eq!(&[0x02, 0x01, b'a', 0x02, b'b', b'c', 0x00][..], encoding);
+        let decoded: crate::Result<Record> = from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        let decoded = decoded.unwrap();
+        assert!(!decoded.names.spilled());
+        assert_eq!(record, decoded);
+    }
+
+    #[test]
+    fn decode_over_capacity() {
+        // The count alone is enough to refuse the encoding, even though the
+        // elements are missing.
+        let decoded = from_bytes::<Record>(&[0x83, 0x00]);
+        assert!(matches!(
+            decoded,
+            Err(Error::Message(message))
+                if message == "invalid length 384, expected a sequence of at most 2 elements"
+        ));
+        let decoded = from_bytes::<Record>(&[0x00, 0x01, 0x07]);
+        assert!(matches!(
+            decoded,
+            Err(Error::Message(message))
+                if message == "invalid length 1, expected a sequence of at most 0 elements"
+        ));
+
+        // A vector which has spilled onto the heap still encodes, but the
+        // encoding cannot be decoded back into the same type.
+        let spilled = Record {
+            names: SmallVec::from_vec(vec![String::new(); 3]),
+            empty: SmallVec::new(),
+        };
+        let encoding = to_bytes(&spilled);
+        assert!(encoding.is_ok());
+        assert!(from_bytes::<Record>(&encoding.unwrap()).is_err());
+
+        // Formats which do not declare the number of elements up front are
+        // stopped once one element too many is found.
+        let decoded = json::from_slice::<Record>(
+            br#"{"names":["a","b","c"],"empty":[]}"#,
+        );
+        assert!(decoded.is_err());
+        let decoded =
+            json::from_slice::<Record>(br#"{"names":["a","b"],"empty":[]}"#);
+        assert!(decoded.is_ok());
+    }
+}
@@ -0,0 +1,110 @@
+//! This encodes a signed integer by interleaving positive and negative
+//! values (0, -1, 1, -2, 2, ...) and encoding the result as an unsigned
+//! integer.  The encoding is as compact as the usual one for signed
+//! integers, and is the one used by Protocol Buffers, so it can be used to
+//! match the layout of messages shared with other systems.
+
+/// This trait is implemented by signed integer types which can be encoded
+/// by interleaving positive and negative values.
+pub trait ZigZag: Copy {
+    /// This is the unsigned integer type of the same width, whose encoding
+    /// is used for the interleaved value.
+    type Unsigned: serde::Serialize + for<'de> serde::Deserialize<'de>;
+
+    /// Return the interleaved value.
+    fn zigzag(self) -> Self::Unsigned;
+
+    /// Return the value which was interleaved to give the given value.
+    fn unzigzag(value: Self::Unsigned) -> Self;
+}
+
+macro_rules! impl_zigzag {
+    ($($ty:ty => $unsigned:ty),* $(,)?) => {
+        $(
+            #[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+            impl ZigZag for $ty {
+                type Unsigned = $unsigned;
+
+                fn zigzag(self) -> $unsigned {
+                    ((self << 1) ^ (self >> (<$ty>::BITS - 1))) as $unsigned
+                }
+
+                fn unzigzag(value: $unsigned) -> Self {
+                    ((value >> 1) as $ty) ^ -((value & 1) as $ty)
+                }
+            }
+        )*
+    };
+}
+
+impl_zigzag! {
+    i8 => u8,
+    i16 => u16,
+    i32 => u32,
+    i64 => u64,
+}
+
+/// Encode the given integer as an interleaved unsigned integer.
+///
+/// # Errors
+///
+/// Any error from the serializer is returned.
+pub fn serialize<T, S>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error>
+where
+    T: ZigZag,
+    S: serde::Serializer,
+{
+    serde::Serialize::serialize(&value.zigzag(), serializer)
+}
+
+/// Decode an integer encoded as an interleaved unsigned integer.
+///
+/// # Errors
+///
+/// Any error from the deserializer is returned.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: ZigZag,
+    D: serde::Deserializer<'de>,
+{
+    <T::Unsigned as serde::Deserialize>::deserialize(deserializer)
+        .map(T::unzigzag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        from_bytes,
+        to_bytes,
+    };
+
+    #[test]
+    fn values_interleave() {
+        for (value, expected) in
+            &[(0_i32, 0_u32), (-1, 1), (1, 2), (-2, 3), (i32::MIN, u32::MAX)]
+        {
+            assert_eq!(*expected, value.zigzag());
+            assert_eq!(*value, i32::unzigzag(*expected));
+        }
+        assert_eq!(0xFF, i8::MIN.zigzag());
+        assert_eq!(i64::MAX, i64::unzigzag(i64::MAX.zigzag()));
+    }
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Delta(#[serde(with = "crate::with::zigzag")] i64);
+
+    #[test]
+    fn field_encoding() {
+        let encoding = to_bytes(&Delta(-65));
+        assert!(encoding.is_ok());
+        let encoding = encoding.unwrap();
+        assert_eq!(&[0x81, 0x01][..], encoding);
+        let decoded: crate::Result<Delta> = from_bytes(&encoding);
+        assert!(decoded.is_ok());
+        assert_eq!(Delta(-65), decoded.unwrap());
+    }
+}
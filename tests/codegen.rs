@@ -0,0 +1,94 @@
+// This checks that the source made by `Codegen` compiles and encodes and
+// decodes values the same way as `to_bytes` and `from_bytes`.  The source
+// is kept in a file so that it is compiled along with this test; set
+// `SERIALIZATION_UPDATE_SNAPSHOTS=1` to write it again after changing the
+// generator or the types below.
+
+#![cfg(feature = "std")]
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serialization::{
+    from_bytes,
+    to_bytes,
+    Codegen,
+    Deserializer,
+    Schema,
+};
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub enum Shape {
+    Point,
+    Polygon([(i16, i16); 3]),
+    Grid {
+        cells: [[u8; 2]; 2],
+    },
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct Frame {
+    pub id: u16,
+    pub samples: [u16; 3],
+    pub nothing: [u8; 0],
+    pub shapes: Vec<Shape>,
+    pub pair: (u128, i128),
+    pub label: Option<[char; 2]>,
+}
+
+mod generated {
+    use super::{
+        Frame,
+        Shape,
+    };
+
+    include!("codegen/generated.rs");
+}
+
+const GENERATED_PATH: &str = "tests/codegen/generated.rs";
+
+fn frame() -> Frame {
+    Frame {
+        id: 300,
+        samples: [1, 200, 60_000],
+        nothing: [],
+        shapes: vec![
+            Shape::Point,
+            Shape::Polygon([(0, 0), (-5, 3), (7, -700)]),
+            Shape::Grid {
+                cells: [[1, 2], [3, 4]],
+            },
+        ],
+        pair: (u128::MAX, i128::MIN),
+        label: Some(['o', 'k']),
+    }
+}
+
+#[test]
+fn generated_source_is_current() {
+    let mut codegen = Codegen::new();
+    codegen.add(&Schema::of::<Frame>().unwrap()).unwrap();
+    let source = codegen.finish();
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join(GENERATED_PATH);
+    if std::env::var_os("SERIALIZATION_UPDATE_SNAPSHOTS")
+        .is_some_and(|value| !value.is_empty() && value != "0")
+    {
+        std::fs::write(&path, &source).unwrap();
+    }
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), source);
+}
+
+#[test]
+fn generated_codecs_match_serde() {
+    let frame = frame();
+    let mut encoded = Vec::new();
+    generated::encode_frame(&frame, &mut encoded);
+    assert_eq!(to_bytes(&frame).unwrap(), encoded);
+    let mut deserializer = Deserializer::new(&encoded);
+    let decoded = generated::decode_frame(&mut deserializer);
+    assert!(decoded.is_ok());
+    assert_eq!(frame, decoded.unwrap());
+    assert_eq!(frame, from_bytes::<Frame>(&encoded).unwrap());
+}
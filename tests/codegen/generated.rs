@@ -0,0 +1,79 @@
+#[allow(clippy::all, unused_variables)]
+pub fn encode_frame(
+    value: &Frame,
+    out: &mut Vec<u8>,
+) {
+    ::serialization::__private::codegen::write_u16(out, *&value.id);
+    let v1 = &value.samples;
+    ::serialization::__private::codegen::write_u16(out, *&v1[0]);
+    ::serialization::__private::codegen::write_u16(out, *&v1[1]);
+    ::serialization::__private::codegen::write_u16(out, *&v1[2]);
+    let v2 = &value.nothing;
+    let v3 = &value.shapes;
+    ::serialization::__private::codegen::write_len(out, v3.len());
+    for v4 in v3 {
+        encode_shape(v4, out);
+    }
+    let v5 = &value.pair;
+    ::serialization::__private::codegen::write_u128(out, *&v5.0);
+    ::serialization::__private::codegen::write_i128(out, *&v5.1);
+    match &value.label {
+        Some(v6) => {
+            ::serialization::__private::codegen::write_option(out, true);
+            let v7 = v6;
+            ::serialization::__private::codegen::write_char(out, *&v7[0]);
+            ::serialization::__private::codegen::write_char(out, *&v7[1]);
+        },
+        None => ::serialization::__private::codegen::write_option(out, false),
+    }
+}
+
+#[allow(clippy::all, unused_variables)]
+pub fn decode_frame(
+    de: &mut ::serialization::Deserializer<'_>,
+) -> ::serialization::Result<Frame> {
+    Ok(Frame { id: ::serialization::__private::codegen::read_u16(de)?, samples: [::serialization::__private::codegen::read_u16(de)?, ::serialization::__private::codegen::read_u16(de)?, ::serialization::__private::codegen::read_u16(de)?], nothing: [], shapes: { let len = ::serialization::__private::codegen::read_len(de)?; (0..len).map(|_| -> ::serialization::Result<_> { Ok(decode_shape(de)?.into()) }).collect::<::serialization::Result<_>>()? }, pair: (::serialization::__private::codegen::read_u128(de)?, ::serialization::__private::codegen::read_i128(de)?,), label: if ::serialization::__private::codegen::read_option(de)? { Some([::serialization::__private::codegen::read_char(de)?, ::serialization::__private::codegen::read_char(de)?]) } else { None } })
+}
+
+#[allow(clippy::all, unused_variables)]
+pub fn encode_shape(
+    value: &Shape,
+    out: &mut Vec<u8>,
+) {
+    match value {
+        Shape::Point => {
+            ::serialization::__private::codegen::write_len(out, 0);
+        },
+        Shape::Polygon(f0) => {
+            ::serialization::__private::codegen::write_len(out, 1);
+            let v8 = f0;
+            let v9 = &v8[0];
+            ::serialization::__private::codegen::write_i16(out, *&v9.0);
+            ::serialization::__private::codegen::write_i16(out, *&v9.1);
+            let v10 = &v8[1];
+            ::serialization::__private::codegen::write_i16(out, *&v10.0);
+            ::serialization::__private::codegen::write_i16(out, *&v10.1);
+            let v11 = &v8[2];
+            ::serialization::__private::codegen::write_i16(out, *&v11.0);
+            ::serialization::__private::codegen::write_i16(out, *&v11.1);
+        },
+        Shape::Grid { cells: f0 } => {
+            ::serialization::__private::codegen::write_len(out, 2);
+            let v12 = f0;
+            let v13 = &v12[0];
+            ::serialization::__private::codegen::write_u8(out, *&v13[0]);
+            ::serialization::__private::codegen::write_u8(out, *&v13[1]);
+            let v14 = &v12[1];
+            ::serialization::__private::codegen::write_u8(out, *&v14[0]);
+            ::serialization::__private::codegen::write_u8(out, *&v14[1]);
+        },
+    }
+}
+
+#[allow(clippy::all, unused_variables)]
+pub fn decode_shape(
+    de: &mut ::serialization::Deserializer<'_>,
+) -> ::serialization::Result<Shape> {
+    match ::serialization::__private::codegen::read_len(de)? { 0 => Ok(Shape::Point), 1 => Ok(Shape::Polygon([(::serialization::__private::codegen::read_i16(de)?, ::serialization::__private::codegen::read_i16(de)?,), (::serialization::__private::codegen::read_i16(de)?, ::serialization::__private::codegen::read_i16(de)?,), (::serialization::__private::codegen::read_i16(de)?, ::serialization::__private::codegen::read_i16(de)?,)])), 2 => Ok(Shape::Grid { cells: [[::serialization::__private::codegen::read_u8(de)?, ::serialization::__private::codegen::read_u8(de)?], [::serialization::__private::codegen::read_u8(de)?, ::serialization::__private::codegen::read_u8(de)?]] }), index => Err(::serialization::__private::codegen::unknown_variant("Shape", index)) }
+}
+
@@ -0,0 +1,131 @@
+// This checks that values are encoded into and decoded from fixed buffers
+// without allocating, as targets without a heap need, by counting the
+// allocations made by the thread doing so.  It is kept apart from the other
+// tests since it replaces the global allocator of the program it is in.
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serialization::{
+    from_bytes,
+    to_slice,
+    Error,
+};
+use std::{
+    alloc::{
+        GlobalAlloc,
+        Layout,
+        System,
+    },
+    cell::Cell,
+};
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+struct CountingAllocator;
+
+fn count_allocation() {
+    let _ = ALLOCATIONS.try_with(|allocations| {
+        allocations.set(allocations.get() + 1);
+    });
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(
+        &self,
+        layout: Layout,
+    ) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+    ) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(
+        &self,
+        ptr: *mut u8,
+        layout: Layout,
+        new_size: usize,
+    ) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+enum Reading<'a> {
+    Missing,
+    Celsius(i16),
+    Labeled {
+        label: &'a str,
+        raw: &'a [u8],
+    },
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct Sample<'a> {
+    sensor: &'a str,
+    sequence: u64,
+    readings: [Reading<'a>; 3],
+    offset: Option<i32>,
+    flags: (bool, char),
+}
+
+// Return the number of allocations made by the current thread while
+// calling the given function, along with what it returns.
+fn allocations_in<F, R>(f: F) -> (usize, R)
+where
+    F: FnOnce() -> R,
+{
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (ALLOCATIONS.with(Cell::get) - before, result)
+}
+
+#[test]
+fn slices_without_allocating() {
+    let sample = Sample {
+        sensor: "porch",
+        sequence: 1 << 40,
+        readings: [
+            Reading::Missing,
+            Reading::Celsius(-40),
+            Reading::Labeled {
+                label: "raw",
+                raw: &[1, 2, 3],
+            },
+        ],
+        offset: Some(-70_000),
+        flags: (true, 'é'),
+    };
+    let mut buffer = [0; 64];
+    let (allocations, len) = allocations_in(|| to_slice(&sample, &mut buffer));
+    assert_eq!(0, allocations);
+    assert!(len.is_ok());
+    let len = len.unwrap();
+    let (allocations, decoded) =
+        allocations_in(|| from_bytes::<Sample>(&buffer[..len]));
+    assert_eq!(0, allocations);
+    assert!(decoded.is_ok());
+    assert_eq!(sample, decoded.unwrap());
+    let mut short = [0; 8];
+    let (allocations, result) =
+        allocations_in(|| to_slice(&sample, &mut short));
+    assert_eq!(0, allocations);
+    assert!(matches!(result, Err(Error::BufferFull)));
+    // Make sure allocations are counted at all.
+    let (allocations, _) = allocations_in(|| serialization::to_bytes(&sample));
+    assert!(allocations > 0);
+}